@@ -300,7 +300,7 @@ class Browser:
                     print(_info("  (connection closed)"))
                     break
                 if ev.is_event:
-                    seq = ev.get("Seq", "?")
+                    seq = ev.get("Event-Seq", ev.get("Seq", "?"))
                     ts = ev.get("Timestamp", "")
                     body = ev.body.rstrip()
                     ts_display = f"  {_C.DIM}{ts}{_C.RESET}" if ts else ""
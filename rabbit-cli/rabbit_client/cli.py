@@ -105,7 +105,8 @@ def cmd_sub(args: argparse.Namespace) -> None:
                 if ev is None:
                     break
                 if ev.is_event:
-                    seq = ev.get("Seq", "?")
+                    # Event-Seq is what --since resumes from.
+                    seq = ev.get("Event-Seq", ev.get("Seq", "?"))
                     body = ev.body.rstrip()
                     print(f"{seq}\t{body}", flush=True)
     except ProtocolError as e:
@@ -189,7 +190,7 @@ def main(argv: list[str] | None = None) -> None:
     p_sub = sub.add_parser("sub", help="Subscribe to an event topic")
     p_sub.add_argument("addr", help="Burrow address")
     p_sub.add_argument("topic", help="Event topic (e.g. /q/chat)")
-    p_sub.add_argument("--since", type=int, default=0, help="Replay from seq")
+    p_sub.add_argument("--since", type=int, default=0, help="Replay events after this Event-Seq")
     p_sub.set_defaults(func=cmd_sub)
 
     # pub
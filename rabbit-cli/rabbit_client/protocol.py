@@ -79,6 +79,7 @@ TYPE_GLYPHS: dict[str, str] = {
 HDR_LANE = "Lane"
 HDR_TXN = "Txn"
 HDR_SEQ = "Seq"
+HDR_EVENT_SEQ = "Event-Seq"
 HDR_ACK = "ACK"
 HDR_CREDIT = "Credit"
 HDR_LENGTH = "Length"
@@ -154,6 +155,14 @@ class Frame:
     def seq(self) -> int:
         return int(self.headers.get(HDR_SEQ, "0"))
 
+    def event_seq(self) -> int:
+        """The event's number in its topic, which ``Since`` resumes from.
+
+        ``Seq`` numbers the frame on its lane; burrows that predate
+        ``Event-Seq`` sent the event number there instead.
+        """
+        return int(self.headers.get(HDR_EVENT_SEQ, self.headers.get(HDR_SEQ, "0")))
+
     def length(self) -> int:
         return int(self.headers.get(HDR_LENGTH, "0"))
 
@@ -0,0 +1,823 @@
+//! Local admin interface over a Unix domain socket.
+//!
+//! A serving burrow can expose an admin socket (`[admin] socket` in
+//! the config) so that `rabbitctl` can inspect and manage it without
+//! a restart and without opening a network port.  Access is governed
+//! by file permissions: the socket is created mode `0600`.
+//!
+//! The wire format is one JSON object per line in each direction.
+//! Requests are tagged by `cmd`:
+//!
+//! ```text
+//! {"cmd":"status"}
+//! {"cmd":"peers"}
+//! {"cmd":"stats"}
+//! {"cmd":"listeners"}
+//! {"cmd":"links"}
+//! {"cmd":"close-link","peer":"ed25519:…","reason":"retired"}
+//! {"cmd":"anchors"}
+//! {"cmd":"add-anchor","id":"ed25519:…","address":"hub.example.org:7443"}
+//! {"cmd":"forget-anchor","id":"ed25519:…"}
+//! {"cmd":"grant","peer":"ed25519:…","capability":"Publish","ttl":3600}
+//! {"cmd":"ungrant","peer":"ed25519:…","capability":"Publish"}
+//! {"cmd":"prune-topic","topic":"/q/chat","keep":100}
+//! {"cmd":"verify-topic","topic":"/q/chat"}
+//! {"cmd":"snapshot","path":"/var/backups/burrow.tar"}
+//! {"cmd":"dead-letters"}
+//! {"cmd":"retry-dead-letter","id":3}
+//! {"cmd":"purge-dead-letters","older_than":86400}
+//! {"cmd":"sessions"}
+//! {"cmd":"revoke","peer":"ed25519:…"}
+//! {"cmd":"revoke","session":"<token>"}
+//! ```
+//!
+//! Each is answered with `{"ok":true,"result":…}` or
+//! `{"ok":false,"error":"…"}`.  Commands map onto the operations the
+//! control lane offers remote peers (a grant is announced to the
+//! target with `DELEGATE-GRANT`, as for `DELEGATE`), but run with the
+//! local operator's authority, so no capability check applies.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::burrow::Burrow;
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+use crate::security::permissions::Capability;
+use crate::warren::federation::Anchor;
+
+/// Default grant lifetime when a request does not give one.
+pub const DEFAULT_GRANT_TTL: u64 = 3600;
+
+/// A command sent to the admin socket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "kebab-case")]
+pub enum AdminRequest {
+    /// Identity and counters of the running burrow.
+    Status,
+    /// The peer table.
+    Peers,
+    /// Traffic and lane statistics of each open tunnel.
+    Stats,
+    /// Accept and refusal counters of each listener.
+    Listeners,
+    /// State and health of each federation link.
+    Links,
+    /// Tear down the federation link to a peer.
+    CloseLink {
+        /// Burrow ID at the other end of the link.
+        peer: String,
+        /// Why, as told to the peer.
+        #[serde(default)]
+        reason: String,
+    },
+    /// The anchors trusted here.
+    Anchors,
+    /// Trust an anchor, across restarts.
+    AddAnchor {
+        /// The anchor's burrow ID.
+        id: String,
+        /// `host:port` to dial it at on startup.
+        #[serde(default)]
+        address: Option<String>,
+    },
+    /// Stop trusting an anchor.
+    ForgetAnchor {
+        /// The anchor's burrow ID.
+        id: String,
+    },
+    /// Grant a capability to a peer.
+    Grant {
+        /// Burrow ID receiving the capability.
+        peer: String,
+        /// Capability label, e.g. `Publish`.
+        capability: String,
+        /// Lifetime in seconds.
+        #[serde(default = "default_ttl")]
+        ttl: u64,
+    },
+    /// Revoke one capability from a peer, or all of them.  The
+    /// revocation is logged, so it survives a restart.
+    Ungrant {
+        /// Burrow ID losing the capability.
+        peer: String,
+        /// Capability label; all capabilities if absent.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        capability: Option<String>,
+    },
+    /// Drop all but the newest `keep` events of a topic, in memory
+    /// and in the continuity log.
+    PruneTopic {
+        /// Topic path, e.g. `/q/chat`.
+        topic: String,
+        /// Number of events to retain.
+        #[serde(default)]
+        keep: usize,
+    },
+    /// Check a topic's continuity log for tampering or truncation.
+    VerifyTopic {
+        /// Topic path, e.g. `/q/chat`.
+        topic: String,
+    },
+    /// Write a snapshot of the burrow's persistent state.
+    Snapshot {
+        /// Archive to write, as the burrow sees the filesystem.
+        path: PathBuf,
+    },
+    /// Frames parked after running out of retransmissions.
+    DeadLetters,
+    /// Send a parked frame to its peer again.
+    RetryDeadLetter {
+        /// Dead-letter ID.
+        id: u64,
+    },
+    /// Drop parked frames.
+    PurgeDeadLetters {
+        /// Only drop entries at least this many seconds old.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        older_than: Option<u64>,
+    },
+    /// Session tokens issued by handshakes.
+    Sessions,
+    /// End a peer's sessions, or one session, closing their tunnels.
+    Revoke {
+        /// Burrow ID whose sessions are all revoked.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        peer: Option<String>,
+        /// A single session token to revoke.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session: Option<String>,
+    },
+}
+
+fn default_ttl() -> u64 {
+    DEFAULT_GRANT_TTL
+}
+
+/// Reply to an [`AdminRequest`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdminResponse {
+    /// Whether the command succeeded.
+    pub ok: bool,
+    /// Command output on success.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// What went wrong on failure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AdminResponse {
+    /// A successful reply carrying `result`.
+    pub fn success(result: Value) -> Self {
+        Self {
+            ok: true,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    /// A failed reply.
+    pub fn failure(error: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            result: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+impl From<Result<Value, ProtocolError>> for AdminResponse {
+    fn from(result: Result<Value, ProtocolError>) -> Self {
+        match result {
+            Ok(value) => Self::success(value),
+            Err(e) => Self::failure(e.detail()),
+        }
+    }
+}
+
+/// Run one admin command against `burrow`.
+pub async fn execute(burrow: &Burrow, request: AdminRequest) -> AdminResponse {
+    match request {
+        AdminRequest::Status => AdminResponse::success(status(burrow).await),
+        AdminRequest::Peers => AdminResponse::success(peers(burrow).await),
+        AdminRequest::Stats => AdminResponse::success(json!(burrow.tunnel_stats.snapshot().await)),
+        AdminRequest::Listeners => AdminResponse::success(json!(burrow.listener_stats.snapshot())),
+        AdminRequest::Links => AdminResponse::success(json!(burrow.federation.link_status())),
+        AdminRequest::CloseLink { peer, reason } => burrow
+            .close_link(&peer, &reason)
+            .map(|()| json!({ "peer": peer }))
+            .into(),
+        AdminRequest::Anchors => AdminResponse::success(json!(burrow.federation.anchors())),
+        AdminRequest::AddAnchor { id, address } => burrow
+            .register_anchor(Anchor::new(&id, address.as_deref()))
+            .map(|added| json!({ "id": id, "added": added }))
+            .into(),
+        AdminRequest::ForgetAnchor { id } => burrow
+            .forget_anchor(&id)
+            .map(|forgotten| json!({ "id": id, "forgotten": forgotten }))
+            .into(),
+        AdminRequest::Grant {
+            peer,
+            capability,
+            ttl,
+        } => grant(burrow, &peer, &capability, ttl).await.into(),
+        AdminRequest::Ungrant { peer, capability } => {
+            ungrant(burrow, &peer, capability.as_deref()).into()
+        }
+        AdminRequest::PruneTopic { topic, keep } => prune_topic(burrow, &topic, keep).into(),
+        AdminRequest::VerifyTopic { topic } => verify_topic(burrow, &topic).into(),
+        AdminRequest::Snapshot { path } => burrow
+            .snapshot(&path)
+            .map(|manifest| {
+                json!({
+                    "path": path,
+                    "files": manifest.files.len(),
+                    "bytes": manifest.files.iter().map(|f| f.bytes).sum::<u64>(),
+                })
+            })
+            .into(),
+        AdminRequest::DeadLetters => AdminResponse::success(json!(burrow.dead_letters.list())),
+        AdminRequest::RetryDeadLetter { id } => burrow
+            .retry_dead_letter(id)
+            .await
+            .map(|entry| json!(entry))
+            .into(),
+        AdminRequest::PurgeDeadLetters { older_than } => {
+            purge_dead_letters(burrow, older_than).into()
+        }
+        AdminRequest::Sessions => AdminResponse::success(sessions(burrow)),
+        AdminRequest::Revoke { peer, session } => {
+            revoke(burrow, peer.as_deref(), session.as_deref()).into()
+        }
+    }
+}
+
+async fn status(burrow: &Burrow) -> Value {
+    let peers = burrow.peers.list().await;
+    let connected = peers.iter().filter(|p| p.connected).count();
+    json!({
+        "name": burrow.name,
+        "id": burrow.burrow_id(),
+        "peers": peers.len(),
+        "connected_peers": connected,
+        "connections": burrow
+            .active_connections
+            .load(std::sync::atomic::Ordering::Relaxed),
+        "sessions": burrow.sessions.session_count(),
+        "topics": burrow.events.topics().len(),
+        "routes": burrow.routing.len().await,
+        "trusted": burrow.trust.lock().unwrap_or_else(|e| e.into_inner()).len(),
+        "dead_letters": burrow.dead_letters.len(),
+    })
+}
+
+async fn peers(burrow: &Burrow) -> Value {
+    let mut peers = burrow.peers.list().await;
+    peers.sort_by(|a, b| a.id.cmp(&b.id));
+    let caps = burrow
+        .capabilities
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    Value::Array(
+        peers
+            .iter()
+            .map(|p| {
+                let granted: Vec<&str> = caps
+                    .active_capabilities(&p.id)
+                    .iter()
+                    .map(|c| c.label())
+                    .collect();
+                json!({
+                    "id": p.id,
+                    "name": p.name,
+                    "address": p.address,
+                    "connected": p.connected,
+                    "last_seen": p.last_seen,
+                    "health": p.health.label(),
+                    "capabilities": granted,
+                })
+            })
+            .collect(),
+    )
+}
+
+async fn grant(
+    burrow: &Burrow,
+    peer: &str,
+    capability: &str,
+    ttl: u64,
+) -> Result<Value, ProtocolError> {
+    let cap = Capability::from_label(capability)
+        .ok_or_else(|| ProtocolError::BadRequest(format!("unknown capability: {capability}")))?;
+    if peer.trim().is_empty() {
+        return Err(ProtocolError::BadRequest("grant requires a peer".into()));
+    }
+    burrow
+        .capabilities
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .grant(peer, cap, ttl);
+
+    // Tell the target, as a DELEGATE from a remote manager would.
+    let mut notice = Frame::with_args("DELEGATE-GRANT", vec![cap.label().to_string()]);
+    notice.set_header("TTL", ttl.to_string());
+    notice.set_header("Granted-By", burrow.burrow_id());
+    burrow
+        .sessions
+        .broadcast(vec![(peer.to_string(), notice)])
+        .await;
+
+    Ok(json!({ "peer": peer, "capability": cap.label(), "ttl": ttl }))
+}
+
+fn ungrant(burrow: &Burrow, peer: &str, capability: Option<&str>) -> Result<Value, ProtocolError> {
+    let cap = capability
+        .map(|label| {
+            Capability::from_label(label)
+                .ok_or_else(|| ProtocolError::BadRequest(format!("unknown capability: {label}")))
+        })
+        .transpose()?;
+    let mut caps = burrow
+        .capabilities
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    match cap {
+        Some(cap) => caps.revoke(peer, cap),
+        None => caps.revoke_all(peer),
+    }
+    Ok(json!({ "peer": peer, "capability": cap.map_or("*", |c| c.label()) }))
+}
+
+fn prune_topic(burrow: &Burrow, topic: &str, keep: usize) -> Result<Value, ProtocolError> {
+    if !burrow.events.has_topic(topic) {
+        return Err(ProtocolError::Missing(format!("no such topic: {topic}")));
+    }
+    let before = burrow.events.event_count(topic);
+    let dropped = burrow.events.prune(topic, keep);
+    match &burrow.continuity {
+        Some(store) => {
+            let compaction = store.prune(topic, keep)?;
+            burrow.quotas.release_topic(
+                topic,
+                compaction.removed_bytes,
+                compaction.removed as u64,
+            );
+            if let Some(seq) = compaction.last_removed {
+                burrow.quotas.release_publishers(topic, seq);
+            }
+        }
+        None => {
+            let bytes = dropped.iter().map(|e| e.body.len() as u64).sum();
+            burrow
+                .quotas
+                .release_topic(topic, bytes, dropped.len() as u64);
+            if let Some(last) = dropped.iter().map(|e| e.seq).max() {
+                burrow.quotas.release_publishers(topic, last);
+            }
+        }
+    }
+    let remaining = burrow.events.event_count(topic);
+    Ok(json!({
+        "topic": topic,
+        "removed": before - remaining,
+        "remaining": remaining,
+    }))
+}
+
+fn verify_topic(burrow: &Burrow, topic: &str) -> Result<Value, ProtocolError> {
+    let Some(store) = &burrow.continuity else {
+        return Err(ProtocolError::Missing("no continuity store".into()));
+    };
+    if !store.has_topic(topic) {
+        return Err(ProtocolError::Missing(format!("no log for topic: {topic}")));
+    }
+    let report = store.verify_topic(topic)?;
+    Ok(json!({
+        "topic": topic,
+        "intact": report.is_intact(),
+        "records": report.records,
+        "chained": report.chained,
+        "signed": report.signed,
+        "problems": report.problems,
+    }))
+}
+
+fn sessions(burrow: &Burrow) -> Value {
+    Value::Array(
+        burrow
+            .session_store
+            .list()
+            .into_iter()
+            .map(|s| {
+                json!({
+                    "token": s.token,
+                    "peer_id": s.peer_id,
+                    "issued_at": s.issued_at,
+                    "expires_at": s.expires_at,
+                    "connected": burrow.sessions.has_session(&s.peer_id),
+                })
+            })
+            .collect(),
+    )
+}
+
+fn revoke(
+    burrow: &Burrow,
+    peer: Option<&str>,
+    session: Option<&str>,
+) -> Result<Value, ProtocolError> {
+    let revoked = match (peer, session) {
+        (Some(peer), None) => burrow.revoke_peer(peer).len(),
+        (None, Some(token)) => burrow.revoke_session(token).into_iter().count(),
+        _ => {
+            return Err(ProtocolError::BadRequest(
+                "revoke needs either a peer or a session".into(),
+            ))
+        }
+    };
+    Ok(json!({ "revoked": revoked }))
+}
+
+fn purge_dead_letters(burrow: &Burrow, older_than: Option<u64>) -> Result<Value, ProtocolError> {
+    let before = older_than.map(|age| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        now.saturating_sub(age)
+    });
+    let purged = burrow.dead_letters.purge(before)?;
+    Ok(json!({ "purged": purged, "remaining": burrow.dead_letters.len() }))
+}
+
+// ── Socket ─────────────────────────────────────────────────────
+
+#[cfg(unix)]
+pub use self::unix::{request, AdminServer};
+#[cfg(not(unix))]
+pub use self::unsupported::{request, AdminServer};
+
+#[cfg(unix)]
+mod unix {
+    use std::path::{Path, PathBuf};
+
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::sync::watch;
+    use tracing::{debug, info, warn};
+
+    use super::*;
+
+    fn io_error(what: &str, path: &Path, e: std::io::Error) -> ProtocolError {
+        ProtocolError::InternalError(format!("{} {}: {}", what, path.display(), e))
+    }
+
+    /// A bound admin socket.  The socket file is removed when the
+    /// server is dropped.
+    #[derive(Debug)]
+    pub struct AdminServer {
+        listener: UnixListener,
+        path: PathBuf,
+    }
+
+    impl AdminServer {
+        /// Bind the socket at `path`.
+        ///
+        /// A leftover socket file from a previous run is replaced;
+        /// binding fails if another process is still listening on it.
+        pub async fn bind(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+            use std::os::unix::fs::PermissionsExt;
+
+            let path = path.as_ref().to_path_buf();
+            if path.exists() {
+                if UnixStream::connect(&path).await.is_ok() {
+                    return Err(ProtocolError::InternalError(format!(
+                        "admin socket {} is in use",
+                        path.display()
+                    )));
+                }
+                std::fs::remove_file(&path)
+                    .map_err(|e| io_error("failed to remove stale socket", &path, e))?;
+            }
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| io_error("failed to create directory", parent, e))?;
+            }
+            let listener =
+                UnixListener::bind(&path).map_err(|e| io_error("failed to bind", &path, e))?;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+                .map_err(|e| io_error("failed to restrict", &path, e))?;
+            Ok(Self { listener, path })
+        }
+
+        /// Path of the socket file.
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+
+        /// Serve admin connections until dropped.
+        ///
+        /// Each command runs against the burrow current at the time
+        /// it arrives, so a server started once keeps working across
+        /// config reloads that replace the burrow.
+        pub async fn run(self, burrow: watch::Receiver<Arc<Burrow>>) {
+            info!(path = %self.path.display(), "admin socket listening");
+            loop {
+                match self.listener.accept().await {
+                    Ok((stream, _)) => {
+                        let burrow = burrow.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = serve_connection(stream, burrow).await {
+                                debug!(err = %e, "admin connection ended");
+                            }
+                        });
+                    }
+                    Err(e) => warn!(err = %e, "admin accept failed"),
+                }
+            }
+        }
+    }
+
+    impl Drop for AdminServer {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    async fn serve_connection(
+        stream: UnixStream,
+        burrow: watch::Receiver<Arc<Burrow>>,
+    ) -> std::io::Result<()> {
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<AdminRequest>(&line) {
+                Ok(request) => {
+                    debug!(?request, "admin command");
+                    let current = Arc::clone(&burrow.borrow());
+                    execute(&current, request).await
+                }
+                Err(e) => AdminResponse::failure(format!("bad request: {}", e)),
+            };
+            let mut out = serde_json::to_string(&response).map_err(std::io::Error::other)?;
+            out.push('\n');
+            write.write_all(out.as_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    /// Send one command to the admin socket at `path` and wait for
+    /// the reply.
+    pub async fn request(
+        path: impl AsRef<Path>,
+        request: &AdminRequest,
+    ) -> Result<AdminResponse, ProtocolError> {
+        let path = path.as_ref();
+        let stream = UnixStream::connect(path)
+            .await
+            .map_err(|e| io_error("cannot connect to", path, e))?;
+        let (read, mut write) = stream.into_split();
+        let mut line = serde_json::to_string(request)
+            .map_err(|e| ProtocolError::InternalError(format!("encode request: {}", e)))?;
+        line.push('\n');
+        write
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| io_error("failed to write to", path, e))?;
+        let reply = BufReader::new(read)
+            .lines()
+            .next_line()
+            .await
+            .map_err(|e| io_error("failed to read from", path, e))?
+            .ok_or_else(|| {
+                ProtocolError::InternalError("admin socket closed without a reply".into())
+            })?;
+        serde_json::from_str(&reply)
+            .map_err(|e| ProtocolError::InternalError(format!("bad admin reply: {}", e)))
+    }
+}
+
+#[cfg(not(unix))]
+mod unsupported {
+    use std::path::Path;
+
+    use tokio::sync::watch;
+
+    use super::*;
+
+    fn unsupported() -> ProtocolError {
+        ProtocolError::InternalError("the admin socket needs Unix domain sockets".into())
+    }
+
+    /// Placeholder: admin sockets are only available on Unix.
+    #[derive(Debug)]
+    pub struct AdminServer;
+
+    impl AdminServer {
+        /// Always fails off Unix.
+        pub async fn bind(_path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+            Err(unsupported())
+        }
+
+        /// Never returns.
+        pub async fn run(self, _burrow: watch::Receiver<Arc<Burrow>>) {
+            std::future::pending().await
+        }
+    }
+
+    /// Always fails off Unix.
+    pub async fn request(
+        _path: impl AsRef<Path>,
+        _request: &AdminRequest,
+    ) -> Result<AdminResponse, ProtocolError> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_json_shape() {
+        let req: AdminRequest =
+            serde_json::from_str(r#"{"cmd":"grant","peer":"p","capability":"Fetch"}"#).unwrap();
+        assert_eq!(
+            req,
+            AdminRequest::Grant {
+                peer: "p".into(),
+                capability: "Fetch".into(),
+                ttl: DEFAULT_GRANT_TTL,
+            }
+        );
+        let line = serde_json::to_string(&AdminRequest::PruneTopic {
+            topic: "/q/chat".into(),
+            keep: 5,
+        })
+        .unwrap();
+        assert_eq!(line, r#"{"cmd":"prune-topic","topic":"/q/chat","keep":5}"#);
+        let req: AdminRequest =
+            serde_json::from_str(r#"{"cmd":"verify-topic","topic":"/q/chat"}"#).unwrap();
+        assert_eq!(
+            req,
+            AdminRequest::VerifyTopic {
+                topic: "/q/chat".into()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn grant_and_prune() {
+        let burrow = Burrow::in_memory("admin-test");
+        let resp = execute(
+            &burrow,
+            AdminRequest::Grant {
+                peer: "ed25519:PEER".into(),
+                capability: "Publish".into(),
+                ttl: 60,
+            },
+        )
+        .await;
+        assert!(resp.ok, "{:?}", resp.error);
+        assert!(burrow
+            .capabilities
+            .lock()
+            .unwrap()
+            .check("ed25519:PEER", Capability::Publish));
+
+        let resp = execute(
+            &burrow,
+            AdminRequest::Ungrant {
+                peer: "ed25519:PEER".into(),
+                capability: Some("Publish".into()),
+            },
+        )
+        .await;
+        assert!(resp.ok, "{:?}", resp.error);
+        assert!(!burrow
+            .capabilities
+            .lock()
+            .unwrap()
+            .check("ed25519:PEER", Capability::Publish));
+
+        let bad = execute(
+            &burrow,
+            AdminRequest::Grant {
+                peer: "ed25519:PEER".into(),
+                capability: "Fly".into(),
+                ttl: 60,
+            },
+        )
+        .await;
+        assert!(!bad.ok);
+
+        burrow.events.subscribe("/q/log", "sys", "0", None);
+        for i in 0..5 {
+            let _ = burrow.events.publish("/q/log", &format!("e{}", i));
+        }
+        burrow.quotas.seed_topic("/q/log", 10, 5);
+        let resp = execute(
+            &burrow,
+            AdminRequest::PruneTopic {
+                topic: "/q/log".into(),
+                keep: 2,
+            },
+        )
+        .await;
+        assert_eq!(resp.result.unwrap()["removed"], 3);
+        assert_eq!(burrow.events.event_count("/q/log"), 2);
+        let usage = burrow.quotas.topic_usage("/q/log");
+        assert_eq!((usage.bytes, usage.events), (4, 2));
+    }
+
+    #[tokio::test]
+    async fn dead_letters_list_retry_and_purge() {
+        let burrow = Burrow::in_memory("admin-test");
+        let frame = Frame::with_args("EVENT", vec!["/q/chat".into()]).serialize();
+        let id = burrow
+            .dead_letters
+            .record("ed25519:PEER", 1, frame.as_str(), "unacknowledged")
+            .unwrap();
+        burrow
+            .dead_letters
+            .record("ed25519:PEER", 1, frame.as_str(), "tunnel closed")
+            .unwrap();
+
+        let resp = execute(&burrow, AdminRequest::DeadLetters).await;
+        let listed = resp.result.unwrap();
+        assert_eq!(listed.as_array().unwrap().len(), 2);
+        assert_eq!(listed[0]["reason"], "unacknowledged");
+
+        // The peer has no tunnel open, so the entry stays parked.
+        let resp = execute(&burrow, AdminRequest::RetryDeadLetter { id }).await;
+        assert!(!resp.ok);
+        let mut rx = burrow.sessions.register("ed25519:PEER", 4);
+        let resp = execute(&burrow, AdminRequest::RetryDeadLetter { id }).await;
+        assert!(resp.ok, "{:?}", resp.error);
+        assert_eq!(rx.recv().await.unwrap().verb, "EVENT");
+
+        let resp = execute(&burrow, AdminRequest::PurgeDeadLetters { older_than: None }).await;
+        assert_eq!(resp.result.unwrap()["purged"], 1);
+        assert!(burrow.dead_letters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sessions_list_and_revoke() {
+        let burrow = Burrow::in_memory("admin-test");
+        burrow.session_store.issue("t1", "ed25519:PEER", 60);
+        burrow.session_store.issue("t2", "ed25519:PEER", 60);
+
+        let resp = execute(&burrow, AdminRequest::Sessions).await;
+        let listed = resp.result.unwrap();
+        assert_eq!(listed.as_array().unwrap().len(), 2);
+        assert_eq!(listed[0]["peer_id"], "ed25519:PEER");
+
+        let neither = AdminRequest::Revoke {
+            peer: None,
+            session: None,
+        };
+        assert!(!execute(&burrow, neither).await.ok);
+        let one = AdminRequest::Revoke {
+            peer: None,
+            session: Some("t1".into()),
+        };
+        assert_eq!(execute(&burrow, one).await.result.unwrap()["revoked"], 1);
+        let all = AdminRequest::Revoke {
+            peer: Some("ed25519:PEER".into()),
+            session: None,
+        };
+        assert_eq!(execute(&burrow, all).await.result.unwrap()["revoked"], 1);
+        assert!(burrow.session_store.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn socket_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("admin.sock");
+        let server = AdminServer::bind(&path).await.unwrap();
+        let burrow = Arc::new(Burrow::in_memory("admin-sock"));
+        let (_tx, rx) = tokio::sync::watch::channel(Arc::clone(&burrow));
+        let task = tokio::spawn(server.run(rx));
+
+        let resp = request(&path, &AdminRequest::Status).await.unwrap();
+        let status = resp.result.unwrap();
+        assert_eq!(status["name"], "admin-sock");
+        assert_eq!(status["id"], burrow.burrow_id());
+
+        // A second server cannot take over a live socket.
+        assert!(AdminServer::bind(&path).await.is_err());
+
+        task.abort();
+        let _ = task.await;
+        assert!(!path.exists());
+    }
+}
@@ -6,24 +6,48 @@
 //! burrow serve                     # serve from ./config.toml
 //! burrow serve --config path.toml  # serve from a specific config
 //! burrow serve --port 8443         # override the listening port
+//! burrow serve --daemonize --pid-file /run/burrow.pid
+//! burrow serve --capture debug.rcap  # record frames for rabbit-dump
 //! burrow init                      # generate a starter config.toml
 //! burrow info                      # show burrow identity
+//! burrow snapshot backup.tar       # back up a stopped burrow's state
+//! burrow restore backup.tar        # ...and restore it elsewhere
 //! ```
+//!
+//! While serving, SIGTERM or SIGINT saves trust, sessions, and routes
+//! and exits; SIGHUP re-reads the config file.  With `[admin] socket`
+//! set, the running burrow can be managed with `rabbitctl`.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use std::sync::Arc;
 
 use clap::{Parser, Subcommand};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 use tracing::{error, info, warn};
 
+use rabbit_engine::admin::AdminServer;
 use rabbit_engine::burrow::Burrow;
-use rabbit_engine::config::Config;
-use rabbit_engine::transport::cert::{generate_self_signed, make_server_config, CertPair};
-use rabbit_engine::transport::connector::{connect, make_client_config_insecure};
-use rabbit_engine::transport::listener::RabbitListener;
+use rabbit_engine::config::{Config, ListenerLimits, LoggingConfig};
+use rabbit_engine::daemon::{spawn_background, PidFile, ServiceSignal, ServiceSignals};
+use rabbit_engine::hooks::BurrowState;
+use rabbit_engine::logging::{self, LogGuard};
+use rabbit_engine::network::discovery::MdnsService;
+use rabbit_engine::network::acceptor::run_listener;
+use rabbit_engine::network::nat;
+use rabbit_engine::network::ConnectionManager;
+use rabbit_engine::snapshot;
+use rabbit_engine::transport::capture::CaptureWriter;
+use rabbit_engine::transport::cert::make_server_config;
+use rabbit_engine::transport::listener::{bind_tcp, RabbitListener};
+use rabbit_engine::transport::tcp::PlainListener;
+use rabbit_engine::transport::stats::ListenerCounters;
+use rabbit_engine::transport::tap::FrameTap;
+use rabbit_engine::transport::tunnel::Acceptor;
+use rabbit_engine::warren::gossip;
 use rabbit_engine::ai::connector::spawn_connectors;
 use rabbit_engine::ai::http::tls_config;
-use rabbit_engine::transport::tunnel::Tunnel;
 
 /// Rabbit burrow — headless peer-to-peer node.
 #[derive(Parser)]
@@ -57,6 +81,24 @@ enum Commands {
         /// Can be specified multiple times.
         #[arg(long)]
         connect: Vec<String>,
+
+        /// Stay attached to the terminal (the default; use under
+        /// systemd).
+        #[arg(long, conflicts_with = "daemonize")]
+        foreground: bool,
+
+        /// Detach and keep serving in the background.
+        #[arg(long)]
+        daemonize: bool,
+
+        /// Write the process ID to this file while serving.
+        #[arg(long)]
+        pid_file: Option<PathBuf>,
+
+        /// Record every frame on incoming tunnels to this capture
+        /// file (read it with `rabbit-dump show`).
+        #[arg(long)]
+        capture: Option<PathBuf>,
     },
 
     /// Generate a starter config.toml in the current directory.
@@ -72,17 +114,34 @@ enum Commands {
         #[arg(short, long, default_value = "config.toml")]
         config: PathBuf,
     },
+
+    /// Back up a stopped burrow's persistent state to an archive
+    /// (`rabbitctl snapshot` backs up a running one).
+    Snapshot {
+        /// Archive to write.
+        archive: PathBuf,
+
+        /// Path to config.toml (default: ./config.toml).
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+    },
+
+    /// Restore persistent state from a snapshot into the storage
+    /// directory, which must not already hold any of it.
+    Restore {
+        /// Archive to read.
+        archive: PathBuf,
+
+        /// Path to config.toml (default: ./config.toml).
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+    },
 }
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing (structured logging).
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_timer(tracing_subscriber::fmt::time::uptime())
-        .init();
-
     let cli = Cli::parse();
+    let _log = init_logging(&cli);
 
     match cli.command {
         Commands::Serve {
@@ -91,8 +150,29 @@ async fn main() {
             port,
             storage,
             connect: connect_peers,
+            foreground: _,
+            daemonize,
+            pid_file,
+            capture,
         } => {
-            if let Err(e) = cmd_serve(config, name, port, storage, connect_peers).await {
+            if daemonize {
+                match spawn_background() {
+                    Ok(pid) => info!(pid, "burrow running in the background"),
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+            let opts = ServeOptions {
+                config_path: config,
+                name,
+                port,
+                storage,
+                connect: connect_peers,
+            };
+            if let Err(e) = cmd_serve(opts, pid_file, capture).await {
                 error!("{}", e);
                 std::process::exit(1);
             }
@@ -109,193 +189,455 @@ async fn main() {
                 std::process::exit(1);
             }
         }
+        Commands::Snapshot { archive, config } => {
+            if let Err(e) = cmd_snapshot(archive, config) {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Restore { archive, config } => {
+            if let Err(e) = cmd_restore(archive, config) {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Install the log subscriber from the `[logging]` section of the
+/// command's config file.  If the file does not load, the defaults
+/// are used and the command itself reports the problem.
+fn init_logging(cli: &Cli) -> LogGuard {
+    let config_path = match &cli.command {
+        Commands::Serve { config, .. }
+        | Commands::Info { config }
+        | Commands::Snapshot { config, .. }
+        | Commands::Restore { config, .. } => Some(config),
+        Commands::Init { .. } => None,
+    };
+    let (config, base_dir) = config_path
+        .and_then(|path| {
+            let config = Config::load(path).ok()?;
+            let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+            Some((config.logging, base_dir.to_path_buf()))
+        })
+        .unwrap_or_else(|| (LoggingConfig::default(), PathBuf::from(".")));
+    match logging::init(&config, &base_dir) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("burrow: {}", e);
+            std::process::exit(1);
+        }
     }
 }
 
 // ── Serve ──────────────────────────────────────────────────────
 
-async fn cmd_serve(
+/// `serve` arguments, re-applied on every config reload.
+struct ServeOptions {
     config_path: PathBuf,
-    name_override: Option<String>,
-    port_override: Option<u16>,
-    storage_override: Option<PathBuf>,
-    connect_peers: Vec<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Load config.
-    let mut config = Config::load(&config_path)?;
-    let base_dir = config_path
-        .parent()
-        .unwrap_or_else(|| std::path::Path::new("."))
-        .to_path_buf();
+    name: Option<String>,
+    port: Option<u16>,
+    storage: Option<PathBuf>,
+    connect: Vec<String>,
+}
 
-    // Apply CLI overrides.
-    if let Some(n) = name_override {
-        config.identity.name = n;
-    }
-    if let Some(p) = port_override {
-        config.network.port = p;
-    }
-    if let Some(s) = storage_override {
-        config.identity.storage = s;
-    }
+impl ServeOptions {
+    /// Load and validate the config file, then apply CLI overrides.
+    /// Returns the config and its base directory.
+    fn load(&self) -> Result<(Config, PathBuf), Box<dyn std::error::Error>> {
+        let mut config = Config::load(&self.config_path)?;
+        let base_dir = self
+            .config_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+
+        if let Some(n) = &self.name {
+            config.identity.name = n.clone();
+        }
+        if let Some(p) = self.port {
+            config.network.port = p;
+        }
+        if let Some(s) = &self.storage {
+            config.identity.storage = s.clone();
+        }
 
-    // Merge --connect peers with config peers.
-    for peer in &connect_peers {
-        if !config.network.peers.contains(peer) {
-            config.network.peers.push(peer.clone());
+        // Merge --connect peers with config peers.
+        for peer in &self.connect {
+            if !config.network.peers.contains(peer) {
+                config.network.peers.push(peer.clone());
+            }
         }
+
+        config.validate()?;
+        Ok((config, base_dir))
     }
+}
 
-    // Build the burrow.
-    let burrow = Arc::new(Burrow::from_config(&config, &base_dir)?);
-    info!(
-        name = %burrow.name,
-        id = %burrow.burrow_id(),
-        "burrow identity loaded"
-    );
+/// A burrow and the background tasks started for it.
+struct Running {
+    burrow: Arc<Burrow>,
+    connections: ConnectionManager,
+    session_sweeper: Option<JoinHandle<()>>,
+    route_adverts: Option<JoinHandle<()>>,
+    manifest_gossip: Option<JoinHandle<()>>,
+    link_heartbeats: Option<JoinHandle<()>>,
+    peer_checker: Option<JoinHandle<()>>,
+    log_compactor: Option<JoinHandle<()>>,
+    mdns: Option<JoinHandle<()>>,
+    port_mapping: Option<JoinHandle<()>>,
+    ai_shutdown: Option<watch::Sender<bool>>,
+}
 
-    // Generate or load TLS certificates.
-    let cert_dir = base_dir.join(&config.identity.certs);
-    let cert_pair = load_or_generate_certs(&cert_dir)?;
-    let server_config = make_server_config(&cert_pair)?;
+impl Running {
+    /// Install the frame tap, dial the configured peers, start the
+    /// session sweeper, route gossip, peer health checks, event log
+    /// compaction, mDNS discovery and port mapping and spawn AI
+    /// connectors.
+    fn start(
+        burrow: Arc<Burrow>,
+        config: &Config,
+        tap: Option<&Arc<dyn FrameTap>>,
+    ) -> Self {
+        burrow.advance(BurrowState::Starting);
+        burrow.set_frame_tap(tap.cloned());
+        info!(
+            name = %burrow.name,
+            id = %burrow.burrow_id(),
+            "burrow identity loaded"
+        );
+
+        // Keep outgoing peer connections up.
+        let connections = ConnectionManager::from_config(Arc::clone(&burrow), &config.network);
+
+        // Sweep expired sessions, closing the tunnels that used them.
+        let session_sweeper = (burrow.session_sweep_secs > 0).then(|| {
+            let burrow = Arc::clone(&burrow);
+            tokio::spawn(async move {
+                let period = Duration::from_secs(burrow.session_sweep_secs);
+                let mut ticker = tokio::time::interval(period);
+                loop {
+                    ticker.tick().await;
+                    burrow.sweep_sessions();
+                }
+            })
+        });
 
-    let listen_addr = format!("0.0.0.0:{}", config.network.port);
-    let listener = RabbitListener::bind(&listen_addr, server_config).await?;
-    let local_addr = listener.local_addr()?;
-    info!(%local_addr, "listening for connections");
-
-    // Spawn outgoing peer connections.
-    let client_config = make_client_config_insecure();
-    for peer_addr in &config.network.peers {
-        let burrow = Arc::clone(&burrow);
-        let addr = peer_addr.clone();
-        let cc = Arc::clone(&client_config);
-        tokio::spawn(async move {
-            info!(peer = %addr, "connecting to peer");
-            match connect_to_peer(&burrow, &addr, cc).await {
-                Ok(id) => info!(peer = %addr, remote_id = %id, "peer session ended"),
-                Err(e) => warn!(peer = %addr, err = %e, "peer connection failed"),
+        // Advertise routes to connected peers, dropping stale ones.
+        let route_adverts = (burrow.route_advert_secs > 0).then(|| {
+            let burrow = Arc::clone(&burrow);
+            tokio::spawn(async move {
+                let period = Duration::from_secs(burrow.route_advert_secs);
+                let mut ticker = tokio::time::interval(period);
+                loop {
+                    ticker.tick().await;
+                    burrow.expire_routes().await;
+                    burrow.advertise_routes().await;
+                }
+            })
+        });
+
+        // Gossip anchors' manifests with federation links.
+        let manifest_gossip = (burrow.gossip_secs > 0).then(|| {
+            gossip::spawn_gossip(
+                Arc::clone(&burrow),
+                Duration::from_secs(burrow.gossip_secs),
+                Duration::from_secs(burrow.gossip_max_secs),
+            )
+        });
+
+        // Keep open federation links alive.
+        let link_heartbeats = (burrow.federation.heartbeat_secs() > 0).then(|| {
+            let burrow = Arc::clone(&burrow);
+            tokio::spawn(async move {
+                let period = Duration::from_secs(burrow.federation.heartbeat_secs());
+                let mut ticker = tokio::time::interval(period);
+                loop {
+                    ticker.tick().await;
+                    burrow.heartbeat_links();
+                }
+            })
+        });
+
+        // Ping peers and judge their health.
+        let peer_checker = (burrow.peer_check_secs > 0).then(|| {
+            let burrow = Arc::clone(&burrow);
+            tokio::spawn(async move {
+                let period = Duration::from_secs(burrow.peer_check_secs);
+                let mut ticker = tokio::time::interval(period);
+                loop {
+                    ticker.tick().await;
+                    burrow.check_peers().await;
+                }
+            })
+        });
+
+        // The janitor: drop events beyond each topic's retention limits.
+        let log_compactor = burrow.spawn_janitor();
+
+        // Advertise the burrow on the local network and find others.
+        let mdns = if config.discovery.mdns {
+            match MdnsService::bind() {
+                Ok(service) => Some(service.spawn(
+                    Arc::clone(&burrow),
+                    config.network.port,
+                    Duration::from_secs(config.discovery.mdns_interval_secs),
+                )),
+                Err(e) => {
+                    warn!(err = %e, "mDNS discovery unavailable");
+                    None
+                }
             }
+        } else {
+            None
+        };
+
+        // Have the gateway forward the port, so peers outside can dial.
+        let port_mapping = config.network.nat_mapping.then(|| {
+            let gateway = config
+                .network
+                .nat_gateway
+                .as_deref()
+                .and_then(|ip| ip.parse().ok());
+            nat::spawn_port_mapping(Arc::clone(&burrow), gateway, config.network.port)
         });
+
+        // Spawn AI connectors if configured.
+        let ai_shutdown = if !burrow.ai_chats.is_empty() {
+            let ai_tls = tls_config();
+            let ai_events = Arc::clone(&burrow.events);
+            let chats = burrow.ai_chats.clone();
+            info!(count = chats.len(), "spawning AI connectors");
+            Some(spawn_connectors(chats, ai_events, ai_tls))
+        } else {
+            None
+        };
+        burrow.advance(BurrowState::Running);
+
+        Self {
+            burrow,
+            connections,
+            session_sweeper,
+            route_adverts,
+            manifest_gossip,
+            link_heartbeats,
+            peer_checker,
+            log_compactor,
+            mdns,
+            port_mapping,
+            ai_shutdown,
+        }
     }
 
-    // Spawn AI connectors if configured.
-    let _ai_shutdown = if !burrow.ai_chats.is_empty() {
-        let ai_tls = tls_config();
-        let ai_events = std::sync::Arc::clone(&burrow.events);
-        let chats = burrow.ai_chats.clone();
-        info!(count = chats.len(), "spawning AI connectors");
-        Some(spawn_connectors(chats, ai_events, ai_tls))
-    } else {
-        None
+    /// Stop outgoing peer sessions, the session sweeper, route and
+    /// manifest gossip, link heartbeats, peer health checks, event log
+    /// compaction, mDNS discovery, port mapping and AI connectors.
+    fn stop(&mut self) {
+        self.connections.stop();
+        if let Some(task) = self.session_sweeper.take() {
+            task.abort();
+        }
+        if let Some(task) = self.route_adverts.take() {
+            task.abort();
+        }
+        if let Some(task) = self.manifest_gossip.take() {
+            task.abort();
+        }
+        if let Some(task) = self.link_heartbeats.take() {
+            task.abort();
+        }
+        if let Some(task) = self.peer_checker.take() {
+            task.abort();
+        }
+        if let Some(task) = self.log_compactor.take() {
+            task.abort();
+        }
+        if let Some(task) = self.mdns.take() {
+            task.abort();
+        }
+        if let Some(task) = self.port_mapping.take() {
+            task.abort();
+        }
+        if let Some(tx) = self.ai_shutdown.take() {
+            info!("stopping AI connectors");
+            let _ = tx.send(true);
+        }
+    }
+}
+
+async fn cmd_serve(
+    opts: ServeOptions,
+    pid_file: Option<PathBuf>,
+    capture: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Install handlers first so an early SIGTERM is not lost.
+    let mut signals = ServiceSignals::new()?;
+    let _pid_file = pid_file.map(PidFile::create).transpose()?;
+
+    let tap: Option<Arc<dyn FrameTap>> = match &capture {
+        Some(path) => {
+            info!(path = %path.display(), "capturing frames");
+            Some(Arc::new(CaptureWriter::create(path)?))
+        }
+        None => None,
     };
 
-    // Accept loop — runs until Ctrl-C.
-    let shutdown = tokio::signal::ctrl_c();
-    tokio::pin!(shutdown);
+    let (config, base_dir) = opts.load()?;
+    let burrow = Arc::new(Burrow::from_config(&config, &base_dir)?);
+    let mut running = Running::start(burrow, &config, tap.as_ref());
+    // The config the running burrow was built from, to fall back on.
+    let mut loaded = (config.clone(), base_dir.clone());
+
+    // The admin socket follows the current burrow across reloads.
+    let (current_burrow, admin_burrow) = watch::channel(Arc::clone(&running.burrow));
+    let admin_task = match &config.admin.socket {
+        Some(socket) => {
+            let server = AdminServer::bind(base_dir.join(socket)).await?;
+            Some(tokio::spawn(server.run(admin_burrow)))
+        }
+        None => None,
+    };
 
+    // The burrow loaded its TLS certificate, or generated one on
+    // first start.
+    let cert_pair = running
+        .burrow
+        .certificate
+        .clone()
+        .ok_or("burrow has no TLS certificate")?;
+    let server_config = make_server_config(&cert_pair)?;
+
+    // One listener per bind address, then any configured with TLS
+    // settings of their own.
+    let mut listeners = Vec::new();
+    let mut bound_port = None;
+    for addr in config.network.bind_addrs()? {
+        let limits = &config.network.limits;
+        let tcp = bind_tcp(&addr.to_string(), limits.backlog).await?;
+        bound_port.get_or_insert(tcp.local_addr()?.port());
+        let listener = RabbitListener::from_tcp(tcp, Arc::clone(&server_config));
+        listeners.push(start_listener(listener, limits, &current_burrow)?);
+    }
+    for listener_config in &config.network.listeners {
+        let limits = listener_config.limits.as_ref().unwrap_or(&config.network.limits);
+        let tcp = bind_tcp(&listener_config.address, limits.backlog).await?;
+        let listener = if !listener_config.tls {
+            start_listener(PlainListener::from_tcp(tcp), limits, &current_burrow)?
+        } else {
+            let server_config = match listener_config.cert_pair(&base_dir)? {
+                Some(pair) => make_server_config(&pair)?,
+                None => Arc::clone(&server_config),
+            };
+            let listener = RabbitListener::from_tcp(tcp, server_config);
+            start_listener(listener, limits, &current_burrow)?
+        };
+        listeners.push(listener);
+    }
+
+    // Runs until SIGTERM/SIGINT.
     loop {
-        tokio::select! {
-            accept_result = listener.accept() => {
-                match accept_result {
-                    Ok(mut tunnel) => {
-                        let burrow = Arc::clone(&burrow);
-                        tokio::spawn(async move {
-                            let peer_addr = "tls-peer";
-                            info!(peer = peer_addr, "accepted connection");
-                            match burrow.handle_tunnel(&mut tunnel).await {
-                                Ok(id) => info!(peer_id = %id, "tunnel closed cleanly"),
-                                Err(e) => warn!(err = %e, "tunnel error"),
-                            }
-                        });
-                    }
-                    Err(e) => {
-                        warn!(err = %e, "accept failed");
-                    }
-                }
-            }
-            _ = &mut shutdown => {
+        match signals.recv().await {
+            ServiceSignal::Shutdown => {
                 info!("received shutdown signal");
                 break;
             }
+            ServiceSignal::Reload => {
+                info!("received SIGHUP, reloading config");
+                let port = bound_port.unwrap_or(config.network.port);
+                if reload(&opts, &mut running, &mut loaded, port, tap.as_ref()).await? {
+                    current_burrow.send_replace(Arc::clone(&running.burrow));
+                    for (_, counters) in &listeners {
+                        running.burrow.listener_stats.register(Arc::clone(counters));
+                    }
+                    info!("config reloaded");
+                }
+            }
         }
     }
 
-    // Graceful shutdown: stop AI connectors.
-    if let Some(tx) = _ai_shutdown {
-        info!("stopping AI connectors");
-        let _ = tx.send(true);
+    // Graceful shutdown: stop accepting, stop background tasks and
+    // persist state.
+    for (task, _) in listeners {
+        task.abort();
     }
-
-    // Save trust cache.
-    info!("saving trust cache");
-    if let Err(e) = burrow.save_trust() {
-        warn!(err = %e, "failed to save trust cache");
+    if let Some(task) = admin_task {
+        task.abort();
+        let _ = task.await;
+    }
+    running.stop();
+    if let Err(e) = running.burrow.shutdown().await {
+        warn!(err = %e, "failed to save state");
     }
 
     info!("shutdown complete");
     Ok(())
 }
 
-/// Connect to a single peer, run client handshake, then dispatch loop.
-async fn connect_to_peer(
-    burrow: &Burrow,
-    addr: &str,
-    client_config: Arc<rustls::ClientConfig>,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let mut tunnel = connect(addr, client_config, "localhost").await?;
-    let server_id = burrow.client_handshake(&mut tunnel).await?;
-    info!(remote_id = %server_id, "handshake complete with peer");
-
-    // Register the peer.
-    let peer_info =
-        rabbit_engine::warren::peers::PeerInfo::new(server_id.clone(), addr.to_string(), "");
-    burrow.peers.register(peer_info).await;
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    burrow.peers.mark_connected(&server_id, now).await;
-
-    // Run dispatch loop — read frames from the peer.
-    let dispatcher = burrow.dispatcher();
-    loop {
-        let frame = match tunnel.recv_frame().await? {
-            Some(f) => f,
-            None => break,
-        };
-        let result = dispatcher.dispatch(&frame, &server_id).await;
-        tunnel.send_frame(&result.response).await?;
-        for extra in &result.extras {
-            tunnel.send_frame(extra).await?;
-        }
-    }
-
-    burrow.peers.mark_disconnected(&server_id).await;
-    Ok(server_id)
+/// Serve the current burrow on `acceptor` within `limits`, with
+/// counters registered for the admin socket.
+fn start_listener<A: Acceptor + 'static>(
+    acceptor: A,
+    limits: &ListenerLimits,
+    burrows: &watch::Sender<Arc<Burrow>>,
+) -> Result<(JoinHandle<()>, Arc<ListenerCounters>), Box<dyn std::error::Error>> {
+    let counters = Arc::new(ListenerCounters::new(acceptor.local_addr()?.to_string()));
+    burrows.borrow().listener_stats.register(Arc::clone(&counters));
+    let task = run_listener(acceptor, limits.clone(), Arc::clone(&counters), burrows.subscribe());
+    Ok((task, counters))
 }
 
-/// Load TLS certs from disk, or generate and save them.
-fn load_or_generate_certs(
-    cert_dir: &std::path::Path,
-) -> Result<CertPair, Box<dyn std::error::Error>> {
-    let cert_path = cert_dir.join("cert.pem");
-    let key_path = cert_dir.join("key.pem");
-
-    if cert_path.exists() && key_path.exists() {
-        info!("loading TLS certificates from {}", cert_dir.display());
-        let cert_pem = std::fs::read_to_string(&cert_path)?;
-        let key_pem = std::fs::read_to_string(&key_path)?;
-        Ok(CertPair { cert_pem, key_pem })
-    } else {
-        info!("generating self-signed TLS certificates");
-        let pair = generate_self_signed()?;
-        std::fs::create_dir_all(cert_dir)?;
-        std::fs::write(&cert_path, &pair.cert_pem)?;
-        std::fs::write(&key_path, &pair.key_pem)?;
-        info!("saved certificates to {}", cert_dir.display());
-        Ok(pair)
+/// Rebuild the burrow from a fresh read of the config file.
+///
+/// Returns `false`, leaving the current burrow serving, if the new
+/// config cannot be loaded.  Otherwise the current burrow is drained
+/// as at shutdown — its tunnels are sent `GOAWAY` and closed and its
+/// state saved — before the new one opens the storage directory, since
+/// only one burrow may hold its event logs at a time; peers reconnect
+/// to the new burrow.  Should the new burrow fail to build, one is
+/// built from the `previous` config instead, and only if that fails
+/// too is the error returned.  The listening addresses, TLS
+/// certificates, and admin socket only change on restart.
+async fn reload(
+    opts: &ServeOptions,
+    current: &mut Running,
+    previous: &mut (Config, PathBuf),
+    bound_port: u16,
+    tap: Option<&Arc<dyn FrameTap>>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let (config, base_dir) = match opts.load() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            warn!(err = %e, "reload failed, keeping current config");
+            return Ok(false);
+        }
+    };
+    if config.network.port != 0 && config.network.port != bound_port {
+        warn!(
+            port = config.network.port,
+            "port change takes effect on restart"
+        );
+    }
+    current.stop();
+    if let Err(e) = current.burrow.shutdown().await {
+        warn!(err = %e, "failed to save state before reload");
     }
+    if let Err(e) = current.burrow.wait_closed().await {
+        warn!(err = %e, "failed to flush events before reload");
+    }
+    match Burrow::from_config(&config, &base_dir) {
+        Ok(burrow) => {
+            *current = Running::start(Arc::new(burrow), &config, tap);
+            *previous = (config, base_dir);
+        }
+        Err(e) => {
+            warn!(err = %e, "reload failed, restoring previous config");
+            let (config, base_dir) = &*previous;
+            let burrow = Burrow::from_config(config, base_dir)?;
+            *current = Running::start(Arc::new(burrow), config, tap);
+        }
+    }
+    Ok(true)
 }
 
 // ── Init ───────────────────────────────────────────────────────
@@ -343,8 +685,7 @@ body = "Hello, world! Edit config.toml to customise this burrow."
 
     std::fs::write(&output, template)?;
     info!("created {}", output.display());
-    println!("Created {}", output.display());
-    println!("Run `burrow serve` to start your burrow.");
+    info!("run `burrow serve` to start your burrow");
     Ok(())
 }
 
@@ -389,3 +730,38 @@ fn cmd_info(config_path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+// ── Snapshot ───────────────────────────────────────────────────
+
+/// The storage directory named by the config at `config_path`.
+fn storage_dir(config_path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let config = Config::load(config_path)?;
+    let base_dir = config_path.parent().unwrap_or_else(|| Path::new("."));
+    Ok(base_dir.join(&config.identity.storage))
+}
+
+fn cmd_snapshot(archive: PathBuf, config_path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    let storage = storage_dir(&config_path)?;
+    let manifest = snapshot::snapshot(&storage, &archive)?;
+    let bytes: u64 = manifest.files.iter().map(|f| f.bytes).sum();
+    println!(
+        "Wrote {}: {} files, {} bytes",
+        archive.display(),
+        manifest.files.len(),
+        bytes
+    );
+    Ok(())
+}
+
+fn cmd_restore(archive: PathBuf, config_path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    let storage = storage_dir(&config_path)?;
+    let manifest = snapshot::restore(&archive, &storage)?;
+    println!(
+        "Restored {} files into {} (snapshot version {}, taken at {})",
+        manifest.files.len(),
+        storage.display(),
+        manifest.version,
+        manifest.created
+    );
+    Ok(())
+}
@@ -1,40 +1,148 @@
-//! `rabbit` — interactive Rabbit protocol browser.
+//! `rabbit` — interactive Rabbit protocol browser and command-line peer.
 //!
 //! A rabbit is a full peer (it has its own identity and can serve
 //! content) that happens to have a human at the keyboard.  It connects
 //! to burrows, browses their menus, fetches text, and subscribes to
-//! event streams — all through an interactive text UI.
+//! event streams — all through an interactive text UI or one-shot
+//! subcommands.
 //!
 //! # Usage
 //!
 //! ```text
-//! rabbit browse 127.0.0.1:7443            # interactive menu navigation
-//! rabbit fetch  127.0.0.1:7443 /0/readme  # one-shot content fetch
-//! rabbit sub    127.0.0.1:7443 /q/chat    # subscribe to events
+//! rabbit keygen -o rabbit.key                    # create a persistent identity
+//! rabbit keygen -o data/identity.key --rotate     # replace it, signing a rotation record
+//! rabbit cert -d certs/                          # create a self-signed TLS cert
+//! rabbit serve --config config.toml              # serve a burrow
+//! rabbit serve --daemonize --pid-file rabbit.pid # serve in the background
+//! rabbit --config base.toml --config local.toml --set network.port=8443 serve
+//! rabbit config check                            # validate ./config.toml
+//! rabbit config print-effective                  # show the merged config
+//! rabbit connect 127.0.0.1:7443                  # handshake and report the peer
+//! rabbit connect example.org                     # find the burrow by DNS SRV/TXT
+//! rabbit browse  127.0.0.1:7443                  # interactive menu navigation
+//! rabbit list    127.0.0.1:7443 /                # print a menu
+//! rabbit fetch   127.0.0.1:7443 /0/readme        # one-shot content fetch
+//! rabbit publish 127.0.0.1:7443 /q/chat "hello"  # publish an event
+//! rabbit subscribe 127.0.0.1:7443 /q/chat --since 10
+//! rabbit trust list                              # show the TOFU trust cache
+//! rabbit trust revoke ed25519:ABC…               # refuse a peer
+//! rabbit trust quarantine ed25519:ABC… --reason "key leaked"
+//! rabbit --identity rabbit.key grant 127.0.0.1:7443 Publish ed25519:XYZ…
+//! rabbit --identity rabbit.key group 127.0.0.1:7443 add oak-family ed25519:XYZ…
 //! ```
+//!
+//! Configuration is layered: every `--config` file is merged over the
+//! previous one (default: `./config.toml` if present), then each
+//! `--set section.key=value` is applied on top.  Without `--identity`
+//! the rabbit uses `<storage>/identity.key` from the config if it
+//! exists, and otherwise a fresh ephemeral identity per session.
 
-use std::io::{self, BufRead, Write};
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use clap::{Parser, Subcommand};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
+use rabbit_engine::admin::AdminServer;
+use rabbit_engine::burrow::BurrowBuilder;
+use rabbit_engine::config::{Config, LoggingConfig};
 use rabbit_engine::content::store::MenuItem;
+use rabbit_engine::daemon::{spawn_background, PidFile, ServiceSignal, ServiceSignals};
+use rabbit_engine::logging::{self, LogGuard};
+use rabbit_engine::network::resolver::{is_domain, Resolver};
 use rabbit_engine::protocol::frame::Frame;
-use rabbit_engine::security::auth::{build_auth_proof, build_hello};
-use rabbit_engine::security::identity::Identity;
+use rabbit_engine::security::auth::{build_auth_proof, build_hello, stamp_frame};
+use rabbit_engine::security::groups::{GroupChange, GroupOp};
+use rabbit_engine::security::identity::{fingerprint, Identity, PASSPHRASE_ENV};
+use rabbit_engine::security::permissions::Capability;
+use rabbit_engine::security::trust::{PeerStatus, TrustCache};
+use rabbit_engine::transport::cert::generate_self_signed;
 use rabbit_engine::transport::connector::{connect, make_client_config_insecure};
+use rabbit_engine::transport::tls::ClientTunnel;
 use rabbit_engine::transport::tunnel::Tunnel;
 
 /// Rabbit — interactive peer-to-peer browser.
 #[derive(Parser)]
 #[command(name = "rabbit", version, about)]
 struct Cli {
+    /// Identity key file to use instead of the configured or an
+    /// ephemeral identity.
+    #[arg(long, global = true)]
+    identity: Option<PathBuf>,
+
+    /// Config file to load; repeat to layer files (later ones win).
+    /// Defaults to ./config.toml if it exists.
+    #[arg(short, long = "config", global = true)]
+    config: Vec<PathBuf>,
+
+    /// Override a config value, e.g. `--set network.port=8443`.
+    /// Applied after all config files; may be repeated.
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    set: Vec<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Generate a new Ed25519 identity and save it to a key file.
+    Keygen {
+        /// Output path for the identity key.
+        #[arg(short, long, default_value = "identity.key")]
+        output: PathBuf,
+
+        /// Overwrite an existing key file.
+        #[arg(long)]
+        force: bool,
+
+        /// Replace an existing key, keeping it as `<output>.old` and
+        /// writing a rotation record signed by both keys to
+        /// `rotation.frame` beside it.
+        #[arg(long, conflicts_with = "force")]
+        rotate: bool,
+    },
+
+    /// Generate a self-signed TLS certificate (cert.pem and key.pem).
+    Cert {
+        /// Directory to write the certificate into.
+        #[arg(short, long, default_value = "certs")]
+        dir: PathBuf,
+
+        /// Overwrite an existing certificate.
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Serve a burrow from the effective config until SIGTERM/SIGINT.
+    /// SIGHUP reloads the config.
+    Serve {
+        /// Override the listening port.
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Stay attached to the terminal (the default; use under
+        /// systemd).
+        #[arg(long, conflicts_with = "daemonize")]
+        foreground: bool,
+
+        /// Detach and keep serving in the background.
+        #[arg(long)]
+        daemonize: bool,
+
+        /// Write the process ID to this file while serving.
+        #[arg(long)]
+        pid_file: Option<PathBuf>,
+    },
+
+    /// Connect to a burrow, run the handshake, and report the peer.
+    Connect {
+        /// Address of the burrow (e.g. 127.0.0.1:7443), or a domain
+        /// publishing `_rabbit._tcp` SRV records (e.g. example.org).
+        addr: String,
+    },
+
     /// Browse a burrow interactively.
     Browse {
         /// Address of the burrow (e.g. 127.0.0.1:7443).
@@ -45,6 +153,16 @@ enum Commands {
         selector: String,
     },
 
+    /// Print a menu as tab-separated `type label selector` lines.
+    List {
+        /// Address of the burrow (e.g. 127.0.0.1:7443).
+        addr: String,
+
+        /// Menu selector (default: root menu).
+        #[arg(default_value = "/")]
+        selector: String,
+    },
+
     /// Fetch a single resource and print it to stdout.
     Fetch {
         /// Address of the burrow (e.g. 127.0.0.1:7443).
@@ -54,8 +172,21 @@ enum Commands {
         selector: String,
     },
 
+    /// Publish an event to a topic.
+    Publish {
+        /// Address of the burrow (e.g. 127.0.0.1:7443).
+        addr: String,
+
+        /// Topic path (e.g. /q/chat).
+        topic: String,
+
+        /// Event body (read from stdin if omitted).
+        message: Option<String>,
+    },
+
     /// Subscribe to an event topic and stream events to stdout.
-    Sub {
+    #[command(alias = "sub")]
+    Subscribe {
         /// Address of the burrow (e.g. 127.0.0.1:7443).
         addr: String,
 
@@ -66,61 +197,369 @@ enum Commands {
         #[arg(long)]
         since: Option<u64>,
     },
+
+    /// Inspect or edit the local TOFU trust cache.
+    Trust {
+        #[command(subcommand)]
+        command: TrustCommands,
+    },
+
+    /// Delegate a capability to another burrow (DELEGATE verb).
+    ///
+    /// The remote burrow only accepts this if our identity holds
+    /// ManageBurrows or ManageWarren there, and the capability itself
+    /// — use `--identity` with a known key.
+    Grant {
+        /// Address of the burrow (e.g. 127.0.0.1:7443).
+        addr: String,
+
+        /// Capability label (e.g. Publish, Subscribe, Federation).
+        capability: String,
+
+        /// Burrow ID receiving the capability.
+        target: String,
+
+        /// Grant lifetime in seconds.
+        #[arg(long, default_value_t = 3600)]
+        ttl: u64,
+
+        /// Limit the grant to selectors matching a pattern (e.g.
+        /// `/q/dialogue/*` or `/files/**`).
+        #[arg(long)]
+        scope: Option<String>,
+    },
+
+    /// Add a member to a group, or remove one (signed GROUP verb).
+    ///
+    /// The remote burrow only accepts this if our identity holds
+    /// ManageBurrows or ManageWarren there.  Capabilities granted to
+    /// `group:<name>` reach every member.
+    Group {
+        /// Address of the burrow (e.g. 127.0.0.1:7443).
+        addr: String,
+
+        /// `add` or `remove`.
+        op: String,
+
+        /// Group name (e.g. oak-family).
+        group: String,
+
+        /// Burrow ID, or `group:<name>` to nest a group.
+        member: String,
+    },
+
+    /// Validate or print the effective configuration.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Load and validate the config layers; exit non-zero on error.
+    Check,
+
+    /// Print the merged config, with defaults and overrides, as TOML.
+    PrintEffective,
+}
+
+#[derive(Subcommand)]
+enum TrustCommands {
+    /// List known peers and their status.
+    List {
+        /// Path to the trust cache (default: `<storage>/trust.tsv`).
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Revoke a peer so future connections from it are refused.
+    #[command(alias = "block")]
+    Revoke {
+        /// Burrow ID to revoke.
+        burrow_id: String,
+
+        /// Path to the trust cache (default: `<storage>/trust.tsv`).
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Quarantine a peer so its sessions are limited or refused, as
+    /// `trust.quarantine` says.
+    Quarantine {
+        /// Burrow ID to quarantine.
+        burrow_id: String,
+
+        /// Why, recorded in the trust cache.
+        #[arg(long, default_value = "quarantined by operator")]
+        reason: String,
+
+        /// Path to the trust cache (default: `<storage>/trust.tsv`).
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Write a trust bundle of every known peer and manifest, signed
+    /// with this burrow's identity.
+    Export {
+        /// Where to write the bundle (default: stdout).
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Path to the trust cache (default: `<storage>/trust.tsv`).
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Merge a trust bundle exported by another burrow.
+    Import {
+        /// The bundle file.
+        bundle: PathBuf,
+
+        /// Burrow ID that must have signed the bundle.
+        #[arg(long)]
+        signer: String,
+
+        /// Path to the trust cache (default: `<storage>/trust.tsv`).
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_timer(tracing_subscriber::fmt::time::uptime())
-        .init();
-
     let cli = Cli::parse();
+    let _log = init_logging(&cli);
 
+    if let Err(e) = run(cli).await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    // Keygen and cert work without any configuration.
     match cli.command {
-        Commands::Browse { addr, selector } => {
-            if let Err(e) = cmd_browse(&addr, &selector).await {
-                error!("{}", e);
-                std::process::exit(1);
-            }
+        Commands::Keygen {
+            output,
+            force,
+            rotate,
+        } => return cmd_keygen(&output, force, rotate),
+        Commands::Cert { dir, force } => return cmd_cert(&dir, force),
+        _ => {}
+    }
+
+    let loaded = load_config(&cli.config, &cli.set)?;
+    let identity_path = cli
+        .identity
+        .clone()
+        .or_else(|| Some(loaded.storage_path("identity.key")).filter(|p| p.exists()));
+    let identity = identity_path.as_deref();
+
+    match cli.command {
+        Commands::Keygen { .. } | Commands::Cert { .. } => unreachable!("handled above"),
+        Commands::Config { command } => match command {
+            ConfigCommands::Check => cmd_config_check(&loaded),
+            ConfigCommands::PrintEffective => cmd_config_print(&loaded),
+        },
+        Commands::Serve {
+            daemonize: true, ..
+        } => {
+            let pid = spawn_background()?;
+            info!(pid, "rabbit serving in the background");
+            Ok(())
         }
-        Commands::Fetch { addr, selector } => {
-            if let Err(e) = cmd_fetch(&addr, &selector).await {
-                error!("{}", e);
-                std::process::exit(1);
-            }
+        Commands::Serve { port, pid_file, .. } => {
+            cmd_serve(loaded, port, pid_file, || {
+                load_config(&cli.config, &cli.set)
+            })
+            .await
         }
-        Commands::Sub { addr, topic, since } => {
-            if let Err(e) = cmd_sub(&addr, &topic, since).await {
-                error!("{}", e);
-                std::process::exit(1);
+        Commands::Connect { addr } => cmd_connect(&addr, identity).await,
+        Commands::Browse { addr, selector } => cmd_browse(&addr, &selector, identity).await,
+        Commands::List { addr, selector } => cmd_list(&addr, &selector, identity).await,
+        Commands::Fetch { addr, selector } => cmd_fetch(&addr, &selector, identity).await,
+        Commands::Publish {
+            addr,
+            topic,
+            message,
+        } => cmd_publish(&addr, &topic, message, identity).await,
+        Commands::Subscribe { addr, topic, since } => cmd_sub(&addr, &topic, since, identity).await,
+        Commands::Trust { command } => match command {
+            TrustCommands::List { file } => {
+                cmd_trust_list(&file.unwrap_or_else(|| loaded.storage_path("trust.tsv")))
             }
+            TrustCommands::Revoke { burrow_id, file } => cmd_trust_revoke(
+                &file.unwrap_or_else(|| loaded.storage_path("trust.tsv")),
+                &burrow_id,
+            ),
+            TrustCommands::Quarantine {
+                burrow_id,
+                reason,
+                file,
+            } => cmd_trust_quarantine(
+                &file.unwrap_or_else(|| loaded.storage_path("trust.tsv")),
+                &burrow_id,
+                &reason,
+            ),
+            TrustCommands::Export { output, file } => cmd_trust_export(
+                &file.unwrap_or_else(|| loaded.storage_path("trust.tsv")),
+                &loaded.storage_path("manifests"),
+                output.as_deref(),
+                identity.ok_or("exporting a trust bundle needs --identity or a stored identity")?,
+            ),
+            TrustCommands::Import {
+                bundle,
+                signer,
+                file,
+            } => cmd_trust_import(
+                &file.unwrap_or_else(|| loaded.storage_path("trust.tsv")),
+                &loaded.storage_path("manifests"),
+                &bundle,
+                &signer,
+            ),
+        },
+        Commands::Grant {
+            addr,
+            capability,
+            target,
+            ttl,
+            scope,
+        } => cmd_grant(&addr, &capability, &target, ttl, scope.as_deref(), identity).await,
+        Commands::Group {
+            addr,
+            op,
+            group,
+            member,
+        } => cmd_group(&addr, &op, &group, &member, identity).await,
+    }
+}
+
+// ── Configuration ──────────────────────────────────────────────
+
+/// Install the log subscriber from the effective config's
+/// `[logging]` section.  If the config does not load, the defaults
+/// are used and `run` reports the problem.
+fn init_logging(cli: &Cli) -> LogGuard {
+    let (config, base_dir) = match cli.command {
+        Commands::Keygen { .. } | Commands::Cert { .. } => None,
+        _ => load_config(&cli.config, &cli.set).ok(),
+    }
+    .map(|loaded| (loaded.config.logging, loaded.base_dir))
+    .unwrap_or_else(|| (LoggingConfig::default(), PathBuf::from(".")));
+    match logging::init(&config, &base_dir) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("rabbit: {}", e);
+            std::process::exit(1);
         }
     }
 }
 
+/// Config file used when no `--config` is given.
+const DEFAULT_CONFIG: &str = "config.toml";
+
+/// The effective configuration and where it came from.
+struct LoadedConfig {
+    config: Config,
+    /// Files merged to produce `config`, in order.
+    sources: Vec<PathBuf>,
+    /// Directory that relative paths in the config resolve against
+    /// (the first config file's directory).
+    base_dir: PathBuf,
+}
+
+impl LoadedConfig {
+    /// Resolve a file inside the configured storage directory.
+    fn storage_path(&self, name: &str) -> PathBuf {
+        self.base_dir.join(&self.config.identity.storage).join(name)
+    }
+}
+
+/// Merge the `--config` layers and `--set` overrides, then validate.
+fn load_config(
+    paths: &[PathBuf],
+    overrides: &[String],
+) -> Result<LoadedConfig, Box<dyn std::error::Error>> {
+    let mut sources = paths.to_vec();
+    if sources.is_empty() && Path::new(DEFAULT_CONFIG).exists() {
+        sources.push(PathBuf::from(DEFAULT_CONFIG));
+    }
+    let config = Config::load_layered(&sources, overrides)?;
+    config.validate()?;
+    let base_dir = sources
+        .first()
+        .and_then(|p| p.parent())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    Ok(LoadedConfig {
+        config,
+        sources,
+        base_dir,
+    })
+}
+
+fn cmd_config_check(loaded: &LoadedConfig) -> Result<(), Box<dyn std::error::Error>> {
+    if loaded.sources.is_empty() {
+        println!("config OK (built-in defaults)");
+    } else {
+        let names: Vec<String> = loaded
+            .sources
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect();
+        println!("config OK ({})", names.join(", "));
+    }
+    Ok(())
+}
+
+fn cmd_config_print(loaded: &LoadedConfig) -> Result<(), Box<dyn std::error::Error>> {
+    print!("{}", loaded.config.to_toml()?);
+    Ok(())
+}
+
 // ── Connection helpers ─────────────────────────────────────────
 
+/// Load the identity from `path`, or generate an ephemeral one.
+fn load_identity(path: Option<&Path>) -> Result<Identity, Box<dyn std::error::Error>> {
+    match path {
+        Some(p) => Ok(Identity::from_file(p)?),
+        None => Ok(Identity::generate()),
+    }
+}
+
 /// Connect to a burrow and run the Rabbit handshake.
 ///
-/// Returns the tunnel and the remote burrow's ID.  The rabbit
-/// generates an ephemeral identity for each session — it's a full
-/// peer, just one that lives for one conversation.
+/// Returns the tunnel and the remote burrow's ID.  Unless an identity
+/// file is given, the rabbit generates an ephemeral identity for each
+/// session — it's a full peer, just one that lives for one
+/// conversation.
 async fn open_tunnel(
     addr: &str,
-) -> Result<
-    (
-        rabbit_engine::transport::tls::TlsTunnel<
-            tokio_rustls::client::TlsStream<tokio::net::TcpStream>,
-        >,
-        String,
-        Identity,
-    ),
-    Box<dyn std::error::Error>,
-> {
-    let identity = Identity::generate();
+    identity_path: Option<&Path>,
+) -> Result<(ClientTunnel, String, Identity), Box<dyn std::error::Error>> {
+    let identity = load_identity(identity_path)?;
     let client_config = make_client_config_insecure();
-    let mut tunnel = connect(addr, client_config, "localhost").await?;
+    // A bare domain names a warren: dial its SRV targets in order, and
+    // expect the burrow its TXT record names.
+    let (mut tunnel, expected_id) = if is_domain(addr) {
+        let warren = Resolver::system()?.lookup_warren(addr).await?;
+        let mut dialed = None;
+        for target in &warren.targets {
+            match connect(&target.address(), client_config.clone(), "localhost").await {
+                Ok(tunnel) => {
+                    info!(domain = %addr, target = %target.address(), "dialing warren");
+                    dialed = Some(tunnel);
+                    break;
+                }
+                Err(e) => warn!(target = %target.address(), err = %e, "warren target failed"),
+            }
+        }
+        let tunnel = dialed.ok_or_else(|| format!("no burrow of {} is reachable", addr))?;
+        (tunnel, warren.burrow_id)
+    } else {
+        (connect(addr, client_config, "localhost").await?, None)
+    };
 
     // Run the client-side handshake.
     let hello = build_hello(&identity);
@@ -158,15 +597,229 @@ async fn open_tunnel(
         .into());
     };
 
+    if let Some(expected) = expected_id {
+        if server_id != expected {
+            return Err(format!(
+                "{} published {} but the burrow is {}",
+                addr, expected, server_id
+            )
+            .into());
+        }
+    }
     debug!(remote_id = %server_id, "handshake complete");
     Ok((tunnel, server_id, identity))
 }
 
+// ── Keygen / cert ──────────────────────────────────────────────
+
+fn cmd_keygen(output: &Path, force: bool, rotate: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if rotate {
+        return cmd_rotate_key(output);
+    }
+    if output.exists() && !force {
+        return Err(format!(
+            "{} already exists — use --force to overwrite",
+            output.display()
+        )
+        .into());
+    }
+    let identity = Identity::generate();
+    save_identity(&identity, output)?;
+    println!("Saved identity to {}", output.display());
+    println!("ID:          {}", identity.burrow_id());
+    println!("Fingerprint: {}", fingerprint(&identity.public_key_bytes()));
+    Ok(())
+}
+
+/// Replace the key at `path`, keeping the old one and the signed
+/// rotation record beside it.
+fn cmd_rotate_key(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let old = Identity::from_file(path)?;
+    let (new, rotation) = old.rotate();
+    let mut old_path = path.as_os_str().to_owned();
+    old_path.push(".old");
+    let old_path = PathBuf::from(old_path);
+    let rotation_path = path.with_file_name("rotation.frame");
+
+    save_identity(&old, &old_path)?;
+    rotation.save(&rotation_path)?;
+    save_identity(&new, path)?;
+    println!("Rotated {}", path.display());
+    println!(
+        "Old ID:      {} (kept in {})",
+        rotation.old_id,
+        old_path.display()
+    );
+    println!("New ID:      {}", rotation.new_id);
+    println!("Fingerprint: {}", fingerprint(&new.public_key_bytes()));
+    println!("Record:      {}", rotation_path.display());
+    Ok(())
+}
+
+/// Save a key, encrypted if `RABBIT_KEY_PASSPHRASE` is set.
+fn save_identity(identity: &Identity, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    match std::env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty()) {
+        Some(passphrase) => identity.save_encrypted(path, &passphrase)?,
+        None => identity.save(path)?,
+    }
+    Ok(())
+}
+
+fn cmd_cert(dir: &Path, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+    if (cert_path.exists() || key_path.exists()) && !force {
+        return Err(format!(
+            "certificate already exists in {} — use --force to overwrite",
+            dir.display()
+        )
+        .into());
+    }
+    let pair = generate_self_signed()?;
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&cert_path, &pair.cert_pem)?;
+    std::fs::write(&key_path, &pair.key_pem)?;
+    println!("Wrote {}", cert_path.display());
+    println!("Wrote {}", key_path.display());
+    Ok(())
+}
+
+// ── Serve ──────────────────────────────────────────────────────
+
+async fn cmd_serve(
+    loaded: LoadedConfig,
+    port: Option<u16>,
+    pid_file: Option<PathBuf>,
+    reload_config: impl Fn() -> Result<LoadedConfig, Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut signals = ServiceSignals::new()?;
+    let _pid_file = pid_file.map(PidFile::create).transpose()?;
+
+    let LoadedConfig {
+        mut config,
+        mut base_dir,
+        ..
+    } = loaded;
+    if let Some(p) = port {
+        config.network.port = p;
+    }
+
+    // The listeners of `network.bind` and `network.listeners`, within
+    // their limits, and the janitor, as `burrow serve` starts them.
+    let mut listening = BurrowBuilder::new(config.clone(), &base_dir).start().await?;
+    info!(
+        name = %listening.burrow.name,
+        id = %listening.burrow.burrow_id(),
+        addrs = ?listening.local_addrs(),
+        "serving"
+    );
+
+    let (current_burrow, admin_burrow) =
+        tokio::sync::watch::channel(Arc::clone(&listening.burrow));
+    let admin_task = match &config.admin.socket {
+        Some(socket) => {
+            let server = AdminServer::bind(base_dir.join(socket)).await?;
+            Some(tokio::spawn(server.run(admin_burrow)))
+        }
+        None => None,
+    };
+
+    loop {
+        match signals.recv().await {
+            ServiceSignal::Shutdown => break,
+            ServiceSignal::Reload => {
+                info!("reloading config");
+                let mut next = match reload_config() {
+                    Ok(next) => next,
+                    Err(e) => {
+                        warn!(err = %e, "reload failed, keeping current config");
+                        continue;
+                    }
+                };
+                if let Some(p) = port {
+                    next.config.network.port = p;
+                }
+                // Only one burrow may hold the storage directory, so
+                // open tunnels are drained as at shutdown and peers
+                // reconnect to the new burrow.  The admin socket only
+                // changes on restart.
+                let burrow = Arc::clone(&listening.burrow);
+                if let Err(e) = listening.shutdown().await {
+                    warn!(err = %e, "failed to save state before reload");
+                }
+                if let Err(e) = burrow.wait_closed().await {
+                    warn!(err = %e, "failed to flush events before reload");
+                }
+                listening = match BurrowBuilder::new(next.config.clone(), &next.base_dir)
+                    .start()
+                    .await
+                {
+                    Ok(started) => {
+                        (config, base_dir) = (next.config, next.base_dir);
+                        started
+                    }
+                    Err(e) => {
+                        warn!(err = %e, "reload failed, restoring previous config");
+                        BurrowBuilder::new(config.clone(), &base_dir).start().await?
+                    }
+                };
+                current_burrow.send_replace(Arc::clone(&listening.burrow));
+                info!(name = %listening.burrow.name, "config reloaded");
+            }
+        }
+    }
+
+    if let Some(task) = admin_task {
+        task.abort();
+        let _ = task.await;
+    }
+    if let Err(e) = listening.shutdown().await {
+        warn!(err = %e, "failed to save state");
+    }
+    Ok(())
+}
+
+// ── Connect ────────────────────────────────────────────────────
+
+async fn cmd_connect(
+    addr: &str,
+    identity: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tunnel, server_id, identity) = open_tunnel(addr, identity).await?;
+
+    let mut ping = Frame::new("PING");
+    ping.set_header("Lane", "0");
+    let started = std::time::Instant::now();
+    tunnel.send_frame(&ping).await?;
+    let pong = tunnel
+        .recv_frame()
+        .await?
+        .ok_or("tunnel closed during PING")?;
+    let rtt = started.elapsed();
+
+    println!("Connected to {}", addr);
+    println!("Remote ID:   {}", server_id);
+    println!("Local ID:    {}", identity.burrow_id());
+    println!(
+        "Ping:        {} {} ({} ms)",
+        pong.verb,
+        pong.args.join(" "),
+        rtt.as_millis()
+    );
+
+    let _ = tunnel.close().await;
+    Ok(())
+}
+
 // ── Browse ─────────────────────────────────────────────────────
 
 /// Interactive browse session.
-async fn cmd_browse(addr: &str, start_selector: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let (mut tunnel, server_id, _identity) = open_tunnel(addr).await?;
+async fn cmd_browse(
+    addr: &str,
+    start_selector: &str,
+    identity: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tunnel, server_id, _identity) = open_tunnel(addr, identity).await?;
 
     println!();
     println!("  \u{1F407} Connected to {}", short_id(&server_id));
@@ -434,7 +1087,7 @@ async fn subscribe_and_stream<T: Tunnel>(
         };
 
         if frame.verb == "EVENT" || frame.verb == "210" {
-            let seq = frame.header("Seq").unwrap_or("?");
+            let seq = frame.header("Event-Seq").unwrap_or("?");
             let ts = frame.header("Timestamp").unwrap_or("");
             let body = frame.body.as_deref().unwrap_or("");
             println!("  [{}] {} {}", seq, ts, body.trim());
@@ -446,10 +1099,44 @@ async fn subscribe_and_stream<T: Tunnel>(
     Ok(())
 }
 
+// ── List (one-shot) ────────────────────────────────────────────
+
+async fn cmd_list(
+    addr: &str,
+    selector: &str,
+    identity: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tunnel, server_id, _identity) = open_tunnel(addr, identity).await?;
+    info!(remote = %short_id(&server_id), "connected");
+
+    let list = Frame::with_args("LIST", vec![selector.to_string()]);
+    tunnel.send_frame(&list).await?;
+
+    let response = tunnel
+        .recv_frame()
+        .await?
+        .ok_or("tunnel closed during LIST")?;
+    let _ = tunnel.close().await;
+
+    if !response.verb.starts_with("200") {
+        return Err(format!("{} {}", response.verb, response.args.join(" ")).into());
+    }
+
+    let items = parse_rabbitmap(response.body.as_deref().unwrap_or(""));
+    for item in &items {
+        println!("{}\t{}\t{}", item.type_code, item.label, item.selector);
+    }
+    Ok(())
+}
+
 // ── Fetch (one-shot) ───────────────────────────────────────────
 
-async fn cmd_fetch(addr: &str, selector: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let (mut tunnel, server_id, _identity) = open_tunnel(addr).await?;
+async fn cmd_fetch(
+    addr: &str,
+    selector: &str,
+    identity: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut tunnel, server_id, _identity) = open_tunnel(addr, identity).await?;
     info!(remote = %short_id(&server_id), "connected");
 
     let fetch = Frame::with_args("FETCH", vec![selector.to_string()]);
@@ -461,7 +1148,7 @@ async fn cmd_fetch(addr: &str, selector: &str) -> Result<(), Box<dyn std::error:
         .ok_or("tunnel closed during FETCH")?;
 
     if !response.verb.starts_with("200") {
-        eprintln!("error: {} {}", response.verb, response.args.join(" "));
+        error!("{} {}", response.verb, response.args.join(" "));
         std::process::exit(1);
     }
 
@@ -473,14 +1160,59 @@ async fn cmd_fetch(addr: &str, selector: &str) -> Result<(), Box<dyn std::error:
     Ok(())
 }
 
+// ── Publish (one-shot) ─────────────────────────────────────────
+
+async fn cmd_publish(
+    addr: &str,
+    topic: &str,
+    message: Option<String>,
+    identity: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let body = match message {
+        Some(m) => m,
+        None => {
+            let mut buf = String::new();
+            io::stdin().read_to_string(&mut buf)?;
+            buf
+        }
+    };
+
+    let (mut tunnel, server_id, _identity) = open_tunnel(addr, identity).await?;
+    info!(remote = %short_id(&server_id), "connected");
+
+    let mut publish = Frame::with_args("PUBLISH", vec![topic.to_string()]);
+    publish.set_header("Lane", "0");
+    publish.set_body(body);
+    tunnel.send_frame(&publish).await?;
+
+    let response = tunnel
+        .recv_frame()
+        .await?
+        .ok_or("tunnel closed during PUBLISH")?;
+    let _ = tunnel.close().await;
+
+    if !response.verb.starts_with("204") && !response.verb.starts_with("200") {
+        return Err(format!(
+            "{} {}: {}",
+            response.verb,
+            response.args.join(" "),
+            response.body.as_deref().unwrap_or("")
+        )
+        .into());
+    }
+    info!(%topic, "published");
+    Ok(())
+}
+
 // ── Subscribe (streaming) ──────────────────────────────────────
 
 async fn cmd_sub(
     addr: &str,
     topic: &str,
     since: Option<u64>,
+    identity: Option<&Path>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let (mut tunnel, server_id, _identity) = open_tunnel(addr).await?;
+    let (mut tunnel, server_id, _identity) = open_tunnel(addr, identity).await?;
     info!(remote = %short_id(&server_id), "connected");
 
     let mut sub = Frame::with_args("SUBSCRIBE", vec![topic.to_string()]);
@@ -496,27 +1228,27 @@ async fn cmd_sub(
         .ok_or("tunnel closed during SUBSCRIBE")?;
 
     if !ack.verb.starts_with("201") && !ack.verb.starts_with("200") {
-        eprintln!("error: {} {}", ack.verb, ack.args.join(" "));
+        error!("{} {}", ack.verb, ack.args.join(" "));
         std::process::exit(1);
     }
 
-    eprintln!("subscribed to {} \u{2014} streaming events", topic);
+    info!(%topic, "subscribed, streaming events");
 
     loop {
         let frame = match tunnel.recv_frame().await {
             Ok(Some(f)) => f,
             Ok(None) => {
-                eprintln!("(stream ended)");
+                info!("stream ended");
                 break;
             }
             Err(e) => {
-                eprintln!("error: {}", e);
+                error!("{}", e);
                 break;
             }
         };
 
         if frame.verb == "EVENT" || frame.verb == "210" {
-            let seq = frame.header("Seq").unwrap_or("?");
+            let seq = frame.header("Event-Seq").unwrap_or("?");
             let body = frame.body.as_deref().unwrap_or("");
             println!("{}\t{}", seq, body.trim());
         }
@@ -526,6 +1258,191 @@ async fn cmd_sub(
     Ok(())
 }
 
+// ── Trust ──────────────────────────────────────────────────────
+
+fn cmd_trust_list(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let cache = TrustCache::load(file)?;
+    if cache.is_empty() {
+        println!("(no trusted peers in {})", file.display());
+        return Ok(());
+    }
+    for peer in cache.entries() {
+        println!(
+            "{}\t{}\t{}\t{}",
+            peer.status.label(),
+            peer.burrow_id,
+            peer.fingerprint,
+            peer.last_seen
+        );
+    }
+    Ok(())
+}
+
+fn cmd_trust_revoke(file: &Path, burrow_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cache = TrustCache::load(file)?;
+    cache.revoke(burrow_id);
+    cache.save(file)?;
+    println!("Revoked {}", burrow_id);
+    Ok(())
+}
+
+fn cmd_trust_quarantine(
+    file: &Path,
+    burrow_id: &str,
+    reason: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cache = TrustCache::load(file)?;
+    cache.quarantine(burrow_id, reason);
+    cache.save(file)?;
+    match cache.status(burrow_id) {
+        Some(PeerStatus::Quarantined { .. }) => println!("Quarantined {}", burrow_id),
+        _ => println!("{} is revoked; left as is", burrow_id),
+    }
+    Ok(())
+}
+
+fn cmd_trust_export(
+    file: &Path,
+    manifests: &Path,
+    output: Option<&Path>,
+    identity: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cache = TrustCache::load(file)?;
+    cache.load_manifests(manifests);
+    let bundle = cache.export_bundle(&Identity::from_file(identity)?);
+    match output {
+        Some(path) => {
+            std::fs::write(path, bundle)?;
+            eprintln!(
+                "Exported {} peers and {} manifests to {}",
+                cache.len(),
+                cache.manifests().len(),
+                path.display()
+            );
+        }
+        None => print!("{}", bundle),
+    }
+    Ok(())
+}
+
+fn cmd_trust_import(
+    file: &Path,
+    manifests: &Path,
+    bundle: &Path,
+    signer: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cache = TrustCache::load(file)?;
+    cache.load_manifests(manifests);
+    let import = cache.import_bundle(&std::fs::read_to_string(bundle)?, signer)?;
+    cache.save(file)?;
+    cache.save_manifests(manifests)?;
+    println!(
+        "Imported {} new peers, updated {}, kept {} manifests",
+        import.added, import.updated, import.manifests
+    );
+    for burrow_id in &import.conflicts {
+        println!("conflict\t{}\t(kept the local fingerprint)", burrow_id);
+    }
+    Ok(())
+}
+
+// ── Grant ──────────────────────────────────────────────────────
+
+async fn cmd_grant(
+    addr: &str,
+    capability: &str,
+    target: &str,
+    ttl: u64,
+    scope: Option<&str>,
+    identity: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cap = Capability::from_label(capability)
+        .ok_or_else(|| format!("unknown capability: {}", capability))?;
+
+    let (mut tunnel, server_id, _identity) = open_tunnel(addr, identity).await?;
+    info!(remote = %short_id(&server_id), "connected");
+
+    let mut delegate = Frame::with_args(
+        "DELEGATE",
+        vec![cap.label().to_string(), target.to_string()],
+    );
+    delegate.set_header("Lane", "0");
+    delegate.set_header("TTL", ttl.to_string());
+    if let Some(scope) = scope {
+        delegate.set_header("Scope", scope);
+    }
+    stamp_frame(&mut delegate);
+    tunnel.send_frame(&delegate).await?;
+
+    let response = tunnel
+        .recv_frame()
+        .await?
+        .ok_or("tunnel closed during DELEGATE")?;
+    let _ = tunnel.close().await;
+
+    if !response.verb.starts_with("200") {
+        return Err(format!(
+            "{} {}: {}",
+            response.verb,
+            response.args.join(" "),
+            response.body.as_deref().unwrap_or("")
+        )
+        .into());
+    }
+    match scope {
+        Some(scope) => println!(
+            "Granted {} on {} to {} for {}s",
+            cap.label(),
+            scope,
+            target,
+            ttl
+        ),
+        None => println!("Granted {} to {} for {}s", cap.label(), target, ttl),
+    }
+    Ok(())
+}
+
+async fn cmd_group(
+    addr: &str,
+    op: &str,
+    group: &str,
+    member: &str,
+    identity: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let op = GroupOp::parse(op).ok_or_else(|| format!("expected add or remove, not {}", op))?;
+
+    let (mut tunnel, server_id, identity) = open_tunnel(addr, identity).await?;
+    info!(remote = %short_id(&server_id), "connected");
+
+    let mut frame = GroupChange::sign(&identity, op, group, member).to_frame();
+    frame.set_header("Lane", "0");
+    tunnel.send_frame(&frame).await?;
+
+    let response = tunnel
+        .recv_frame()
+        .await?
+        .ok_or("tunnel closed during GROUP")?;
+    let _ = tunnel.close().await;
+
+    if !response.verb.starts_with("200") {
+        return Err(format!(
+            "{} {}: {}",
+            response.verb,
+            response.args.join(" "),
+            response.body.as_deref().unwrap_or("")
+        )
+        .into());
+    }
+    let changed = response.header("Changed") == Some("true");
+    match (op, changed) {
+        (GroupOp::Add, true) => println!("Added {} to {}", member, group),
+        (GroupOp::Remove, true) => println!("Removed {} from {}", member, group),
+        (GroupOp::Add, false) => println!("{} is already in {}", member, group),
+        (GroupOp::Remove, false) => println!("{} is not in {}", member, group),
+    }
+    Ok(())
+}
+
 // ── Menu rendering ─────────────────────────────────────────────
 
 /// Parse a rabbitmap body into menu items.
@@ -710,4 +1627,159 @@ mod tests {
     fn short_id_non_ed25519() {
         assert_eq!(short_id("anonymous"), "anonymous");
     }
+
+    #[test]
+    fn cli_parses_subscribe_with_alias() {
+        let cli =
+            Cli::try_parse_from(["rabbit", "sub", "127.0.0.1:7443", "/q/chat", "--since", "5"])
+                .unwrap();
+        match cli.command {
+            Commands::Subscribe { topic, since, .. } => {
+                assert_eq!(topic, "/q/chat");
+                assert_eq!(since, Some(5));
+            }
+            _ => panic!("expected subscribe"),
+        }
+    }
+
+    #[test]
+    fn cli_parses_global_identity() {
+        let cli = Cli::try_parse_from([
+            "rabbit",
+            "grant",
+            "127.0.0.1:7443",
+            "Publish",
+            "ed25519:XYZ",
+            "--identity",
+            "me.key",
+            "--scope",
+            "/q/dialogue/*",
+        ])
+        .unwrap();
+        assert_eq!(cli.identity.as_deref(), Some(Path::new("me.key")));
+        match cli.command {
+            Commands::Grant {
+                capability,
+                ttl,
+                scope,
+                ..
+            } => {
+                assert_eq!(capability, "Publish");
+                assert_eq!(ttl, 3600);
+                assert_eq!(scope.as_deref(), Some("/q/dialogue/*"));
+            }
+            _ => panic!("expected grant"),
+        }
+    }
+
+    #[test]
+    fn cli_parses_group() {
+        let cli = Cli::try_parse_from([
+            "rabbit",
+            "group",
+            "127.0.0.1:7443",
+            "add",
+            "oak-family",
+            "group:oak-kids",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Group {
+                op, group, member, ..
+            } => {
+                assert_eq!(op, "add");
+                assert_eq!(group, "oak-family");
+                assert_eq!(member, "group:oak-kids");
+            }
+            _ => panic!("expected group"),
+        }
+    }
+
+    #[test]
+    fn cli_parses_trust_block() {
+        let cli = Cli::try_parse_from(["rabbit", "trust", "block", "ed25519:BAD"]).unwrap();
+        match cli.command {
+            Commands::Trust {
+                command: TrustCommands::Revoke { burrow_id, file },
+            } => {
+                assert_eq!(burrow_id, "ed25519:BAD");
+                assert!(file.is_none());
+            }
+            _ => panic!("expected trust revoke"),
+        }
+    }
+
+    #[test]
+    fn cli_parses_trust_import() {
+        let cli = Cli::try_parse_from([
+            "rabbit",
+            "trust",
+            "import",
+            "family.bundle",
+            "--signer",
+            "ed25519:HUB",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Trust {
+                command:
+                    TrustCommands::Import {
+                        bundle,
+                        signer,
+                        file,
+                    },
+            } => {
+                assert_eq!(bundle, PathBuf::from("family.bundle"));
+                assert_eq!(signer, "ed25519:HUB");
+                assert!(file.is_none());
+            }
+            _ => panic!("expected trust import"),
+        }
+    }
+
+    #[test]
+    fn cli_parses_layered_config() {
+        let cli = Cli::try_parse_from([
+            "rabbit",
+            "-c",
+            "base.toml",
+            "--config",
+            "local.toml",
+            "config",
+            "print-effective",
+            "--set",
+            "network.port=9000",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.config,
+            vec![PathBuf::from("base.toml"), PathBuf::from("local.toml")]
+        );
+        assert_eq!(cli.set, vec!["network.port=9000"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Config {
+                command: ConfigCommands::PrintEffective
+            }
+        ));
+    }
+
+    #[test]
+    fn load_config_resolves_storage_against_first_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[identity]\nstorage = \"state\"\n").unwrap();
+        let loaded = load_config(&[path], &["identity.name=x".to_string()]).unwrap();
+        assert_eq!(loaded.config.identity.name, "x");
+        assert_eq!(
+            loaded.storage_path("trust.tsv"),
+            dir.path().join("state").join("trust.tsv")
+        );
+    }
+
+    #[test]
+    fn load_config_rejects_invalid() {
+        let err = load_config(&[], &["identity.name=".to_string()]);
+        assert!(err.is_err());
+    }
 }
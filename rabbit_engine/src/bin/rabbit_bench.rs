@@ -0,0 +1,1035 @@
+//! `rabbit-bench` — load and soak testing against a burrow.
+//!
+//! # Usage
+//!
+//! ```text
+//! rabbit-bench --addr 127.0.0.1:7443 -p 4 -r 50 -t 4 -s 8 -d 60
+//! rabbit-bench --addr 127.0.0.1:7443 --duration 6h --pid 4242 -o soak.json
+//! rabbit-bench --config config.toml --replay --subscriber-delay 10
+//! rabbit-bench -d 30 -o new.json --baseline old.json --tolerance 10
+//! rabbit-bench --loopback --coalesce-ms 0 -s 32 -r 500 -o direct.json
+//! rabbit-bench --loopback --coalesce-ms 2 -s 32 -r 500 --baseline direct.json
+//! ```
+//!
+//! `--publishers` tunnels each publish `--rate` events per second,
+//! round-robin over `--topics` topics.  `--subscribers` tunnels each
+//! subscribe to every topic, ACK what they receive and, with
+//! `--replay`, ask for the topics' history first.  Every event body
+//! carries its publish time, so latency is measured end to end:
+//! publisher → burrow → subscriber.
+//!
+//! Without `--addr` the target is a burrow in this process, built from
+//! `--config` or empty, reached over memory tunnels or, with
+//! `--loopback`, over TLS on 127.0.0.1.  `--coalesce-ms` sets how long
+//! that burrow lets frames wait to share a write; the two `--loopback`
+//! runs above measure what coalescing is worth for fan-out.  `--pid` samples
+//! a live burrow's resident memory; the in-process target samples this
+//! process.
+//!
+//! The report gives throughput, delivery rate, latency percentiles and
+//! memory growth, plus a sample every `--sample-secs`.  `--output`
+//! writes it as JSON; `--baseline` compares it with an earlier report
+//! and exits non-zero if any headline metric regressed by more than
+//! `--tolerance` percent.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::Parser;
+use serde_json::{json, Value};
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use rabbit_engine::burrow::Burrow;
+use rabbit_engine::config::{Config, LoggingConfig};
+use rabbit_engine::logging;
+use rabbit_engine::protocol::error::ProtocolError;
+use rabbit_engine::protocol::frame::Frame;
+use rabbit_engine::transport::cert::{generate_self_signed, make_server_config};
+use rabbit_engine::transport::connector::{connect, make_client_config_insecure};
+use rabbit_engine::transport::listener::RabbitListener;
+use rabbit_engine::transport::memory::{memory_tunnel_pair, MemoryTunnel};
+use rabbit_engine::transport::tls::ClientTunnel;
+use rabbit_engine::transport::tunnel::Tunnel;
+
+/// Version of the JSON report layout.
+const REPORT_VERSION: u64 = 1;
+
+/// Subscribers ACK after this many events, or on the next flush tick.
+const ACK_BATCH: u64 = 16;
+
+/// How often subscribers flush pending ACKs.
+const ACK_FLUSH: Duration = Duration::from_millis(100);
+
+/// Drive a publish/subscribe workload against a burrow and report
+/// latency, throughput and memory growth.
+#[derive(Parser)]
+#[command(name = "rabbit-bench", version, about)]
+struct Cli {
+    /// Live burrow to benchmark (default: one in this process).
+    #[arg(short, long, conflicts_with = "config")]
+    addr: Option<String>,
+
+    /// Config for the in-process burrow (default: an empty one).
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Serve the in-process burrow over TLS on loopback instead of
+    /// memory tunnels, so socket writes are part of the measurement.
+    #[arg(long, conflicts_with = "addr")]
+    loopback: bool,
+
+    /// Flush deadline for the in-process burrow's coalesced writes in
+    /// milliseconds, 0 for none (default: `network.write_coalesce_ms`).
+    #[arg(long, conflicts_with = "addr")]
+    coalesce_ms: Option<u64>,
+
+    /// Number of publishing tunnels.
+    #[arg(short, long, default_value_t = 2)]
+    publishers: usize,
+
+    /// Events per second per publisher.
+    #[arg(short, long, default_value_t = 10.0)]
+    rate: f64,
+
+    /// Number of topics published to.
+    #[arg(short, long, default_value_t = 1)]
+    topics: usize,
+
+    /// Number of subscribing tunnels, each subscribed to every topic.
+    #[arg(short, long, default_value_t = 2)]
+    subscribers: usize,
+
+    /// Subscribers replay each topic's history before going live.
+    #[arg(long)]
+    replay: bool,
+
+    /// Seconds to wait before subscribing.
+    #[arg(long, default_value_t = 0)]
+    subscriber_delay: u64,
+
+    /// How long to publish for: seconds, or with an s/m/h suffix.
+    #[arg(short, long, default_value = "30", value_parser = parse_duration)]
+    duration: Duration,
+
+    /// Seconds to keep receiving after publishing stops.
+    #[arg(long, default_value_t = 2)]
+    drain_secs: u64,
+
+    /// Event body size in bytes.
+    #[arg(long, default_value_t = 64)]
+    body_bytes: usize,
+
+    /// Seconds between report samples.
+    #[arg(long, default_value_t = 10)]
+    sample_secs: u64,
+
+    /// Process whose memory to sample (with `--addr`).
+    #[arg(long)]
+    pid: Option<u32>,
+
+    /// Write the JSON report to this file.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Print the JSON report instead of a summary.
+    #[arg(long)]
+    json: bool,
+
+    /// Earlier report to compare against.
+    #[arg(long)]
+    baseline: Option<PathBuf>,
+
+    /// Percent a metric may regress against the baseline.
+    #[arg(long, default_value_t = 10.0)]
+    tolerance: f64,
+}
+
+/// Parse `90`, `90s`, `15m` or `6h`.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (digits, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let n: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid duration {:?}", s))?;
+    match unit {
+        "s" => Ok(Duration::from_secs(n)),
+        "m" => Ok(Duration::from_secs(n * 60)),
+        "h" => Ok(Duration::from_secs(n * 3600)),
+        _ => Err(format!("invalid duration unit {:?} (use s, m or h)", unit)),
+    }
+}
+
+// ── Latency histogram ──────────────────────────────────────────
+
+/// Sub-buckets per power of two: values are kept to within ~6%.
+const SUB_BUCKETS: u64 = 16;
+
+/// Log-linear histogram of microsecond values.  Its size is fixed, so
+/// hours of samples do not show up as memory growth.
+#[derive(Clone)]
+struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            counts: vec![0; 61 * SUB_BUCKETS as usize],
+            total: 0,
+            max: 0,
+        }
+    }
+
+    fn index(v: u64) -> usize {
+        if v < SUB_BUCKETS {
+            return v as usize;
+        }
+        let exp = 63 - v.leading_zeros() as u64;
+        let sub = (v >> (exp - 4)) - SUB_BUCKETS;
+        ((exp - 3) * SUB_BUCKETS + sub) as usize
+    }
+
+    /// Midpoint of a bucket.
+    fn value(index: usize) -> u64 {
+        let i = index as u64;
+        if i < SUB_BUCKETS {
+            return i;
+        }
+        let shift = i / SUB_BUCKETS - 1;
+        let lower = (SUB_BUCKETS + i % SUB_BUCKETS) << shift;
+        lower + (1 << shift) / 2
+    }
+
+    fn record(&mut self, micros: u64) {
+        self.counts[Self::index(micros)] += 1;
+        self.total += 1;
+        self.max = self.max.max(micros);
+    }
+
+    fn percentile(&self, p: f64) -> Option<u64> {
+        if self.total == 0 {
+            return None;
+        }
+        let target = ((p / 100.0) * self.total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, &n) in self.counts.iter().enumerate() {
+            seen += n;
+            if seen >= target {
+                return Some(Self::value(i).min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    fn summary(&self) -> Value {
+        let ms = |v: Option<u64>| v.map(|us| us as f64 / 1000.0);
+        json!({
+            "count": self.total,
+            "p50": ms(self.percentile(50.0)),
+            "p90": ms(self.percentile(90.0)),
+            "p99": ms(self.percentile(99.0)),
+            "p999": ms(self.percentile(99.9)),
+            "max": ms((self.total > 0).then_some(self.max)),
+        })
+    }
+}
+
+// ── Shared state ───────────────────────────────────────────────
+
+fn topic_name(topic: usize) -> String {
+    format!("/q/bench/{}", topic)
+}
+
+/// One subscriber's subscription to one topic.
+struct Subscription {
+    topic: usize,
+    /// Run-relative time (µs) the subscription was confirmed;
+    /// `u64::MAX` until then.  Events published from then on are
+    /// expected live.
+    confirmed_at: AtomicU64,
+    expected: AtomicU64,
+    delivered: AtomicU64,
+}
+
+struct Stats {
+    start: Instant,
+    /// Random tag in every body, telling this run's events from
+    /// history left by earlier runs.
+    run_tag: String,
+    published: AtomicU64,
+    rejected: AtomicU64,
+    publish_errors: AtomicU64,
+    delivered: AtomicU64,
+    /// Events received that were published before their subscription
+    /// was confirmed: replayed history, or events racing the 201.
+    /// They are not counted as deliveries.
+    backlog: AtomicU64,
+    /// Longest time (µs) from a SUBSCRIBE to its last backlog event.
+    backlog_catchup: AtomicU64,
+    disconnects: AtomicU64,
+    subscriptions: Vec<Subscription>,
+    latency: Mutex<Histogram>,
+    window: Mutex<Histogram>,
+}
+
+impl Stats {
+    fn micros(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+
+    /// A publish of `topic` made at `at` was accepted.
+    fn accepted(&self, topic: usize, at: u64) {
+        self.published.fetch_add(1, Ordering::Relaxed);
+        for s in self.subscriptions.iter().filter(|s| s.topic == topic) {
+            if at >= s.confirmed_at.load(Ordering::Relaxed) {
+                s.expected.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// ── Tunnels ────────────────────────────────────────────────────
+
+/// A tunnel to either kind of target.
+enum BenchTunnel {
+    Live(Box<ClientTunnel>),
+    Local(MemoryTunnel),
+}
+
+impl Tunnel for BenchTunnel {
+    async fn send_frame(&mut self, frame: &Frame) -> Result<(), ProtocolError> {
+        match self {
+            Self::Live(t) => t.send_frame(frame).await,
+            Self::Local(t) => t.send_frame(frame).await,
+        }
+    }
+
+    async fn recv_frame(&mut self) -> Result<Option<Frame>, ProtocolError> {
+        match self {
+            Self::Live(t) => t.recv_frame().await,
+            Self::Local(t) => t.recv_frame().await,
+        }
+    }
+
+    fn peer_id(&self) -> &str {
+        match self {
+            Self::Live(t) => t.peer_id(),
+            Self::Local(t) => t.peer_id(),
+        }
+    }
+
+    fn coalesce_writes(&mut self, flush_after: Duration) {
+        match self {
+            Self::Live(t) => t.coalesce_writes(flush_after),
+            Self::Local(t) => t.coalesce_writes(flush_after),
+        }
+    }
+
+    async fn close(&mut self) -> Result<(), ProtocolError> {
+        match self {
+            Self::Live(t) => t.close().await,
+            Self::Local(t) => t.close().await,
+        }
+    }
+}
+
+enum Target {
+    Live(String),
+    Local(Arc<Burrow>),
+}
+
+impl Target {
+    /// Open a tunnel and handshake as `client`.
+    async fn open(&self, client: &Burrow) -> Result<BenchTunnel, Box<dyn std::error::Error>> {
+        let mut tunnel = match self {
+            Self::Live(addr) => BenchTunnel::Live(Box::new(
+                connect(addr, make_client_config_insecure(), "localhost").await?,
+            )),
+            Self::Local(burrow) => {
+                let (tunnel, mut server_side) = memory_tunnel_pair(&client.name, &burrow.name);
+                let burrow = Arc::clone(burrow);
+                tokio::spawn(async move {
+                    let _ = burrow.handle_tunnel(&mut server_side).await;
+                });
+                BenchTunnel::Local(tunnel)
+            }
+        };
+        client.client_handshake(&mut tunnel).await?;
+        Ok(tunnel)
+    }
+}
+
+/// Resident memory of `pid` (or this process) in KiB, where
+/// `/proc` is available.
+fn rss_kib(pid: Option<u32>) -> Option<u64> {
+    let path = match pid {
+        Some(pid) => format!("/proc/{}/status", pid),
+        None => "/proc/self/status".to_string(),
+    };
+    let status = std::fs::read_to_string(path).ok()?;
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))
+        .and_then(|v| v.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+// ── Main ───────────────────────────────────────────────────────
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let log_config = LoggingConfig {
+        level: "warn".into(),
+        ..LoggingConfig::default()
+    };
+    let _log = match logging::init(&log_config, Path::new(".")) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("rabbit-bench: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = run(cli).await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    if cli.publishers == 0 || cli.topics == 0 || cli.rate <= 0.0 {
+        return Err("need at least one publisher, one topic and a positive rate".into());
+    }
+    let mut local = match (&cli.addr, &cli.config) {
+        (Some(_), _) => None,
+        (None, Some(path)) => {
+            let config = Config::load(path)?;
+            let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+            Some(Burrow::from_config(&config, base_dir)?)
+        }
+        (None, None) => Some(Burrow::in_memory("bench-target")),
+    };
+    if let (Some(burrow), Some(ms)) = (&mut local, cli.coalesce_ms) {
+        burrow.write_coalesce_ms = ms;
+    }
+    let (target, target_name) = match (local, &cli.addr) {
+        (Some(burrow), _) if cli.loopback => {
+            let burrow = Arc::new(burrow);
+            let cert = generate_self_signed()?;
+            let listener = RabbitListener::bind("127.0.0.1:0", make_server_config(&cert)?).await?;
+            let addr = listener.local_addr()?.to_string();
+            tokio::spawn(async move { burrow.serve(&listener).await });
+            (
+                Target::Live(addr),
+                "in-process over loopback TLS".to_string(),
+            )
+        }
+        (Some(burrow), _) => (Target::Local(Arc::new(burrow)), "in-process".to_string()),
+        (None, addr) => {
+            let addr = addr.clone().unwrap_or_default();
+            (Target::Live(addr.clone()), addr)
+        }
+    };
+    let target = Arc::new(target);
+    // A live target's memory is only known with --pid; the in-process
+    // target's is this process's.
+    let memory_pid = cli.addr.as_ref().and(cli.pid);
+    let sample_memory = cli.addr.is_none() || cli.pid.is_some();
+
+    let stats = Arc::new(Stats {
+        start: Instant::now(),
+        run_tag: format!("{:016x}", rand::random::<u64>()),
+        published: AtomicU64::new(0),
+        rejected: AtomicU64::new(0),
+        publish_errors: AtomicU64::new(0),
+        delivered: AtomicU64::new(0),
+        backlog: AtomicU64::new(0),
+        backlog_catchup: AtomicU64::new(0),
+        disconnects: AtomicU64::new(0),
+        subscriptions: (0..cli.subscribers * cli.topics)
+            .map(|i| Subscription {
+                topic: i % cli.topics,
+                confirmed_at: AtomicU64::new(u64::MAX),
+                expected: AtomicU64::new(0),
+                delivered: AtomicU64::new(0),
+            })
+            .collect(),
+        latency: Mutex::new(Histogram::new()),
+        window: Mutex::new(Histogram::new()),
+    });
+    let (stop_publishing, publishing) = watch::channel(false);
+    let (stop_receiving, receiving) = watch::channel(false);
+
+    let mut publishers = Vec::new();
+    for i in 0..cli.publishers {
+        let client = Burrow::in_memory(format!("bench-pub-{}", i));
+        let tunnel = target.open(&client).await?;
+        publishers.push(tokio::spawn(publish(
+            tunnel,
+            i,
+            cli.topics,
+            cli.rate,
+            cli.body_bytes,
+            Arc::clone(&stats),
+            publishing.clone(),
+        )));
+    }
+
+    let subscribers = {
+        let target = Arc::clone(&target);
+        let stats = Arc::clone(&stats);
+        let (count, topics, replay) = (cli.subscribers, cli.topics, cli.replay);
+        let delay = Duration::from_secs(cli.subscriber_delay);
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let mut tasks = Vec::new();
+            for i in 0..count {
+                let client = Burrow::in_memory(format!("bench-sub-{}", i));
+                match target.open(&client).await {
+                    Ok(tunnel) => tasks.push(tokio::spawn(subscribe(
+                        tunnel,
+                        i * topics,
+                        topics,
+                        replay,
+                        Arc::clone(&stats),
+                        receiving.clone(),
+                    ))),
+                    Err(e) => {
+                        warn!(subscriber = i, err = %e, "subscriber failed to connect");
+                        stats.disconnects.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            for task in tasks {
+                let _ = task.await;
+            }
+        })
+    };
+
+    let (stop_sampling, sampling) = watch::channel(false);
+    let sampler = tokio::spawn(sample(
+        Arc::clone(&stats),
+        Duration::from_secs(cli.sample_secs.max(1)),
+        memory_pid,
+        sample_memory,
+        sampling,
+    ));
+    let memory_start = sample_memory.then(|| rss_kib(memory_pid)).flatten();
+
+    info!(duration = ?cli.duration, "benchmark running");
+    let interrupted = tokio::select! {
+        _ = tokio::time::sleep(cli.duration) => false,
+        _ = tokio::signal::ctrl_c() => true,
+    };
+    let elapsed = stats.start.elapsed();
+    let _ = stop_publishing.send(true);
+    for task in publishers {
+        let _ = task.await;
+    }
+    if !interrupted {
+        tokio::time::sleep(Duration::from_secs(cli.drain_secs)).await;
+    }
+    let _ = stop_receiving.send(true);
+    let _ = subscribers.await;
+    let _ = stop_sampling.send(true);
+    let samples = sampler.await.unwrap_or_default();
+    let memory_end = sample_memory.then(|| rss_kib(memory_pid)).flatten();
+
+    let report = build_report(
+        &cli,
+        &target_name,
+        &stats,
+        elapsed,
+        interrupted,
+        samples,
+        memory_start,
+        memory_end,
+    );
+    if let Some(path) = &cli.output {
+        std::fs::write(path, serde_json::to_string_pretty(&report)? + "\n")
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        info!(path = %path.display(), "report written");
+    }
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+
+    if let Some(path) = &cli.baseline {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let baseline: Value =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let regressions = compare(&baseline, &report, cli.tolerance);
+        if regressions > 0 {
+            return Err(format!(
+                "{} metrics regressed by more than {}%",
+                regressions, cli.tolerance
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+// ── Workers ────────────────────────────────────────────────────
+
+/// Publish round-robin over the topics at `rate` events per second.
+async fn publish(
+    mut tunnel: BenchTunnel,
+    index: usize,
+    topics: usize,
+    rate: f64,
+    body_bytes: usize,
+    stats: Arc<Stats>,
+    mut stop: watch::Receiver<bool>,
+) {
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+    // Publish times in flight; replies on lane 0 arrive in order.
+    let mut outstanding = std::collections::VecDeque::new();
+    let mut next_topic = index % topics;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                let at = stats.micros();
+                let mut body = format!("bench {} {}", stats.run_tag, at);
+                if body.len() < body_bytes {
+                    body.push(' ');
+                    body.push_str(&".".repeat(body_bytes.saturating_sub(body.len())));
+                }
+                let mut frame = Frame::with_args("PUBLISH", vec![topic_name(next_topic)]);
+                frame.set_header("Lane", "0");
+                frame.set_body(body);
+                if tunnel.send_frame(&frame).await.is_err() {
+                    break;
+                }
+                outstanding.push_back((next_topic, at));
+                next_topic = (next_topic + 1) % topics;
+            }
+            received = tunnel.recv_frame() => {
+                let frame = match received {
+                    Ok(Some(frame)) => frame,
+                    _ => break,
+                };
+                let status = frame.verb.as_str();
+                if status == "PING" {
+                    let _ = tunnel.send_frame(&Frame::new("PONG")).await;
+                    continue;
+                }
+                let Some((topic, at)) = outstanding.pop_front() else {
+                    continue;
+                };
+                match status {
+                    "200" | "204" => stats.accepted(topic, at),
+                    "429" => {
+                        stats.rejected.fetch_add(1, Ordering::Relaxed);
+                    }
+                    _ => {
+                        stats.publish_errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            _ = stop.changed() => break,
+        }
+    }
+    if !*stop.borrow() {
+        warn!(publisher = index, "publisher tunnel closed early");
+        stats.disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+    // Collect the replies still owed, briefly.
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while !outstanding.is_empty() {
+        let Ok(Ok(Some(frame))) = tokio::time::timeout_at(deadline, tunnel.recv_frame()).await
+        else {
+            break;
+        };
+        if matches!(frame.verb.as_str(), "200" | "204") {
+            if let Some((topic, at)) = outstanding.pop_front() {
+                stats.accepted(topic, at);
+            }
+        } else if frame.verb != "PING" {
+            outstanding.pop_front();
+        }
+    }
+    let _ = tunnel.close().await;
+}
+
+/// Subscribe to every topic (lane `topic + 1`) and record deliveries.
+async fn subscribe(
+    mut tunnel: BenchTunnel,
+    first_subscription: usize,
+    topics: usize,
+    replay: bool,
+    stats: Arc<Stats>,
+    mut stop: watch::Receiver<bool>,
+) {
+    let subscribed_at = stats.micros();
+    for topic in 0..topics {
+        let mut sub = Frame::with_args("SUBSCRIBE", vec![topic_name(topic)]);
+        sub.set_header("Lane", (topic + 1).to_string());
+        if replay {
+            sub.set_header("Since", "0");
+        }
+        if tunnel.send_frame(&sub).await.is_err() {
+            stats.disconnects.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+
+    // Highest Seq seen and acknowledged per lane.
+    let mut seen = vec![0u64; topics];
+    let mut acked = vec![0u64; topics];
+    let mut flush = tokio::time::interval(ACK_FLUSH);
+    loop {
+        let frame = tokio::select! {
+            received = tunnel.recv_frame() => match received {
+                Ok(Some(frame)) => frame,
+                _ => {
+                    warn!("subscriber tunnel closed early");
+                    stats.disconnects.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            },
+            _ = flush.tick() => {
+                for lane in 0..topics {
+                    if seen[lane] > acked[lane] && send_ack(&mut tunnel, lane, seen[lane]).await {
+                        acked[lane] = seen[lane];
+                    }
+                }
+                continue;
+            }
+            _ = stop.changed() => break,
+        };
+        let lane = frame
+            .header("Lane")
+            .and_then(|l| l.parse::<usize>().ok())
+            .and_then(|l| l.checked_sub(1))
+            .filter(|&l| l < topics);
+        match (frame.verb.as_str(), lane) {
+            ("PING", _) => {
+                let _ = tunnel.send_frame(&Frame::new("PONG")).await;
+            }
+            ("201", Some(topic)) => {
+                stats.subscriptions[first_subscription + topic]
+                    .confirmed_at
+                    .store(stats.micros(), Ordering::Relaxed);
+            }
+            ("EVENT", Some(topic)) => {
+                let now = stats.micros();
+                let subscription = &stats.subscriptions[first_subscription + topic];
+                let mut fields = frame.body.as_deref().unwrap_or("").split(' ');
+                let published_at = match (fields.next(), fields.next(), fields.next()) {
+                    (Some("bench"), Some(tag), Some(at)) if tag == stats.run_tag => at.parse().ok(),
+                    _ => None,
+                };
+                match published_at {
+                    Some(at) if at >= subscription.confirmed_at.load(Ordering::Relaxed) => {
+                        let latency = now.saturating_sub(at);
+                        stats
+                            .latency
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .record(latency);
+                        stats
+                            .window
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .record(latency);
+                        subscription.delivered.fetch_add(1, Ordering::Relaxed);
+                        stats.delivered.fetch_add(1, Ordering::Relaxed);
+                    }
+                    _ => {
+                        stats.backlog.fetch_add(1, Ordering::Relaxed);
+                        stats
+                            .backlog_catchup
+                            .fetch_max(now - subscribed_at, Ordering::Relaxed);
+                    }
+                }
+
+                let seq: u64 = frame
+                    .header("Seq")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0);
+                seen[topic] = seen[topic].max(seq);
+                if seen[topic] >= acked[topic] + ACK_BATCH
+                    && send_ack(&mut tunnel, topic, seen[topic]).await
+                {
+                    acked[topic] = seen[topic];
+                }
+            }
+            _ => {}
+        }
+    }
+    let _ = tunnel.close().await;
+}
+
+/// Send a cumulative ACK for `lane_index`'s lane.
+async fn send_ack(tunnel: &mut BenchTunnel, lane_index: usize, seq: u64) -> bool {
+    let mut ack = Frame::new("ACK");
+    ack.set_header("Lane", (lane_index + 1).to_string());
+    ack.set_header("ACK", seq.to_string());
+    tunnel.send_frame(&ack).await.is_ok()
+}
+
+/// Take a sample every `interval` until stopped.
+async fn sample(
+    stats: Arc<Stats>,
+    interval: Duration,
+    pid: Option<u32>,
+    memory: bool,
+    mut stop: watch::Receiver<bool>,
+) -> Vec<Value> {
+    let mut samples = Vec::new();
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    let (mut last_published, mut last_delivered) = (0, 0);
+    let mut last_at = Instant::now();
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = stop.changed() => break,
+        }
+        let secs = last_at.elapsed().as_secs_f64();
+        last_at = Instant::now();
+        let published = stats.published.load(Ordering::Relaxed);
+        let delivered = stats.delivered.load(Ordering::Relaxed);
+        let window = std::mem::replace(
+            &mut *stats.window.lock().unwrap_or_else(|e| e.into_inner()),
+            Histogram::new(),
+        );
+        let latency = window.summary();
+        samples.push(json!({
+            "elapsed_secs": stats.start.elapsed().as_secs(),
+            "publish_per_sec": (published - last_published) as f64 / secs,
+            "deliver_per_sec": (delivered - last_delivered) as f64 / secs,
+            "latency_p50_ms": latency["p50"],
+            "latency_p99_ms": latency["p99"],
+            "rss_kib": if memory { rss_kib(pid) } else { None },
+        }));
+        last_published = published;
+        last_delivered = delivered;
+    }
+    samples
+}
+
+// ── Report ─────────────────────────────────────────────────────
+
+/// Least-squares slope of RSS over the samples, in KiB per hour.
+fn memory_growth(samples: &[Value]) -> Option<f64> {
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .filter_map(|s| Some((s["elapsed_secs"].as_f64()?, s["rss_kib"].as_f64()?)))
+        .collect();
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let var: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    if var == 0.0 {
+        return None;
+    }
+    let cov: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    Some(cov / var * 3600.0)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_report(
+    cli: &Cli,
+    target: &str,
+    stats: &Stats,
+    elapsed: Duration,
+    interrupted: bool,
+    samples: Vec<Value>,
+    memory_start: Option<u64>,
+    memory_end: Option<u64>,
+) -> Value {
+    let load = |a: &AtomicU64| a.load(Ordering::Relaxed);
+    let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+    let expected: u64 = stats.subscriptions.iter().map(|s| load(&s.expected)).sum();
+    let live: u64 = stats.subscriptions.iter().map(|s| load(&s.delivered)).sum();
+    let memory_max = samples
+        .iter()
+        .filter_map(|s| s["rss_kib"].as_u64())
+        .chain(memory_start)
+        .chain(memory_end)
+        .max();
+    let growth = memory_growth(&samples);
+    let latency = stats
+        .latency
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .summary();
+
+    json!({
+        "version": REPORT_VERSION,
+        "target": target,
+        "workload": {
+            "publishers": cli.publishers,
+            "rate": cli.rate,
+            "topics": cli.topics,
+            "subscribers": cli.subscribers,
+            "replay": cli.replay,
+            "body_bytes": cli.body_bytes,
+            "duration_secs": cli.duration.as_secs(),
+        },
+        "elapsed_secs": elapsed.as_secs_f64(),
+        "interrupted": interrupted,
+        "published": load(&stats.published),
+        "rejected": load(&stats.rejected),
+        "publish_errors": load(&stats.publish_errors),
+        "expected_deliveries": expected,
+        "deliveries": live,
+        "delivery_rate": if expected == 0 { 1.0 } else { live as f64 / expected as f64 },
+        "backlog": load(&stats.backlog),
+        "backlog_catchup_ms": load(&stats.backlog_catchup) as f64 / 1000.0,
+        "disconnects": load(&stats.disconnects),
+        "throughput": {
+            "publish_per_sec": load(&stats.published) as f64 / secs,
+            "deliver_per_sec": live as f64 / secs,
+        },
+        "latency_ms": latency,
+        "memory_kib": {
+            "start": memory_start,
+            "end": memory_end,
+            "max": memory_max,
+            "growth_per_hour": growth,
+        },
+        "samples": samples,
+    })
+}
+
+fn print_report(report: &Value) {
+    let num = |v: &Value| match v.as_f64() {
+        Some(f) => format!("{:.2}", f),
+        None => "-".to_string(),
+    };
+    let w = &report["workload"];
+    println!(
+        "{}: {} publishers × {}/s over {} topics, {} subscribers{}",
+        report["target"].as_str().unwrap_or(""),
+        w["publishers"],
+        w["rate"],
+        w["topics"],
+        w["subscribers"],
+        if w["replay"] == true { " (replay)" } else { "" }
+    );
+    println!(
+        "ran {}s{}",
+        num(&report["elapsed_secs"]),
+        if report["interrupted"] == true {
+            " (interrupted)"
+        } else {
+            ""
+        }
+    );
+    println!();
+    println!(
+        "Published:        {} ({} rejected, {} errors)",
+        report["published"], report["rejected"], report["publish_errors"]
+    );
+    println!(
+        "Delivered:        {} of {} ({:.2}%)",
+        report["deliveries"],
+        report["expected_deliveries"],
+        report["delivery_rate"].as_f64().unwrap_or(0.0) * 100.0
+    );
+    if report["backlog"].as_u64().unwrap_or(0) > 0 {
+        println!(
+            "Backlog:          {} (caught up in {} ms)",
+            report["backlog"],
+            num(&report["backlog_catchup_ms"])
+        );
+    }
+    let t = &report["throughput"];
+    println!(
+        "Throughput:       {} published/s, {} delivered/s",
+        num(&t["publish_per_sec"]),
+        num(&t["deliver_per_sec"])
+    );
+    let l = &report["latency_ms"];
+    println!(
+        "Latency (ms):     p50 {}  p90 {}  p99 {}  p99.9 {}  max {}",
+        num(&l["p50"]),
+        num(&l["p90"]),
+        num(&l["p99"]),
+        num(&l["p999"]),
+        num(&l["max"])
+    );
+    let m = &report["memory_kib"];
+    if !m["end"].is_null() {
+        println!(
+            "Memory (KiB):     start {}  end {}  max {}  growth {}/h",
+            m["start"],
+            m["end"],
+            m["max"],
+            num(&m["growth_per_hour"])
+        );
+    }
+    if report["disconnects"].as_u64().unwrap_or(0) > 0 {
+        println!("Disconnects:      {}", report["disconnects"]);
+    }
+}
+
+/// Metrics compared against a baseline: JSON pointer, and whether
+/// higher is better.
+const COMPARED: &[(&str, bool)] = &[
+    ("/delivery_rate", true),
+    ("/throughput/deliver_per_sec", true),
+    ("/latency_ms/p50", false),
+    ("/latency_ms/p99", false),
+    ("/memory_kib/max", false),
+];
+
+/// Print each compared metric and return how many regressed by more
+/// than `tolerance` percent.
+fn compare(baseline: &Value, report: &Value, tolerance: f64) -> usize {
+    if baseline["workload"] != report["workload"] {
+        warn!("baseline was run with a different workload");
+    }
+    println!();
+    println!(
+        "{:<30} {:>12} {:>12} {:>9}",
+        "metric", "baseline", "current", "change"
+    );
+    let mut regressions = 0;
+    for &(pointer, higher_is_better) in COMPARED {
+        let (Some(old), Some(new)) = (
+            baseline.pointer(pointer).and_then(Value::as_f64),
+            report.pointer(pointer).and_then(Value::as_f64),
+        ) else {
+            continue;
+        };
+        let change = if old == 0.0 {
+            0.0
+        } else {
+            (new - old) / old * 100.0
+        };
+        let worse = if higher_is_better { -change } else { change };
+        let flag = if worse > tolerance {
+            regressions += 1;
+            "  REGRESSED"
+        } else {
+            ""
+        };
+        println!(
+            "{:<30} {:>12.2} {:>12.2} {:>8.1}%{}",
+            &pointer[1..],
+            old,
+            new,
+            change,
+            flag
+        );
+    }
+    regressions
+}
@@ -0,0 +1,291 @@
+//! `rabbit-bridge` — connect an MQTT broker to a warren.
+//!
+//! # Usage
+//!
+//! ```text
+//! rabbit-bridge --config bridge.toml
+//! ```
+//!
+//! The config names the broker, the burrow, the bridge's identity
+//! file and the topic mappings (see [`rabbit_engine::bridge`]).  MQTT
+//! messages matching an inbound mapping are published to the mapped
+//! Rabbit topic; events on an outbound mapping's Rabbit topic are
+//! published to its MQTT topic.  On a `both` mapping the bridge drops
+//! the copy of its own message that comes back, so nothing loops.
+//!
+//! QoS 1 MQTT messages are acknowledged once the burrow has accepted
+//! the event.  Rabbit events are acknowledged once they have been
+//! handed to the broker.  When either connection drops, both are
+//! re-established with backoff and Rabbit subscriptions resume after
+//! the last event forwarded (its `Event-Seq`).  On first connecting,
+//! they resume after the last event the bridge acknowledged to the
+//! burrow, or else from the start of the topic.
+//!
+//! The bridge publishes under its own burrow ID (printed at start-up),
+//! which needs the `Publish` capability on the burrow.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use tracing::{debug, error, info, warn};
+
+use rabbit_engine::bridge::mqtt::{ConnectOptions, MqttConnection, Packet};
+use rabbit_engine::bridge::{BridgeConfig, Cursors, EchoFilter};
+use rabbit_engine::burrow::Burrow;
+use rabbit_engine::config::LoggingConfig;
+use rabbit_engine::logging;
+use rabbit_engine::protocol::frame::Frame;
+use rabbit_engine::transport::connector::{connect, make_client_config_insecure};
+use rabbit_engine::transport::tunnel::Tunnel;
+
+/// Longest wait between reconnection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Bridge MQTT topics into a warren.
+#[derive(Parser)]
+#[command(name = "rabbit-bridge", version, about)]
+struct Cli {
+    /// Bridge config file.
+    #[arg(short, long, default_value = "bridge.toml")]
+    config: PathBuf,
+}
+
+/// State kept across reconnections.
+struct Bridge {
+    config: BridgeConfig,
+    client: Burrow,
+    client_config: Arc<rustls::ClientConfig>,
+    /// Where each Rabbit topic's subscription resumes.
+    cursors: Cursors,
+    backoff: Duration,
+}
+
+impl Bridge {
+    /// Connect both sides and forward messages until the bridge is
+    /// stopped (`Ok`) or a connection fails (`Err`).
+    async fn session(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let broker = &self.config.broker;
+        let mut mqtt = MqttConnection::connect(
+            &broker.address,
+            &ConnectOptions {
+                client_id: broker.client_id.clone(),
+                username: broker.username.clone(),
+                password: broker.password.clone(),
+                keepalive_secs: broker.keepalive_secs,
+            },
+        )
+        .await?;
+        info!(broker = %broker.address, "connected to MQTT broker");
+
+        let filters: Vec<(String, u8)> = self
+            .config
+            .mappings
+            .iter()
+            .filter(|m| m.inbound())
+            .map(|m| (m.mqtt.clone(), m.qos.min(1)))
+            .collect();
+        if !filters.is_empty() {
+            let codes = mqtt.subscribe(filters.clone()).await?;
+            for ((filter, _), code) in filters.iter().zip(codes) {
+                if code == 0x80 {
+                    warn!(filter = %filter, "broker refused subscription");
+                }
+            }
+        }
+
+        let addr = &self.config.rabbit.burrow;
+        let mut tunnel = connect(addr, Arc::clone(&self.client_config), "localhost").await?;
+        let server_id = self.client.client_handshake(&mut tunnel).await?;
+        info!(burrow = %addr, server = %server_id, "connected to burrow");
+
+        // One lane per outbound mapping, resuming after the last event
+        // forwarded from its topic.
+        for (i, m) in self.config.mappings.iter().enumerate() {
+            if !m.outbound() {
+                continue;
+            }
+            let sub = self.cursors.subscribe(m, (i + 1) as u16);
+            tunnel.send_frame(&sub).await?;
+        }
+        self.backoff = Duration::from_secs(1);
+
+        // Messages the bridge sent on each side, to recognise echoes.
+        let mut to_rabbit = EchoFilter::new();
+        let mut to_mqtt = EchoFilter::new();
+        // MQTT packet IDs awaiting the burrow's reply, by Txn.
+        let mut pending: HashMap<String, u16> = HashMap::new();
+        let mut next_txn = 0u64;
+
+        let keepalive = Duration::from_secs(u64::from(broker.keepalive_secs.max(1)));
+        let mut ping = tokio::time::interval(keepalive);
+        ping.tick().await;
+
+        loop {
+            tokio::select! {
+                packet = mqtt.recv() => match packet? {
+                    Some(Packet::Publish { topic, payload, packet_id, .. }) => {
+                        let ack = packet_id.map(|packet_id| Packet::PubAck { packet_id });
+                        let route = self.config.route_inbound(&topic);
+                        let forward = match (&route, String::from_utf8(payload)) {
+                            (None, _) => None,
+                            (Some((_, rabbit_topic)), Ok(body)) => {
+                                if to_mqtt.is_echo(&topic, body.as_bytes()) {
+                                    None
+                                } else {
+                                    Some((rabbit_topic.clone(), body))
+                                }
+                            }
+                            (Some(_), Err(_)) => {
+                                warn!(topic = %topic, "dropping non-UTF-8 MQTT payload");
+                                None
+                            }
+                        };
+                        let Some((rabbit_topic, body)) = forward else {
+                            if let Some(ack) = ack {
+                                mqtt.send(&ack).await?;
+                            }
+                            continue;
+                        };
+
+                        next_txn += 1;
+                        let txn = format!("mqtt-{}", next_txn);
+                        let mut publish = Frame::with_args("PUBLISH", vec![rabbit_topic.clone()]);
+                        publish.set_header("Lane", "0");
+                        publish.set_header("Txn", &txn);
+                        publish.set_body(body.clone());
+                        if self.config.route_outbound(&rabbit_topic).is_some() {
+                            to_rabbit.sent(&rabbit_topic, body.as_bytes());
+                        }
+                        if let Some(id) = packet_id {
+                            pending.insert(txn, id);
+                        }
+                        debug!(mqtt = %topic, rabbit = %rabbit_topic, "forwarding to burrow");
+                        tunnel.send_frame(&publish).await?;
+                    }
+                    Some(Packet::PubAck { .. } | Packet::SubAck { .. } | Packet::PingResp) => {}
+                    Some(other) => debug!(packet = ?other, "ignoring MQTT packet"),
+                    None => return Err("MQTT broker closed the connection".into()),
+                },
+
+                frame = tunnel.recv_frame() => {
+                    let frame = frame?.ok_or("burrow closed the tunnel")?;
+                    match frame.verb.as_str() {
+                        "PING" => tunnel.send_frame(&Frame::new("PONG")).await?,
+                        "EVENT" => {
+                            self.forward_event(&frame, &mut mqtt, &mut tunnel, &mut to_rabbit, &mut to_mqtt)
+                                .await?
+                        }
+                        "201" => debug!(lane = ?frame.header("Lane"), "subscribed"),
+                        verb => {
+                            let id = frame.header("Txn").and_then(|t| pending.remove(t));
+                            if verb.starts_with('2') {
+                                if let Some(packet_id) = id {
+                                    mqtt.send(&Packet::PubAck { packet_id }).await?;
+                                }
+                            } else if verb.starts_with('4') || verb.starts_with('5') {
+                                warn!(
+                                    status = %verb,
+                                    reason = %frame.args.join(" "),
+                                    body = frame.body.as_deref().unwrap_or(""),
+                                    "burrow rejected a request"
+                                );
+                            }
+                        }
+                    }
+                }
+
+                _ = ping.tick() => mqtt.send(&Packet::PingReq).await?,
+
+                _ = tokio::signal::ctrl_c() => {
+                    let _ = mqtt.disconnect().await;
+                    let _ = tunnel.close().await;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Publish a Rabbit event to MQTT and acknowledge it.
+    async fn forward_event<T: Tunnel>(
+        &mut self,
+        frame: &Frame,
+        mqtt: &mut MqttConnection,
+        tunnel: &mut T,
+        to_rabbit: &mut EchoFilter,
+        to_mqtt: &mut EchoFilter,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(topic) = frame.args.first() else {
+            return Ok(());
+        };
+        let body = frame.body.as_deref().unwrap_or("");
+        if !to_rabbit.is_echo(topic, body.as_bytes()) {
+            if let Some(mapping) = self.config.route_outbound(topic) {
+                if self.config.route_inbound(&mapping.mqtt).is_some() {
+                    to_mqtt.sent(&mapping.mqtt, body.as_bytes());
+                }
+                debug!(rabbit = %topic, mqtt = %mapping.mqtt, "forwarding to broker");
+                mqtt.publish(&mapping.mqtt, body.as_bytes(), mapping.qos, false)
+                    .await?;
+            }
+        }
+
+        if let Some(ack) = self.cursors.forwarded(frame) {
+            tunnel.send_frame(&ack).await?;
+        }
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let _log = match logging::init(&LoggingConfig::default(), Path::new(".")) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("rabbit-bridge: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let config = BridgeConfig::load(&cli.config)?;
+    let mut client = Burrow::in_memory("rabbit-bridge");
+    client.identity = config.load_identity()?;
+    info!(
+        id = %client.identity.burrow_id(),
+        mappings = config.mappings.len(),
+        "bridge identity loaded; press Ctrl-C to stop"
+    );
+
+    let mut bridge = Bridge {
+        config,
+        client,
+        client_config: make_client_config_insecure(),
+        cursors: Cursors::new(),
+        backoff: Duration::from_secs(1),
+    };
+    loop {
+        match bridge.session().await {
+            Ok(()) => break,
+            Err(e) => {
+                warn!(err = %e, retry_in = ?bridge.backoff, "bridge connection lost");
+            }
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(bridge.backoff) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+        bridge.backoff = (bridge.backoff * 2).min(MAX_BACKOFF);
+    }
+    info!("bridge stopped");
+    Ok(())
+}
@@ -0,0 +1,403 @@
+//! `rabbit-dump` — record, inspect and replay Rabbit frame captures.
+//!
+//! # Usage
+//!
+//! ```text
+//! rabbit-dump record --upstream 127.0.0.1:7443 --listen 127.0.0.1:7450 -o run.rcap
+//! rabbit-dump show run.rcap                    # one line per frame
+//! rabbit-dump show run.rcap --conn 2 --full    # whole frames of one tunnel
+//! rabbit-dump replay run.rcap --addr 127.0.0.1:7443
+//! rabbit-dump replay run.rcap --config config.toml --check
+//! ```
+//!
+//! `record` is a TLS proxy: clients connect to `--listen`, every frame
+//! is forwarded to `--upstream` and written, decrypted, to the
+//! capture.  A burrow can also record its own incoming tunnels with
+//! `burrow serve --capture`.
+//!
+//! `replay` opens one tunnel per captured connection, performs a fresh
+//! handshake, and resends the frames the client sent, skipping the
+//! captured handshake.  Each reply's status is compared with the one
+//! captured.  Without `--addr` the frames go to a simulated burrow in
+//! this process, built from `--config` or empty.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+use tracing::{error, info, warn};
+
+use rabbit_engine::burrow::Burrow;
+use rabbit_engine::config::{Config, LoggingConfig};
+use rabbit_engine::logging;
+use rabbit_engine::protocol::frame::Frame;
+use rabbit_engine::transport::capture::{read_capture, CaptureWriter, CapturedFrame};
+use rabbit_engine::transport::cert::{generate_self_signed, make_server_config};
+use rabbit_engine::transport::connector::{connect, make_client_config_insecure};
+use rabbit_engine::transport::listener::RabbitListener;
+use rabbit_engine::transport::memory::memory_tunnel_pair;
+use rabbit_engine::transport::tap::{FrameTap, TapDirection, TapTunnel};
+use rabbit_engine::transport::tunnel::Tunnel;
+
+/// Frames that are part of the handshake and are not replayed.
+const HANDSHAKE_VERBS: &[&str] = &["HELLO", "AUTH"];
+
+/// Unsolicited frames that are not treated as replies.
+const BACKGROUND_VERBS: &[&str] = &["PING", "PONG", "OFFER"];
+
+/// Record, inspect and replay Rabbit frame captures.
+#[derive(Parser)]
+#[command(name = "rabbit-dump", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Proxy connections to a burrow and record every frame.
+    Record {
+        /// Burrow to forward to (e.g. 127.0.0.1:7443).
+        #[arg(short, long)]
+        upstream: String,
+
+        /// Address clients connect to.
+        #[arg(short, long, default_value = "127.0.0.1:7450")]
+        listen: String,
+
+        /// Capture file to write.
+        #[arg(short, long, default_value = "capture.rcap")]
+        output: PathBuf,
+    },
+
+    /// Print a capture.
+    Show {
+        /// Capture file.
+        file: PathBuf,
+
+        /// Only show this connection.
+        #[arg(long)]
+        conn: Option<u64>,
+
+        /// Print whole frames, not just start lines.
+        #[arg(long)]
+        full: bool,
+    },
+
+    /// Resend a capture's client frames and compare the replies.
+    Replay {
+        /// Capture file.
+        file: PathBuf,
+
+        /// Live burrow to replay against.
+        #[arg(short, long, conflicts_with = "config")]
+        addr: Option<String>,
+
+        /// Config for the simulated burrow (default: an empty one).
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Only replay this connection.
+        #[arg(long)]
+        conn: Option<u64>,
+
+        /// How long to wait for replies to each frame, in ms.
+        #[arg(long, default_value_t = 300)]
+        settle_ms: u64,
+
+        /// Exit non-zero if any reply differs from the capture.
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let _log = match logging::init(&LoggingConfig::default(), Path::new(".")) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("rabbit-dump: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Commands::Record {
+            upstream,
+            listen,
+            output,
+        } => cmd_record(&upstream, &listen, &output).await,
+        Commands::Show { file, conn, full } => cmd_show(&file, conn, full),
+        Commands::Replay {
+            file,
+            addr,
+            config,
+            conn,
+            settle_ms,
+            check,
+        } => {
+            let settle = Duration::from_millis(settle_ms);
+            match cmd_replay(&file, addr.as_deref(), config.as_deref(), conn, settle).await {
+                Ok(mismatches) if check && mismatches > 0 => {
+                    Err(format!("{} replies differ from the capture", mismatches).into())
+                }
+                Ok(_) => Ok(()),
+                Err(e) => Err(e),
+            }
+        }
+    };
+    if let Err(e) = result {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+// ── Record ─────────────────────────────────────────────────────
+
+async fn cmd_record(
+    upstream: &str,
+    listen: &str,
+    output: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tap: Arc<dyn FrameTap> = Arc::new(CaptureWriter::create(output)?);
+    let server_config = make_server_config(&generate_self_signed()?)?;
+    let listener = RabbitListener::bind(listen, server_config).await?;
+    let client_config = make_client_config_insecure();
+    info!(
+        listen = %listener.local_addr()?,
+        upstream,
+        output = %output.display(),
+        "recording; press Ctrl-C to stop"
+    );
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(mut client) => {
+                    let tap = Arc::clone(&tap);
+                    let upstream = upstream.to_string();
+                    let cc = Arc::clone(&client_config);
+                    tokio::spawn(async move {
+                        let mut tapped = TapTunnel::new(&mut client, tap);
+                        let conn = tapped.conn();
+                        info!(conn, "client connected");
+                        match proxy(&mut tapped, &upstream, cc).await {
+                            Ok(()) => info!(conn, "connection closed"),
+                            Err(e) => warn!(conn, err = %e, "connection failed"),
+                        }
+                    });
+                }
+                Err(e) => warn!(err = %e, "accept failed"),
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    info!(output = %output.display(), "capture saved");
+    Ok(())
+}
+
+/// Forward frames between a client and the upstream burrow until
+/// either side closes.
+async fn proxy<T: Tunnel>(
+    client: &mut T,
+    upstream: &str,
+    client_config: Arc<rustls::ClientConfig>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut server = connect(upstream, client_config, "localhost").await?;
+    loop {
+        tokio::select! {
+            from_client = client.recv_frame() => match from_client? {
+                Some(frame) => server.send_frame(&frame).await?,
+                None => break,
+            },
+            from_server = server.recv_frame() => match from_server? {
+                Some(frame) => client.send_frame(&frame).await?,
+                None => break,
+            },
+        }
+    }
+    let _ = server.close().await;
+    let _ = client.close().await;
+    Ok(())
+}
+
+// ── Show ───────────────────────────────────────────────────────
+
+fn start_line(frame: &Frame) -> String {
+    std::iter::once(frame.verb.as_str())
+        .chain(frame.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn cmd_show(file: &Path, conn: Option<u64>, full: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let records = read_capture(file)?;
+    let Some(first) = records.first() else {
+        println!("(empty capture)");
+        return Ok(());
+    };
+    let start = first.micros;
+    for record in records.iter().filter(|r| conn.is_none_or(|c| r.conn == c)) {
+        let offset = record.micros.saturating_sub(start) as f64 / 1_000_000.0;
+        let arrow = match record.direction {
+            TapDirection::Inbound => "->",
+            TapDirection::Outbound => "<-",
+        };
+        println!(
+            "{:>10.3}s  #{:<4} {} {}  [{}]",
+            offset,
+            record.conn,
+            arrow,
+            start_line(&record.frame),
+            record.peer
+        );
+        if full {
+            for line in record.frame.serialize().lines() {
+                println!("                  {}", line);
+            }
+        }
+    }
+    Ok(())
+}
+
+// ── Replay ─────────────────────────────────────────────────────
+
+/// A client frame to resend and the reply status that was captured.
+struct Exchange {
+    request: Frame,
+    expected: Option<String>,
+}
+
+/// Split a capture into per-connection exchanges.
+fn exchanges(records: &[CapturedFrame]) -> BTreeMap<u64, Vec<Exchange>> {
+    let mut by_conn: BTreeMap<u64, Vec<&CapturedFrame>> = BTreeMap::new();
+    for record in records {
+        by_conn.entry(record.conn).or_default().push(record);
+    }
+    by_conn
+        .into_iter()
+        .map(|(conn, frames)| {
+            let mut list = Vec::new();
+            for (i, record) in frames.iter().enumerate() {
+                let verb = record.frame.verb.as_str();
+                if record.direction != TapDirection::Inbound
+                    || HANDSHAKE_VERBS.contains(&verb)
+                    || BACKGROUND_VERBS.contains(&verb)
+                {
+                    continue;
+                }
+                let expected = frames[i + 1..]
+                    .iter()
+                    .take_while(|r| r.direction == TapDirection::Outbound)
+                    .find(|r| !BACKGROUND_VERBS.contains(&r.frame.verb.as_str()))
+                    .map(|r| start_line(&r.frame));
+                list.push(Exchange {
+                    request: record.frame.clone(),
+                    expected,
+                });
+            }
+            (conn, list)
+        })
+        .collect()
+}
+
+async fn cmd_replay(
+    file: &Path,
+    addr: Option<&str>,
+    config: Option<&Path>,
+    only_conn: Option<u64>,
+    settle: Duration,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let records = read_capture(file)?;
+    let client = Burrow::in_memory("rabbit-dump");
+
+    let simulated = match (addr, config) {
+        (Some(_), _) => None,
+        (None, Some(path)) => {
+            let config = Config::load(path)?;
+            let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+            Some(Arc::new(Burrow::from_config(&config, base_dir)?))
+        }
+        (None, None) => Some(Arc::new(Burrow::in_memory("simulated"))),
+    };
+    let client_config = make_client_config_insecure();
+
+    let mut mismatches = 0;
+    for (conn, list) in exchanges(&records) {
+        if only_conn.is_some_and(|c| c != conn) || list.is_empty() {
+            continue;
+        }
+        println!("conn #{}: {} frames", conn, list.len());
+        mismatches += match (&simulated, addr) {
+            (Some(burrow), _) => {
+                let (mut tunnel, mut server_side) = memory_tunnel_pair("rabbit-dump", "simulated");
+                let burrow = Arc::clone(burrow);
+                let server =
+                    tokio::spawn(async move { burrow.handle_tunnel(&mut server_side).await });
+                client.client_handshake(&mut tunnel).await?;
+                let n = replay_exchanges(&mut tunnel, &list, settle).await?;
+                let _ = tunnel.close().await;
+                drop(tunnel);
+                let _ = server.await;
+                n
+            }
+            (None, Some(addr)) => {
+                let mut tunnel = connect(addr, Arc::clone(&client_config), "localhost").await?;
+                client.client_handshake(&mut tunnel).await?;
+                let n = replay_exchanges(&mut tunnel, &list, settle).await?;
+                let _ = tunnel.close().await;
+                n
+            }
+            (None, None) => unreachable!("replay target is either simulated or live"),
+        };
+    }
+    println!("{} mismatches", mismatches);
+    Ok(mismatches)
+}
+
+/// Send each request and compare its first reply with the capture.
+/// Returns the number of mismatches.
+async fn replay_exchanges<T: Tunnel>(
+    tunnel: &mut T,
+    list: &[Exchange],
+    settle: Duration,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut mismatches = 0;
+    for exchange in list {
+        tunnel.send_frame(&exchange.request).await?;
+
+        let mut reply = None;
+        while let Ok(received) = tokio::time::timeout(settle, tunnel.recv_frame()).await {
+            let Some(frame) = received? else { break };
+            if frame.verb == "PING" {
+                tunnel.send_frame(&Frame::new("PONG")).await?;
+            }
+            if reply.is_none() && !BACKGROUND_VERBS.contains(&frame.verb.as_str()) {
+                reply = Some(start_line(&frame));
+            }
+        }
+
+        let request = start_line(&exchange.request);
+        let status = |line: &Option<String>| {
+            line.as_deref()
+                .and_then(|l| l.split(' ').next())
+                .map(str::to_string)
+        };
+        let actual = reply.as_deref().unwrap_or("(no reply)");
+        if status(&reply) == status(&exchange.expected) {
+            println!("  ok        {} -> {}", request, actual);
+        } else {
+            mismatches += 1;
+            println!(
+                "  MISMATCH  {} -> {} (captured {})",
+                request,
+                actual,
+                exchange.expected.as_deref().unwrap_or("no reply")
+            );
+        }
+    }
+    Ok(mismatches)
+}
@@ -0,0 +1,255 @@
+//! `rabbit-gopher` — serve a burrow to Gopher (RFC 1436) clients.
+//!
+//! # Usage
+//!
+//! ```text
+//! rabbit-gopher --burrow 127.0.0.1:7443                       # listens on 0.0.0.0:70
+//! rabbit-gopher --burrow 127.0.0.1:7443 -l 0.0.0.0:7070 --host gopher.example.org
+//! rabbit-gopher --burrow 127.0.0.1:7443 --follow-remote
+//! ```
+//!
+//! Each Gopher request is translated into a `LIST`, `FETCH` or
+//! `SEARCH` frame on a tunnel to the burrow (see
+//! [`rabbit_engine::content::gopher`]), and the reply is sent back as
+//! a Gopher menu, text file or binary.  Tunnels are kept open between
+//! requests and redialed when they drop.
+//!
+//! Menus link back to this gateway using `--host` and the listening
+//! port (or `--port` behind a port forward).  With `--follow-remote`,
+//! items on other burrows are fetched through the gateway too.
+//! Binding port 70 usually needs elevated privileges.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use rabbit_engine::burrow::Burrow;
+use rabbit_engine::config::LoggingConfig;
+use rabbit_engine::content::gopher::{
+    gopher_error, gopher_response, parse_gopher_request, rabbit_request,
+};
+use rabbit_engine::logging;
+use rabbit_engine::protocol::frame::Frame;
+use rabbit_engine::transport::connector::{connect, make_client_config_insecure};
+use rabbit_engine::transport::tls::ClientTunnel;
+use rabbit_engine::transport::tunnel::Tunnel;
+
+/// Longest request line accepted, in bytes.
+const MAX_REQUEST: u64 = 1024;
+
+/// How long a client has to send its request line.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Unsolicited frames skipped while waiting for a reply.
+const BACKGROUND_VERBS: &[&str] = &["PING", "PONG", "OFFER", "EVENT"];
+
+type UpstreamTunnel = ClientTunnel;
+
+/// Serve a burrow to Gopher clients.
+#[derive(Parser)]
+#[command(name = "rabbit-gopher", version, about)]
+struct Cli {
+    /// Burrow to serve (e.g. 127.0.0.1:7443).
+    #[arg(short, long)]
+    burrow: String,
+
+    /// Address to listen on for Gopher clients.
+    #[arg(short, long, default_value = "0.0.0.0:70")]
+    listen: String,
+
+    /// Hostname put in menus (default: localhost).
+    #[arg(long, default_value = "localhost")]
+    host: String,
+
+    /// Port put in menus (default: the listening port).
+    #[arg(long)]
+    port: Option<u16>,
+
+    /// Fetch items on other burrows through the gateway.
+    #[arg(long)]
+    follow_remote: bool,
+
+    /// Seconds to wait for the burrow's reply.
+    #[arg(long, default_value_t = 10)]
+    timeout: u64,
+}
+
+/// Tunnels to the burrow and, with `--follow-remote`, to other
+/// burrows, opened on first use.
+struct Upstreams {
+    client: Burrow,
+    client_config: Arc<rustls::ClientConfig>,
+    tunnels: Mutex<HashMap<String, Arc<Mutex<Option<UpstreamTunnel>>>>>,
+    timeout: Duration,
+}
+
+impl Upstreams {
+    /// Send `request` to the burrow at `addr` and return its reply.
+    /// A stale tunnel is redialed once.
+    async fn request(
+        &self,
+        addr: &str,
+        request: &Frame,
+    ) -> Result<Frame, Box<dyn std::error::Error + Send + Sync>> {
+        let slot = Arc::clone(
+            self.tunnels
+                .lock()
+                .await
+                .entry(addr.to_string())
+                .or_default(),
+        );
+        let mut tunnel = slot.lock().await;
+        let mut last_error = None;
+        for _ in 0..2 {
+            if tunnel.is_none() {
+                let mut fresh = connect(addr, Arc::clone(&self.client_config), "localhost").await?;
+                self.client.client_handshake(&mut fresh).await?;
+                info!(burrow = addr, "connected");
+                *tunnel = Some(fresh);
+            }
+            let Some(t) = tunnel.as_mut() else { break };
+            match tokio::time::timeout(self.timeout, exchange(t, request)).await {
+                Ok(Ok(reply)) => return Ok(reply),
+                Ok(Err(e)) => last_error = Some(e),
+                Err(_) => last_error = Some("timed out waiting for the burrow".into()),
+            }
+            *tunnel = None;
+        }
+        Err(last_error.unwrap_or_else(|| "no reply".into()))
+    }
+}
+
+/// Send a frame and wait for its reply, answering keepalives.
+async fn exchange(
+    tunnel: &mut UpstreamTunnel,
+    request: &Frame,
+) -> Result<Frame, Box<dyn std::error::Error + Send + Sync>> {
+    tunnel.send_frame(request).await?;
+    loop {
+        let frame = tunnel
+            .recv_frame()
+            .await?
+            .ok_or("burrow closed the tunnel")?;
+        if frame.verb == "PING" {
+            tunnel.send_frame(&Frame::new("PONG")).await?;
+        }
+        if !BACKGROUND_VERBS.contains(&frame.verb.as_str()) {
+            return Ok(frame);
+        }
+    }
+}
+
+struct Gateway {
+    upstreams: Upstreams,
+    burrow: String,
+    host: String,
+    port: u16,
+    follow_remote: bool,
+}
+
+impl Gateway {
+    /// Answer one Gopher connection: read the request line, reply and
+    /// close.
+    async fn serve(&self, stream: TcpStream) -> std::io::Result<()> {
+        let peer = stream.peer_addr()?;
+        let (reader, mut writer) = stream.into_split();
+        let mut line = String::new();
+        let mut reader = BufReader::new(reader.take(MAX_REQUEST));
+        match tokio::time::timeout(REQUEST_TIMEOUT, reader.read_line(&mut line)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Ok(()),
+        }
+
+        let request = parse_gopher_request(&line);
+        let response = match &request.remote {
+            Some(_) if !self.follow_remote => {
+                gopher_error("this gateway does not follow remote items").into_bytes()
+            }
+            remote => {
+                let addr = remote.as_deref().unwrap_or(&self.burrow);
+                info!(%peer, burrow = addr, selector = %request.selector, "request");
+                match self
+                    .upstreams
+                    .request(addr, &rabbit_request(&request))
+                    .await
+                {
+                    Ok(reply) => gopher_response(&reply, &self.host, self.port, self.follow_remote),
+                    Err(e) => {
+                        warn!(burrow = addr, err = %e, "request failed");
+                        gopher_error(&format!("burrow unavailable: {}", e)).into_bytes()
+                    }
+                }
+            }
+        };
+        writer.write_all(&response).await?;
+        writer.shutdown().await
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let _log = match logging::init(&LoggingConfig::default(), Path::new(".")) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("rabbit-gopher: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(&cli.listen)
+        .await
+        .map_err(|e| format!("failed to listen on {}: {}", cli.listen, e))?;
+    let local = listener.local_addr()?;
+    let gateway = Arc::new(Gateway {
+        upstreams: Upstreams {
+            client: Burrow::in_memory("rabbit-gopher"),
+            client_config: make_client_config_insecure(),
+            tunnels: Mutex::new(HashMap::new()),
+            timeout: Duration::from_secs(cli.timeout),
+        },
+        burrow: cli.burrow,
+        host: cli.host,
+        port: cli.port.unwrap_or(local.port()),
+        follow_remote: cli.follow_remote,
+    });
+    info!(
+        listen = %local,
+        burrow = %gateway.burrow,
+        "serving Gopher; press Ctrl-C to stop"
+    );
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    let gateway = Arc::clone(&gateway);
+                    tokio::spawn(async move {
+                        if let Err(e) = gateway.serve(stream).await {
+                            warn!(err = %e, "gopher connection failed");
+                        }
+                    });
+                }
+                Err(e) => warn!(err = %e, "accept failed"),
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    info!("gateway stopped");
+    Ok(())
+}
@@ -7,12 +7,15 @@
 //! Build: `cargo build --features gui --bin rabbit-gui`
 //! Run:   `cargo run  --features gui --bin rabbit-gui -- <host:port> [selector]`
 
+use std::path::Path;
+
 use clap::Parser;
 
 use rabbit_engine::config::Config;
 use rabbit_engine::gui::renderer::Renderer;
 use rabbit_engine::gui::theme::Theme;
 use rabbit_engine::gui::view_gen::{fallback_html, ViewContent};
+use rabbit_engine::logging;
 
 #[cfg(feature = "gui")]
 use rabbit_engine::gui::app::launch_gui;
@@ -38,6 +41,14 @@ fn main() {
 
     // Load config (or use defaults).
     let config = Config::load(&args.config).unwrap_or_default();
+    let base_dir = Path::new(&args.config).parent().unwrap_or(Path::new("."));
+    let _log = match logging::init(&config.logging, base_dir) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("rabbit-gui: {}", e);
+            std::process::exit(1);
+        }
+    };
     let gui_config = config.gui.clone();
     let _theme = Theme::parse(&gui_config.theme);
     let _renderer = Renderer::parse(&gui_config.renderer).resolve();
@@ -52,18 +63,20 @@ fn main() {
 
     #[cfg(feature = "gui")]
     {
-        eprintln!(
-            "rabbit-gui: connecting to {} selector={} renderer={}",
-            args.host, args.selector, _renderer
+        tracing::info!(
+            host = %args.host,
+            selector = %args.selector,
+            renderer = %_renderer,
+            "connecting"
         );
         launch_gui(gui_config, _initial_html, args.host.clone(), args.selector.clone());
     }
 
     #[cfg(not(feature = "gui"))]
     {
-        eprintln!(
-            "rabbit-gui: the 'gui' feature is not enabled.\n\
-             Rebuild with: cargo build --features gui --bin rabbit-gui"
+        tracing::error!(
+            "the 'gui' feature is not enabled; \
+             rebuild with: cargo build --features gui --bin rabbit-gui"
         );
         std::process::exit(1);
     }
@@ -0,0 +1,912 @@
+//! `rabbit-sim` — run a warren over simulated links and measure it.
+//!
+//! # Usage
+//!
+//! ```text
+//! rabbit-sim                                   # 5-burrow ring, clean links
+//! rabbit-sim --loss 0.02 --latency-ms 80       # lossy, slow links
+//! rabbit-sim --scenario partition.toml --json  # scripted scenario, JSON report
+//! ```
+//!
+//! Every burrow runs in this process and publishes to its own topic.
+//! Each link of the generated topology is a subscription: the dialing
+//! burrow subscribes to the other's topic over a
+//! [`SimLink`](rabbit_engine::transport::sim::SimLink) with the
+//! scenario's latency, jitter and loss.  Partitions cut a group of
+//! burrows off for a while; churn severs a random link now and then.
+//! Subscribers ACK events, answer keepalives and reconnect whenever
+//! their tunnel closes.
+//!
+//! The report gives delivery rate, duplicate deliveries,
+//! retransmissions, frame loss, reconnects, latency percentiles and
+//! convergence time: how long after a partition heals (or a severed
+//! link redials) the affected subscribers receive new events again.
+//!
+//! # Scenario file
+//!
+//! ```toml
+//! burrows = 6
+//! topology = "ring"          # line | ring | star | mesh
+//! duration_secs = 30
+//! drain_secs = 5
+//! publish_rate = 10.0        # events per second per burrow
+//! seed = 7
+//!
+//! [link]
+//! latency_ms = 40
+//! jitter_ms = 10
+//! loss = 0.01
+//!
+//! [[partition]]
+//! at_secs = 10
+//! duration_secs = 4
+//! burrows = [0, 1]
+//!
+//! [churn]
+//! interval_secs = 5
+//! downtime_ms = 500
+//!
+//! [protocol]
+//! keepalive_secs = 5
+//! retransmit_timeout_ms = 500
+//! retransmit_max_retries = 5
+//! handshake_timeout_secs = 2
+//! ```
+
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::Parser;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::{debug, error, info};
+
+use rabbit_engine::burrow::Burrow;
+use rabbit_engine::config::LoggingConfig;
+use rabbit_engine::logging;
+use rabbit_engine::protocol::frame::Frame;
+use rabbit_engine::transport::sim::{sim_tunnel_pair, LinkConditions, LinkStats, SimLink};
+use rabbit_engine::transport::tap::{FrameTap, TapDirection};
+use rabbit_engine::transport::tunnel::Tunnel;
+
+/// Topic every simulated burrow publishes to.
+const TOPIC: &str = "/q/sim";
+
+/// Lane subscribers use for their subscription.
+const LANE: &str = "1";
+
+/// How often convergence is checked.
+const CONVERGENCE_POLL: Duration = Duration::from_millis(10);
+
+/// Run a warren over simulated links and report delivery, latency
+/// and convergence.
+#[derive(Parser)]
+#[command(name = "rabbit-sim", version, about)]
+struct Cli {
+    /// Scenario file (TOML); flags below override it.
+    #[arg(short, long)]
+    scenario: Option<PathBuf>,
+
+    /// Number of burrows.
+    #[arg(short = 'n', long)]
+    burrows: Option<usize>,
+
+    /// Topology: line, ring, star or mesh.
+    #[arg(short, long)]
+    topology: Option<String>,
+
+    /// Seconds to publish for.
+    #[arg(short, long)]
+    duration: Option<f64>,
+
+    /// Events per second per burrow.
+    #[arg(long)]
+    rate: Option<f64>,
+
+    /// One-way link latency in milliseconds.
+    #[arg(long)]
+    latency_ms: Option<u64>,
+
+    /// Extra random latency in milliseconds.
+    #[arg(long)]
+    jitter_ms: Option<u64>,
+
+    /// Frame loss probability (0.0–1.0).
+    #[arg(long)]
+    loss: Option<f64>,
+
+    /// Random seed for loss, jitter and churn.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Print the report as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+// ── Scenario ───────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Scenario {
+    burrows: usize,
+    topology: String,
+    duration_secs: f64,
+    drain_secs: f64,
+    publish_rate: f64,
+    seed: u64,
+    link: LinkSpec,
+    #[serde(rename = "partition")]
+    partitions: Vec<PartitionSpec>,
+    churn: ChurnSpec,
+    protocol: ProtocolSpec,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            burrows: 5,
+            topology: "ring".into(),
+            duration_secs: 20.0,
+            drain_secs: 5.0,
+            publish_rate: 5.0,
+            seed: 1,
+            link: LinkSpec::default(),
+            partitions: Vec::new(),
+            churn: ChurnSpec::default(),
+            protocol: ProtocolSpec::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LinkSpec {
+    latency_ms: u64,
+    jitter_ms: u64,
+    loss: f64,
+}
+
+impl LinkSpec {
+    fn conditions(&self) -> LinkConditions {
+        LinkConditions {
+            latency: Duration::from_millis(self.latency_ms),
+            jitter: Duration::from_millis(self.jitter_ms),
+            loss: self.loss,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PartitionSpec {
+    at_secs: f64,
+    duration_secs: f64,
+    burrows: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ChurnSpec {
+    /// Seconds between severed links; 0 disables churn.
+    interval_secs: f64,
+    downtime_ms: u64,
+}
+
+impl Default for ChurnSpec {
+    fn default() -> Self {
+        Self {
+            interval_secs: 0.0,
+            downtime_ms: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProtocolSpec {
+    keepalive_secs: u64,
+    retransmit_timeout_ms: u64,
+    retransmit_max_retries: u32,
+    handshake_timeout_secs: u64,
+}
+
+impl Default for ProtocolSpec {
+    fn default() -> Self {
+        Self {
+            keepalive_secs: 5,
+            retransmit_timeout_ms: 500,
+            retransmit_max_retries: 5,
+            handshake_timeout_secs: 2,
+        }
+    }
+}
+
+impl Scenario {
+    fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    fn apply(&mut self, cli: &Cli) {
+        if let Some(n) = cli.burrows {
+            self.burrows = n;
+        }
+        if let Some(t) = &cli.topology {
+            self.topology = t.clone();
+        }
+        if let Some(d) = cli.duration {
+            self.duration_secs = d;
+        }
+        if let Some(r) = cli.rate {
+            self.publish_rate = r;
+        }
+        if let Some(l) = cli.latency_ms {
+            self.link.latency_ms = l;
+        }
+        if let Some(j) = cli.jitter_ms {
+            self.link.jitter_ms = j;
+        }
+        if let Some(l) = cli.loss {
+            self.link.loss = l;
+        }
+        if let Some(s) = cli.seed {
+            self.seed = s;
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        if self.burrows < 2 {
+            problems.push("burrows must be at least 2".to_string());
+        }
+        if !["line", "ring", "star", "mesh"].contains(&self.topology.as_str()) {
+            problems.push(format!(
+                "topology must be line, ring, star or mesh, not {:?}",
+                self.topology
+            ));
+        }
+        if self.duration_secs <= 0.0 || self.drain_secs < 0.0 {
+            problems.push("duration_secs must be positive and drain_secs not negative".into());
+        }
+        if self.publish_rate <= 0.0 {
+            problems.push("publish_rate must be positive".into());
+        }
+        if !(0.0..1.0).contains(&self.link.loss) {
+            problems.push("link.loss must be in 0.0..1.0".into());
+        }
+        for (i, p) in self.partitions.iter().enumerate() {
+            if let Some(b) = p.burrows.iter().find(|&&b| b >= self.burrows) {
+                problems.push(format!("partition {}: no burrow {}", i, b));
+            }
+            if p.at_secs < 0.0 || p.duration_secs <= 0.0 {
+                problems.push(format!("partition {}: bad timing", i));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+
+    /// `(subscriber, publisher)` pairs for the topology.
+    fn edges(&self) -> Vec<(usize, usize)> {
+        let n = self.burrows;
+        match self.topology.as_str() {
+            "star" => (1..n).map(|i| (i, 0)).collect(),
+            "mesh" => (1..n).flat_map(|i| (0..i).map(move |j| (i, j))).collect(),
+            "ring" if n > 2 => (1..n).map(|i| (i, i - 1)).chain([(0, n - 1)]).collect(),
+            _ => (1..n).map(|i| (i, i - 1)).collect(),
+        }
+    }
+}
+
+// ── Shared state ───────────────────────────────────────────────
+
+/// Publish times of every event, per burrow.
+struct Publications {
+    times: Vec<Mutex<Vec<Instant>>>,
+}
+
+impl Publications {
+    fn count(&self, node: usize) -> u64 {
+        self.times[node]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len() as u64
+    }
+
+    /// When event `id` (1-based) of `node` was published.
+    fn time(&self, node: usize, id: u64) -> Option<Instant> {
+        let times = self.times[node].lock().unwrap_or_else(|e| e.into_inner());
+        times.get(id.checked_sub(1)? as usize).copied()
+    }
+}
+
+/// One subscription and what it has received.
+struct LinkState {
+    subscriber: usize,
+    publisher: usize,
+    link: Arc<SimLink>,
+    received: Mutex<HashSet<u64>>,
+    latencies: Mutex<Vec<Duration>>,
+    /// Publish time of the newest event received.
+    newest: Mutex<Option<Instant>>,
+    duplicates: AtomicU64,
+    connects: AtomicU64,
+    subscribed: AtomicBool,
+}
+
+impl LinkState {
+    fn newest(&self) -> Option<Instant> {
+        *self.newest.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Counts EVENT frames a burrow sends more than once on a tunnel.
+#[derive(Default)]
+struct RetransmitCounter {
+    seen: Mutex<HashSet<(u64, String, String)>>,
+    count: AtomicU64,
+}
+
+impl FrameTap for RetransmitCounter {
+    fn on_frame(&self, conn: u64, _peer: &str, direction: TapDirection, frame: &Frame) {
+        if direction != TapDirection::Outbound || frame.verb != "EVENT" {
+            return;
+        }
+        let key = (
+            conn,
+            frame.header("Lane").unwrap_or("0").to_string(),
+            frame.header("Seq").unwrap_or("").to_string(),
+        );
+        if !self
+            .seen
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key)
+        {
+            self.count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Convergence results: `None` for disruptions a link never
+/// recovered from before the run ended.
+type Convergence = Arc<Mutex<Vec<Option<Duration>>>>;
+
+// ── Main ───────────────────────────────────────────────────────
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let log_config = LoggingConfig {
+        level: "warn".into(),
+        ..LoggingConfig::default()
+    };
+    let _log = match logging::init(&log_config, Path::new(".")) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("rabbit-sim: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut scenario = match &cli.scenario {
+        Some(path) => match Scenario::load(path) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => Scenario::default(),
+    };
+    scenario.apply(&cli);
+    if let Err(e) = scenario.validate() {
+        error!("invalid scenario: {}", e);
+        std::process::exit(1);
+    }
+
+    let report = run(&scenario).await;
+    if cli.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).unwrap_or_default()
+        );
+    } else {
+        print_report(&scenario, &report);
+    }
+}
+
+async fn run(scenario: &Scenario) -> serde_json::Value {
+    let proto = &scenario.protocol;
+    let retransmits = Arc::new(RetransmitCounter::default());
+    let burrows: Vec<Arc<Burrow>> = (0..scenario.burrows)
+        .map(|i| {
+            let mut b = Burrow::in_memory(format!("sim-{}", i));
+            b.keepalive_secs = proto.keepalive_secs;
+            b.retransmit_timeout_ms = proto.retransmit_timeout_ms;
+            b.retransmit_max_retries = proto.retransmit_max_retries;
+            b.handshake_timeout_secs = proto.handshake_timeout_secs;
+            b.set_frame_tap(Some(retransmits.clone()));
+            Arc::new(b)
+        })
+        .collect();
+    let publications = Arc::new(Publications {
+        times: (0..scenario.burrows)
+            .map(|_| Mutex::new(Vec::new()))
+            .collect(),
+    });
+    let links: Vec<Arc<LinkState>> = scenario
+        .edges()
+        .into_iter()
+        .enumerate()
+        .map(|(i, (subscriber, publisher))| {
+            Arc::new(LinkState {
+                subscriber,
+                publisher,
+                link: SimLink::new(
+                    scenario.link.conditions(),
+                    scenario.seed.wrapping_add(i as u64),
+                ),
+                received: Mutex::new(HashSet::new()),
+                latencies: Mutex::new(Vec::new()),
+                newest: Mutex::new(None),
+                duplicates: AtomicU64::new(0),
+                connects: AtomicU64::new(0),
+                subscribed: AtomicBool::new(false),
+            })
+        })
+        .collect();
+
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let downtime = Duration::from_millis(scenario.churn.downtime_ms);
+    let link_tasks: Vec<_> = links
+        .iter()
+        .map(|state| {
+            tokio::spawn(run_link(
+                Arc::clone(&burrows[state.subscriber]),
+                Arc::clone(&burrows[state.publisher]),
+                Arc::clone(state),
+                Arc::clone(&publications),
+                Duration::from_secs(proto.handshake_timeout_secs),
+                downtime,
+                stop_rx.clone(),
+            ))
+        })
+        .collect();
+
+    // Publish only once every subscription is up, so the delivery
+    // rate measures the scenario rather than start-up.
+    let setup_deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < setup_deadline
+        && !links.iter().all(|l| l.subscribed.load(Ordering::Relaxed))
+    {
+        tokio::time::sleep(CONVERGENCE_POLL).await;
+    }
+
+    let start = Instant::now();
+    let duration = Duration::from_secs_f64(scenario.duration_secs);
+    let end = start + duration + Duration::from_secs_f64(scenario.drain_secs);
+    let convergence: Convergence = Arc::new(Mutex::new(Vec::new()));
+    let mut disruptions = 0usize;
+
+    let publishers: Vec<_> = burrows
+        .iter()
+        .enumerate()
+        .map(|(i, burrow)| {
+            tokio::spawn(publish(
+                i,
+                Arc::clone(burrow),
+                Arc::clone(&publications),
+                scenario.publish_rate,
+                start + duration,
+            ))
+        })
+        .collect();
+
+    for partition in &scenario.partitions {
+        let group: HashSet<usize> = partition.burrows.iter().copied().collect();
+        let cut: Vec<Arc<LinkState>> = links
+            .iter()
+            .filter(|l| group.contains(&l.subscriber) != group.contains(&l.publisher))
+            .cloned()
+            .collect();
+        disruptions += cut.len();
+        let begin = start + Duration::from_secs_f64(partition.at_secs);
+        let heal = begin + Duration::from_secs_f64(partition.duration_secs);
+        let convergence = Arc::clone(&convergence);
+        tokio::spawn(async move {
+            tokio::time::sleep_until(begin).await;
+            debug!(links = cut.len(), "partition begins");
+            for l in &cut {
+                l.link.set_partitioned(true);
+            }
+            tokio::time::sleep_until(heal).await;
+            debug!(links = cut.len(), "partition heals");
+            for l in &cut {
+                l.link.set_partitioned(false);
+            }
+            watch_convergence(cut, heal, end, convergence).await;
+        });
+    }
+
+    if scenario.churn.interval_secs > 0.0 {
+        let interval = Duration::from_secs_f64(scenario.churn.interval_secs);
+        let mut rng = StdRng::seed_from_u64(scenario.seed);
+        let mut at = start + interval;
+        while at < start + duration {
+            let victim = Arc::clone(&links[rng.gen_range(0..links.len())]);
+            let convergence = Arc::clone(&convergence);
+            tokio::spawn(async move {
+                tokio::time::sleep_until(at).await;
+                debug!(
+                    subscriber = victim.subscriber,
+                    publisher = victim.publisher,
+                    "severing link"
+                );
+                victim.link.sever();
+                watch_convergence(vec![victim], at + downtime, end, convergence).await;
+            });
+            disruptions += 1;
+            at += interval;
+        }
+    }
+
+    for task in publishers {
+        let _ = task.await;
+    }
+    tokio::time::sleep_until(end).await;
+    let _ = stop_tx.send(true);
+    for task in link_tasks {
+        let _ = task.await;
+    }
+
+    let convergence = convergence
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    build_report(
+        &links,
+        &publications,
+        retransmits.count.load(Ordering::Relaxed),
+        &convergence,
+        disruptions,
+    )
+}
+
+/// Publish events on `node`'s topic at `rate` per second until `until`.
+async fn publish(
+    node: usize,
+    burrow: Arc<Burrow>,
+    publications: Arc<Publications>,
+    rate: f64,
+    until: Instant,
+) {
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        if Instant::now() >= until {
+            break;
+        }
+        // The body carries the event's topic sequence number, since
+        // fan-out replaces the Seq header with the lane's.
+        let id = publications.count(node) + 1;
+        publications.times[node]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Instant::now());
+        let (frames, _) = burrow.events.publish(TOPIC, &id.to_string());
+        burrow.sessions.broadcast(frames).await;
+    }
+}
+
+/// Keep a subscription alive: connect, subscribe, receive and ACK
+/// events, and reconnect after `downtime` whenever the tunnel closes.
+async fn run_link(
+    subscriber: Arc<Burrow>,
+    publisher: Arc<Burrow>,
+    state: Arc<LinkState>,
+    publications: Arc<Publications>,
+    handshake_timeout: Duration,
+    downtime: Duration,
+    mut stop: watch::Receiver<bool>,
+) {
+    while !*stop.borrow() {
+        let (mut tunnel, mut server_side) =
+            sim_tunnel_pair(&state.link, &subscriber.name, &publisher.name);
+        let server = Arc::clone(&publisher);
+        let server_task = tokio::spawn(async move {
+            let _ = server.handle_tunnel(&mut server_side).await;
+        });
+
+        let handshake =
+            tokio::time::timeout(handshake_timeout, subscriber.client_handshake(&mut tunnel)).await;
+        let connected = match handshake {
+            Ok(Ok(_)) => {
+                let mut sub = Frame::with_args("SUBSCRIBE", vec![TOPIC.into()]);
+                sub.set_header("Lane", LANE);
+                tunnel.send_frame(&sub).await.is_ok()
+            }
+            _ => false,
+        };
+        if connected {
+            state.connects.fetch_add(1, Ordering::Relaxed);
+            if !receive(&mut tunnel, &state, &publications, &mut stop).await {
+                let _ = tunnel.close().await;
+                server_task.abort();
+                return;
+            }
+        }
+
+        state.subscribed.store(false, Ordering::Relaxed);
+        let _ = tunnel.close().await;
+        drop(tunnel);
+        // Let the publisher finish with the old tunnel before the
+        // new one registers under the same peer ID.
+        if tokio::time::timeout(Duration::from_secs(1), server_task)
+            .await
+            .is_err()
+        {
+            debug!("publisher side of a closed tunnel did not finish");
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(downtime) => {}
+            _ = stop.changed() => {}
+        }
+    }
+}
+
+/// Receive on a subscribed tunnel until it closes (returns `true`)
+/// or the run stops (returns `false`).
+async fn receive<T: Tunnel>(
+    tunnel: &mut T,
+    state: &LinkState,
+    publications: &Publications,
+    stop: &mut watch::Receiver<bool>,
+) -> bool {
+    // Lane sequence numbers received beyond the contiguous prefix;
+    // ACKs are cumulative, so only the prefix is acknowledged.
+    let mut next_lane_seq = 1u64;
+    let mut ahead = BTreeSet::new();
+    loop {
+        let frame = tokio::select! {
+            received = tunnel.recv_frame() => match received {
+                Ok(Some(frame)) => frame,
+                _ => return true,
+            },
+            _ = stop.changed() => return false,
+        };
+        match frame.verb.as_str() {
+            "201" => state.subscribed.store(true, Ordering::Relaxed),
+            "PING" if tunnel.send_frame(&Frame::new("PONG")).await.is_err() => return true,
+            "EVENT" => {
+                let id: u64 = frame
+                    .body
+                    .as_deref()
+                    .and_then(|b| b.trim().parse().ok())
+                    .unwrap_or(0);
+                let fresh = state
+                    .received
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(id);
+                if fresh {
+                    if let Some(at) = publications.time(state.publisher, id) {
+                        state
+                            .latencies
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .push(at.elapsed());
+                        let mut newest = state.newest.lock().unwrap_or_else(|e| e.into_inner());
+                        *newest = Some(newest.map_or(at, |n| n.max(at)));
+                    }
+                } else {
+                    state.duplicates.fetch_add(1, Ordering::Relaxed);
+                }
+
+                let lane_seq: u64 = frame
+                    .header("Seq")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0);
+                if lane_seq >= next_lane_seq {
+                    ahead.insert(lane_seq);
+                    while ahead.remove(&next_lane_seq) {
+                        next_lane_seq += 1;
+                    }
+                    let mut ack = Frame::new("ACK");
+                    ack.set_header("Lane", LANE);
+                    ack.set_header("ACK", (next_lane_seq - 1).to_string());
+                    if tunnel.send_frame(&ack).await.is_err() {
+                        return true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Record, for each link, how long after `since` it first receives an
+/// event published after `since`.
+async fn watch_convergence(
+    links: Vec<Arc<LinkState>>,
+    since: Instant,
+    deadline: Instant,
+    results: Convergence,
+) {
+    tokio::time::sleep_until(since).await;
+    let mut pending = links;
+    while !pending.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(CONVERGENCE_POLL).await;
+        let now = Instant::now();
+        pending.retain(|l| {
+            let done = l.newest().is_some_and(|t| t > since);
+            if done {
+                results
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(Some(now - since));
+            }
+            !done
+        });
+    }
+    let mut results = results.lock().unwrap_or_else(|e| e.into_inner());
+    results.extend(pending.iter().map(|_| None));
+}
+
+// ── Report ─────────────────────────────────────────────────────
+
+fn millis(d: Duration) -> f64 {
+    (d.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((p / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+    Some(millis(sorted[rank]))
+}
+
+fn build_report(
+    links: &[Arc<LinkState>],
+    publications: &Publications,
+    retransmissions: u64,
+    convergence: &[Option<Duration>],
+    disruptions: usize,
+) -> serde_json::Value {
+    let mut latencies = Vec::new();
+    let mut frames = LinkStats::default();
+    let mut expected = 0u64;
+    let mut delivered = 0u64;
+    let mut duplicates = 0u64;
+    let mut reconnects = 0u64;
+    let mut per_link = Vec::new();
+    for l in links {
+        let published = publications.count(l.publisher);
+        let received = l.received.lock().unwrap_or_else(|e| e.into_inner()).len() as u64;
+        let stats = l.link.stats();
+        let connects = l.connects.load(Ordering::Relaxed);
+        expected += published;
+        delivered += received;
+        duplicates += l.duplicates.load(Ordering::Relaxed);
+        reconnects += connects.saturating_sub(1);
+        frames.sent += stats.sent;
+        frames.dropped += stats.dropped;
+        frames.delivered += stats.delivered;
+        latencies.extend(l.latencies.lock().unwrap_or_else(|e| e.into_inner()).iter());
+        per_link.push(json!({
+            "subscriber": l.subscriber,
+            "publisher": l.publisher,
+            "published": published,
+            "delivered": received,
+            "connects": connects,
+            "frames_sent": stats.sent,
+            "frames_dropped": stats.dropped,
+        }));
+    }
+    latencies.sort();
+    let converged: Vec<Duration> = convergence.iter().flatten().copied().collect();
+    let mean_convergence = (!converged.is_empty())
+        .then(|| millis(converged.iter().sum::<Duration>() / converged.len() as u32));
+
+    json!({
+        "published": (0..publications.times.len()).map(|i| publications.count(i)).sum::<u64>(),
+        "expected_deliveries": expected,
+        "deliveries": delivered,
+        "delivery_rate": if expected == 0 { 1.0 } else { delivered as f64 / expected as f64 },
+        "duplicates": duplicates,
+        "retransmissions": retransmissions,
+        "reconnects": reconnects,
+        "frames": {
+            "sent": frames.sent,
+            "dropped": frames.dropped,
+            "delivered": frames.delivered,
+        },
+        "latency_ms": {
+            "p50": percentile(&latencies, 50.0),
+            "p95": percentile(&latencies, 95.0),
+            "p99": percentile(&latencies, 99.0),
+            "max": latencies.last().map(|d| millis(*d)),
+        },
+        "convergence_ms": {
+            "disruptions": disruptions,
+            "converged": converged.len(),
+            "unconverged": convergence.len() - converged.len(),
+            "mean": mean_convergence,
+            "max": converged.iter().max().map(|d| millis(*d)),
+        },
+        "links": per_link,
+    })
+}
+
+fn print_report(scenario: &Scenario, report: &serde_json::Value) {
+    let num = |v: &serde_json::Value| match v.as_f64() {
+        Some(f) => format!("{:.1}", f),
+        None => "-".to_string(),
+    };
+    info!("simulation finished");
+    println!(
+        "{} of {} burrows, {}s publishing at {}/s each (+{}s drain), seed {}",
+        scenario.topology,
+        scenario.burrows,
+        scenario.duration_secs,
+        scenario.publish_rate,
+        scenario.drain_secs,
+        scenario.seed
+    );
+    println!(
+        "links: latency {}ms + up to {}ms jitter, loss {:.1}%, {} partitions, churn every {}s",
+        scenario.link.latency_ms,
+        scenario.link.jitter_ms,
+        scenario.link.loss * 100.0,
+        scenario.partitions.len(),
+        scenario.churn.interval_secs
+    );
+    println!();
+    println!("Events published:   {}", report["published"]);
+    println!(
+        "Deliveries:         {} of {} ({:.2}%)",
+        report["deliveries"],
+        report["expected_deliveries"],
+        report["delivery_rate"].as_f64().unwrap_or(0.0) * 100.0
+    );
+    println!("Duplicates:         {}", report["duplicates"]);
+    println!("Retransmissions:    {}", report["retransmissions"]);
+    println!("Reconnects:         {}", report["reconnects"]);
+    let frames = &report["frames"];
+    println!(
+        "Frames:             {} sent, {} dropped",
+        frames["sent"], frames["dropped"]
+    );
+    let lat = &report["latency_ms"];
+    println!(
+        "Latency (ms):       p50 {}  p95 {}  p99 {}  max {}",
+        num(&lat["p50"]),
+        num(&lat["p95"]),
+        num(&lat["p99"]),
+        num(&lat["max"])
+    );
+    let conv = &report["convergence_ms"];
+    if conv["disruptions"].as_u64().unwrap_or(0) > 0 {
+        println!(
+            "Convergence (ms):   mean {}  max {}  ({} converged, {} did not)",
+            num(&conv["mean"]),
+            num(&conv["max"]),
+            conv["converged"],
+            conv["unconverged"]
+        );
+    }
+}
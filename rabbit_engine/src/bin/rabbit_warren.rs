@@ -1,7 +1,14 @@
-//! `rabbit-warren` — launch a test warren of N burrows in a single process.
+//! `rabbit-warren` — launch a test warren of burrows in a single process.
 //!
-//! Each burrow gets its own TLS listener on `base_port + i`.  The
-//! first burrow is the "root"; all others connect to it automatically.
+//! The warren is described by a topology (see
+//! [`rabbit_engine::warren::topology`]): which burrows to start, on
+//! which ports, who dials whom, and which grants and federation
+//! anchors they set up.  Without `--topology` the classic star is
+//! used — `--count` burrows on `base_port + i`, each child dialing
+//! the root.
+//!
+//! After launch every declared link is checked; if any fails to come
+//! up within `--link-timeout` seconds the launcher exits non-zero.
 //!
 //! # Usage
 //!
@@ -9,50 +16,73 @@
 //! rabbit-warren                           # 3 burrows on ports 7443-7445
 //! rabbit-warren --count 5 --base-port 9000
 //! rabbit-warren --config-dir ./warrens    # each burrow reads <dir>/burrow-<i>/config.toml
+//! rabbit-warren --topology warren.toml    # launch a declared topology
 //! ```
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use clap::Parser;
+use futures_util::StreamExt;
 use tracing::{error, info, warn};
 
 use rabbit_engine::burrow::Burrow;
-use rabbit_engine::config::Config;
+use rabbit_engine::config::{Config, LoggingConfig};
+use rabbit_engine::daemon::{ServiceSignal, ServiceSignals};
+use rabbit_engine::logging;
+use rabbit_engine::security::permissions::Capability;
 use rabbit_engine::transport::cert::{generate_self_signed, make_server_config};
-use rabbit_engine::transport::connector::{connect, make_client_config_insecure};
+use rabbit_engine::transport::connector::connect;
 use rabbit_engine::transport::listener::RabbitListener;
 use rabbit_engine::transport::tunnel::Tunnel;
+use rabbit_engine::warren::federation::Anchor;
+use rabbit_engine::warren::topology::{BurrowRole, Topology, TopologyBurrow};
+
+/// Lifetime of the `Federation` grant given to declared anchors.
+const ANCHOR_TTL_SECS: u64 = 365 * 86400;
 
 /// Launch a test warren of multiple Rabbit burrows in one process.
 #[derive(Parser)]
 #[command(name = "rabbit-warren", version, about)]
 struct Cli {
-    /// Number of burrows to launch.
+    /// Number of burrows to launch (ignored with --topology).
     #[arg(short = 'n', long, default_value_t = 3)]
     count: usize,
 
-    /// Base port — burrow i listens on base_port + i.
+    /// Base port — burrow i listens on base_port + i (ignored with
+    /// --topology).
     #[arg(short, long, default_value_t = 7443)]
     base_port: u16,
 
     /// Directory containing per-burrow config directories.
     /// Each burrow reads `<config-dir>/burrow-<i>/config.toml`.
     /// If absent, default configs are generated in-memory.
-    #[arg(long)]
+    #[arg(long, conflicts_with = "topology")]
     config_dir: Option<PathBuf>,
+
+    /// Topology file describing the warren.
+    #[arg(short, long)]
+    topology: Option<PathBuf>,
+
+    /// Seconds to wait for every declared link to come up.
+    #[arg(long, default_value_t = 10)]
+    link_timeout: u64,
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_timer(tracing_subscriber::fmt::time::uptime())
-        .init();
-
     let cli = Cli::parse();
+    let _log = match logging::init(&LoggingConfig::default(), Path::new(".")) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("rabbit-warren: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    if cli.count < 2 {
+    if cli.topology.is_none() && cli.count < 2 {
         error!("a warren needs at least 2 burrows");
         std::process::exit(1);
     }
@@ -63,32 +93,48 @@ async fn main() {
     }
 }
 
+/// Build the topology from `--topology`, or the classic star.
+fn load_topology(cli: &Cli) -> Result<Topology, Box<dyn std::error::Error>> {
+    if let Some(path) = &cli.topology {
+        return Ok(Topology::load(path)?);
+    }
+    let mut topology = Topology::star(cli.count, cli.base_port);
+    if let Some(dir) = &cli.config_dir {
+        for (i, b) in topology.burrows.iter_mut().enumerate() {
+            b.config = Some(dir.join(format!("burrow-{}", i)).join("config.toml"));
+        }
+    }
+    Ok(topology)
+}
+
 async fn run_warren(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let mut signals = ServiceSignals::new()?;
+    let topology = load_topology(&cli)?;
     let cert_pair = generate_self_signed()?;
     let server_config = make_server_config(&cert_pair)?;
-    let client_config = make_client_config_insecure();
 
     // ── Build and start each burrow ────────────────────────────
 
     struct RunningBurrow {
         burrow: Arc<Burrow>,
         port: u16,
+        role: BurrowRole,
     }
 
     let mut running: Vec<RunningBurrow> = Vec::new();
 
-    for i in 0..cli.count {
-        let port = cli.base_port + i as u16;
-        let (config, base_dir) = load_burrow_config(&cli, i, port)?;
+    for (i, spec) in topology.burrows.iter().enumerate() {
+        let (config, base_dir) = load_burrow_config(&topology, spec, i)?;
         let burrow = Arc::new(Burrow::from_config(&config, &base_dir)?);
 
-        let listen_addr = format!("127.0.0.1:{}", port);
+        let listen_addr = format!("127.0.0.1:{}", config.network.port);
         let listener = RabbitListener::bind(&listen_addr, Arc::clone(&server_config)).await?;
         let actual_port = listener.local_addr()?.port();
 
         info!(
             index = i,
             name = %burrow.name,
+            role = spec.role.label(),
             id = %burrow.burrow_id(),
             port = actual_port,
             "burrow started"
@@ -119,87 +165,151 @@ async fn run_warren(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
         running.push(RunningBurrow {
             burrow,
             port: actual_port,
+            role: spec.role,
         });
     }
 
-    // ── Connect children to root ───────────────────────────────
+    // ── Grants and federation anchors ──────────────────────────
+
+    for (i, spec) in topology.burrows.iter().enumerate() {
+        let mut caps = running[i]
+            .burrow
+            .capabilities
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        for grant in &spec.grants {
+            let (Some(j), Some(cap)) = (
+                topology.index_of(&grant.to),
+                Capability::from_label(&grant.capability),
+            ) else {
+                continue;
+            };
+            let target = running[j].burrow.burrow_id();
+            info!(from = %spec.name, to = %grant.to, capability = cap.label(), "granting");
+            caps.grant(&target, cap, grant.ttl);
+        }
+    }
+    for (i, j) in topology.anchors() {
+        let (truster, anchor) = (&running[i], &running[j]);
+        info!(
+            burrow = %topology.burrows[i].name,
+            anchor = %topology.burrows[j].name,
+            "registering federation anchor"
+        );
+        let id = anchor.burrow.burrow_id();
+        let address = format!("127.0.0.1:{}", anchor.port);
+        truster
+            .burrow
+            .register_anchor(Anchor::new(&id, Some(&address)))?;
+        truster
+            .burrow
+            .capabilities
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .grant(&id, Capability::Federation, ANCHOR_TTL_SECS);
+    }
+
+    // ── Bring up declared links ────────────────────────────────
 
-    let root_addr = format!("127.0.0.1:{}", running[0].port);
+    let links = topology.links();
+    let mut link_up: Vec<Arc<AtomicBool>> = Vec::new();
 
-    for (i, rb) in running.iter().enumerate().skip(1) {
-        let burrow = Arc::clone(&rb.burrow);
-        let addr = root_addr.clone();
-        let cc = Arc::clone(&client_config);
+    for &(from, to) in &links {
+        let dialer = &running[from];
+        let listener = &running[to];
+        let addr = format!("127.0.0.1:{}", listener.port);
 
         info!(
-            child = i,
-            child_name = %burrow.name,
-            root_addr = %addr,
-            "connecting to root"
+            from = %dialer.burrow.name,
+            to = %listener.burrow.name,
+            addr = %addr,
+            "connecting"
         );
 
-        // Register the child in the root's peer table so /warren
-        // discovery works.
-        let child_addr = format!("127.0.0.1:{}", rb.port);
-        let mut child_peer = rabbit_engine::warren::peers::PeerInfo::new(
-            rb.burrow.burrow_id(),
-            &child_addr,
-            &rb.burrow.name,
+        // Register the dialer in the listener's peer table so
+        // /warren discovery works.
+        let dialer_addr = format!("127.0.0.1:{}", dialer.port);
+        let mut dialer_peer = rabbit_engine::warren::peers::PeerInfo::new(
+            dialer.burrow.burrow_id(),
+            &dialer_addr,
+            &dialer.burrow.name,
         );
-        child_peer.connected = true;
-        child_peer.last_seen = std::time::SystemTime::now()
+        dialer_peer.connected = true;
+        dialer_peer.last_seen = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
-        running[0].burrow.peers.register(child_peer).await;
+        listener.burrow.peers.register(dialer_peer).await;
+
+        let up = Arc::new(AtomicBool::new(false));
+        link_up.push(Arc::clone(&up));
 
-        let burrow_for_task = Arc::clone(&burrow);
+        let burrow_for_task = Arc::clone(&dialer.burrow);
         tokio::spawn(async move {
-            match connect_and_dispatch(&burrow_for_task, &addr, cc).await {
+            match connect_and_dispatch(&burrow_for_task, &addr, &up).await {
                 Ok(id) => {
-                    info!(child_name = %burrow_for_task.name, remote_id = %id, "peer session ended")
+                    info!(name = %burrow_for_task.name, remote_id = %id, "peer session ended")
                 }
                 Err(e) => {
-                    warn!(child_name = %burrow_for_task.name, err = %e, "peer connection failed")
+                    warn!(name = %burrow_for_task.name, err = %e, "peer connection failed")
                 }
             }
         });
 
         // Small delay so connections don't race.
-        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    // ── Validate links ─────────────────────────────────────────
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(cli.link_timeout);
+    while !link_up.iter().all(|u| u.load(Ordering::Relaxed))
+        && tokio::time::Instant::now() < deadline
+    {
+        tokio::time::sleep(Duration::from_millis(50)).await;
     }
 
-    // ── Print status summary ───────────────────────────────────
-
-    // Give connections a moment to establish.
-    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-
-    println!();
-    println!("=== Warren Status ===");
-    println!();
-    for (i, rb) in running.iter().enumerate() {
-        let role = if i == 0 { "root" } else { "child" };
-        let peer_count = rb.burrow.peers.count().await;
-        println!(
-            "  [{}] {} (port {}) — {} — {} peers",
-            role,
-            rb.burrow.name,
-            rb.port,
-            rb.burrow.burrow_id(),
-            peer_count,
+    // ── Report status ──────────────────────────────────────────
+
+    for rb in &running {
+        info!(
+            name = %rb.burrow.name,
+            role = rb.role.label(),
+            port = rb.port,
+            id = %rb.burrow.burrow_id(),
+            peers = rb.burrow.peers.count().await,
+            "warren member"
         );
     }
-    println!();
-    println!("Press Ctrl-C to shut down the warren.");
-    println!();
+    let mut down = 0;
+    for (&(from, to), up) in links.iter().zip(&link_up) {
+        let (from, to) = (&running[from].burrow.name, &running[to].burrow.name);
+        if up.load(Ordering::Relaxed) {
+            info!(%from, %to, "link up");
+        } else {
+            error!(%from, %to, "link down");
+            down += 1;
+        }
+    }
+    if down > 0 {
+        return Err(format!(
+            "{} of {} declared links failed to come up",
+            down,
+            links.len()
+        )
+        .into());
+    }
+    info!("warren running; press Ctrl-C to shut it down");
 
-    // Wait for shutdown.
-    tokio::signal::ctrl_c().await?;
+    // Wait for SIGTERM/SIGINT; a warren has nothing to reload.
+    while signals.recv().await != ServiceSignal::Shutdown {
+        info!("ignoring reload request");
+    }
     info!("shutting down warren");
 
     for rb in &running {
-        if let Err(e) = rb.burrow.save_trust() {
-            warn!(name = %rb.burrow.name, err = %e, "failed to save trust cache");
+        if let Err(e) = rb.burrow.shutdown().await {
+            warn!(name = %rb.burrow.name, err = %e, "failed to save state");
         }
     }
 
@@ -207,15 +317,21 @@ async fn run_warren(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-/// Connect to a peer and run the dispatch loop.
+/// Connect to a peer and run the dispatch loop.  `up` is set once the
+/// handshake, and the federation link's authentication if the peer is
+/// linked, succeeds.
 async fn connect_and_dispatch(
     burrow: &Burrow,
     addr: &str,
-    client_config: Arc<rustls::ClientConfig>,
+    up: &AtomicBool,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let mut tunnel = connect(addr, client_config, "localhost").await?;
+    let mut tunnel = connect(addr, burrow.client_tls_config(None), "localhost").await?;
     let server_id = burrow.client_handshake(&mut tunnel).await?;
     info!(remote_id = %server_id, "handshake complete");
+    if burrow.federation.link(&server_id).is_some() {
+        burrow.authenticate_link(&mut tunnel, &server_id).await?;
+    }
+    up.store(true, Ordering::Relaxed);
 
     let peer_info =
         rabbit_engine::warren::peers::PeerInfo::new(server_id.clone(), addr.to_string(), "");
@@ -237,39 +353,50 @@ async fn connect_and_dispatch(
         for extra in &result.extras {
             tunnel.send_frame(extra).await?;
         }
+        if let Some(mut replay) = result.replay {
+            while let Some(event) = replay.frames.next().await {
+                tunnel.send_frame(&event).await?;
+            }
+        }
     }
 
     burrow.peers.mark_disconnected(&server_id).await;
     Ok(server_id)
 }
 
-/// Load or generate config for burrow `index`.
+/// Load or generate config for the burrow at `index`.
+///
+/// A declared config file keeps its own name; generated configs use
+/// the topology name.  A non-zero topology port always wins.
 fn load_burrow_config(
-    cli: &Cli,
+    topology: &Topology,
+    spec: &TopologyBurrow,
     index: usize,
-    port: u16,
 ) -> Result<(Config, PathBuf), Box<dyn std::error::Error>> {
-    if let Some(dir) = &cli.config_dir {
-        let burrow_dir = dir.join(format!("burrow-{}", index));
-        let config_path = burrow_dir.join("config.toml");
+    // Grants and anchors are keyed by burrow ID, which only an
+    // authenticating burrow learns.
+    let needs_auth = !spec.grants.is_empty() || !spec.anchors.is_empty();
+
+    let (mut config, base_dir) = if let Some(config_path) = topology.config_path(spec) {
         let config = Config::load(&config_path)?;
-        Ok((config, burrow_dir))
+        if needs_auth && !config.identity.require_auth {
+            warn!(
+                burrow = %spec.name,
+                "grants or anchors declared but require_auth is false; peers will be anonymous"
+            );
+        }
+        let burrow_dir = config_path
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_else(|| PathBuf::from("."));
+        (config, burrow_dir)
     } else {
-        // Generate an in-memory default config.
-        let name = if index == 0 {
-            "warren-root".to_string()
-        } else {
-            format!("burrow-{}", index)
-        };
-
+        let name = &spec.name;
         let toml_str = format!(
             r#"
 [identity]
 name = "{name}"
-require_auth = false
-
-[network]
-port = {port}
+require_auth = {needs_auth}
 
 [[content.menus]]
 selector = "/"
@@ -285,12 +412,21 @@ body = "This is {name}, part of a test warren."
 "#
         );
 
-        let config = Config::parse(&toml_str)?;
+        let mut config = Config::parse(&toml_str)?;
+        config.network.port = 0;
 
         // Use a temporary directory for storage.
-        let base_dir = std::env::temp_dir().join(format!("rabbit-warren-{}-{}", port, index));
+        let base_dir = std::env::temp_dir().join(format!("rabbit-warren-{}-{}", spec.port, index));
         std::fs::create_dir_all(&base_dir)?;
 
-        Ok((config, base_dir))
+        (config, base_dir)
+    };
+
+    if spec.port != 0 {
+        config.network.port = spec.port;
+    }
+    if spec.role == BurrowRole::Headed {
+        config.gui.enabled = true;
     }
+    Ok((config, base_dir))
 }
@@ -0,0 +1,512 @@
+//! `rabbitctl` — manage a running burrow through its admin socket.
+//!
+//! # Usage
+//!
+//! ```text
+//! rabbitctl status                           # identity and counters
+//! rabbitctl peers                            # peer table with grants
+//! rabbitctl stats                            # per-tunnel and per-lane traffic
+//! rabbitctl listeners                        # connections accepted and refused
+//! rabbitctl links                            # federation links and their health
+//! rabbitctl close-link <peer-id> --reason retired
+//! rabbitctl anchors                          # anchors trusted here
+//! rabbitctl add-anchor <anchor-id> --address hub.example.org:7443
+//! rabbitctl forget-anchor <anchor-id>
+//! rabbitctl grant <peer-id> Publish --ttl 600
+//! rabbitctl prune-topic /q/chat --keep 100
+//! rabbitctl verify-topic /q/chat             # check a log for tampering
+//! rabbitctl snapshot backups/burrow.tar      # back up persistent state
+//! rabbitctl dead-letters                     # undeliverable frames
+//! rabbitctl retry-dead-letter 3
+//! rabbitctl purge-dead-letters --older-than 86400
+//! rabbitctl --socket /run/rabbit/admin.sock --json status
+//! ```
+//!
+//! The burrow must be serving with `[admin] socket` set in its config.
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use serde_json::Value;
+
+use rabbit_engine::admin::{request, AdminRequest, DEFAULT_GRANT_TTL};
+
+/// Manage a running Rabbit burrow.
+#[derive(Parser)]
+#[command(name = "rabbitctl", version, about)]
+struct Cli {
+    /// Path to the burrow's admin socket.
+    #[arg(short, long, default_value = "data/admin.sock")]
+    socket: PathBuf,
+
+    /// Print the raw JSON result.
+    #[arg(long)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Show the burrow's identity and counters.
+    Status,
+
+    /// List known peers and their capabilities.
+    Peers,
+
+    /// Show traffic and lane statistics for each open tunnel.
+    Stats,
+
+    /// Show connections accepted and refused by each listener.
+    Listeners,
+
+    /// Show the state and health of each federation link.
+    Links,
+
+    /// Tear down the federation link to a peer.
+    CloseLink {
+        /// Burrow ID at the other end of the link.
+        peer: String,
+
+        /// Why, as told to the peer.
+        #[arg(long, default_value = "")]
+        reason: String,
+    },
+
+    /// List the anchors this burrow trusts.
+    Anchors,
+
+    /// Trust an anchor, across restarts.
+    AddAnchor {
+        /// The anchor's burrow ID.
+        id: String,
+
+        /// host:port to dial it at on startup.
+        #[arg(long)]
+        address: Option<String>,
+    },
+
+    /// Stop trusting an anchor.
+    ForgetAnchor {
+        /// The anchor's burrow ID.
+        id: String,
+    },
+
+    /// Grant a capability to a peer.
+    Grant {
+        /// Burrow ID receiving the capability.
+        peer: String,
+
+        /// Capability label (e.g. Publish, Subscribe, Federation).
+        capability: String,
+
+        /// Grant lifetime in seconds.
+        #[arg(long, default_value_t = DEFAULT_GRANT_TTL)]
+        ttl: u64,
+    },
+
+    /// Revoke a capability from a peer, or all of its capabilities.
+    Ungrant {
+        /// Burrow ID losing the capability.
+        peer: String,
+
+        /// Capability label; all capabilities if omitted.
+        capability: Option<String>,
+    },
+
+    /// Drop old events from a topic.
+    PruneTopic {
+        /// Topic path (e.g. /q/chat).
+        topic: String,
+
+        /// Number of newest events to keep.
+        #[arg(long, default_value_t = 0)]
+        keep: usize,
+    },
+
+    /// Check a topic's log for tampering or truncation.
+    VerifyTopic {
+        /// Topic path (e.g. /q/chat).
+        topic: String,
+    },
+
+    /// Write a snapshot of the burrow's persistent state.
+    Snapshot {
+        /// Archive to write.
+        path: PathBuf,
+    },
+
+    /// List frames that could not be delivered.
+    DeadLetters,
+
+    /// Send an undeliverable frame to its peer again.
+    RetryDeadLetter {
+        /// Dead-letter ID, as shown by `dead-letters`.
+        id: u64,
+    },
+
+    /// Drop undeliverable frames.
+    PurgeDeadLetters {
+        /// Only drop entries at least this many seconds old.
+        #[arg(long)]
+        older_than: Option<u64>,
+    },
+
+    /// List issued session tokens.
+    Sessions,
+
+    /// End a peer's sessions (or one session), closing its tunnels.
+    Revoke {
+        /// Burrow ID whose sessions are revoked.
+        #[arg(required_unless_present = "session", conflicts_with = "session")]
+        peer: Option<String>,
+
+        /// Revoke only this session token.
+        #[arg(long)]
+        session: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let req = match cli.command {
+        Commands::Status => AdminRequest::Status,
+        Commands::Peers => AdminRequest::Peers,
+        Commands::Stats => AdminRequest::Stats,
+        Commands::Listeners => AdminRequest::Listeners,
+        Commands::Links => AdminRequest::Links,
+        Commands::CloseLink { peer, reason } => AdminRequest::CloseLink { peer, reason },
+        Commands::Anchors => AdminRequest::Anchors,
+        Commands::AddAnchor { id, address } => AdminRequest::AddAnchor { id, address },
+        Commands::ForgetAnchor { id } => AdminRequest::ForgetAnchor { id },
+        Commands::Grant {
+            peer,
+            capability,
+            ttl,
+        } => AdminRequest::Grant {
+            peer,
+            capability,
+            ttl,
+        },
+        Commands::Ungrant { peer, capability } => AdminRequest::Ungrant { peer, capability },
+        Commands::PruneTopic { topic, keep } => AdminRequest::PruneTopic { topic, keep },
+        Commands::VerifyTopic { topic } => AdminRequest::VerifyTopic { topic },
+        // The burrow resolves paths from its own working directory.
+        Commands::Snapshot { path } => AdminRequest::Snapshot {
+            path: std::path::absolute(&path).unwrap_or(path),
+        },
+        Commands::DeadLetters => AdminRequest::DeadLetters,
+        Commands::RetryDeadLetter { id } => AdminRequest::RetryDeadLetter { id },
+        Commands::PurgeDeadLetters { older_than } => AdminRequest::PurgeDeadLetters { older_than },
+        Commands::Sessions => AdminRequest::Sessions,
+        Commands::Revoke { peer, session } => AdminRequest::Revoke { peer, session },
+    };
+
+    let response = match request(&cli.socket, &req).await {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("rabbitctl: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if !response.ok {
+        eprintln!(
+            "rabbitctl: {}",
+            response.error.as_deref().unwrap_or("command failed")
+        );
+        std::process::exit(1);
+    }
+
+    let result = response.result.unwrap_or(Value::Null);
+    if cli.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&result).unwrap_or_default()
+        );
+        return;
+    }
+    match req {
+        AdminRequest::Status => print_status(&result),
+        AdminRequest::Peers => print_peers(&result),
+        AdminRequest::Stats => print_stats(&result),
+        AdminRequest::Listeners => print_listeners(&result),
+        AdminRequest::Links => print_links(&result),
+        AdminRequest::CloseLink { .. } => {
+            println!("Closed federation link to {}", text(&result["peer"]))
+        }
+        AdminRequest::Anchors => print_anchors(&result),
+        AdminRequest::AddAnchor { .. } => {
+            if result["added"].as_bool() == Some(true) {
+                println!("Trusting anchor {}", text(&result["id"]))
+            } else {
+                println!("Already trusting anchor {}", text(&result["id"]))
+            }
+        }
+        AdminRequest::ForgetAnchor { .. } => {
+            if result["forgotten"].as_bool() == Some(true) {
+                println!("No longer trusting anchor {}", text(&result["id"]))
+            } else {
+                println!("{} was not a registered anchor", text(&result["id"]))
+            }
+        }
+        AdminRequest::Grant { .. } => println!(
+            "Granted {} to {} for {}s",
+            text(&result["capability"]),
+            text(&result["peer"]),
+            result["ttl"]
+        ),
+        AdminRequest::Ungrant { .. } => println!(
+            "Revoked {} from {}",
+            text(&result["capability"]),
+            text(&result["peer"])
+        ),
+        AdminRequest::PruneTopic { .. } => println!(
+            "Pruned {}: removed {}, {} remaining",
+            text(&result["topic"]),
+            result["removed"],
+            result["remaining"]
+        ),
+        AdminRequest::VerifyTopic { .. } => {
+            println!(
+                "{}: {} records, {} chained, {} signed",
+                text(&result["topic"]),
+                result["records"],
+                result["chained"],
+                result["signed"]
+            );
+            let problems = result["problems"].as_array().cloned().unwrap_or_default();
+            if problems.is_empty() {
+                println!("No sign of tampering");
+            }
+            for problem in &problems {
+                println!("  {}", text(problem));
+            }
+        }
+        AdminRequest::Snapshot { .. } => println!(
+            "Wrote {}: {} files, {} bytes",
+            text(&result["path"]),
+            result["files"],
+            result["bytes"]
+        ),
+        AdminRequest::DeadLetters => print_dead_letters(&result),
+        AdminRequest::RetryDeadLetter { id } => {
+            println!(
+                "Requeued dead letter {} to {}",
+                id,
+                text(&result["peer_id"])
+            )
+        }
+        AdminRequest::PurgeDeadLetters { .. } => println!(
+            "Purged {} dead letters, {} remaining",
+            result["purged"], result["remaining"]
+        ),
+        AdminRequest::Sessions => print_sessions(&result),
+        AdminRequest::Revoke { .. } => println!("Revoked {} sessions", result["revoked"]),
+    }
+}
+
+/// Render a JSON value without the quotes around strings.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn print_status(status: &Value) {
+    println!("Burrow:      {}", text(&status["name"]));
+    println!("ID:          {}", text(&status["id"]));
+    println!(
+        "Peers:       {} ({} connected)",
+        status["peers"], status["connected_peers"]
+    );
+    println!("Connections: {}", status["connections"]);
+    println!("Sessions:    {}", status["sessions"]);
+    println!("Topics:      {}", status["topics"]);
+    println!("Routes:      {}", status["routes"]);
+    println!("Trusted:     {}", status["trusted"]);
+    println!("Dead letters: {}", status["dead_letters"]);
+}
+
+fn print_peers(peers: &Value) {
+    let peers = peers.as_array().map(Vec::as_slice).unwrap_or_default();
+    if peers.is_empty() {
+        println!("(no peers)");
+        return;
+    }
+    for peer in peers {
+        let caps: Vec<String> = peer["capabilities"]
+            .as_array()
+            .map(|a| a.iter().map(text).collect())
+            .unwrap_or_default();
+        println!(
+            "{} {:<20} {:<22} {}",
+            if peer["connected"] == Value::Bool(true) {
+                "*"
+            } else {
+                " "
+            },
+            text(&peer["name"]),
+            text(&peer["address"]),
+            text(&peer["id"]),
+        );
+        if !caps.is_empty() {
+            println!("    capabilities: {}", caps.join(", "));
+        }
+    }
+}
+
+fn print_stats(tunnels: &Value) {
+    let tunnels = tunnels.as_array().map(Vec::as_slice).unwrap_or_default();
+    if tunnels.is_empty() {
+        println!("(no open tunnels)");
+        return;
+    }
+    for tunnel in tunnels {
+        let rtt = match tunnel["smoothed_rtt_ms"].as_u64() {
+            Some(ms) => format!("{} ms", ms),
+            None => "-".into(),
+        };
+        println!("{}", text(&tunnel["peer_id"]));
+        println!(
+            "  sent {} frames / {} bytes, received {} frames / {} bytes, {} retransmits, RTT {}",
+            tunnel["frames_sent"],
+            tunnel["bytes_sent"],
+            tunnel["frames_received"],
+            tunnel["bytes_received"],
+            tunnel["retransmits"],
+            rtt
+        );
+        let lanes = tunnel["lanes"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for lane in lanes {
+            println!(
+                "  lane {:<5} {:<11} prio {} credit {:<4} queued {:<4} in flight {:<4} sent {} recv {}",
+                lane["lane"],
+                text(&lane["state"]),
+                lane["priority"],
+                lane["credits"],
+                lane["queued"],
+                lane["in_flight"],
+                lane["frames_sent"],
+                lane["frames_received"]
+            );
+        }
+    }
+}
+
+fn print_listeners(listeners: &Value) {
+    let listeners = listeners.as_array().map(Vec::as_slice).unwrap_or_default();
+    if listeners.is_empty() {
+        println!("(no listeners)");
+        return;
+    }
+    for listener in listeners {
+        println!(
+            "{}  {} open, {} accepted, refused {} full / {} rate, {} handshake timeouts, {} failed",
+            text(&listener["address"]),
+            listener["active"],
+            listener["accepted"],
+            listener["refused_full"],
+            listener["refused_rate"],
+            listener["handshake_timeouts"],
+            listener["accept_failures"]
+        );
+    }
+}
+
+fn print_links(links: &Value) {
+    let links = links.as_array().map(Vec::as_slice).unwrap_or_default();
+    if links.is_empty() {
+        println!("(no federation links)");
+        return;
+    }
+    for link in links {
+        let detail = text(&link["detail"]);
+        println!(
+            "{}  {} {}, {}, last seen {}{}",
+            text(&link["peer"]),
+            text(&link["state"]),
+            text(&link["health"]),
+            text(&link["auth"]),
+            link["last_seen"],
+            if detail.is_empty() {
+                String::new()
+            } else {
+                format!("  ({})", detail)
+            }
+        );
+        let services: Vec<&str> = link["services"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        if !services.is_empty() {
+            println!("    services: {}", services.join(", "));
+        }
+    }
+}
+
+fn print_anchors(anchors: &Value) {
+    let anchors = anchors.as_array().map(Vec::as_slice).unwrap_or_default();
+    if anchors.is_empty() {
+        println!("(no anchors)");
+        return;
+    }
+    for anchor in anchors {
+        match anchor["address"].as_str() {
+            Some(address) => println!("{}  at {}", text(&anchor["id"]), address),
+            None => println!("{}", text(&anchor["id"])),
+        }
+    }
+}
+
+fn print_dead_letters(entries: &Value) {
+    let entries = entries.as_array().map(Vec::as_slice).unwrap_or_default();
+    if entries.is_empty() {
+        println!("(no dead letters)");
+        return;
+    }
+    for entry in entries {
+        let start_line = entry["frame"]
+            .as_str()
+            .and_then(|f| f.lines().next())
+            .unwrap_or("");
+        println!(
+            "{:>4} lane {:<5} {} {}",
+            entry["id"],
+            entry["lane"],
+            text(&entry["peer_id"]),
+            start_line
+        );
+        println!("     {}", text(&entry["reason"]));
+    }
+}
+
+fn print_sessions(sessions: &Value) {
+    let sessions = sessions.as_array().map(Vec::as_slice).unwrap_or_default();
+    if sessions.is_empty() {
+        println!("(no sessions)");
+        return;
+    }
+    for session in sessions {
+        let state = if session["connected"].as_bool().unwrap_or(false) {
+            "connected"
+        } else {
+            "idle"
+        };
+        println!(
+            "{}  {}  expires {}  {}",
+            text(&session["token"]),
+            text(&session["peer_id"]),
+            session["expires_at"],
+            state
+        );
+    }
+}
@@ -0,0 +1,553 @@
+//! Bridging MQTT brokers into a warren.
+//!
+//! The `rabbit-bridge` binary connects to an MQTT broker and to a
+//! burrow, and forwards messages between MQTT topics and Rabbit event
+//! topics according to a list of mappings, so devices that only speak
+//! MQTT can publish into a warren and follow its events.
+//!
+//! ```toml
+//! [broker]
+//! address = "127.0.0.1:1883"
+//! client_id = "rabbit-bridge"
+//! username = "bridge"          # optional
+//! password = "secret"          # optional
+//!
+//! [rabbit]
+//! burrow = "127.0.0.1:7443"
+//! identity = "bridge.key"      # created on first run
+//!
+//! # Every sensor under home/ becomes a topic under /q/home/.
+//! [[mapping]]
+//! mqtt = "home/#"
+//! rabbit = "/q/home/#"
+//! direction = "mqtt-to-rabbit"
+//!
+//! # Warren announcements are shown on a display; its replies come back.
+//! [[mapping]]
+//! mqtt = "display/announce"
+//! rabbit = "/q/announce"
+//! direction = "both"
+//! qos = 0
+//! ```
+//!
+//! A mapping's `qos` is the MQTT QoS used in both directions and picks
+//! the Rabbit delivery class: QoS 0 subscribes as a best-effort
+//! `stream`, QoS 1 and 2 as acknowledged `event`s.  QoS 2 is carried
+//! as QoS 1 on the MQTT side.
+//!
+//! MQTT filters may use `+` and `#`.  A Rabbit topic ending in `/#`
+//! pairs with an MQTT filter ending in `/#`: the levels under the MQTT
+//! prefix are appended to the Rabbit prefix.  Otherwise every matching
+//! MQTT message goes to the one Rabbit topic.  Rabbit subscriptions
+//! cannot use wildcards, so mappings that forward into MQTT need a
+//! concrete Rabbit topic and MQTT topic.
+
+pub mod mqtt;
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+use crate::security::identity::Identity;
+
+/// Number of forwarded messages remembered for loop suppression.
+const ECHO_CAPACITY: usize = 256;
+
+/// Top-level bridge configuration.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BridgeConfig {
+    /// MQTT broker connection.
+    pub broker: BrokerConfig,
+    /// Burrow connection and the bridge's own identity.
+    pub rabbit: RabbitConfig,
+    /// Topic mappings, tried in order; the first match wins.
+    #[serde(rename = "mapping")]
+    pub mappings: Vec<Mapping>,
+    /// Directory relative paths are resolved against.
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+/// `[broker]` section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BrokerConfig {
+    /// Broker address (default: `127.0.0.1:1883`).
+    pub address: String,
+    /// MQTT client identifier (default: `rabbit-bridge`).
+    pub client_id: String,
+    /// Optional user name.
+    pub username: Option<String>,
+    /// Optional password.
+    pub password: Option<String>,
+    /// Keepalive interval in seconds (default: 30).
+    pub keepalive_secs: u16,
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:1883".into(),
+            client_id: "rabbit-bridge".into(),
+            username: None,
+            password: None,
+            keepalive_secs: 30,
+        }
+    }
+}
+
+/// `[rabbit]` section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RabbitConfig {
+    /// Burrow to connect to (default: `127.0.0.1:7443`).
+    pub burrow: String,
+    /// Identity key file for the bridge (default: `bridge.key`).
+    /// Generated on first run, so the bridge keeps one burrow ID that
+    /// capabilities can be granted to.
+    pub identity: PathBuf,
+}
+
+impl Default for RabbitConfig {
+    fn default() -> Self {
+        Self {
+            burrow: "127.0.0.1:7443".into(),
+            identity: PathBuf::from("bridge.key"),
+        }
+    }
+}
+
+/// Which way a mapping forwards messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    /// MQTT messages are published into the warren.
+    #[default]
+    MqttToRabbit,
+    /// Rabbit events are published to the broker.
+    RabbitToMqtt,
+    /// Both of the above.
+    Both,
+}
+
+/// One MQTT ↔ Rabbit topic mapping.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Mapping {
+    /// MQTT topic or filter.
+    pub mqtt: String,
+    /// Rabbit topic, e.g. `/q/home`, optionally ending in `/#`.
+    pub rabbit: String,
+    /// Forwarding direction (default: `mqtt-to-rabbit`).
+    #[serde(default)]
+    pub direction: Direction,
+    /// MQTT QoS, 0–2 (default: 1).
+    #[serde(default = "default_qos")]
+    pub qos: u8,
+}
+
+fn default_qos() -> u8 {
+    1
+}
+
+impl Mapping {
+    /// Whether MQTT messages are forwarded into the warren.
+    pub fn inbound(&self) -> bool {
+        self.direction != Direction::RabbitToMqtt
+    }
+
+    /// Whether Rabbit events are forwarded to the broker.
+    pub fn outbound(&self) -> bool {
+        self.direction != Direction::MqttToRabbit
+    }
+
+    /// The Rabbit topic for a message received on `mqtt_topic`, or
+    /// `None` if the mapping's filter does not match it.
+    pub fn rabbit_topic_for(&self, mqtt_topic: &str) -> Option<String> {
+        if !mqtt::topic_matches(&self.mqtt, mqtt_topic) {
+            return None;
+        }
+        let Some(prefix) = self.rabbit.strip_suffix("/#") else {
+            return Some(self.rabbit.clone());
+        };
+        let mqtt_prefix = self.mqtt.strip_suffix('#').unwrap_or(&self.mqtt);
+        let rest = mqtt_topic
+            .strip_prefix(mqtt_prefix)
+            .unwrap_or("")
+            .trim_matches('/');
+        if rest.is_empty() {
+            Some(prefix.to_string())
+        } else {
+            Some(format!("{}/{}", prefix, rest))
+        }
+    }
+
+    /// The MQTT topic for an event on `rabbit_topic`, if this mapping
+    /// forwards it.
+    pub fn mqtt_topic_for(&self, rabbit_topic: &str) -> Option<&str> {
+        (self.outbound() && self.rabbit == rabbit_topic).then_some(self.mqtt.as_str())
+    }
+
+    /// The `QoS` header for the Rabbit subscription.
+    pub fn rabbit_qos(&self) -> &'static str {
+        if self.qos == 0 {
+            "stream"
+        } else {
+            "event"
+        }
+    }
+}
+
+impl BridgeConfig {
+    /// Load and validate a bridge config file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ProtocolError::InternalError(format!(
+                "failed to read bridge config {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let mut config = Self::parse(&content)?;
+        config.base_dir = path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("."));
+        Ok(config)
+    }
+
+    /// Parse and validate a bridge config from a TOML string.
+    pub fn parse(toml_str: &str) -> Result<Self, ProtocolError> {
+        let config: Self = toml::from_str(toml_str).map_err(|e| {
+            ProtocolError::InternalError(format!("invalid bridge config TOML: {}", e))
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that every mapping can be carried out.
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        if self.mappings.is_empty() {
+            return Err(ProtocolError::InternalError(
+                "bridge config declares no mappings".into(),
+            ));
+        }
+        for (i, m) in self.mappings.iter().enumerate() {
+            let fail = |why: &str| {
+                Err(ProtocolError::InternalError(format!(
+                    "mapping {} ({} ↔ {}): {}",
+                    i + 1,
+                    m.mqtt,
+                    m.rabbit,
+                    why
+                )))
+            };
+            if m.mqtt.is_empty() || !m.rabbit.starts_with('/') {
+                return fail("needs an MQTT topic and a Rabbit topic starting with '/'");
+            }
+            if m.qos > 2 {
+                return fail("qos must be 0, 1 or 2");
+            }
+            let mqtt_wild = m.mqtt.contains(['+', '#']);
+            let rabbit_wild = m.rabbit.ends_with("/#");
+            if m.rabbit[..m.rabbit.len() - if rabbit_wild { 2 } else { 0 }].contains(['+', '#']) {
+                return fail("Rabbit topics may only use '#' as the last level");
+            }
+            if rabbit_wild && !m.mqtt.ends_with('#') {
+                return fail("a Rabbit topic ending in '/#' needs an MQTT filter ending in '#'");
+            }
+            if m.outbound() && (mqtt_wild || rabbit_wild) {
+                return fail("mappings that forward to MQTT need concrete topics");
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve a path from the config relative to its file.
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.base_dir.join(path)
+        }
+    }
+
+    /// Load the bridge identity, generating and saving one if the key
+    /// file does not exist yet.
+    pub fn load_identity(&self) -> Result<Identity, ProtocolError> {
+        Identity::load_or_create(self.resolve(&self.rabbit.identity))
+    }
+
+    /// The first inbound mapping matching an MQTT topic, with the
+    /// Rabbit topic to publish to.
+    pub fn route_inbound(&self, mqtt_topic: &str) -> Option<(&Mapping, String)> {
+        self.mappings
+            .iter()
+            .filter(|m| m.inbound())
+            .find_map(|m| m.rabbit_topic_for(mqtt_topic).map(|t| (m, t)))
+    }
+
+    /// The first outbound mapping for a Rabbit topic.
+    pub fn route_outbound(&self, rabbit_topic: &str) -> Option<&Mapping> {
+        self.mappings
+            .iter()
+            .find(|m| m.mqtt_topic_for(rabbit_topic).is_some())
+    }
+}
+
+/// Recently forwarded messages, used to drop the copy that comes
+/// straight back on a `both` mapping.
+#[derive(Debug, Default)]
+pub struct EchoFilter {
+    recent: VecDeque<(String, Vec<u8>)>,
+}
+
+impl EchoFilter {
+    /// Create an empty filter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember a message the bridge has just sent to `topic`.
+    pub fn sent(&mut self, topic: &str, payload: &[u8]) {
+        if self.recent.len() == ECHO_CAPACITY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((topic.to_string(), payload.to_vec()));
+    }
+
+    /// Whether a message arriving on `topic` is the echo of one the
+    /// bridge sent; the matching entry is consumed.
+    pub fn is_echo(&mut self, topic: &str, payload: &[u8]) -> bool {
+        match self
+            .recent
+            .iter()
+            .position(|(t, p)| t == topic && p == payload)
+        {
+            Some(i) => {
+                self.recent.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Where each Rabbit topic's subscription resumes: the `Event-Seq` of
+/// the last event forwarded from it.  An event's `Seq` numbers it on
+/// the tunnel's lane, which starts again with every tunnel, so it is
+/// only good for acknowledging the event.
+#[derive(Debug, Default)]
+pub struct Cursors {
+    last: HashMap<String, u64>,
+}
+
+impl Cursors {
+    /// Create cursors with nothing forwarded yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The SUBSCRIBE for `mapping` on `lane`, resuming after the last
+    /// event forwarded from its topic.  Before any has been, `Since` is
+    /// left out and the burrow resumes after the last event the bridge
+    /// acknowledged, or replays the topic's history.
+    pub fn subscribe(&self, mapping: &Mapping, lane: u16) -> Frame {
+        let mut sub = Frame::with_args("SUBSCRIBE", vec![mapping.rabbit.clone()]);
+        sub.set_header("Lane", lane.to_string());
+        sub.set_header("QoS", mapping.rabbit_qos());
+        if let Some(seq) = self.last.get(&mapping.rabbit) {
+            sub.set_header("Since", seq.to_string());
+        }
+        sub
+    }
+
+    /// Note that `event` has been forwarded, returning the ACK for it.
+    pub fn forwarded(&mut self, event: &Frame) -> Option<Frame> {
+        let topic = event.args.first()?;
+        if let Some(seq) = event.header("Event-Seq").and_then(|s| s.parse().ok()) {
+            self.last.insert(topic.clone(), seq);
+        }
+        let seq = event.header("Seq")?;
+        let mut ack = Frame::new("ACK");
+        if let Some(lane) = event.header("Lane") {
+            ack.set_header("Lane", lane);
+        }
+        ack.set_header("ACK", seq);
+        Some(ack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+        [broker]
+        address = "10.0.0.5:1883"
+        username = "bridge"
+
+        [rabbit]
+        burrow = "127.0.0.1:7443"
+
+        [[mapping]]
+        mqtt = "home/#"
+        rabbit = "/q/home/#"
+
+        [[mapping]]
+        mqtt = "sensors/+/battery"
+        rabbit = "/q/batteries"
+        qos = 0
+
+        [[mapping]]
+        mqtt = "display/announce"
+        rabbit = "/q/announce"
+        direction = "both"
+    "#;
+
+    #[test]
+    fn parse_sample() {
+        let config = BridgeConfig::parse(SAMPLE).unwrap();
+        assert_eq!(config.broker.address, "10.0.0.5:1883");
+        assert_eq!(config.broker.client_id, "rabbit-bridge");
+        assert_eq!(config.broker.username.as_deref(), Some("bridge"));
+        assert_eq!(config.mappings.len(), 3);
+        assert_eq!(config.mappings[0].direction, Direction::MqttToRabbit);
+        assert_eq!(config.mappings[0].rabbit_qos(), "event");
+        assert_eq!(config.mappings[1].rabbit_qos(), "stream");
+        assert!(config.mappings[2].inbound() && config.mappings[2].outbound());
+    }
+
+    #[test]
+    fn routes_topics() {
+        let config = BridgeConfig::parse(SAMPLE).unwrap();
+        let inbound = |t: &str| config.route_inbound(t).map(|(_, r)| r);
+        assert_eq!(
+            inbound("home/kitchen/temp").as_deref(),
+            Some("/q/home/kitchen/temp")
+        );
+        assert_eq!(inbound("home").as_deref(), Some("/q/home"));
+        assert_eq!(
+            inbound("sensors/door/battery").as_deref(),
+            Some("/q/batteries")
+        );
+        assert_eq!(inbound("display/announce").as_deref(), Some("/q/announce"));
+        assert_eq!(inbound("garden/temp"), None);
+
+        assert_eq!(
+            config
+                .route_outbound("/q/announce")
+                .and_then(|m| m.mqtt_topic_for("/q/announce")),
+            Some("display/announce")
+        );
+        assert!(config.route_outbound("/q/batteries").is_none());
+    }
+
+    #[test]
+    fn rejects_unmappable_configs() {
+        for bad in [
+            "",
+            "[[mapping]]\nmqtt = \"a\"\nrabbit = \"q/a\"",
+            "[[mapping]]\nmqtt = \"a\"\nrabbit = \"/q/a\"\nqos = 3",
+            "[[mapping]]\nmqtt = \"a/+\"\nrabbit = \"/q/a/#\"",
+            "[[mapping]]\nmqtt = \"a/#\"\nrabbit = \"/q/#/a\"",
+            "[[mapping]]\nmqtt = \"a/+\"\nrabbit = \"/q/a\"\ndirection = \"both\"",
+            "[[mapping]]\nmqtt = \"a\"\nrabbit = \"/q/a\"\ndirection = \"sideways\"",
+        ] {
+            assert!(BridgeConfig::parse(bad).is_err(), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn echo_filter_consumes_matches() {
+        let mut echoes = EchoFilter::new();
+        echoes.sent("display/announce", b"dinner");
+        assert!(!echoes.is_echo("display/announce", b"lunch"));
+        assert!(echoes.is_echo("display/announce", b"dinner"));
+        assert!(!echoes.is_echo("display/announce", b"dinner"));
+    }
+
+    #[test]
+    fn identity_is_created_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = BridgeConfig::parse(SAMPLE).unwrap();
+        config.base_dir = dir.path().to_path_buf();
+        let first = config.load_identity().unwrap();
+        assert!(dir.path().join("bridge.key").exists());
+        let second = config.load_identity().unwrap();
+        assert_eq!(first.burrow_id(), second.burrow_id());
+    }
+
+    #[tokio::test]
+    async fn cursors_resume_by_event_number_on_a_new_lane() {
+        use crate::burrow::Burrow;
+        use crate::transport::memory::memory_tunnel_pair;
+        use crate::transport::tunnel::Tunnel;
+        use std::sync::Arc;
+
+        async fn publish<T: Tunnel>(tunnel: &mut T, body: &str) {
+            let mut publish = Frame::with_args("PUBLISH", vec!["/q/announce".into()]);
+            publish.set_body(body);
+            tunnel.send_frame(&publish).await.unwrap();
+        }
+        async fn next_event<T: Tunnel>(tunnel: &mut T) -> Frame {
+            loop {
+                let frame = tunnel.recv_frame().await.unwrap().unwrap();
+                if frame.verb == "EVENT" {
+                    return frame;
+                }
+            }
+        }
+
+        let config = BridgeConfig::parse(SAMPLE).unwrap();
+        let mapping = &config.mappings[2];
+        let server = Arc::new(Burrow::in_memory("server"));
+        let client = Burrow::in_memory("bridge");
+        let mut cursors = Cursors::new();
+        let mut since = Vec::new();
+
+        // Each tunnel publishes, subscribes on a lane of its own and
+        // forwards what it is sent, up to and including `last`.
+        for (lane, bodies, last) in [(2, ["a", "b"], "b"), (1, ["c", "d"], "d")] {
+            let (mut c, mut s) = memory_tunnel_pair("c", "s");
+            let handler = Arc::clone(&server);
+            tokio::spawn(async move { handler.handle_tunnel(&mut s).await });
+            client.client_handshake(&mut c).await.unwrap();
+            for body in bodies {
+                publish(&mut c, body).await;
+            }
+            if lane == 1 {
+                publish(&mut c, "e").await;
+            }
+            let sub = cursors.subscribe(mapping, lane);
+            since.push(sub.header("Since").map(String::from));
+            c.send_frame(&sub).await.unwrap();
+            loop {
+                let event = next_event(&mut c).await;
+                c.send_frame(&cursors.forwarded(&event).unwrap()).await.unwrap();
+                if event.body.as_deref() == Some(last) {
+                    break;
+                }
+            }
+            c.close().await.unwrap();
+        }
+        // Events 3 and 4 went out as 1 and 2 on lane 1.
+        assert_eq!(since, [None, Some("2".to_string())]);
+        let sub = cursors.subscribe(mapping, 3);
+        assert_eq!(sub.header("Since"), Some("4"));
+
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let handler = Arc::clone(&server);
+        tokio::spawn(async move { handler.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+        c.send_frame(&sub).await.unwrap();
+        let event = next_event(&mut c).await;
+        assert_eq!(event.body.as_deref(), Some("e"));
+        assert_eq!(event.header("Event-Seq"), Some("5"));
+        assert_eq!(event.header("Seq"), Some("1"));
+    }
+}
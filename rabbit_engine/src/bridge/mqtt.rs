@@ -0,0 +1,595 @@
+//! A small MQTT 3.1.1 client.
+//!
+//! Just enough of the protocol for a bridge: connecting (with optional
+//! username and password), subscribing, publishing at QoS 0 and 1, and
+//! keepalive pings.  QoS 2 is not supported; subscriptions ask for at
+//! most QoS 1, so brokers downgrade QoS 2 messages on delivery.
+//!
+//! [`encode`] and [`decode`] handle the wire format; [`MqttConnection`]
+//! runs them over TCP.  `decode` works on a growing buffer, so packets
+//! split across reads are handled.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::protocol::error::ProtocolError;
+
+/// Largest packet accepted from a broker.
+pub const MAX_PACKET_BYTES: usize = 1024 * 1024;
+
+/// An MQTT control packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    /// Client → broker: open a session.
+    Connect {
+        client_id: String,
+        username: Option<String>,
+        password: Option<String>,
+        keepalive_secs: u16,
+        clean_session: bool,
+    },
+    /// Broker → client: session accepted (code 0) or refused.
+    ConnAck {
+        session_present: bool,
+        code: u8,
+    },
+    /// An application message, either way.
+    Publish {
+        topic: String,
+        payload: Vec<u8>,
+        qos: u8,
+        retain: bool,
+        dup: bool,
+        /// Present for QoS 1 and 2.
+        packet_id: Option<u16>,
+    },
+    /// Acknowledges a QoS 1 publish.
+    PubAck {
+        packet_id: u16,
+    },
+    /// Client → broker: subscribe to `(filter, max QoS)` pairs.
+    Subscribe {
+        packet_id: u16,
+        filters: Vec<(String, u8)>,
+    },
+    /// Broker → client: granted QoS per filter (`0x80` = refused).
+    SubAck {
+        packet_id: u16,
+        codes: Vec<u8>,
+    },
+    PingReq,
+    PingResp,
+    Disconnect,
+}
+
+fn malformed(what: &str) -> ProtocolError {
+    ProtocolError::BadRequest(format!("malformed MQTT packet: {}", what))
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Serialize a packet.
+pub fn encode(packet: &Packet) -> Vec<u8> {
+    let mut body = Vec::new();
+    let first: u8 = match packet {
+        Packet::Connect {
+            client_id,
+            username,
+            password,
+            keepalive_secs,
+            clean_session,
+        } => {
+            put_str(&mut body, "MQTT");
+            body.push(4); // protocol level 3.1.1
+            let mut flags = 0u8;
+            if username.is_some() {
+                flags |= 0x80;
+            }
+            if password.is_some() {
+                flags |= 0x40;
+            }
+            if *clean_session {
+                flags |= 0x02;
+            }
+            body.push(flags);
+            body.extend_from_slice(&keepalive_secs.to_be_bytes());
+            put_str(&mut body, client_id);
+            if let Some(u) = username {
+                put_str(&mut body, u);
+            }
+            if let Some(p) = password {
+                put_str(&mut body, p);
+            }
+            0x10
+        }
+        Packet::ConnAck {
+            session_present,
+            code,
+        } => {
+            body.push(u8::from(*session_present));
+            body.push(*code);
+            0x20
+        }
+        Packet::Publish {
+            topic,
+            payload,
+            qos,
+            retain,
+            dup,
+            packet_id,
+        } => {
+            put_str(&mut body, topic);
+            if let Some(id) = packet_id {
+                body.extend_from_slice(&id.to_be_bytes());
+            }
+            body.extend_from_slice(payload);
+            0x30 | (u8::from(*dup) << 3) | ((qos & 0x03) << 1) | u8::from(*retain)
+        }
+        Packet::PubAck { packet_id } => {
+            body.extend_from_slice(&packet_id.to_be_bytes());
+            0x40
+        }
+        Packet::Subscribe { packet_id, filters } => {
+            body.extend_from_slice(&packet_id.to_be_bytes());
+            for (filter, qos) in filters {
+                put_str(&mut body, filter);
+                body.push(*qos);
+            }
+            0x82
+        }
+        Packet::SubAck { packet_id, codes } => {
+            body.extend_from_slice(&packet_id.to_be_bytes());
+            body.extend_from_slice(codes);
+            0x90
+        }
+        Packet::PingReq => 0xC0,
+        Packet::PingResp => 0xD0,
+        Packet::Disconnect => 0xE0,
+    };
+
+    let mut out = Vec::with_capacity(body.len() + 5);
+    out.push(first);
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    out.extend_from_slice(&body);
+    out
+}
+
+/// Reads fields from a packet body.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ProtocolError> {
+        if self.data.len() < n {
+            return Err(malformed("truncated body"));
+        }
+        let (head, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ProtocolError> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn string(&mut self) -> Result<String, ProtocolError> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| malformed("invalid UTF-8"))
+    }
+}
+
+/// Parse one packet from the front of `buf`.
+///
+/// Returns the packet and the number of bytes it used, or `None` if
+/// `buf` does not yet hold a whole packet.
+pub fn decode(buf: &[u8]) -> Result<Option<(Packet, usize)>, ProtocolError> {
+    let Some(&first) = buf.first() else {
+        return Ok(None);
+    };
+    let mut len = 0usize;
+    let mut header = 1;
+    loop {
+        let Some(&byte) = buf.get(header) else {
+            return Ok(None);
+        };
+        len += ((byte & 0x7F) as usize) << (7 * (header - 1));
+        header += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if header > 4 {
+            return Err(malformed("remaining length too long"));
+        }
+    }
+    if len > MAX_PACKET_BYTES {
+        return Err(malformed("packet too large"));
+    }
+    let Some(data) = buf.get(header..header + len) else {
+        return Ok(None);
+    };
+    let mut r = Reader { data };
+    let flags = first & 0x0F;
+
+    let packet = match first >> 4 {
+        1 => {
+            if r.string()? != "MQTT" || r.u8()? != 4 {
+                return Err(malformed("unsupported protocol version"));
+            }
+            let connect_flags = r.u8()?;
+            let keepalive_secs = r.u16()?;
+            let client_id = r.string()?;
+            if connect_flags & 0x04 != 0 {
+                return Err(malformed("will messages are not supported"));
+            }
+            let username = (connect_flags & 0x80 != 0)
+                .then(|| r.string())
+                .transpose()?;
+            let password = (connect_flags & 0x40 != 0)
+                .then(|| r.string())
+                .transpose()?;
+            Packet::Connect {
+                client_id,
+                username,
+                password,
+                keepalive_secs,
+                clean_session: connect_flags & 0x02 != 0,
+            }
+        }
+        2 => Packet::ConnAck {
+            session_present: r.u8()? & 0x01 != 0,
+            code: r.u8()?,
+        },
+        3 => {
+            let qos = (flags >> 1) & 0x03;
+            if qos == 3 {
+                return Err(malformed("invalid QoS"));
+            }
+            let topic = r.string()?;
+            let packet_id = if qos > 0 { Some(r.u16()?) } else { None };
+            Packet::Publish {
+                topic,
+                payload: r.data.to_vec(),
+                qos,
+                retain: flags & 0x01 != 0,
+                dup: flags & 0x08 != 0,
+                packet_id,
+            }
+        }
+        4 => Packet::PubAck {
+            packet_id: r.u16()?,
+        },
+        8 => {
+            let packet_id = r.u16()?;
+            let mut filters = Vec::new();
+            while !r.data.is_empty() {
+                let filter = r.string()?;
+                filters.push((filter, r.u8()?));
+            }
+            Packet::Subscribe { packet_id, filters }
+        }
+        9 => Packet::SubAck {
+            packet_id: r.u16()?,
+            codes: r.data.to_vec(),
+        },
+        12 => Packet::PingReq,
+        13 => Packet::PingResp,
+        14 => Packet::Disconnect,
+        other => {
+            return Err(malformed(&format!("unsupported packet type {}", other)));
+        }
+    };
+    Ok(Some((packet, header + len)))
+}
+
+/// Whether `topic` matches an MQTT topic filter with `+` and `#`
+/// wildcards.
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut levels = topic.split('/');
+    for part in filter.split('/') {
+        match (part, levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (p, Some(level)) if p == level => {}
+            _ => return false,
+        }
+    }
+    levels.next().is_none()
+}
+
+/// Human-readable reason for a refused CONNACK.
+fn connack_reason(code: u8) -> &'static str {
+    match code {
+        1 => "unacceptable protocol version",
+        2 => "client identifier rejected",
+        3 => "server unavailable",
+        4 => "bad user name or password",
+        5 => "not authorized",
+        _ => "unknown reason",
+    }
+}
+
+/// Options for [`MqttConnection::connect`].
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Seconds between pings the broker should expect (0 = none).
+    pub keepalive_secs: u16,
+}
+
+/// A connection to an MQTT broker.
+pub struct MqttConnection {
+    stream: TcpStream,
+    buf: Vec<u8>,
+    next_id: u16,
+}
+
+impl MqttConnection {
+    /// Connect to a broker and wait for it to accept the session.
+    pub async fn connect(addr: &str, options: &ConnectOptions) -> Result<Self, ProtocolError> {
+        let stream = TcpStream::connect(addr).await.map_err(|e| {
+            ProtocolError::InternalError(format!("MQTT connect to {} failed: {}", addr, e))
+        })?;
+        let mut conn = Self {
+            stream,
+            buf: Vec::new(),
+            next_id: 1,
+        };
+        conn.send(&Packet::Connect {
+            client_id: options.client_id.clone(),
+            username: options.username.clone(),
+            password: options.password.clone(),
+            keepalive_secs: options.keepalive_secs,
+            clean_session: true,
+        })
+        .await?;
+        match conn.recv().await? {
+            Some(Packet::ConnAck { code: 0, .. }) => Ok(conn),
+            Some(Packet::ConnAck { code, .. }) => Err(ProtocolError::Forbidden(format!(
+                "MQTT broker refused connection: {}",
+                connack_reason(code)
+            ))),
+            other => Err(ProtocolError::BadHello(format!(
+                "expected CONNACK, got {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Allocate a packet identifier.
+    pub fn next_packet_id(&mut self) -> u16 {
+        let id = self.next_id;
+        self.next_id = self.next_id.checked_add(1).unwrap_or(1);
+        id
+    }
+
+    /// Send a packet.
+    pub async fn send(&mut self, packet: &Packet) -> Result<(), ProtocolError> {
+        self.stream
+            .write_all(&encode(packet))
+            .await
+            .map_err(|e| ProtocolError::InternalError(format!("MQTT write failed: {}", e)))
+    }
+
+    /// Receive the next packet, or `None` once the broker closes the
+    /// connection.  Safe to use in `select!`: partial packets stay
+    /// buffered.
+    pub async fn recv(&mut self) -> Result<Option<Packet>, ProtocolError> {
+        loop {
+            if let Some((packet, used)) = decode(&self.buf)? {
+                self.buf.drain(..used);
+                return Ok(Some(packet));
+            }
+            let n =
+                self.stream.read_buf(&mut self.buf).await.map_err(|e| {
+                    ProtocolError::InternalError(format!("MQTT read failed: {}", e))
+                })?;
+            if n == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Subscribe and wait for the broker's SUBACK.  Returns the
+    /// granted QoS for each filter (`0x80` if refused).
+    pub async fn subscribe(
+        &mut self,
+        filters: Vec<(String, u8)>,
+    ) -> Result<Vec<u8>, ProtocolError> {
+        let packet_id = self.next_packet_id();
+        self.send(&Packet::Subscribe { packet_id, filters }).await?;
+        loop {
+            match self.recv().await? {
+                Some(Packet::SubAck {
+                    packet_id: id,
+                    codes,
+                }) if id == packet_id => return Ok(codes),
+                Some(Packet::PingResp) => {}
+                Some(other) => {
+                    return Err(ProtocolError::BadRequest(format!(
+                        "expected SUBACK, got {:?}",
+                        other
+                    )))
+                }
+                None => {
+                    return Err(ProtocolError::InternalError(
+                        "MQTT broker closed the connection".into(),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Publish a message.  QoS 1 messages get a packet identifier;
+    /// their PUBACK arrives through [`MqttConnection::recv`].
+    pub async fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        qos: u8,
+        retain: bool,
+    ) -> Result<(), ProtocolError> {
+        let qos = qos.min(1);
+        let packet_id = (qos > 0).then(|| self.next_packet_id());
+        self.send(&Packet::Publish {
+            topic: topic.to_string(),
+            payload: payload.to_vec(),
+            qos,
+            retain,
+            dup: false,
+            packet_id,
+        })
+        .await
+    }
+
+    /// Say goodbye and close the connection.
+    pub async fn disconnect(&mut self) -> Result<(), ProtocolError> {
+        self.send(&Packet::Disconnect).await?;
+        let _ = self.stream.shutdown().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_roundtrip() {
+        let packets = vec![
+            Packet::Connect {
+                client_id: "bridge".into(),
+                username: Some("home".into()),
+                password: Some("secret".into()),
+                keepalive_secs: 30,
+                clean_session: true,
+            },
+            Packet::ConnAck {
+                session_present: false,
+                code: 0,
+            },
+            Packet::Publish {
+                topic: "home/kitchen/temp".into(),
+                payload: vec![0xFF; 300],
+                qos: 1,
+                retain: true,
+                dup: false,
+                packet_id: Some(7),
+            },
+            Packet::Subscribe {
+                packet_id: 2,
+                filters: vec![("home/#".into(), 1), ("lights/+".into(), 0)],
+            },
+            Packet::SubAck {
+                packet_id: 2,
+                codes: vec![1, 0],
+            },
+            Packet::PubAck { packet_id: 7 },
+            Packet::PingReq,
+            Packet::Disconnect,
+        ];
+        for packet in packets {
+            let bytes = encode(&packet);
+            let (decoded, used) = decode(&bytes).unwrap().unwrap();
+            assert_eq!(decoded, packet);
+            assert_eq!(used, bytes.len());
+        }
+    }
+
+    #[test]
+    fn partial_packets_wait_for_more() {
+        let mut bytes = encode(&Packet::Publish {
+            topic: "a".into(),
+            payload: vec![1; 200],
+            qos: 0,
+            retain: false,
+            dup: false,
+            packet_id: None,
+        });
+        bytes.extend(encode(&Packet::PingResp));
+        for cut in [0, 1, 2, 50] {
+            assert!(decode(&bytes[..cut]).unwrap().is_none());
+        }
+        let (_, used) = decode(&bytes).unwrap().unwrap();
+        assert_eq!(decode(&bytes[used..]).unwrap().unwrap().0, Packet::PingResp);
+        assert!(decode(&[0x30, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]).is_err());
+    }
+
+    #[test]
+    fn wildcard_matching() {
+        assert!(topic_matches("home/+/temp", "home/kitchen/temp"));
+        assert!(!topic_matches("home/+/temp", "home/kitchen/humidity"));
+        assert!(topic_matches("home/#", "home/kitchen/temp"));
+        assert!(topic_matches("home/#", "home"));
+        assert!(!topic_matches("home/kitchen", "home/kitchen/temp"));
+        assert!(topic_matches("lights", "lights"));
+    }
+
+    #[tokio::test]
+    async fn connects_and_exchanges_messages() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let broker = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut conn = MqttConnection {
+                stream,
+                buf: Vec::new(),
+                next_id: 1,
+            };
+            assert!(matches!(
+                conn.recv().await.unwrap(),
+                Some(Packet::Connect { .. })
+            ));
+            conn.send(&Packet::ConnAck {
+                session_present: false,
+                code: 0,
+            })
+            .await
+            .unwrap();
+            let Some(Packet::Publish { topic, payload, .. }) = conn.recv().await.unwrap() else {
+                panic!("expected PUBLISH");
+            };
+            conn.publish(&format!("{}/echo", topic), &payload, 0, false)
+                .await
+                .unwrap();
+        });
+
+        let options = ConnectOptions {
+            client_id: "test".into(),
+            username: None,
+            password: None,
+            keepalive_secs: 0,
+        };
+        let mut client = MqttConnection::connect(&addr, &options).await.unwrap();
+        client.publish("ping", b"hi", 0, false).await.unwrap();
+        match client.recv().await.unwrap() {
+            Some(Packet::Publish { topic, payload, .. }) => {
+                assert_eq!(topic, "ping/echo");
+                assert_eq!(payload, b"hi");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        broker.await.unwrap();
+    }
+}
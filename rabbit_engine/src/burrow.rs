@@ -7,75 +7,305 @@
 //! The main entry point is [`Burrow::from_config`], which loads or
 //! generates the identity, populates the content store from the TOML
 //! config (including file-backed text), and wires everything together.
+//! [`BurrowBuilder`] does the same with any of the identity, trust
+//! policy, event store, content providers, frame handlers and frame
+//! tap supplied by the caller, and can bind the configured listeners
+//! once the burrow is assembled.
 //!
 //! After construction the caller can:
 //! * Register additional content programmatically.
 //! * Call [`Burrow::handle_tunnel`] to run the protocol loop on an
 //!   incoming tunnel (handshake → dispatch → close).
+//! * Call [`Burrow::shutdown`] before exiting to close tunnels with
+//!   `GOAWAY` and persist trust, saved sessions, and routes.
+//! * Register callbacks on [`Burrow::hooks`] to hear of peers, events
+//!   and the burrow's [lifecycle state](crate::hooks::BurrowState).
 
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use tracing::{debug, info, instrument, warn};
+use futures_util::stream::{self, SelectAll, StreamExt};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, debug_span, info, instrument, warn, Instrument};
 
 use std::sync::atomic::AtomicU32;
 
-use crate::config::{AiChatConfig, Config};
+use crate::config::{AiChatConfig, Config, ListenerLimits, ReplicationConfig, TrustConfig};
 use crate::content::loader::load_content;
+use crate::content::provider::{ContentProvider, FileProvider, ProviderRegistry};
 use crate::content::search::SearchIndex;
 use crate::content::store::ContentStore;
+use crate::dispatch::handlers::{FrameHandler, HandlerRegistry};
 use crate::dispatch::idem_cache::IdemCache;
-use crate::dispatch::rate_limiter::RateLimiter;
 use crate::dispatch::router::{DispatchResult, Dispatcher};
-use crate::events::continuity::ContinuityStore;
+use crate::error::RabbitError;
+use crate::events::continuity::{ContinuityOptions, ContinuityStore};
+use crate::events::dead_letter::{DeadLetter, DeadLetterStore};
 use crate::events::engine::EventEngine;
-use crate::protocol::error::ProtocolError;
-use crate::protocol::frame::Frame;
+use crate::events::offsets::ConsumerOffsets;
+use crate::events::quota::QuotaManager;
+use crate::events::replication::Replicator;
+use crate::events::retention::{self, RetentionPolicies};
+use crate::events::segment::Retention;
+#[cfg(feature = "sqlite")]
+use crate::events::sqlite::SqliteStore;
+use crate::events::store::{append_in_place, EventStore};
+use crate::hooks::{BurrowState, Hooks};
+use crate::network::acceptor::run_listener;
+use crate::protocol::address::{RabbitAddress, Scope};
+use crate::protocol::credit::CreditController;
+use crate::protocol::error::{ErrorFrame, ProtocolError};
+use crate::protocol::frame::{Frame, FrameLimits, Verb, VerbKind};
+use crate::protocol::lane::{LaneState, SelectiveAck};
 use crate::protocol::lane_manager::LaneManager;
-use crate::security::auth::{build_auth_proof, build_hello, Authenticator};
-use crate::security::identity::Identity;
+use crate::protocol::scheduler::{self, LaneScheduler};
+use crate::security::audit::{AuditKind, AuditLog};
+use crate::security::auth::{build_auth_proof, build_hello, Authenticator, ReplayCache};
+use crate::security::groups::GroupManager;
+use crate::security::identity::{fingerprint, parse_burrow_id, Identity};
+use crate::security::identity_cert::{
+    extract_rabbit_id_from_cert, generate_identity_cert, load_or_create_identity_cert,
+};
+use crate::security::manifest::TrustManifest;
 use crate::security::permissions::{Capability, CapabilityManager};
-use crate::security::trust::TrustCache;
-use crate::session::SessionManager;
-use crate::transport::tunnel::Tunnel;
-use crate::warren::peers::PeerTable;
-use crate::warren::routing::RoutingTable;
+use crate::security::rate_limiter::RateLimiter;
+use crate::security::rotation::KeyRotation;
+use crate::security::token::{TokenClaims, TokenKind};
+use crate::security::trust::{PeerStatus, TlsVerify, TrustCache};
+use crate::security::trust_policy::{policy_from_config, TrustPolicy};
+use crate::session::{
+    load_session_states, save_session_states, SessionManager, SessionRecord, SessionStore,
+};
+use crate::snapshot::{self, SnapshotManifest};
+use crate::transport::cert::{make_server_config, CertPair};
+use crate::transport::connector::{
+    make_client_config_insecure, make_client_config_pinned, ServerCertPolicy,
+};
+use crate::transport::keepalive::{self, Keepalive};
+use crate::transport::listener::{bind_tcp, RabbitListener};
+use crate::transport::stats::{
+    ListenerCounters, ListenerStatsRegistry, StatsTunnel, TunnelCounters, TunnelStatsRegistry,
+};
+use crate::transport::tap::{FrameTap, TapTunnel};
+use crate::transport::tcp::PlainListener;
+use crate::transport::tls::WireFormat;
+use crate::transport::tunnel::{Acceptor, Tunnel};
+use crate::util::now_unix;
+use crate::warren::federation::{manifest_reply, Anchor, FederationManager};
+use crate::warren::gossip;
+use crate::warren::offer::{PeerOffer, PendingOffers};
+use crate::warren::peers::{HealthReport, Liveness, PeerHealth, PeerInfo, PeerTable};
+use crate::warren::relay::RelayTable;
+use crate::warren::routing::{
+    address_reply, via_hops, Forward, RoutingTable, Topology, DEFAULT_HOP_COUNT,
+};
+use crate::warren::services::Service;
 
 /// Global session counter for unique session IDs.
 static SESSION_COUNTER: AtomicU64 = AtomicU64::new(0);
 
+/// Saved sessions file, relative to the storage directory.
+const SESSIONS_FILE: &str = "sessions.tsv";
+
+/// Issued session tokens, relative to the storage directory.
+const SESSION_TOKENS_FILE: &str = "session_tokens.tsv";
+
+/// `Wire` header value by which a `HELLO` offers, and the final 200
+/// accepts, length-prefixed framing.
+const WIRE_LENGTH_PREFIXED: &str = "length-prefixed";
+
+/// Capabilities granted to an anonymous peer at handshake.
+const ANONYMOUS_CAPS: &[Capability] = &[Capability::Fetch, Capability::List];
+
+/// Capabilities granted to an authenticated peer at handshake.
+const AUTHENTICATED_CAPS: &[Capability] = &[
+    Capability::Fetch,
+    Capability::List,
+    Capability::Subscribe,
+    Capability::Publish,
+];
+
+/// Capability grants, relative to the storage directory.
+const CAPABILITIES_FILE: &str = "capabilities.json";
+
+/// Append-only capability revocation log, relative to the storage
+/// directory.
+const REVOCATIONS_FILE: &str = "revocations.log";
+
+/// Group memberships, relative to the storage directory.
+const GROUPS_FILE: &str = "groups.json";
+
+/// Verified trust manifests, one file per anchor, relative to the
+/// storage directory.
+const MANIFESTS_DIR: &str = "manifests";
+
+/// The manifest this burrow publishes as an anchor, relative to the
+/// storage directory.
+const PUBLISHED_MANIFEST_FILE: &str = "published.manifest";
+
+/// Federation link state, relative to the storage directory.
+const FEDERATION_LINKS_FILE: &str = "federation_links.tsv";
+
+/// Anchors registered with the federation manager, relative to the
+/// storage directory.
+const FEDERATION_ANCHORS_FILE: &str = "federation_anchors.tsv";
+
+/// Security audit log directory, relative to the storage directory.
+const AUDIT_DIR: &str = "audit";
+
+/// Routing table file, relative to the storage directory.
+const ROUTES_FILE: &str = "routes.tsv";
+
+/// Dead-letter file, relative to the storage directory.
+const DEAD_LETTERS_FILE: &str = "dead_letters.tsv";
+
+/// Key rotation record, relative to the storage directory.
+const ROTATION_FILE: &str = "rotation.frame";
+
+/// How far followed topics have been replicated, relative to the
+/// storage directory.
+const REPLICATION_FILE: &str = "replication.tsv";
+
+/// Retention limits set at run time, relative to the storage
+/// directory.
+const RETENTION_FILE: &str = "retention.tsv";
+
+/// Subscribers' acknowledged offsets, relative to the storage
+/// directory.
+const OFFSETS_FILE: &str = "offsets.tsv";
+
+/// SQLite event store under `event_store = "sqlite"`, relative to the
+/// storage directory.
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+const EVENTS_DB_FILE: &str = "events.db";
+
+/// What a [snapshot](crate::snapshot) of the storage directory holds,
+/// relative to it: files, and directories taken whole.
+pub(crate) const SNAPSHOT_PATHS: &[&str] = &[
+    "identity.key",
+    ROTATION_FILE,
+    "trust.tsv",
+    MANIFESTS_DIR,
+    CAPABILITIES_FILE,
+    REVOCATIONS_FILE,
+    GROUPS_FILE,
+    PUBLISHED_MANIFEST_FILE,
+    FEDERATION_ANCHORS_FILE,
+    FEDERATION_LINKS_FILE,
+    "events",
+    EVENTS_DB_FILE,
+    REPLICATION_FILE,
+    RETENTION_FILE,
+    OFFSETS_FILE,
+    AUDIT_DIR,
+];
+
+/// Frames a tunnel's outbox may hold before replayed events stop being
+/// read into it.
+const REPLAY_WINDOW: usize = 64;
+
 /// A fully assembled burrow, ready to serve content and events.
 pub struct Burrow {
     /// The burrow's Ed25519 identity.
     pub identity: Identity,
+    /// Record of the older key this identity replaced, announced to
+    /// peers after each outgoing handshake.
+    pub rotation: Option<KeyRotation>,
     /// The burrow's human-readable name.
     pub name: String,
+    /// TLS certificate bound to the identity, kept in the certs
+    /// directory (none for in-memory burrows).
+    pub certificate: Option<CertPair>,
     /// In-memory content store (menus and text).
     pub content: ContentStore,
     /// Pub/sub event engine (shared with AI connectors).
     pub events: Arc<EventEngine>,
-    /// Append-only event persistence.
-    pub continuity: Option<ContinuityStore>,
+    /// Append-only event persistence: a [`ContinuityStore`] or
+    /// another [`EventStore`] backend.
+    pub continuity: Option<Box<dyn EventStore>>,
+    /// Interval between compactions of the event logs (0 = disabled).
+    pub compact_secs: u64,
+    /// How long each topic's events are kept.
+    pub retention: RetentionPolicies,
+    /// Topics followed on other burrows.
+    pub replicator: Replicator,
+    /// The last event each subscriber acknowledged in each topic.
+    pub consumer_offsets: ConsumerOffsets,
+    /// Frames that ran out of retransmissions.
+    pub dead_letters: DeadLetterStore,
+    /// Traffic and lane statistics of the tunnels being served.
+    pub tunnel_stats: TunnelStatsRegistry,
+    /// Accept and refusal counters of the listeners serving this burrow.
+    pub listener_stats: ListenerStatsRegistry,
+    /// Per-peer and per-topic storage quotas for published events.
+    pub quotas: QuotaManager,
     /// TOFU trust cache (interior mutability for concurrent tunnel access).
     pub trust: Mutex<TrustCache>,
+    /// The manifest published as an anchor, and fetching of others'.
+    pub federation: FederationManager,
+    /// Interval between manifest digests gossiped to federation links
+    /// in seconds (0 = disabled).
+    pub gossip_secs: u64,
+    /// Longest the gossip interval backs off to, in seconds.
+    pub gossip_max_secs: u64,
+    /// OFFERs awaiting a manifest that vouches for their signer, kept
+    /// when `trust.anchors` is set; without anchors OFFERs need not be
+    /// signed.
+    pub pending_offers: Option<PendingOffers>,
+    /// The `[trust]` settings the trust policy is built from, with the
+    /// registered anchors added each time they change.
+    pub trust_config: TrustConfig,
+    /// Hash-chained record of auth and trust decisions.
+    pub audit: AuditLog,
     /// Capability grants (interior mutability for concurrent tunnel access).
     pub capabilities: Mutex<CapabilityManager>,
     /// Known peers (warren membership).
     pub peers: PeerTable,
     /// Session manager for cross-tunnel event fan-out.
     pub sessions: SessionManager,
+    /// Handlers for verbs beyond the protocol's own.
+    pub handlers: HandlerRegistry,
+    /// Providers for selectors the content store lacks.
+    pub providers: ProviderRegistry,
     /// Whether authentication is required for incoming connections.
     pub require_auth: bool,
     /// Base directory for the burrow's configuration.
     base_dir: PathBuf,
+    /// Storage directory (identity, trust, sessions, routes, events).
+    storage: PathBuf,
     /// Keepalive interval in seconds (0 = disabled).
     pub keepalive_secs: u64,
+    /// Unanswered keepalive probes before a tunnel is closed.
+    pub keepalive_max_missed: u32,
     /// Handshake timeout in seconds.
     pub handshake_timeout_secs: u64,
+    /// Flush deadline for coalesced writes in milliseconds (0 = off).
+    pub write_coalesce_ms: u64,
     /// Maximum inbound frame size in bytes.
     pub max_frame_bytes: usize,
+    /// Maximum headers per inbound frame.
+    pub max_frame_headers: usize,
+    /// Maximum size of an inbound frame's start line and headers.
+    pub max_header_bytes: usize,
+    /// Whether EVENT frames and FETCH responses must carry a `Digest`.
+    pub require_digest: bool,
+    /// Whether the handshake offers and accepts length-prefixed
+    /// framing on tunnels that support it.
+    pub length_prefixed: bool,
+    /// Whether quarantined peers are refused a session rather than
+    /// limited to the anonymous grants.
+    pub refuse_quarantined: bool,
+    /// How outgoing connections check the server's TLS certificate.
+    pub tls_verify: TlsVerify,
+    /// Maximum lanes per tunnel besides the control lane (0 = unlimited).
+    pub max_lanes: u32,
+    /// Smallest and largest credit windows granted to a peer's lanes.
+    pub credit_windows: (u32, u32),
     /// Retransmission timeout in milliseconds.
     pub retransmit_timeout_ms: u64,
     /// Maximum retransmission attempts before giving up.
@@ -86,8 +316,31 @@ pub struct Burrow {
     pub offer_interval_secs: u64,
     /// Routing table for multi-hop forwarding.
     pub routing: RoutingTable,
+    /// Interval for `ROUTE-ADVERT` gossip in seconds (0 = disabled).
+    pub route_advert_secs: u64,
+    /// Lifetime of a learned route in seconds.
+    pub route_ttl_secs: u64,
+    /// Burrows relayed for, and relays heard of through route adverts.
+    pub relay: RelayTable,
+    /// Relays to register with whenever a tunnel to one is dialed.
+    pub register_relays: Vec<String>,
+    /// Interval between peer health checks in seconds (0 = disabled).
+    pub peer_check_secs: u64,
+    /// When unseen peers are judged degraded, down and gone.
+    pub liveness: Liveness,
     /// Saved session states for resumption.
     pub saved_sessions: std::sync::Mutex<Vec<crate::session::SavedSessionState>>,
+    /// Session tokens issued by handshakes, until they expire or are
+    /// revoked.
+    pub session_store: SessionStore,
+    /// Lifetime of an issued session token in seconds.
+    pub session_ttl_secs: u64,
+    /// Lifetime of an issued refresh token in seconds.
+    pub refresh_ttl_secs: u64,
+    /// Interval between sweeps for expired sessions (0 = disabled).
+    pub session_sweep_secs: u64,
+    /// Nonces of recent stamped frames, against replays.
+    pub replay_cache: ReplayCache,
     /// Per-peer frame rate limiter.
     pub rate_limiter: RateLimiter,
     /// Idempotency token cache.
@@ -98,113 +351,306 @@ pub struct Burrow {
     pub max_per_peer: u32,
     /// Current number of active tunnels.
     pub active_connections: AtomicU32,
+    /// Seconds tunnels get to settle in-flight frames at shutdown.
+    pub shutdown_grace_secs: u64,
+    /// Raised by [`Burrow::shutdown`] to make every tunnel go away.
+    going_away: watch::Sender<bool>,
     /// AI chat configurations (spawned as background tasks).
     pub ai_chats: Vec<AiChatConfig>,
+    /// Callbacks run as the burrow and its peers change.
+    pub hooks: Hooks,
+    /// Where the burrow is in its life; see [`Burrow::advance`].
+    state: watch::Sender<BurrowState>,
+    /// Observer for frames on incoming tunnels, if any.
+    frame_tap: Mutex<Option<Arc<dyn FrameTap>>>,
+    /// Address the gateway forwards to the burrow, once mapped.
+    external_addr: Mutex<Option<SocketAddr>>,
 }
 
 impl Burrow {
     /// Build a burrow from a [`Config`] and a base directory.
     ///
     /// * If `<storage>/identity.key` exists, the identity is loaded
-    ///   from it.  Otherwise a new identity is generated and saved.
+    ///   from it.  Otherwise a new identity is generated and saved
+    ///   (encrypted if `RABBIT_KEY_PASSPHRASE` is set; see
+    ///   [`Identity::load_or_create`]).
+    /// * A rotation record in `<storage>/rotation.frame` whose new key
+    ///   is this identity is announced after outgoing handshakes.
+    /// * The TLS certificate is loaded from `cert.pem` and `key.pem`
+    ///   in the certs directory, or generated there, bound to the
+    ///   identity, on first start (see [`load_or_create_identity_cert`]).
     /// * The content store is populated from the config's content
     ///   section — menu definitions, inline text, and file-backed text
-    ///   are all resolved relative to `base_dir`.
-    /// * A continuity store is created at `<storage>/events/`.
+    ///   are all resolved relative to `base_dir`.  Each
+    ///   `[[content.files]]` directory, also relative to `base_dir`, is
+    ///   served by a [`FileProvider`] and must exist.
+    /// * A continuity store is created at `<storage>/events/`, signing
+    ///   what it logs with the identity under `integrity = "signed"`,
+    ///   or with `event_store = "sqlite"` kept in a SQLite database at
+    ///   `<storage>/events.db`.  Retention limits set by `TOPIC-CONFIG`
+    ///   are kept in `<storage>/retention.tsv`.
+    /// * Undeliverable frames are parked in
+    ///   `<storage>/dead_letters.tsv`.
+    /// * Topics in `[[replication.follow]]` are followed, with how far
+    ///   each has been replicated kept in `<storage>/replication.tsv`.
+    /// * Subscribers' acknowledged offsets are loaded from
+    ///   `<storage>/offsets.tsv`.
     /// * The trust cache is loaded from `<storage>/trust.tsv` if it
-    ///   exists.
-    #[instrument(skip(config, base_dir), fields(name = %config.identity.name))]
+    ///   exists, with the `[trust]` policy and the manifests kept in
+    ///   `<storage>/manifests/`.  The manifest this burrow publishes
+    ///   as an anchor is kept in `<storage>/published.manifest`, and
+    ///   `[[federation.links]]` provision the link secrets.
+    /// * Saved sessions and routes left by [`Burrow::shutdown`] are
+    ///   restored from `<storage>/sessions.tsv` and
+    ///   `<storage>/routes.tsv`, and unexpired session tokens from
+    ///   `<storage>/session_tokens.tsv`.
+    /// * Capability grants are restored from
+    ///   `<storage>/capabilities.json`, less those revoked in
+    ///   `<storage>/revocations.log`, where new revocations are
+    ///   appended.  Groups are kept in `<storage>/groups.json`.
+    ///
+    /// To supply subsystems of your own, use [`BurrowBuilder`].
     pub fn from_config(config: &Config, base_dir: impl AsRef<Path>) -> Result<Self, ProtocolError> {
-        let base_dir = base_dir.as_ref().to_path_buf();
+        BurrowBuilder::new(config.clone(), base_dir).build()
+    }
+
+    /// Assemble a burrow as [`Burrow::from_config`] describes, with
+    /// `identity` and `event_store`, if given, in place of those kept
+    /// in the storage directory.
+    #[instrument(skip_all, fields(name = %config.identity.name))]
+    fn assemble(
+        config: &Config,
+        base_dir: PathBuf,
+        identity: Option<Identity>,
+        event_store: Option<Box<dyn EventStore>>,
+    ) -> Result<Self, ProtocolError> {
         let storage = base_dir.join(&config.identity.storage);
 
         // ── Identity ───────────────────────────────────────────
-        let identity_path = storage.join("identity.key");
-        let identity = if identity_path.exists() {
-            info!(path = %identity_path.display(), "loading existing identity");
-            Identity::from_file(&identity_path)?
+        let identity = match identity {
+            Some(identity) => identity,
+            None => {
+                let identity_path = storage.join("identity.key");
+                if identity_path.exists() {
+                    info!(path = %identity_path.display(), "loading existing identity");
+                } else {
+                    info!(path = %identity_path.display(), "generating new identity");
+                }
+                Identity::load_or_create(&identity_path)?
+            }
+        };
+        let rotation_path = storage.join(ROTATION_FILE);
+        let rotation = if rotation_path.exists() {
+            let rotation = KeyRotation::load(&rotation_path)?;
+            if rotation.new_id == identity.burrow_id() {
+                Some(rotation)
+            } else {
+                warn!(path = %rotation_path.display(), "rotation record is for another key; ignoring");
+                None
+            }
         } else {
-            info!(path = %identity_path.display(), "generating new identity");
-            let id = Identity::generate();
-            id.save(&identity_path)?;
-            id
+            None
         };
 
+        // ── TLS certificate ────────────────────────────────────
+        let certificate = load_or_create_identity_cert(
+            &base_dir.join(&config.identity.certs),
+            &identity,
+            &config.identity.name,
+        )?;
+
         // ── Content store from config ──────────────────────────
         let content = load_content(config, &base_dir)?;
-
-        // ── Event engine ───────────────────────────────────────
-        let events = Arc::new(EventEngine::new());
+        let providers = ProviderRegistry::new();
+        for files in &config.content.files {
+            let root = base_dir.join(&files.root);
+            if !root.is_dir() {
+                return Err(ProtocolError::InternalError(format!(
+                    "content.files {:?}: '{}' is not a directory",
+                    files.selector,
+                    root.display()
+                )));
+            }
+            let provider = FileProvider::new(&files.selector, root);
+            providers.register(&files.selector, Arc::new(provider))?;
+        }
 
         // ── Continuity store ───────────────────────────────────
         let events_dir = storage.join("events");
-        let continuity = ContinuityStore::new(&events_dir).ok();
-
-        // Restore persisted events into the engine from continuity.
-        if let Some(ref cont) = continuity {
-            if let Ok(entries) = std::fs::read_dir(&events_dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.extension().and_then(|e| e.to_str()) == Some("log") {
-                        // Derive topic from filename: q_chat.log → /q/chat
-                        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                            let topic = format!("/{}", stem.replace('_', "/"));
-                            if let Ok(loaded) = cont.load(&topic) {
-                                if !loaded.is_empty() {
-                                    info!(topic = %topic, count = loaded.len(), "restored events from continuity");
-                                    events.load_events(&topic, loaded);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+        let mut continuity_options = ContinuityOptions::from_config(&config.continuity);
+        let retention = RetentionPolicies::open(
+            storage.join(RETENTION_FILE),
+            continuity_options.retention,
+            &config.continuity.topics,
+        )?;
+        if config.continuity.integrity == "signed" {
+            let signer = Identity::from_bytes(identity.public_key_bytes(), identity.seed_bytes())?;
+            continuity_options.signer = Some(Arc::new(signer));
         }
+        let continuity: Box<dyn EventStore> = match event_store {
+            Some(store) => store,
+            #[cfg(feature = "sqlite")]
+            None if config.identity.event_store == "sqlite" => Box::new(SqliteStore::with_options(
+                storage.join(EVENTS_DB_FILE),
+                continuity_options,
+            )?),
+            None => Box::new(ContinuityStore::with_options(
+                &events_dir,
+                continuity_options,
+            )?),
+        };
+
+        // ── Event engine ───────────────────────────────────────
+        // Events beyond the memory limit are replayed from the log.
+        let events =
+            Arc::new(EventEngine::new().with_memory_limit(config.continuity.memory_events));
+        let quotas = QuotaManager::from_config(&config.quota);
+        restore_events(continuity.as_ref(), &events, &quotas);
+
+        let dead_letters = DeadLetterStore::open(storage.join(DEAD_LETTERS_FILE))?;
+        let replicator = Replicator::open(storage.join(REPLICATION_FILE), &config.replication)?;
+        let consumer_offsets = ConsumerOffsets::open(storage.join(OFFSETS_FILE))?;
 
         // ── Trust cache ────────────────────────────────────────
         let trust_path = storage.join("trust.tsv");
-        let trust = if trust_path.exists() {
+        let mut trust = if trust_path.exists() {
             TrustCache::load(&trust_path)?
         } else {
             TrustCache::new()
         };
+        trust.set_quarantine_mismatches(config.trust.on_mismatch == "quarantine");
+        trust.load_manifests(storage.join(MANIFESTS_DIR));
 
         // ── Capabilities and peers ─────────────────────────────
         let sessions = SessionManager::new();
-        let capabilities = CapabilityManager::new();
+        let mut capabilities = CapabilityManager::load(storage.join(CAPABILITIES_FILE))?;
+        capabilities.set_revocation_log(storage.join(REVOCATIONS_FILE))?;
+        capabilities.set_groups(GroupManager::load(storage.join(GROUPS_FILE))?);
         let peers = PeerTable::new();
         let search_index = SearchIndex::build_from_store(&content);
 
+        // ── Sessions and routes from the last shutdown ─────────
+        let saved_sessions = load_session_states(&storage.join(SESSIONS_FILE));
+        let session_store = SessionStore::load(storage.join(SESSION_TOKENS_FILE))?;
+        let routing = RoutingTable::load(storage.join(ROUTES_FILE));
+
+        // ── Federation ─────────────────────────────────────────
+        let federation = FederationManager::load(storage.join(PUBLISHED_MANIFEST_FILE))?
+            .with_heartbeat(config.federation.heartbeat_secs)
+            .with_link_state(storage.join(FEDERATION_LINKS_FILE))
+            .with_anchors(storage.join(FEDERATION_ANCHORS_FILE));
+        for link in &config.federation.links {
+            federation.add_link(&link.peer, link.secret_bytes(&base_dir)?);
+            if let Some(address) = &link.address {
+                federation.set_link_address(&link.peer, address);
+            }
+        }
+        for anchor in &config.federation.anchors {
+            federation.register_anchor(Anchor::new(&anchor.id, anchor.address.as_deref()))?;
+        }
+        // Anchors registered at runtime admit peers as configured ones do.
+        let trust_config = config.trust.clone();
+        let anchored_trust = anchored(&trust_config, &federation);
+        trust.set_policy(policy_from_config(&anchored_trust)?);
+        let signed_offers = !anchored_trust.anchors.is_empty();
+        for service in &config.federation.services {
+            let requires = service
+                .requires
+                .iter()
+                .filter_map(|label| Capability::from_label(label))
+                .collect();
+            federation.services().declare(Service::new(
+                &service.name,
+                &service.selector,
+                requires,
+            ))?;
+        }
+
         Ok(Self {
             identity,
+            rotation,
             name: config.identity.name.clone(),
+            certificate: Some(certificate),
             content,
             events,
-            continuity,
+            continuity: Some(continuity),
+            compact_secs: config.continuity.compact_secs,
+            retention,
+            replicator,
+            consumer_offsets,
+            dead_letters,
+            tunnel_stats: TunnelStatsRegistry::new(),
+            listener_stats: ListenerStatsRegistry::new(),
+            quotas,
             trust: Mutex::new(trust),
+            federation,
+            gossip_secs: config.federation.gossip_secs,
+            gossip_max_secs: config.federation.gossip_max_secs,
+            pending_offers: signed_offers.then(PendingOffers::new),
+            trust_config,
+            audit: AuditLog::open(storage.join(AUDIT_DIR))?,
             capabilities: Mutex::new(capabilities),
             peers,
             sessions,
+            handlers: HandlerRegistry::new(),
+            providers,
             require_auth: config.identity.require_auth,
             base_dir,
+            storage,
             keepalive_secs: config.network.keepalive_secs,
+            keepalive_max_missed: config.network.keepalive_max_missed,
             handshake_timeout_secs: config.network.handshake_timeout_secs,
+            write_coalesce_ms: config.network.write_coalesce_ms,
             max_frame_bytes: config.network.max_frame_bytes,
+            max_frame_headers: config.network.max_frame_headers,
+            max_header_bytes: config.network.max_header_bytes,
+            require_digest: config.network.require_digest,
+            length_prefixed: config.network.length_prefixed,
+            refuse_quarantined: config.trust.quarantine == "refuse",
+            tls_verify: TlsVerify::from_label(&config.trust.tls_verify).unwrap_or_default(),
+            max_lanes: config.network.max_lanes,
+            credit_windows: (
+                config.network.credit_min_window,
+                config.network.credit_max_window,
+            ),
             retransmit_timeout_ms: config.network.retransmit_timeout_ms,
             retransmit_max_retries: config.network.retransmit_max_retries,
             search_index,
             offer_interval_secs: config.network.offer_interval_secs,
-            routing: RoutingTable::new(),
-            saved_sessions: std::sync::Mutex::new(Vec::new()),
+            routing,
+            route_advert_secs: config.network.route_advert_secs,
+            route_ttl_secs: config.network.route_ttl_secs,
+            relay: RelayTable::new(config.relay.max_clients, config.relay.bytes_per_sec),
+            register_relays: config.relay.register_with.clone(),
+            peer_check_secs: config.network.peer_check_secs,
+            liveness: Liveness {
+                degraded_secs: config.network.peer_degraded_secs,
+                down_secs: config.network.peer_down_secs,
+                prune_secs: config.network.peer_prune_secs,
+            },
+            saved_sessions: std::sync::Mutex::new(saved_sessions),
+            session_store,
+            session_ttl_secs: config.identity.session_ttl_secs,
+            refresh_ttl_secs: config.identity.refresh_ttl_secs,
+            session_sweep_secs: config.identity.session_sweep_secs,
+            replay_cache: ReplayCache::new(config.identity.replay_window_secs),
             rate_limiter: RateLimiter::new(
                 config.network.rate_limit_fps,
                 config.network.publish_rate_limit_fps,
-            ),
+            )
+            .with_handshake_limit(config.network.handshake_rate_limit_per_min)
+            .with_fetch_bandwidth(config.network.fetch_rate_limit_bps),
             idem_cache: IdemCache::new(config.network.idem_ttl_secs),
             max_connections: config.network.max_connections,
             max_per_peer: config.network.max_per_peer,
             active_connections: AtomicU32::new(0),
+            shutdown_grace_secs: config.network.shutdown_grace_secs,
+            going_away: watch::Sender::new(false),
             ai_chats: config.ai.chats.clone(),
+            hooks: Hooks::new(),
+            state: watch::Sender::new(BurrowState::Initialized),
+            frame_tap: Mutex::new(None),
+            external_addr: Mutex::new(None),
         })
     }
 
@@ -215,64 +661,1060 @@ impl Burrow {
     pub fn in_memory(name: impl Into<String>) -> Self {
         Self {
             identity: Identity::generate(),
+            rotation: None,
             name: name.into(),
+            certificate: None,
             content: ContentStore::new(),
             events: Arc::new(EventEngine::new()),
             continuity: None,
+            compact_secs: 300,
+            retention: RetentionPolicies::in_memory(Retention::default(), &[]),
+            replicator: Replicator::in_memory(&ReplicationConfig::default()),
+            consumer_offsets: ConsumerOffsets::in_memory(),
+            dead_letters: DeadLetterStore::in_memory(),
+            tunnel_stats: TunnelStatsRegistry::new(),
+            listener_stats: ListenerStatsRegistry::new(),
+            quotas: QuotaManager::new(),
             trust: Mutex::new(TrustCache::new()),
+            federation: FederationManager::new().with_heartbeat(30),
+            gossip_secs: 60,
+            gossip_max_secs: 900,
+            pending_offers: None,
+            trust_config: TrustConfig::default(),
+            audit: AuditLog::in_memory(),
             capabilities: Mutex::new(CapabilityManager::new()),
             peers: PeerTable::new(),
             sessions: SessionManager::new(),
+            handlers: HandlerRegistry::new(),
+            providers: ProviderRegistry::new(),
             require_auth: true,
             base_dir: PathBuf::from("."),
+            storage: PathBuf::from("data"),
             keepalive_secs: 30,
+            keepalive_max_missed: 3,
             handshake_timeout_secs: 10,
+            write_coalesce_ms: 2,
             max_frame_bytes: 1_048_576,
+            max_frame_headers: 64,
+            max_header_bytes: 16_384,
+            require_digest: false,
+            length_prefixed: false,
+            refuse_quarantined: false,
+            tls_verify: TlsVerify::Any,
+            max_lanes: 256,
+            credit_windows: (4, 256),
             retransmit_timeout_ms: 5000,
             retransmit_max_retries: 3,
             search_index: SearchIndex::build_from_store(&ContentStore::new()),
             offer_interval_secs: 60,
             routing: RoutingTable::new(),
+            route_advert_secs: 30,
+            route_ttl_secs: 90,
+            relay: RelayTable::default(),
+            register_relays: Vec::new(),
+            peer_check_secs: 15,
+            liveness: Liveness::default(),
             saved_sessions: std::sync::Mutex::new(Vec::new()),
+            session_store: SessionStore::new(),
+            session_ttl_secs: 86_400,
+            refresh_ttl_secs: 604_800,
+            session_sweep_secs: 60,
+            replay_cache: ReplayCache::default(),
             rate_limiter: RateLimiter::new(0, 0),
             idem_cache: IdemCache::new(60),
             max_connections: 0,
             max_per_peer: 0,
             active_connections: AtomicU32::new(0),
+            shutdown_grace_secs: 5,
+            going_away: watch::Sender::new(false),
             ai_chats: Vec::new(),
+            hooks: Hooks::new(),
+            state: watch::Sender::new(BurrowState::Initialized),
+            frame_tap: Mutex::new(None),
+            external_addr: Mutex::new(None),
         }
     }
 
+    /// Install or remove the frame tap.
+    ///
+    /// Tunnels accepted afterwards by [`Burrow::handle_tunnel`] report
+    /// every frame, handshake included, to the tap; tunnels already
+    /// open are unaffected.
+    pub fn set_frame_tap(&self, tap: Option<Arc<dyn FrameTap>>) {
+        *self.frame_tap.lock().unwrap_or_else(|e| e.into_inner()) = tap;
+    }
+
+    /// Where peers outside the local network can dial this burrow, if
+    /// a port mapping has said; see [`crate::network::nat`].
+    pub fn external_addr(&self) -> Option<SocketAddr> {
+        *self.external_addr.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record the address a port mapping gave the burrow, or clear it.
+    pub fn set_external_addr(&self, addr: Option<SocketAddr>) {
+        *self.external_addr.lock().unwrap_or_else(|e| e.into_inner()) = addr;
+    }
+
+    /// The signed `OFFER` of reachable peers sent periodically on
+    /// every tunnel, led by this burrow at its external address if it
+    /// has one.  `None` if there is no one to offer.
+    pub async fn offer_frame(&self) -> Option<Frame> {
+        let mut peers = Vec::new();
+        if let Some(addr) = self.external_addr() {
+            peers.push(PeerInfo::new(
+                self.burrow_id(),
+                addr.to_string(),
+                &self.name,
+            ));
+        }
+        peers.extend(self.peers.list_reachable().await);
+        if peers.is_empty() {
+            return None;
+        }
+        Some(PeerOffer::sign(&self.identity, peers).to_frame())
+    }
+
+    /// Register the peers of pending OFFERs whose signers a held
+    /// manifest now vouches for.  Returns how many were registered.
+    pub async fn release_offers(&self) -> usize {
+        let Some(pending) = &self.pending_offers else {
+            return 0;
+        };
+        let released = pending.release(|id| {
+            self.trust
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .is_vouched_for(id)
+        });
+        let mut registered = 0;
+        for offer in released {
+            info!(warren_id = %offer.warren_id, peers = offer.peers.len(), "offer vouched for");
+            for peer in offer.peers {
+                self.peers.register(peer).await;
+                registered += 1;
+            }
+        }
+        registered
+    }
+
+    /// Where the burrow is in its life.
+    pub fn state(&self) -> BurrowState {
+        *self.state.borrow()
+    }
+
+    /// A receiver that sees each state the burrow moves to.
+    pub fn watch_state(&self) -> watch::Receiver<BurrowState> {
+        self.state.subscribe()
+    }
+
+    /// Move the burrow on to `state`, running the state hooks.  A
+    /// burrow never goes back, so a state at or before the current one
+    /// is ignored; returns whether the state changed.
+    pub fn advance(&self, state: BurrowState) -> bool {
+        let moved = self.state.send_if_modified(|current| {
+            let forward = state > *current;
+            if forward {
+                *current = state;
+            }
+            forward
+        });
+        if moved {
+            info!(name = %self.name, state = %state, "burrow state changed");
+            self.hooks.state_changed(state);
+        }
+        moved
+    }
+
     /// Return the burrow's ID (`ed25519:<base32>`).
     pub fn burrow_id(&self) -> String {
         self.identity.burrow_id()
     }
 
+    /// The capabilities granted to a peer that authenticates.
+    pub fn authenticated_caps(&self) -> &'static [Capability] {
+        AUTHENTICATED_CAPS
+    }
+
     /// Get a reference to the base directory.
     pub fn base_dir(&self) -> &Path {
         &self.base_dir
     }
 
-    /// Save the trust cache to disk (if a storage directory exists).
+    /// Get a reference to the storage directory.
+    pub fn storage_dir(&self) -> &Path {
+        &self.storage
+    }
+
+    /// Save the trust cache to `<storage>/trust.tsv`.
     pub fn save_trust(&self) -> Result<(), ProtocolError> {
-        let storage = self.base_dir.join("data");
-        let trust_path = storage.join("trust.tsv");
+        let trust_path = self.storage.join("trust.tsv");
         self.trust
             .lock()
             .unwrap_or_else(|e| e.into_inner())
             .save(&trust_path)
     }
 
+    /// Replace the policy deciding which peers are admitted, e.g. with
+    /// an [`Interactive`](crate::security::trust_policy::Interactive)
+    /// one.
+    pub fn set_trust_policy(&self, policy: Arc<dyn TrustPolicy>) {
+        self.trust
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .set_policy(policy);
+    }
+
+    /// Verify an anchor's manifest and keep it, in the trust cache and
+    /// in `<storage>/manifests/`, unless a newer one from the same
+    /// anchor is already held.  Returns whether it was kept.
+    pub fn accept_manifest(&self, manifest: TrustManifest) -> Result<bool, ProtocolError> {
+        let path = self.storage.join(MANIFESTS_DIR).join(manifest.file_name()?);
+        let added = self
+            .trust
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .add_manifest(manifest.clone());
+        let detail = match &added {
+            Ok(true) => format!("serial {} accepted", manifest.serial),
+            Ok(false) => format!("serial {} superseded", manifest.serial),
+            Err(e) => format!("serial {} rejected: {}", manifest.serial, e),
+        };
+        self.audit
+            .record(AuditKind::Manifest, &manifest.anchor, &detail);
+        let kept = added?;
+        if kept {
+            info!(anchor = %manifest.anchor, members = manifest.members.len(), "manifest accepted");
+            manifest.save(path)?;
+            self.federation.note_change();
+        }
+        Ok(kept)
+    }
+
+    /// The unexpired anchors' manifests held here, this burrow's own
+    /// published one included, sorted by anchor.
+    pub fn held_manifests(&self) -> Vec<TrustManifest> {
+        let mut held: Vec<TrustManifest> = self
+            .trust
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .manifests()
+            .into_iter()
+            .filter(|m| !m.is_expired())
+            .cloned()
+            .collect();
+        if let Some(own) = self.federation.published() {
+            held.retain(|m| m.anchor != own.anchor);
+            held.push(own);
+            held.sort_by(|a, b| a.anchor.cmp(&b.anchor));
+        }
+        held
+    }
+
+    /// Send a digest of the manifests held here to each connected peer
+    /// holding `Federation`; see [`crate::warren::gossip`].  Returns how
+    /// many peers were sent one.
+    pub fn gossip_manifests(&self) -> usize {
+        let digest = gossip::digest_frame(&self.held_manifests());
+        let linked: Vec<String> = {
+            let capabilities = self.capabilities.lock().unwrap_or_else(|e| e.into_inner());
+            self.sessions
+                .peer_ids()
+                .into_iter()
+                .filter(|peer| capabilities.check(peer, Capability::Federation))
+                .collect()
+        };
+        linked
+            .iter()
+            .filter(|peer| self.sessions.send(peer, digest.clone()).is_ok())
+            .count()
+    }
+
+    /// Answer a `MANIFEST` frame from `peer_id`.  Anyone may fetch the
+    /// manifest published here with `MANIFEST latest`; pushing a
+    /// manifest, or gossiping with `digest` and `want`, needs
+    /// `Federation`.  Manifests the gossip calls for, and any `want` of
+    /// our own, follow the reply as extras.
+    pub async fn answer_manifest(&self, frame: &Frame, peer_id: &str) -> DispatchResult {
+        let lane = frame.header("Lane").unwrap_or("0").to_string();
+        let refuse = |e: ProtocolError| {
+            DispatchResult::single(ErrorFrame::from(&e).in_reply_to(frame).build())
+        };
+        let arg = frame.args.first().map(String::as_str);
+        if arg == Some("latest") {
+            let Some(manifest) = self.federation.published() else {
+                return refuse(ProtocolError::Missing(
+                    "no manifest is published here".into(),
+                ));
+            };
+            let mut reply = manifest_reply(&manifest);
+            reply.set_header("Lane", lane);
+            return DispatchResult::single(reply);
+        }
+        // Anchors, and peers relaying for them, need Federation; the
+        // signature is checked against the anchor named inside.
+        if !self
+            .capabilities
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .check(peer_id, Capability::Federation)
+        {
+            return refuse(
+                RabbitError::Capability {
+                    peer_id: peer_id.to_string(),
+                    capability: Capability::Federation,
+                }
+                .into(),
+            );
+        }
+        let push = |m: &TrustManifest| {
+            let mut frame = m.to_frame();
+            frame.set_header("Lane", "0");
+            frame
+        };
+        let mut ok = Frame::new("200 OK");
+        ok.set_header("Lane", lane);
+        let mut extras = Vec::new();
+        match arg {
+            Some("digest") => {
+                let plan = gossip::reconcile(&self.held_manifests(), &gossip::parse_digest(frame));
+                ok.set_header("Pushed", plan.push.len().to_string());
+                ok.set_header("Wanted", plan.want.len().to_string());
+                extras.extend(plan.push.iter().map(push));
+                if !plan.want.is_empty() {
+                    extras.push(gossip::want_frame(&plan.want));
+                }
+            }
+            Some("want") => {
+                let wanted = gossip::parse_want(frame);
+                extras.extend(
+                    self.held_manifests()
+                        .iter()
+                        .filter(|m| wanted.contains(&m.anchor))
+                        .map(push),
+                );
+                ok.set_header("Pushed", extras.len().to_string());
+            }
+            _ => match TrustManifest::from_frame(frame).and_then(|m| self.accept_manifest(m)) {
+                Ok(kept) => {
+                    ok.set_header("Kept", kept.to_string());
+                    if kept {
+                        self.release_offers().await;
+                    }
+                }
+                Err(e) => return refuse(e),
+            },
+        }
+        DispatchResult::with_extras(ok, extras)
+    }
+
+    /// The TLS client configuration for one outgoing connection, to
+    /// `peer` if its ID is known in advance.
+    ///
+    /// Unless [`Burrow::tls_verify`] is `Any`, the server's certificate
+    /// must be pinned in the trust cache (or, under `Tofu`, be new);
+    /// see [`crate::security::trust::PinnedCerts`].
+    pub fn client_tls_config(&self, peer: Option<&str>) -> Arc<rustls::ClientConfig> {
+        if self.tls_verify == TlsVerify::Any {
+            return make_client_config_insecure();
+        }
+        let mut pinned = self
+            .trust
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pinned_certs()
+            .allow_unknown(self.tls_verify == TlsVerify::Tofu);
+        if let Some(peer) = peer {
+            pinned = pinned.expecting(peer);
+        }
+        make_client_config_pinned(Arc::new(pinned))
+    }
+
+    /// Authenticate the federation link to `peer` over `tunnel`, a
+    /// tunnel we dialed to it that has completed the handshake.  On
+    /// success `peer` is granted `Federation` for a session's lifetime;
+    /// either way the outcome is audited.
+    pub async fn authenticate_link<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+        peer: &str,
+    ) -> Result<(), ProtocolError> {
+        let result = self
+            .federation
+            .authenticate_link(tunnel, &self.burrow_id(), peer)
+            .await;
+        self.link_authenticated(peer, &result);
+        result
+    }
+
+    /// Grant `Federation` to a peer whose link has authenticated, and
+    /// audit how it went.
+    fn link_authenticated(&self, peer: &str, result: &Result<(), ProtocolError>) {
+        match result {
+            Ok(()) => {
+                let ttl = self.session_ttl_secs;
+                self.capabilities
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .grant(peer, Capability::Federation, ttl);
+                info!(peer_id = %peer, "federation link authenticated");
+                let detail = format!("Federation for {}s by federation link", ttl);
+                self.audit.record(AuditKind::Grant, peer, &detail);
+            }
+            Err(e) => {
+                warn!(peer_id = %peer, err = %e, "federation link authentication failed");
+                let detail = format!("federation link: {}", e);
+                self.audit.record(AuditKind::AuthFailure, peer, &detail);
+            }
+        }
+    }
+
+    /// Answer a `FED-LINK-OPEN`, `FED-LINK-ACCEPT` or `FED-LINK-CLOSE`
+    /// from `peer_id`; see [`crate::warren::federation`].  Returns the
+    /// reply, if any: an accept is not answered.  A link closed by the
+    /// peer takes its `Federation` grant with it.
+    pub fn answer_link(&self, frame: &Frame, peer_id: &str) -> Option<Frame> {
+        let answer = match frame.verb_kind() {
+            VerbKind::Verb(Verb::FedLinkOpen) => self.federation.answer_link_open(frame, peer_id),
+            VerbKind::Verb(Verb::FedLinkClose) => {
+                let answer = self.federation.answer_link_close(frame, peer_id);
+                if answer.is_ok() {
+                    let reason = frame.header("Reason").unwrap_or("closed by peer");
+                    self.link_torn_down(peer_id, reason);
+                }
+                answer
+            }
+            _ => {
+                self.federation.note_link_accept(frame, peer_id);
+                return None;
+            }
+        };
+        Some(answer.unwrap_or_else(|e| ErrorFrame::from(&e).in_reply_to(frame).build()))
+    }
+
+    /// Tear down the federation link to `peer` for `reason`, telling
+    /// the peer if it is connected.  Fails with `Missing` if there is
+    /// no link to `peer`.
+    pub fn close_link(&self, peer: &str, reason: &str) -> Result<(), ProtocolError> {
+        let frame = self
+            .federation
+            .close_link(peer, reason)
+            .ok_or_else(|| ProtocolError::Missing(format!("no federation link to {}", peer)))?;
+        self.link_torn_down(peer, reason);
+        if self.sessions.send(peer, frame).is_err() {
+            debug!(peer_id = %peer, "closed federation link to a peer not connected");
+        }
+        Ok(())
+    }
+
+    /// Send a heartbeat on each open federation link whose peer is
+    /// connected.  Returns how many were sent.
+    pub fn heartbeat_links(&self) -> usize {
+        self.federation
+            .open_links()
+            .iter()
+            .filter(|peer| {
+                self.sessions
+                    .send(peer, self.federation.open_frame())
+                    .is_ok()
+            })
+            .count()
+    }
+
+    /// Trust `anchor` from now on and across restarts, admitting the
+    /// peers its manifests vouch for under `anchor-required`.  Returns
+    /// whether it was new or changed.  Fails with `BadRequest` if its
+    /// ID is not a burrow ID.
+    pub fn register_anchor(&self, anchor: Anchor) -> Result<bool, ProtocolError> {
+        let id = anchor.id.clone();
+        let changed = self.federation.register_anchor(anchor)?;
+        if changed {
+            self.refresh_trust_policy()?;
+            self.audit
+                .record(AuditKind::Grant, &id, "registered as anchor");
+        }
+        Ok(changed)
+    }
+
+    /// Stop trusting the anchor `id`, unless `trust.anchors` names it.
+    /// Returns whether it was registered.
+    pub fn forget_anchor(&self, id: &str) -> Result<bool, ProtocolError> {
+        let forgotten = self.federation.forget_anchor(id)?;
+        if forgotten {
+            self.refresh_trust_policy()?;
+            self.audit
+                .record(AuditKind::Revoke, id, "no longer an anchor");
+        }
+        Ok(forgotten)
+    }
+
+    fn refresh_trust_policy(&self) -> Result<(), ProtocolError> {
+        let policy = policy_from_config(&anchored(&self.trust_config, &self.federation))?;
+        self.trust
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .set_policy(policy);
+        Ok(())
+    }
+
+    /// Revoke `Federation` from a peer whose link was closed, and
+    /// audit it.
+    fn link_torn_down(&self, peer: &str, reason: &str) {
+        self.capabilities
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .revoke(peer, Capability::Federation);
+        info!(peer_id = %peer, reason, "federation link closed");
+        let detail = format!("Federation by federation link closed: {}", reason);
+        self.audit.record(AuditKind::Revoke, peer, &detail);
+    }
+
+    /// Answer frames of `verb`, which the protocol does not define, with
+    /// `handler` on every tunnel; see [`crate::dispatch::handlers`].
+    pub fn register_handler(
+        &self,
+        verb: &str,
+        handler: Arc<dyn FrameHandler>,
+    ) -> Result<(), ProtocolError> {
+        self.handlers.register(verb, handler)
+    }
+
+    /// Serve the selectors under `prefix` that the content store lacks
+    /// from `provider`; see [`crate::content::provider`].
+    pub fn register_provider(
+        &self,
+        prefix: &str,
+        provider: Arc<dyn ContentProvider>,
+    ) -> Result<(), ProtocolError> {
+        self.providers.register(prefix, provider)
+    }
+
+    /// Queue `frame` for the open tunnel to `peer_id`, accepted or
+    /// dialed.  Fails with `Missing` if there is none, and with `Busy`
+    /// if it is backed up.
+    pub fn send_to(&self, peer_id: &str, frame: Frame) -> Result<(), ProtocolError> {
+        self.sessions.send(peer_id, frame)
+    }
+
+    /// Queue a copy of `frame` for every open tunnel.  Returns how many
+    /// tunnels it was queued for.
+    pub fn broadcast(&self, frame: &Frame) -> usize {
+        self.sessions.broadcast_all(frame, None)
+    }
+
+    /// Like [`Burrow::broadcast`], but skipping the tunnel to
+    /// `peer_id`, e.g. the peer the frame came from.
+    pub fn broadcast_except(&self, frame: &Frame, peer_id: &str) -> usize {
+        self.sessions.broadcast_all(frame, Some(peer_id))
+    }
+
+    /// The `REPLICATE` requests for the topics followed on `peer_id`;
+    /// see [`crate::events::replication`].
+    pub fn replication_requests(&self, peer_id: &str) -> Vec<Frame> {
+        self.replicator
+            .requests(peer_id, self.continuity.as_deref())
+    }
+
+    /// Take in `frame` from `peer_id` if it is an event of a topic
+    /// followed there, and deliver it to local subscribers.  Returns
+    /// whether it was one.
+    pub async fn accept_replicated(&self, frame: &Frame, peer_id: &str) -> bool {
+        let continuity = self.continuity.as_deref();
+        let Some(broadcast) = self
+            .replicator
+            .accept(frame, peer_id, &self.events, continuity)
+        else {
+            return false;
+        };
+        if !broadcast.is_empty() {
+            self.sessions.broadcast(broadcast).await;
+        }
+        true
+    }
+
+    /// A snapshot of the warren as this burrow knows it, from its peer
+    /// table, its tunnels and its routing table; see
+    /// [`crate::warren::routing`].
+    pub async fn topology(&self) -> Topology {
+        let peers = self.peers.list().await;
+        let direct = self.sessions.peer_ids();
+        self.routing
+            .topology(&self.burrow_id(), &peers, &direct)
+            .await
+    }
+
+    /// Where `address` is served, as seen from here; see
+    /// [`crate::protocol::address`].  A burrow in the warren is
+    /// reached if the topology has a path to it; one that is the
+    /// remote end of a federation link, only while the link is open.
+    /// Fails with `NoRoute` if it cannot be reached.
+    pub async fn resolve(&self, address: &RabbitAddress) -> Result<Scope, ProtocolError> {
+        let warren = address.warren.as_str();
+        if warren == self.identity.burrow_id() {
+            return Ok(Scope::Local);
+        }
+        if self.federation.link(warren).is_some() {
+            if self
+                .federation
+                .open_links()
+                .iter()
+                .any(|peer| peer == warren)
+            {
+                return Ok(Scope::Federated);
+            }
+            return Err(ProtocolError::NoRoute(format!(
+                "federation link to {} is not open",
+                warren
+            )));
+        }
+        if self.topology().await.shortest_path(warren).is_some() {
+            return Ok(Scope::Warren);
+        }
+        Err(ProtocolError::NoRoute(format!("no route to {}", warren)))
+    }
+
+    /// `frame` addressed with `Target` to the burrow its `rabbit://`
+    /// address names, or `None` if it gives no such address, names
+    /// this burrow, or already has a `Target`.
+    async fn address_request(&self, frame: &Frame) -> Result<Option<Frame>, ProtocolError> {
+        if frame.header("Target").is_some() {
+            return Ok(None);
+        }
+        let Some(address) = RabbitAddress::of_request(frame).transpose()? else {
+            return Ok(None);
+        };
+        let scope = self.resolve(&address).await?;
+        if scope == Scope::Local {
+            return Ok(None);
+        }
+        debug!(address = %address, scope = scope.label(), "relaying request for address");
+        let mut addressed = frame.clone();
+        addressed.set_header("Target", &address.warren);
+        Ok(Some(addressed))
+    }
+
+    /// Relay `frame`, received from `from`, if it is addressed to
+    /// another burrow, by a `Target` header or by the `rabbit://`
+    /// address a `FETCH` or `LIST` gives; see
+    /// [`crate::warren::routing`].  The frame goes to the target
+    /// itself if it has a tunnel here, or else to the next hop in the
+    /// routing table.  Frames to or from a burrow this one relays for
+    /// count against its bandwidth; see [`crate::warren::relay`].
+    pub async fn forward(&self, frame: &Frame, from: &str) -> Forward {
+        let addressed;
+        let frame = match self.address_request(frame).await {
+            Ok(Some(request)) => {
+                addressed = request;
+                &addressed
+            }
+            Ok(None) => frame,
+            Err(e) => return Forward::Reply(ErrorFrame::from(&e).in_reply_to(frame).build()),
+        };
+        let me = self.identity.burrow_id();
+        let Some(target) = frame.header("Target").filter(|&t| t != me) else {
+            return Forward::Local;
+        };
+        let mut via = via_hops(frame);
+        if via.is_empty() {
+            via.push(from);
+        }
+        let origin = via[0].to_string();
+        let is_response = matches!(frame.verb_kind(), VerbKind::Status(_));
+
+        let hop_count: u32 = frame
+            .header("Hop-Count")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_HOP_COUNT);
+        let error = if hop_count == 0 {
+            ErrorFrame::new(400, "HOP LIMIT").detail("hop count exceeded")
+        } else if via.contains(&me.as_str()) {
+            ErrorFrame::from(&ProtocolError::NoRoute(format!(
+                "routing loop toward {}",
+                target
+            )))
+        } else {
+            let next_hop = if self.sessions.has_session(target) {
+                Some(target.to_string())
+            } else {
+                self.routing.next_hop(target).await
+            };
+            match next_hop {
+                Some(next_hop) => {
+                    let mut relayed = frame.clone();
+                    relayed.set_header("Hop-Count", (hop_count - 1).to_string());
+                    relayed.set_header("Via", format!("{}, {}", via.join(", "), me));
+                    let size = relayed.serialize().len();
+                    let now = Instant::now();
+                    let sent = self
+                        .relay
+                        .charge(from, size, now)
+                        .and_then(|()| self.relay.charge(&next_hop, size, now))
+                        .and_then(|()| self.sessions.send(&next_hop, relayed));
+                    match sent {
+                        Ok(()) => {
+                            debug!(target = %target, next_hop = %next_hop, "frame forwarded");
+                            return Forward::Sent(next_hop);
+                        }
+                        Err(ProtocolError::Missing(_)) => {
+                            ErrorFrame::from(&ProtocolError::NoRoute(format!(
+                                "no route to {}: {} is not connected",
+                                target, next_hop
+                            )))
+                        }
+                        Err(e) => ErrorFrame::from(&e),
+                    }
+                }
+                None => {
+                    ErrorFrame::from(&ProtocolError::NoRoute(format!("no route to {}", target)))
+                }
+            }
+        };
+
+        if is_response {
+            // Answering an undeliverable response could bounce errors
+            // between burrows forever.
+            warn!(target = %target, status = %frame.verb, "dropping undeliverable response");
+            return Forward::Dropped;
+        }
+        let mut error = error.in_reply_to(frame);
+        if origin != from {
+            error = error.header("Target", origin);
+        }
+        Forward::Reply(error.build())
+    }
+
+    /// Send each connected peer a `ROUTE-ADVERT` of the burrows
+    /// reachable through this one; see [`crate::warren::routing`].  A
+    /// relay with room says so in a `Relay` header.  Returns how many
+    /// peers were sent one.
+    pub async fn advertise_routes(&self) -> usize {
+        let me = self.identity.burrow_id();
+        let direct = self.sessions.peer_ids();
+        let relay_slots = self.relay.free_slots();
+        let mut sent = 0;
+        for peer in &direct {
+            let mut body = String::new();
+            for other in direct.iter().filter(|&other| other != peer) {
+                body.push_str(&format!("{}\t1\n", other));
+            }
+            for (target, distance) in self.routing.advert_for(peer).await {
+                if target != me && !direct.contains(&target) {
+                    body.push_str(&format!("{}\t{}\n", target, distance));
+                }
+            }
+            if body.is_empty() && relay_slots == 0 {
+                continue;
+            }
+            let mut advert = Frame::new("ROUTE-ADVERT");
+            if relay_slots > 0 {
+                advert.set_header("Relay", relay_slots.to_string());
+            }
+            advert.set_body(body);
+            if self.sessions.send(peer, advert).is_ok() {
+                sent += 1;
+            }
+        }
+        debug!(peers = sent, "sent route adverts");
+        sent
+    }
+
+    /// Ping each connected peer and judge every peer's health by when
+    /// it was last seen; see [`crate::warren::peers`].  Routes through
+    /// peers that are down or pruned are dropped.
+    pub async fn check_peers(&self) -> HealthReport {
+        for peer in self.sessions.peer_ids() {
+            let _ = self.sessions.send(&peer, keepalive::health_probe());
+        }
+        let now = now_unix();
+        let report = self.peers.check_health(now, &self.liveness).await;
+        for (id, health) in &report.changed {
+            info!(peer_id = %id, health = health.label(), "peer health changed");
+            if *health == PeerHealth::Down {
+                self.routing.remove_via(id).await;
+            }
+        }
+        for id in &report.pruned {
+            info!(peer_id = %id, "pruned stale peer");
+            self.routing.remove_via(id).await;
+        }
+        report
+    }
+
+    /// Drop each topic's events beyond its retention limits, from its
+    /// log and from memory; see [`retention::sweep`].  Returns how many
+    /// were dropped.
+    pub fn compact_events(&self) -> usize {
+        retention::sweep(
+            &self.events,
+            self.continuity.as_deref(),
+            &self.retention,
+            &self.quotas,
+        )
+    }
+
+    /// Start the janitor: a task that runs
+    /// [`compact_events`](Self::compact_events) every `compact_secs`,
+    /// off the runtime's worker threads.  Returns `None`, starting
+    /// nothing, if `compact_secs` is 0.
+    pub fn spawn_janitor(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        if self.compact_secs == 0 {
+            return None;
+        }
+        let burrow = Arc::clone(self);
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(burrow.compact_secs));
+            loop {
+                ticker.tick().await;
+                let burrow = Arc::clone(&burrow);
+                let _ = tokio::task::spawn_blocking(move || burrow.compact_events()).await;
+            }
+        }))
+    }
+
+    /// Drop learned routes that have not been advertised again within
+    /// `route_ttl_secs`.  Returns how many were dropped.
+    pub async fn expire_routes(&self) -> usize {
+        let now = now_unix();
+        self.routing.expire(now).await
+    }
+
+    /// Save unexpired capability grants to
+    /// `<storage>/capabilities.json`.
+    pub fn save_capabilities(&self) -> Result<(), ProtocolError> {
+        self.capabilities
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .save(self.storage.join(CAPABILITIES_FILE))
+    }
+
+    /// Write a [snapshot](crate::snapshot) of the burrow's persistent
+    /// state to `archive`, having saved the trust cache, capability
+    /// grants and consumer offsets and written out every queued event.
+    pub fn snapshot(&self, archive: &Path) -> Result<SnapshotManifest, ProtocolError> {
+        self.save_trust()?;
+        self.save_capabilities()?;
+        self.consumer_offsets.save()?;
+        if let Some(store) = &self.continuity {
+            store.flush()?;
+        }
+        self.audit.flush()?;
+        snapshot::snapshot(&self.storage, archive)
+    }
+
+    /// Save resumable session states to `<storage>/sessions.tsv` and
+    /// unexpired session tokens to `<storage>/session_tokens.tsv`.
+    pub fn save_sessions(&self) -> Result<(), ProtocolError> {
+        let saved = self
+            .saved_sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        save_session_states(&saved, &self.storage.join(SESSIONS_FILE)).map_err(|e| {
+            ProtocolError::InternalError(format!("failed to write sessions: {}", e))
+        })?;
+        self.session_store
+            .save(self.storage.join(SESSION_TOKENS_FILE))
+    }
+
+    /// Revoke one session.  Its tunnel, if open, is closed, and the
+    /// token can no longer resume.  Returns the session, if it existed.
+    pub fn revoke_session(&self, token: &str) -> Option<SessionRecord> {
+        let revoked = self.session_store.revoke_session(token);
+        if let Some(session) = &revoked {
+            info!(peer_id = %session.peer_id, "session revoked");
+            self.persist_revocation();
+        }
+        revoked
+    }
+
+    /// Revoke every session of a peer, closing its open tunnels.
+    /// Returns the revoked sessions.
+    pub fn revoke_peer(&self, peer_id: &str) -> Vec<SessionRecord> {
+        let revoked = self.session_store.revoke_peer(peer_id);
+        if !revoked.is_empty() {
+            info!(peer_id = %peer_id, count = revoked.len(), "peer sessions revoked");
+            self.persist_revocation();
+        }
+        revoked
+    }
+
+    /// Trade a peer's refresh token for a new session token and
+    /// refresh token, naming its current capabilities.
+    ///
+    /// The refresh token must be one this burrow issued to `peer_id`.
+    /// The new session token is recorded like a handshake's; revoking
+    /// the old one is up to the caller.
+    pub fn refresh_session(
+        &self,
+        peer_id: &str,
+        refresh_token: &str,
+    ) -> Result<(String, String), ProtocolError> {
+        let issuer = self.identity.burrow_id();
+        let claims = TokenClaims::verify_from(refresh_token, &issuer, TokenKind::Refresh)?;
+        if claims.subject != peer_id {
+            return Err(ProtocolError::Forbidden(format!(
+                "refresh token was issued to {}",
+                claims.subject
+            )));
+        }
+        let caps = self
+            .capabilities
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .active_capabilities(peer_id);
+        let token =
+            self.identity
+                .issue_token(TokenKind::Access, peer_id, &caps, self.session_ttl_secs);
+        let refresh =
+            self.identity
+                .issue_token(TokenKind::Refresh, peer_id, &caps, self.refresh_ttl_secs);
+        self.session_store
+            .issue(&token, peer_id, self.session_ttl_secs);
+        debug!(peer_id = %peer_id, "session refreshed");
+        Ok((token, refresh))
+    }
+
+    /// Drop expired session tokens, closing the tunnels that used
+    /// them.  Returns how many were dropped.
+    pub fn sweep_sessions(&self) -> usize {
+        let swept = self.session_store.sweep_expired();
+        if swept > 0 {
+            debug!(count = swept, "expired sessions swept");
+        }
+        swept
+    }
+
+    /// Write the session tokens out at once, so a revocation survives
+    /// a crash.
+    fn persist_revocation(&self) {
+        if let Err(e) = self
+            .session_store
+            .save(self.storage.join(SESSION_TOKENS_FILE))
+        {
+            warn!(err = %e, "failed to save session tokens");
+        }
+    }
+
+    /// Close every tunnel, then persist trust, capability grants,
+    /// saved sessions, and routes before exit.
+    ///
+    /// Each tunnel sends `GOAWAY`, refuses new requests, and closes
+    /// once the peer has acknowledged everything in flight or
+    /// `shutdown_grace_secs` have passed; frames still unacknowledged
+    /// then are parked as dead letters.  Every save step is attempted;
+    /// the first failure is returned.
+    pub async fn shutdown(&self) -> Result<(), ProtocolError> {
+        self.advance(BurrowState::Draining);
+        self.going_away.send_replace(true);
+        let deadline = tokio::time::Instant::now()
+            + Duration::from_secs(self.shutdown_grace_secs)
+            + Duration::from_secs(1);
+        while self.active_connections.load(Ordering::Relaxed) > 0
+            && tokio::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        info!(name = %self.name, "saving state for shutdown");
+        let results = [
+            self.save_trust(),
+            self.save_capabilities(),
+            self.save_sessions(),
+            self.consumer_offsets.save(),
+            self.routing.save(self.storage.join(ROUTES_FILE)).await,
+        ];
+        for r in &results {
+            if let Err(e) = r {
+                warn!(err = %e, "failed to save state");
+            }
+        }
+        self.advance(BurrowState::Stopped);
+        results.into_iter().collect()
+    }
+
+    /// Wait until every tunnel the burrow serves has closed, then
+    /// flush its event store, so that another burrow may open the
+    /// storage directory.  Call after [`shutdown`](Self::shutdown),
+    /// whose `GOAWAY` has the tunnels close within
+    /// `shutdown_grace_secs`.
+    pub async fn wait_closed(&self) -> Result<(), ProtocolError> {
+        while self.active_connections.load(Ordering::Relaxed) > 0 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        match self.continuity.as_deref() {
+            Some(store) => append_in_place(|| store.flush()),
+            None => Ok(()),
+        }
+    }
+
+    /// Send dead letter `id` to its peer again.
+    ///
+    /// The peer must have a tunnel open; the frame is queued on it like
+    /// any fan-out frame and given a fresh sequence number.  Returns
+    /// the entry, which is removed from the store.
+    pub async fn retry_dead_letter(&self, id: u64) -> Result<DeadLetter, ProtocolError> {
+        let entry = self
+            .dead_letters
+            .list()
+            .into_iter()
+            .find(|e| e.id == id)
+            .ok_or_else(|| ProtocolError::Missing(format!("no dead letter {}", id)))?;
+        if !self.sessions.has_session(&entry.peer_id) {
+            return Err(ProtocolError::Missing(format!(
+                "{} is not connected",
+                entry.peer_id
+            )));
+        }
+        let mut frame = Frame::parse(&entry.frame)?;
+        frame.headers.remove("Seq");
+        self.dead_letters.take(id)?;
+        self.sessions
+            .broadcast(vec![(entry.peer_id.clone(), frame)])
+            .await;
+        Ok(entry)
+    }
+
+    /// Park every frame still in flight on `lanes` as a dead letter
+    /// for `peer_id`.
+    async fn park_in_flight(&self, peer_id: &str, lanes: &LaneManager) {
+        for (lane, sent) in lanes.drain_in_flight().await {
+            let reason = if sent.retries >= self.retransmit_max_retries {
+                format!("unacknowledged after {} retransmissions", sent.retries)
+            } else {
+                "tunnel closed before acknowledgement".to_string()
+            };
+            if let Err(e) = self.dead_letters.record(peer_id, lane, sent.data, reason) {
+                warn!(peer_id = %peer_id, err = %e, "failed to park undeliverable frame");
+            }
+        }
+    }
+
     /// Create a [`Dispatcher`] that borrows this burrow's content,
-    /// event engine, peer table, capabilities, and continuity store.
+    /// event engine, peer table, capabilities, quotas, replay cache,
+    /// audit log, rate limiter, and continuity store.
     pub fn dispatcher(&self) -> Dispatcher<'_> {
         let mut d = Dispatcher::new(&self.content, &self.events)
             .with_peers(&self.peers)
             .with_capabilities(&self.capabilities)
-            .with_search_index(&self.search_index);
-        if let Some(ref cont) = self.continuity {
+            .with_search_index(&self.search_index)
+            .with_quotas(&self.quotas)
+            .with_retention(&self.retention)
+            .with_offsets(&self.consumer_offsets)
+            .with_hooks(&self.hooks)
+            .with_replay_cache(&self.replay_cache)
+            .with_audit(&self.audit)
+            .with_rate_limiter(&self.rate_limiter)
+            .with_handlers(&self.handlers)
+            .with_trust(&self.trust)
+            .with_providers(&self.providers)
+            .with_routing(&self.routing, self.route_ttl_secs)
+            .with_relays(&self.relay)
+            .with_sessions(&self.sessions)
+            .with_local_id(self.burrow_id());
+        if let Some(cont) = self.continuity.as_deref() {
             d = d.with_continuity(cont);
         }
+        if let Some(ref pending) = self.pending_offers {
+            d = d.with_pending_offers(pending);
+        }
         d
     }
 
@@ -287,12 +1729,132 @@ impl Burrow {
     /// 5. Save trust cache on exit.
     ///
     /// Returns the authenticated peer ID (or "anonymous").
-    #[instrument(skip(self, tunnel), fields(burrow = %self.name))]
+    ///
+    /// Runs in a span carrying the burrow name and, once the handshake
+    /// completes, the peer ID; each frame is dispatched in a child
+    /// span with its lane and verb.  If a frame tap is installed the
+    /// tunnel is wrapped in a [`TapTunnel`] first.
     pub async fn handle_tunnel<T: Tunnel>(&self, tunnel: &mut T) -> Result<String, ProtocolError> {
+        let handshake_timeout = Duration::from_secs(self.handshake_timeout_secs);
+        self.handle_tunnel_within(tunnel, handshake_timeout).await
+    }
+
+    /// [`Burrow::handle_tunnel`], allowing the handshake
+    /// `handshake_timeout` instead of `handshake_timeout_secs`, as a
+    /// listener with limits of its own does.  A handshake that runs
+    /// over fails with `Timeout`.
+    #[instrument(skip(self, tunnel), fields(burrow = %self.name, peer = tracing::field::Empty))]
+    pub async fn handle_tunnel_within<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+        handshake_timeout: Duration,
+    ) -> Result<String, ProtocolError> {
+        let tap = self
+            .frame_tap
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let result = match tap {
+            Some(tap) => {
+                let tunnel = &mut TapTunnel::new(tunnel, tap);
+                self.serve_tunnel(tunnel, handshake_timeout).await
+            }
+            None => self.serve_tunnel(tunnel, handshake_timeout).await,
+        };
+        if let Err(e) = &result {
+            self.hooks.error(tunnel.peer_id(), e);
+        }
+        result
+    }
+
+    /// Accept tunnels from `acceptor` and handle each on a task of its
+    /// own, until the future is dropped.  The burrow serves TLS, plain
+    /// TCP and QUIC alike this way.
+    pub async fn serve<A: Acceptor>(self: &Arc<Self>, acceptor: &A) {
+        loop {
+            let mut tunnel = match acceptor.accept().await {
+                Ok(tunnel) => tunnel,
+                Err(e) => {
+                    warn!(err = %e, "accept failed");
+                    continue;
+                }
+            };
+            let burrow = Arc::clone(self);
+            tokio::spawn(async move {
+                match burrow.handle_tunnel(&mut tunnel).await {
+                    Ok(id) => info!(peer_id = %id, "tunnel closed cleanly"),
+                    Err(e) => warn!(err = %e, "tunnel error"),
+                }
+            });
+        }
+    }
+
+    /// Have `tunnel` coalesce its writes, if configured to.  Called once
+    /// the handshake is done, so its frames are not held back.
+    pub fn coalesce_writes<T: Tunnel>(&self, tunnel: &mut T) {
+        if self.write_coalesce_ms > 0 {
+            tunnel.coalesce_writes(Duration::from_millis(self.write_coalesce_ms));
+        }
+    }
+
+    /// The size limits applied to inbound frames.
+    pub fn frame_limits(&self) -> FrameLimits {
+        FrameLimits {
+            max_headers: self.max_frame_headers,
+            max_header_bytes: self.max_header_bytes,
+            max_body_bytes: self.max_frame_bytes,
+        }
+    }
+
+    /// Add a `Digest` to an outbound EVENT or FETCH response when
+    /// digests are required.  `request` is the frame being answered.
+    fn stamp_digest(&self, frame: &mut Frame, request: Option<&Frame>) {
+        if !self.require_digest {
+            return;
+        }
+        let is_event = frame.verb_kind() == VerbKind::Verb(Verb::Event);
+        let answers_fetch = request.is_some_and(|r| r.verb_kind() == VerbKind::Verb(Verb::Fetch));
+        if is_event || answers_fetch {
+            frame.set_digest();
+        }
+    }
+
+    /// Number a frame sent to `peer_id` on its lane and note its
+    /// delivery.  The lane's next `Seq` replaces an EVENT's number in
+    /// its topic, which the event keeps as `Event-Seq` for followers
+    /// and consumer offsets.  Returns the lane and the number.
+    async fn sequence(
+        &self,
+        peer_id: &str,
+        tunnel_id: u64,
+        lanes: &LaneManager,
+        frame: &mut Frame,
+    ) -> (u16, u64) {
+        let lane_id = frame_lane(frame);
+        let seq = lanes.next_seq(lane_id).await;
+        if frame.verb_kind() == VerbKind::Verb(Verb::Event) {
+            if let Some(event_seq) = frame.header("Seq").map(str::to_string) {
+                frame.set_header("Event-Seq", event_seq);
+            }
+        }
+        frame.set_header("Seq", seq.to_string());
+        self.note_delivery(peer_id, tunnel_id, frame);
+        (lane_id, seq)
+    }
+
+    async fn serve_tunnel<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+        handshake_timeout: Duration,
+    ) -> Result<String, ProtocolError> {
+        let counters = Arc::new(TunnelCounters::default());
+        let tunnel = &mut StatsTunnel::new(tunnel, counters.clone());
+        tunnel.set_limits(self.frame_limits());
+
         // ── Connection limit enforcement (H3) ─────────────────
         let current = self.active_connections.fetch_add(1, Ordering::Relaxed);
+        let _slot = ConnectionSlot(&self.active_connections);
         if self.max_connections > 0 && current >= self.max_connections {
-            self.active_connections.fetch_sub(1, Ordering::Relaxed);
             let mut err = Frame::new("503 BUSY");
             err.set_body("connection limit reached");
             let _ = tunnel.send_frame(&err).await;
@@ -301,23 +1863,53 @@ impl Burrow {
             ));
         }
 
+        // ── Handshake throttling ───────────────────────────────
+        let addr = tunnel
+            .peer_addr()
+            .map_or_else(|| tunnel.peer_id().to_string(), |a| a.ip().to_string());
+        if let Err(e) = self.rate_limiter.check_handshake(&addr) {
+            self.audit
+                .record(AuditKind::AuthFailure, &addr, &e.to_string());
+            let _ = tunnel.send_frame(&Frame::from(e.clone())).await;
+            return Err(e);
+        }
+
         // ── Handshake (with timeout) ───────────────────────────
-        let handshake_timeout = Duration::from_secs(self.handshake_timeout_secs);
-        let peer_id =
+        let (peer_id, mut session_token) =
             match tokio::time::timeout(handshake_timeout, self.run_handshake(tunnel)).await {
-                Ok(result) => result?,
+                Ok(Ok(result)) => result,
+                Ok(Err(e)) => {
+                    self.audit
+                        .record(AuditKind::AuthFailure, tunnel.peer_id(), &e.to_string());
+                    return Err(e);
+                }
                 Err(_) => {
+                    self.audit.record(
+                        AuditKind::AuthFailure,
+                        tunnel.peer_id(),
+                        "handshake timed out",
+                    );
                     return Err(ProtocolError::Timeout("handshake timed out".into()));
                 }
             };
+        tracing::Span::current().record("peer", peer_id.as_str());
+        self.coalesce_writes(tunnel);
+        self.hooks.peer_connected(&peer_id);
 
         // ── Dispatch loop with lane management ─────────────────
-        let dispatcher = self.dispatcher();
-        let lanes = LaneManager::new();
+        let lanes = Arc::new(LaneManager::with_max_lanes(self.max_lanes as usize));
+        let dispatcher = self.dispatcher().with_lanes(&lanes);
+        // The registration also tells this tunnel's deliveries apart
+        // from those on the peer's other tunnels.
+        let tunnel_id = self
+            .tunnel_stats
+            .register(&peer_id, counters.clone(), lanes.clone());
+        let mut credit = CreditController::new(self.credit_windows.0, self.credit_windows.1);
 
         // Register this tunnel with the session manager for cross-
-        // tunnel event fan-out.  The receiver feeds the writer half.
-        let mut fanout_rx = self.sessions.register(&peer_id, 256);
+        // tunnel event fan-out.  The receiver feeds the writer half;
+        // the registration is dropped however the loop ends.
+        let (_registration, mut fanout_rx) = self.sessions.attach(&peer_id, 256);
 
         // Keepalive state.
         let keepalive_enabled = self.keepalive_secs > 0;
@@ -328,8 +1920,8 @@ impl Burrow {
                 3600 // inert; never fires in practice
             }));
         keepalive_ticker.tick().await; // consume initial instant tick
-        let mut missed_pongs: u32 = 0;
-        let mut awaiting_pong = false;
+        let mut keepalive = Keepalive::new(self.keepalive_max_missed);
+        let mut peer_dead = false;
 
         // Retransmission state.
         let retransmit_enabled = self.retransmit_timeout_ms > 0;
@@ -347,72 +1939,425 @@ impl Burrow {
         }));
         offer_ticker.tick().await; // consume initial instant tick
 
+        // CHUNK frames of FETCH responses, sent between inbound frames
+        // so a CANCEL can stop a transfer partway, and shared fairly
+        // between lanes by priority.
+        // Events, numbered on their lanes, wait their turn here too,
+        // each with its number so that it is tracked for
+        // retransmission once sent.
+        let mut outbox: LaneScheduler<(Frame, Option<u64>)> = LaneScheduler::new();
+
+        // Events replayed from the continuity log, read into the
+        // outbox as it drains so that a long replay keeps pace with
+        // the tunnel instead of being read all at once.  Each replay
+        // ends with a `None` for its lane; until then, live events for
+        // the lane are held back so as not to overtake older ones.
+        let mut replays = SelectAll::new();
+        let mut replaying: HashMap<u16, usize> = HashMap::new();
+        let mut held: HashMap<u16, Vec<Frame>> = HashMap::new();
+
+        // Graceful shutdown: once the burrow is going away we send
+        // GOAWAY, refuse new requests, and close when the peer has
+        // acknowledged everything in flight or the grace period ends.
+        let mut going_away = self.going_away.subscribe();
+        let mut drain_deadline: Option<tokio::time::Instant> = None;
+        let mut peer_going_away = false;
+
+        // Revocation: the tunnel closes once its session is revoked
+        // or swept as expired.
+        let mut session_removals = self.session_store.subscribe();
+
         loop {
+            if drain_deadline.is_none() && *going_away.borrow_and_update() {
+                let mut goaway = Frame::new("GOAWAY");
+                goaway.set_header("Lane", "0");
+                tunnel.send_frame(&goaway).await?;
+                debug!(peer_id = %peer_id, "going away");
+                drain_deadline = Some(
+                    tokio::time::Instant::now() + Duration::from_secs(self.shutdown_grace_secs),
+                );
+            }
+            if let Some(deadline) = drain_deadline {
+                if (outbox.is_empty() && lanes.unsettled().await == 0)
+                    || tokio::time::Instant::now() >= deadline
+                {
+                    break;
+                }
+            }
+
             tokio::select! {
                 // ── Inbound: frames from the tunnel ────────────
                 inbound = tunnel.recv_frame() => {
-                    let frame = match inbound? {
-                        Some(f) => f,
-                        None => {
+                    let frame = match inbound {
+                        Ok(Some(f)) => f,
+                        Ok(None) => {
                             debug!(peer_id = %peer_id, "tunnel closed");
                             break;
                         }
-                    };
-
-                    // ── Max frame size enforcement ─────────────
-                    if let Some(ref body) = frame.body {
-                        if body.len() > self.max_frame_bytes {
-                            let err_frame: Frame = ProtocolError::BadRequest(
-                                format!(
-                                    "frame body {} bytes exceeds limit {}",
-                                    body.len(),
-                                    self.max_frame_bytes
-                                ),
-                            )
-                            .into();
-                            tunnel.send_frame(&err_frame).await?;
+                        // The tunnel dropped an oversized or corrupted
+                        // frame and can carry on with the next one.
+                        Err(
+                            e @ (ProtocolError::TooLarge(_)
+                            | ProtocolError::PreconditionFailed(_)),
+                        ) => {
+                            warn!(peer_id = %peer_id, err = %e, "rejected inbound frame");
+                            tunnel.send_frame(&e.into()).await?;
+                            continue;
+                        }
+                        Err(e) => return Err(e),
+                    };
+
+                    // ── Max frame size enforcement ─────────────
+                    // Tunnels that parse bytes themselves reject these
+                    // earlier; this covers the rest.
+                    if let Some(ref body) = frame.body {
+                        if body.len() > self.max_frame_bytes {
+                            let err_frame: Frame = ProtocolError::TooLarge(
+                                format!(
+                                    "frame body {} bytes exceeds limit {}",
+                                    body.len(),
+                                    self.max_frame_bytes
+                                ),
+                            )
+                            .into();
+                            tunnel.send_frame(&err_frame).await?;
+                            continue;
+                        }
+                    }
+
+                    // ── Body digest ────────────────────────────
+                    // Checked again here for tunnels that hand over
+                    // frames without decoding bytes.
+                    let digested = match frame.verify_digest() {
+                        Ok(digested) => digested,
+                        Err(e) => {
+                            warn!(peer_id = %peer_id, err = %e, "frame failed digest check");
+                            let err = ErrorFrame::from(&e).in_reply_to(&frame).build();
+                            tunnel.send_frame(&err).await?;
+                            continue;
+                        }
+                    };
+                    if self.require_digest
+                        && !digested
+                        && frame.verb_kind() == VerbKind::Verb(Verb::Event)
+                    {
+                        let err = ErrorFrame::from(&ProtocolError::PreconditionFailed(
+                            "EVENT frames must carry a Digest header".into(),
+                        ))
+                        .in_reply_to(&frame)
+                        .build();
+                        tunnel.send_frame(&err).await?;
+                        continue;
+                    }
+
+                    // ── Rate limiting (H2) ─────────────────────
+                    if self.rate_limiter.is_enabled() {
+                        let is_publish = frame.verb == "PUBLISH";
+                        if let Err(e) = self.rate_limiter.check_frame(&peer_id, is_publish) {
+                            let err = ErrorFrame::from(&e).in_reply_to(&frame).build();
+                            tunnel.send_frame(&err).await?;
+                            continue;
+                        }
+                    }
+
+                    // ── Forwarding ─────────────────────────────
+                    // Frames addressed to other burrows are relayed
+                    // before anything here acts on them.
+                    match self.forward(&frame, &peer_id).await {
+                        Forward::Local => {}
+                        Forward::Reply(err) => {
+                            tunnel.send_frame(&err).await?;
+                            continue;
+                        }
+                        Forward::Sent(_) | Forward::Dropped => continue,
+                    }
+
+                    let lane_id: u16 = frame
+                        .header("Lane")
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(0);
+
+                    // ── ACK/CREDIT/CANCEL/LANE-*/PING/PONG: handle at tunnel level ─
+                    match frame.verb_kind() {
+                        VerbKind::Verb(Verb::Ping) => {
+                            tunnel.send_frame(&keepalive::pong_for(&frame)).await?;
+                            continue;
+                        }
+                        _ if keepalive::is_pong(&frame) => {
+                            let now = now_unix();
+                            if let Some(rtt) = keepalive.on_pong(&frame) {
+                                let srtt = keepalive.smoothed_rtt().unwrap_or(rtt);
+                                counters.record_rtt(rtt, srtt);
+                                debug!(peer_id = %peer_id, rtt_ms = rtt.as_millis() as u64, "pong");
+                                self.peers
+                                    .record_rtt(&peer_id, srtt.as_millis() as u64, now)
+                                    .await;
+                                self.trust
+                                    .lock()
+                                    .unwrap_or_else(|e| e.into_inner())
+                                    .touch(&peer_id);
+                            } else {
+                                // Answers a health check, not our probe.
+                                self.peers.touch(&peer_id, now).await;
+                            }
+                            continue;
+                        }
+                        VerbKind::Verb(Verb::Ack) => {
+                            match SelectiveAck::from_frame(&frame) {
+                                Ok(ack) => {
+                                    self.consumer_offsets.acked(&peer_id, tunnel_id, lane_id, ack.cumulative);
+                                    lanes.sack(lane_id, &ack).await
+                                }
+                                Err(e) => {
+                                    let err = ErrorFrame::from(&e).in_reply_to(&frame).build();
+                                    tunnel.send_frame(&err).await?;
+                                    continue;
+                                }
+                            }
+                            let mut resp = Frame::new("200 OK");
+                            resp.set_header("Lane", lane_id.to_string());
+                            tunnel.send_frame(&resp).await?;
+                            continue;
+                        }
+                        VerbKind::Verb(Verb::Cancel) => {
+                            let Some(txn) = frame.args.first().or(frame.headers.get("Txn")) else {
+                                let err = ErrorFrame::from(&ProtocolError::BadRequest(
+                                    "CANCEL needs a transaction ID".into(),
+                                ))
+                                .in_reply_to(&frame)
+                                .build();
+                                tunnel.send_frame(&err).await?;
+                                continue;
+                            };
+                            let queued = outbox.len();
+                            outbox.retain(|(f, _)| f.header("Txn") != Some(txn.as_str()));
+                            debug!(
+                                peer_id = %peer_id,
+                                txn = %txn,
+                                dropped = queued - outbox.len(),
+                                "transaction canceled"
+                            );
+                            let mut resp = Frame::new("499 CANCELED");
+                            resp.set_header("Lane", lane_id.to_string());
+                            resp.set_header("Txn", txn.as_str());
+                            tunnel.send_frame(&resp).await?;
+                            continue;
+                        }
+                        VerbKind::Verb(Verb::LaneOpen) => {
+                            let opened = async {
+                                let priority = scheduler::priority_header(&frame)?;
+                                lanes.open(lane_id).await?;
+                                if let Some(priority) = priority {
+                                    lanes.set_priority(lane_id, priority).await;
+                                }
+                                Ok::<_, ProtocolError>(())
+                            };
+                            let resp = match opened.await {
+                                Ok(()) => lane_state_frame(lane_id, LaneState::Open),
+                                Err(e) => ErrorFrame::from(&e).in_reply_to(&frame).build(),
+                            };
+                            tunnel.send_frame(&resp).await?;
+                            continue;
+                        }
+                        VerbKind::Verb(Verb::LaneClose) => {
+                            // Close our side too, unless chunks are still
+                            // queued for the lane; the outbox closes it
+                            // once they are sent.
+                            let mut result = lanes.close_remote(lane_id).await;
+                            if matches!(result, Ok(LaneState::HalfClosed))
+                                && outbox.queued(lane_id) == 0
+                            {
+                                result = lanes.close_local(lane_id).await;
+                            }
+                            if matches!(result, Ok(LaneState::Closed)) {
+                                credit.remove(lane_id);
+                            }
+                            let resp = match result {
+                                Ok(state) => lane_state_frame(lane_id, state),
+                                Err(e) => ErrorFrame::from(&e).in_reply_to(&frame).build(),
+                            };
+                            tunnel.send_frame(&resp).await?;
+                            continue;
+                        }
+                        VerbKind::Verb(Verb::LaneReset) => {
+                            lanes.reset(lane_id).await;
+                            credit.remove(lane_id);
+                            outbox.clear_lane(lane_id);
+                            debug!(peer_id = %peer_id, lane = lane_id, "lane reset");
+                            tunnel.send_frame(&lane_state_frame(lane_id, LaneState::Closed)).await?;
+                            continue;
+                        }
+                        VerbKind::Verb(Verb::Rotate) => {
+                            // Only the holder of the new key may hand
+                            // the old key's trust on to it.
+                            let applied = KeyRotation::from_frame(&frame).and_then(|rotation| {
+                                if rotation.new_id != peer_id {
+                                    return Err(ProtocolError::Forbidden(
+                                        "a rotation must be announced by its new key".into(),
+                                    ));
+                                }
+                                self.trust
+                                    .lock()
+                                    .unwrap_or_else(|e| e.into_inner())
+                                    .apply_rotation(&rotation)?;
+                                info!(peer_id = %peer_id, old_id = %rotation.old_id, "peer rotated its key");
+                                Ok(())
+                            });
+                            let resp = match applied {
+                                Ok(()) => {
+                                    let mut ok = Frame::new("200 OK");
+                                    ok.set_header("Lane", lane_id.to_string());
+                                    ok
+                                }
+                                Err(e) => ErrorFrame::from(&e).in_reply_to(&frame).build(),
+                            };
+                            tunnel.send_frame(&resp).await?;
+                            continue;
+                        }
+                        VerbKind::Verb(Verb::Manifest) => {
+                            let result = self.answer_manifest(&frame, &peer_id).await;
+                            tunnel.send_frame(&result.response).await?;
+                            for extra in &result.extras {
+                                tunnel.send_frame(extra).await?;
+                            }
                             continue;
                         }
-                    }
-
-                    // ── Rate limiting (H2) ─────────────────────
-                    if self.rate_limiter.is_enabled() {
-                        let is_publish = frame.verb == "PUBLISH";
-                        if !self.rate_limiter.check(&peer_id, is_publish) {
-                            let mut err = Frame::new("429 FLOW-LIMIT");
-                            err.set_body("rate limit exceeded");
-                            if let Some(lane) = frame.header("Lane") {
-                                err.set_header("Lane", lane);
+                        VerbKind::Verb(Verb::FedAuth) => {
+                            // The peer dialed us over a federation
+                            // link and is proving its secret.
+                            let answer = self
+                                .federation
+                                .answer_link_auth(&frame, &self.burrow_id(), &peer_id);
+                            if frame.args.first().map(String::as_str) == Some("proof") {
+                                let outcome = answer.as_ref().map(|_| ()).map_err(Clone::clone);
+                                self.link_authenticated(&peer_id, &outcome);
                             }
-                            tunnel.send_frame(&err).await?;
+                            let resp = match answer {
+                                Ok(reply) => reply,
+                                Err(e) => ErrorFrame::from(&e).in_reply_to(&frame).build(),
+                            };
+                            tunnel.send_frame(&resp).await?;
                             continue;
                         }
-                    }
-
-                    let lane_id: u16 = frame
-                        .header("Lane")
-                        .and_then(|s| s.parse().ok())
-                        .unwrap_or(0);
-
-                    // ── ACK/CREDIT/PONG: handle at tunnel level ─
-                    match frame.verb.as_str() {
-                        "PONG" => {
-                            awaiting_pong = false;
-                            missed_pongs = 0;
+                        VerbKind::Verb(
+                            Verb::FedLinkOpen | Verb::FedLinkAccept | Verb::FedLinkClose,
+                        ) => {
+                            if let Some(resp) = self.answer_link(&frame, &peer_id) {
+                                tunnel.send_frame(&resp).await?;
+                            }
                             continue;
                         }
-                        "ACK" => {
-                            let ack_seq: u64 = frame
-                                .header("ACK")
-                                .and_then(|s| s.parse().ok())
-                                .unwrap_or(0);
-                            lanes.ack(lane_id, ack_seq).await;
-                            let mut resp = Frame::new("200 OK");
-                            resp.set_header("Lane", lane_id.to_string());
+                        VerbKind::Verb(Verb::RelayRegister) => {
+                            // The peer cannot be dialed and asks us to
+                            // carry its frames for as long as this
+                            // tunnel lasts.
+                            let registered = if !self
+                                .capabilities
+                                .lock()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .check(&peer_id, Capability::Relay)
+                            {
+                                Err(RabbitError::Capability {
+                                    peer_id: peer_id.clone(),
+                                    capability: Capability::Relay,
+                                }
+                                .into())
+                            } else {
+                                self.relay.register(&peer_id)
+                            };
+                            let resp = match registered {
+                                Ok(()) => {
+                                    info!(peer_id = %peer_id, "relaying for peer");
+                                    let mut ok = Frame::new("200 OK");
+                                    ok.set_header("Lane", lane_id.to_string());
+                                    if let Some(txn) = frame.header("Txn") {
+                                        ok.set_header("Txn", txn);
+                                    }
+                                    let bandwidth = self.relay.bytes_per_sec().to_string();
+                                    ok.set_header("Relay-Bandwidth", bandwidth);
+                                    ok
+                                }
+                                Err(e) => ErrorFrame::from(&e).in_reply_to(&frame).build(),
+                            };
+                            tunnel.send_frame(&resp).await?;
+                            continue;
+                        }
+                        VerbKind::Verb(Verb::Revoke) => {
+                            // REVOKE <peer_id>, or a Session-Token
+                            // header for a single session.
+                            let revoked = if !self
+                                .capabilities
+                                .lock()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .check(&peer_id, Capability::ManageBurrows)
+                            {
+                                Err(RabbitError::Capability {
+                                    peer_id: peer_id.clone(),
+                                    capability: Capability::ManageBurrows,
+                                }
+                                .into())
+                            } else if let Some(token) = frame.header("Session-Token") {
+                                let revoked = self.revoke_session(token);
+                                if let Some(session) = &revoked {
+                                    let detail = format!("session, by {}", peer_id);
+                                    self.audit.record(AuditKind::Revoke, &session.peer_id, &detail);
+                                }
+                                Ok(revoked.into_iter().count())
+                            } else if let Some(target) = frame.args.first() {
+                                let count = self.revoke_peer(target).len();
+                                let detail = format!("{} sessions, by {}", count, peer_id);
+                                self.audit.record(AuditKind::Revoke, target, &detail);
+                                Ok(count)
+                            } else {
+                                Err(ProtocolError::BadRequest(
+                                    "REVOKE needs a peer or a Session-Token".into(),
+                                ))
+                            };
+                            let resp = match revoked {
+                                Ok(count) => {
+                                    let mut ok = Frame::new("200 OK");
+                                    ok.set_header("Lane", lane_id.to_string());
+                                    ok.set_header("Revoked", count.to_string());
+                                    ok
+                                }
+                                Err(e) => ErrorFrame::from(&e).in_reply_to(&frame).build(),
+                            };
+                            tunnel.send_frame(&resp).await?;
+                            continue;
+                        }
+                        VerbKind::Verb(Verb::Refresh) => {
+                            // REFRESH with a Refresh-Token header trades
+                            // it for a new pair; the tunnel carries on
+                            // under the new session token.
+                            let refreshed = match frame.header("Refresh-Token") {
+                                Some(refresh) => self.refresh_session(&peer_id, refresh),
+                                None => Err(ProtocolError::BadRequest(
+                                    "REFRESH needs a Refresh-Token".into(),
+                                )),
+                            };
+                            let resp = match refreshed {
+                                Ok((token, refresh)) => {
+                                    let old = std::mem::replace(&mut session_token, token);
+                                    self.session_store.revoke_session(&old);
+                                    let mut ok = Frame::new("200 OK");
+                                    ok.set_header("Lane", lane_id.to_string());
+                                    ok.set_header("Session-Token", &session_token);
+                                    ok.set_header("Refresh-Token", refresh);
+                                    ok
+                                }
+                                Err(e) => ErrorFrame::from(&e).in_reply_to(&frame).build(),
+                            };
                             tunnel.send_frame(&resp).await?;
                             continue;
                         }
-                        "CREDIT" => {
+                        VerbKind::Verb(Verb::GoAway) => {
+                            // The peer closes once it has settled; what
+                            // it leaves unacknowledged is parked below.
+                            info!(peer_id = %peer_id, "peer is going away");
+                            peer_going_away = true;
+                            continue;
+                        }
+                        VerbKind::Verb(Verb::Credit) => {
                             let n: u32 = frame
                                 .header("Credit")
                                 .and_then(|s| s.trim_start_matches('+').parse().ok())
@@ -426,37 +2371,42 @@ impl Burrow {
                         _ => {}
                     }
 
-                    // ── Hop-Count enforcement for forwarded frames ──
-                    if let Some(target) = frame.header("Target") {
-                        if target != self.identity.burrow_id() {
-                            let hop_count: u32 = frame
-                                .header("Hop-Count")
-                                .and_then(|s| s.parse().ok())
-                                .unwrap_or(8);
-                            if hop_count == 0 {
-                                let mut err = Frame::new("400 HOP LIMIT");
-                                err.set_body("hop count exceeded");
-                                if let Some(lane) = frame.header("Lane") {
-                                    err.set_header("Lane", lane);
-                                }
-                                tunnel.send_frame(&err).await?;
-                                continue;
-                            }
-                            // Forward to next hop via session manager.
-                            if let Some(next_hop) = self.routing.next_hop(target).await {
-                                let mut fwd = frame.clone();
-                                fwd.set_header("Hop-Count", (hop_count - 1).to_string());
-                                self.sessions.broadcast(vec![(next_hop, fwd)]).await;
-                                continue;
-                            } else {
-                                let mut err = Frame::new("404 NO ROUTE");
-                                err.set_body(format!("no route to {}", target));
-                                if let Some(lane) = frame.header("Lane") {
-                                    err.set_header("Lane", lane);
-                                }
-                                tunnel.send_frame(&err).await?;
-                                continue;
-                            }
+                    // ── Responses ──────────────────────────────
+                    // Status frames answer our own CREDIT, ACK and
+                    // fan-out frames.  Nothing waits on them here, and
+                    // answering one would start an endless exchange of
+                    // errors with the peer.
+                    if matches!(frame.verb_kind(), VerbKind::Status(_)) {
+                        debug!(peer_id = %peer_id, status = %frame.verb, "response ignored");
+                        continue;
+                    }
+
+                    // ── Going away ─────────────────────────────
+                    if drain_deadline.is_some() {
+                        let err = ErrorFrame::from(&ProtocolError::Busy(
+                            "burrow is shutting down".into(),
+                        ))
+                        .in_reply_to(&frame)
+                        .build();
+                        tunnel.send_frame(&err).await?;
+                        continue;
+                    }
+
+                    // ── Lane admission ─────────────────────────
+                    // Requests may open lanes implicitly, up to the
+                    // lane limit, but not on a lane the peer closed.
+                    if let Err(e) = lanes.open(lane_id).await {
+                        let err = ErrorFrame::from(&e).in_reply_to(&frame).build();
+                        tunnel.send_frame(&err).await?;
+                        continue;
+                    }
+
+                    // ── Adaptive credit ────────────────────────
+                    // Sequenced frames spend the peer's credit.  Chunks
+                    // still queued for sending stand in for our backlog.
+                    if lane_id != 0 && frame.header("Seq").is_some() {
+                        if let Some(grant) = credit.on_receive(lane_id, outbox.len()) {
+                            tunnel.send_frame(&grant).await?;
                         }
                     }
 
@@ -472,11 +2422,12 @@ impl Burrow {
                     let timeout_secs: Option<u64> = frame
                         .header("Timeout")
                         .and_then(|s| s.parse().ok());
+                    let frame_span = debug_span!("frame", lane = lane_id, verb = %frame.verb);
 
-                    let result: DispatchResult = if let Some(t) = timeout_secs {
+                    let mut result: DispatchResult = if let Some(t) = timeout_secs {
                         match tokio::time::timeout(
                             Duration::from_secs(t),
-                            dispatcher.dispatch(&frame, &peer_id),
+                            dispatcher.dispatch(&frame, &peer_id).instrument(frame_span),
                         ).await {
                             Ok(r) => r,
                             Err(_) => {
@@ -490,9 +2441,16 @@ impl Burrow {
                             }
                         }
                     } else {
-                        dispatcher.dispatch(&frame, &peer_id).await
+                        dispatcher.dispatch(&frame, &peer_id).instrument(frame_span).await
                     };
 
+                    address_reply(&frame, &mut result.response);
+                    self.stamp_digest(&mut result.response, Some(&frame));
+                    for extra in &mut result.extras {
+                        address_reply(&frame, extra);
+                        self.stamp_digest(extra, Some(&frame));
+                    }
+
                     // Cache response if Idem token is present.
                     if let Some(idem_token) = frame.header("Idem") {
                         self.idem_cache.insert(idem_token.to_string(), result.response.clone());
@@ -500,9 +2458,34 @@ impl Burrow {
 
                     tunnel.send_frame(&result.response).await?;
 
-                    // Same-tunnel extras (e.g. SUBSCRIBE replay).
-                    for extra in &result.extras {
-                        tunnel.send_frame(extra).await?;
+                    // Same-tunnel extras (e.g. SUBSCRIBE replay); chunks
+                    // are queued behind any still being sent.
+                    for mut extra in result.extras {
+                        match extra.verb_kind() {
+                            VerbKind::Verb(Verb::Chunk) => {
+                                let lane_id = frame_lane(&extra);
+                                outbox.push(lane_id, lanes.priority(lane_id).await, (extra, None));
+                            }
+                            VerbKind::Verb(Verb::Event) => {
+                                let lane_id = frame_lane(&extra);
+                                if replaying.contains_key(&lane_id) {
+                                    held.entry(lane_id).or_default().push(extra);
+                                    continue;
+                                }
+                                let (lane_id, seq) = self.sequence(&peer_id, tunnel_id, &lanes, &mut extra).await;
+                                outbox.push(lane_id, lanes.priority(lane_id).await, (extra, Some(seq)));
+                            }
+                            _ => tunnel.send_frame(&extra).await?,
+                        }
+                    }
+                    if let Some(replay) = result.replay {
+                        let (request, lane_id) = (frame.clone(), replay.lane);
+                        *replaying.entry(lane_id).or_default() += 1;
+                        let frames = replay.frames.map(move |mut event| {
+                            address_reply(&request, &mut event);
+                            (lane_id, Some(event))
+                        });
+                        replays.push(frames.chain(stream::iter([(lane_id, None)])));
                     }
 
                     // Cross-tunnel broadcast via session manager.
@@ -511,22 +2494,68 @@ impl Burrow {
                     }
                 }
 
+                // ── Outbound: replayed events ──────────────────
+                replayed = replays.next(),
+                    if !replays.is_empty() && outbox.len() < REPLAY_WINDOW =>
+                {
+                    match replayed {
+                        Some((_, Some(mut event))) => {
+                            let (lane_id, seq) = self.sequence(&peer_id, tunnel_id, &lanes, &mut event).await;
+                            self.stamp_digest(&mut event, None);
+                            outbox.push(lane_id, lanes.priority(lane_id).await, (event, Some(seq)));
+                        }
+                        // The replay is over; what was held back for
+                        // its lane follows it.
+                        Some((lane_id, None)) => {
+                            let active = replaying.entry(lane_id).or_default();
+                            *active = active.saturating_sub(1);
+                            if *active == 0 {
+                                replaying.remove(&lane_id);
+                                for mut frame in held.remove(&lane_id).unwrap_or_default() {
+                                    let (lane_id, seq) = self.sequence(&peer_id, tunnel_id, &lanes, &mut frame).await;
+                                    self.stamp_digest(&mut frame, None);
+                                    outbox.push(lane_id, lanes.priority(lane_id).await, (frame, Some(seq)));
+                                }
+                            }
+                        }
+                        None => {}
+                    }
+                }
+
+                // ── Outbound: queued chunks and events ─────────
+                _ = std::future::ready(()), if !outbox.is_empty() => {
+                    if let Some((lane_id, (frame, seq))) = outbox.pop() {
+                        if let (Some(seq), true) = (seq, retransmit_enabled) {
+                            lanes.record_sent(lane_id, seq, frame.serialize()).await;
+                        }
+                        tunnel.send_frame(&frame).await?;
+                        if lanes.state(lane_id).await == Some(LaneState::HalfClosed)
+                            && outbox.queued(lane_id) == 0
+                        {
+                            let mut close = Frame::new("LANE-CLOSE");
+                            close.set_header("Lane", lane_id.to_string());
+                            let _ = lanes.close_local(lane_id).await;
+                            tunnel.send_frame(&close).await?;
+                        }
+                    }
+                }
+
                 // ── Outbound: fan-out frames from other tunnels ──
                 fanout = fanout_rx.recv() => {
                     match fanout {
                         Some(mut frame) => {
-                            // Assign sequence number for retransmission tracking.
-                            let lane_id: u16 = frame
-                                .header("Lane")
-                                .and_then(|s| s.parse().ok())
-                                .unwrap_or(0);
-                            let seq = lanes.next_seq(lane_id).await;
-                            frame.set_header("Seq", seq.to_string());
-                            if retransmit_enabled {
-                                let data = frame.serialize();
-                                lanes.record_sent(lane_id, seq, data).await;
+                            // Held back behind a replay on the same
+                            // lane, or else numbered for retransmission
+                            // tracking and queued behind what the lane
+                            // already has queued.
+                            let lane_id = frame_lane(&frame);
+                            if replaying.contains_key(&lane_id) {
+                                held.entry(lane_id).or_default().push(frame);
+                                continue;
                             }
-                            tunnel.send_frame(&frame).await?;
+                            let (lane_id, seq) = self.sequence(&peer_id, tunnel_id, &lanes, &mut frame).await;
+                            self.stamp_digest(&mut frame, None);
+                            outbox.push(lane_id, lanes.priority(lane_id).await, (frame, Some(seq)));
                         }
                         None => {
                             // Session manager dropped our channel —
@@ -537,18 +2566,39 @@ impl Burrow {
                     }
                 }
 
+                // ── Session revoked ────────────────────────────
+                _ = session_removals.changed() => {
+                    if !self.session_store.is_active(&session_token) {
+                        info!(peer_id = %peer_id, "session revoked, closing tunnel");
+                        let mut err: Frame =
+                            ProtocolError::Forbidden("session revoked".into()).into();
+                        err.set_header("Lane", "0");
+                        let _ = tunnel.send_frame(&err).await;
+                        let _ = tunnel.close().await;
+                        break;
+                    }
+                }
+
+                // ── Shutdown ───────────────────────────────────
+                _ = going_away.changed(), if drain_deadline.is_none() => {}
+                _ = tokio::time::sleep_until(
+                    drain_deadline.unwrap_or_else(tokio::time::Instant::now),
+                ), if drain_deadline.is_some() => {}
+
                 // ── Keepalive timer ────────────────────────────
                 _ = keepalive_ticker.tick(), if keepalive_enabled => {
-                    if awaiting_pong {
-                        missed_pongs += 1;
-                        if missed_pongs >= 3 {
-                            warn!(peer_id = %peer_id, "3 missed pongs — closing tunnel");
+                    match keepalive.on_tick() {
+                        Some(ping) => tunnel.send_frame(&ping).await?,
+                        None => {
+                            warn!(
+                                peer_id = %peer_id,
+                                missed = keepalive.missed(),
+                                "keepalive probes unanswered — closing tunnel"
+                            );
+                            peer_dead = true;
                             break;
                         }
                     }
-                    let ping = Frame::new("PING");
-                    tunnel.send_frame(&ping).await?;
-                    awaiting_pong = true;
                 }
 
                 // ── Retransmission check ───────────────────────
@@ -564,6 +2614,7 @@ impl Burrow {
                         }
                         Err(seq) => {
                             warn!(peer_id = %peer_id, seq = seq, "frame exceeded max retries — closing tunnel");
+                            self.park_in_flight(&peer_id, &lanes).await;
                             break;
                         }
                     }
@@ -571,20 +2622,8 @@ impl Burrow {
 
                 // ── Periodic OFFER — advertise peer table ──────
                 _ = offer_ticker.tick(), if offer_enabled => {
-                    let peers_list = self.peers.list().await;
-                    if !peers_list.is_empty() {
-                        let mut body = String::new();
-                        for p in &peers_list {
-                            body.push_str(&p.id);
-                            body.push('\t');
-                            body.push_str(&p.address);
-                            body.push('\t');
-                            body.push_str(&p.name);
-                            body.push('\n');
-                        }
-                        let mut offer = Frame::with_args("OFFER", vec!["/warren".into()]);
-                        offer.set_body(body);
-                        debug!(peer_id = %peer_id, count = peers_list.len(), "sending periodic OFFER");
+                    if let Some(offer) = self.offer_frame().await {
+                        debug!(peer_id = %peer_id, "sending periodic OFFER");
                         tunnel.send_frame(&offer).await?;
                     }
                 }
@@ -592,9 +2631,29 @@ impl Burrow {
         }
 
         // ── Cleanup ────────────────────────────────────────────
-        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+        if peer_dead {
+            // Stop advertising and routing through a peer that no
+            // longer answers.
+            self.peers.mark_disconnected(&peer_id).await;
+            self.routing.remove_via(&peer_id).await;
+        }
+        if drain_deadline.is_some() || peer_going_away {
+            // Keep whatever the peer never acknowledged for a retry
+            // once it is back.
+            self.park_in_flight(&peer_id, &lanes).await;
+        }
+        if drain_deadline.is_some() {
+            let _ = tunnel.close().await;
+        }
+        if self.relay.unregister(&peer_id) {
+            info!(peer_id = %peer_id, "stopped relaying for peer");
+        }
         self.rate_limiter.remove_peer(&peer_id);
-        self.sessions.unregister(&peer_id);
+        self.tunnel_stats.unregister(tunnel_id);
+        self.consumer_offsets.disconnected(&peer_id, tunnel_id);
+        if let Err(e) = self.consumer_offsets.save() {
+            warn!(error = %e, "failed to save consumer offsets on tunnel close");
+        }
 
         if let Err(e) = self.save_trust() {
             warn!(error = %e, "failed to save trust cache on tunnel close");
@@ -603,19 +2662,65 @@ impl Burrow {
         Ok(peer_id)
     }
 
+    /// Note an EVENT sent to `peer_id` on tunnel `tunnel_id`, numbered
+    /// on its lane by [`sequence`](Self::sequence), so that its ACK on
+    /// that tunnel moves the peer's offset in the topic; see
+    /// [`crate::events::offsets`].  Anonymous peers keep no offsets.
+    fn note_delivery(&self, peer_id: &str, tunnel_id: u64, frame: &Frame) {
+        if peer_id.starts_with("anonymous") || frame.verb_kind() != VerbKind::Verb(Verb::Event) {
+            return;
+        }
+        let seq = |name| frame.header(name).and_then(|s| s.parse::<u64>().ok());
+        let (Some(topic), Some(lane_seq), Some(event_seq)) =
+            (frame.args.first(), seq("Seq"), seq("Event-Seq"))
+        else {
+            return;
+        };
+        self.consumer_offsets.sent(
+            peer_id,
+            tunnel_id,
+            frame_lane(frame),
+            lane_seq,
+            topic,
+            event_seq,
+        );
+    }
+
     /// Perform the server-side handshake (HELLO / CHALLENGE / AUTH),
-    /// TOFU verification, and capability grants.  Returns the peer ID.
-    async fn run_handshake<T: Tunnel>(&self, tunnel: &mut T) -> Result<String, ProtocolError> {
+    /// TOFU verification, and capability grants.  Returns the peer ID
+    /// and the session token, which is recorded in the session store.
+    async fn run_handshake<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+    ) -> Result<(String, String), ProtocolError> {
         let mut auth = Authenticator::new(
             Identity::from_bytes(self.identity.public_key_bytes(), self.identity.seed_bytes())?,
             self.require_auth,
-        );
+        )
+        .with_token_lifetimes(self.session_ttl_secs, self.refresh_ttl_secs)
+        .with_capabilities(AUTHENTICATED_CAPS, ANONYMOUS_CAPS);
+        if let Some(cert) = tunnel.peer_certificate() {
+            if let Err(e) = auth.set_peer_certificate(cert) {
+                return Err(reject_handshake(tunnel, e).await);
+            }
+        }
 
         let hello = tunnel
             .recv_frame()
             .await?
             .ok_or_else(|| ProtocolError::BadHello("tunnel closed before HELLO".into()))?;
-        let response = auth.handle_hello(&hello)?;
+        let mut response = match auth.handle_hello(&hello) {
+            Ok(response) => response,
+            Err(e) => return Err(reject_handshake(tunnel, e).await),
+        };
+        // The final 200 accepts the framing the client offered; both
+        // ends switch once it has been sent.
+        let length_prefixed = self.length_prefixed
+            && tunnel.supports_length_prefixed()
+            && hello.header("Wire") == Some(WIRE_LENGTH_PREFIXED);
+        if length_prefixed && auth.is_authenticated() {
+            response.set_header("Wire", WIRE_LENGTH_PREFIXED);
+        }
         tunnel.send_frame(&response).await?;
 
         if !auth.is_authenticated() {
@@ -623,9 +2728,18 @@ impl Burrow {
                 .recv_frame()
                 .await?
                 .ok_or_else(|| ProtocolError::BadHello("tunnel closed before AUTH".into()))?;
-            let ok = auth.handle_auth(&auth_frame)?;
+            let mut ok = match auth.handle_auth(&auth_frame) {
+                Ok(ok) => ok,
+                Err(e) => return Err(reject_handshake(tunnel, e).await),
+            };
+            if length_prefixed {
+                ok.set_header("Wire", WIRE_LENGTH_PREFIXED);
+            }
             tunnel.send_frame(&ok).await?;
         }
+        if length_prefixed {
+            tunnel.set_wire_format(WireFormat::LengthPrefixed);
+        }
 
         let base_id = auth.peer_id().unwrap_or("anonymous").to_string();
         let peer_id = if base_id == "anonymous" {
@@ -644,6 +2758,10 @@ impl Burrow {
             saved
                 .iter()
                 .any(|s| s.session_token == resume_token && s.peer_id == peer_id)
+                || self
+                    .session_store
+                    .get(resume_token)
+                    .is_some_and(|s| s.peer_id == peer_id)
         } else {
             false
         };
@@ -654,40 +2772,151 @@ impl Burrow {
         }
 
         // ── TOFU trust verification ────────────────────────────
+        let mut quarantined = false;
         if let Some(peer_pubkey) = auth.peer_pubkey() {
-            self.trust
-                .lock()
-                .unwrap()
-                .verify_or_remember(&peer_id, &peer_pubkey)?;
+            let mut trust = self.trust.lock().unwrap();
+            let presented = fingerprint(&peer_pubkey);
+            if let Some(pinned) = trust
+                .get(&peer_id)
+                .map(|p| p.fingerprint.as_str())
+                .filter(|&fp| fp != "-" && fp != presented)
+            {
+                let detail = format!("pinned {}, presented {}", pinned, presented);
+                self.audit.record(AuditKind::Mismatch, &peer_id, &detail);
+            }
+            if let PeerStatus::Quarantined { reason } =
+                trust.verify_or_remember(&peer_id, &peer_pubkey)?
+            {
+                if self.refuse_quarantined {
+                    return Err(ProtocolError::Forbidden(format!(
+                        "{} is quarantined: {}",
+                        peer_id, reason
+                    )));
+                }
+                warn!(peer_id = %peer_id, %reason, "quarantined peer limited to anonymous grants");
+                quarantined = true;
+            }
             debug!(peer_id = %peer_id, "TOFU verified");
+            if !quarantined {
+                self.hooks.peer_trusted(&peer_id);
+            }
+            let bound = auth.peer_certificate_id().is_some();
+            if let Some(cert) = tunnel.peer_certificate().filter(|_| bound) {
+                trust.bind_certificate(&peer_id, cert)?;
+                debug!(peer_id = %peer_id, "TLS certificate bound");
+            }
         }
 
         // ── Default capability grants ──────────────────────────
         {
             let mut caps = self.capabilities.lock().unwrap_or_else(|e| e.into_inner());
-            if peer_id.starts_with("anonymous") {
-                caps.grant(&peer_id, Capability::Fetch, 86400);
-                caps.grant(&peer_id, Capability::List, 86400);
+            if quarantined {
+                caps.revoke_all(&peer_id);
+                let detail = "all capabilities, quarantined";
+                self.audit.record(AuditKind::Revoke, &peer_id, detail);
+            }
+            let defaults = if quarantined || peer_id.starts_with("anonymous") {
+                ANONYMOUS_CAPS
             } else {
-                caps.grant(&peer_id, Capability::Fetch, 86400);
-                caps.grant(&peer_id, Capability::List, 86400);
-                caps.grant(&peer_id, Capability::Subscribe, 86400);
-                caps.grant(&peer_id, Capability::Publish, 86400);
+                AUTHENTICATED_CAPS
+            };
+            for &capability in defaults {
+                caps.grant(&peer_id, capability, 86400);
             }
+            let labels: Vec<&str> = defaults.iter().map(|c| c.label()).collect();
+            let detail = format!("{} for 86400s at handshake", labels.join(","));
+            self.audit.record(AuditKind::Grant, &peer_id, &detail);
         }
 
-        Ok(peer_id)
+        // ── Session token ──────────────────────────────────────
+        let token = auth.session_token().ok_or_else(|| {
+            ProtocolError::InternalError("handshake finished without a session token".into())
+        })?;
+        self.session_store
+            .issue(token, &peer_id, self.session_ttl_secs);
+        let detail = match (auth.peer_pubkey().is_some(), resumed) {
+            (true, true) => "authenticated, resumed",
+            (true, false) => "authenticated",
+            (false, _) => "anonymous",
+        };
+        self.audit.record(AuditKind::Handshake, &peer_id, detail);
+
+        Ok((peer_id, token.to_string()))
     }
 
-    /// Run the client-side handshake on an outgoing tunnel.
+    /// Run the client-side handshake on an outgoing tunnel, then
+    /// announce our key rotation, if any, with a `ROTATE` frame.
     ///
-    /// Returns the server's burrow ID on success.
+    /// A server whose TLS certificate is bound to a burrow ID must
+    /// answer with that `Burrow-ID`.  Unless [`Burrow::tls_verify`] is
+    /// `Any`, the certificate must also be the one pinned for that ID,
+    /// and is pinned if the server is new.  Returns the server's burrow
+    /// ID on success.
     #[instrument(skip(self, tunnel), fields(burrow = %self.name))]
     pub async fn client_handshake<T: Tunnel>(
         &self,
         tunnel: &mut T,
     ) -> Result<String, ProtocolError> {
-        let hello = build_hello(&self.identity);
+        let server_id = self.run_client_handshake(tunnel).await?;
+        if let Some(cert) = tunnel.peer_certificate() {
+            match extract_rabbit_id_from_cert(cert)? {
+                Some(cert_id) if server_id != "anonymous" && cert_id != server_id => {
+                    return Err(ProtocolError::Forbidden(format!(
+                        "Burrow-ID {} does not match TLS certificate ({})",
+                        server_id, cert_id
+                    )));
+                }
+                _ => {}
+            }
+        }
+        if self.tls_verify != TlsVerify::Any {
+            self.pin_server_certificate(&server_id, tunnel.peer_certificate())?;
+        }
+        if let Some(rotation) = &self.rotation {
+            tunnel.send_frame(&rotation.to_frame()).await?;
+        }
+        Ok(server_id)
+    }
+
+    /// Check the certificate `server_id` presented against the one
+    /// pinned for it, and pin it if `server_id` is new.
+    fn pin_server_certificate(
+        &self,
+        server_id: &str,
+        cert: Option<&[u8]>,
+    ) -> Result<(), ProtocolError> {
+        let Some(cert) = cert else {
+            return Ok(());
+        };
+        if !server_id.starts_with("ed25519:") {
+            return Err(ProtocolError::Forbidden(format!(
+                "cannot pin a TLS certificate for {}",
+                server_id
+            )));
+        }
+        let mut trust = self.trust.lock().unwrap_or_else(|e| e.into_inner());
+        trust
+            .pinned_certs()
+            .expecting(server_id)
+            .allow_unknown(self.tls_verify == TlsVerify::Tofu)
+            .check(cert)?;
+        if trust.get(server_id).is_none() {
+            trust.verify_or_remember(server_id, &parse_burrow_id(server_id)?)?;
+        }
+        trust.bind_certificate(server_id, cert)?;
+        debug!(peer_id = %server_id, "server TLS certificate pinned");
+        Ok(())
+    }
+
+    async fn run_client_handshake<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+    ) -> Result<String, ProtocolError> {
+        let mut hello = build_hello(&self.identity);
+        let offered = self.length_prefixed && tunnel.supports_length_prefixed();
+        if offered {
+            hello.set_header("Wire", WIRE_LENGTH_PREFIXED);
+        }
         tunnel.send_frame(&hello).await?;
 
         let response = tunnel
@@ -712,6 +2941,7 @@ impl Burrow {
                 )));
             }
             let server_id = ok.header("Burrow-ID").unwrap_or("unknown").to_string();
+            accept_wire_format(tunnel, offered, &ok);
             Ok(server_id)
         } else if response.verb.starts_with("200") {
             // Anonymous or no-auth — already authenticated.
@@ -719,6 +2949,7 @@ impl Burrow {
                 .header("Burrow-ID")
                 .unwrap_or("unknown")
                 .to_string();
+            accept_wire_format(tunnel, offered, &response);
             Ok(server_id)
         } else {
             Err(ProtocolError::Forbidden(format!(
@@ -729,11 +2960,324 @@ impl Burrow {
     }
 }
 
+/// Assembles a [`Burrow`], with subsystems supplied by the caller in
+/// place of those the config describes.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use rabbit_engine::burrow::BurrowBuilder;
+/// # use rabbit_engine::config::Config;
+/// # use rabbit_engine::security::trust_policy::TrustPolicy;
+/// # async fn run(config: Config, policy: Arc<dyn TrustPolicy>) -> Result<(), Box<dyn std::error::Error>> {
+/// let listening = BurrowBuilder::new(config, "/srv/oak")
+///     .trust_policy(policy)
+///     .start()
+///     .await?;
+/// println!("listening on {:?}", listening.local_addrs());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`build`](Self::build) assembles the burrow without touching the
+/// network; [`start`](Self::start) also binds the listeners of
+/// `network.bind` and `network.listeners`.
+pub struct BurrowBuilder {
+    config: Config,
+    /// Where the storage directory is; `None` keeps nothing on disk.
+    base_dir: Option<PathBuf>,
+    identity: Option<Identity>,
+    trust_policy: Option<Arc<dyn TrustPolicy>>,
+    event_store: Option<Box<dyn EventStore>>,
+    providers: Vec<(String, Arc<dyn ContentProvider>)>,
+    handlers: Vec<(String, Arc<dyn FrameHandler>)>,
+    frame_tap: Option<Arc<dyn FrameTap>>,
+}
+
+impl BurrowBuilder {
+    /// A burrow built from `config`, with its storage directory, TLS
+    /// certificate and content resolved relative to `base_dir`; see
+    /// [`Burrow::from_config`].
+    pub fn new(config: Config, base_dir: impl AsRef<Path>) -> Self {
+        Self {
+            config,
+            base_dir: Some(base_dir.as_ref().to_path_buf()),
+            identity: None,
+            trust_policy: None,
+            event_store: None,
+            providers: Vec::new(),
+            handlers: Vec::new(),
+            frame_tap: None,
+        }
+    }
+
+    /// A burrow that keeps nothing on disk, as [`Burrow::in_memory`],
+    /// unless given an event store.
+    pub fn in_memory(name: impl Into<String>) -> Self {
+        let mut config = Config::default();
+        config.identity.name = name.into();
+        Self {
+            base_dir: None,
+            ..Self::new(config, ".")
+        }
+    }
+
+    /// Use `identity` instead of the one in `<storage>/identity.key`.
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Decide unknown and mismatched peers with `policy` instead of
+    /// the `[trust]` policy.
+    pub fn trust_policy(mut self, policy: Arc<dyn TrustPolicy>) -> Self {
+        self.trust_policy = Some(policy);
+        self
+    }
+
+    /// Persist events in `store` instead of the one `event_store`
+    /// names.  Events already in it are restored at build.
+    pub fn event_store(mut self, store: Box<dyn EventStore>) -> Self {
+        self.event_store = Some(store);
+        self
+    }
+
+    /// Serve the selectors under `prefix` from `provider`; see
+    /// [`Burrow::register_provider`].
+    pub fn provider(
+        mut self,
+        prefix: impl Into<String>,
+        provider: Arc<dyn ContentProvider>,
+    ) -> Self {
+        self.providers.push((prefix.into(), provider));
+        self
+    }
+
+    /// Handle `verb` with `handler`; see [`Burrow::register_handler`].
+    pub fn handler(mut self, verb: impl Into<String>, handler: Arc<dyn FrameHandler>) -> Self {
+        self.handlers.push((verb.into(), handler));
+        self
+    }
+
+    /// Report the frames of every tunnel to `tap`; see
+    /// [`Burrow::set_frame_tap`].
+    pub fn frame_tap(mut self, tap: Arc<dyn FrameTap>) -> Self {
+        self.frame_tap = Some(tap);
+        self
+    }
+
+    /// Assemble the burrow.  Nothing is bound; fails if any subsystem
+    /// cannot be loaded or a provider or handler is refused.
+    pub fn build(self) -> Result<Burrow, ProtocolError> {
+        let burrow = match self.base_dir {
+            Some(base_dir) => {
+                Burrow::assemble(&self.config, base_dir, self.identity, self.event_store)?
+            }
+            None => {
+                let mut burrow = Burrow::in_memory(self.config.identity.name.clone());
+                if let Some(identity) = self.identity {
+                    burrow.identity = identity;
+                }
+                if let Some(store) = self.event_store {
+                    burrow.events = Arc::new(
+                        EventEngine::new().with_memory_limit(self.config.continuity.memory_events),
+                    );
+                    restore_events(store.as_ref(), &burrow.events, &burrow.quotas);
+                    burrow.continuity = Some(store);
+                }
+                burrow
+            }
+        };
+        if let Some(policy) = self.trust_policy {
+            burrow.set_trust_policy(policy);
+        }
+        for (prefix, provider) in self.providers {
+            burrow.register_provider(&prefix, provider)?;
+        }
+        for (verb, handler) in self.handlers {
+            burrow.register_handler(&verb, handler)?;
+        }
+        if let Some(tap) = self.frame_tap {
+            burrow.set_frame_tap(Some(tap));
+        }
+        Ok(burrow)
+    }
+
+    /// Assemble the burrow and serve it over TLS on each address of
+    /// `network.bind`, within `network.limits`, and on each of
+    /// `network.listeners` with its own TLS settings and limits, as
+    /// `burrow serve` does, with its [janitor](Burrow::spawn_janitor)
+    /// running.  A burrow without a certificate, as one in memory, is
+    /// given one bound to its identity.
+    pub async fn start(self) -> Result<Listening, ProtocolError> {
+        let network = self.config.network.clone();
+        let base_dir = self.base_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+        let mut burrow = self.build()?;
+        burrow.advance(BurrowState::Starting);
+        let certificate = match burrow.certificate.take() {
+            Some(certificate) => certificate,
+            None => generate_identity_cert(&burrow.identity, &burrow.name)?,
+        };
+        let server_config = make_server_config(&certificate)?;
+        burrow.certificate = Some(certificate);
+
+        let burrow = Arc::new(burrow);
+        let (current, _) = watch::channel(Arc::clone(&burrow));
+        let mut listeners = Vec::new();
+        for addr in network.bind_addrs()? {
+            let tcp = bind_tcp(&addr.to_string(), network.limits.backlog).await?;
+            let listener = RabbitListener::from_tcp(tcp, Arc::clone(&server_config));
+            listeners.push(serve_listener(listener, &network.limits, &current)?);
+        }
+        for listener_config in &network.listeners {
+            let limits = listener_config.limits.as_ref().unwrap_or(&network.limits);
+            let tcp = bind_tcp(&listener_config.address, limits.backlog).await?;
+            let listener = if !listener_config.tls {
+                serve_listener(PlainListener::from_tcp(tcp), limits, &current)?
+            } else {
+                let server_config = match listener_config.cert_pair(&base_dir)? {
+                    Some(pair) => make_server_config(&pair)?,
+                    None => Arc::clone(&server_config),
+                };
+                serve_listener(
+                    RabbitListener::from_tcp(tcp, server_config),
+                    limits,
+                    &current,
+                )?
+            };
+            listeners.push(listener);
+        }
+        let janitor = burrow.spawn_janitor();
+        burrow.advance(BurrowState::Running);
+        Ok(Listening {
+            burrow,
+            listeners,
+            janitor,
+        })
+    }
+}
+
+/// Serve the burrow `burrows` holds on `acceptor` within `limits`,
+/// with counters registered for the admin socket.
+fn serve_listener<A: Acceptor + 'static>(
+    acceptor: A,
+    limits: &ListenerLimits,
+    burrows: &watch::Sender<Arc<Burrow>>,
+) -> Result<(SocketAddr, JoinHandle<()>), ProtocolError> {
+    let local_addr = acceptor.local_addr()?;
+    let counters = Arc::new(ListenerCounters::new(local_addr.to_string()));
+    burrows
+        .borrow()
+        .listener_stats
+        .register(Arc::clone(&counters));
+    let task = run_listener(acceptor, limits.clone(), counters, burrows.subscribe());
+    Ok((local_addr, task))
+}
+
+/// A burrow serving the listeners [`BurrowBuilder::start`] bound.
+pub struct Listening {
+    /// The burrow being served.
+    pub burrow: Arc<Burrow>,
+    listeners: Vec<(SocketAddr, JoinHandle<()>)>,
+    /// The task keeping topics within their retention limits, if any.
+    janitor: Option<JoinHandle<()>>,
+}
+
+impl Listening {
+    /// The addresses being listened on, with the ports bound.
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.listeners.iter().map(|(addr, _)| *addr).collect()
+    }
+
+    /// Stop accepting connections, closing the listening sockets, stop
+    /// the janitor, and [shut the burrow down](Burrow::shutdown).
+    pub async fn shutdown(self) -> Result<(), ProtocolError> {
+        for (_, task) in self.listeners {
+            task.abort();
+            let _ = task.await;
+        }
+        if let Some(janitor) = self.janitor {
+            janitor.abort();
+        }
+        self.burrow.shutdown().await
+    }
+}
+
+/// Tell the peer why its handshake failed, then hand the error back.
+async fn reject_handshake<T: Tunnel>(tunnel: &mut T, err: RabbitError) -> ProtocolError {
+    let _ = tunnel.send_frame(&err.clone().into()).await;
+    err.into()
+}
+
+/// Switch `tunnel` to length-prefixed framing if we `offered` it and
+/// the server's final 200 accepted it.
+fn accept_wire_format<T: Tunnel>(tunnel: &mut T, offered: bool, ok: &Frame) {
+    if offered && ok.header("Wire") == Some(WIRE_LENGTH_PREFIXED) {
+        tunnel.set_wire_format(WireFormat::LengthPrefixed);
+    }
+}
+
+/// The lane a frame travels on, from its `Lane` header (default 0).
+fn frame_lane(frame: &Frame) -> u16 {
+    frame
+        .header("Lane")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Holds a tunnel's place in [`Burrow::active_connections`], however
+/// the tunnel ends.
+struct ConnectionSlot<'a>(&'a AtomicU32);
+
+impl Drop for ConnectionSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// `trust` with the anchors registered with `federation` added to
+/// `trust.anchors`.
+fn anchored(trust: &TrustConfig, federation: &FederationManager) -> TrustConfig {
+    let mut trust = trust.clone();
+    for anchor in federation.anchors() {
+        if !trust.anchors.contains(&anchor.id) {
+            trust.anchors.push(anchor.id);
+        }
+    }
+    trust
+}
+
+/// Load the events kept in `store` into `events`, seeding each topic's
+/// quota usage, and number new ones after any pruned.
+fn restore_events(store: &dyn EventStore, events: &EventEngine, quotas: &QuotaManager) {
+    for topic in store.topics() {
+        if let Ok(loaded) = store.load(&topic) {
+            if !loaded.is_empty() {
+                info!(topic = %topic, count = loaded.len(), "restored events from continuity");
+                let bytes = loaded.iter().map(|e| e.body.len() as u64).sum();
+                quotas.seed_topic(&topic, bytes, loaded.len() as u64);
+                events.load_events(&topic, loaded);
+            }
+        }
+        if let Ok(last @ 1..) = store.last_seq(&topic) {
+            events.resume_after(&topic, last);
+        }
+    }
+}
+
+/// `200 OK` reporting a lane's state after a `LANE-*` request.
+fn lane_state_frame(lane_id: u16, state: LaneState) -> Frame {
+    let mut resp = Frame::new("200 OK");
+    resp.set_header("Lane", lane_id.to_string());
+    resp.set_header("Lane-State", state.as_str());
+    resp
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{AiChatConfig, Config};
+    use crate::config::Config;
     use crate::content::store::MenuItem;
+    use crate::events::engine::Event;
     use crate::protocol::frame::Frame;
     use crate::transport::memory::memory_tunnel_pair;
     use std::io::Write;
@@ -745,6 +3289,135 @@ mod tests {
         assert!(burrow.burrow_id().starts_with("ed25519:"));
     }
 
+    #[test]
+    fn builder_uses_the_identity_and_event_store_it_is_given() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ContinuityStore::new(dir.path()).unwrap();
+        for seq in 1..=2 {
+            let event = Event {
+                seq,
+                body: format!("event {seq}"),
+            };
+            store.append("/q/log", &event).unwrap();
+        }
+        let identity = Identity::generate();
+        let id = identity.burrow_id();
+
+        let burrow = BurrowBuilder::in_memory("oak")
+            .identity(identity)
+            .event_store(Box::new(store))
+            .build()
+            .unwrap();
+        assert_eq!(burrow.burrow_id(), id);
+        assert_eq!(burrow.events.event_count("/q/log"), 2);
+        let (_, event) = burrow.events.publish("/q/log", "event 3");
+        assert_eq!(event.seq, 3);
+        assert!(burrow.continuity.is_some());
+    }
+
+    #[tokio::test]
+    async fn the_janitor_drops_events_beyond_retention_and_releases_their_quota() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.continuity.max_events = 2;
+        config.continuity.compact_secs = 1;
+        let burrow = Arc::new(Burrow::from_config(&config, dir.path()).unwrap());
+        let store = burrow.continuity.as_deref().unwrap();
+        for n in 1..=5 {
+            let (_, event) = burrow.events.publish("/q/log", &format!("event {n}"));
+            store.append("/q/log", &event).unwrap();
+        }
+        burrow.quotas.seed_topic("/q/log", 5 * 7, 5);
+
+        let janitor = burrow.spawn_janitor().unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            // Quota is released last.
+            while burrow.quotas.topic_usage("/q/log").events > 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the janitor never ran");
+        janitor.abort();
+        assert_eq!(store.records("/q/log").unwrap().len(), 2);
+        assert_eq!(burrow.events.event_count("/q/log"), 2);
+    }
+
+    #[tokio::test]
+    async fn started_burrow_admits_peers_by_the_policy_it_is_given() {
+        use crate::security::trust_policy::DenyList;
+        use crate::transport::connector::{connect, make_client_config_insecure};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.network.port = 0;
+        config.network.bind = vec!["127.0.0.1".into()];
+        config.identity.require_auth = true;
+        let banned = Burrow::in_memory("banned");
+        let welcome = Burrow::in_memory("welcome");
+        let listening = BurrowBuilder::new(config, dir.path())
+            .trust_policy(Arc::new(DenyList::new([banned.burrow_id()])))
+            .start()
+            .await
+            .unwrap();
+        let addr = listening.local_addrs()[0].to_string();
+
+        let mut tunnel = connect(&addr, make_client_config_insecure(), "localhost")
+            .await
+            .unwrap();
+        let _ = banned.client_handshake(&mut tunnel).await;
+        // The refused peer is disconnected without being remembered.
+        while let Ok(Some(_)) = tunnel.recv_frame().await {}
+        let mut tunnel = connect(&addr, make_client_config_insecure(), "localhost")
+            .await
+            .unwrap();
+        welcome.client_handshake(&mut tunnel).await.unwrap();
+        tunnel.send_frame(&Frame::new("PING")).await.unwrap();
+        assert_eq!(tunnel.recv_frame().await.unwrap().unwrap().verb, "200");
+
+        {
+            let trust = listening.burrow.trust.lock().unwrap();
+            assert!(trust.get(&banned.burrow_id()).is_none());
+            assert!(trust.get(&welcome.burrow_id()).is_some());
+        }
+        tunnel.close().await.unwrap();
+        listening.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn started_burrow_serves_its_configured_listeners() {
+        use crate::config::ListenerConfig;
+        use crate::transport::tcp::connect_plain;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.network.port = 0;
+        config.network.bind = vec!["127.0.0.1".into()];
+        config.network.listeners = vec![ListenerConfig {
+            address: "127.0.0.1:0".into(),
+            tls: false,
+            cert: None,
+            key: None,
+            limits: None,
+        }];
+        let listening = BurrowBuilder::new(config, dir.path())
+            .start()
+            .await
+            .unwrap();
+        let addrs = listening.local_addrs();
+        assert_eq!(addrs.len(), 2);
+        assert_eq!(listening.burrow.listener_stats.snapshot().len(), 2);
+
+        // The plain listener speaks Rabbit without TLS.
+        let client = Burrow::in_memory("client");
+        let mut tunnel = connect_plain(&addrs[1].to_string()).await.unwrap();
+        client.client_handshake(&mut tunnel).await.unwrap();
+        tunnel.send_frame(&Frame::new("PING")).await.unwrap();
+        assert_eq!(tunnel.recv_frame().await.unwrap().unwrap().verb, "200");
+        tunnel.close().await.unwrap();
+        listening.shutdown().await.unwrap();
+    }
+
     #[test]
     fn from_config_creates_identity() {
         let dir = tempfile::tempdir().unwrap();
@@ -771,6 +3444,66 @@ mod tests {
         assert_eq!(id1, id2);
     }
 
+    #[tokio::test]
+    async fn shutdown_state_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.identity.storage = "state".into();
+
+        let burrow = Burrow::from_config(&config, dir.path()).unwrap();
+        burrow
+            .routing
+            .update("ed25519:TARGET", "ed25519:HOP", 2)
+            .await;
+        burrow
+            .saved_sessions
+            .lock()
+            .unwrap()
+            .push(crate::session::SavedSessionState {
+                peer_id: "ed25519:PEER".into(),
+                session_token: "tok".into(),
+                lanes: Vec::new(),
+            });
+        burrow.shutdown().await.unwrap();
+        assert!(dir.path().join("state").join("trust.tsv").exists());
+
+        let restarted = Burrow::from_config(&config, dir.path()).unwrap();
+        assert_eq!(
+            restarted.routing.next_hop("ed25519:TARGET").await,
+            Some("ed25519:HOP".into())
+        );
+        let sessions = restarted.saved_sessions.lock().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session_token, "tok");
+    }
+
+    #[tokio::test]
+    async fn a_drained_burrow_leaves_its_storage_to_the_next() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::default();
+        let mut server = Burrow::from_config(&config, dir.path()).unwrap();
+        server.shutdown_grace_secs = 1;
+        let server = Arc::new(server);
+
+        let client = Burrow::in_memory("client");
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let srv = Arc::clone(&server);
+        tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+        let mut publish = Frame::with_args("PUBLISH", vec!["/q/test".into()]);
+        publish.set_body("kept");
+        c.send_frame(&publish).await.unwrap();
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "204");
+
+        // The peer never closes; the tunnel ends with the grace period.
+        server.shutdown().await.unwrap();
+        server.wait_closed().await.unwrap();
+        assert_eq!(server.active_connections.load(Ordering::Relaxed), 0);
+
+        let next = Burrow::from_config(&config, dir.path()).unwrap();
+        assert_eq!(next.events.event_count("/q/test"), 1);
+    }
+
     #[test]
     fn from_config_loads_content() {
         let dir = tempfile::tempdir().unwrap();
@@ -907,6 +3640,401 @@ file = "readme.txt"
         sh.await.unwrap().unwrap();
     }
 
+    #[tokio::test]
+    async fn cancel_stops_a_chunked_fetch() {
+        let mut server = Burrow::in_memory("server");
+        server.require_auth = false;
+        server.content.register_text("/0/big", "x".repeat(1000));
+
+        let client = Burrow::in_memory("client");
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let sh = tokio::spawn(async move { server.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+
+        let mut fetch = Frame::with_args("FETCH", vec!["/0/big".into()]);
+        fetch.set_header("Lane", "3");
+        fetch.set_header("Txn", "F1");
+        fetch.set_header("Chunk-Size", "10");
+        c.send_frame(&fetch).await.unwrap();
+        c.send_frame(&crate::protocol::txn::cancel_frame("F1", 3))
+            .await
+            .unwrap();
+
+        let head = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(head.header("Chunks"), Some("100"));
+        let mut chunks = 0;
+        loop {
+            let frame = c.recv_frame().await.unwrap().unwrap();
+            if frame.verb == "CHUNK" {
+                chunks += 1;
+                continue;
+            }
+            assert_eq!(frame.verb, "499");
+            assert_eq!(frame.header("Txn"), Some("F1"));
+            assert_eq!(frame.header("Lane"), Some("3"));
+            break;
+        }
+        assert!(chunks < 100);
+
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn lanes_open_close_and_respect_the_limit() {
+        let mut server = Burrow::in_memory("server");
+        server.require_auth = false;
+        server.max_lanes = 1;
+        server.content.register_text("/0/hello", "Hello, world!");
+
+        let client = Burrow::in_memory("client");
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let sh = tokio::spawn(async move { server.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+
+        let mut open = Frame::new("LANE-OPEN");
+        open.set_header("Lane", "5");
+        c.send_frame(&open).await.unwrap();
+        let resp = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(resp.verb, "200");
+        assert_eq!(resp.header("Lane-State"), Some("open"));
+
+        // A request on a second lane would exceed the limit.
+        let mut fetch = Frame::with_args("FETCH", vec!["/0/hello".into()]);
+        fetch.set_header("Lane", "6");
+        c.send_frame(&fetch).await.unwrap();
+        let resp = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(resp.verb, "429");
+        assert_eq!(resp.header("Lane"), Some("6"));
+
+        let mut close = Frame::new("LANE-CLOSE");
+        close.set_header("Lane", "5");
+        c.send_frame(&close).await.unwrap();
+        let resp = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(resp.header("Lane-State"), Some("closed"));
+
+        // The closed lane's slot is free again.
+        c.send_frame(&fetch).await.unwrap();
+        let resp = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(resp.verb, "200");
+
+        let mut reset = Frame::new("LANE-RESET");
+        reset.set_header("Lane", "6");
+        c.send_frame(&reset).await.unwrap();
+        let resp = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(resp.header("Lane-State"), Some("closed"));
+
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn sequenced_frames_earn_credit() {
+        let mut server = Burrow::in_memory("server");
+        server.require_auth = false;
+        server.content.register_text("/0/hello", "Hello, world!");
+
+        let client = Burrow::in_memory("client");
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let sh = tokio::spawn(async move { server.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+
+        let mut grant = None;
+        for seq in 1..=8 {
+            let mut fetch = Frame::with_args("FETCH", vec!["/0/hello".into()]);
+            fetch.set_header("Lane", "2");
+            fetch.set_header("Seq", seq.to_string());
+            c.send_frame(&fetch).await.unwrap();
+            let mut resp = c.recv_frame().await.unwrap().unwrap();
+            if resp.verb == "CREDIT" {
+                grant = Some(resp);
+                resp = c.recv_frame().await.unwrap().unwrap();
+            }
+            assert_eq!(resp.verb, "200");
+        }
+        let grant = grant.expect("no CREDIT after half the window");
+        assert_eq!(grant.header("Lane"), Some("2"));
+        assert!(grant.header("Credit").unwrap().starts_with('+'));
+
+        // Responses are never answered.
+        c.send_frame(&Frame::new("200 OK")).await.unwrap();
+        c.send_frame(&Frame::with_args("FETCH", vec!["/0/hello".into()]))
+            .await
+            .unwrap();
+        let resp = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(resp.args.first().map(String::as_str), Some("CONTENT"));
+
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn tunnel_stats_count_traffic() {
+        let mut server = Burrow::in_memory("server");
+        server.require_auth = false;
+        server.content.register_text("/0/hello", "Hello, world!");
+        let server = Arc::new(server);
+
+        let client = Burrow::in_memory("client");
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let srv = server.clone();
+        let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+
+        let mut fetch = Frame::with_args("FETCH", vec!["/0/hello".into()]);
+        fetch.set_header("Lane", "2");
+        c.send_frame(&fetch).await.unwrap();
+        c.recv_frame().await.unwrap().unwrap();
+
+        let stats = server.tunnel_stats.snapshot().await;
+        assert_eq!(stats.len(), 1);
+        // The HELLO, then the FETCH.
+        assert!(stats[0].frames_received >= 2);
+        let lane = stats[0].lanes.iter().find(|l| l.lane == 2).unwrap();
+        assert_eq!(lane.frames_received, 1);
+        assert_eq!(lane.frames_sent, 1);
+        assert!(lane.bytes_sent > 0);
+
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+        assert!(server.tunnel_stats.snapshot().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn shutdown_sends_goaway_and_parks_unacknowledged_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut server = Burrow::from_config(&Config::default(), dir.path()).unwrap();
+        server.shutdown_grace_secs = 1;
+        server.content.register_text("/0/hello", "Hello, world!");
+        let server = Arc::new(server);
+
+        let client = Burrow::in_memory("client");
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let srv = server.clone();
+        let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+
+        let mut event = Frame::with_args("EVENT", vec!["/q/chat".into()]);
+        event.set_header("Lane", "3");
+        server
+            .sessions
+            .broadcast(vec![(client.burrow_id(), event)])
+            .await;
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "EVENT");
+
+        let srv = server.clone();
+        let shutdown = tokio::spawn(async move { srv.shutdown().await });
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "GOAWAY");
+
+        // New requests are refused while the tunnel drains.
+        let fetch = Frame::with_args("FETCH", vec!["/0/hello".into()]);
+        c.send_frame(&fetch).await.unwrap();
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "503");
+
+        // The EVENT is never acknowledged, so the tunnel closes at the
+        // end of the grace period and parks it.
+        assert!(c.recv_frame().await.unwrap().is_none());
+        sh.await.unwrap().unwrap();
+        shutdown.await.unwrap().unwrap();
+        let parked = server.dead_letters.list();
+        assert_eq!(parked.len(), 1);
+        assert_eq!(parked[0].peer_id, client.burrow_id());
+        assert_eq!(parked[0].lane, 3);
+    }
+
+    #[tokio::test]
+    async fn revoke_kicks_peer_and_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Arc::new(Burrow::from_config(&Config::default(), dir.path()).unwrap());
+
+        let peer = Burrow::in_memory("peer");
+        let (mut p, mut s) = memory_tunnel_pair("p", "s");
+        let srv = server.clone();
+        let peer_handle = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+        peer.client_handshake(&mut p).await.unwrap();
+
+        let admin = Burrow::in_memory("admin");
+        let (mut a, mut s) = memory_tunnel_pair("a", "s");
+        let srv = server.clone();
+        let admin_handle = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+        admin.client_handshake(&mut a).await.unwrap();
+        assert_eq!(server.session_store.len(), 2);
+
+        // Only a peer allowed to manage burrows may revoke.
+        let revoke = Frame::with_args("REVOKE", vec![peer.burrow_id()]);
+        a.send_frame(&revoke).await.unwrap();
+        assert_eq!(a.recv_frame().await.unwrap().unwrap().verb, "403");
+        server.capabilities.lock().unwrap().grant(
+            &admin.burrow_id(),
+            Capability::ManageBurrows,
+            60,
+        );
+        a.send_frame(&revoke).await.unwrap();
+        let ok = a.recv_frame().await.unwrap().unwrap();
+        assert_eq!(ok.verb, "200");
+        assert_eq!(ok.header("Revoked"), Some("1"));
+
+        // The revoked peer is told and its tunnel closes.
+        assert_eq!(p.recv_frame().await.unwrap().unwrap().verb, "403");
+        assert!(p.recv_frame().await.unwrap().is_none());
+        peer_handle.await.unwrap().unwrap();
+
+        a.close().await.unwrap();
+        admin_handle.await.unwrap().unwrap();
+        server.shutdown().await.unwrap();
+        let restarted = Burrow::from_config(&Config::default(), dir.path()).unwrap();
+        let sessions = restarted.session_store.list();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].peer_id, admin.burrow_id());
+    }
+
+    #[tokio::test]
+    async fn refresh_replaces_the_session_token() {
+        let server = Arc::new(Burrow::in_memory("server"));
+        let client = Identity::generate();
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let srv = server.clone();
+        let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+
+        c.send_frame(&build_hello(&client)).await.unwrap();
+        let challenge = c.recv_frame().await.unwrap().unwrap();
+        c.send_frame(&build_auth_proof(&client, &challenge).unwrap())
+            .await
+            .unwrap();
+        let hello = c.recv_frame().await.unwrap().unwrap();
+        let old = hello.header("Session-Token").unwrap().to_string();
+        let refresh = hello.header("Refresh-Token").unwrap().to_string();
+
+        // A session token is not a refresh token.
+        let mut frame = Frame::new("REFRESH");
+        frame.set_header("Refresh-Token", &old);
+        c.send_frame(&frame).await.unwrap();
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "400");
+
+        frame.set_header("Refresh-Token", &refresh);
+        c.send_frame(&frame).await.unwrap();
+        let ok = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(ok.verb, "200");
+        let new = ok.header("Session-Token").unwrap();
+        let claims = TokenClaims::verify_from(new, &server.burrow_id(), TokenKind::Access).unwrap();
+        assert_eq!(claims.subject, client.burrow_id());
+        assert!(!server.session_store.is_active(&old));
+        assert!(server.session_store.is_active(new));
+
+        // The tunnel lives on under the new token.
+        c.send_frame(&Frame::new("PING")).await.unwrap();
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "200");
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn rotated_key_inherits_trust() {
+        let server = Arc::new(Burrow::in_memory("server"));
+        let old = Burrow::in_memory("client");
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let srv = server.clone();
+        let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+        old.client_handshake(&mut c).await.unwrap();
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+
+        let (identity, rotation) = old.identity.rotate();
+        let mut new = Burrow::in_memory("client");
+        new.identity = identity;
+        new.rotation = Some(rotation);
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let srv = server.clone();
+        let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+        new.client_handshake(&mut c).await.unwrap();
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "200");
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+
+        let trust = server.trust.lock().unwrap();
+        assert_eq!(
+            trust.get(&old.burrow_id()).unwrap().rotated_to,
+            Some(new.burrow_id())
+        );
+        assert!(trust.get(&new.burrow_id()).is_some());
+    }
+
+    #[tokio::test]
+    async fn required_digests_are_added_and_checked() {
+        let mut server = Burrow::in_memory("server");
+        server.require_auth = false;
+        server.require_digest = true;
+        server.content.register_text("/0/hello", "Hello, world!");
+
+        let client = Burrow::in_memory("client");
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let sh = tokio::spawn(async move { server.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+
+        let fetch = Frame::with_args("FETCH", vec!["/0/hello".into()]);
+        c.send_frame(&fetch).await.unwrap();
+        let resp = c.recv_frame().await.unwrap().unwrap();
+        assert!(resp.header("Digest").is_some());
+        assert!(resp.verify_digest().unwrap());
+
+        let mut event = Frame::with_args("EVENT", vec!["/q/chat".into()]);
+        event.set_header("Lane", "2");
+        event.set_body("hi");
+        c.send_frame(&event).await.unwrap();
+        let resp = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(resp.verb, "412");
+        assert_eq!(resp.header("Lane"), Some("2"));
+
+        event.set_digest();
+        event.body = Some("ho".into());
+        c.send_frame(&event).await.unwrap();
+        let resp = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(resp.verb, "412");
+
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn frame_tap_captures_tunnel() {
+        use crate::transport::capture::{read_capture, CaptureWriter};
+        use crate::transport::tap::TapDirection;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tunnel.rcap");
+        let mut server = Burrow::in_memory("server");
+        server.require_auth = false;
+        server.content.register_text("/0/hello", "Hello, world!");
+        server.set_frame_tap(Some(Arc::new(CaptureWriter::create(&path).unwrap())));
+
+        let client = Burrow::in_memory("client");
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let sh = tokio::spawn(async move { server.handle_tunnel(&mut s).await });
+
+        client.client_handshake(&mut c).await.unwrap();
+        let fetch = Frame::with_args("FETCH", vec!["/0/hello".into()]);
+        c.send_frame(&fetch).await.unwrap();
+        c.recv_frame().await.unwrap().unwrap();
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+
+        let records = read_capture(&path).unwrap();
+        let seen: Vec<(TapDirection, &str)> = records
+            .iter()
+            .map(|r| (r.direction, r.frame.verb.as_str()))
+            .collect();
+        assert_eq!(
+            seen,
+            vec![
+                (TapDirection::Inbound, "HELLO"),
+                (TapDirection::Outbound, "200"),
+                (TapDirection::Inbound, "FETCH"),
+                (TapDirection::Outbound, "200"),
+            ]
+        );
+        assert!(records.iter().all(|r| r.conn == records[0].conn));
+    }
+
     #[tokio::test]
     async fn handle_tunnel_pub_sub() {
         // Use authenticated mode so the peer gets Subscribe + Publish caps.
@@ -940,4 +4068,185 @@ file = "readme.txt"
         c.close().await.unwrap();
         sh.await.unwrap().unwrap();
     }
+
+    #[tokio::test]
+    async fn resubscribing_resumes_after_the_last_acknowledged_event() {
+        let server = Arc::new(Burrow::in_memory("server"));
+        let client = Burrow::in_memory("client");
+        let mut sub = Frame::with_args("SUBSCRIBE", vec!["/q/test".into()]);
+        sub.set_header("Lane", "1");
+
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let handler = Arc::clone(&server);
+        let sh = tokio::spawn(async move { handler.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+        c.send_frame(&sub).await.unwrap();
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "201");
+        for n in 1..=3 {
+            let mut publish = Frame::with_args("PUBLISH", vec!["/q/test".into()]);
+            publish.set_body(format!("event {n}"));
+            c.send_frame(&publish).await.unwrap();
+        }
+        let mut delivered = Vec::new();
+        while delivered.len() < 3 {
+            let frame = c.recv_frame().await.unwrap().unwrap();
+            if frame.verb == "EVENT" {
+                delivered.push(frame);
+            }
+        }
+        // Only the first two are acknowledged before the tunnel drops.
+        let mut ack = Frame::new("ACK");
+        ack.set_header("Lane", "1");
+        ack.set_header("ACK", delivered[1].header("Seq").unwrap());
+        c.send_frame(&ack).await.unwrap();
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "200");
+        c.close().await.unwrap();
+        let peer_id = sh.await.unwrap().unwrap();
+        assert_eq!(server.consumer_offsets.get("/q/test", &peer_id), Some(2));
+
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let handler = Arc::clone(&server);
+        let sh = tokio::spawn(async move { handler.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+        c.send_frame(&sub).await.unwrap();
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "201");
+        let replayed = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(replayed.header("Event-Seq"), Some("3"));
+        assert_eq!(replayed.body.as_deref(), Some("event 3"));
+        // Replayed events are numbered on the lane like live ones, and
+        // acknowledged by that number.
+        assert_eq!(replayed.header("Seq"), Some("1"));
+        ack.set_header("ACK", "1");
+        c.send_frame(&ack).await.unwrap();
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "200");
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+        assert_eq!(server.consumer_offsets.get("/q/test", &peer_id), Some(3));
+    }
+
+    #[tokio::test]
+    async fn live_events_wait_behind_a_replay_and_replayed_ones_are_retransmitted() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.continuity.memory_events = 2;
+        config.network.retransmit_timeout_ms = 100;
+        let server = Arc::new(Burrow::from_config(&config, dir.path()).unwrap());
+        let store = server.continuity.as_deref().unwrap();
+        for n in 1..=200 {
+            let (_, event) = server.events.publish("/q/log", &format!("event {n}"));
+            store.append("/q/log", &event).unwrap();
+        }
+        let client = Burrow::in_memory("client");
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let handler = Arc::clone(&server);
+        let sh = tokio::spawn(async move { handler.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+
+        // A live event is published while the log is still replaying.
+        let mut sub = Frame::with_args("SUBSCRIBE", vec!["/q/log".into()]);
+        sub.set_header("Lane", "1");
+        sub.set_header("Since", "0");
+        c.send_frame(&sub).await.unwrap();
+        let mut publish = Frame::with_args("PUBLISH", vec!["/q/log".into()]);
+        publish.set_body("live");
+        c.send_frame(&publish).await.unwrap();
+        let mut events = Vec::new();
+        while events.len() < 201 {
+            let frame = c.recv_frame().await.unwrap().unwrap();
+            if frame.verb == "EVENT" {
+                events.push(frame);
+            }
+        }
+        for (n, event) in events.iter().enumerate() {
+            assert_eq!(
+                event.header("Event-Seq"),
+                Some((n + 1).to_string().as_str())
+            );
+            assert_eq!(event.header("Seq"), Some((n + 1).to_string().as_str()));
+        }
+        assert_eq!(events[200].body.as_deref(), Some("live"));
+
+        // Nothing was acknowledged, so the replayed events come again.
+        let resent = loop {
+            let frame = c.recv_frame().await.unwrap().unwrap();
+            if frame.verb == "EVENT" {
+                break frame;
+            }
+        };
+        assert!(resent.header("Event-Seq").is_some());
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn hooks_hear_of_peers_events_and_state_changes() {
+        use tokio::sync::mpsc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.identity.require_auth = true;
+        let server = Arc::new(Burrow::from_config(&config, dir.path()).unwrap());
+        let client = Burrow::in_memory("client");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sent = tx.clone();
+        server.hooks.on_peer_connected(move |peer_id| {
+            let sent = sent.clone();
+            async move { sent.send(format!("connected {peer_id}")).unwrap() }
+        });
+        let sent = tx.clone();
+        server.hooks.on_peer_trusted(move |peer_id| {
+            let sent = sent.clone();
+            async move { sent.send(format!("trusted {peer_id}")).unwrap() }
+        });
+        let sent = tx.clone();
+        server.hooks.on_event_published(move |event| {
+            let sent = sent.clone();
+            async move {
+                sent.send(format!("published {} {}", event.topic, event.seq))
+                    .unwrap()
+            }
+        });
+        server.hooks.on_state_changed(move |state| {
+            let sent = tx.clone();
+            async move { sent.send(format!("state {state}")).unwrap() }
+        });
+        let mut states = server.watch_state();
+        assert_eq!(server.state(), BurrowState::Initialized);
+
+        let (mut c, mut s) = memory_tunnel_pair("c", "s");
+        let handler = Arc::clone(&server);
+        let sh = tokio::spawn(async move { handler.handle_tunnel(&mut s).await });
+        client.client_handshake(&mut c).await.unwrap();
+        let id = client.burrow_id();
+        let mut heard = vec![rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+        heard.sort();
+        assert_eq!(heard, [format!("connected {id}"), format!("trusted {id}")]);
+
+        let mut publish = Frame::with_args("PUBLISH", vec!["/q/chat".into()]);
+        publish.set_body("hello");
+        c.send_frame(&publish).await.unwrap();
+        assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "204");
+        assert_eq!(rx.recv().await.unwrap(), "published /q/chat 1");
+        c.close().await.unwrap();
+        sh.await.unwrap().unwrap();
+
+        // Shutting down drains and stops; going back is refused.
+        server.shutdown().await.unwrap();
+        assert_eq!(rx.recv().await.unwrap(), "state draining");
+        assert_eq!(rx.recv().await.unwrap(), "state stopped");
+        assert!(!server.advance(BurrowState::Running));
+        assert!(states.has_changed().unwrap());
+        assert_eq!(*states.borrow_and_update(), BurrowState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn offers_lead_with_the_external_address() {
+        let burrow = Burrow::in_memory("oak");
+        assert!(burrow.offer_frame().await.is_none());
+
+        burrow.set_external_addr(Some("203.0.113.7:7443".parse().unwrap()));
+        let offer = burrow.offer_frame().await.unwrap();
+        let expected = format!("{}\t203.0.113.7:7443\toak\n", burrow.burrow_id());
+        assert_eq!(offer.body, Some(expected));
+    }
 }
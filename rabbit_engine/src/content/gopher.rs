@@ -0,0 +1,299 @@
+//! Translation between Gopher (RFC 1436) and Rabbit content.
+//!
+//! Rabbit selectors and item types are close to Gopher's, so the
+//! `rabbit-gopher` gateway passes selectors through unchanged: a
+//! Gopher request becomes a `LIST`, `FETCH` or `SEARCH` frame
+//! ([`rabbit_request`]) and the reply becomes a Gopher menu, text
+//! document or binary file ([`gopher_response`]).
+//!
+//! Menu items that live on another burrow are either shown as info
+//! lines or, when the gateway follows remote items, linked through the
+//! gateway with a `//<burrow>` selector prefix that
+//! [`parse_gopher_request`] recognizes.
+
+use base64::Engine as _;
+
+use crate::content::store::MenuItem;
+use crate::protocol::frame::Frame;
+
+/// Host field Gopher clients expect on non-navigable lines.
+const NO_HOST: &str = "error.host\t1";
+
+/// A parsed Gopher request line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GopherRequest {
+    /// Rabbit selector (`/` for the empty Gopher selector).
+    pub selector: String,
+    /// Search terms for type `7` items.
+    pub query: Option<String>,
+    /// Burrow to forward to, from a `//<burrow>` prefix.
+    pub remote: Option<String>,
+}
+
+/// Parse a request line: `<selector>[\t<query>]\r\n`.
+///
+/// Gopher+ suffixes (`\t$`, `\t+…`) are ignored.
+pub fn parse_gopher_request(line: &str) -> GopherRequest {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let (selector, query) = match line.split_once('\t') {
+        Some((s, q)) => {
+            let q = q.split('\t').next().unwrap_or("");
+            let query = (!q.is_empty() && q != "$" && !q.starts_with('+')).then(|| q.to_string());
+            (s, query)
+        }
+        None => (line, None),
+    };
+    let (remote, selector) = match selector.strip_prefix("//") {
+        Some(rest) => match rest.find('/') {
+            Some(i) => (Some(rest[..i].to_string()), &rest[i..]),
+            None => (Some(rest.to_string()), "/"),
+        },
+        None => (None, selector),
+    };
+    GopherRequest {
+        selector: if selector.is_empty() {
+            "/".to_string()
+        } else {
+            selector.to_string()
+        },
+        query,
+        remote: remote.filter(|r| !r.is_empty()),
+    }
+}
+
+/// The item type encoded in a Rabbit selector (`/0/readme` → `0`).
+pub fn selector_type(selector: &str) -> Option<char> {
+    let mut parts = selector.trim_start_matches('/').splitn(2, '/');
+    let first = parts.next()?;
+    let mut chars = first.chars();
+    match (chars.next(), chars.next(), parts.next()) {
+        (Some(c), None, Some(_)) => Some(c),
+        _ => None,
+    }
+}
+
+/// The Rabbit frame that answers a Gopher request: `SEARCH` for
+/// search items, `LIST` for menus and `FETCH` for everything else.
+pub fn rabbit_request(request: &GopherRequest) -> Frame {
+    let verb = match selector_type(&request.selector) {
+        Some('7') => "SEARCH",
+        Some('1') | None => "LIST",
+        Some(_) => "FETCH",
+    };
+    let mut frame = Frame::with_args(verb, vec![request.selector.clone()]);
+    frame.set_header("Lane", "0");
+    if verb == "SEARCH" {
+        frame.set_body(request.query.clone().unwrap_or_default());
+    }
+    frame
+}
+
+/// The Gopher item type for a Rabbit item type, if it has one.
+/// UI bundles are JSON and served as text.
+pub fn gopher_type(rabbit: char) -> Option<char> {
+    match rabbit {
+        '0' | '1' | '7' | '9' | 'i' => Some(rabbit),
+        'u' => Some('0'),
+        _ => None,
+    }
+}
+
+/// Make a string safe for a tab-delimited Gopher line.
+fn field(s: &str) -> String {
+    s.replace(['\t', '\r', '\n'], " ")
+}
+
+fn info_line(text: &str) -> String {
+    format!("i{}\t\t{}\r\n", field(text), NO_HOST)
+}
+
+/// Render Rabbit menu items as a Gopher menu.
+///
+/// `host` and `port` are the gateway's public address.  Items on other
+/// burrows are linked through the gateway when `follow_remote` is set
+/// and shown as info lines otherwise; items with no Gopher equivalent
+/// (such as event streams) are also shown as info lines.
+pub fn gophermap(items: &[MenuItem], host: &str, port: u16, follow_remote: bool) -> String {
+    let mut out = String::new();
+    for item in items {
+        let remote = !item.burrow.is_empty() && item.burrow != "=";
+        let line = match gopher_type(item.type_code) {
+            Some('i') => info_line(&item.label),
+            Some(t) if !remote => format!(
+                "{}{}\t{}\t{}\t{}\r\n",
+                t,
+                field(&item.label),
+                field(&item.selector),
+                host,
+                port
+            ),
+            Some(t) if follow_remote => format!(
+                "{}{}\t//{}{}\t{}\t{}\r\n",
+                t,
+                field(&item.label),
+                field(&item.burrow),
+                field(&item.selector),
+                host,
+                port
+            ),
+            Some(_) => info_line(&format!("{} (on {})", item.label, item.burrow)),
+            None if item.type_code == 'q' => {
+                info_line(&format!("{} (event stream: {})", item.label, item.selector))
+            }
+            None => info_line(&item.label),
+        };
+        out.push_str(&line);
+    }
+    out.push_str(".\r\n");
+    out
+}
+
+/// Render text as a Gopher text document: CRLF line endings, lines
+/// starting with `.` doubled, and a terminating `.` line.
+pub fn gopher_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 8);
+    for line in text.lines() {
+        if line.starts_with('.') {
+            out.push('.');
+        }
+        out.push_str(line);
+        out.push_str("\r\n");
+    }
+    out.push_str(".\r\n");
+    out
+}
+
+/// A Gopher error menu (item type `3`).
+pub fn gopher_error(message: &str) -> String {
+    format!("3{}\t\t{}\r\n.\r\n", field(message), NO_HOST)
+}
+
+/// Convert a Rabbit reply into the bytes sent to the Gopher client.
+///
+/// Menus (`200 MENU`, or any reply with a `text/rabbitmap` view)
+/// become Gopher menus, base64 transfers are decoded and sent raw, and
+/// other content is sent as text.  Error replies become a type `3`
+/// error item.
+pub fn gopher_response(reply: &Frame, host: &str, port: u16, follow_remote: bool) -> Vec<u8> {
+    let body = reply.body.as_deref().unwrap_or("");
+    if !reply.verb.starts_with('2') {
+        let status = std::iter::once(reply.verb.as_str())
+            .chain(reply.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let message = if body.is_empty() {
+            status
+        } else {
+            format!("{}: {}", status, body.lines().next().unwrap_or(""))
+        };
+        return gopher_error(&message).into_bytes();
+    }
+
+    let is_menu = reply.args.first().is_some_and(|a| a == "MENU")
+        || reply.header("View") == Some("text/rabbitmap");
+    if is_menu {
+        let items: Vec<MenuItem> = body
+            .lines()
+            .take_while(|l| *l != ".")
+            .filter_map(MenuItem::from_rabbitmap_line)
+            .collect();
+        return gophermap(&items, host, port, follow_remote).into_bytes();
+    }
+    if reply.header("Transfer") == Some("base64") {
+        return match base64::engine::general_purpose::STANDARD.decode(body.trim()) {
+            Ok(bytes) => bytes,
+            Err(_) => gopher_error("undecodable binary content").into_bytes(),
+        };
+    }
+    gopher_text(body).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(
+            parse_gopher_request("\r\n"),
+            GopherRequest {
+                selector: "/".into(),
+                query: None,
+                remote: None,
+            }
+        );
+        let search = parse_gopher_request("/7/search\trabbit holes\r\n");
+        assert_eq!(search.selector, "/7/search");
+        assert_eq!(search.query.as_deref(), Some("rabbit holes"));
+        assert_eq!(parse_gopher_request("/1/docs\t$\r\n").query, None);
+
+        let remote = parse_gopher_request("//10.0.0.2:7443/0/readme\r\n");
+        assert_eq!(remote.remote.as_deref(), Some("10.0.0.2:7443"));
+        assert_eq!(remote.selector, "/0/readme");
+        assert_eq!(parse_gopher_request("//peer\r\n").selector, "/");
+    }
+
+    #[test]
+    fn maps_selectors_to_verbs() {
+        let verb = |line: &str| rabbit_request(&parse_gopher_request(line)).verb;
+        assert_eq!(verb(""), "LIST");
+        assert_eq!(verb("/1/docs"), "LIST");
+        assert_eq!(verb("/0/readme"), "FETCH");
+        assert_eq!(verb("/9/logo.png"), "FETCH");
+        let search = rabbit_request(&parse_gopher_request("/7/search\tcarrots"));
+        assert_eq!(search.verb, "SEARCH");
+        assert_eq!(search.body.as_deref(), Some("carrots"));
+        assert_eq!(selector_type("/warren"), None);
+    }
+
+    #[test]
+    fn renders_menus() {
+        let items = vec![
+            MenuItem::info("Welcome"),
+            MenuItem::local('1', "Docs", "/1/docs"),
+            MenuItem::local('q', "Chat", "/q/chat"),
+            MenuItem::new('0', "Their readme", "/0/readme", "10.0.0.2:7443", ""),
+        ];
+        let map = gophermap(&items, "gopher.example", 70, false);
+        let lines: Vec<&str> = map.split("\r\n").collect();
+        assert_eq!(lines[0], "iWelcome\t\terror.host\t1");
+        assert_eq!(lines[1], "1Docs\t/1/docs\tgopher.example\t70");
+        assert!(lines[2].starts_with("iChat (event stream: /q/chat)\t"));
+        assert!(lines[3].starts_with("iTheir readme (on 10.0.0.2:7443)\t"));
+        assert_eq!(lines[4], ".");
+
+        let followed = gophermap(&items[3..], "gopher.example", 70, true);
+        assert_eq!(
+            followed,
+            "0Their readme\t//10.0.0.2:7443/0/readme\tgopher.example\t70\r\n.\r\n"
+        );
+    }
+
+    #[test]
+    fn converts_replies() {
+        let mut menu = Frame::new("200 MENU");
+        menu.set_body("1Docs\t/1/docs\t=\t\r\niAbout\t\t=\t\r\n.\r\n");
+        assert_eq!(
+            gopher_response(&menu, "h", 7070, false),
+            b"1Docs\t/1/docs\th\t7070\r\niAbout\t\terror.host\t1\r\n.\r\n".to_vec()
+        );
+
+        let mut text = Frame::new("200 CONTENT");
+        text.set_header("View", "text/plain");
+        text.set_body("hello\n.hidden\n");
+        assert_eq!(
+            gopher_response(&text, "h", 70, false),
+            b"hello\r\n..hidden\r\n.\r\n".to_vec()
+        );
+
+        let mut binary = Frame::new("200 CONTENT");
+        binary.set_header("Transfer", "base64");
+        binary.set_body("AAEC");
+        assert_eq!(gopher_response(&binary, "h", 70, false), vec![0, 1, 2]);
+
+        let mut missing = Frame::new("404 MISSING");
+        missing.set_body("selector not found: /0/nope");
+        let error = String::from_utf8(gopher_response(&missing, "h", 70, false)).unwrap();
+        assert!(error.starts_with("3404 MISSING: selector not found: /0/nope\t"));
+    }
+}
@@ -17,96 +17,90 @@ use crate::protocol::frame::Frame;
 /// resolves to text, we still return it (with a `View` header).
 /// If not found, returns `404 MISSING`.
 pub fn handle_list(store: &ContentStore, selector: &str, request: &Frame) -> Frame {
-    let lane = request.header("Lane").unwrap_or("0");
-    let txn = request.header("Txn").unwrap_or("");
-
     match store.get(selector) {
-        Some(entry) => {
-            let (verb, body) = match entry {
-                ContentEntry::Menu(_) => ("200 MENU", entry.to_body()),
-                ContentEntry::Text(_) => ("200 CONTENT", entry.to_body()),
-                ContentEntry::Binary(_, _) => ("200 CONTENT", entry.to_body()),
-                ContentEntry::Ui(_) => ("200 CONTENT", entry.to_body()),
-            };
-            let mut response = Frame::new(verb);
-            response.set_header("Lane", lane);
-            if !txn.is_empty() {
-                response.set_header("Txn", txn);
-            }
-            response.set_header("View", entry.view_type());
-            response.set_body(body);
-            response
-        }
-        None => {
-            let err = ProtocolError::Missing(format!("selector not found: {}", selector));
-            let mut frame: Frame = err.into();
-            frame.set_header("Lane", lane);
-            if !txn.is_empty() {
-                frame.set_header("Txn", txn);
-            }
-            frame
-        }
+        Some(entry) => list_entry(entry, request),
+        None => missing(selector, request),
     }
 }
 
+/// The `LIST` response for `entry`, wherever it came from: `200 MENU`
+/// for a menu, `200 CONTENT` for anything else.
+pub fn list_entry(entry: &ContentEntry, request: &Frame) -> Frame {
+    let verb = match entry {
+        ContentEntry::Menu(_) => "200 MENU",
+        ContentEntry::Text(_) | ContentEntry::Binary(_, _) | ContentEntry::Ui(_) => "200 CONTENT",
+    };
+    let mut response = Frame::new(verb);
+    reply_headers(&mut response, request);
+    response.set_header("View", entry.view_type());
+    response.set_body(entry.to_body());
+    response
+}
+
 /// Handle a `FETCH` request.
 ///
 /// Looks up the selector in the store.  Returns `200 CONTENT` with
 /// the body and `View` header, or `404 MISSING` if not found.
 pub fn handle_fetch(store: &ContentStore, selector: &str, request: &Frame) -> Frame {
-    let lane = request.header("Lane").unwrap_or("0");
-    let txn = request.header("Txn").unwrap_or("");
-
     match store.get(selector) {
-        Some(entry) => {
-            // Check Accept-View negotiation if present.
-            if let Some(accept) = request.header("Accept-View") {
-                let view = entry.view_type();
-                let accepted: Vec<&str> = accept.split(',').map(|s| s.trim()).collect();
-                if !accepted.iter().any(|a| *a == view || *a == "*/*") {
-                    let mut resp = Frame::new("406 NOT ACCEPTABLE");
-                    resp.set_header("Lane", lane);
-                    if !txn.is_empty() {
-                        resp.set_header("Txn", txn);
-                    }
-                    resp.set_body(format!(
-                        "no acceptable view: offered {}, accepted {:?}",
-                        view, accepted
-                    ));
-                    return resp;
-                }
-            }
+        Some(entry) => fetch_entry(entry, request),
+        None => missing(selector, request),
+    }
+}
 
-            let mut response = Frame::new("200 CONTENT");
-            response.set_header("Lane", lane);
-            if !txn.is_empty() {
-                response.set_header("Txn", txn);
-            }
-            response.set_header("View", entry.view_type());
-            match entry {
-                ContentEntry::Binary(data, _) => {
-                    // Encode binary as base64 for text-based transport.
-                    use base64::Engine as _;
-                    let encoded = base64::engine::general_purpose::STANDARD.encode(data);
-                    response.set_header("Transfer", "base64");
-                    response.set_body(encoded);
-                }
-                _ => {
-                    response.set_body(entry.to_body());
-                }
-            }
-            response
+/// The `FETCH` response for `entry`, wherever it came from: `200
+/// CONTENT` with a base64 body for binary content, or `406 NOT
+/// ACCEPTABLE` if the request's `Accept-View` rules out its view.
+pub fn fetch_entry(entry: &ContentEntry, request: &Frame) -> Frame {
+    // Check Accept-View negotiation if present.
+    if let Some(accept) = request.header("Accept-View") {
+        let view = entry.view_type();
+        let accepted: Vec<&str> = accept.split(',').map(|s| s.trim()).collect();
+        if !accepted.iter().any(|a| *a == view || *a == "*/*") {
+            let mut resp = Frame::new("406 NOT ACCEPTABLE");
+            reply_headers(&mut resp, request);
+            resp.set_body(format!(
+                "no acceptable view: offered {}, accepted {:?}",
+                view, accepted
+            ));
+            return resp;
         }
-        None => {
-            let err = ProtocolError::Missing(format!("selector not found: {}", selector));
-            let mut frame: Frame = err.into();
-            frame.set_header("Lane", lane);
-            if !txn.is_empty() {
-                frame.set_header("Txn", txn);
-            }
-            frame
+    }
+
+    let mut response = Frame::new("200 CONTENT");
+    reply_headers(&mut response, request);
+    response.set_header("View", entry.view_type());
+    match entry {
+        ContentEntry::Binary(data, _) => {
+            // Encode binary as base64 for text-based transport.
+            use base64::Engine as _;
+            let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+            response.set_header("Transfer", "base64");
+            response.set_body(encoded);
+        }
+        _ => {
+            response.set_body(entry.to_body());
         }
     }
+    response
+}
+
+/// `404 MISSING` for `selector`.
+fn missing(selector: &str, request: &Frame) -> Frame {
+    let err = ProtocolError::Missing(format!("selector not found: {}", selector));
+    let mut frame: Frame = err.into();
+    reply_headers(&mut frame, request);
+    frame
+}
+
+/// Copy the request's `Lane` (default `0`) and `Txn`, if any, to a
+/// response.
+fn reply_headers(response: &mut Frame, request: &Frame) {
+    response.set_header("Lane", request.header("Lane").unwrap_or("0"));
+    let txn = request.header("Txn").unwrap_or("");
+    if !txn.is_empty() {
+        response.set_header("Txn", txn);
+    }
 }
 
 /// Handle a `DESCRIBE` request.
@@ -3,9 +3,14 @@
 //! Menus (rabbitmaps) and plain text content are registered in a
 //! [`ContentStore`](store::ContentStore) and served by the
 //! [`handle_list`](handler::handle_list) and
-//! [`handle_fetch`](handler::handle_fetch) functions.
+//! [`handle_fetch`](handler::handle_fetch) functions.  Selectors the
+//! store lacks can be served by a [`provider`], such as a directory of
+//! files.  The [`gopher`] module translates them for the
+//! `rabbit-gopher` gateway.
 
+pub mod gopher;
 pub mod handler;
 pub mod loader;
+pub mod provider;
 pub mod search;
 pub mod store;
@@ -0,0 +1,381 @@
+//! Content served from outside the [`ContentStore`](super::store::ContentStore).
+//!
+//! A [`ContentProvider`] answers for every selector under a prefix, so
+//! a burrow can serve content it does not hold in memory: a directory
+//! tree, a database, something generated per request.  Providers are
+//! registered in a [`ProviderRegistry`] (see
+//! [`crate::burrow::Burrow::register_provider`]); `LIST` and `FETCH`
+//! consult the provider with the longest matching prefix for any
+//! selector the store does not have.
+//!
+//! A prefix ending in `/` (`/files/`) matches the selectors under it
+//! and the bare directory (`/files`); any other (`/about`) matches
+//! itself and the selectors below it (`/about/team`), but not
+//! `/aboutface`.
+//!
+//! [`FileProvider`] serves a directory tree, as `[[content.files]]` in
+//! the config does.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::content::store::{ContentEntry, MenuItem};
+use crate::protocol::error::ProtocolError;
+
+/// Bytes read from a file to decide whether it is text.
+const SNIFF_BYTES: usize = 1024;
+
+/// Serves the content under a selector prefix.
+pub trait ContentProvider: Send + Sync {
+    /// The content at `selector` (the whole selector, prefix included).
+    /// Fails with `Missing` if there is none.
+    fn fetch(&self, selector: &str) -> Result<ContentEntry, ProtocolError>;
+}
+
+impl<F> ContentProvider for F
+where
+    F: Fn(&str) -> Result<ContentEntry, ProtocolError> + Send + Sync,
+{
+    fn fetch(&self, selector: &str) -> Result<ContentEntry, ProtocolError> {
+        self(selector)
+    }
+}
+
+/// The providers registered with a burrow, by prefix.
+#[derive(Default)]
+pub struct ProviderRegistry {
+    providers: Mutex<Vec<(String, Arc<dyn ContentProvider>)>>,
+}
+
+impl std::fmt::Debug for ProviderRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProviderRegistry")
+            .field("prefixes", &self.prefixes())
+            .finish()
+    }
+}
+
+impl ProviderRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve the selectors under `prefix` from `provider`, replacing
+    /// any provider registered for it before.  Fails with `BadRequest`
+    /// if `prefix` does not start with `/`.
+    pub fn register(
+        &self,
+        prefix: &str,
+        provider: Arc<dyn ContentProvider>,
+    ) -> Result<(), ProtocolError> {
+        if !prefix.starts_with('/') {
+            return Err(ProtocolError::BadRequest(format!(
+                "provider prefix {:?} must start with '/'",
+                prefix
+            )));
+        }
+        let mut providers = self.providers.lock().unwrap_or_else(|e| e.into_inner());
+        providers.retain(|(p, _)| p != prefix);
+        providers.push((prefix.to_string(), provider));
+        Ok(())
+    }
+
+    /// Stop serving `prefix`.  Returns whether a provider was
+    /// registered for it.
+    pub fn unregister(&self, prefix: &str) -> bool {
+        let mut providers = self.providers.lock().unwrap_or_else(|e| e.into_inner());
+        let before = providers.len();
+        providers.retain(|(p, _)| p != prefix);
+        providers.len() != before
+    }
+
+    /// The provider with the longest prefix matching `selector`, if any.
+    pub fn resolve(&self, selector: &str) -> Option<Arc<dyn ContentProvider>> {
+        self.providers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(prefix, _)| under_prefix(prefix, selector).is_some())
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, provider)| Arc::clone(provider))
+    }
+
+    /// The prefixes with providers, sorted.
+    pub fn prefixes(&self) -> Vec<String> {
+        let mut prefixes: Vec<String> = self
+            .providers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(p, _)| p.clone())
+            .collect();
+        prefixes.sort();
+        prefixes
+    }
+
+    /// Whether no provider is registered.
+    pub fn is_empty(&self) -> bool {
+        self.providers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+}
+
+/// The part of `selector` below `prefix`, without a leading `/`, or
+/// `None` if `prefix` does not match it.
+fn under_prefix<'s>(prefix: &str, selector: &'s str) -> Option<&'s str> {
+    let base = prefix.trim_end_matches('/');
+    let rest = selector.strip_prefix(base)?;
+    if rest.is_empty() {
+        return Some(rest);
+    }
+    rest.strip_prefix('/')
+}
+
+/// Serves the files under a directory.
+///
+/// `<prefix>/a/b.txt` is the file `<root>/a/b.txt`.  A file whose start
+/// is UTF-8 without NUL bytes is served as text, any other as binary,
+/// with a MIME type from its extension.  A directory is served as a
+/// menu of its entries (type `1` for directories, `0` for text, `9`
+/// for binary), leaving out those whose names start with `.`.  A
+/// selector reaching outside the root, through `..` or a symbolic
+/// link, is refused with `403 FORBIDDEN`.
+#[derive(Debug, Clone)]
+pub struct FileProvider {
+    prefix: String,
+    root: PathBuf,
+}
+
+impl FileProvider {
+    /// Serve the files under `root` at `prefix`.
+    pub fn new(prefix: impl Into<String>, root: impl Into<PathBuf>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            root: root.into(),
+        }
+    }
+
+    /// The directory served.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The selector of the file at `relative` (a `/`-separated path
+    /// below the root).
+    fn selector_for(&self, relative: &str) -> String {
+        let base = self.prefix.trim_end_matches('/');
+        if relative.is_empty() {
+            base.to_string()
+        } else {
+            format!("{}/{}", base, relative)
+        }
+    }
+
+    /// The menu for the directory at `relative`.
+    fn directory_menu(&self, dir: &Path, relative: &str) -> Result<ContentEntry, ProtocolError> {
+        let read = std::fs::read_dir(dir).map_err(|e| io_error(dir, e))?;
+        let mut entries: Vec<(String, bool)> = read
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let is_dir = entry.file_type().ok()?.is_dir();
+                (!name.starts_with('.')).then_some((name, is_dir))
+            })
+            .collect();
+        entries.sort();
+
+        let mut items = Vec::with_capacity(entries.len());
+        for (name, is_dir) in entries {
+            let child = if relative.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", relative, name)
+            };
+            let type_code = if is_dir {
+                '1'
+            } else if is_text(&dir.join(&name)) {
+                '0'
+            } else {
+                '9'
+            };
+            items.push(MenuItem::local(type_code, name, self.selector_for(&child)));
+        }
+        if items.is_empty() {
+            items.push(MenuItem::info("(empty)"));
+        }
+        Ok(ContentEntry::Menu(items))
+    }
+}
+
+impl ContentProvider for FileProvider {
+    fn fetch(&self, selector: &str) -> Result<ContentEntry, ProtocolError> {
+        let missing = || ProtocolError::Missing(format!("selector not found: {}", selector));
+        let relative = under_prefix(&self.prefix, selector).ok_or_else(missing)?;
+        let mut parts = Vec::new();
+        for part in relative.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    return Err(ProtocolError::Forbidden(format!(
+                        "{} leaves the served directory",
+                        selector
+                    )))
+                }
+                part => parts.push(part),
+            }
+        }
+        let relative = parts.join("/");
+
+        let root = self
+            .root
+            .canonicalize()
+            .map_err(|e| io_error(&self.root, e))?;
+        let path = root.join(&relative).canonicalize().map_err(|_| missing())?;
+        if !path.starts_with(&root) {
+            return Err(ProtocolError::Forbidden(format!(
+                "{} leaves the served directory",
+                selector
+            )));
+        }
+        if path.is_dir() {
+            return self.directory_menu(&path, &relative);
+        }
+        let data = std::fs::read(&path).map_err(|e| io_error(&path, e))?;
+        if looks_like_text(&data) {
+            if let Ok(text) = String::from_utf8(data.clone()) {
+                return Ok(ContentEntry::Text(text));
+            }
+        }
+        Ok(ContentEntry::Binary(data, mime_for(&path).to_string()))
+    }
+}
+
+/// Whether the start of the file at `path` looks like text.
+fn is_text(path: &Path) -> bool {
+    use std::io::Read;
+    let mut start = Vec::with_capacity(SNIFF_BYTES);
+    match std::fs::File::open(path) {
+        Ok(file) => {
+            file.take(SNIFF_BYTES as u64)
+                .read_to_end(&mut start)
+                .is_ok()
+                && looks_like_text(&start)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether `data` starts with UTF-8 without NUL bytes.  A character cut
+/// off by the end of the sample does not count against it.
+fn looks_like_text(data: &[u8]) -> bool {
+    let sample = &data[..data.len().min(SNIFF_BYTES)];
+    if sample.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && sample.len() == SNIFF_BYTES,
+    }
+}
+
+/// The MIME type of a binary file, by extension.
+fn mime_for(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("gz") => "application/gzip",
+        Some("wasm") => "application/wasm",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("mp4") => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> ProtocolError {
+    ProtocolError::InternalError(format!("failed to read '{}': {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_prefix_wins_on_segment_boundaries() {
+        let registry = ProviderRegistry::new();
+        let about = Arc::new(|_: &str| Ok(ContentEntry::Text("about".into())));
+        let team = Arc::new(|_: &str| Ok(ContentEntry::Text("team".into())));
+        registry.register("/about", about).unwrap();
+        registry.register("/about/team/", team).unwrap();
+        assert!(registry
+            .register(
+                "about",
+                Arc::new(|_: &str| Err(ProtocolError::Missing(String::new())))
+            )
+            .is_err());
+
+        let served = |selector: &str| match registry.resolve(selector)?.fetch(selector) {
+            Ok(ContentEntry::Text(text)) => Some(text),
+            _ => None,
+        };
+        assert_eq!(served("/about").as_deref(), Some("about"));
+        assert_eq!(served("/about/history").as_deref(), Some("about"));
+        assert_eq!(served("/about/team").as_deref(), Some("team"));
+        assert_eq!(served("/about/team/alice").as_deref(), Some("team"));
+        assert_eq!(served("/aboutface"), None);
+        assert_eq!(registry.prefixes(), ["/about", "/about/team/"]);
+        assert!(registry.unregister("/about/team/"));
+        assert_eq!(served("/about/team").as_deref(), Some("about"));
+    }
+
+    #[test]
+    fn files_are_served_from_the_root_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("public");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs/readme.txt"), "hello").unwrap();
+        std::fs::write(root.join("logo.png"), [0x89, b'P', b'N', b'G', 0, 1]).unwrap();
+        std::fs::write(root.join(".secret"), "hidden").unwrap();
+        std::fs::write(dir.path().join("outside.txt"), "private").unwrap();
+
+        let files = FileProvider::new("/files/", &root);
+        match files.fetch("/files").unwrap() {
+            ContentEntry::Menu(items) => {
+                let listed: Vec<(char, &str)> = items
+                    .iter()
+                    .map(|i| (i.type_code, i.selector.as_str()))
+                    .collect();
+                assert_eq!(listed, [('1', "/files/docs"), ('9', "/files/logo.png")]);
+            }
+            other => panic!("expected a menu, got {:?}", other),
+        }
+        assert!(matches!(
+            files.fetch("/files/docs/readme.txt").unwrap(),
+            ContentEntry::Text(text) if text == "hello"
+        ));
+        assert_eq!(
+            files.fetch("/files/logo.png").unwrap().mime_type(),
+            "image/png"
+        );
+        assert!(matches!(
+            files.fetch("/files/missing.txt"),
+            Err(ProtocolError::Missing(_))
+        ));
+        assert!(matches!(
+            files.fetch("/files/../outside.txt"),
+            Err(ProtocolError::Forbidden(_))
+        ));
+    }
+}
@@ -0,0 +1,237 @@
+//! Service behaviour for long-running binaries.
+//!
+//! * [`ServiceSignals`] maps SIGTERM and SIGINT to
+//!   [`ServiceSignal::Shutdown`] and SIGHUP to
+//!   [`ServiceSignal::Reload`].  Off Unix only Ctrl-C is observed.
+//! * [`PidFile`] records the process ID and removes the file again
+//!   when dropped.
+//! * [`spawn_background`] implements `--daemonize` by re-running the
+//!   current executable detached from the terminal, with
+//!   `--foreground` in place of `--daemonize`.
+//!
+//! Under systemd run in the foreground (`Type=simple`) and let the
+//! service manager own the process; `--daemonize` and `--pid-file`
+//! exist for init systems that expect a forking service.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::protocol::error::ProtocolError;
+
+/// What a received signal asks the service to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceSignal {
+    /// Save state and exit (SIGTERM, SIGINT).
+    Shutdown,
+    /// Re-read configuration (SIGHUP).
+    Reload,
+}
+
+/// Listens for service-control signals.
+///
+/// Create it once at startup, before serving, so that signals sent
+/// early are not lost.
+pub struct ServiceSignals {
+    #[cfg(unix)]
+    term: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    int: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    hup: tokio::signal::unix::Signal,
+}
+
+impl ServiceSignals {
+    /// Install the signal handlers.  Must be called inside a Tokio
+    /// runtime.
+    pub fn new() -> Result<Self, ProtocolError> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let install = |kind: SignalKind| {
+                signal(kind).map_err(|e| {
+                    ProtocolError::InternalError(format!("failed to install signal handler: {}", e))
+                })
+            };
+            Ok(Self {
+                term: install(SignalKind::terminate())?,
+                int: install(SignalKind::interrupt())?,
+                hup: install(SignalKind::hangup())?,
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(Self {})
+        }
+    }
+
+    /// Wait for the next signal.
+    pub async fn recv(&mut self) -> ServiceSignal {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.term.recv() => ServiceSignal::Shutdown,
+                _ = self.int.recv() => ServiceSignal::Shutdown,
+                _ = self.hup.recv() => ServiceSignal::Reload,
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+            ServiceSignal::Shutdown
+        }
+    }
+}
+
+impl std::fmt::Debug for ServiceSignals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServiceSignals").finish_non_exhaustive()
+    }
+}
+
+/// A PID file that is removed when dropped.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    pid: u32,
+}
+
+impl PidFile {
+    /// Write the current process ID to `path`.
+    ///
+    /// Fails if the file names a process that is still running; a
+    /// stale file left by a crash is overwritten.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(pid) = read_pid(&path) {
+            if process_alive(pid) {
+                return Err(ProtocolError::InternalError(format!(
+                    "{} names running process {}",
+                    path.display(),
+                    pid
+                )));
+            }
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                ProtocolError::InternalError(format!("failed to create directory: {}", e))
+            })?;
+        }
+        let pid = std::process::id();
+        std::fs::write(&path, format!("{}\n", pid)).map_err(|e| {
+            ProtocolError::InternalError(format!("failed to write pid file: {}", e))
+        })?;
+        Ok(Self { path, pid })
+    }
+
+    /// Path of the PID file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Leave the file alone if another process has taken it over.
+        if read_pid(&self.path) == Some(self.pid) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether `pid` is a live process.  Without a portable check this
+/// assumes it is, except on Linux where `/proc` is consulted.
+fn process_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return false;
+    }
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
+
+/// Rewrite a command line for the detached child: every
+/// `--daemonize` becomes `--foreground`.
+pub fn foreground_args<I>(args: I) -> Vec<OsString>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    args.into_iter()
+        .map(|a| {
+            if a == "--daemonize" {
+                OsString::from("--foreground")
+            } else {
+                a
+            }
+        })
+        .collect()
+}
+
+/// Re-run the current executable in the background and return the
+/// child's process ID.
+///
+/// The child gets the current arguments rewritten by
+/// [`foreground_args`], null stdio, and (on Unix) its own process
+/// group so terminal signals do not reach it.  The caller should exit
+/// once this returns.
+pub fn spawn_background() -> Result<u32, ProtocolError> {
+    let exe = std::env::current_exe().map_err(|e| {
+        ProtocolError::InternalError(format!("cannot locate current executable: {}", e))
+    })?;
+    let mut cmd = Command::new(exe);
+    cmd.args(foreground_args(std::env::args_os().skip(1)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    let child = cmd
+        .spawn()
+        .map_err(|e| ProtocolError::InternalError(format!("failed to daemonize: {}", e)))?;
+    Ok(child.id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pid_file_written_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run").join("burrow.pid");
+        {
+            let pid = PidFile::create(&path).unwrap();
+            assert_eq!(pid.path(), path);
+            assert_eq!(read_pid(&path), Some(std::process::id()));
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn stale_pid_file_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("burrow.pid");
+        // Our own PID counts as stale: it cannot be another instance.
+        std::fs::write(&path, format!("{}\n", std::process::id())).unwrap();
+        let _pid = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+    }
+
+    #[test]
+    fn daemonize_flag_rewritten() {
+        let args = ["serve", "--daemonize", "--pid-file", "x.pid"].map(OsString::from);
+        assert_eq!(
+            foreground_args(args),
+            ["serve", "--foreground", "--pid-file", "x.pid"].map(OsString::from)
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Handlers for verbs the burrow does not know.
+//!
+//! The [`Dispatcher`](super::router::Dispatcher) handles every verb of
+//! the protocol itself.  An embedder can teach a burrow further verbs
+//! by registering a [`FrameHandler`] for each in a [`HandlerRegistry`]
+//! (see [`crate::burrow::Burrow::register_handler`]); a frame with a
+//! registered verb is then passed to its handler instead of being
+//! refused with `400 BAD REQUEST`.  Built-in verbs cannot be replaced.
+//!
+//! A handler's response, if it has no `Lane` or `Txn` header, gets the
+//! request's, so it reaches the lane and transaction it answers.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::{Frame, Verb, VerbKind};
+use crate::security::permissions::Capability;
+
+use super::router::DispatchResult;
+
+/// Answers the frames of one verb.
+pub trait FrameHandler: Send + Sync {
+    /// Answer `frame`, sent by `peer_id`.
+    fn handle(&self, frame: &Frame, peer_id: &str) -> DispatchResult;
+
+    /// The capability a peer needs to send the verb; `None` (the
+    /// default) lets anyone, anonymous peers included.
+    fn capability(&self) -> Option<Capability> {
+        None
+    }
+}
+
+impl<F> FrameHandler for F
+where
+    F: Fn(&Frame, &str) -> DispatchResult + Send + Sync,
+{
+    fn handle(&self, frame: &Frame, peer_id: &str) -> DispatchResult {
+        self(frame, peer_id)
+    }
+}
+
+/// The handlers registered with a burrow, by verb.
+#[derive(Default)]
+pub struct HandlerRegistry {
+    handlers: Mutex<HashMap<String, Arc<dyn FrameHandler>>>,
+}
+
+impl std::fmt::Debug for HandlerRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HandlerRegistry")
+            .field("verbs", &self.verbs())
+            .finish()
+    }
+}
+
+impl HandlerRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle `verb` with `handler` from now on, replacing any handler
+    /// registered for it before.  Fails with `BadRequest` if `verb` is
+    /// not an upper-case token (letters, digits and `-`) or is one the
+    /// burrow handles itself.
+    pub fn register(
+        &self,
+        verb: &str,
+        handler: Arc<dyn FrameHandler>,
+    ) -> Result<(), ProtocolError> {
+        let token = verb.starts_with(|c: char| c.is_ascii_uppercase())
+            && verb
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-');
+        if !token {
+            return Err(ProtocolError::BadRequest(format!(
+                "invalid verb {:?}",
+                verb
+            )));
+        }
+        if !matches!(VerbKind::try_from(verb), Ok(VerbKind::Verb(Verb::Other(_)))) {
+            return Err(ProtocolError::BadRequest(format!(
+                "{} is a built-in verb",
+                verb
+            )));
+        }
+        self.handlers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(verb.to_string(), handler);
+        Ok(())
+    }
+
+    /// Stop handling `verb`.  Returns whether a handler was registered.
+    pub fn unregister(&self, verb: &str) -> bool {
+        self.handlers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(verb)
+            .is_some()
+    }
+
+    /// The handler registered for `verb`, if any.
+    pub fn get(&self, verb: &str) -> Option<Arc<dyn FrameHandler>> {
+        self.handlers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(verb)
+            .cloned()
+    }
+
+    /// The verbs with handlers, sorted.
+    pub fn verbs(&self) -> Vec<String> {
+        let mut verbs: Vec<String> = self
+            .handlers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect();
+        verbs.sort();
+        verbs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_new_verbs_can_be_registered() {
+        let registry = HandlerRegistry::new();
+        let echo = Arc::new(|frame: &Frame, _: &str| {
+            DispatchResult::single(Frame::new(format!("200 {}", frame.verb)))
+        });
+        registry.register("ECHO", echo.clone()).unwrap();
+        registry.register("X-ECHO-2", echo.clone()).unwrap();
+        for verb in ["FETCH", "HELLO", "FED-AUTH", "200", "echo", "", "A B"] {
+            assert!(
+                matches!(
+                    registry.register(verb, echo.clone()),
+                    Err(ProtocolError::BadRequest(_))
+                ),
+                "{verb:?}"
+            );
+        }
+        assert_eq!(registry.verbs(), ["ECHO", "X-ECHO-2"]);
+
+        let reply = registry
+            .get("ECHO")
+            .unwrap()
+            .handle(&Frame::new("ECHO"), "-");
+        assert_eq!(reply.response.args, ["ECHO"]);
+        assert!(registry.unregister("ECHO"));
+        assert!(registry.get("ECHO").is_none());
+    }
+}
@@ -4,6 +4,6 @@
 //! on the verb.  This is the "brain" of the burrow — it ties together
 //! authentication, content serving, event delivery, and flow control.
 
+pub mod handlers;
 pub mod idem_cache;
-pub mod rate_limiter;
 pub mod router;
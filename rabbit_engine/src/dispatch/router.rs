@@ -2,27 +2,64 @@
 //!
 //! The [`Dispatcher`] holds references to all subsystems (content
 //! store, event engine, authenticator state) and produces a response
-//! frame for every incoming frame.  Unknown verbs yield `400 BAD
-//! REQUEST`.
+//! frame for every incoming frame.  Verbs it does not know go to the
+//! handler registered for them, if any (see [`super::handlers`]), and
+//! otherwise yield `400 BAD REQUEST`.  A `FETCH` or `LIST` of a
+//! `rabbit://` address is served only if the address names this
+//! burrow (see [`crate::protocol::address`]); addresses naming others
+//! are relayed before they reach the dispatcher.
 
+use std::pin::Pin;
 use std::sync::Mutex;
 
+use futures_util::stream::{self, Stream, StreamExt};
+
 use crate::content::handler as content_handler;
+use crate::content::provider::ProviderRegistry;
 use crate::content::search::SearchIndex;
-use crate::content::store::{ContentEntry, ContentStore};
-use crate::events::continuity::ContinuityStore;
-use crate::events::engine::{EventEngine, QoS};
-use crate::events::handler as event_handler;
-use crate::protocol::error::ProtocolError;
-use crate::protocol::frame::Frame;
+use crate::content::store::{ContentEntry, ContentStore, MenuItem};
+use crate::error::RabbitError;
+use crate::events::continuity::TopicInfo;
+use crate::events::engine::{self as event_engine, Event, EventEngine, QoS};
+use crate::events::handler::{self as event_handler, Since};
+use crate::events::offsets::ConsumerOffsets;
+use crate::hooks::Hooks;
+use crate::events::quota::QuotaManager;
+use crate::events::retention::RetentionPolicies;
+use crate::events::segment::Retention;
+use crate::events::store::{append_in_place, EventStore};
+use crate::protocol::address::RabbitAddress;
+use crate::protocol::chunk;
+use crate::protocol::error::{ErrorFrame, ProtocolError};
+use crate::protocol::frame::{Frame, FrameBuilder, Verb, VerbKind};
+use crate::protocol::lane_manager::LaneManager;
+use crate::security::audit::{AuditKind, AuditLog, AuditQuery, AUDIT_TOPIC};
+use crate::security::auth::ReplayCache;
+use crate::security::delegation::DelegationToken;
+use crate::security::groups::GroupChange;
 use crate::security::permissions::{Capability, CapabilityManager};
-use crate::warren::discovery;
-use crate::warren::peers::PeerTable;
+use crate::security::rate_limiter::RateLimiter;
+use crate::security::trust::TrustCache;
+use crate::session::SessionManager;
+use crate::util::now_unix;
+use crate::warren::discovery::{
+    self, ANCHORS_SELECTOR, TOPOLOGY_SELECTOR, TRUSTED_SELECTOR, WARREN_SELECTOR,
+};
+use crate::warren::offer::{self, PeerOffer, PendingOffers};
+use crate::warren::peers::{PeerInfo, PeerTable};
+use crate::warren::relay::RelayTable;
+use crate::warren::routing::{RoutingTable, DEFAULT_HOP_COUNT};
+
+use super::handlers::HandlerRegistry;
+
+/// The selector listing the burrow's event topics.
+pub const TOPICS_SELECTOR: &str = "/q";
 
 /// Result of dispatching a frame.
 ///
 /// Most verbs produce a single response.  `SUBSCRIBE` may produce an
-/// initial response *and* replay frames (in `extras`).  `PUBLISH`
+/// initial response *and* replay frames (in `extras`, or in `replay`
+/// when they have to be read from the continuity log).  `PUBLISH`
 /// produces a response for the publisher and targeted broadcast
 /// frames (in `broadcast`) that should be fanned out to subscriber
 /// tunnels via the session manager.
@@ -36,6 +73,23 @@ pub struct DispatchResult {
     /// Targeted broadcast frames: `(peer_id, frame)` pairs to be
     /// fanned out to other tunnels via the session manager.
     pub broadcast: Vec<(String, Frame)>,
+    /// Frames to send to the same tunnel after `extras`, as it has
+    /// room for them.
+    pub replay: Option<Replay>,
+}
+
+/// Frames replayed on a lane, read as they are sent.
+pub struct Replay {
+    /// The lane they are sent on.
+    pub lane: u16,
+    /// The frames, in order.
+    pub frames: Pin<Box<dyn Stream<Item = Frame> + Send>>,
+}
+
+impl std::fmt::Debug for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Replay").field("lane", &self.lane).finish()
+    }
 }
 
 impl DispatchResult {
@@ -45,6 +99,7 @@ impl DispatchResult {
             response,
             extras: Vec::new(),
             broadcast: Vec::new(),
+            replay: None,
         }
     }
 
@@ -54,6 +109,17 @@ impl DispatchResult {
             response,
             extras,
             broadcast: Vec::new(),
+            replay: None,
+        }
+    }
+
+    /// Create a result with a response and frames replayed after it.
+    pub fn with_replay(response: Frame, replay: Replay) -> Self {
+        Self {
+            response,
+            extras: Vec::new(),
+            broadcast: Vec::new(),
+            replay: Some(replay),
         }
     }
 
@@ -63,6 +129,7 @@ impl DispatchResult {
             response,
             extras: Vec::new(),
             broadcast,
+            replay: None,
         }
     }
 }
@@ -81,10 +148,58 @@ pub struct Dispatcher<'a> {
     peers: Option<&'a PeerTable>,
     /// Capability manager for permission enforcement (optional).
     capabilities: Option<&'a Mutex<CapabilityManager>>,
-    /// Continuity store for event persistence (optional).
-    continuity: Option<&'a ContinuityStore>,
+    /// Event store for persistence (optional).
+    continuity: Option<&'a dyn EventStore>,
     /// Search index for SEARCH queries (optional).
     search_index: Option<&'a SearchIndex>,
+    /// Storage quotas for PUBLISH (optional).
+    quotas: Option<&'a QuotaManager>,
+    /// Per-topic retention for TOPIC-CONFIG (optional).
+    retention: Option<&'a RetentionPolicies>,
+    /// Subscribers' acknowledged offsets, where a SUBSCRIBE without
+    /// `Since` resumes (optional).
+    offsets: Option<&'a ConsumerOffsets>,
+    /// Callbacks told of each event published (optional).
+    hooks: Option<&'a Hooks>,
+    /// The tunnel's lanes, for dropping retransmitted frames (optional).
+    lanes: Option<&'a LaneManager>,
+    /// Nonces of accepted DELEGATE and GROUP frames (optional).
+    replay: Option<&'a ReplayCache>,
+    /// Audit log for `/t/audit` and delegated grants (optional).
+    audit: Option<&'a AuditLog>,
+    /// Rate limiter for FETCH bandwidth (optional).
+    rate_limiter: Option<&'a RateLimiter>,
+    /// Handlers for verbs the dispatcher does not know (optional).
+    handlers: Option<&'a HandlerRegistry>,
+    /// Trust cache for `/anchors` and `/trusted` (optional).
+    trust: Option<&'a Mutex<TrustCache>>,
+    /// Providers for selectors the content store lacks (optional).
+    providers: Option<&'a ProviderRegistry>,
+    /// Routing table learning from ROUTE-ADVERT, and the lifetime of a
+    /// learned route in seconds (optional).
+    routing: Option<(&'a RoutingTable, u64)>,
+    /// Relays heard of through the `Relay` header of ROUTE-ADVERT
+    /// (optional).
+    relays: Option<&'a RelayTable>,
+    /// OFFERs awaiting a manifest that vouches for their signer; when
+    /// attached, unsigned OFFERs are refused (optional).
+    pending_offers: Option<&'a PendingOffers>,
+    /// This burrow's ID, for serving `rabbit://` addresses naming it
+    /// (optional).
+    local_id: Option<String>,
+    /// Sessions with directly connected peers, for `/warren/topology`
+    /// (optional).
+    sessions: Option<&'a SessionManager>,
+}
+
+/// The `403 FORBIDDEN` reply to a request the peer lacks `capability`
+/// for.
+fn denied(request: &Frame, peer_id: &str, capability: Capability) -> DispatchResult {
+    let err = RabbitError::Capability {
+        peer_id: peer_id.to_string(),
+        capability,
+    };
+    DispatchResult::single(err.error_frame().in_reply_to(request).build())
 }
 
 impl<'a> Dispatcher<'a> {
@@ -97,6 +212,22 @@ impl<'a> Dispatcher<'a> {
             capabilities: None,
             continuity: None,
             search_index: None,
+            quotas: None,
+            retention: None,
+            offsets: None,
+            hooks: None,
+            lanes: None,
+            replay: None,
+            audit: None,
+            rate_limiter: None,
+            handlers: None,
+            trust: None,
+            providers: None,
+            routing: None,
+            relays: None,
+            pending_offers: None,
+            local_id: None,
+            sessions: None,
         }
     }
 
@@ -112,8 +243,9 @@ impl<'a> Dispatcher<'a> {
         self
     }
 
-    /// Attach a continuity store for event persistence.
-    pub fn with_continuity(mut self, store: &'a ContinuityStore) -> Self {
+    /// Attach an event store for persistence, e.g. a
+    /// [`ContinuityStore`](crate::events::continuity::ContinuityStore).
+    pub fn with_continuity(mut self, store: &'a dyn EventStore) -> Self {
         self.continuity = Some(store);
         self
     }
@@ -124,6 +256,165 @@ impl<'a> Dispatcher<'a> {
         self
     }
 
+    /// Attach a quota manager for PUBLISH storage limits.
+    pub fn with_quotas(mut self, quotas: &'a QuotaManager) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Attach subscribers' offsets, so a SUBSCRIBE without `Since`
+    /// replays what the subscriber has not acknowledged.
+    pub fn with_offsets(mut self, offsets: &'a ConsumerOffsets) -> Self {
+        self.offsets = Some(offsets);
+        self
+    }
+
+    /// Attach the burrow's hooks, run for each event published.
+    pub fn with_hooks(mut self, hooks: &'a Hooks) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Attach per-topic retention, read and set by TOPIC-CONFIG.
+    pub fn with_retention(mut self, retention: &'a RetentionPolicies) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Attach the tunnel's lanes, so EVENT and PUBLISH frames that
+    /// repeat a `Seq` already seen are acknowledged but not processed
+    /// again.
+    pub fn with_lanes(mut self, lanes: &'a LaneManager) -> Self {
+        self.lanes = Some(lanes);
+        self
+    }
+
+    /// Attach a replay cache, so DELEGATE and GROUP frames must carry
+    /// a fresh `Nonce` and `Timestamp`.
+    pub fn with_replay_cache(mut self, cache: &'a ReplayCache) -> Self {
+        self.replay = Some(cache);
+        self
+    }
+
+    /// Attach an audit log, which records delegated grants and is read
+    /// with `LIST /t/audit` by peers holding `ManageBurrows`.
+    pub fn with_audit(mut self, audit: &'a AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Attach a rate limiter, which holds FETCH responses to each
+    /// peer's bandwidth allowance.
+    pub fn with_rate_limiter(mut self, limiter: &'a RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Attach handlers for verbs the dispatcher does not know.
+    pub fn with_handlers(mut self, handlers: &'a HandlerRegistry) -> Self {
+        self.handlers = Some(handlers);
+        self
+    }
+
+    /// Attach a trust cache, whose anchors and peers are listed at
+    /// `/anchors` and `/trusted`.
+    pub fn with_trust(mut self, trust: &'a Mutex<TrustCache>) -> Self {
+        self.trust = Some(trust);
+        self
+    }
+
+    /// Attach content providers, which LIST and FETCH consult for
+    /// selectors the content store lacks.
+    pub fn with_providers(mut self, providers: &'a ProviderRegistry) -> Self {
+        self.providers = Some(providers);
+        self
+    }
+
+    /// Attach a routing table, which learns the routes peers advertise
+    /// with ROUTE-ADVERT, each for `ttl_secs`.
+    pub fn with_routing(mut self, routing: &'a RoutingTable, ttl_secs: u64) -> Self {
+        self.routing = Some((routing, ttl_secs));
+        self
+    }
+
+    /// Attach a relay table, which notes the peers whose route adverts
+    /// offer to relay, for as long as a learned route lives.
+    pub fn with_relays(mut self, relays: &'a RelayTable) -> Self {
+        self.relays = Some(relays);
+        self
+    }
+
+    /// Attach a set of pending offers, so that OFFERs must be signed
+    /// and their peers wait there until the trust cache holds a
+    /// manifest vouching for the signer; see [`crate::warren::offer`].
+    pub fn with_pending_offers(mut self, pending: &'a PendingOffers) -> Self {
+        self.pending_offers = Some(pending);
+        self
+    }
+
+    /// Serve `FETCH` and `LIST` of `rabbit://` addresses naming
+    /// `burrow_id`, this burrow.
+    pub fn with_local_id(mut self, burrow_id: impl Into<String>) -> Self {
+        self.local_id = Some(burrow_id.into());
+        self
+    }
+
+    /// Serve `/warren/topology` from the routing table, the peer table
+    /// and `sessions`, which must all be attached, with this burrow's
+    /// ID.
+    pub fn with_sessions(mut self, sessions: &'a SessionManager) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// `frame` with the `rabbit://` address it gives in place of a
+    /// selector replaced by the selector, or `None` if it gives none.
+    /// Fails with `BadRequest` for a malformed address and `NoRoute`
+    /// for one naming another burrow.
+    fn localize(&self, frame: &Frame) -> Result<Option<Frame>, ProtocolError> {
+        let Some(address) = RabbitAddress::of_request(frame).transpose()? else {
+            return Ok(None);
+        };
+        if self.local_id.as_deref() != Some(address.warren.as_str()) {
+            return Err(ProtocolError::NoRoute(format!(
+                "{} is not served here",
+                address
+            )));
+        }
+        let mut local = frame.clone();
+        local.args[0] = address.selector;
+        Ok(Some(local))
+    }
+
+    /// The reply to a retransmitted EVENT or PUBLISH, or `None` if the
+    /// frame is new (or carries no `Seq`) and should be processed.
+    ///
+    /// The reply is `200 OK` with `ACK: <seq>` and `Duplicate: true`,
+    /// so the sender stops retransmitting.
+    pub async fn duplicate_reply(&self, frame: &Frame) -> Option<Frame> {
+        let lanes = self.lanes?;
+        if !matches!(
+            frame.verb_kind(),
+            VerbKind::Verb(Verb::Event | Verb::Publish)
+        ) {
+            return None;
+        }
+        let seq: u64 = frame.header("Seq")?.parse().ok()?;
+        let lane: u16 = frame
+            .header("Lane")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(0);
+        if lanes.observe_inbound(lane, seq).await {
+            return None;
+        }
+        tracing::debug!(lane, seq, verb = %frame.verb, "duplicate frame acknowledged");
+        reply_builder("200 OK", frame)
+            .header("ACK", seq.to_string())
+            .header("Duplicate", "true")
+            .build()
+            .ok()
+    }
+
     /// Check whether a peer has a specific capability.
     ///
     /// If no capability manager is attached, all operations are
@@ -138,93 +429,280 @@ impl<'a> Dispatcher<'a> {
         }
     }
 
+    /// The peers an OFFER adds now, or `None` if it is held until a
+    /// manifest vouches for its signer.  A signed OFFER must verify
+    /// under its `Warren-ID`.
+    fn take_offer(&self, frame: &Frame) -> Result<Option<Vec<PeerInfo>>, ProtocolError> {
+        if frame.header("Signature").is_none() {
+            if self.pending_offers.is_some() {
+                return Err(ProtocolError::Forbidden("OFFER must be signed".into()));
+            }
+            return Ok(Some(offer::parse_peers(
+                frame.body.as_deref().unwrap_or(""),
+            )));
+        }
+        let offer = PeerOffer::from_frame(frame)?;
+        offer.verify()?;
+        let Some(pending) = self.pending_offers else {
+            return Ok(Some(offer.peers));
+        };
+        let vouched = self.trust.is_some_and(|trust| {
+            trust
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .is_vouched_for(&offer.warren_id)
+        });
+        if vouched {
+            return Ok(Some(offer.peers));
+        }
+        pending.hold(offer)?;
+        Ok(None)
+    }
+
+    /// Check whether a peer has a capability on a selector or topic,
+    /// through a global or a scoped grant.
+    fn check_cap_on(&self, peer_id: &str, cap: Capability, selector: &str) -> bool {
+        match &self.capabilities {
+            Some(mgr) => mgr
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .allowed(peer_id, cap, selector),
+            None => true,
+        }
+    }
+
+    /// Check a capability for a request on a selector: a grant to the
+    /// peer covering it, or a `Delegation` header carrying a token that
+    /// allows it (see [`DelegationToken::require`]).
+    fn authorized(&self, frame: &Frame, peer_id: &str, cap: Capability) -> bool {
+        let selector = frame.args.first().map(|s| s.as_str()).unwrap_or("/");
+        if self.check_cap_on(peer_id, cap, selector) {
+            return true;
+        }
+        let (Some(mgr), Some(token)) = (self.capabilities, frame.header("Delegation")) else {
+            return false;
+        };
+        let mgr = mgr.lock().unwrap_or_else(|e| e.into_inner());
+        match DelegationToken::decode(token).and_then(|t| t.require(&mgr, peer_id, cap, selector)) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::debug!(peer_id, err = %e, "delegation refused");
+                false
+            }
+        }
+    }
+
     /// Dispatch a single incoming frame and return the response(s).
     ///
     /// The `peer_id` identifies the sender (used for subscriber
     /// tracking in the event engine).
     pub async fn dispatch(&self, frame: &Frame, peer_id: &str) -> DispatchResult {
-        match frame.verb.as_str() {
+        let localized;
+        let frame = match self.localize(frame) {
+            Ok(Some(local)) => {
+                localized = local;
+                &localized
+            }
+            Ok(None) => frame,
+            Err(e) => {
+                return DispatchResult::single(ErrorFrame::from(&e).in_reply_to(frame).build())
+            }
+        };
+        if let Some(reply) = self.duplicate_reply(frame).await {
+            return DispatchResult::single(reply);
+        }
+        match frame.verb_kind() {
             // ── Content ────────────────────────────────────────
-            "LIST" => {
+            VerbKind::Verb(Verb::List) => {
                 let required = Capability::List;
-                if !self.check_cap(peer_id, required) {
-                    return DispatchResult::single(
-                        ProtocolError::Forbidden(format!("{peer_id} lacks {required:?}")).into(),
-                    );
+                if !self.authorized(frame, peer_id, required) {
+                    return denied(frame, peer_id, required);
                 }
                 let selector = frame.args.first().map(|s| s.as_str()).unwrap_or("/");
-                if selector == "/warren" {
+                if selector == WARREN_SELECTOR {
                     if let Some(peers) = self.peers {
                         let response = self.warren_response(peers, frame).await;
                         return DispatchResult::single(response);
                     }
                 }
-                let response = content_handler::handle_list(self.content, selector, frame);
+                if selector == TOPOLOGY_SELECTOR {
+                    if let Some(response) = self.topology_response(frame, false).await {
+                        return DispatchResult::single(response);
+                    }
+                }
+                if selector == ANCHORS_SELECTOR || selector == TRUSTED_SELECTOR {
+                    if let Some(trust) = self.trust {
+                        let trust = trust.lock().unwrap_or_else(|e| e.into_inner());
+                        let items = if selector == ANCHORS_SELECTOR {
+                            discovery::anchors_menu(&trust)
+                        } else {
+                            discovery::trusted_menu(&trust)
+                        };
+                        return DispatchResult::single(menu_response(items, frame));
+                    }
+                }
+                if selector == TOPICS_SELECTOR
+                    || (selector.starts_with("/q/") && event_engine::is_pattern(selector))
+                {
+                    return DispatchResult::single(self.topics_response(selector, frame));
+                }
+                if selector == AUDIT_TOPIC {
+                    if let Some(audit) = self.audit {
+                        if !self.check_cap(peer_id, Capability::ManageBurrows) {
+                            return denied(frame, peer_id, Capability::ManageBurrows);
+                        }
+                        return DispatchResult::single(self.audit_response(audit, frame));
+                    }
+                }
+                let response = match self.provided(selector) {
+                    Some(Ok(entry)) => content_handler::list_entry(&entry, frame),
+                    Some(Err(e)) => ErrorFrame::from(&e).in_reply_to(frame).build(),
+                    None => content_handler::handle_list(self.content, selector, frame),
+                };
                 DispatchResult::single(response)
             }
-            "FETCH" => {
+            VerbKind::Verb(Verb::Fetch) => {
                 let required = Capability::Fetch;
-                if !self.check_cap(peer_id, required) {
-                    return DispatchResult::single(
-                        ProtocolError::Forbidden(format!("{peer_id} lacks {required:?}")).into(),
-                    );
+                if !self.authorized(frame, peer_id, required) {
+                    return denied(frame, peer_id, required);
                 }
                 let selector = frame.args.first().map(|s| s.as_str()).unwrap_or("/");
-                if selector == "/warren" {
+                if selector == WARREN_SELECTOR {
                     if let Some(peers) = self.peers {
                         let response = self.warren_response(peers, frame).await;
                         return DispatchResult::single(response);
                     }
                 }
-                let response = content_handler::handle_fetch(self.content, selector, frame);
-                DispatchResult::single(response)
+                if selector == TOPOLOGY_SELECTOR {
+                    if let Some(response) = self.topology_response(frame, true).await {
+                        return DispatchResult::single(response);
+                    }
+                }
+                if selector == "/quota" {
+                    if let Some(quotas) = self.quotas {
+                        return DispatchResult::single(self.quota_response(quotas, frame));
+                    }
+                }
+                if let Some(limiter) = self.rate_limiter {
+                    if let Err(e) = limiter.check_fetch(peer_id) {
+                        return DispatchResult::single(
+                            ErrorFrame::from(&e).in_reply_to(frame).build(),
+                        );
+                    }
+                }
+                let response = match self.provided(selector) {
+                    Some(Ok(entry)) => content_handler::fetch_entry(&entry, frame),
+                    Some(Err(e)) => ErrorFrame::from(&e).in_reply_to(frame).build(),
+                    None => content_handler::handle_fetch(self.content, selector, frame),
+                };
+                if let Some(limiter) = self.rate_limiter {
+                    let bytes = response.body.as_ref().map_or(0, |b| b.len());
+                    limiter.charge_fetch(peer_id, bytes as u64);
+                }
+                match chunk::requested_chunk_size(frame) {
+                    Some(size) => {
+                        let mut frames = chunk::chunk_frames(&response, size).into_iter();
+                        let head = frames.next().unwrap_or(response);
+                        DispatchResult::with_extras(head, frames.collect())
+                    }
+                    None => DispatchResult::single(response),
+                }
             }
 
             // ── Events ─────────────────────────────────────────
-            "SUBSCRIBE" => {
-                let required = Capability::Subscribe;
-                if !self.check_cap(peer_id, required) {
-                    return DispatchResult::single(
-                        ProtocolError::Forbidden(format!("{peer_id} lacks {required:?}")).into(),
-                    );
-                }
+            VerbKind::Verb(Verb::Subscribe) => {
+                self.subscribe(frame, peer_id, "201 SUBSCRIBED", None)
+            }
+            VerbKind::Verb(Verb::Replicate) => {
+                // A follower's REPLICATE is a SUBSCRIBE that replays
+                // the whole log unless it says where to start.
                 let topic = frame.args.first().map(|s| s.as_str()).unwrap_or("");
-                let since_seq = frame.header("Since").and_then(|s| s.parse::<u64>().ok());
-                let lane = frame.header("Lane").unwrap_or("0").to_string();
-                let txn = frame.header("Txn").unwrap_or("").to_string();
-                let qos = frame
-                    .header("QoS")
-                    .map(QoS::from_header)
-                    .unwrap_or(QoS::Event);
-                let result = self
-                    .events
-                    .subscribe_with_qos(topic, peer_id, &lane, since_seq, qos);
-                let mut response = Frame::new("201 SUBSCRIBED");
-                if !lane.is_empty() {
-                    response.set_header("Lane", &lane);
+                if event_engine::is_pattern(topic) {
+                    let e = ProtocolError::BadRequest(format!("cannot replicate {topic}"));
+                    return DispatchResult::single(ErrorFrame::from(&e).in_reply_to(frame).build());
                 }
-                if !txn.is_empty() {
-                    response.set_header("Txn", &txn);
+                self.subscribe(frame, peer_id, "201 REPLICATING", Some(0))
+            }
+            VerbKind::Verb(Verb::TopicConfig) => self.topic_config(frame, peer_id),
+            VerbKind::Verb(Verb::Unsubscribe) => {
+                let required = Capability::Subscribe;
+                if !self.authorized(frame, peer_id, required) {
+                    return denied(frame, peer_id, required);
                 }
-                DispatchResult::with_extras(response, result)
+                let topic = frame.args.first().map(|s| s.as_str()).unwrap_or("");
+                // Unsubscribing from a topic not followed is not an
+                // error: the peer's intent holds either way.
+                event_handler::handle_unsubscribe(self.events, topic, peer_id);
+                let response = reply_builder("204 DONE", frame)
+                    .build()
+                    .unwrap_or_else(Frame::from);
+                DispatchResult::single(response)
             }
-            "PUBLISH" => {
+            VerbKind::Verb(Verb::Publish) => {
                 let required = Capability::Publish;
-                if !self.check_cap(peer_id, required) {
-                    return DispatchResult::single(
-                        ProtocolError::Forbidden(format!("{peer_id} lacks {required:?}")).into(),
-                    );
+                if !self.authorized(frame, peer_id, required) {
+                    return denied(frame, peer_id, required);
                 }
                 let topic = frame.args.first().map(|s| s.as_str()).unwrap_or("");
+                if event_engine::is_pattern(topic) {
+                    let e = ProtocolError::BadRequest(format!("cannot publish to {topic}"));
+                    return DispatchResult::single(ErrorFrame::from(&e).in_reply_to(frame).build());
+                }
                 let body = frame.body.as_deref().unwrap_or("");
                 let lane = frame.header("Lane").unwrap_or("0").to_string();
                 let txn = frame.header("Txn").unwrap_or("").to_string();
-                let (broadcast, event) = event_handler::handle_publish(self.events, topic, body);
 
-                // Persist to continuity store if available.
-                if let Some(cont) = self.continuity {
-                    if let Err(e) = cont.append(topic, &event) {
-                        tracing::warn!(topic, error = %e, "continuity append failed");
+                // Charge storage quotas.  Operators holding
+                // ManageWarren are exempt.
+                let mut warnings = Vec::new();
+                let mut charged = false;
+                if let Some(quotas) = self.quotas {
+                    let exempt = self.capabilities.is_some()
+                        && self.check_cap(peer_id, Capability::ManageWarren);
+                    if !exempt {
+                        match quotas.charge(peer_id, topic, body.len() as u64) {
+                            Ok(w) => {
+                                warnings = w;
+                                charged = true;
+                            }
+                            Err(e) => {
+                                let mut frame: Frame = e.into();
+                                frame.set_header("Lane", &lane);
+                                if !txn.is_empty() {
+                                    frame.set_header("Txn", &txn);
+                                }
+                                return DispatchResult::single(frame);
+                            }
+                        }
+                    }
+                }
+
+                let (mut broadcast, event) =
+                    event_handler::handle_publish(self.events, topic, body);
+                self.persist(topic, lane.parse().unwrap_or(0), &event);
+                if let Some(hooks) = self.hooks {
+                    hooks.event_published(topic, event.seq, peer_id);
+                }
+
+                // Soft-limit warnings go to the operator topic.
+                if let Some(quotas) = self.quotas {
+                    if charged {
+                        quotas.record_publisher(topic, event.seq, peer_id, body.len() as u64);
+                    }
+                    for warning in &warnings {
+                        tracing::warn!(
+                            scope = %warning.scope,
+                            resource = warning.resource,
+                            "quota soft limit reached"
+                        );
+                        let op_topic = quotas.operator_topic();
+                        let (op_broadcast, op_event) = event_handler::handle_publish(
+                            self.events,
+                            op_topic,
+                            &warning.to_body(),
+                        );
+                        self.persist(op_topic, 0, &op_event);
+                        broadcast.extend(op_broadcast);
                     }
                 }
 
@@ -239,7 +717,7 @@ impl<'a> Dispatcher<'a> {
             }
 
             // ── Keepalive ──────────────────────────────────────
-            "PING" => {
+            VerbKind::Verb(Verb::Ping) => {
                 let mut pong = Frame::new("200 PONG");
                 if let Some(lane) = frame.header("Lane") {
                     pong.set_header("Lane", lane);
@@ -248,7 +726,7 @@ impl<'a> Dispatcher<'a> {
             }
 
             // ── Flow control ───────────────────────────────────
-            "ACK" | "CREDIT" => {
+            VerbKind::Verb(Verb::Ack) | VerbKind::Verb(Verb::Credit) => {
                 // ACK and CREDIT are handled at the lane-manager
                 // level, not here.  Return a no-op acknowledgement
                 // so the caller knows dispatch succeeded.
@@ -260,7 +738,7 @@ impl<'a> Dispatcher<'a> {
             }
 
             // ── Metadata ────────────────────────────────────────
-            "DESCRIBE" => {
+            VerbKind::Verb(Verb::Describe) => {
                 let selector = frame.args.first().map(|s| s.as_str()).unwrap_or("/");
                 let response =
                     content_handler::handle_describe(self.content, self.events, selector, frame);
@@ -268,7 +746,7 @@ impl<'a> Dispatcher<'a> {
             }
 
             // ── Search ─────────────────────────────────────────
-            "SEARCH" => {
+            VerbKind::Verb(Verb::Search) => {
                 let selector = frame.args.first().map(|s| s.as_str()).unwrap_or("/");
                 match &self.search_index {
                     Some(index) => {
@@ -289,14 +767,18 @@ impl<'a> Dispatcher<'a> {
             }
 
             // ── Delegation ──────────────────────────────────────
-            "DELEGATE" => {
+            VerbKind::Verb(Verb::Delegate) => {
                 // DELEGATE <capability> <target_burrow_id>
-                // Requires ManageBurrows capability.
+                // Requires ManageBurrows or ManageWarren, and the
+                // delegator must hold the capability it hands on.
                 let required = Capability::ManageBurrows;
-                if !self.check_cap(peer_id, required) {
-                    return DispatchResult::single(
-                        ProtocolError::Forbidden(format!("{peer_id} lacks {required:?}")).into(),
-                    );
+                if !self.check_cap(peer_id, required)
+                    && !self.check_cap(peer_id, Capability::ManageWarren)
+                {
+                    return denied(frame, peer_id, required);
+                }
+                if let Some(Err(e)) = self.replay.map(|cache| cache.check(frame)) {
+                    return DispatchResult::single(e.into());
                 }
 
                 let cap_label = match frame.args.first() {
@@ -328,6 +810,16 @@ impl<'a> Dispatcher<'a> {
                         );
                     }
                 };
+                // An optional `Scope` limits the grant to a selector
+                // pattern, which the delegator's own grants must cover.
+                let scope = frame.header("Scope");
+                let held = match scope {
+                    Some(scope) => self.check_cap_on(peer_id, cap, scope),
+                    None => self.check_cap(peer_id, cap),
+                };
+                if !held {
+                    return denied(frame, peer_id, cap);
+                }
                 let ttl: u64 = frame
                     .header("TTL")
                     .and_then(|s| s.parse().ok())
@@ -335,15 +827,30 @@ impl<'a> Dispatcher<'a> {
 
                 // Grant the capability to the target.
                 if let Some(mgr) = self.capabilities {
-                    mgr.lock()
-                        .unwrap_or_else(|e| e.into_inner())
-                        .grant(&target, cap, ttl);
+                    let mut mgr = mgr.lock().unwrap_or_else(|e| e.into_inner());
+                    match scope {
+                        Some(scope) => mgr.grant_scoped(&target, cap, scope, ttl),
+                        None => mgr.grant(&target, cap, ttl),
+                    }
+                }
+                if let Some(audit) = self.audit {
+                    let detail = format!(
+                        "{} on {} for {}s, delegated by {}",
+                        cap_label,
+                        scope.unwrap_or("*"),
+                        ttl,
+                        peer_id
+                    );
+                    audit.record(AuditKind::Grant, &target, &detail);
                 }
 
                 let mut response = Frame::new("200 OK");
                 response.set_header("Capability", cap_label);
                 response.set_header("Target", &target);
                 response.set_header("TTL", ttl.to_string());
+                if let Some(scope) = scope {
+                    response.set_header("Scope", scope);
+                }
                 if let Some(lane) = frame.header("Lane") {
                     response.set_header("Lane", lane);
                 }
@@ -356,45 +863,87 @@ impl<'a> Dispatcher<'a> {
                 let mut grant_frame =
                     Frame::with_args("DELEGATE-GRANT", vec![cap_label.to_string()]);
                 grant_frame.set_header("TTL", ttl.to_string());
+                if let Some(scope) = scope {
+                    grant_frame.set_header("Scope", scope);
+                }
                 grant_frame.set_header("Granted-By", peer_id);
 
                 let broadcast = vec![(target, grant_frame)];
                 DispatchResult::with_broadcast(response, broadcast)
             }
+            VerbKind::Verb(Verb::Group) => {
+                // GROUP <add|remove> <group> <member>, signed by a
+                // burrow holding ManageBurrows or ManageWarren, who
+                // need not be the sender.
+                let change = match GroupChange::from_frame(frame).and_then(|c| {
+                    c.verify()?;
+                    Ok(c)
+                }) {
+                    Ok(c) => c,
+                    Err(e) => return DispatchResult::single(e.into()),
+                };
+                let required = Capability::ManageBurrows;
+                if !self.check_cap(&change.signer, required)
+                    && !self.check_cap(&change.signer, Capability::ManageWarren)
+                {
+                    return denied(frame, &change.signer, required);
+                }
+                if let Some(Err(e)) = self.replay.map(|cache| cache.check(frame)) {
+                    return DispatchResult::single(e.into());
+                }
+
+                let changed = match self.capabilities {
+                    Some(mgr) => mgr
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .groups_mut()
+                        .apply(&change),
+                    None => false,
+                };
+                let mut response = Frame::new("200 OK");
+                response.set_header("Group", &change.group);
+                response.set_header("Member", &change.member);
+                response.set_header("Changed", changed.to_string());
+                if let Some(lane) = frame.header("Lane") {
+                    response.set_header("Lane", lane);
+                }
+                if let Some(txn) = frame.header("Txn") {
+                    response.set_header("Txn", txn);
+                }
+                DispatchResult::single(response)
+            }
 
             // ── Peer advertisement ─────────────────────────────
-            "OFFER" => {
+            VerbKind::Verb(Verb::Offer) => {
                 // OFFER body: tab-separated peer lines
                 //   id\taddress\tname
-                // Requires Federation capability.
+                // Requires Federation capability.  Signed offers may
+                // wait for a manifest; see crate::warren::offer.
                 let required = Capability::Federation;
                 if !self.check_cap(peer_id, required) {
-                    return DispatchResult::single(
-                        ProtocolError::Forbidden(format!("{peer_id} lacks {required:?}")).into(),
-                    );
+                    return denied(frame, peer_id, required);
                 }
 
-                let body = frame.body.as_deref().unwrap_or("");
+                let offered = match self.take_offer(frame) {
+                    Ok(offered) => offered,
+                    Err(e) => {
+                        return DispatchResult::single(
+                            ErrorFrame::from(&e).in_reply_to(frame).build(),
+                        )
+                    }
+                };
+                let mut response = Frame::new("200 OK");
                 let mut accepted = 0usize;
-                if let Some(peers) = self.peers {
-                    for line in body.lines() {
-                        let parts: Vec<&str> = line.split('\t').collect();
-                        if parts.len() >= 2 {
-                            let id = parts[0].to_string();
-                            let address = parts[1].to_string();
-                            let name = if parts.len() >= 3 {
-                                parts[2].to_string()
-                            } else {
-                                String::new()
-                            };
-                            let peer_info = crate::warren::peers::PeerInfo::new(id, address, name);
+                match (offered, self.peers) {
+                    (Some(offered), Some(peers)) => {
+                        for peer_info in offered {
                             peers.register(peer_info).await;
                             accepted += 1;
                         }
                     }
+                    (Some(_), None) => {}
+                    (None, _) => response.set_header("Pending", "true"),
                 }
-
-                let mut response = Frame::new("200 OK");
                 response.set_header("Accepted", accepted.to_string());
                 if let Some(lane) = frame.header("Lane") {
                     response.set_header("Lane", lane);
@@ -405,31 +954,374 @@ impl<'a> Dispatcher<'a> {
                 DispatchResult::single(response)
             }
 
-            // ── Unknown verb ───────────────────────────────────
+            // ── Route advertisement ────────────────────────────
+            VerbKind::Verb(Verb::RouteAdvert) => {
+                // ROUTE-ADVERT body: tab-separated route lines
+                //   burrow-id\thops
+                // each reachable through the sender, and a `Relay`
+                // header if the sender relays.  Requires Federation
+                // capability.
+                let required = Capability::Federation;
+                if !self.check_cap(peer_id, required) {
+                    return denied(frame, peer_id, required);
+                }
+
+                let body = frame.body.as_deref().unwrap_or("");
+                let mut accepted = 0usize;
+                if let Some((routing, ttl_secs)) = self.routing {
+                    let now = now_unix();
+                    for line in body.lines() {
+                        let Some((target, hops)) = line.split_once('\t') else {
+                            continue;
+                        };
+                        let Ok(hops) = hops.trim().parse::<u32>() else {
+                            continue;
+                        };
+                        let distance = hops.saturating_add(1);
+                        if target.is_empty() || target == peer_id || distance > DEFAULT_HOP_COUNT {
+                            continue;
+                        }
+                        routing
+                            .learn(target, peer_id, distance, now, ttl_secs)
+                            .await;
+                        accepted += 1;
+                    }
+                    if let Some(relays) = self.relays {
+                        match frame.header("Relay").and_then(|s| s.parse::<u32>().ok()) {
+                            Some(slots) if slots > 0 => relays.heard(peer_id, now, ttl_secs),
+                            _ => relays.forget(peer_id),
+                        }
+                    }
+                }
+
+                let response = reply_builder("200 OK", frame)
+                    .header("Accepted", accepted.to_string())
+                    .build()
+                    .unwrap_or_else(Frame::from);
+                DispatchResult::single(response)
+            }
+
+            // ── Registered and unknown verbs ───────────────────
             _ => {
-                let err = ProtocolError::BadRequest(format!("unknown verb: {}", frame.verb));
-                DispatchResult::single(err.into())
+                let Some(handler) = self.handlers.and_then(|h| h.get(&frame.verb)) else {
+                    let err = ProtocolError::BadRequest(format!("unknown verb: {}", frame.verb));
+                    return DispatchResult::single(err.into());
+                };
+                if let Some(required) = handler.capability() {
+                    if !self.authorized(frame, peer_id, required) {
+                        return denied(frame, peer_id, required);
+                    }
+                }
+                let mut result = handler.handle(frame, peer_id);
+                for name in ["Lane", "Txn"] {
+                    if let (Some(value), None) = (frame.header(name), result.response.header(name))
+                    {
+                        result.response.set_header(name, value);
+                    }
+                }
+                result
             }
         }
     }
 
-    /// Build a dynamic `200 MENU` response for `/warren` from the
-    /// peer table.
-    async fn warren_response(&self, peers: &PeerTable, request: &Frame) -> Frame {
-        let lane = request.header("Lane").unwrap_or("0");
-        let txn = request.header("Txn").unwrap_or("");
+    /// Build the `/quota` usage report (one TSV line per scope).
+    fn quota_response(&self, quotas: &QuotaManager, request: &Frame) -> Frame {
+        reply_builder("200 CONTENT", request)
+            .header("View", "text/plain")
+            .body_text(quotas.status_body())
+            .build()
+            .unwrap_or_else(Frame::from)
+    }
 
-        let items = discovery::warren_menu(peers).await;
+    /// Build a `200 MENU` response listing the event topics under
+    /// `/q`, or those matching a pattern, with what is logged of each.
+    /// Topics only held in memory are listed from the event engine.
+    fn topics_response(&self, selector: &str, request: &Frame) -> Frame {
+        let mut topics: Vec<TopicInfo> = match self.continuity.map(|store| store.list_topics()) {
+            Some(Ok(topics)) => topics,
+            Some(Err(e)) => return ErrorFrame::from(&e).in_reply_to(request).build(),
+            None => Vec::new(),
+        };
+        for topic in self.events.topics() {
+            if topics.iter().any(|t| t.topic == topic) {
+                continue;
+            }
+            let events = self.events.events(&topic);
+            topics.push(TopicInfo {
+                first_seq: events.first().map_or(0, |e| e.seq),
+                last_seq: events.last().map_or(0, |e| e.seq),
+                events: events.len(),
+                bytes: events.iter().map(|e| e.body.len() as u64).sum(),
+                last_write: 0,
+                topic,
+            });
+        }
+        topics.retain(|t| match selector {
+            TOPICS_SELECTOR => t.topic.starts_with("/q/"),
+            pattern => event_engine::topic_matches(pattern, &t.topic),
+        });
+        topics.sort_by(|a, b| a.topic.cmp(&b.topic));
+        if topics.is_empty() {
+            return menu_response(vec![MenuItem::info("No topics")], request);
+        }
+        let now = now_unix();
+        let items = topics
+            .into_iter()
+            .map(|t| {
+                let mut label = format!(
+                    "{} \u{2014} {} events, seq {}\u{2013}{}, {} bytes",
+                    t.topic, t.events, t.first_seq, t.last_seq, t.bytes
+                );
+                if t.last_write > 0 {
+                    label.push_str(&format!(
+                        ", written {}s ago",
+                        now.saturating_sub(t.last_write)
+                    ));
+                }
+                MenuItem::local('q', label, t.topic)
+            })
+            .collect();
+        menu_response(items, request)
+    }
+
+    /// Build a `200 MENU` response listing the audit records matched
+    /// by the request's headers, one info line each.
+    fn audit_response(&self, audit: &AuditLog, request: &Frame) -> Frame {
+        let query = match AuditQuery::from_frame(request) {
+            Ok(query) => query,
+            Err(e) => return Frame::from(e),
+        };
+        let items = audit
+            .query(&query)
+            .iter()
+            .map(|r| MenuItem::info(r.to_line()))
+            .collect();
         let entry = ContentEntry::Menu(items);
+        reply_builder("200 MENU", request)
+            .header("View", entry.view_type())
+            .body_text(entry.to_body())
+            .build()
+            .unwrap_or_else(Frame::from)
+    }
 
-        let mut response = Frame::new("200 MENU");
-        response.set_header("Lane", lane);
+    /// Subscribe the sender of a SUBSCRIBE or REPLICATE to its topic,
+    /// answering with `status`.  Without a `Since` header, replay
+    /// starts after `since_default`, if given, or else after the last
+    /// event the sender acknowledged, if it has.
+    fn subscribe(
+        &self,
+        frame: &Frame,
+        peer_id: &str,
+        status: &str,
+        since_default: Option<u64>,
+    ) -> DispatchResult {
+        let required = Capability::Subscribe;
+        if !self.authorized(frame, peer_id, required) {
+            return denied(frame, peer_id, required);
+        }
+        let topic = frame.args.first().map(|s| s.as_str()).unwrap_or("");
+        let since_seq = match frame.header("Since").map(Since::parse) {
+            None => since_default.or_else(|| self.offsets?.get(topic, peer_id)),
+            Some(Ok(Since::Seq(seq))) => Some(seq),
+            Some(Ok(Since::Time(secs))) => Some(self.last_seq_before(topic, secs)),
+            Some(Err(e)) => {
+                return DispatchResult::single(ErrorFrame::from(&e).in_reply_to(frame).build())
+            }
+        };
+        let lane = frame.header("Lane").unwrap_or("0").to_string();
+        let txn = frame.header("Txn").unwrap_or("").to_string();
+        let qos = frame
+            .header("QoS")
+            .map(QoS::from_header)
+            .unwrap_or(QoS::Event);
+        let result = self
+            .events
+            .subscribe_with_qos(topic, peer_id, &lane, since_seq, qos);
+        let mut response = Frame::new(status);
+        if !lane.is_empty() {
+            response.set_header("Lane", &lane);
+        }
         if !txn.is_empty() {
-            response.set_header("Txn", txn);
+            response.set_header("Txn", &txn);
+        }
+        // Events the engine has let go of are read from the
+        // log, ahead of those it still holds.
+        let spilled = self.events.spilled_through(topic);
+        match (self.continuity, since_seq) {
+            (Some(cont), Some(since)) if spilled > since => {
+                let frames = cont
+                    .replay_stream(topic, since, &lane, None, Some(spilled))
+                    .chain(stream::iter(result));
+                let replay = Replay {
+                    lane: lane.parse().unwrap_or(0),
+                    frames: Box::pin(frames),
+                };
+                DispatchResult::with_replay(response, replay)
+            }
+            _ => DispatchResult::with_extras(response, result),
+        }
+    }
+
+    /// The sequence number after which to replay the events of `topic`
+    /// logged at or after `secs`.  Without a continuity store, event
+    /// times are unknown and every retained event is replayed.
+    fn last_seq_before(&self, topic: &str, secs: u64) -> u64 {
+        let Some(cont) = self.continuity else {
+            return 0;
+        };
+        cont.last_seq_before(topic, secs).unwrap_or_else(|e| {
+            tracing::warn!(topic, error = %e, "continuity lookup failed; replaying all");
+            0
+        })
+    }
+
+    /// Persist an event published on `lane` to the continuity store,
+    /// if one is attached.
+    fn persist(&self, topic: &str, lane: u16, event: &Event) {
+        if let Some(cont) = self.continuity {
+            let appended = append_in_place(|| cont.append_on_lane(topic, lane, event));
+            if let Err(e) = appended {
+                tracing::warn!(topic, error = %e, "continuity append failed");
+            }
         }
-        response.set_header("View", entry.view_type());
-        response.set_body(entry.to_body());
-        response
+    }
+
+    /// Answer a TOPIC-CONFIG with the topic's retention limits, having
+    /// first set them if it asks to.  `Retention: forever` drops every
+    /// limit and `Retention: default` any set before; otherwise the
+    /// `Max-Events`, `Max-Age-Secs` and `Max-Bytes` given, with the
+    /// rest unlimited, replace them.  Reading needs `Subscribe`, and
+    /// setting `ManageWarren`.
+    fn topic_config(&self, frame: &Frame, peer_id: &str) -> DispatchResult {
+        let reply_error = |e: ProtocolError| {
+            DispatchResult::single(ErrorFrame::from(&e).in_reply_to(frame).build())
+        };
+        let setting = frame.header("Retention").is_some()
+            || RETENTION_LIMITS
+                .iter()
+                .any(|name| frame.header(name).is_some());
+        let required = match setting {
+            true => Capability::ManageWarren,
+            false => Capability::Subscribe,
+        };
+        if !self.authorized(frame, peer_id, required) {
+            return denied(frame, peer_id, required);
+        }
+        let topic = frame.args.first().map(|s| s.as_str()).unwrap_or("");
+        if !topic.starts_with('/') || event_engine::is_pattern(topic) {
+            return reply_error(ProtocolError::BadRequest(format!(
+                "cannot configure topic {topic:?}"
+            )));
+        }
+        let Some(policies) = self.retention else {
+            return reply_error(ProtocolError::BadRequest(
+                "topic retention is not configurable here".into(),
+            ));
+        };
+        let saved = match frame.header("Retention") {
+            Some("default") => policies.reset_topic(topic),
+            Some("forever") => policies.set_topic(topic, Retention::default()),
+            Some(other) => Err(ProtocolError::BadRequest(format!(
+                "Retention must be forever or default, not {other}"
+            ))),
+            None if setting => {
+                requested_retention(frame).and_then(|r| policies.set_topic(topic, r))
+            }
+            None => Ok(()),
+        };
+        if let Err(e) = saved {
+            return reply_error(e);
+        }
+        if setting {
+            tracing::info!(peer_id, topic, "topic retention changed");
+        }
+
+        let (retention, source) = policies.get(topic);
+        let response = reply_builder("200 TOPIC-CONFIG", frame)
+            .header("Topic", topic)
+            .header("Max-Events", retention.max_events.to_string())
+            .header("Max-Age-Secs", retention.max_age_secs.to_string())
+            .header("Max-Bytes", retention.max_bytes.to_string())
+            .header("Retention", source.label())
+            .build()
+            .unwrap_or_else(Frame::from);
+        DispatchResult::single(response)
+    }
+
+    /// The content a provider serves at `selector`, or `None` if the
+    /// content store has the selector or no provider's prefix matches.
+    fn provided(&self, selector: &str) -> Option<Result<ContentEntry, ProtocolError>> {
+        if self.content.get(selector).is_some() {
+            return None;
+        }
+        let provider = self.providers?.resolve(selector)?;
+        Some(provider.fetch(selector))
+    }
+
+    /// The reply to a `LIST` (a menu) or `FETCH` (JSON) of
+    /// `/warren/topology`, or `None` if the subsystems it is built
+    /// from are not attached.
+    async fn topology_response(&self, request: &Frame, json: bool) -> Option<Frame> {
+        let ((routing, _), peers, sessions) = (self.routing?, self.peers?, self.sessions?);
+        let local = self.local_id.as_deref()?;
+        let topology = routing
+            .topology(local, &peers.list().await, &sessions.peer_ids())
+            .await;
+        if !json {
+            return Some(menu_response(discovery::topology_menu(&topology), request));
+        }
+        let mut response = reply_builder("200 CONTENT", request)
+            .build()
+            .unwrap_or_else(Frame::from);
+        if let Err(e) = response.set_json_body(&topology) {
+            return Some(ErrorFrame::from(&e).in_reply_to(request).build());
+        }
+        Some(response)
+    }
+
+    /// Build a dynamic `200 MENU` response for `/warren` from the
+    /// peer table.
+    async fn warren_response(&self, peers: &PeerTable, request: &Frame) -> Frame {
+        menu_response(discovery::warren_menu(peers).await, request)
+    }
+}
+
+/// A `200 MENU` reply to `request` listing `items`.
+fn menu_response(items: Vec<MenuItem>, request: &Frame) -> Frame {
+    let entry = ContentEntry::Menu(items);
+    reply_builder("200 MENU", request)
+        .header("View", entry.view_type())
+        .body_text(entry.to_body())
+        .build()
+        .unwrap_or_else(Frame::from)
+}
+
+/// The headers of a TOPIC-CONFIG that set a topic's retention limits.
+const RETENTION_LIMITS: [&str; 3] = ["Max-Events", "Max-Age-Secs", "Max-Bytes"];
+
+/// The retention limits a TOPIC-CONFIG sets, each unlimited unless
+/// given.
+fn requested_retention(frame: &Frame) -> Result<Retention, ProtocolError> {
+    let [events, age, bytes] = RETENTION_LIMITS.map(|name| match frame.header(name) {
+        None => Ok(0),
+        Some(value) => value
+            .parse::<u64>()
+            .map_err(|_| ProtocolError::BadRequest(format!("bad {name}: {value}"))),
+    });
+    Ok(Retention {
+        max_events: events? as usize,
+        max_age_secs: age?,
+        max_bytes: bytes?,
+    })
+}
+
+/// Start a response on the request's lane (default `0`), echoing its
+/// `Txn` if it has one.
+fn reply_builder(start_line: &str, request: &Frame) -> FrameBuilder {
+    let builder = Frame::builder(start_line).header("Lane", request.header("Lane").unwrap_or("0"));
+    match request.header("Txn").filter(|txn| !txn.is_empty()) {
+        Some(txn) => builder.txn(txn),
+        None => builder,
     }
 }
 
@@ -503,4 +1395,178 @@ mod tests {
         let result = d.dispatch(&frame, "test-peer").await;
         assert_eq!(result.response.verb, "404");
     }
+
+    #[tokio::test]
+    async fn fetch_with_chunk_size_streams_chunks() {
+        let (mut cs, ee) = make_subsystems();
+        let text = "carrots ".repeat(64);
+        cs.register_text("/0/big", text.clone());
+        let d = Dispatcher::new(&cs, &ee);
+        let mut frame = Frame::with_args("FETCH", vec!["/0/big".into()]);
+        frame.set_header("Lane", "1");
+        frame.set_header("Txn", "F1");
+        frame.set_header("Chunk-Size", "100");
+        let result = d.dispatch(&frame, "test-peer").await;
+        assert_eq!(result.response.header("Chunks"), Some("6"));
+        assert_eq!(result.extras.len(), 6);
+
+        let mut assembler = chunk::ChunkAssembler::new();
+        assert!(assembler.feed(result.response).unwrap().is_none());
+        let done: Vec<Frame> = result
+            .extras
+            .into_iter()
+            .filter_map(|f| assembler.feed(f).unwrap())
+            .collect();
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].body.as_deref(), Some(text.as_str()));
+    }
+
+    fn quota_manager() -> QuotaManager {
+        QuotaManager::from_config(&crate::config::QuotaConfig {
+            peer_max_events: 5,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn publish_over_quota_returns_507() {
+        let (cs, ee) = make_subsystems();
+        let quotas = quota_manager();
+        let d = Dispatcher::new(&cs, &ee).with_quotas(&quotas);
+        let mut frame = Frame::with_args("PUBLISH", vec!["/q/chat".into()]);
+        frame.set_header("Lane", "2");
+        frame.set_body("hi");
+        for _ in 0..5 {
+            let result = d.dispatch(&frame, "test-peer").await;
+            assert_eq!(result.response.verb, "204");
+        }
+        let result = d.dispatch(&frame, "test-peer").await;
+        assert_eq!(result.response.verb, "507");
+        assert_eq!(result.response.header("Quota-Resource"), Some("events"));
+        assert_eq!(result.response.header("Lane"), Some("2"));
+        assert_eq!(ee.event_count("/q/chat"), 5);
+    }
+
+    #[tokio::test]
+    async fn swept_events_give_their_publisher_room_to_publish_again() {
+        let (cs, ee) = make_subsystems();
+        let quotas = quota_manager();
+        let d = Dispatcher::new(&cs, &ee).with_quotas(&quotas);
+        let mut frame = Frame::with_args("PUBLISH", vec!["/q/chat".into()]);
+        frame.set_body("hi");
+        for _ in 0..5 {
+            assert_eq!(d.dispatch(&frame, "test-peer").await.response.verb, "204");
+        }
+        assert_eq!(d.dispatch(&frame, "test-peer").await.response.verb, "507");
+
+        let keep_two = Retention {
+            max_events: 2,
+            ..Retention::default()
+        };
+        let policies = RetentionPolicies::in_memory(keep_two, &[]);
+        assert_eq!(
+            crate::events::retention::sweep(&ee, None, &policies, &quotas),
+            3
+        );
+        assert_eq!(quotas.peer_usage("test-peer").events, 2);
+
+        for _ in 0..3 {
+            assert_eq!(d.dispatch(&frame, "test-peer").await.response.verb, "204");
+        }
+        assert_eq!(d.dispatch(&frame, "test-peer").await.response.verb, "507");
+    }
+
+    #[tokio::test]
+    async fn topic_config_reads_and_sets_retention() {
+        let (cs, ee) = make_subsystems();
+        let defaults = Retention {
+            max_events: 100,
+            ..Retention::default()
+        };
+        let policies = RetentionPolicies::in_memory(defaults, &[]);
+        let d = Dispatcher::new(&cs, &ee).with_retention(&policies);
+        let mut frame = Frame::with_args("TOPIC-CONFIG", vec!["/q/presence".into()]);
+        let read = d.dispatch(&frame, "test-peer").await;
+        assert_eq!(read.response.verb, "200");
+        assert_eq!(read.response.header("Max-Events"), Some("100"));
+        assert_eq!(read.response.header("Retention"), Some("default"));
+
+        frame.set_header("Max-Age-Secs", "3600");
+        let set = d.dispatch(&frame, "test-peer").await;
+        assert_eq!(set.response.header("Max-Events"), Some("0"));
+        assert_eq!(set.response.header("Max-Age-Secs"), Some("3600"));
+        assert_eq!(set.response.header("Retention"), Some("set"));
+        assert_eq!(policies.get("/q/presence").0.max_age_secs, 3600);
+
+        frame.set_header("Max-Age-Secs", "an hour");
+        let bad = d.dispatch(&frame, "test-peer").await;
+        assert_eq!(bad.response.verb, "400");
+
+        let mut reset = Frame::with_args("TOPIC-CONFIG", vec!["/q/presence".into()]);
+        reset.set_header("Retention", "default");
+        let reset = d.dispatch(&reset, "test-peer").await;
+        assert_eq!(reset.response.header("Max-Events"), Some("100"));
+        let pattern = Frame::with_args("TOPIC-CONFIG", vec!["/q/*".into()]);
+        assert_eq!(d.dispatch(&pattern, "test-peer").await.response.verb, "400");
+    }
+
+    #[tokio::test]
+    async fn retransmitted_publish_is_acked_once_processed() {
+        let (cs, ee) = make_subsystems();
+        let lanes = LaneManager::new();
+        let d = Dispatcher::new(&cs, &ee).with_lanes(&lanes);
+        let mut frame = Frame::with_args("PUBLISH", vec!["/q/chat".into()]);
+        frame.set_header("Lane", "2");
+        frame.set_header("Seq", "7");
+        frame.set_body("hi");
+
+        let first = d.dispatch(&frame, "test-peer").await;
+        assert_eq!(first.response.verb, "204");
+        let again = d.dispatch(&frame, "test-peer").await;
+        assert_eq!(again.response.verb, "200");
+        assert_eq!(again.response.header("ACK"), Some("7"));
+        assert_eq!(again.response.header("Duplicate"), Some("true"));
+        assert_eq!(again.response.header("Lane"), Some("2"));
+        assert_eq!(ee.event_count("/q/chat"), 1);
+
+        // The same Seq on another lane is a different frame.
+        frame.set_header("Lane", "3");
+        let other = d.dispatch(&frame, "test-peer").await;
+        assert_eq!(other.response.verb, "204");
+    }
+
+    #[tokio::test]
+    async fn publish_soft_limit_warns_operator_topic() {
+        let (cs, ee) = make_subsystems();
+        let quotas = quota_manager();
+        ee.subscribe("/q/operator", "operator", "0", None);
+        let d = Dispatcher::new(&cs, &ee).with_quotas(&quotas);
+        let mut frame = Frame::with_args("PUBLISH", vec!["/q/chat".into()]);
+        frame.set_body("hi");
+        for _ in 0..3 {
+            let result = d.dispatch(&frame, "test-peer").await;
+            assert!(result.broadcast.is_empty());
+        }
+        let result = d.dispatch(&frame, "test-peer").await;
+        assert_eq!(result.broadcast.len(), 1);
+        assert_eq!(result.broadcast[0].0, "operator");
+        assert_eq!(ee.event_count("/q/operator"), 1);
+    }
+
+    #[tokio::test]
+    async fn fetch_quota_reports_usage() {
+        let (cs, ee) = make_subsystems();
+        let quotas = quota_manager();
+        let d = Dispatcher::new(&cs, &ee).with_quotas(&quotas);
+        let mut publish = Frame::with_args("PUBLISH", vec!["/q/chat".into()]);
+        publish.set_body("hello");
+        d.dispatch(&publish, "test-peer").await;
+
+        let fetch = Frame::with_args("FETCH", vec!["/quota".into()]);
+        let result = d.dispatch(&fetch, "test-peer").await;
+        assert_eq!(result.response.verb, "200");
+        let body = result.response.body.unwrap();
+        assert!(body.contains("peer\ttest-peer\t5\t1\t0\t5"));
+        assert!(body.contains("topic\t/q/chat\t5\t1"));
+    }
 }
@@ -0,0 +1,186 @@
+//! Crate-wide error taxonomy.
+//!
+//! [`ProtocolError`] names wire-level failures by status code.
+//! [`RabbitError`] groups failures by the subsystem that raised them
+//! (protocol, authentication, capabilities, routing, persistence) and
+//! maps each group onto a status code, so every subsystem answers a
+//! peer the same way: a status frame whose `Error-Kind` header names
+//! the group and whose body carries the detail.
+//!
+//! ```text
+//! 403 FORBIDDEN
+//! Error-Kind: capability
+//! Lane: 3
+//! Length: 28
+//! End:
+//! ed25519:ABC… lacks Publish
+//! ```
+
+use crate::protocol::error::{ErrorFrame, ProtocolError};
+use crate::protocol::frame::Frame;
+use crate::security::permissions::Capability;
+
+/// An error from any Rabbit subsystem.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum RabbitError {
+    /// A malformed, unexpected or over-limit frame.
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+
+    /// The peer failed to prove its identity.
+    #[error("authentication failed: {0}")]
+    Auth(String),
+
+    /// The peer lacks a capability the request needs.
+    #[error("{peer_id} lacks {capability:?}")]
+    Capability {
+        /// The peer that made the request.
+        peer_id: String,
+        /// The capability it lacks.
+        capability: Capability,
+    },
+
+    /// No route to the requested burrow or selector.
+    #[error("no route: {0}")]
+    Routing(String),
+
+    /// Reading or writing stored state failed.
+    #[error("persistence failed: {0}")]
+    Persistence(String),
+}
+
+impl RabbitError {
+    /// The subsystem group, sent as the `Error-Kind` header.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Protocol(_) => "protocol",
+            Self::Auth(_) => "auth",
+            Self::Capability { .. } => "capability",
+            Self::Routing(_) => "routing",
+            Self::Persistence(_) => "persistence",
+        }
+    }
+
+    /// Return the numeric status code for this error.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Self::Protocol(e) => e.status_code(),
+            Self::Auth(_) => 440,
+            Self::Capability { .. } => 403,
+            Self::Routing(_) => 404,
+            Self::Persistence(_) => 520,
+        }
+    }
+
+    /// Return the status label (e.g. `"FORBIDDEN"`).
+    pub fn status_label(&self) -> &'static str {
+        match self {
+            Self::Protocol(e) => e.status_label(),
+            Self::Auth(_) => "AUTH-REQUIRED",
+            Self::Capability { .. } => "FORBIDDEN",
+            Self::Routing(_) => "MISSING",
+            Self::Persistence(_) => "INTERNAL ERROR",
+        }
+    }
+
+    /// Extract the human-readable detail message.
+    pub fn detail(&self) -> String {
+        match self {
+            Self::Protocol(e) => e.detail(),
+            Self::Auth(s) | Self::Routing(s) | Self::Persistence(s) => s.clone(),
+            Self::Capability { .. } => self.to_string(),
+        }
+    }
+
+    /// Start an error frame for this error, tagged with its kind.
+    pub fn error_frame(&self) -> ErrorFrame {
+        let builder = match self {
+            Self::Protocol(e) => ErrorFrame::from(e),
+            _ => ErrorFrame::new(self.status_code(), self.status_label()).detail(self.detail()),
+        };
+        builder.header("Error-Kind", self.kind())
+    }
+}
+
+impl From<RabbitError> for ProtocolError {
+    /// Collapse an error onto the protocol error with the same status.
+    fn from(err: RabbitError) -> Self {
+        match err {
+            RabbitError::Protocol(e) => e,
+            RabbitError::Auth(s) => ProtocolError::AuthRequired(s),
+            RabbitError::Capability { .. } => ProtocolError::Forbidden(err.detail()),
+            RabbitError::Routing(s) => ProtocolError::Missing(s),
+            RabbitError::Persistence(s) => ProtocolError::InternalError(s),
+        }
+    }
+}
+
+impl From<std::io::Error> for RabbitError {
+    fn from(err: std::io::Error) -> Self {
+        RabbitError::Persistence(err.to_string())
+    }
+}
+
+impl From<RabbitError> for Frame {
+    fn from(err: RabbitError) -> Frame {
+        err.error_frame().build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_map_to_status_codes() {
+        let cases = [
+            (
+                RabbitError::Protocol(ProtocolError::Timeout("t".into())),
+                "408",
+                "protocol",
+            ),
+            (RabbitError::Auth("bad proof".into()), "440", "auth"),
+            (
+                RabbitError::Capability {
+                    peer_id: "p".into(),
+                    capability: Capability::Publish,
+                },
+                "403",
+                "capability",
+            ),
+            (RabbitError::Routing("/x".into()), "404", "routing"),
+            (
+                RabbitError::Persistence("disk full".into()),
+                "520",
+                "persistence",
+            ),
+        ];
+        for (err, code, kind) in cases {
+            let frame: Frame = err.clone().into();
+            assert_eq!(frame.verb, code);
+            assert_eq!(frame.header("Error-Kind"), Some(kind));
+            let protocol: ProtocolError = err.into();
+            assert_eq!(protocol.status_code().to_string(), code);
+        }
+    }
+
+    #[test]
+    fn capability_detail_names_peer_and_capability() {
+        let err = RabbitError::Capability {
+            peer_id: "ed25519:ABC".into(),
+            capability: Capability::Publish,
+        };
+        let frame = err.error_frame().build();
+        assert_eq!(frame.args, vec!["FORBIDDEN"]);
+        assert_eq!(frame.body.as_deref(), Some("ed25519:ABC lacks Publish"));
+    }
+
+    #[test]
+    fn protocol_errors_keep_their_headers() {
+        let err = RabbitError::from(ProtocolError::OutOfOrder { expected: 7 });
+        let frame: Frame = err.into();
+        assert_eq!(frame.verb, "409");
+        assert_eq!(frame.header("Expected"), Some("7"));
+        assert_eq!(frame.header("Error-Kind"), Some("protocol"));
+    }
+}
@@ -1,34 +1,286 @@
 //! Continuity engine — append-only persistence for event streams.
 //!
-//! Each topic's events are stored in a TSV (tab-separated values)
-//! file, one event per line:
+//! Each topic's events are stored in a log file of length-prefixed,
+//! checksummed records (see [`crate::events::record`]), each with the
+//! event's sequence number, when it was logged, the lane it was
+//! published on and its body.  Append-only writes for crash safety: a
+//! record torn by a crash is dropped when the log is read, and cut off
+//! before the log is appended to again.  Logs in the old line format
+//! are read as they are and rewritten in this one before they are
+//! appended to.
 //!
-//! ```text
-//! <seq>\t<timestamp_secs>\t<body>\n
-//! ```
+//! Appending does not touch the disk.  Each topic has a writer thread
+//! of its own, started by its first append, that takes queued events
+//! in batches of up to [`ContinuityOptions::batch_size`], writes each
+//! batch with one write and syncs the file as
+//! [`ContinuityOptions::fsync`] says.  At most
+//! [`ContinuityOptions::queue_depth`] events wait for a writer; beyond
+//! that an append waits for it to catch up.  A writer that fails stops,
+//! and the next append to its topic reports the error and starts a
+//! new one.  Each store runs writers of its own, so a log file should
+//! be written through one store at a time; reads through that store
+//! see every event appended before them.  Dropping a store writes and
+//! syncs whatever it queued.
 //!
-//! No JSON.  Human-readable.  Append-only writes for crash safety.
+//! A writer seals its topic's active log segment and starts another
+//! once the segment reaches [`ContinuityOptions::segment_bytes`] or
+//! [`ContinuityOptions::segment_secs`]; see [`crate::events::segment`].
+//! [`ContinuityStore::prune`] and [`ContinuityStore::compact`] drop a
+//! topic's oldest events from disk, deleting the segments they filled.
+//!
+//! Sequence numbers only rise within a topic: an append whose number
+//! is not above the highest logged is refused, and
+//! [`ContinuityStore::append_auto`] numbers an event itself.  The
+//! highest number is kept across pruning and restarts by the log's
+//! high-water mark.
+//!
+//! With [`ContinuityOptions::chained`], each record holds the hash of
+//! the one before, and with a [`ContinuityOptions::signer`] is signed
+//! too, so that [`ContinuityStore::verify_topic`] can tell whether a
+//! log has been edited, or cut short at either end.
 
-use std::io::{BufRead, Write};
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
-use crate::events::engine::Event;
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt};
+use tracing::{info, warn};
+
+use crate::config::ContinuityConfig;
+use crate::events::engine::{event_frame, Event};
+use crate::events::record::{self, LogContents, LogRecord, Origin};
+use crate::events::segment::{self, Retention};
 use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+use crate::security::identity::Identity;
+use crate::util::now_unix;
+
+/// One log file as a store writes it.  Appends hold its lock, so
+/// they reach the writer in sequence order, and so does dropping
+/// events while the log is rewritten; other logs are left alone.
+#[derive(Default)]
+struct TopicLog {
+    /// The highest sequence number logged, once looked up.
+    last: Option<u64>,
+    /// The writer, while one is running.
+    writer: Option<TopicWriter>,
+}
+
+/// A log file's state, shared by whatever is writing or reading it.
+type SharedLog = Arc<Mutex<TopicLog>>;
+
+/// `log`, locked.
+fn lock(log: &SharedLog) -> MutexGuard<'_, TopicLog> {
+    log.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl TopicLog {
+    /// The highest sequence number logged to the log at `path`, looked
+    /// up and kept if not already: the higher of its last record's and
+    /// the high-water mark saved when events were dropped.  A writer
+    /// is only started once it is known, so the log on disk is
+    /// complete when it is looked up.
+    fn high_water(&mut self, path: &Path) -> Result<u64, ProtocolError> {
+        if let Some(seq) = self.last {
+            return Ok(seq);
+        }
+        let seq = segment::last_seq(path)?.max(segment::read_mark(path));
+        self.last = Some(seq);
+        Ok(seq)
+    }
+
+    /// Queue `record` for the writer, starting one with `options` if
+    /// none is running.
+    fn send(
+        &mut self,
+        path: &Path,
+        options: &ContinuityOptions,
+        record: LogRecord,
+    ) -> Result<(), ProtocolError> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => self.writer.insert(TopicWriter::spawn(path, options)?),
+        };
+        if writer.commands.send(Command::Append(record)).is_err() {
+            return Err(self.stopped(path));
+        }
+        Ok(())
+    }
+
+    /// Stop the writer, if one is running, once it has written and
+    /// synced what is queued.
+    fn close(&mut self) -> Result<(), ProtocolError> {
+        match self.writer.take() {
+            Some(writer) => writer.close(),
+            None => Ok(()),
+        }
+    }
+
+    /// Remove the stopped writer for the log at `path`, returning what
+    /// stopped it.
+    fn stopped(&mut self, path: &Path) -> ProtocolError {
+        match self.close() {
+            Err(e) => e,
+            Ok(()) => ProtocolError::InternalError(format!(
+                "continuity writer for {} stopped",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// When a topic's writer syncs its log file to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// After every batch.
+    Always,
+    /// At most this long after a batch is written.
+    Interval(Duration),
+    /// Never; the OS writes the file back in its own time.
+    Never,
+}
+
+/// How a [`ContinuityStore`] writes.
+#[derive(Debug, Clone)]
+pub struct ContinuityOptions {
+    /// When logs are synced to disk.
+    pub fsync: FsyncPolicy,
+    /// Events a writer takes per batch.
+    pub batch_size: usize,
+    /// Events queued per topic before appends wait.
+    pub queue_depth: usize,
+    /// Bytes at which a writer seals its log segment; 0 is no limit.
+    pub segment_bytes: u64,
+    /// Seconds after its first event at which a writer seals its log
+    /// segment; 0 is no limit.
+    pub segment_secs: u64,
+    /// What [`ContinuityStore::compact`] keeps.
+    pub retention: Retention,
+    /// Whether each record written holds the hash of the one before.
+    pub chained: bool,
+    /// The identity each record written is signed with, if any.
+    /// Records are signed only if they are also `chained`.
+    pub signer: Option<Arc<Identity>>,
+}
+
+impl ContinuityOptions {
+    /// Options from the `[continuity]` config section.  An unknown
+    /// `fsync` policy is taken as `interval`.  Under `integrity =
+    /// "signed"` records are chained, but signed only once a
+    /// [`signer`](Self::signer) is set.
+    pub fn from_config(config: &ContinuityConfig) -> Self {
+        let fsync = match config.fsync.as_str() {
+            "always" => FsyncPolicy::Always,
+            "never" => FsyncPolicy::Never,
+            _ => FsyncPolicy::Interval(Duration::from_millis(config.fsync_interval_ms.max(1))),
+        };
+        Self {
+            fsync,
+            batch_size: config.batch_size.max(1),
+            queue_depth: config.queue_depth.max(1),
+            segment_bytes: config.segment_bytes,
+            segment_secs: config.segment_secs,
+            retention: Retention {
+                max_events: config.max_events,
+                max_age_secs: config.max_age_secs,
+                max_bytes: config.max_bytes,
+            },
+            chained: matches!(config.integrity.as_str(), "chain" | "signed"),
+            signer: None,
+        }
+    }
+}
+
+impl Default for ContinuityOptions {
+    fn default() -> Self {
+        Self::from_config(&ContinuityConfig::default())
+    }
+}
+
+/// What [`ContinuityStore::prune`] or [`ContinuityStore::compact`]
+/// dropped from a topic's log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Compaction {
+    /// Events dropped.
+    pub removed: usize,
+    /// Sequence number of the newest event dropped, if any was.
+    pub last_removed: Option<u64>,
+    /// Body bytes of the events dropped.
+    pub removed_bytes: u64,
+}
+
+/// What [`ContinuityStore::verify_topic`] found in a topic's log.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Records read.
+    pub records: usize,
+    /// Records holding the hash of the one before.
+    pub chained: usize,
+    /// Records signed.
+    pub signed: usize,
+    /// Each sign of tampering or truncation found.
+    pub problems: Vec<String>,
+}
+
+impl IntegrityReport {
+    /// Whether no problem was found.
+    pub fn is_intact(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// What is logged of one topic, as [`ContinuityStore::list_topics`]
+/// finds it on disk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TopicInfo {
+    /// Topic path, e.g. `/q/chat`.
+    pub topic: String,
+    /// Events logged and not dropped.
+    pub events: usize,
+    /// Sequence number of the oldest of them, or 0 if there are none.
+    pub first_seq: u64,
+    /// Highest sequence number logged, even if since dropped, or 0.
+    pub last_seq: u64,
+    /// Bytes of log, over every segment.
+    pub bytes: u64,
+    /// When the log was last written (Unix seconds).
+    pub last_write: u64,
+}
 
 /// Persistent storage for event streams.
 ///
-/// Each topic maps to a file at `<base_dir>/<sanitized_topic>.log`.
+/// Each topic maps to an active segment at
+/// `<base_dir>/<sanitized_topic>.log`, and the segments sealed before
+/// it.
 pub struct ContinuityStore {
     /// Directory where topic log files are stored.
     base_dir: PathBuf,
+    /// How the writers this store starts batch and sync.
+    options: ContinuityOptions,
+    /// Log files this store has written or read, by path.
+    logs: Mutex<BTreeMap<PathBuf, SharedLog>>,
 }
 
 impl ContinuityStore {
-    /// Create a new continuity store rooted at the given directory.
+    /// Create a new continuity store rooted at the given directory,
+    /// with [`ContinuityOptions::default`].
     ///
     /// The directory is created if it doesn't exist.
     pub fn new(base_dir: impl Into<PathBuf>) -> Result<Self, ProtocolError> {
+        Self::with_options(base_dir, ContinuityOptions::default())
+    }
+
+    /// Create a continuity store that writes as `options` says.
+    pub fn with_options(
+        base_dir: impl Into<PathBuf>,
+        options: ContinuityOptions,
+    ) -> Result<Self, ProtocolError> {
         let base_dir = base_dir.into();
         std::fs::create_dir_all(&base_dir).map_err(|e| {
             ProtocolError::InternalError(format!(
@@ -37,95 +289,428 @@ impl ContinuityStore {
                 e
             ))
         })?;
-        Ok(Self { base_dir })
+        Ok(Self {
+            base_dir,
+            options,
+            logs: Mutex::new(BTreeMap::new()),
+        })
     }
 
     /// Append an event to a topic's log file.
+    ///
+    /// The event is queued for the topic's writer, which is started
+    /// with this store's options if none is running; this waits only
+    /// if the queue is full.  Fails with `409 OUT-OF-ORDER` if the
+    /// event's sequence number is not above every one the topic has
+    /// logged, and with the error that stopped the writer, if it has
+    /// stopped.
     pub fn append(&self, topic: &str, event: &Event) -> Result<(), ProtocolError> {
+        self.append_on_lane(topic, 0, event)
+    }
+
+    /// Append an event published on `lane`; see [`append`](Self::append).
+    pub fn append_on_lane(
+        &self,
+        topic: &str,
+        lane: u16,
+        event: &Event,
+    ) -> Result<(), ProtocolError> {
+        self.append_record(topic, LogRecord::new(event, lane, now_unix()))
+    }
+
+    /// Append an event replicated from another burrow, recording its
+    /// `origin`; see [`append`](Self::append).
+    pub fn append_replicated(
+        &self,
+        topic: &str,
+        lane: u16,
+        event: &Event,
+        origin: Origin,
+    ) -> Result<(), ProtocolError> {
+        let mut record = LogRecord::new(event, lane, now_unix());
+        record.origin = Some(origin);
+        self.append_record(topic, record)
+    }
+
+    /// Queue `record` for the topic's writer if its sequence number is
+    /// above every one logged.
+    fn append_record(&self, topic: &str, record: LogRecord) -> Result<(), ProtocolError> {
         let path = self.topic_path(topic);
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&path)
-            .map_err(|e| {
-                ProtocolError::InternalError(format!(
-                    "failed to open log {}: {}",
-                    path.display(),
-                    e
-                ))
-            })?;
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        // Escape newlines in body to keep one-line-per-event invariant
-        let escaped_body = event.body.replace('\n', "\\n").replace('\t', "\\t");
-        writeln!(file, "{}\t{}\t{}", event.seq, timestamp, escaped_body).map_err(|e| {
-            ProtocolError::InternalError(format!(
-                "failed to write to log {}: {}",
-                path.display(),
-                e
-            ))
-        })
+        let log = self.log(&path);
+        let mut log = lock(&log);
+        let high = log.high_water(&path)?;
+        if record.seq <= high {
+            return Err(ProtocolError::OutOfOrder { expected: high + 1 });
+        }
+        let seq = record.seq;
+        log.send(&path, &self.options, record)?;
+        log.last = Some(seq);
+        Ok(())
+    }
+
+    /// Append `body`, published on `lane`, as the topic's next event,
+    /// returning the sequence number it was given: one above the
+    /// highest the topic has logged, even if those events have since
+    /// been pruned, here or before a restart.
+    pub fn append_auto(&self, topic: &str, lane: u16, body: &str) -> Result<u64, ProtocolError> {
+        let path = self.topic_path(topic);
+        let log = self.log(&path);
+        let mut log = lock(&log);
+        let seq = log.high_water(&path)? + 1;
+        let event = Event {
+            seq,
+            body: body.to_string(),
+        };
+        log.send(&path, &self.options, LogRecord::new(&event, lane, now_unix()))?;
+        log.last = Some(seq);
+        Ok(seq)
+    }
+
+    /// The highest sequence number the topic has logged, or 0.
+    pub fn last_seq(&self, topic: &str) -> Result<u64, ProtocolError> {
+        let path = self.topic_path(topic);
+        lock(&self.log(&path)).high_water(&path)
+    }
+
+    /// The state of the log file at `path`.
+    fn log(&self, path: &Path) -> SharedLog {
+        let mut logs = self.logs.lock().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(logs.entry(path.to_path_buf()).or_default())
+    }
+
+    /// Every log file this store has written or read, with its state.
+    fn logs(&self) -> Vec<(PathBuf, SharedLog)> {
+        let logs = self.logs.lock().unwrap_or_else(|e| e.into_inner());
+        logs.iter()
+            .map(|(path, log)| (path.clone(), Arc::clone(log)))
+            .collect()
     }
 
-    /// Load all events from a topic's log file.
+    /// Wait until the writer of the topic's log at `path`, if any, has
+    /// written (and, if `sync`, synced) everything queued before now.
+    fn flush_path(&self, path: &Path, sync: bool) -> Result<(), ProtocolError> {
+        flush_log(path, &self.log(path), sync)
+    }
+
+    /// Write and sync every event this store has queued, of every
+    /// topic.
+    pub fn flush(&self) -> Result<(), ProtocolError> {
+        for (path, log) in self.logs() {
+            flush_log(&path, &log, true)?;
+        }
+        Ok(())
+    }
+
+    /// Load all events from a topic's log.
     ///
     /// Returns an empty vec if the file doesn't exist.
     pub fn load(&self, topic: &str) -> Result<Vec<Event>, ProtocolError> {
+        Ok(self
+            .records(topic)?
+            .iter()
+            .map(LogRecord::to_event)
+            .collect())
+    }
+
+    /// Every intact record in a topic's log, oldest first.
+    pub fn records(&self, topic: &str) -> Result<Vec<LogRecord>, ProtocolError> {
         let path = self.topic_path(topic);
-        if !path.exists() {
-            return Ok(Vec::new());
-        }
-        let file = std::fs::File::open(&path).map_err(|e| {
-            ProtocolError::InternalError(format!("failed to open log {}: {}", path.display(), e))
-        })?;
-        let reader = std::io::BufReader::new(file);
-        let mut events = Vec::new();
-        for line_result in reader.lines() {
-            let line = line_result.map_err(|e| {
-                ProtocolError::InternalError(format!("failed to read log line: {}", e))
-            })?;
-            if line.is_empty() {
-                continue;
-            }
-            if let Some(event) = parse_log_line(&line) {
-                events.push(event);
-            }
-        }
-        Ok(events)
+        self.flush_path(&path, false)?;
+        Ok(checked(&path, segment::read_all(&path)?))
     }
 
     /// Replay events after a given sequence number.
+    ///
+    /// Only the segments that can hold them are read, from the record
+    /// their indexes place at or just before `since_seq`.
     pub fn replay(&self, topic: &str, since_seq: u64) -> Result<Vec<Event>, ProtocolError> {
-        let events = self.load(topic)?;
-        Ok(events.into_iter().filter(|e| e.seq > since_seq).collect())
+        let path = self.topic_path(topic);
+        self.flush_path(&path, false)?;
+        let records = checked(&path, segment::read_after(&path, since_seq)?);
+        Ok(records.iter().map(LogRecord::to_event).collect())
+    }
+
+    /// Replay events after `since_seq` as EVENT frames on `lane`,
+    /// reading the log a segment at a time as the stream is polled.
+    ///
+    /// The stream ends after `limit` events or the last event at or
+    /// before `until_seq`, where given, or else at the end of the log.
+    /// A failed read ends it early, with a warning.
+    pub fn replay_stream(
+        &self,
+        topic: &str,
+        since_seq: u64,
+        lane: &str,
+        limit: Option<usize>,
+        until_seq: Option<u64>,
+    ) -> impl Stream<Item = Frame> + Send + 'static {
+        let path = self.topic_path(topic);
+        let log = self.log(&path);
+        let (topic, lane) = (topic.to_string(), lane.to_string());
+        let batches = stream::unfold(Some(since_seq), move |since| {
+            let (path, log) = (path.clone(), Arc::clone(&log));
+            async move {
+                let since = since.filter(|s| until_seq.is_none_or(|until| *s < until))?;
+                let read = tokio::task::spawn_blocking(move || {
+                    flush_log(&path, &log, false)?;
+                    Ok(checked(&path, segment::read_next(&path, since)?))
+                })
+                .await
+                .unwrap_or_else(|e| {
+                    Err(ProtocolError::InternalError(format!(
+                        "replay read failed: {}",
+                        e
+                    )))
+                });
+                match read {
+                    Ok(records) => {
+                        let last = records.last()?.seq;
+                        Some((records, Some(last)))
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "continuity replay stopped");
+                        None
+                    }
+                }
+            }
+        });
+        batches
+            .flat_map(stream::iter)
+            .take_while(move |r| future::ready(until_seq.is_none_or(|until| r.seq <= until)))
+            .take(limit.unwrap_or(usize::MAX))
+            .filter_map(move |r| future::ready(event_frame(&topic, &r.to_event(), &lane)))
+    }
+
+    /// The sequence number of the last event logged before `secs` (Unix
+    /// time), or 0 if there is none, so that replaying after it
+    /// yields the events logged at or after `secs`.
+    pub fn last_seq_before(&self, topic: &str, secs: u64) -> Result<u64, ProtocolError> {
+        let last = self
+            .records(topic)?
+            .iter()
+            .take_while(|r| r.timestamp < secs)
+            .last()
+            .map_or(0, |r| r.seq);
+        Ok(last)
     }
 
     /// Prune a topic's log, keeping only the last `keep` events.
     ///
-    /// Rewrites the file with only the retained events.
-    pub fn prune(&self, topic: &str, keep: usize) -> Result<(), ProtocolError> {
-        let events = self.load(topic)?;
-        if events.len() <= keep {
-            return Ok(());
+    /// Segments holding only pruned events are deleted and the one the
+    /// pruned events end in is rewritten; the retained events keep the
+    /// times they were logged.  The topic's writer is stopped first,
+    /// and appends wait until the log is rewritten.
+    pub fn prune(&self, topic: &str, keep: usize) -> Result<Compaction, ProtocolError> {
+        self.drop_oldest(topic, |records| records.len().saturating_sub(keep))
+    }
+
+    /// Drop a topic's oldest events beyond [`ContinuityOptions::retention`],
+    /// as [`prune`](Self::prune) does.  The writer is left alone if
+    /// nothing is to be dropped.
+    pub fn compact(&self, topic: &str) -> Result<Compaction, ProtocolError> {
+        self.compact_with(topic, self.options.retention)
+    }
+
+    /// Drop a topic's oldest events beyond `retention`, as
+    /// [`compact`](Self::compact) does.
+    pub fn compact_with(
+        &self,
+        topic: &str,
+        retention: Retention,
+    ) -> Result<Compaction, ProtocolError> {
+        if retention == Retention::default() {
+            return Ok(Compaction::default());
+        }
+        let now = now_unix();
+        let excess = |records: &[LogRecord]| retention.excess(records, now);
+        if excess(&self.records(topic)?) == 0 {
+            return Ok(Compaction::default());
         }
-        let retained = &events[events.len() - keep..];
+        self.drop_oldest(topic, excess)
+    }
+
+    /// Stop the topic's writer and drop as many of its oldest events
+    /// as `excess` counts in the whole log, holding back appends to the
+    /// topic, and only the topic, until it is rewritten.
+    fn drop_oldest(
+        &self,
+        topic: &str,
+        excess: impl FnOnce(&[LogRecord]) -> usize,
+    ) -> Result<Compaction, ProtocolError> {
         let path = self.topic_path(topic);
-        let mut file = std::fs::File::create(&path).map_err(|e| {
-            ProtocolError::InternalError(format!("failed to rewrite log {}: {}", path.display(), e))
-        })?;
-        for event in retained {
-            let escaped_body = event.body.replace('\n', "\\n").replace('\t', "\\t");
-            let timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs();
-            writeln!(file, "{}\t{}\t{}", event.seq, timestamp, escaped_body).map_err(|e| {
-                ProtocolError::InternalError(format!("failed to write pruned log: {}", e))
-            })?;
+        let log = self.log(&path);
+        let mut log = lock(&log);
+        log.close()?;
+        let records = segment::read_all(&path)?.records;
+        let removed = excess(&records).min(records.len());
+        if removed == 0 {
+            return Ok(Compaction::default());
         }
-        Ok(())
+        // Numbering carries on from the dropped events, and the chain
+        // from the newest of them.
+        let newest = records.iter().map(|r| r.seq).max().unwrap_or(0);
+        if newest > segment::read_mark(&path) {
+            segment::write_mark(&path, newest)?;
+        }
+        let chained = records
+            .get(removed)
+            .map_or(self.options.chained, |r| r.prev_hash.is_some());
+        if chained {
+            segment::write_anchor(&path, &records[removed - 1].hash())?;
+        }
+        segment::drop_oldest(&path, removed)?;
+        Ok(Compaction {
+            removed,
+            last_removed: Some(records[removed - 1].seq),
+            removed_bytes: records[..removed]
+                .iter()
+                .map(|r| r.body.len() as u64)
+                .sum(),
+        })
+    }
+
+    /// Check a topic's log for signs of tampering or truncation.
+    ///
+    /// Each chained record must hold the hash of the record before it,
+    /// the first the hash of the newest record dropped, and no record
+    /// may follow a chained one unchained.  Signatures are checked
+    /// against the [`signer`](ContinuityOptions::signer)'s key, if this
+    /// store has one, and no chained record may follow a signed one
+    /// unsigned.  The newest record written, as noted when it was,
+    /// must still be there unchanged, or else must have been dropped.
+    pub fn verify_topic(&self, topic: &str) -> Result<IntegrityReport, ProtocolError> {
+        let path = self.topic_path(topic);
+        self.flush_path(&path, false)?;
+        let contents = segment::read_all(&path)?;
+        let records = contents.records;
+        let mut report = IntegrityReport {
+            records: records.len(),
+            ..IntegrityReport::default()
+        };
+        if contents.torn_bytes > 0 {
+            report
+                .problems
+                .push(format!("{} torn or corrupt bytes", contents.torn_bytes));
+        }
+        let key = self.options.signer.as_ref().map(|s| s.public_key_bytes());
+        let mut prev = segment::read_anchor(&path)?;
+        let mut last: Option<u64> = None;
+        for record in &records {
+            let seq = record.seq;
+            if let Some(last) = last.filter(|&last| seq <= last) {
+                report
+                    .problems
+                    .push(format!("event {} follows event {}", seq, last));
+            }
+            match record.prev_hash {
+                Some(hash) => {
+                    report.chained += 1;
+                    if hash != prev {
+                        report
+                            .problems
+                            .push(format!("event {} does not chain from the one before", seq));
+                    }
+                }
+                None if report.chained > 0 => {
+                    report
+                        .problems
+                        .push(format!("event {} is not chained", seq));
+                }
+                None => {}
+            }
+            match (&record.signature, &key) {
+                (Some(signature), Some(key)) => {
+                    report.signed += 1;
+                    if Identity::verify(key, &record.hash(), signature).is_err() {
+                        report
+                            .problems
+                            .push(format!("event {} has a bad signature", seq));
+                    }
+                }
+                (Some(_), None) => report.signed += 1,
+                (None, _) if report.signed > 0 && record.prev_hash.is_some() => {
+                    report.problems.push(format!("event {} is not signed", seq));
+                }
+                (None, _) => {}
+            }
+            prev = record.hash();
+            last = Some(seq);
+        }
+        if let Some((seq, hash)) = segment::read_head(&path)? {
+            match records.iter().find(|r| r.seq == seq) {
+                Some(record) if record.hash() != hash => {
+                    report
+                        .problems
+                        .push(format!("event {} is not the one written", seq));
+                }
+                None if last.is_none_or(|last| last < seq) && prev != hash => {
+                    report.problems.push(format!(
+                        "events through {} were written but the log ends at {}",
+                        seq,
+                        last.unwrap_or(0)
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(report)
+    }
+
+    /// Every topic with a log here, sorted.
+    ///
+    /// Topics are named after their log files, each `_` read as a `/`,
+    /// as [`topic_path`](Self::topic_path) writes them.
+    pub fn topics(&self) -> Vec<String> {
+        // A writer creates its log when it starts.
+        for (path, log) in self.logs() {
+            let _ = flush_log(&path, &log, false);
+        }
+        let Ok(entries) = std::fs::read_dir(&self.base_dir) else {
+            return Vec::new();
+        };
+        let mut topics: Vec<String> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+            .filter_map(|path| {
+                let stem = path.file_stem()?.to_str()?;
+                Some(format!("/{}", stem.replace('_', "/")))
+            })
+            .collect();
+        topics.sort();
+        topics
+    }
+
+    /// Every topic with a log here, sorted, with what is logged of it.
+    pub fn list_topics(&self) -> Result<Vec<TopicInfo>, ProtocolError> {
+        self.topics()
+            .into_iter()
+            .map(|topic| self.topic_info(&topic))
+            .collect()
+    }
+
+    /// What is logged of `topic`.
+    pub fn topic_info(&self, topic: &str) -> Result<TopicInfo, ProtocolError> {
+        let path = self.topic_path(topic);
+        self.flush_path(&path, false)?;
+        let records = segment::read_all(&path)?.records;
+        let mut bytes = 0;
+        for file in segment::sealed(&path)?.iter().chain([&path]) {
+            bytes += std::fs::metadata(file).map_or(0, |m| m.len());
+        }
+        let last_write = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_secs());
+        Ok(TopicInfo {
+            topic: topic.to_string(),
+            events: records.len(),
+            first_seq: records.first().map_or(0, |r| r.seq),
+            last_seq: self.last_seq(topic)?,
+            bytes,
+            last_write,
+        })
     }
 
     /// Return the file path for a topic's log.
@@ -136,10 +721,252 @@ impl ContinuityStore {
 
     /// Check whether a log file exists for a topic.
     pub fn has_log(&self, topic: &str) -> bool {
-        self.topic_path(topic).exists()
+        let path = self.topic_path(topic);
+        let _ = self.flush_path(&path, false);
+        path.exists()
+    }
+}
+
+impl Drop for ContinuityStore {
+    fn drop(&mut self) {
+        for (path, log) in self.logs() {
+            if let Err(e) = lock(&log).close() {
+                warn!(path = %path.display(), error = %e, "continuity writer failed");
+            }
+        }
     }
 }
 
+/// Wait until the writer of `log`, the log file at `path`, if it has
+/// one, has written (and, if `sync`, synced) everything queued before
+/// now.  Appends to the log are not held back meanwhile.
+fn flush_log(path: &Path, log: &SharedLog, sync: bool) -> Result<(), ProtocolError> {
+    let commands = match &lock(log).writer {
+        Some(writer) => writer.commands.clone(),
+        None => return Ok(()),
+    };
+    let (done, written) = mpsc::channel();
+    if commands.send(Command::Flush { sync, done }).is_err() || written.recv().is_err() {
+        return Err(lock(log).stopped(path));
+    }
+    Ok(())
+}
+
+/// What a topic's writer is asked to do.
+enum Command {
+    /// Write one record.
+    Append(LogRecord),
+    /// Write everything queued, sync it if `sync`, then say so.
+    Flush { sync: bool, done: mpsc::Sender<()> },
+}
+
+/// The sending side of one log file's writer thread.
+struct TopicWriter {
+    commands: SyncSender<Command>,
+    failure: Arc<Mutex<Option<ProtocolError>>>,
+    thread: JoinHandle<()>,
+}
+
+impl TopicWriter {
+    /// Start a writer appending to `path`.
+    fn spawn(path: &Path, options: &ContinuityOptions) -> Result<Self, ProtocolError> {
+        let (commands, queue) = mpsc::sync_channel(options.queue_depth);
+        let failure = Arc::new(Mutex::new(None));
+        let (path, options, failed) = (path.to_path_buf(), options.clone(), Arc::clone(&failure));
+        let name = format!(
+            "continuity-{}",
+            path.file_stem().unwrap_or_default().to_string_lossy()
+        );
+        let thread = std::thread::Builder::new()
+            .name(name)
+            .spawn(move || {
+                if let Err(e) = run_writer(&path, &queue, &options) {
+                    warn!(path = %path.display(), error = %e, "continuity writer stopped");
+                    *failed.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+                }
+            })
+            .map_err(|e| {
+                ProtocolError::InternalError(format!("failed to start continuity writer: {}", e))
+            })?;
+        Ok(Self {
+            commands,
+            failure,
+            thread,
+        })
+    }
+
+    /// Stop the writer once it has written and synced what is queued.
+    /// Fails with the error that stopped it, if one did.
+    fn close(self) -> Result<(), ProtocolError> {
+        drop(self.commands);
+        let _ = self.thread.join();
+        match self
+            .failure
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The writer loop: recover the log at `path`, then append what
+/// arrives on `queue` to it in batches, syncing and sealing segments
+/// as `options` says, until every sender is gone.
+fn run_writer(
+    path: &Path,
+    queue: &Receiver<Command>,
+    options: &ContinuityOptions,
+) -> Result<(), ProtocolError> {
+    let failed = |what: &str, e: std::io::Error| {
+        ProtocolError::InternalError(format!("failed to {} log {}: {}", what, path.display(), e))
+    };
+    let found = record::recover(path)?;
+    if found.legacy {
+        info!(path = %path.display(), "rewrote continuity log in the record format");
+    }
+    if found.torn_bytes > 0 {
+        warn!(
+            path = %path.display(),
+            bytes = found.torn_bytes,
+            "cut torn or corrupt tail from continuity log"
+        );
+    }
+    let open = || {
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map(BufWriter::new)
+            .map_err(|e| failed("open", e))
+    };
+    let mut out = open()?;
+    let sync = |out: &mut BufWriter<File>| {
+        out.flush()
+            .and_then(|()| out.get_ref().sync_data())
+            .map_err(|e| failed("sync", e))
+    };
+    // The active segment's size, and its first record's sequence
+    // number and time.
+    let mut size = std::fs::metadata(path).map_or(0, |m| m.len());
+    let mut segment_start = found.records.first().map(|r| (r.seq, r.timestamp));
+    // The hash the next record chains from, if chaining, and the
+    // sequence number of the newest record chained since the head was
+    // last saved.
+    let mut prev = match options.chained {
+        true => Some(match segment::last_record(path)? {
+            Some(last) => last.hash(),
+            None => segment::read_anchor(path)?,
+        }),
+        false => None,
+    };
+    let mut head = None;
+    let mut unsynced = false;
+    let mut last_sync = Instant::now();
+    loop {
+        let first = match options.fsync {
+            FsyncPolicy::Interval(every) if unsynced => {
+                match queue.recv_timeout(every.saturating_sub(last_sync.elapsed())) {
+                    Ok(command) => command,
+                    Err(RecvTimeoutError::Timeout) => {
+                        sync(&mut out)?;
+                        unsynced = false;
+                        last_sync = Instant::now();
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            _ => match queue.recv() {
+                Ok(command) => command,
+                Err(_) => break,
+            },
+        };
+
+        let mut waiting = Vec::new();
+        let mut sync_asked = false;
+        let mut lines = 0;
+        let mut next = Some(first);
+        while let Some(command) = next {
+            match command {
+                Command::Append(mut record) => {
+                    if let Some(prev) = &mut prev {
+                        record.prev_hash = Some(*prev);
+                        if let Some(signer) = &options.signer {
+                            record.signature = signer.sign(&record.hash()).try_into().ok();
+                        }
+                        *prev = record.hash();
+                        head = Some(record.seq);
+                    }
+                    let bytes = record.encode();
+                    if let Some((first_seq, first_time)) = segment_start {
+                        let full = options.segment_bytes > 0
+                            && size + bytes.len() as u64 > options.segment_bytes;
+                        let old = options.segment_secs > 0
+                            && record.timestamp >= first_time.saturating_add(options.segment_secs);
+                        if full || old {
+                            sync(&mut out)?;
+                            segment::seal(path, first_seq)?;
+                            out = open()?;
+                            size = record::MAGIC.len() as u64;
+                            segment_start = None;
+                        }
+                    }
+                    out.write_all(&bytes).map_err(|e| failed("write to", e))?;
+                    size += bytes.len() as u64;
+                    segment_start.get_or_insert((record.seq, record.timestamp));
+                    unsynced = true;
+                    lines += 1;
+                }
+                Command::Flush { sync, done } => {
+                    sync_asked |= sync;
+                    waiting.push(done);
+                }
+            }
+            next = if lines < options.batch_size {
+                queue.try_recv().ok()
+            } else {
+                None
+            };
+        }
+        out.flush().map_err(|e| failed("write to", e))?;
+        if let (Some(seq), Some(hash)) = (head.take(), &prev) {
+            segment::write_head(path, seq, hash)?;
+        }
+        let due = match options.fsync {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::Interval(every) => last_sync.elapsed() >= every,
+            FsyncPolicy::Never => false,
+        };
+        if unsynced && (sync_asked || due) {
+            sync(&mut out)?;
+            unsynced = false;
+            last_sync = Instant::now();
+        }
+        for done in waiting {
+            let _ = done.send(());
+        }
+    }
+    if unsynced && options.fsync != FsyncPolicy::Never {
+        sync(&mut out)?;
+    }
+    out.flush().map_err(|e| failed("write to", e))
+}
+
+/// The records of `contents`, read from the log at `path`, warning of
+/// any torn tail.
+fn checked(path: &Path, contents: LogContents) -> Vec<LogRecord> {
+    if contents.torn_bytes > 0 {
+        warn!(
+            path = %path.display(),
+            bytes = contents.torn_bytes,
+            "ignoring torn or corrupt tail of continuity log"
+        );
+    }
+    contents.records
+}
+
 /// Sanitize a topic path for use as a filename.
 ///
 /// Replaces `/` with `_`, strips leading underscores.
@@ -152,18 +979,6 @@ fn sanitize_topic(topic: &str) -> String {
     s.trim_start_matches('_').to_string()
 }
 
-/// Parse a single line from a log file into an Event.
-fn parse_log_line(line: &str) -> Option<Event> {
-    let parts: Vec<&str> = line.splitn(3, '\t').collect();
-    if parts.len() < 3 {
-        return None;
-    }
-    let seq: u64 = parts[0].parse().ok()?;
-    // parts[1] is the timestamp — we don't store it in Event
-    let body = parts[2].replace("\\n", "\n").replace("\\t", "\t");
-    Some(Event { seq, body })
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,6 +990,86 @@ mod tests {
         (store, dir)
     }
 
+    #[test]
+    fn batched_writes_reach_disk_under_each_fsync_policy() {
+        let policies = [
+            FsyncPolicy::Always,
+            FsyncPolicy::Interval(Duration::from_millis(5)),
+            FsyncPolicy::Never,
+        ];
+        for fsync in policies {
+            let dir = TempDir::new().unwrap();
+            let options = ContinuityOptions {
+                fsync,
+                batch_size: 4,
+                queue_depth: 2,
+                ..ContinuityOptions::default()
+            };
+            let store = ContinuityStore::with_options(dir.path(), options.clone()).unwrap();
+            for seq in 1..=50 {
+                let body = format!("event {}", seq);
+                store.append("/q/load", &Event { seq, body }).unwrap();
+            }
+            assert_eq!(store.load("/q/load").unwrap().len(), 50);
+            store
+                .append(
+                    "/q/other",
+                    &Event {
+                        seq: 1,
+                        body: "x".into(),
+                    },
+                )
+                .unwrap();
+            store.flush().unwrap();
+            store.prune("/q/load", 10).unwrap();
+            assert_eq!(store.replay("/q/load", 45).unwrap().len(), 5);
+            drop(store);
+
+            let reopened = ContinuityStore::with_options(dir.path(), options).unwrap();
+            assert_eq!(reopened.load("/q/load").unwrap()[0].seq, 41);
+            assert!(reopened.has_log("/q/other"));
+        }
+    }
+
+    #[test]
+    fn a_failed_writer_is_reported_and_replaced() {
+        let (store, dir) = make_store();
+        let event = Event {
+            seq: 1,
+            body: "lost".into(),
+        };
+        // A directory where the log should be cannot be opened.
+        std::fs::create_dir_all(dir.path().join("events/q_broken.log")).unwrap();
+        // The failure is reported by the append itself if the writer
+        // has already stopped, or else by the next read.
+        let reported = store.append("/q/broken", &event).is_err()
+            || store.flush_path(&dir.path().join("events/q_broken.log"), false).is_err();
+        assert!(reported);
+
+        std::fs::remove_dir(dir.path().join("events/q_broken.log")).unwrap();
+        store.append("/q/broken", &event).unwrap();
+        assert_eq!(store.load("/q/broken").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn old_logs_are_read_and_rewritten_before_appending() {
+        let (store, dir) = make_store();
+        let path = dir.path().join("events/q_chat.log");
+        std::fs::write(&path, "1\t100\tfirst\\tline\n2\t200\tsecond\n").unwrap();
+        assert_eq!(store.load("/q/chat").unwrap()[0].body, "first\tline");
+        assert_eq!(store.last_seq_before("/q/chat", 150).unwrap(), 1);
+
+        let event = Event {
+            seq: 3,
+            body: "third".into(),
+        };
+        store.append_on_lane("/q/chat", 5, &event).unwrap();
+        let records = store.records("/q/chat").unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!((records[0].lane, records[2].lane), (0, 5));
+        assert!(std::fs::read(&path).unwrap().starts_with(record::MAGIC));
+    }
+
     #[test]
     fn append_and_load() {
         let (store, _dir) = make_store();
@@ -231,6 +1126,143 @@ mod tests {
         assert_eq!(events[1].seq, 5);
     }
 
+    #[test]
+    fn last_seq_before_finds_where_a_time_starts() {
+        let (store, _dir) = make_store();
+        std::fs::write(
+            store.topic_path("/q/log"),
+            "1\t100\tearly\n2\t200\tmiddle\n3\t200\talso middle\n4\t300\tlate\n",
+        )
+        .unwrap();
+        assert_eq!(store.last_seq_before("/q/log", 50).unwrap(), 0);
+        assert_eq!(store.last_seq_before("/q/log", 200).unwrap(), 1);
+        assert_eq!(store.last_seq_before("/q/log", 250).unwrap(), 3);
+        assert_eq!(store.last_seq_before("/q/log", 1000).unwrap(), 4);
+        assert_eq!(store.last_seq_before("/q/missing", 1000).unwrap(), 0);
+    }
+
+    #[test]
+    fn sequence_numbers_rise_across_pruning_and_restarts() {
+        let (store, dir) = make_store();
+        assert_eq!(store.append_auto("/q/seq", 0, "one").unwrap(), 1);
+        assert_eq!(store.append_auto("/q/seq", 0, "two").unwrap(), 2);
+        let event = |seq| Event {
+            seq,
+            body: "explicit".into(),
+        };
+        store.append("/q/seq", &event(5)).unwrap();
+        assert!(matches!(
+            store.append("/q/seq", &event(5)),
+            Err(ProtocolError::OutOfOrder { expected: 6 })
+        ));
+        assert!(store.append("/q/seq", &event(3)).is_err());
+        assert_eq!(store.append_auto("/q/seq", 0, "six").unwrap(), 6);
+
+        store.prune("/q/seq", 0).unwrap();
+        assert!(store.load("/q/seq").unwrap().is_empty());
+        assert_eq!(store.last_seq("/q/seq").unwrap(), 6);
+        drop(store);
+
+        // A copy at another path is looked up afresh, as after a
+        // restart, and finds the high-water mark.
+        let moved = dir.path().join("moved");
+        std::fs::create_dir(&moved).unwrap();
+        for entry in std::fs::read_dir(dir.path().join("events")).unwrap() {
+            let path = entry.unwrap().path();
+            std::fs::copy(&path, moved.join(path.file_name().unwrap())).unwrap();
+        }
+        let reopened = ContinuityStore::new(&moved).unwrap();
+        assert_eq!(reopened.last_seq("/q/seq").unwrap(), 6);
+        assert_eq!(reopened.append_auto("/q/seq", 2, "seven").unwrap(), 7);
+        assert_eq!(reopened.records("/q/seq").unwrap()[0].lane, 2);
+    }
+
+    #[test]
+    fn signed_chains_reveal_tampering_and_truncation() {
+        let dir = TempDir::new().unwrap();
+        let options = ContinuityOptions {
+            chained: true,
+            signer: Some(Arc::new(Identity::generate())),
+            ..ContinuityOptions::default()
+        };
+        let open = || ContinuityStore::with_options(dir.path(), options.clone()).unwrap();
+        let store = open();
+        for body in ["a", "b", "c", "d", "e"] {
+            store.append_auto("/q/chain", 0, body).unwrap();
+        }
+        let report = store.verify_topic("/q/chain").unwrap();
+        assert!(report.is_intact(), "{:?}", report.problems);
+        assert_eq!((report.records, report.chained, report.signed), (5, 5, 5));
+
+        // The oldest left chains from the newest pruned.
+        store.prune("/q/chain", 3).unwrap();
+        assert!(store.verify_topic("/q/chain").unwrap().is_intact());
+        drop(store);
+
+        let path = dir.path().join("q_chain.log");
+        let records = segment::read_all(&path).unwrap().records;
+        let mut edited = records.clone();
+        edited[1].body = "forged".into();
+        record::write_log(&path, &edited).unwrap();
+        let problems = open().verify_topic("/q/chain").unwrap().problems;
+        assert!(problems.contains(&"event 4 has a bad signature".to_string()));
+        assert!(problems.contains(&"event 5 does not chain from the one before".to_string()));
+
+        record::write_log(&path, &records[1..]).unwrap();
+        let problems = open().verify_topic("/q/chain").unwrap().problems;
+        assert_eq!(problems, ["event 4 does not chain from the one before"]);
+
+        record::write_log(&path, &records[..2]).unwrap();
+        let problems = open().verify_topic("/q/chain").unwrap().problems;
+        assert_eq!(
+            problems,
+            ["events through 5 were written but the log ends at 4"]
+        );
+
+        // Appending carries the chain on from the newest record.
+        record::write_log(&path, &records).unwrap();
+        let store = open();
+        store.append_auto("/q/chain", 0, "f").unwrap();
+        assert!(store.verify_topic("/q/chain").unwrap().is_intact());
+    }
+
+    #[tokio::test]
+    async fn replay_streams_read_lazily_within_bounds() {
+        use futures_util::StreamExt;
+
+        let dir = TempDir::new().unwrap();
+        let options = ContinuityOptions {
+            segment_bytes: 200,
+            ..ContinuityOptions::default()
+        };
+        let store = ContinuityStore::with_options(dir.path(), options).unwrap();
+        for seq in 1..=40 {
+            let body = format!("event {:02}", seq);
+            store.append("/q/roll", &Event { seq, body }).unwrap();
+        }
+        let seqs = |stream: Vec<Frame>| -> Vec<u64> {
+            stream
+                .iter()
+                .filter_map(|f| f.header("Seq")?.parse().ok())
+                .collect()
+        };
+
+        let all: Vec<Frame> = store
+            .replay_stream("/q/roll", 0, "3", None, None)
+            .collect()
+            .await;
+        assert_eq!(seqs(all.clone()), (1..=40).collect::<Vec<_>>());
+        assert_eq!(all[0].header("Lane"), Some("3"));
+        assert_eq!(all[0].body.as_deref(), Some("event 01"));
+
+        let bounded = store.replay_stream("/q/roll", 12, "3", Some(5), None);
+        assert_eq!(seqs(bounded.collect().await), [13, 14, 15, 16, 17]);
+        let until = store.replay_stream("/q/roll", 12, "3", None, Some(21));
+        assert_eq!(seqs(until.collect().await), (13..=21).collect::<Vec<_>>());
+        let none = store.replay_stream("/q/roll", 40, "3", None, None);
+        assert!(none.collect::<Vec<_>>().await.is_empty());
+    }
+
     #[test]
     fn prune_keeps_last_n() {
         let (store, _dir) = make_store();
@@ -252,6 +1284,59 @@ mod tests {
         assert_eq!(events[2].seq, 10);
     }
 
+    #[test]
+    fn full_segments_are_sealed_and_compaction_deletes_them() {
+        let dir = TempDir::new().unwrap();
+        let options = ContinuityOptions {
+            fsync: FsyncPolicy::Never,
+            segment_bytes: 200,
+            retention: Retention {
+                max_events: 10,
+                ..Retention::default()
+            },
+            ..ContinuityOptions::default()
+        };
+        let store = ContinuityStore::with_options(dir.path(), options).unwrap();
+        for seq in 1..=40 {
+            let body = format!("event {:02}", seq);
+            store.append("/q/roll", &Event { seq, body }).unwrap();
+        }
+        store.flush().unwrap();
+        // Five 35-byte records after the magic fill a 200-byte segment.
+        let active = dir.path().join("q_roll.log");
+        let sealed = segment::sealed(&active).unwrap();
+        assert_eq!(sealed.len(), 7);
+        assert_eq!(sealed[1], segment::sealed_path(&active, 6));
+        assert_eq!(store.load("/q/roll").unwrap().len(), 40);
+
+        let compaction = store.compact("/q/roll").unwrap();
+        assert_eq!(
+            compaction,
+            Compaction {
+                removed: 30,
+                last_removed: Some(30),
+                removed_bytes: 30 * 8,
+            }
+        );
+        assert_eq!(segment::sealed(&active).unwrap().len(), 1);
+        let seqs: Vec<u64> = store
+            .load("/q/roll")
+            .unwrap()
+            .iter()
+            .map(|e| e.seq)
+            .collect();
+        assert_eq!(seqs, (31..=40).collect::<Vec<_>>());
+        assert_eq!(store.compact("/q/roll").unwrap(), Compaction::default());
+
+        let body = "after".to_string();
+        store.append("/q/roll", &Event { seq: 41, body }).unwrap();
+        assert_eq!(store.prune("/q/roll", 3).unwrap().last_removed, Some(38));
+        // 41 sealed 36..=40; what is left of it is renamed for 39.
+        let sealed = segment::sealed(&active).unwrap();
+        assert_eq!(sealed, vec![segment::sealed_path(&active, 39)]);
+        assert_eq!(store.load("/q/roll").unwrap().len(), 3);
+    }
+
     #[test]
     fn body_with_newlines_preserved() {
         let (store, _dir) = make_store();
@@ -0,0 +1,276 @@
+//! Dead-letter store for frames that could not be delivered.
+//!
+//! When a frame goes unacknowledged through every retransmission the
+//! tunnel is closed, and the frame — along with anything else still
+//! in flight on that tunnel — is parked here instead of being lost.
+//! An operator can list the parked frames, retry them once the peer
+//! is back, or purge them.
+//!
+//! Entries are kept in a TSV file, one per line:
+//!
+//! ```text
+//! <id>\t<timestamp_secs>\t<peer_id>\t<lane>\t<reason>\t<frame>\n
+//! ```
+//!
+//! Backslashes, tabs, CRs and newlines in the reason and the
+//! serialized frame are escaped so each entry stays on one line.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::protocol::error::ProtocolError;
+use crate::util::now_unix;
+
+/// A frame that could not be delivered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeadLetter {
+    /// Identifier, unique within the store.
+    pub id: u64,
+    /// When the frame was given up on (seconds since the epoch).
+    pub timestamp: u64,
+    /// The peer it was addressed to.
+    pub peer_id: String,
+    /// The lane it was sent on.
+    pub lane: u16,
+    /// Why delivery failed.
+    pub reason: String,
+    /// The serialized frame.
+    pub frame: String,
+}
+
+impl DeadLetter {
+    fn to_tsv(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.id,
+            self.timestamp,
+            self.peer_id,
+            self.lane,
+            escape(&self.reason),
+            escape(&self.frame)
+        )
+    }
+
+    fn from_tsv(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(6, '\t');
+        Some(Self {
+            id: fields.next()?.parse().ok()?,
+            timestamp: fields.next()?.parse().ok()?,
+            peer_id: fields.next()?.to_string(),
+            lane: fields.next()?.parse().ok()?,
+            reason: unescape(fields.next()?),
+            frame: unescape(fields.next()?),
+        })
+    }
+}
+
+/// Parked undeliverable frames, optionally backed by a file.
+pub struct DeadLetterStore {
+    /// Backing file; `None` keeps entries in memory only.
+    path: Option<PathBuf>,
+    entries: Mutex<Vec<DeadLetter>>,
+}
+
+impl DeadLetterStore {
+    /// A store that keeps entries in memory only.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Open the store at `path`, loading any entries already there.
+    ///
+    /// A missing file is an empty store; it is created on the first
+    /// write.  Unparseable lines are skipped.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, ProtocolError> {
+        let path = path.into();
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(text) => text.lines().filter_map(DeadLetter::from_tsv).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(ProtocolError::InternalError(format!(
+                    "failed to read dead letters {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        Ok(Self {
+            path: Some(path),
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Park a frame that could not be delivered to `peer_id`.
+    /// Returns the new entry's ID.
+    pub fn record(
+        &self,
+        peer_id: &str,
+        lane: u16,
+        frame: impl Into<String>,
+        reason: impl Into<String>,
+    ) -> Result<u64, ProtocolError> {
+        let mut entries = self.lock();
+        let entry = DeadLetter {
+            id: entries.iter().map(|e| e.id).max().unwrap_or(0) + 1,
+            timestamp: now_unix(),
+            peer_id: peer_id.to_string(),
+            lane,
+            reason: reason.into(),
+            frame: frame.into(),
+        };
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| write_error(path, e))?;
+            }
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| write_error(path, e))?;
+            writeln!(file, "{}", entry.to_tsv()).map_err(|e| write_error(path, e))?;
+        }
+        let id = entry.id;
+        entries.push(entry);
+        Ok(id)
+    }
+
+    /// All parked frames, oldest first.
+    pub fn list(&self) -> Vec<DeadLetter> {
+        self.lock().clone()
+    }
+
+    /// Number of parked frames.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no frames are parked.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Remove entry `id` so it can be sent again.
+    pub fn take(&self, id: u64) -> Result<Option<DeadLetter>, ProtocolError> {
+        let mut entries = self.lock();
+        let Some(pos) = entries.iter().position(|e| e.id == id) else {
+            return Ok(None);
+        };
+        let entry = entries.remove(pos);
+        self.rewrite(&entries)?;
+        Ok(Some(entry))
+    }
+
+    /// Drop entries parked before `before` (seconds since the epoch),
+    /// or every entry if `before` is `None`.  Returns how many were
+    /// dropped.
+    pub fn purge(&self, before: Option<u64>) -> Result<usize, ProtocolError> {
+        let mut entries = self.lock();
+        let count = entries.len();
+        entries.retain(|e| before.is_some_and(|t| e.timestamp >= t));
+        let purged = count - entries.len();
+        if purged > 0 {
+            self.rewrite(&entries)?;
+        }
+        Ok(purged)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<DeadLetter>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn rewrite(&self, entries: &[DeadLetter]) -> Result<(), ProtocolError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text: String = entries.iter().map(|e| e.to_tsv() + "\n").collect();
+        std::fs::write(path, text).map_err(|e| write_error(path, e))
+    }
+}
+
+fn write_error(path: &std::path::Path, e: std::io::Error) -> ProtocolError {
+    ProtocolError::InternalError(format!(
+        "failed to write dead letters {}: {}",
+        path.display(),
+        e
+    ))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::frame::Frame;
+
+    #[test]
+    fn entries_survive_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dead_letters.tsv");
+        let mut frame = Frame::with_args("EVENT", vec!["/q/chat".into()]);
+        frame.set_body("line one\n\tline two \\ done");
+
+        let store = DeadLetterStore::open(&path).unwrap();
+        let id = store
+            .record(
+                "ed25519:PEER",
+                3,
+                frame.serialize(),
+                "no ACK after 3 retries",
+            )
+            .unwrap();
+        store
+            .record("ed25519:PEER", 3, "PING\r\nEnd:\r\n", "tunnel closed")
+            .unwrap();
+
+        let reopened = DeadLetterStore::open(&path).unwrap();
+        let entries = reopened.list();
+        assert_eq!(entries, store.list());
+        assert_eq!(entries[0].id, id);
+        assert_eq!(Frame::parse(&entries[0].frame).unwrap(), frame);
+
+        let taken = reopened.take(id).unwrap().unwrap();
+        assert_eq!(taken.lane, 3);
+        assert!(reopened.take(id).unwrap().is_none());
+        assert_eq!(DeadLetterStore::open(&path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn purge_by_age() {
+        let store = DeadLetterStore::in_memory();
+        store.record("p", 1, "PING\r\nEnd:\r\n", "r").unwrap();
+        store.record("p", 1, "PING\r\nEnd:\r\n", "r").unwrap();
+        assert_eq!(store.purge(Some(0)).unwrap(), 0);
+        assert_eq!(store.purge(None).unwrap(), 2);
+        assert!(store.is_empty());
+    }
+}
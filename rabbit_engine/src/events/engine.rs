@@ -11,6 +11,16 @@
 //!
 //! Interior mutability (`std::sync::Mutex`) is used so the engine
 //! can be shared via `&EventEngine` (required by the dispatcher).
+//!
+//! An engine given a memory limit keeps only that many of each
+//! topic's newest events, and notes how far it has let go of older
+//! ones, which the continuity log still holds.
+//!
+//! A subscription may name a pattern rather than a topic, with `*`
+//! standing for any one level (`/q/dialogue/*`); see
+//! [`topic_matches`].  It receives the events published to every
+//! matching topic from then on, but replays none, as sequence numbers
+//! are per topic.
 
 use std::collections::HashMap;
 use std::sync::Mutex;
@@ -67,6 +77,8 @@ struct TopicState {
     subscribers: HashMap<String, SubscriberState>,
     /// Next sequence number to assign.
     next_seq: u64,
+    /// Newest event let go of to stay within the memory limit.
+    spilled_through: u64,
 }
 
 impl TopicState {
@@ -75,19 +87,52 @@ impl TopicState {
             events: Vec::new(),
             subscribers: HashMap::new(),
             next_seq: 1,
+            spilled_through: 0,
         }
     }
 
-    /// Build an EVENT frame for a given event on a topic.
-    fn event_frame(topic: &str, event: &Event, lane: &str) -> Frame {
-        let mut frame = Frame::with_args("EVENT", vec![topic.to_string()]);
-        frame.set_header("Lane", lane);
-        frame.set_header("Seq", event.seq.to_string());
-        frame.set_body(&event.body);
-        frame
+    /// Let go of the oldest events beyond `limit` (0 = no limit).
+    fn trim(&mut self, limit: usize) {
+        if limit > 0 && self.events.len() > limit {
+            let spilled = self.events.len() - limit;
+            self.spilled_through = self.events[spilled - 1].seq;
+            self.events.drain(..spilled);
+        }
     }
 }
 
+/// Whether `topic` is a pattern: whether any of its levels is `*`.
+pub fn is_pattern(topic: &str) -> bool {
+    topic.split('/').any(|level| level == "*")
+}
+
+/// Whether `topic` matches `pattern`, level by level, a `*` level
+/// matching any one non-empty level.  A pattern without a `*` matches
+/// only itself.
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    let mut levels = topic.split('/');
+    pattern.split('/').all(|part| {
+        levels
+            .next()
+            .is_some_and(|level| part == level || (part == "*" && !level.is_empty()))
+    }) && levels.next().is_none()
+}
+
+/// Build an EVENT frame for a given event on a topic.
+///
+/// Returns `None`, with a warning, if the topic or lane cannot be
+/// carried in a frame.
+pub fn event_frame(topic: &str, event: &Event, lane: &str) -> Option<Frame> {
+    Frame::builder("EVENT")
+        .selector(topic)
+        .header("Lane", lane)
+        .seq(event.seq)
+        .body_text(event.body.as_str())
+        .build()
+        .map_err(|e| tracing::warn!(topic, error = %e, "dropping unsendable event"))
+        .ok()
+}
+
 /// The pub/sub event engine.
 ///
 /// Manages topics, subscriber tracking, event logging, and broadcast
@@ -96,6 +141,11 @@ impl TopicState {
 pub struct EventEngine {
     /// Topics keyed by topic path (e.g. `/q/chat`).
     inner: Mutex<HashMap<String, TopicState>>,
+    /// Subscribers to patterns, keyed by pattern and then peer ID.
+    /// Locked after `inner` when both are.
+    patterns: Mutex<HashMap<String, HashMap<String, SubscriberState>>>,
+    /// Events kept in memory per topic (0 = all).
+    memory_limit: usize,
 }
 
 impl std::fmt::Debug for EventEngine {
@@ -109,15 +159,27 @@ impl EventEngine {
     pub fn new() -> Self {
         Self {
             inner: Mutex::new(HashMap::new()),
+            patterns: Mutex::new(HashMap::new()),
+            memory_limit: 0,
         }
     }
 
+    /// Keep only each topic's newest `limit` events in memory (0 = all).
+    ///
+    /// Only for an engine whose events are also persisted: those let
+    /// go of are replayed from there; see
+    /// [`spilled_through`](Self::spilled_through).
+    pub fn with_memory_limit(mut self, limit: usize) -> Self {
+        self.memory_limit = limit;
+        self
+    }
+
     /// Subscribe a peer to a topic.
     ///
     /// If the topic doesn't exist yet, it is created.  If `since_seq`
     /// is provided, returns EVENT frames for all events with sequence
     /// numbers strictly greater than `since_seq` (replay).  Otherwise
-    /// returns an empty vec.
+    /// returns an empty vec, as it does for a pattern.
     pub fn subscribe(
         &self,
         topic: &str,
@@ -137,20 +199,26 @@ impl EventEngine {
         since_seq: Option<u64>,
         qos: QoS,
     ) -> Vec<Frame> {
+        let subscriber = SubscriberState {
+            peer_id: peer_id.to_string(),
+            lane: lane.to_string(),
+            last_delivered_seq: since_seq.unwrap_or(0),
+            qos,
+        };
+        if is_pattern(topic) {
+            let mut patterns = self.patterns.lock().unwrap_or_else(|e| e.into_inner());
+            patterns
+                .entry(topic.to_string())
+                .or_default()
+                .insert(peer_id.to_string(), subscriber);
+            return Vec::new();
+        }
         let mut topics = self.inner.lock().unwrap_or_else(|e| e.into_inner());
         let state = topics
             .entry(topic.to_string())
             .or_insert_with(TopicState::new);
 
-        state.subscribers.insert(
-            peer_id.to_string(),
-            SubscriberState {
-                peer_id: peer_id.to_string(),
-                lane: lane.to_string(),
-                last_delivered_seq: since_seq.unwrap_or(0),
-                qos,
-            },
-        );
+        state.subscribers.insert(peer_id.to_string(), subscriber);
 
         // Replay events after since_seq
         let replay_from = since_seq.unwrap_or(0);
@@ -158,7 +226,7 @@ impl EventEngine {
             .events
             .iter()
             .filter(|e| e.seq > replay_from)
-            .map(|e| TopicState::event_frame(topic, e, lane))
+            .filter_map(|e| event_frame(topic, e, lane))
             .collect()
     }
 
@@ -166,6 +234,17 @@ impl EventEngine {
     ///
     /// Returns `true` if the peer was subscribed, `false` otherwise.
     pub fn unsubscribe(&self, topic: &str, peer_id: &str) -> bool {
+        if is_pattern(topic) {
+            let mut patterns = self.patterns.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(subscribers) = patterns.get_mut(topic) else {
+                return false;
+            };
+            let removed = subscribers.remove(peer_id).is_some();
+            if subscribers.is_empty() {
+                patterns.remove(topic);
+            }
+            return removed;
+        }
         let mut topics = self.inner.lock().unwrap_or_else(|e| e.into_inner());
         if let Some(state) = topics.get_mut(topic) {
             state.subscribers.remove(peer_id).is_some()
@@ -177,7 +256,9 @@ impl EventEngine {
     /// Publish an event to a topic.
     ///
     /// Appends the event to the topic log and returns `(peer_id, Frame)`
-    /// pairs for each active subscriber, plus the persisted [`Event`].
+    /// pairs for each active subscriber, and each subscriber to a
+    /// matching pattern not also subscribed to the topic, plus the
+    /// persisted [`Event`].
     /// The caller uses the peer IDs to route frames to the correct
     /// tunnels via the session manager.
     ///
@@ -195,20 +276,36 @@ impl EventEngine {
         state.next_seq += 1;
 
         // Build targeted broadcast frames: (peer_id, frame) for each subscriber
-        let frames: Vec<(String, Frame)> = state
+        let mut frames: Vec<(String, Frame)> = state
             .subscribers
             .values_mut()
-            .map(|sub| {
+            .filter_map(|sub| {
                 sub.last_delivered_seq = event.seq;
-                (
-                    sub.peer_id.clone(),
-                    TopicState::event_frame(topic, &event, &sub.lane),
-                )
+                let frame = event_frame(topic, &event, &sub.lane)?;
+                Some((sub.peer_id.clone(), frame))
             })
             .collect();
+        let patterns = self.patterns.lock().unwrap_or_else(|e| e.into_inner());
+        for (pattern, subscribers) in patterns.iter() {
+            if !topic_matches(pattern, topic) {
+                continue;
+            }
+            for sub in subscribers.values() {
+                if state.subscribers.contains_key(&sub.peer_id)
+                    || frames.iter().any(|(peer, _)| *peer == sub.peer_id)
+                {
+                    continue;
+                }
+                if let Some(frame) = event_frame(topic, &event, &sub.lane) {
+                    frames.push((sub.peer_id.clone(), frame));
+                }
+            }
+        }
+        drop(patterns);
 
         let event_clone = event.clone();
         state.events.push(event);
+        state.trim(self.memory_limit);
         (frames, event_clone)
     }
 
@@ -223,7 +320,7 @@ impl EventEngine {
                 .events
                 .iter()
                 .filter(|e| e.seq > since_seq)
-                .map(|e| TopicState::event_frame(topic, e, lane))
+                .filter_map(|e| event_frame(topic, e, lane))
                 .collect(),
             None => Vec::new(),
         }
@@ -235,8 +332,12 @@ impl EventEngine {
         topics.get(topic).map(|t| t.events.len()).unwrap_or(0)
     }
 
-    /// Return the number of subscribers for a topic.
+    /// Return the number of subscribers for a topic, or a pattern.
     pub fn subscriber_count(&self, topic: &str) -> usize {
+        if is_pattern(topic) {
+            let patterns = self.patterns.lock().unwrap_or_else(|e| e.into_inner());
+            return patterns.get(topic).map_or(0, HashMap::len);
+        }
         let topics = self.inner.lock().unwrap_or_else(|e| e.into_inner());
         topics.get(topic).map(|t| t.subscribers.len()).unwrap_or(0)
     }
@@ -276,18 +377,53 @@ impl EventEngine {
         let max_seq = events.iter().map(|e| e.seq).max().unwrap_or(0);
         state.events = events;
         state.next_seq = max_seq + 1;
+        state.trim(self.memory_limit);
+    }
+
+    /// Number a topic's next event after `seq`, unless it would be
+    /// already, as after events up to `seq` were pruned.
+    pub fn resume_after(&self, topic: &str, seq: u64) {
+        let mut topics = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let state = topics
+            .entry(topic.to_string())
+            .or_insert_with(TopicState::new);
+        state.next_seq = state.next_seq.max(seq + 1);
+    }
+
+    /// The newest event of a topic let go of to stay within the memory
+    /// limit, or 0 if none was.  Events up to it are not replayed from
+    /// memory.
+    pub fn spilled_through(&self, topic: &str) -> u64 {
+        let topics = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        topics.get(topic).map_or(0, |t| t.spilled_through)
     }
 
     /// Prune events for a topic, keeping only the last `keep` events.
-    pub fn prune(&self, topic: &str, keep: usize) {
+    /// Returns the events dropped.
+    pub fn prune(&self, topic: &str, keep: usize) -> Vec<Event> {
         let mut topics = self.inner.lock().unwrap_or_else(|e| e.into_inner());
-        if let Some(state) = topics.get_mut(topic) {
-            if state.events.len() > keep {
+        match topics.get_mut(topic) {
+            Some(state) if state.events.len() > keep => {
                 let drain_count = state.events.len() - keep;
-                state.events.drain(..drain_count);
+                state.events.drain(..drain_count).collect()
             }
+            _ => Vec::new(),
         }
     }
+
+    /// Drop a topic's events up to and including sequence number `seq`.
+    /// Returns the events dropped.
+    pub fn prune_through(&self, topic: &str, seq: u64) -> Vec<Event> {
+        let mut topics = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(state) = topics.get_mut(topic) else {
+            return Vec::new();
+        };
+        let (dropped, kept) = std::mem::take(&mut state.events)
+            .into_iter()
+            .partition(|e| e.seq <= seq);
+        state.events = kept;
+        dropped
+    }
 }
 
 impl Default for EventEngine {
@@ -309,6 +445,45 @@ mod tests {
         assert_eq!(engine.subscriber_count("/q/chat"), 1);
     }
 
+    #[test]
+    fn patterns_match_one_level_per_star() {
+        assert!(topic_matches("/q/dialogue/*", "/q/dialogue/alice"));
+        assert!(!topic_matches("/q/dialogue/*", "/q/dialogue"));
+        assert!(!topic_matches("/q/dialogue/*", "/q/dialogue/"));
+        assert!(!topic_matches("/q/dialogue/*", "/q/dialogue/alice/bob"));
+        assert!(topic_matches("/q/*/log", "/q/build/log"));
+        assert!(topic_matches("/q/chat", "/q/chat"));
+        assert!(!topic_matches("/q/chat", "/q/chats"));
+        assert!(is_pattern("/q/*/log"));
+        assert!(!is_pattern("/q/a*"));
+    }
+
+    #[test]
+    fn pattern_subscribers_get_each_matching_topic_once() {
+        let engine = EventEngine::new();
+        assert!(engine
+            .subscribe("/q/dialogue/*", "alice", "3", Some(0))
+            .is_empty());
+        engine.subscribe("/q/dialogue/*", "bob", "4", None);
+        engine.subscribe("/q/dialogue/bob", "bob", "5", None);
+        assert!(!engine.has_topic("/q/dialogue/*"));
+        assert_eq!(engine.subscriber_count("/q/dialogue/*"), 2);
+
+        let (frames, _) = engine.publish("/q/dialogue/bob", "hi");
+        let mut lanes: Vec<(&str, &str)> = frames
+            .iter()
+            .map(|(peer, f)| (peer.as_str(), f.header("Lane").unwrap()))
+            .collect();
+        lanes.sort();
+        assert_eq!(lanes, [("alice", "3"), ("bob", "5")]);
+        assert_eq!(frames[0].1.args[0], "/q/dialogue/bob");
+        assert!(engine.publish("/q/other", "x").0.is_empty());
+
+        assert!(engine.unsubscribe("/q/dialogue/*", "alice"));
+        assert!(!engine.unsubscribe("/q/dialogue/*", "alice"));
+        assert_eq!(engine.publish("/q/dialogue/carol", "hey").0.len(), 1);
+    }
+
     #[test]
     fn publish_creates_event() {
         let engine = EventEngine::new();
@@ -409,6 +584,20 @@ mod tests {
         assert_eq!(frames[0].header("Lane"), Some("9"));
     }
 
+    #[test]
+    fn a_memory_limit_keeps_the_newest_events() {
+        let engine = EventEngine::new().with_memory_limit(2);
+        for body in ["a", "b", "c"] {
+            let _ = engine.publish("/q/log", body);
+        }
+        assert_eq!(engine.event_count("/q/log"), 2);
+        assert_eq!(engine.spilled_through("/q/log"), 1);
+        let replayed = engine.subscribe("/q/log", "sys", "0", Some(0));
+        assert_eq!(replayed[0].body.as_deref(), Some("b"));
+        assert_eq!(engine.publish("/q/log", "d").1.seq, 4);
+        assert_eq!(engine.spilled_through("/q/log"), 2);
+    }
+
     #[test]
     fn replay_nonexistent_topic() {
         let engine = EventEngine::new();
@@ -461,7 +650,9 @@ mod tests {
             let _ = engine.publish("/q/prune", &format!("event-{}", i));
         }
         assert_eq!(engine.event_count("/q/prune"), 10);
-        engine.prune("/q/prune", 3);
+        let dropped = engine.prune("/q/prune", 3);
+        assert_eq!(dropped.len(), 7);
+        assert_eq!(dropped[6].body, "event-6");
         assert_eq!(engine.event_count("/q/prune"), 3);
         let events = engine.events("/q/prune");
         assert_eq!(events[0].seq, 8);
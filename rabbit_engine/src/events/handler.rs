@@ -1,12 +1,41 @@
-//! Frame handlers for SUBSCRIBE and PUBLISH.
+//! Frame handlers for SUBSCRIBE, UNSUBSCRIBE and PUBLISH.
 //!
 //! These are thin wrappers that translate incoming frames into calls
 //! on the [`EventEngine`](super::engine::EventEngine) and produce
 //! the appropriate response frames.
+//!
+//! A `SUBSCRIBE` may ask for the events it missed with a `Since`
+//! header: either the sequence number of the last event it saw, or an
+//! RFC 3339 time (`2026-01-01T00:00:00Z`) from which on to replay, as
+//! recorded by the continuity log.
 
 use crate::events::engine::{Event, EventEngine};
+use crate::protocol::error::ProtocolError;
 use crate::protocol::frame::Frame;
 
+/// Where replay starts, from a `SUBSCRIBE`'s `Since` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Since {
+    /// After this sequence number.
+    Seq(u64),
+    /// With the events logged at or after this Unix time.
+    Time(u64),
+}
+
+impl Since {
+    /// Parse a `Since` value: a sequence number or an RFC 3339 time.
+    /// Fails with `BadRequest` for anything else.
+    pub fn parse(value: &str) -> Result<Self, ProtocolError> {
+        let value = value.trim();
+        if let Ok(seq) = value.parse() {
+            return Ok(Self::Seq(seq));
+        }
+        parse_rfc3339(value)
+            .map(Self::Time)
+            .ok_or_else(|| ProtocolError::BadRequest(format!("invalid Since: {:?}", value)))
+    }
+}
+
 /// Handle a `PUBLISH` request.
 ///
 /// Publishes the body to the named topic and returns targeted
@@ -20,6 +49,13 @@ pub fn handle_publish(
     engine.publish(topic, body)
 }
 
+/// Handle an `UNSUBSCRIBE` request.
+///
+/// Returns whether the peer was subscribed.
+pub fn handle_unsubscribe(engine: &EventEngine, topic: &str, peer_id: &str) -> bool {
+    engine.unsubscribe(topic, peer_id)
+}
+
 /// Handle a `SUBSCRIBE` request.
 ///
 /// Subscribes the peer and returns any replay frames.
@@ -33,6 +69,91 @@ pub fn handle_subscribe(
     engine.subscribe(topic, peer_id, lane, since_seq)
 }
 
+/// Seconds since the Unix epoch of an RFC 3339 date-time, such as
+/// `2026-01-01T00:00:00Z` or `2026-01-01T09:30:00.5+09:30`.  Fractions
+/// of a second are dropped.
+fn parse_rfc3339(s: &str) -> Option<u64> {
+    let b = s.as_bytes();
+    if b.len() < 20 || !s.is_ascii() {
+        return None;
+    }
+    let num = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = &s[range];
+        digits
+            .bytes()
+            .all(|c| c.is_ascii_digit())
+            .then(|| digits.parse().ok())?
+    };
+    let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    let (hour, minute, second) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    if b[4] != b'-' || b[7] != b'-' || !matches!(b[10], b'T' | b't' | b' ') {
+        return None;
+    }
+    if b[13] != b':' || b[16] != b':' {
+        return None;
+    }
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days = [
+        31,
+        if leap { 29 } else { 28 },
+        31,
+        30,
+        31,
+        30,
+        31,
+        31,
+        30,
+        31,
+        30,
+        31,
+    ];
+    if !(1..=12).contains(&month) || day < 1 || day > month_days[month as usize - 1] {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &s[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 {
+            return None;
+        }
+        rest = &fraction[digits..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            if rest.len() != 6 || rest.as_bytes()[3] != b':' {
+                return None;
+            }
+            let (h, m) = (num(s.len() - 5..s.len() - 3)?, num(s.len() - 2..s.len())?);
+            if h > 23 || m > 59 {
+                return None;
+            }
+            sign * (h * 3600 + m * 60)
+        }
+    };
+
+    // Days from 1970-01-01 to the date (the proleptic Gregorian
+    // calendar, counted in 400-year eras from 0000-03-01).
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    u64::try_from(secs).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,6 +170,40 @@ mod tests {
         assert_eq!(event.body, "hello");
     }
 
+    #[test]
+    fn since_is_a_sequence_number_or_a_time() {
+        assert_eq!(Since::parse("42").unwrap(), Since::Seq(42));
+        assert_eq!(
+            Since::parse("2026-01-01T00:00:00Z").unwrap(),
+            Since::Time(1_767_225_600)
+        );
+        assert_eq!(
+            Since::parse("2024-02-29T12:30:15.250+02:00").unwrap(),
+            Since::Time(1_709_202_615)
+        );
+        assert_eq!(
+            Since::parse("1970-01-01T00:00:00-01:00").unwrap(),
+            Since::Time(3600)
+        );
+        for bad in [
+            "",
+            "-1",
+            "yesterday",
+            "2026-01-01",
+            "2025-02-29T00:00:00Z",
+            "2026-13-01T00:00:00Z",
+            "2026-01-01T24:00:00Z",
+            "2026-01-01T00:00:00",
+            "2026-01-01T00:00:00.Z",
+            "1969-12-31T23:59:59Z",
+        ] {
+            assert!(
+                matches!(Since::parse(bad), Err(ProtocolError::BadRequest(_))),
+                "{bad:?}"
+            );
+        }
+    }
+
     #[test]
     fn subscribe_with_replay() {
         let engine = EventEngine::new();
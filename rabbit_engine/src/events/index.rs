@@ -0,0 +1,150 @@
+//! Sparse sequence-number indexes of log segments.
+//!
+//! A [`SparseIndex`] notes the offset of a record every
+//! [`INDEX_INTERVAL`] bytes or so of a segment, so that reading the
+//! events after a sequence number can start near them instead of at
+//! the start of the file.  Indexes are built the first time a
+//! segment is sought in and kept for the life of the process; as a
+//! segment grows, its index is extended from where it left off.
+//! Whatever replaces or truncates a segment must [`forget`] its index.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::events::record::{self, MAGIC};
+use crate::protocol::error::ProtocolError;
+
+/// Bytes of records between index entries.
+pub const INDEX_INTERVAL: u64 = 4096;
+
+/// The index of each segment sought in, by path.
+static INDEXES: Mutex<BTreeMap<PathBuf, SparseIndex>> = Mutex::new(BTreeMap::new());
+
+/// The indexes, locked.
+fn indexes() -> MutexGuard<'static, BTreeMap<PathBuf, SparseIndex>> {
+    INDEXES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Offsets of some of the records of one segment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SparseIndex {
+    /// `(seq, offset)` of the indexed records, in file order.
+    entries: Vec<(u64, u64)>,
+    /// Bytes of the segment indexed so far.
+    scanned: u64,
+}
+
+impl SparseIndex {
+    /// Index the records of the segment at `path` past those already
+    /// indexed.  A segment in the old line format is not indexed.
+    pub fn extend(&mut self, path: &Path) -> Result<(), ProtocolError> {
+        let failed = |e: std::io::Error| {
+            ProtocolError::InternalError(format!("failed to index log {}: {}", path.display(), e))
+        };
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                *self = Self::default();
+                return Ok(());
+            }
+            Err(e) => return Err(failed(e)),
+        };
+        let len = file.metadata().map_err(failed)?.len();
+        if len < self.scanned {
+            *self = Self::default();
+        }
+        if self.scanned == 0 {
+            let mut magic = [0u8; MAGIC.len()];
+            if file.read_exact(&mut magic).is_err() || &magic != MAGIC {
+                return Ok(());
+            }
+            self.scanned = MAGIC.len() as u64;
+        }
+        let mut rest = Vec::new();
+        file.seek(SeekFrom::Start(self.scanned))
+            .and_then(|_| file.read_to_end(&mut rest))
+            .map_err(failed)?;
+        let mut at = 0;
+        while let Some((found, used)) = record::decode(&rest[at..]) {
+            let offset = self.scanned + at as u64;
+            let due = self
+                .entries
+                .last()
+                .is_none_or(|&(_, last)| offset - last >= INDEX_INTERVAL);
+            if due {
+                self.entries.push((found.seq, offset));
+            }
+            at += used;
+        }
+        self.scanned += at as u64;
+        Ok(())
+    }
+
+    /// The offset to start reading at for the records after `seq`:
+    /// that of the last indexed record at or before `seq + 1`, or of
+    /// the first record.  `None` if nothing is indexed.
+    pub fn offset_after(&self, seq: u64) -> Option<u64> {
+        let first = self.entries.first()?.1;
+        let before = self
+            .entries
+            .partition_point(|&(s, _)| s <= seq.saturating_add(1));
+        Some(before.checked_sub(1).map_or(first, |i| self.entries[i].1))
+    }
+}
+
+/// Where in the segment at `path` to start reading for the records
+/// after `seq`; see [`SparseIndex::offset_after`].
+pub fn seek(path: &Path, seq: u64) -> Result<Option<u64>, ProtocolError> {
+    let mut indexes = indexes();
+    let index = indexes.entry(path.to_path_buf()).or_default();
+    index.extend(path)?;
+    Ok(index.offset_after(seq))
+}
+
+/// Drop the index of the segment at `path`, which has been replaced,
+/// truncated or removed.
+pub fn forget(path: &Path) {
+    indexes().remove(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::record::{write_log, LogRecord};
+
+    #[test]
+    fn seeks_land_at_or_before_the_wanted_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("q_big.log");
+        let body = "x".repeat(1000);
+        let records: Vec<LogRecord> = (1..=40)
+            .map(|seq| LogRecord {
+                seq,
+                timestamp: seq,
+                lane: 0,
+                body: body.clone(),
+                prev_hash: None,
+                signature: None,
+                origin: None,
+            })
+            .collect();
+        write_log(&path, &records).unwrap();
+        let len = records[0].encoded_len() as u64;
+
+        let mut index = SparseIndex::default();
+        index.extend(&path).unwrap();
+        // 1027-byte records: every fourth is indexed.
+        assert_eq!(index.entries.len(), 10);
+        assert_eq!(index.offset_after(0), Some(MAGIC.len() as u64));
+        assert_eq!(index.offset_after(20), Some(8 + 20 * len));
+        assert_eq!(index.offset_after(22), Some(8 + 20 * len));
+
+        let bytes = std::fs::read(&path).unwrap();
+        let at = seek(&path, 22).unwrap().unwrap() as usize;
+        assert_eq!(record::decode(&bytes[at..]).unwrap().0.seq, 21);
+        forget(&path);
+    }
+}
@@ -2,9 +2,32 @@
 //!
 //! Topics are managed by the [`EventEngine`](engine::EventEngine),
 //! persistence is handled by the
-//! [`ContinuityStore`](continuity::ContinuityStore), and incoming
-//! `SUBSCRIBE`/`PUBLISH` frames are processed by the handler module.
+//! [`ContinuityStore`](continuity::ContinuityStore) in logs of
+//! checksummed [records](record) split into [segments](segment) and
+//! [indexed](index) by sequence number, or by another
+//! [`EventStore`](store::EventStore) backend, storage limits are enforced
+//! by the [`QuotaManager`](quota::QuotaManager), and
+//! incoming `SUBSCRIBE`/`PUBLISH` frames are processed by the handler
+//! module.  Each topic's events are kept within its
+//! [retention](retention) limits by the janitor.  Frames that could
+//! not be delivered are parked in the
+//! [`DeadLetterStore`](dead_letter::DeadLetterStore), and topics
+//! followed on other burrows are copied in by the
+//! [`Replicator`](replication::Replicator).  How far each subscriber
+//! has acknowledged each topic is kept in the
+//! [`ConsumerOffsets`](offsets::ConsumerOffsets).
 
 pub mod continuity;
+pub mod dead_letter;
 pub mod engine;
 pub mod handler;
+pub mod index;
+pub mod offsets;
+pub mod quota;
+pub mod record;
+pub mod replication;
+pub mod retention;
+pub mod segment;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
@@ -0,0 +1,280 @@
+//! How far each subscriber has got through each topic.
+//!
+//! Every EVENT sent to a subscriber is noted with the tunnel, lane and
+//! `Seq` it went out on, and when the subscriber acknowledges the lane
+//! up to that `Seq` on the same tunnel, the event's number in its topic
+//! becomes the subscriber's offset there.  Lane numbers are per tunnel,
+//! so a subscriber with two tunnels open keeps their deliveries apart.  A subscriber that reconnects and sends
+//! `SUBSCRIBE` without `Since` is replayed what it has not
+//! acknowledged, so it need not remember its own position.  Only
+//! cumulative acknowledgements count, so an offset never skips an
+//! event that went missing.
+//!
+//! Offsets are kept in a TSV file, one line per subscriber and topic:
+//!
+//! ```text
+//! <subscriber>\t<topic>\t<seq>\n
+//! ```
+//!
+//! written by [`ConsumerOffsets::save`] when a tunnel closes and when
+//! the burrow shuts down.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::protocol::error::ProtocolError;
+
+/// Events noted per subscriber, tunnel and lane while awaiting an ACK;
+/// the oldest are forgotten beyond this.
+const MAX_IN_FLIGHT: usize = 4096;
+
+/// Events awaiting an ACK on one lane, by frame `Seq`: their topic and
+/// number there.
+type Pending = BTreeMap<u64, (String, u64)>;
+
+#[derive(Default)]
+struct Offsets {
+    /// Last acknowledged sequence number, by subscriber and topic.
+    acked: BTreeMap<(String, String), u64>,
+    /// Events sent and not yet acknowledged, by subscriber, tunnel and
+    /// lane.
+    in_flight: HashMap<(String, u64, u16), Pending>,
+    /// Whether `acked` has changed since it was saved.
+    dirty: bool,
+}
+
+/// The last event each subscriber has acknowledged in each topic.
+pub struct ConsumerOffsets {
+    /// Backing file; `None` keeps offsets in memory only.
+    path: Option<PathBuf>,
+    inner: Mutex<Offsets>,
+}
+
+impl ConsumerOffsets {
+    /// Offsets kept in memory only.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            inner: Mutex::new(Offsets::default()),
+        }
+    }
+
+    /// Offsets kept in the file at `path`, which is read if it exists.
+    /// Malformed lines are skipped.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, ProtocolError> {
+        let path = path.into();
+        let mut offsets = Offsets::default();
+        if path.exists() {
+            let text = std::fs::read_to_string(&path).map_err(|e| {
+                ProtocolError::InternalError(format!(
+                    "failed to read consumer offsets {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            for line in text.lines() {
+                let mut fields = line.splitn(3, '\t');
+                let (Some(subscriber), Some(topic), Some(Ok(seq))) = (
+                    fields.next(),
+                    fields.next(),
+                    fields.next().map(str::parse::<u64>),
+                ) else {
+                    continue;
+                };
+                offsets
+                    .acked
+                    .insert((subscriber.to_string(), topic.to_string()), seq);
+            }
+        }
+        Ok(Self {
+            path: Some(path),
+            inner: Mutex::new(offsets),
+        })
+    }
+
+    /// The last event of `topic` that `subscriber` acknowledged, if
+    /// any.
+    pub fn get(&self, topic: &str, subscriber: &str) -> Option<u64> {
+        let key = (subscriber.to_string(), topic.to_string());
+        self.inner().acked.get(&key).copied()
+    }
+
+    /// Every topic `subscriber` has an offset in, with the offset.
+    pub fn list(&self, subscriber: &str) -> Vec<(String, u64)> {
+        self.inner()
+            .acked
+            .iter()
+            .filter(|((s, _), _)| s == subscriber)
+            .map(|((_, topic), seq)| (topic.clone(), *seq))
+            .collect()
+    }
+
+    /// Move `subscriber`'s offset in `topic` up to `seq`.  Offsets
+    /// never move back.
+    pub fn record(&self, topic: &str, subscriber: &str, seq: u64) {
+        Self::raise(&mut self.inner(), topic, subscriber, seq);
+    }
+
+    /// Note that event `event_seq` of `topic` went to `subscriber` on
+    /// `lane` of its tunnel `tunnel` as frame `seq`.
+    pub fn sent(
+        &self,
+        subscriber: &str,
+        tunnel: u64,
+        lane: u16,
+        seq: u64,
+        topic: &str,
+        event_seq: u64,
+    ) {
+        let mut inner = self.inner();
+        let pending = inner
+            .in_flight
+            .entry((subscriber.to_string(), tunnel, lane))
+            .or_default();
+        pending.insert(seq, (topic.to_string(), event_seq));
+        if pending.len() > MAX_IN_FLIGHT {
+            pending.pop_first();
+        }
+    }
+
+    /// Take `subscriber`'s acknowledgement of every frame on `lane` of
+    /// tunnel `tunnel` up to `cumulative`, moving its offsets past the
+    /// events among them.
+    pub fn acked(&self, subscriber: &str, tunnel: u64, lane: u16, cumulative: u64) {
+        let mut inner = self.inner();
+        let key = (subscriber.to_string(), tunnel, lane);
+        let Some(pending) = inner.in_flight.get_mut(&key) else {
+            return;
+        };
+        let still_pending = pending.split_off(&(cumulative.saturating_add(1)));
+        let acked = std::mem::replace(pending, still_pending);
+        if pending.is_empty() {
+            inner.in_flight.remove(&key);
+        }
+        for (topic, event_seq) in acked.into_values() {
+            Self::raise(&mut inner, &topic, subscriber, event_seq);
+        }
+    }
+
+    /// Forget what was sent to `subscriber` on tunnel `tunnel` and not
+    /// acknowledged, as when that tunnel closes.  Its other tunnels
+    /// are left alone.
+    pub fn disconnected(&self, subscriber: &str, tunnel: u64) {
+        self.inner()
+            .in_flight
+            .retain(|(s, t, _), _| s != subscriber || *t != tunnel);
+    }
+
+    /// Write the offsets to the backing file, if they have changed.
+    pub fn save(&self) -> Result<(), ProtocolError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut inner = self.inner();
+        if !inner.dirty {
+            return Ok(());
+        }
+        let text: String = inner
+            .acked
+            .iter()
+            .map(|((subscriber, topic), seq)| format!("{}\t{}\t{}\n", subscriber, topic, seq))
+            .collect();
+        let tmp = path.with_extension("tsv.tmp");
+        std::fs::write(&tmp, text)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| {
+                ProtocolError::InternalError(format!(
+                    "failed to write consumer offsets {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        inner.dirty = false;
+        Ok(())
+    }
+
+    fn inner(&self) -> MutexGuard<'_, Offsets> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn raise(inner: &mut Offsets, topic: &str, subscriber: &str, seq: u64) {
+        let offset = inner
+            .acked
+            .entry((subscriber.to_string(), topic.to_string()))
+            .or_insert(0);
+        if seq > *offset {
+            *offset = seq;
+            inner.dirty = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cumulative_acks_move_offsets_past_the_events_they_cover() {
+        let offsets = ConsumerOffsets::in_memory();
+        // Lane 2 carries two topics; frame Seqs are the lane's own.
+        offsets.sent("ed25519:ELM", 1, 2, 1, "/q/chat", 40);
+        offsets.sent("ed25519:ELM", 1, 2, 2, "/q/news", 7);
+        offsets.sent("ed25519:ELM", 1, 2, 3, "/q/chat", 41);
+        offsets.sent("ed25519:OAK", 1, 2, 1, "/q/chat", 40);
+
+        offsets.acked("ed25519:ELM", 1, 2, 2);
+        assert_eq!(offsets.get("/q/chat", "ed25519:ELM"), Some(40));
+        assert_eq!(offsets.get("/q/news", "ed25519:ELM"), Some(7));
+        assert_eq!(offsets.get("/q/chat", "ed25519:OAK"), None);
+
+        // Acknowledging again, or on another lane, changes nothing.
+        offsets.acked("ed25519:ELM", 1, 2, 2);
+        offsets.acked("ed25519:ELM", 1, 3, 9);
+        assert_eq!(offsets.get("/q/chat", "ed25519:ELM"), Some(40));
+
+        // What was in flight when the tunnel closed is never counted.
+        offsets.disconnected("ed25519:ELM", 1);
+        offsets.acked("ed25519:ELM", 1, 2, 3);
+        assert_eq!(offsets.get("/q/chat", "ed25519:ELM"), Some(40));
+
+        offsets.record("/q/chat", "ed25519:ELM", 12);
+        assert_eq!(
+            offsets.list("ed25519:ELM"),
+            [("/q/chat".to_string(), 40), ("/q/news".to_string(), 7)]
+        );
+    }
+
+    #[test]
+    fn each_tunnel_of_a_subscriber_is_acknowledged_and_closed_apart() {
+        let offsets = ConsumerOffsets::in_memory();
+        // Two tunnels from one peer both number lane 1 from 1.
+        offsets.sent("ed25519:ELM", 7, 1, 1, "/q/chat", 10);
+        offsets.sent("ed25519:ELM", 8, 1, 1, "/q/news", 3);
+        offsets.sent("ed25519:ELM", 8, 1, 2, "/q/chat", 11);
+
+        // An ACK on one tunnel covers only what that tunnel sent.
+        offsets.acked("ed25519:ELM", 7, 1, 1);
+        assert_eq!(offsets.get("/q/chat", "ed25519:ELM"), Some(10));
+        assert_eq!(offsets.get("/q/news", "ed25519:ELM"), None);
+
+        // Closing the first tunnel leaves the second's deliveries.
+        offsets.disconnected("ed25519:ELM", 7);
+        offsets.acked("ed25519:ELM", 8, 1, 2);
+        assert_eq!(offsets.get("/q/news", "ed25519:ELM"), Some(3));
+        assert_eq!(offsets.get("/q/chat", "ed25519:ELM"), Some(11));
+    }
+
+    #[test]
+    fn offsets_survive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("offsets.tsv");
+        let offsets = ConsumerOffsets::open(&path).unwrap();
+        offsets.record("/q/chat", "ed25519:ELM", 5);
+        offsets.save().unwrap();
+        drop(offsets);
+
+        let offsets = ConsumerOffsets::open(&path).unwrap();
+        assert_eq!(offsets.get("/q/chat", "ed25519:ELM"), Some(5));
+    }
+}
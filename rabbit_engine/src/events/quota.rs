@@ -0,0 +1,549 @@
+//! Storage quotas for published events.
+//!
+//! The [`QuotaManager`] tracks how many events (and how many body
+//! bytes) each publishing peer and each topic has accumulated in the
+//! continuity log.  Every `PUBLISH` is charged against both scopes
+//! before it reaches the event engine:
+//!
+//! * Crossing the **soft limit** (a percentage of the hard limit)
+//!   yields a [`QuotaWarning`] which the dispatcher publishes to the
+//!   operator topic.  Each scope warns once per crossing.
+//! * Exceeding the **hard limit** rejects the publish with
+//!   `507 QUOTA-EXCEEDED` and leaves usage untouched.
+//!
+//! A limit of `0` means unlimited.  Topic usage is seeded from the
+//! continuity log on startup and released as events are pruned or
+//! compacted away.  Each charged event is attributed to its publisher
+//! so that the same drops credit the peer back; per-peer usage is not
+//! persisted because the log does not record the publisher.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::config::QuotaConfig;
+use crate::protocol::error::ProtocolError;
+
+/// Byte and event limits for a single quota scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QuotaLimit {
+    /// Maximum accumulated body bytes (0 = unlimited).
+    pub max_bytes: u64,
+    /// Maximum accumulated events (0 = unlimited).
+    pub max_events: u64,
+}
+
+impl QuotaLimit {
+    /// Create a limit from byte and event maxima.
+    pub fn new(max_bytes: u64, max_events: u64) -> Self {
+        Self {
+            max_bytes,
+            max_events,
+        }
+    }
+
+    /// Returns true if neither resource is limited.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_bytes == 0 && self.max_events == 0
+    }
+}
+
+/// Accumulated usage for a single quota scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QuotaUsage {
+    /// Total body bytes charged.
+    pub bytes: u64,
+    /// Total events charged.
+    pub events: u64,
+}
+
+/// A soft-limit crossing, reported to the operator topic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaWarning {
+    /// The scope that crossed its soft limit (`peer <id>` or `topic <path>`).
+    pub scope: String,
+    /// The resource that crossed (`bytes` or `events`).
+    pub resource: &'static str,
+    /// Usage after the charge.
+    pub used: u64,
+    /// The configured hard limit.
+    pub limit: u64,
+}
+
+impl QuotaWarning {
+    /// Render the warning as an event body for the operator topic.
+    pub fn to_body(&self) -> String {
+        format!(
+            "quota soft limit: {} {} {}/{}",
+            self.scope, self.resource, self.used, self.limit
+        )
+    }
+}
+
+/// Mutable accounting state, guarded by a single mutex so that a
+/// publish is checked and charged atomically across both scopes.
+#[derive(Debug, Default)]
+struct QuotaState {
+    peers: HashMap<String, QuotaUsage>,
+    topics: HashMap<String, QuotaUsage>,
+    /// Topic → event seq → `(publisher, bytes)` for events charged to
+    /// a peer, so their release credits the right one.
+    publishers: HashMap<String, BTreeMap<u64, (String, u64)>>,
+    /// `(scope, resource)` pairs that have already warned.
+    warned: HashSet<(String, &'static str)>,
+}
+
+/// Per-peer and per-topic storage quota accounting.
+pub struct QuotaManager {
+    /// Limit applied to every publishing peer.
+    peer_limit: QuotaLimit,
+    /// Limit applied to topics without an override.
+    topic_limit: QuotaLimit,
+    /// Per-topic limit overrides.
+    topic_overrides: HashMap<String, QuotaLimit>,
+    /// Soft-limit threshold as a percentage of the hard limit
+    /// (0 or ≥ 100 disables warnings).
+    soft_limit_percent: u8,
+    /// Topic that receives soft-limit warnings.
+    operator_topic: String,
+    state: Mutex<QuotaState>,
+}
+
+impl std::fmt::Debug for QuotaManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuotaManager")
+            .field("peer_limit", &self.peer_limit)
+            .field("topic_limit", &self.topic_limit)
+            .field("topic_overrides", &self.topic_overrides.len())
+            .field("soft_limit_percent", &self.soft_limit_percent)
+            .finish()
+    }
+}
+
+impl Default for QuotaManager {
+    fn default() -> Self {
+        Self::from_config(&QuotaConfig::default())
+    }
+}
+
+impl QuotaManager {
+    /// Create a quota manager with quotas disabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a quota manager from the `[quota]` config section.
+    pub fn from_config(config: &QuotaConfig) -> Self {
+        let topic_overrides = config
+            .topics
+            .iter()
+            .map(|t| (t.path.clone(), QuotaLimit::new(t.max_bytes, t.max_events)))
+            .collect();
+        Self {
+            peer_limit: QuotaLimit::new(config.peer_max_bytes, config.peer_max_events),
+            topic_limit: QuotaLimit::new(config.topic_max_bytes, config.topic_max_events),
+            topic_overrides,
+            soft_limit_percent: config.soft_limit_percent,
+            operator_topic: config.operator_topic.clone(),
+            state: Mutex::new(QuotaState::default()),
+        }
+    }
+
+    /// Returns true if any limit is configured.
+    pub fn is_enabled(&self) -> bool {
+        !self.peer_limit.is_unlimited()
+            || !self.topic_limit.is_unlimited()
+            || self.topic_overrides.values().any(|l| !l.is_unlimited())
+    }
+
+    /// The topic that receives soft-limit warnings.
+    pub fn operator_topic(&self) -> &str {
+        &self.operator_topic
+    }
+
+    /// The limit applied to every publishing peer.
+    pub fn peer_limit(&self) -> QuotaLimit {
+        self.peer_limit
+    }
+
+    /// The effective limit for `topic` (override or default).
+    pub fn topic_limit(&self, topic: &str) -> QuotaLimit {
+        self.topic_overrides
+            .get(topic)
+            .copied()
+            .unwrap_or(self.topic_limit)
+    }
+
+    /// Charge one event of `bytes` body bytes to `peer_id` and `topic`.
+    ///
+    /// Returns the soft-limit warnings triggered by this charge, or
+    /// `507 QUOTA-EXCEEDED` if either scope would exceed its hard
+    /// limit — in which case nothing is charged.
+    pub fn charge(
+        &self,
+        peer_id: &str,
+        topic: &str,
+        bytes: u64,
+    ) -> Result<Vec<QuotaWarning>, ProtocolError> {
+        let topic_limit = self.topic_limit(topic);
+        let peer_scope = format!("peer {}", peer_id);
+        let topic_scope = format!("topic {}", topic);
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let peer_before = state.peers.get(peer_id).copied().unwrap_or_default();
+        let topic_before = state.topics.get(topic).copied().unwrap_or_default();
+        let peer_after = add(peer_before, bytes);
+        let topic_after = add(topic_before, bytes);
+
+        check_hard(&peer_scope, self.peer_limit, peer_after)?;
+        check_hard(&topic_scope, topic_limit, topic_after)?;
+
+        state.peers.insert(peer_id.to_string(), peer_after);
+        state.topics.insert(topic.to_string(), topic_after);
+
+        let mut warnings = Vec::new();
+        for (scope, limit, after) in [
+            (peer_scope, self.peer_limit, peer_after),
+            (topic_scope, topic_limit, topic_after),
+        ] {
+            for (resource, used, max) in [
+                ("bytes", after.bytes, limit.max_bytes),
+                ("events", after.events, limit.max_events),
+            ] {
+                if self.over_soft(used, max) && state.warned.insert((scope.clone(), resource)) {
+                    warnings.push(QuotaWarning {
+                        scope: scope.clone(),
+                        resource,
+                        used,
+                        limit: max,
+                    });
+                }
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Seed a topic's usage from events already in the continuity log.
+    pub fn seed_topic(&self, topic: &str, bytes: u64, events: u64) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = state.topics.entry(topic.to_string()).or_default();
+        usage.bytes += bytes;
+        usage.events += events;
+    }
+
+    /// Release usage for events dropped from a topic, so that it can
+    /// take new ones in their place.  A resource brought back under
+    /// its soft limit warns again the next time it crosses it.
+    pub fn release_topic(&self, topic: &str, bytes: u64, events: u64) {
+        let limit = self.topic_limit(topic);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(usage) = state.topics.get_mut(topic) else {
+            return;
+        };
+        usage.bytes = usage.bytes.saturating_sub(bytes);
+        usage.events = usage.events.saturating_sub(events);
+        let after = *usage;
+        self.rearm(&mut state, &format!("topic {}", topic), limit, after);
+    }
+
+    /// Attribute event `seq` of `topic`, already charged to `peer_id`
+    /// for `bytes` body bytes, to its publisher.
+    pub fn record_publisher(&self, topic: &str, seq: u64, peer_id: &str, bytes: u64) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .publishers
+            .entry(topic.to_string())
+            .or_default()
+            .insert(seq, (peer_id.to_string(), bytes));
+    }
+
+    /// Release per-peer usage for the events of `topic` numbered up to
+    /// and including `through_seq`, crediting each back to the peer
+    /// that published it.  Like [`release_topic`](Self::release_topic),
+    /// a resource brought back under its soft limit re-arms its warning.
+    pub fn release_publishers(&self, topic: &str, through_seq: u64) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(published) = state.publishers.get_mut(topic) else {
+            return;
+        };
+        let kept = match through_seq.checked_add(1) {
+            Some(next) => published.split_off(&next),
+            None => BTreeMap::new(),
+        };
+        let dropped = std::mem::replace(published, kept);
+        if published.is_empty() {
+            state.publishers.remove(topic);
+        }
+        for (peer_id, bytes) in dropped.into_values() {
+            let Some(usage) = state.peers.get_mut(&peer_id) else {
+                continue;
+            };
+            usage.bytes = usage.bytes.saturating_sub(bytes);
+            usage.events = usage.events.saturating_sub(1);
+            let after = *usage;
+            self.rearm(
+                &mut state,
+                &format!("peer {}", peer_id),
+                self.peer_limit,
+                after,
+            );
+        }
+    }
+
+    /// Current usage charged to `peer_id`.
+    pub fn peer_usage(&self, peer_id: &str) -> QuotaUsage {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.peers.get(peer_id).copied().unwrap_or_default()
+    }
+
+    /// Current usage charged to `topic`.
+    pub fn topic_usage(&self, topic: &str) -> QuotaUsage {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.topics.get(topic).copied().unwrap_or_default()
+    }
+
+    /// Render a usage report, one scope per line:
+    /// `scope\tid\tbytes\tevents\tmax_bytes\tmax_events`.
+    pub fn status_body(&self) -> String {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut peers: Vec<_> = state.peers.iter().collect();
+        peers.sort_by(|a, b| a.0.cmp(b.0));
+        let mut topics: Vec<_> = state.topics.iter().collect();
+        topics.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut body = String::new();
+        for (id, usage) in peers {
+            body.push_str(&status_line("peer", id, *usage, self.peer_limit));
+        }
+        for (topic, usage) in topics {
+            body.push_str(&status_line(
+                "topic",
+                topic,
+                *usage,
+                self.topic_limit(topic),
+            ));
+        }
+        body
+    }
+
+    /// Let `scope` warn again for each resource no longer over its soft
+    /// threshold.
+    fn rearm(&self, state: &mut QuotaState, scope: &str, limit: QuotaLimit, after: QuotaUsage) {
+        for (resource, used, max) in [
+            ("bytes", after.bytes, limit.max_bytes),
+            ("events", after.events, limit.max_events),
+        ] {
+            if !self.over_soft(used, max) {
+                state.warned.remove(&(scope.to_string(), resource));
+            }
+        }
+    }
+
+    /// Whether `used` has reached the soft threshold of `max`.
+    fn over_soft(&self, used: u64, max: u64) -> bool {
+        if max == 0 || self.soft_limit_percent == 0 || self.soft_limit_percent >= 100 {
+            return false;
+        }
+        used * 100 >= max * self.soft_limit_percent as u64
+    }
+}
+
+fn add(usage: QuotaUsage, bytes: u64) -> QuotaUsage {
+    QuotaUsage {
+        bytes: usage.bytes + bytes,
+        events: usage.events + 1,
+    }
+}
+
+fn check_hard(scope: &str, limit: QuotaLimit, after: QuotaUsage) -> Result<(), ProtocolError> {
+    for (resource, used, max) in [
+        ("bytes", after.bytes, limit.max_bytes),
+        ("events", after.events, limit.max_events),
+    ] {
+        if max > 0 && used > max {
+            return Err(ProtocolError::QuotaExceeded {
+                scope: scope.to_string(),
+                resource: resource.to_string(),
+                used,
+                limit: max,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn status_line(kind: &str, id: &str, usage: QuotaUsage, limit: QuotaLimit) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\r\n",
+        kind, id, usage.bytes, usage.events, limit.max_bytes, limit.max_events
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TopicQuotaConfig;
+
+    fn config() -> QuotaConfig {
+        QuotaConfig {
+            peer_max_bytes: 100,
+            topic_max_events: 5,
+            soft_limit_percent: 80,
+            ..QuotaConfig::default()
+        }
+    }
+
+    #[test]
+    fn default_is_disabled() {
+        let q = QuotaManager::new();
+        assert!(!q.is_enabled());
+        for _ in 0..100 {
+            assert!(q.charge("p", "/q/t", 1000).unwrap().is_empty());
+        }
+        assert_eq!(q.topic_usage("/q/t").events, 100);
+    }
+
+    #[test]
+    fn charges_both_scopes() {
+        let q = QuotaManager::from_config(&config());
+        q.charge("alice", "/q/chat", 10).unwrap();
+        q.charge("alice", "/q/other", 5).unwrap();
+        assert_eq!(
+            q.peer_usage("alice"),
+            QuotaUsage {
+                bytes: 15,
+                events: 2
+            }
+        );
+        assert_eq!(
+            q.topic_usage("/q/chat"),
+            QuotaUsage {
+                bytes: 10,
+                events: 1
+            }
+        );
+    }
+
+    #[test]
+    fn hard_limit_rejects_without_charging() {
+        let q = QuotaManager::from_config(&config());
+        q.charge("alice", "/q/a", 90).unwrap();
+        let err = q.charge("alice", "/q/b", 20).unwrap_err();
+        assert_eq!(err.status_code(), 507);
+        assert_eq!(q.peer_usage("alice").bytes, 90);
+        assert_eq!(q.topic_usage("/q/b"), QuotaUsage::default());
+    }
+
+    #[test]
+    fn topic_event_limit() {
+        let q = QuotaManager::from_config(&config());
+        for i in 0..5 {
+            q.charge(&format!("p{}", i), "/q/chat", 1).unwrap();
+        }
+        match q.charge("p9", "/q/chat", 1).unwrap_err() {
+            ProtocolError::QuotaExceeded {
+                scope,
+                resource,
+                used,
+                limit,
+            } => {
+                assert_eq!(scope, "topic /q/chat");
+                assert_eq!(resource, "events");
+                assert_eq!(used, 6);
+                assert_eq!(limit, 5);
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn soft_limit_warns_once() {
+        let q = QuotaManager::from_config(&config());
+        assert!(q.charge("alice", "/q/a", 70).unwrap().is_empty());
+        let warnings = q.charge("alice", "/q/a", 10).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].scope, "peer alice");
+        assert_eq!(warnings[0].resource, "bytes");
+        assert_eq!(warnings[0].used, 80);
+        assert!(warnings[0].to_body().contains("peer alice bytes 80/100"));
+        assert!(q.charge("alice", "/q/a", 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn topic_override_takes_precedence() {
+        let mut cfg = config();
+        cfg.topics.push(TopicQuotaConfig {
+            path: "/q/big".into(),
+            max_bytes: 0,
+            max_events: 0,
+        });
+        let q = QuotaManager::from_config(&cfg);
+        assert!(q.topic_limit("/q/big").is_unlimited());
+        assert_eq!(q.topic_limit("/q/chat").max_events, 5);
+        for i in 0..10 {
+            q.charge(&format!("p{}", i), "/q/big", 1).unwrap();
+        }
+    }
+
+    #[test]
+    fn seeded_usage_counts_toward_limit() {
+        let q = QuotaManager::from_config(&config());
+        q.seed_topic("/q/chat", 40, 5);
+        assert!(q.charge("alice", "/q/chat", 1).is_err());
+    }
+
+    #[test]
+    fn released_usage_makes_room_and_rearms_the_warning() {
+        let q = QuotaManager::from_config(&config());
+        for i in 0..4 {
+            q.charge(&format!("p{}", i), "/q/chat", 1).unwrap();
+        }
+        assert_eq!(q.charge("p4", "/q/chat", 1).unwrap().len(), 0);
+        assert!(q.charge("p5", "/q/chat", 1).is_err());
+
+        q.release_topic("/q/chat", 3, 3);
+        assert_eq!(q.topic_usage("/q/chat").events, 2);
+        q.charge("p5", "/q/chat", 1).unwrap();
+        let warnings = q.charge("p6", "/q/chat", 1).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].scope, "topic /q/chat");
+
+        q.release_topic("/q/chat", 100, 100);
+        assert_eq!(q.topic_usage("/q/chat"), QuotaUsage::default());
+    }
+
+    #[test]
+    fn released_publishers_credit_each_peer() {
+        let q = QuotaManager::from_config(&config());
+        q.charge("alice", "/q/a", 60).unwrap();
+        q.record_publisher("/q/a", 1, "alice", 60);
+        q.charge("bob", "/q/a", 10).unwrap();
+        q.record_publisher("/q/a", 2, "bob", 10);
+        q.charge("alice", "/q/a", 30).unwrap();
+        q.record_publisher("/q/a", 3, "alice", 30);
+        assert!(q.charge("alice", "/q/a", 20).is_err());
+
+        q.release_publishers("/q/a", 2);
+        assert_eq!(
+            q.peer_usage("alice"),
+            QuotaUsage {
+                bytes: 30,
+                events: 1
+            }
+        );
+        assert_eq!(q.peer_usage("bob"), QuotaUsage::default());
+        let warnings = q.charge("alice", "/q/a", 50).unwrap();
+        assert!(warnings.iter().any(|w| w.scope == "peer alice"));
+
+        q.release_publishers("/q/a", u64::MAX);
+        assert_eq!(q.peer_usage("alice").bytes, 50);
+    }
+
+    #[test]
+    fn status_body_lists_scopes() {
+        let q = QuotaManager::from_config(&config());
+        q.charge("alice", "/q/chat", 12).unwrap();
+        let body = q.status_body();
+        assert!(body.contains("peer\talice\t12\t1\t100\t0\r\n"));
+        assert!(body.contains("topic\t/q/chat\t12\t1\t0\t5\r\n"));
+    }
+}
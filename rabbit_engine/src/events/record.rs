@@ -0,0 +1,544 @@
+//! Checksummed binary records of the continuity log.
+//!
+//! A log file starts with the 8-byte magic `RABBITL2`, followed by one
+//! record per event, integers little-endian:
+//!
+//! ```text
+//! <length: u32> <crc32: u32> <seq: u64> <timestamp: u64> <lane: u16>
+//!     <flags: u8> [<prev_hash: 32 bytes>] [<signature: 64 bytes>]
+//!     [<origin_seq: u64> <origin_len: u16> <origin: origin_len bytes>] <body>
+//! ```
+//!
+//! `length` counts the bytes after the checksum, and the checksum is
+//! the CRC-32 (IEEE) of those bytes, so a body may hold any text.
+//! Bit 0 of `flags` says the record holds the [hash](LogRecord::hash)
+//! of the record before it, chaining the log, and bit 1 that it holds
+//! an Ed25519 signature of its own hash.  Bit 2 says the event was
+//! [replicated](crate::events::replication) from another burrow, and
+//! the record names that burrow and the event's number there.  A
+//! record cut short by a crash, or whose checksum does not match, ends
+//! the log: [`read_log`] returns the records before it and how many
+//! bytes follow them, and [`recover`] cuts those bytes off before a
+//! writer appends to the file again.
+//!
+//! Logs written before this format are still read, and [`recover`]
+//! rewrites them in it: `RABBITL1` logs, whose records have neither
+//! flags nor the fields they announce, and before them one
+//! `<seq>\t<timestamp>\t<body>` line per event with tabs and newlines
+//! in the body escaped.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::events::engine::Event;
+use crate::events::index;
+use crate::protocol::error::ProtocolError;
+
+/// The bytes every log file starts with.
+pub const MAGIC: &[u8; 8] = b"RABBITL2";
+
+/// The magic of logs whose records have no flags.
+const MAGIC_V1: &[u8; 8] = b"RABBITL1";
+
+/// Bytes before a record's checksummed part: length and checksum.
+const HEADER_LEN: usize = 8;
+
+/// Bytes of a record's checksummed part besides the body and the
+/// fields its flags announce.
+const FIXED_LEN: usize = 8 + 8 + 2 + 1;
+
+/// Bytes of a `RABBITL1` record's checksummed part besides the body.
+const FIXED_LEN_V1: usize = 8 + 8 + 2;
+
+/// Flag: the record holds the hash of the one before it.
+const CHAINED: u8 = 1;
+
+/// Flag: the record holds a signature of its hash.
+const SIGNED: u8 = 2;
+
+/// Flag: the record names the burrow the event was replicated from.
+const ORIGIN: u8 = 4;
+
+/// The hash a log's first record chains from.
+pub const GENESIS_HASH: [u8; 32] = [0; 32];
+
+/// Largest record [`decode`] accepts, so a corrupt length cannot ask
+/// for an absurd allocation.
+pub const MAX_RECORD_LEN: usize = 64 * 1024 * 1024;
+
+/// Where a replicated event was first logged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The burrow it was replicated from.
+    pub burrow: String,
+    /// Its sequence number in that burrow's log.
+    pub seq: u64,
+}
+
+/// One event as logged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// Sequence number within the topic.
+    pub seq: u64,
+    /// When it was logged (Unix seconds).
+    pub timestamp: u64,
+    /// The lane it was published on.
+    pub lane: u16,
+    /// The event body.
+    pub body: String,
+    /// The hash of the record before it, if the log is chained.
+    pub prev_hash: Option<[u8; 32]>,
+    /// An Ed25519 signature of its hash, if signed.
+    pub signature: Option<[u8; 64]>,
+    /// Where it was replicated from, if it was.
+    pub origin: Option<Origin>,
+}
+
+impl LogRecord {
+    /// The record of `event`, published on `lane` and logged at
+    /// `timestamp`.
+    pub fn new(event: &Event, lane: u16, timestamp: u64) -> Self {
+        Self {
+            seq: event.seq,
+            timestamp,
+            lane,
+            body: event.body.clone(),
+            prev_hash: None,
+            signature: None,
+            origin: None,
+        }
+    }
+
+    /// The SHA-256 hash of everything the record says but its
+    /// signature: the hash a signature signs and the next record in a
+    /// chained log holds.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.seq.to_le_bytes());
+        hasher.update(self.timestamp.to_le_bytes());
+        hasher.update(self.lane.to_le_bytes());
+        hasher.update(self.prev_hash.unwrap_or(GENESIS_HASH));
+        if let Some(origin) = &self.origin {
+            hasher.update(origin.seq.to_le_bytes());
+            hasher.update((origin.burrow.len() as u16).to_le_bytes());
+            hasher.update(origin.burrow.as_bytes());
+        }
+        hasher.update(self.body.as_bytes());
+        hasher.finalize().into()
+    }
+
+    /// The event it records.
+    pub fn to_event(&self) -> Event {
+        Event {
+            seq: self.seq,
+            body: self.body.clone(),
+        }
+    }
+
+    /// How many bytes [`encode`](Self::encode) gives.
+    pub fn encoded_len(&self) -> usize {
+        let chain = self.prev_hash.map_or(0, |h| h.len());
+        let signature = self.signature.map_or(0, |s| s.len());
+        let origin = self.origin.as_ref().map_or(0, |o| 8 + 2 + o.burrow.len());
+        HEADER_LEN + FIXED_LEN + chain + signature + origin + self.body.len()
+    }
+
+    /// The record's bytes, header included.
+    pub fn encode(&self) -> Vec<u8> {
+        let len = self.encoded_len() - HEADER_LEN;
+        let mut flags = 0;
+        if self.prev_hash.is_some() {
+            flags |= CHAINED;
+        }
+        if self.signature.is_some() {
+            flags |= SIGNED;
+        }
+        if self.origin.is_some() {
+            flags |= ORIGIN;
+        }
+        let mut out = Vec::with_capacity(HEADER_LEN + len);
+        out.extend_from_slice(&(len as u32).to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&self.seq.to_le_bytes());
+        out.extend_from_slice(&self.timestamp.to_le_bytes());
+        out.extend_from_slice(&self.lane.to_le_bytes());
+        out.push(flags);
+        if let Some(prev) = &self.prev_hash {
+            out.extend_from_slice(prev);
+        }
+        if let Some(signature) = &self.signature {
+            out.extend_from_slice(signature);
+        }
+        if let Some(origin) = &self.origin {
+            out.extend_from_slice(&origin.seq.to_le_bytes());
+            out.extend_from_slice(&(origin.burrow.len() as u16).to_le_bytes());
+            out.extend_from_slice(origin.burrow.as_bytes());
+        }
+        out.extend_from_slice(self.body.as_bytes());
+        let crc = crc32(&out[HEADER_LEN..]);
+        out[4..HEADER_LEN].copy_from_slice(&crc.to_le_bytes());
+        out
+    }
+}
+
+/// Decode the record at the start of `buf`, returning it and the bytes
+/// it took, or `None` if it is incomplete or corrupt.
+pub fn decode(buf: &[u8]) -> Option<(LogRecord, usize)> {
+    decode_as(buf, true)
+}
+
+/// Decode a record with flags if `flagged`, or else a `RABBITL1` one.
+fn decode_as(buf: &[u8], flagged: bool) -> Option<(LogRecord, usize)> {
+    let header = buf.get(..HEADER_LEN)?;
+    let len = u32::from_le_bytes(header[..4].try_into().ok()?) as usize;
+    let crc = u32::from_le_bytes(header[4..].try_into().ok()?);
+    let fixed = if flagged { FIXED_LEN } else { FIXED_LEN_V1 };
+    if !(fixed..=MAX_RECORD_LEN).contains(&len) {
+        return None;
+    }
+    let data = buf.get(HEADER_LEN..HEADER_LEN + len)?;
+    if crc32(data) != crc {
+        return None;
+    }
+    let flags = if flagged { data[18] } else { 0 };
+    let mut rest = &data[fixed..];
+    let mut take = |n: usize| {
+        let (field, after) = (rest.get(..n)?, rest.get(n..)?);
+        rest = after;
+        Some(field)
+    };
+    let prev_hash = match flags & CHAINED {
+        0 => None,
+        _ => Some(take(32)?.try_into().ok()?),
+    };
+    let signature = match flags & SIGNED {
+        0 => None,
+        _ => Some(take(64)?.try_into().ok()?),
+    };
+    let origin = match flags & ORIGIN {
+        0 => None,
+        _ => {
+            let seq = u64::from_le_bytes(take(8)?.try_into().ok()?);
+            let len = u16::from_le_bytes(take(2)?.try_into().ok()?);
+            let burrow = String::from_utf8(take(len as usize)?.to_vec()).ok()?;
+            Some(Origin { burrow, seq })
+        }
+    };
+    let record = LogRecord {
+        seq: u64::from_le_bytes(data[..8].try_into().ok()?),
+        timestamp: u64::from_le_bytes(data[8..16].try_into().ok()?),
+        lane: u16::from_le_bytes(data[16..18].try_into().ok()?),
+        body: String::from_utf8(rest.to_vec()).ok()?,
+        prev_hash,
+        signature,
+        origin,
+    };
+    Some((record, HEADER_LEN + len))
+}
+
+/// What [`read_log`] found in a log file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogContents {
+    /// Every intact record, oldest first.
+    pub records: Vec<LogRecord>,
+    /// Bytes after the last intact record, left by a torn write or
+    /// corruption.
+    pub torn_bytes: u64,
+    /// Whether the file is in an older format: `RABBITL1` records or
+    /// lines.
+    pub legacy: bool,
+}
+
+/// Read the log at `path`; a missing file reads as empty.
+pub fn read_log(path: &Path) -> Result<LogContents, ProtocolError> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(LogContents::default()),
+        Err(e) => {
+            return Err(ProtocolError::InternalError(format!(
+                "failed to read log {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    if bytes.is_empty() {
+        return Ok(LogContents::default());
+    }
+    let (mut rest, flagged) = if let Some(rest) = bytes.strip_prefix(MAGIC.as_slice()) {
+        (rest, true)
+    } else if let Some(rest) = bytes.strip_prefix(MAGIC_V1.as_slice()) {
+        (rest, false)
+    } else {
+        if MAGIC.starts_with(&bytes) || MAGIC_V1.starts_with(&bytes) {
+            // A new log torn before its first record.
+            return Ok(LogContents {
+                torn_bytes: bytes.len() as u64,
+                ..LogContents::default()
+            });
+        }
+        return Ok(LogContents {
+            records: parse_legacy(&String::from_utf8_lossy(&bytes)),
+            torn_bytes: 0,
+            legacy: true,
+        });
+    };
+    let mut records = Vec::new();
+    while let Some((record, used)) = decode_as(rest, flagged) {
+        records.push(record);
+        rest = &rest[used..];
+    }
+    Ok(LogContents {
+        records,
+        torn_bytes: rest.len() as u64,
+        legacy: !flagged,
+    })
+}
+
+/// Read the records of the log at `path` from byte `offset`, which must
+/// be where a record starts; a missing file reads as empty.
+pub fn read_log_from(path: &Path, offset: u64) -> Result<LogContents, ProtocolError> {
+    if offset == 0 {
+        return read_log(path);
+    }
+    let failed = |e: std::io::Error| {
+        ProtocolError::InternalError(format!("failed to read log {}: {}", path.display(), e))
+    };
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(LogContents::default()),
+        Err(e) => return Err(failed(e)),
+    };
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_to_end(&mut bytes))
+        .map_err(failed)?;
+    let mut rest = bytes.as_slice();
+    let mut records = Vec::new();
+    while let Some((record, used)) = decode(rest) {
+        records.push(record);
+        rest = &rest[used..];
+    }
+    Ok(LogContents {
+        records,
+        torn_bytes: rest.len() as u64,
+        legacy: false,
+    })
+}
+
+/// Write `records` to `path` as a complete log, replacing the file
+/// only once it is written and synced.
+pub fn write_log(path: &Path, records: &[LogRecord]) -> Result<(), ProtocolError> {
+    let failed = |e: std::io::Error| {
+        ProtocolError::InternalError(format!("failed to rewrite log {}: {}", path.display(), e))
+    };
+    let temp = path.with_extension("log.tmp");
+    let mut file = File::create(&temp).map_err(failed)?;
+    let mut bytes = MAGIC.to_vec();
+    for record in records {
+        bytes.extend_from_slice(&record.encode());
+    }
+    file.write_all(&bytes)
+        .and_then(|()| file.sync_data())
+        .map_err(failed)?;
+    index::forget(path);
+    std::fs::rename(&temp, path).map_err(failed)
+}
+
+/// Make the log at `path` safe to append to: rewrite a log in the old
+/// line format, cut off the bytes of a torn write, and start a missing
+/// or empty log with the magic.  Returns what was found.
+pub fn recover(path: &Path) -> Result<LogContents, ProtocolError> {
+    let contents = read_log(path)?;
+    let empty = std::fs::metadata(path).map_or(true, |m| m.len() == 0);
+    if contents.legacy || empty || (contents.records.is_empty() && contents.torn_bytes > 0) {
+        write_log(path, &contents.records)?;
+    } else if contents.torn_bytes > 0 {
+        index::forget(path);
+        let len = std::fs::metadata(path)
+            .map(|m| m.len())
+            .unwrap_or_default()
+            .saturating_sub(contents.torn_bytes);
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_len(len))
+            .map_err(|e| {
+                ProtocolError::InternalError(format!(
+                    "failed to truncate log {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+    }
+    Ok(contents)
+}
+
+/// The records of a log in the old line format; malformed lines are
+/// skipped.
+fn parse_legacy(text: &str) -> Vec<LogRecord> {
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let seq = parts.next()?.parse().ok()?;
+            let timestamp = parts.next()?.parse().ok()?;
+            let body = parts.next()?.replace("\\n", "\n").replace("\\t", "\t");
+            Some(LogRecord {
+                seq,
+                timestamp,
+                lane: 0,
+                body,
+                prev_hash: None,
+                signature: None,
+                origin: None,
+            })
+        })
+        .collect()
+}
+
+/// The CRC-32 (IEEE 802.3) of `bytes`.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut c = i as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                k += 1;
+            }
+            table[i] = c;
+            i += 1;
+        }
+        table
+    };
+    !bytes.iter().fold(!0u32, |crc, &b| {
+        TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(seq: u64, body: &str) -> LogRecord {
+        LogRecord {
+            seq,
+            timestamp: 1000 + seq,
+            lane: 3,
+            body: body.into(),
+            prev_hash: None,
+            signature: None,
+            origin: None,
+        }
+    }
+
+    #[test]
+    fn crc32_matches_the_standard_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn records_round_trip_and_corruption_is_caught() {
+        let original = record(7, "tabs\tand\nnewlines\\n survive");
+        let bytes = original.encode();
+        assert_eq!(decode(&bytes), Some((original, bytes.len())));
+        assert_eq!(decode(&bytes[..bytes.len() - 1]), None);
+        let mut flipped = bytes.clone();
+        flipped[20] ^= 1;
+        assert_eq!(decode(&flipped), None);
+    }
+
+    #[test]
+    fn chained_and_signed_records_round_trip() {
+        let first = record(1, "one");
+        let mut second = record(2, "two");
+        second.prev_hash = Some(first.hash());
+        second.signature = Some([7; 64]);
+        let bytes = second.encode();
+        assert_eq!(bytes.len(), second.encoded_len());
+        assert_eq!(decode(&bytes), Some((second.clone(), bytes.len())));
+        // The hash covers the chain but not the signature.
+        let mut unsigned = second.clone();
+        unsigned.signature = None;
+        assert_eq!(unsigned.hash(), second.hash());
+        assert_ne!(second.hash(), record(2, "two").hash());
+    }
+
+    #[test]
+    fn replicated_records_keep_their_origin() {
+        let mut replicated = record(4, "copied");
+        replicated.prev_hash = Some(record(3, "three").hash());
+        replicated.origin = Some(Origin {
+            burrow: "ed25519:OAK".into(),
+            seq: 90,
+        });
+        let bytes = replicated.encode();
+        assert_eq!(bytes.len(), replicated.encoded_len());
+        assert_eq!(decode(&bytes), Some((replicated.clone(), bytes.len())));
+        // The origin is part of what a signature covers.
+        assert_ne!(replicated.hash(), {
+            let mut local = replicated.clone();
+            local.origin = None;
+            local.hash()
+        });
+    }
+
+    #[test]
+    fn records_without_flags_are_read_and_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("q_old.log");
+        let mut bytes = MAGIC_V1.to_vec();
+        for (seq, body) in [(1u64, "one"), (2, "two")] {
+            let mut data = seq.to_le_bytes().to_vec();
+            data.extend_from_slice(&(100 + seq).to_le_bytes());
+            data.extend_from_slice(&4u16.to_le_bytes());
+            data.extend_from_slice(body.as_bytes());
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&crc32(&data).to_le_bytes());
+            bytes.extend_from_slice(&data);
+        }
+        std::fs::write(&path, &bytes).unwrap();
+
+        let found = recover(&path).unwrap();
+        assert!(found.legacy);
+        assert_eq!(found.records[1].body, "two");
+        assert_eq!(found.records[1].lane, 4);
+        let rewritten = read_log(&path).unwrap();
+        assert!(!rewritten.legacy);
+        assert_eq!(rewritten.records, found.records);
+    }
+
+    #[test]
+    fn torn_tails_are_cut_and_legacy_logs_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("q_chat.log");
+        write_log(&path, &[record(1, "one"), record(2, "two")]).unwrap();
+        let mut torn = std::fs::read(&path).unwrap();
+        let whole = torn.len() as u64;
+        torn.extend_from_slice(&record(3, "three").encode()[..10]);
+        std::fs::write(&path, &torn).unwrap();
+
+        let found = recover(&path).unwrap();
+        assert_eq!(found.records.len(), 2);
+        assert_eq!(found.torn_bytes, 10);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), whole);
+
+        std::fs::write(&path, "1\t100\tfirst\\tline\n2\t101\tsecond\n").unwrap();
+        let found = recover(&path).unwrap();
+        assert!(found.legacy);
+        let rewritten = read_log(&path).unwrap();
+        assert!(!rewritten.legacy);
+        assert_eq!(rewritten.records[0].body, "first\tline");
+        assert_eq!(rewritten.records[1].timestamp, 101);
+    }
+}
@@ -0,0 +1,356 @@
+//! Following topics on other burrows.
+//!
+//! A burrow follows a topic on another burrow by sending it
+//! `REPLICATE <topic>` with a `Since` header once a tunnel to it is
+//! up.  The other burrow answers `201 REPLICATING` and sends, as
+//! EVENT frames on the lane asked for, every event it has logged
+//! after that sequence number and then each new one, numbered by
+//! `Event-Seq` where the lane's own `Seq` replaces it.  The follower
+//! publishes each event to its own subscribers and appends it to its
+//! event store with its [`Origin`]: the burrow it came from and its
+//! sequence number there.
+//!
+//! How far each followed topic has been replicated is kept in a TSV
+//! file, one line per topic:
+//!
+//! ```text
+//! <peer_id>\t<topic>\t<seq>\n
+//! ```
+//!
+//! and is checked against the log the first time it is needed, so a
+//! follower that reconnects or restarts asks only for what it missed.
+//! Events delivered again at or below that point are dropped.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::config::ReplicationConfig;
+use crate::events::engine::EventEngine;
+use crate::events::record::Origin;
+use crate::events::store::{append_in_place, EventStore};
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::{Frame, Verb, VerbKind};
+
+/// A topic followed on another burrow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Follow {
+    /// Burrow ID of the burrow followed.
+    pub peer: String,
+    /// The topic followed.
+    pub topic: String,
+    /// Where to dial the burrow, if configured.
+    pub address: Option<String>,
+}
+
+/// How far each followed topic has been replicated.
+#[derive(Default)]
+struct Cursors {
+    /// Sequence numbers reached, by peer and topic.
+    seqs: BTreeMap<(String, String), u64>,
+    /// Those checked against the continuity log.
+    checked: HashSet<(String, String)>,
+}
+
+/// The topics a burrow follows, and how far each has got.
+pub struct Replicator {
+    follows: Vec<Follow>,
+    /// The lane replicated events are asked for on.
+    lane: u16,
+    /// Backing file; `None` keeps progress in memory only.
+    path: Option<PathBuf>,
+    cursors: Mutex<Cursors>,
+}
+
+impl Replicator {
+    /// A replicator following what `config` says, keeping its
+    /// progress in memory only.
+    pub fn in_memory(config: &ReplicationConfig) -> Self {
+        Self {
+            follows: config
+                .follow
+                .iter()
+                .map(|f| Follow {
+                    peer: f.peer.clone(),
+                    topic: f.topic.clone(),
+                    address: f.address.clone(),
+                })
+                .collect(),
+            lane: config.lane,
+            path: None,
+            cursors: Mutex::new(Cursors::default()),
+        }
+    }
+
+    /// A replicator following what `config` says, with its progress
+    /// kept in the file at `path`, which is read if it exists.
+    /// Malformed lines are skipped.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        config: &ReplicationConfig,
+    ) -> Result<Self, ProtocolError> {
+        let path = path.into();
+        let mut replicator = Self::in_memory(config);
+        if path.exists() {
+            let text = std::fs::read_to_string(&path).map_err(|e| {
+                ProtocolError::InternalError(format!(
+                    "failed to read replication state {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            let seqs = &mut replicator
+                .cursors
+                .get_mut()
+                .unwrap_or_else(|e| e.into_inner())
+                .seqs;
+            for line in text.lines() {
+                let mut fields = line.splitn(3, '\t');
+                let (Some(peer), Some(topic), Some(Ok(seq))) = (
+                    fields.next(),
+                    fields.next(),
+                    fields.next().map(str::parse::<u64>),
+                ) else {
+                    continue;
+                };
+                seqs.insert((peer.to_string(), topic.to_string()), seq);
+            }
+        }
+        replicator.path = Some(path);
+        Ok(replicator)
+    }
+
+    /// The topics followed.
+    pub fn follows(&self) -> &[Follow] {
+        &self.follows
+    }
+
+    /// The configured addresses of the burrows followed, sorted.
+    pub fn dial_addresses(&self) -> Vec<String> {
+        let mut addresses: Vec<String> = self
+            .follows
+            .iter()
+            .filter_map(|f| f.address.clone())
+            .collect();
+        addresses.sort();
+        addresses.dedup();
+        addresses
+    }
+
+    /// Whether `topic` is followed on `peer_id`.
+    pub fn follows_topic(&self, peer_id: &str, topic: &str) -> bool {
+        self.follows
+            .iter()
+            .any(|f| f.peer == peer_id && f.topic == topic)
+    }
+
+    /// The sequence number `topic` has been replicated from `peer_id`
+    /// through, or 0.
+    pub fn position(&self, peer_id: &str, topic: &str, continuity: Option<&dyn EventStore>) -> u64 {
+        self.cursor(&mut self.cursors(), peer_id, topic, continuity)
+    }
+
+    /// The `REPLICATE` requests for every topic followed on `peer_id`,
+    /// each asking for the events after those already replicated.
+    pub fn requests(&self, peer_id: &str, continuity: Option<&dyn EventStore>) -> Vec<Frame> {
+        let mut cursors = self.cursors();
+        self.follows
+            .iter()
+            .filter(|f| f.peer == peer_id)
+            .filter_map(|f| {
+                let since = self.cursor(&mut cursors, peer_id, &f.topic, continuity);
+                Frame::builder("REPLICATE")
+                    .selector(&f.topic)
+                    .header("Lane", self.lane.to_string())
+                    .header("Since", since.to_string())
+                    .build()
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Take in an EVENT from `peer_id` on a topic followed there:
+    /// publish it to `events` and append it to `continuity`, unless it
+    /// has been replicated already.  Returns the frames for local
+    /// subscribers, or `None` if the frame is not a replicated event.
+    pub fn accept(
+        &self,
+        frame: &Frame,
+        peer_id: &str,
+        events: &EventEngine,
+        continuity: Option<&dyn EventStore>,
+    ) -> Option<Vec<(String, Frame)>> {
+        if frame.verb_kind() != VerbKind::Verb(Verb::Event) {
+            return None;
+        }
+        let topic = frame.args.first()?;
+        if !self.follows_topic(peer_id, topic) {
+            return None;
+        }
+        let seq = frame.header("Event-Seq").or_else(|| frame.header("Seq"));
+        let Some(seq) = seq.and_then(|s| s.parse::<u64>().ok()) else {
+            tracing::warn!(peer_id, topic = %topic, "replicated event without a Seq dropped");
+            return Some(Vec::new());
+        };
+        let mut cursors = self.cursors();
+        let reached = self.cursor(&mut cursors, peer_id, topic, continuity);
+        if seq <= reached {
+            tracing::debug!(peer_id, topic = %topic, seq, "replicated event already logged");
+            return Some(Vec::new());
+        }
+        let lane = frame
+            .header("Lane")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(self.lane);
+        let (broadcast, event) = events.publish(topic, frame.body.as_deref().unwrap_or(""));
+        if let Some(cont) = continuity {
+            let origin = Origin {
+                burrow: peer_id.to_string(),
+                seq,
+            };
+            let appended = append_in_place(|| cont.append_replicated(topic, lane, &event, origin));
+            if let Err(e) = appended {
+                tracing::warn!(topic = %topic, error = %e, "continuity append failed");
+            }
+        }
+        cursors
+            .seqs
+            .insert((peer_id.to_string(), topic.clone()), seq);
+        if let Err(e) = self.save(&cursors) {
+            tracing::warn!(error = %e, "could not save replication state");
+        }
+        Some(broadcast)
+    }
+
+    fn cursors(&self) -> MutexGuard<'_, Cursors> {
+        self.cursors.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// How far `topic` has been replicated from `peer_id`: the further
+    /// of what was saved and what its log holds, checked once.
+    fn cursor(
+        &self,
+        cursors: &mut Cursors,
+        peer_id: &str,
+        topic: &str,
+        continuity: Option<&dyn EventStore>,
+    ) -> u64 {
+        let key = (peer_id.to_string(), topic.to_string());
+        let saved = cursors.seqs.get(&key).copied().unwrap_or(0);
+        if cursors.checked.contains(&key) {
+            return saved;
+        }
+        let logged = continuity.map_or(0, |cont| {
+            cont.last_replicated(topic, peer_id).unwrap_or_else(|e| {
+                tracing::warn!(topic, error = %e, "could not read replicated events");
+                0
+            })
+        });
+        let seq = saved.max(logged);
+        cursors.seqs.insert(key.clone(), seq);
+        cursors.checked.insert(key);
+        seq
+    }
+
+    fn save(&self, cursors: &Cursors) -> Result<(), ProtocolError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text: String = cursors
+            .seqs
+            .iter()
+            .map(|((peer, topic), seq)| format!("{}\t{}\t{}\n", peer, topic, seq))
+            .collect();
+        let tmp = path.with_extension("tsv.tmp");
+        std::fs::write(&tmp, text)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| {
+                ProtocolError::InternalError(format!(
+                    "failed to write replication state {}: {}",
+                    path.display(),
+                    e
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FollowConfig;
+    use crate::events::continuity::ContinuityStore;
+    use crate::events::engine::{event_frame, Event};
+
+    fn config() -> ReplicationConfig {
+        ReplicationConfig {
+            lane: 2,
+            follow: vec![FollowConfig {
+                peer: "ed25519:OAK".into(),
+                topic: "/q/chat".into(),
+                address: Some("oak:7443".into()),
+            }],
+        }
+    }
+
+    fn event(seq: u64) -> Frame {
+        let event = Event {
+            seq,
+            body: format!("event {}", seq),
+        };
+        event_frame("/q/chat", &event, "2").unwrap()
+    }
+
+    #[test]
+    fn replicated_events_are_logged_once_with_their_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        let cont = ContinuityStore::new(dir.path().join("events")).unwrap();
+        let events = EventEngine::new();
+        let replicator = Replicator::in_memory(&config());
+        assert_eq!(replicator.dial_addresses(), ["oak:7443"]);
+
+        let request = &replicator.requests("ed25519:OAK", Some(&cont))[0];
+        assert_eq!(request.verb, "REPLICATE");
+        assert_eq!(request.header("Since"), Some("0"));
+        assert_eq!(request.header("Lane"), Some("2"));
+        assert!(replicator.requests("ed25519:ELM", Some(&cont)).is_empty());
+
+        for seq in [10, 11, 11, 10] {
+            let accepted = replicator.accept(&event(seq), "ed25519:OAK", &events, Some(&cont));
+            assert_eq!(accepted, Some(Vec::new()));
+        }
+        // Only followed topics, from the burrow followed, are taken.
+        assert_eq!(
+            replicator.accept(&event(12), "ed25519:ELM", &events, Some(&cont)),
+            None
+        );
+
+        let records = cont.records("/q/chat").unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[0].seq, records[1].seq), (1, 2));
+        let origin = records[1].origin.as_ref().unwrap();
+        assert_eq!((origin.burrow.as_str(), origin.seq), ("ed25519:OAK", 11));
+        assert_eq!(
+            replicator.position("ed25519:OAK", "/q/chat", Some(&cont)),
+            11
+        );
+    }
+
+    #[test]
+    fn progress_survives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("replication.tsv");
+        let cont = ContinuityStore::new(dir.path().join("events")).unwrap();
+        let events = EventEngine::new();
+        let replicator = Replicator::open(&state, &config()).unwrap();
+        replicator.accept(&event(5), "ed25519:OAK", &events, Some(&cont));
+        drop(replicator);
+
+        let again = Replicator::open(&state, &config()).unwrap();
+        let request = &again.requests("ed25519:OAK", None)[0];
+        assert_eq!(request.header("Since"), Some("5"));
+
+        // Without the saved state the log says as much.
+        let fresh = Replicator::in_memory(&config());
+        assert_eq!(fresh.position("ed25519:OAK", "/q/chat", Some(&cont)), 5);
+    }
+}
@@ -0,0 +1,322 @@
+//! Per-topic retention, and the janitor that enforces it.
+//!
+//! Every topic keeps events within the `[continuity]` limits unless it
+//! has limits of its own, from `[[continuity.topics]]` or set at run
+//! time by a `TOPIC-CONFIG` frame.  A topic's own limits replace the
+//! defaults whole, so one with no limits at all keeps its events
+//! forever.  Limits set at run time take precedence over configured
+//! ones and are kept in a TSV file, one line per topic:
+//!
+//! ```text
+//! <topic>\t<max_events>\t<max_age_secs>\t<max_bytes>\n
+//! ```
+//!
+//! Every `compact_secs` the janitor, [`sweep`], drops each topic's
+//! oldest events beyond its limits from the event store and from
+//! memory, and releases the quota they were charged.  Without a store,
+//! only `max_events` can be enforced, since the engine does not know
+//! when its events were published.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::config::TopicRetentionConfig;
+use crate::events::engine::EventEngine;
+use crate::events::quota::QuotaManager;
+use crate::events::segment::Retention;
+use crate::events::store::EventStore;
+use crate::protocol::error::ProtocolError;
+
+/// Where a topic's retention limits come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionSource {
+    /// The `[continuity]` defaults.
+    Default,
+    /// A `[[continuity.topics]]` entry.
+    Config,
+    /// A `TOPIC-CONFIG` frame.
+    Set,
+}
+
+impl RetentionSource {
+    /// The source as the `Retention` header names it.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Config => "config",
+            Self::Set => "set",
+        }
+    }
+}
+
+/// The retention limits of every topic.
+pub struct RetentionPolicies {
+    /// Limits of topics without their own.
+    default: Retention,
+    /// Limits from `[[continuity.topics]]`.
+    configured: BTreeMap<String, Retention>,
+    /// Backing file for the limits set at run time; `None` keeps them
+    /// in memory only.
+    path: Option<PathBuf>,
+    /// Limits set at run time.
+    set: Mutex<BTreeMap<String, Retention>>,
+}
+
+impl RetentionPolicies {
+    /// Policies keeping topics within `default` unless `topics` gives
+    /// them limits of their own, with those set at run time kept in
+    /// memory only.
+    pub fn in_memory(default: Retention, topics: &[TopicRetentionConfig]) -> Self {
+        Self {
+            default,
+            configured: topics
+                .iter()
+                .map(|t| {
+                    let retention = Retention {
+                        max_events: t.max_events,
+                        max_age_secs: t.max_age_secs,
+                        max_bytes: t.max_bytes,
+                    };
+                    (t.path.clone(), retention)
+                })
+                .collect(),
+            path: None,
+            set: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Policies as [`in_memory`](Self::in_memory), with the limits set
+    /// at run time kept in the file at `path`, which is read if it
+    /// exists.  Malformed lines are skipped.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        default: Retention,
+        topics: &[TopicRetentionConfig],
+    ) -> Result<Self, ProtocolError> {
+        let path = path.into();
+        let mut policies = Self::in_memory(default, topics);
+        if path.exists() {
+            let text = std::fs::read_to_string(&path).map_err(|e| {
+                ProtocolError::InternalError(format!(
+                    "failed to read retention settings {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            let set = policies.set.get_mut().unwrap_or_else(|e| e.into_inner());
+            for line in text.lines() {
+                let fields: Vec<&str> = line.split('\t').collect();
+                let [topic, max_events, max_age_secs, max_bytes] = fields[..] else {
+                    continue;
+                };
+                let (Ok(max_events), Ok(max_age_secs), Ok(max_bytes)) =
+                    (max_events.parse(), max_age_secs.parse(), max_bytes.parse())
+                else {
+                    continue;
+                };
+                let retention = Retention {
+                    max_events,
+                    max_age_secs,
+                    max_bytes,
+                };
+                set.insert(topic.to_string(), retention);
+            }
+        }
+        policies.path = Some(path);
+        Ok(policies)
+    }
+
+    /// The limits `topic` is kept within, and where they come from.
+    pub fn get(&self, topic: &str) -> (Retention, RetentionSource) {
+        if let Some(retention) = self.set().get(topic) {
+            return (*retention, RetentionSource::Set);
+        }
+        match self.configured.get(topic) {
+            Some(retention) => (*retention, RetentionSource::Config),
+            None => (self.default, RetentionSource::Default),
+        }
+    }
+
+    /// Keep `topic` within `retention` from now on, replacing any
+    /// limits it had.
+    pub fn set_topic(&self, topic: &str, retention: Retention) -> Result<(), ProtocolError> {
+        let mut set = self.set();
+        set.insert(topic.to_string(), retention);
+        self.save(&set)
+    }
+
+    /// Drop the limits set at run time for `topic`, returning it to
+    /// its configured ones or the defaults.
+    pub fn reset_topic(&self, topic: &str) -> Result<(), ProtocolError> {
+        let mut set = self.set();
+        if set.remove(topic).is_none() {
+            return Ok(());
+        }
+        self.save(&set)
+    }
+
+    fn set(&self) -> MutexGuard<'_, BTreeMap<String, Retention>> {
+        self.set.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, set: &BTreeMap<String, Retention>) -> Result<(), ProtocolError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text: String = set
+            .iter()
+            .map(|(topic, r)| {
+                format!(
+                    "{}\t{}\t{}\t{}\n",
+                    topic, r.max_events, r.max_age_secs, r.max_bytes
+                )
+            })
+            .collect();
+        let tmp = path.with_extension("tsv.tmp");
+        std::fs::write(&tmp, text)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| {
+                ProtocolError::InternalError(format!(
+                    "failed to write retention settings {}: {}",
+                    path.display(),
+                    e
+                ))
+            })
+    }
+}
+
+/// Drop each topic's oldest events beyond its limits, from `store` and
+/// from `events`, releasing their topic's and their publishers' quota
+/// in `quotas`.  Returns how many were dropped.
+pub fn sweep(
+    events: &EventEngine,
+    store: Option<&dyn EventStore>,
+    policies: &RetentionPolicies,
+    quotas: &QuotaManager,
+) -> usize {
+    let mut removed = 0;
+    for topic in events.topics() {
+        let (retention, _) = policies.get(&topic);
+        if retention == Retention::default() {
+            continue;
+        }
+        let Some(store) = store else {
+            if retention.max_events > 0 {
+                let dropped = events.prune(&topic, retention.max_events);
+                let bytes = dropped.iter().map(|e| e.body.len() as u64).sum();
+                quotas.release_topic(&topic, bytes, dropped.len() as u64);
+                if let Some(last) = dropped.iter().map(|e| e.seq).max() {
+                    quotas.release_publishers(&topic, last);
+                }
+                removed += dropped.len();
+            }
+            continue;
+        };
+        match store.compact_with(&topic, retention) {
+            Ok(compaction) => {
+                if let Some(seq) = compaction.last_removed {
+                    events.prune_through(&topic, seq);
+                    quotas.release_topic(
+                        &topic,
+                        compaction.removed_bytes,
+                        compaction.removed as u64,
+                    );
+                    quotas.release_publishers(&topic, seq);
+                    tracing::info!(topic = %topic, removed = compaction.removed, "compacted event log");
+                }
+                removed += compaction.removed;
+            }
+            Err(e) => tracing::warn!(topic = %topic, error = %e, "event log compaction failed"),
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::continuity::ContinuityStore;
+
+    fn defaults() -> Retention {
+        Retention {
+            max_events: 3,
+            ..Retention::default()
+        }
+    }
+
+    fn topics() -> Vec<TopicRetentionConfig> {
+        vec![
+            TopicRetentionConfig {
+                path: "/q/presence".into(),
+                max_events: 1,
+                max_age_secs: 0,
+                max_bytes: 0,
+            },
+            TopicRetentionConfig {
+                path: "/q/archive".into(),
+                max_events: 0,
+                max_age_secs: 0,
+                max_bytes: 0,
+            },
+        ]
+    }
+
+    #[test]
+    fn the_janitor_keeps_each_topic_within_its_own_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ContinuityStore::new(dir.path()).unwrap();
+        let events = EventEngine::new();
+        for topic in ["/q/chat", "/q/presence", "/q/archive"] {
+            for n in 0..5 {
+                let (_, event) = events.publish(topic, &format!("event {n}"));
+                store.append(topic, &event).unwrap();
+            }
+        }
+        let policies = RetentionPolicies::in_memory(defaults(), &topics());
+        let quotas = QuotaManager::new();
+        quotas.seed_topic("/q/chat", 5 * 7, 5);
+
+        assert_eq!(sweep(&events, Some(&store), &policies, &quotas), 2 + 4);
+        assert_eq!(store.records("/q/chat").unwrap().len(), 3);
+        assert_eq!(store.records("/q/presence").unwrap()[0].seq, 5);
+        assert_eq!(store.records("/q/archive").unwrap().len(), 5);
+        assert_eq!(events.event_count("/q/presence"), 1);
+        let usage = quotas.topic_usage("/q/chat");
+        assert_eq!((usage.bytes, usage.events), (3 * 7, 3));
+        assert_eq!(sweep(&events, Some(&store), &policies, &quotas), 0);
+
+        // Without a store, event counts are still enforced.
+        let memory = EventEngine::new();
+        for n in 0..5 {
+            memory.publish("/q/chat", &format!("event {n}"));
+        }
+        assert_eq!(sweep(&memory, None, &policies, &quotas), 2);
+    }
+
+    #[test]
+    fn limits_set_at_run_time_outrank_the_config_and_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("retention.tsv");
+        let policies = RetentionPolicies::open(&path, defaults(), &topics()).unwrap();
+        assert_eq!(
+            policies.get("/q/chat"),
+            (defaults(), RetentionSource::Default)
+        );
+        assert_eq!(policies.get("/q/presence").1, RetentionSource::Config);
+
+        let day = Retention {
+            max_age_secs: 86_400,
+            ..Retention::default()
+        };
+        policies.set_topic("/q/presence", day).unwrap();
+        policies.set_topic("/q/chat", Retention::default()).unwrap();
+        drop(policies);
+
+        let policies = RetentionPolicies::open(&path, defaults(), &topics()).unwrap();
+        assert_eq!(policies.get("/q/presence"), (day, RetentionSource::Set));
+        assert_eq!(policies.get("/q/chat").0, Retention::default());
+        policies.reset_topic("/q/presence").unwrap();
+        assert_eq!(policies.get("/q/presence").1, RetentionSource::Config);
+    }
+}
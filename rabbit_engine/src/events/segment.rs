@@ -0,0 +1,468 @@
+//! Segments of a topic's continuity log, and their retention.
+//!
+//! A topic's newest events are in its active segment,
+//! `<stem>.log`, which its writer appends to.  When that grows past
+//! the segment size or age, the writer seals it, renaming it to
+//! `<stem>.<first_seq>.seg` with the sequence number of its first
+//! record zero-padded to 20 digits, and starts a new active segment.
+//! Read in name order, the sealed segments and then the active one
+//! hold the topic's events oldest first.
+//!
+//! Dropping a topic's oldest events deletes the sealed segments that
+//! hold only dropped events and rewrites the one they end in, so that
+//! pruning and compaction free the space on disk.
+//!
+//! Before events are dropped, the highest sequence number logged is
+//! saved as the log's high-water mark, in `<stem>.seq`, so that
+//! numbering can carry on from it when the events are gone.
+//!
+//! A chained log also keeps, in `<stem>.anchor`, the hash of the
+//! newest record dropped, which the oldest left chains from, and in
+//! `<stem>.head`, the sequence number and hash of the newest record
+//! written, so that records missing from either end can be noticed.
+//!
+//! Reading the events after a sequence number skips the sealed
+//! segments before the one holding it, found by their names, and
+//! starts in that one where its [sparse index](crate::events::index)
+//! says.
+
+use std::path::{Path, PathBuf};
+
+use crate::events::index;
+use crate::events::record::{self, LogContents, LogRecord, GENESIS_HASH};
+use crate::protocol::error::ProtocolError;
+use crate::security::auth::{hex_decode, hex_encode};
+
+/// Extension of a sealed segment.
+const SEALED_EXT: &str = "seg";
+
+/// How much of a topic's log to keep; zero means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retention {
+    /// Events kept.
+    pub max_events: usize,
+    /// Age in seconds of the oldest event kept.
+    pub max_age_secs: u64,
+    /// Bytes of records kept.
+    pub max_bytes: u64,
+}
+
+impl Retention {
+    /// How many of `records`, oldest first, fall outside the policy at
+    /// `now` (Unix seconds).
+    pub fn excess(&self, records: &[LogRecord], now: u64) -> usize {
+        let by_count = match self.max_events {
+            0 => 0,
+            max => records.len().saturating_sub(max),
+        };
+        let by_age = match self.max_age_secs {
+            0 => 0,
+            max => records
+                .iter()
+                .take_while(|r| r.timestamp.saturating_add(max) < now)
+                .count(),
+        };
+        let by_bytes = match self.max_bytes {
+            0 => 0,
+            max => {
+                let mut kept = 0u64;
+                let newest_kept = records
+                    .iter()
+                    .rev()
+                    .take_while(|r| {
+                        kept += r.encoded_len() as u64;
+                        kept <= max
+                    })
+                    .count();
+                records.len() - newest_kept
+            }
+        };
+        by_count.max(by_age).max(by_bytes)
+    }
+}
+
+/// The path a segment of `active` starting at `first_seq` is sealed
+/// under.
+pub fn sealed_path(active: &Path, first_seq: u64) -> PathBuf {
+    let stem = active.file_stem().unwrap_or_default().to_string_lossy();
+    active.with_file_name(format!("{}.{:020}.{}", stem, first_seq, SEALED_EXT))
+}
+
+/// The sealed segments of the log whose active segment is `active`,
+/// oldest first.
+pub fn sealed(active: &Path) -> Result<Vec<PathBuf>, ProtocolError> {
+    let Some(dir) = active.parent() else {
+        return Ok(Vec::new());
+    };
+    let stem = active.file_stem().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{}.", stem);
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(ProtocolError::InternalError(format!(
+                "failed to list {}: {}",
+                dir.display(),
+                e
+            )))
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(SEALED_EXT))
+                .and_then(|seq| seq.strip_suffix('.'))
+                .is_some_and(|seq| seq.len() == 20 && seq.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Every intact record of the log whose active segment is `active`,
+/// oldest first, and the torn bytes ending any segment.
+///
+/// Segments sealed or removed while this reads are caught by listing
+/// them again afterwards, and the log read again.
+pub fn read_all(active: &Path) -> Result<LogContents, ProtocolError> {
+    loop {
+        let before = sealed(active)?;
+        let mut all = LogContents::default();
+        for path in before.iter().map(PathBuf::as_path).chain([active]) {
+            let contents = record::read_log(path)?;
+            all.records.extend(contents.records);
+            all.torn_bytes += contents.torn_bytes;
+            all.legacy |= contents.legacy;
+        }
+        if sealed(active)? == before {
+            return Ok(all);
+        }
+    }
+}
+
+/// The records of the log whose active segment is `active` after
+/// sequence number `since`, oldest first, and the torn bytes ending
+/// the segments read.
+///
+/// Sealed segments are listed again afterwards, as by [`read_all`].
+pub fn read_after(active: &Path, since: u64) -> Result<LogContents, ProtocolError> {
+    read_from(active, since, true)
+}
+
+/// As [`read_after`], but only as far as the end of the first segment
+/// holding any of the records, so that a long log can be read a
+/// segment at a time.  No records means there are none after `since`.
+pub fn read_next(active: &Path, since: u64) -> Result<LogContents, ProtocolError> {
+    read_from(active, since, false)
+}
+
+/// The records after `since`, from every segment holding any if
+/// `whole`, or else from the first.
+fn read_from(active: &Path, since: u64, whole: bool) -> Result<LogContents, ProtocolError> {
+    loop {
+        let before = sealed(active)?;
+        // The last sealed segment starting at or before the record
+        // after `since` holds it, if any sealed segment does.
+        let start = before
+            .iter()
+            .rposition(|path| first_seq(path).is_some_and(|first| first <= since.saturating_add(1)))
+            .unwrap_or(0);
+        let mut all = LogContents::default();
+        let paths = before[start..].iter().map(PathBuf::as_path).chain([active]);
+        for (n, path) in paths.enumerate() {
+            let offset = match n {
+                0 => index::seek(path, since)?.unwrap_or(0),
+                _ => 0,
+            };
+            let contents = record::read_log_from(path, offset)?;
+            all.records
+                .extend(contents.records.into_iter().filter(|r| r.seq > since));
+            all.torn_bytes += contents.torn_bytes;
+            all.legacy |= contents.legacy;
+            if !whole && !all.records.is_empty() {
+                break;
+            }
+        }
+        if sealed(active)? == before {
+            return Ok(all);
+        }
+    }
+}
+
+/// The newest record in the log whose active segment is `active`, if
+/// it has any.
+pub fn last_record(active: &Path) -> Result<Option<LogRecord>, ProtocolError> {
+    for path in [active.to_path_buf()]
+        .into_iter()
+        .chain(sealed(active)?.into_iter().rev())
+    {
+        if let Some(last) = record::read_log(&path)?.records.pop() {
+            return Ok(Some(last));
+        }
+    }
+    Ok(None)
+}
+
+/// The highest sequence number in the log whose active segment is
+/// `active`, or 0 if it is empty.
+pub fn last_seq(active: &Path) -> Result<u64, ProtocolError> {
+    Ok(last_record(active)?.map_or(0, |r| r.seq))
+}
+
+/// The file the high-water mark of the log whose active segment is
+/// `active` is saved in.
+fn mark_path(active: &Path) -> PathBuf {
+    active.with_extension("seq")
+}
+
+/// The saved high-water mark of the log whose active segment is
+/// `active`, or 0 if none is saved.
+pub fn read_mark(active: &Path) -> u64 {
+    std::fs::read_to_string(mark_path(active))
+        .ok()
+        .and_then(|text| text.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Save `seq` as the high-water mark of the log whose active segment
+/// is `active`.
+pub fn write_mark(active: &Path, seq: u64) -> Result<(), ProtocolError> {
+    let path = mark_path(active);
+    std::fs::write(&path, format!("{}\n", seq)).map_err(|e| {
+        ProtocolError::InternalError(format!(
+            "failed to save high-water mark {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// The file the hash of the newest record dropped from the log whose
+/// active segment is `active` is saved in, which its oldest record
+/// left chains from.
+fn anchor_path(active: &Path) -> PathBuf {
+    active.with_extension("anchor")
+}
+
+/// The hash the oldest record of the log whose active segment is
+/// `active` chains from: that of the newest record dropped, or
+/// [`GENESIS_HASH`] if none has been.
+pub fn read_anchor(active: &Path) -> Result<[u8; 32], ProtocolError> {
+    let path = anchor_path(active);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => parse_hash(&path, text.trim()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(GENESIS_HASH),
+        Err(e) => Err(ProtocolError::InternalError(format!(
+            "failed to read {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+/// Save `hash` as that of the newest record dropped from the log
+/// whose active segment is `active`.
+pub fn write_anchor(active: &Path, hash: &[u8; 32]) -> Result<(), ProtocolError> {
+    replace(&anchor_path(active), &format!("{}\n", hex_encode(hash)))
+}
+
+/// The file the sequence number and hash of the newest record written
+/// to the chained log whose active segment is `active` are saved in.
+fn head_path(active: &Path) -> PathBuf {
+    active.with_extension("head")
+}
+
+/// The sequence number and hash of the newest record written to the
+/// chained log whose active segment is `active`, if any is saved.
+pub fn read_head(active: &Path) -> Result<Option<(u64, [u8; 32])>, ProtocolError> {
+    let path = head_path(active);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(ProtocolError::InternalError(format!(
+                "failed to read {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    let (seq, hash) = text.trim().split_once(' ').unwrap_or_default();
+    let seq = seq.parse().map_err(|_| {
+        ProtocolError::InternalError(format!("bad sequence number in {}", path.display()))
+    })?;
+    Ok(Some((seq, parse_hash(&path, hash)?)))
+}
+
+/// Save `seq` and `hash` as those of the newest record written to the
+/// chained log whose active segment is `active`.
+pub fn write_head(active: &Path, seq: u64, hash: &[u8; 32]) -> Result<(), ProtocolError> {
+    replace(
+        &head_path(active),
+        &format!("{} {}\n", seq, hex_encode(hash)),
+    )
+}
+
+/// A hash read from the file at `path`.
+fn parse_hash(path: &Path, hex: &str) -> Result<[u8; 32], ProtocolError> {
+    hex_decode(hex)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| ProtocolError::InternalError(format!("bad hash in {}", path.display())))
+}
+
+/// Replace the file at `path` with `text`, so that it is never seen
+/// half written.
+fn replace(path: &Path, text: &str) -> Result<(), ProtocolError> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!("{}.tmp", name));
+    std::fs::write(&tmp, text)
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .map_err(|e| {
+            ProtocolError::InternalError(format!("failed to write {}: {}", path.display(), e))
+        })
+}
+
+/// The sequence number a sealed segment's name says it starts at.
+fn first_seq(sealed: &Path) -> Option<u64> {
+    let name = sealed.file_stem()?.to_str()?;
+    name.rsplit('.').next()?.parse().ok()
+}
+
+/// Seal the active segment `active`, whose first record is
+/// `first_seq`, and start an empty one in its place.
+pub fn seal(active: &Path, first_seq: u64) -> Result<PathBuf, ProtocolError> {
+    let path = sealed_path(active, first_seq);
+    index::forget(active);
+    index::forget(&path);
+    std::fs::rename(active, &path).map_err(|e| {
+        ProtocolError::InternalError(format!(
+            "failed to seal log segment {}: {}",
+            active.display(),
+            e
+        ))
+    })?;
+    record::write_log(active, &[])?;
+    Ok(path)
+}
+
+/// Drop the oldest `count` records of the log whose active segment is
+/// `active`.  No writer may be appending to it meanwhile.
+pub fn drop_oldest(active: &Path, mut count: usize) -> Result<(), ProtocolError> {
+    let removed = |path: &Path, e: std::io::Error| {
+        ProtocolError::InternalError(format!(
+            "failed to remove log segment {}: {}",
+            path.display(),
+            e
+        ))
+    };
+    for path in sealed(active)? {
+        if count == 0 {
+            return Ok(());
+        }
+        let records = record::read_log(&path)?.records;
+        if records.len() <= count {
+            count -= records.len();
+            index::forget(&path);
+            std::fs::remove_file(&path).map_err(|e| removed(&path, e))?;
+            continue;
+        }
+        let kept = &records[count..];
+        let renamed = sealed_path(active, kept[0].seq);
+        record::write_log(&renamed, kept)?;
+        if renamed != path {
+            index::forget(&path);
+            std::fs::remove_file(&path).map_err(|e| removed(&path, e))?;
+        }
+        return Ok(());
+    }
+    if count > 0 && active.exists() {
+        let records = record::read_log(active)?.records;
+        record::write_log(active, &records[count.min(records.len())..])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(seqs: std::ops::RangeInclusive<u64>) -> Vec<LogRecord> {
+        seqs.map(|seq| LogRecord {
+            seq,
+            timestamp: 1000 + seq,
+            lane: 0,
+            body: format!("event {}", seq),
+            prev_hash: None,
+            signature: None,
+            origin: None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn dropping_spans_segments_and_frees_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let active = dir.path().join("q_chat.log");
+        for (first, last) in [(1, 3), (4, 6)] {
+            record::write_log(&active, &records(first..=last)).unwrap();
+            seal(&active, first).unwrap();
+        }
+        record::write_log(&active, &records(7..=8)).unwrap();
+        // Another topic whose name starts the same is not a segment.
+        record::write_log(&dir.path().join("q_chat.x.log"), &records(1..=1)).unwrap();
+        assert_eq!(sealed(&active).unwrap().len(), 2);
+        assert_eq!(read_all(&active).unwrap().records, records(1..=8));
+
+        let after = |since| -> Vec<u64> {
+            let found = read_after(&active, since).unwrap().records;
+            found.iter().map(|r| r.seq).collect()
+        };
+        assert_eq!(after(0), (1..=8).collect::<Vec<_>>());
+        assert_eq!(after(4), [5, 6, 7, 8]);
+        assert_eq!(after(6), [7, 8]);
+        assert!(after(8).is_empty());
+        let next = |since| read_next(&active, since).unwrap().records.len();
+        assert_eq!(
+            (next(0), next(2), next(3), next(7), next(8)),
+            (3, 1, 3, 1, 0)
+        );
+
+        drop_oldest(&active, 4).unwrap();
+        assert_eq!(sealed(&active).unwrap(), vec![sealed_path(&active, 5)]);
+        assert_eq!(read_all(&active).unwrap().records, records(5..=8));
+
+        drop_oldest(&active, 3).unwrap();
+        assert!(sealed(&active).unwrap().is_empty());
+        assert_eq!(read_all(&active).unwrap().records, records(8..=8));
+    }
+
+    #[test]
+    fn retention_keeps_the_strictest_limit() {
+        let log = records(1..=10);
+        let unlimited = Retention::default();
+        assert_eq!(unlimited.excess(&log, 5000), 0);
+
+        let count = Retention {
+            max_events: 4,
+            ..Retention::default()
+        };
+        assert_eq!(count.excess(&log, 5000), 6);
+
+        // Events logged at 1001..=1010; at 1010, those older than 5s go.
+        let age = Retention {
+            max_events: 8,
+            max_age_secs: 5,
+            ..Retention::default()
+        };
+        assert_eq!(age.excess(&log, 1010), 4);
+
+        let bytes = Retention {
+            max_bytes: log[7..].iter().map(|r| r.encoded_len() as u64).sum(),
+            ..Retention::default()
+        };
+        assert_eq!(bytes.excess(&log, 0), 7);
+    }
+}
@@ -0,0 +1,367 @@
+//! Events kept in a SQLite database (`sqlite` feature).
+//!
+//! Every topic shares one database, normally `<storage>/events.db`,
+//! in two tables:
+//!
+//! ```sql
+//! events (topic, seq, timestamp, lane, body, origin, origin_seq)
+//! topics (topic, last_seq, last_write)
+//! ```
+//!
+//! Each append is a transaction that checks the event's sequence
+//! number against `topics.last_seq` — the topic's high-water mark,
+//! kept when events are pruned — and records both rows, so the
+//! history can be queried with plain SQL while the burrow runs.  The
+//! database is in WAL mode, synced as `continuity.fsync` says.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::events::continuity::{Compaction, ContinuityOptions, FsyncPolicy, TopicInfo};
+use crate::events::engine::Event;
+use crate::events::record::{LogRecord, Origin};
+use crate::events::segment::Retention;
+use crate::events::store::EventStore;
+use crate::protocol::error::ProtocolError;
+use crate::util::now_unix;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+        topic      TEXT    NOT NULL,
+        seq        INTEGER NOT NULL,
+        timestamp  INTEGER NOT NULL,
+        lane       INTEGER NOT NULL,
+        body       TEXT    NOT NULL,
+        origin     TEXT,
+        origin_seq INTEGER,
+        PRIMARY KEY (topic, seq)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS topics (
+        topic      TEXT    PRIMARY KEY,
+        last_seq   INTEGER NOT NULL,
+        last_write INTEGER NOT NULL
+    );
+";
+
+/// A database error as a protocol error.
+fn failed(e: rusqlite::Error) -> ProtocolError {
+    ProtocolError::InternalError(format!("event database: {}", e))
+}
+
+/// Persistent storage for event streams in a SQLite database.
+pub struct SqliteStore {
+    path: PathBuf,
+    conn: Mutex<Connection>,
+    /// What [`compact`](EventStore::compact) keeps.
+    retention: Retention,
+}
+
+impl SqliteStore {
+    /// Open (or create) the database at `path`, with
+    /// [`ContinuityOptions::default`].
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, ProtocolError> {
+        Self::with_options(path, ContinuityOptions::default())
+    }
+
+    /// Open (or create) the database at `path`, syncing and keeping
+    /// events as `options` says.  Its directory is created if it
+    /// doesn't exist.
+    pub fn with_options(
+        path: impl Into<PathBuf>,
+        options: ContinuityOptions,
+    ) -> Result<Self, ProtocolError> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                ProtocolError::InternalError(format!(
+                    "failed to create {}: {}",
+                    parent.display(),
+                    e
+                ))
+            })?;
+        }
+        let conn = Connection::open(&path).map_err(failed)?;
+        let synchronous = match options.fsync {
+            FsyncPolicy::Always => "FULL",
+            FsyncPolicy::Interval(_) => "NORMAL",
+            FsyncPolicy::Never => "OFF",
+        };
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .and_then(|()| conn.pragma_update(None, "synchronous", synchronous))
+            .and_then(|()| conn.execute_batch(SCHEMA))
+            .map_err(failed)?;
+        Ok(Self {
+            path,
+            conn: Mutex::new(conn),
+            retention: options.retention,
+        })
+    }
+
+    /// The database file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Insert `record` if its sequence number is above the topic's
+    /// high-water mark, raising the mark.
+    fn insert(&self, topic: &str, record: &LogRecord) -> Result<(), ProtocolError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(failed)?;
+        let high: u64 = tx
+            .query_row(
+                "SELECT last_seq FROM topics WHERE topic = ?1",
+                [topic],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(failed)?
+            .unwrap_or(0);
+        if record.seq <= high {
+            return Err(ProtocolError::OutOfOrder { expected: high + 1 });
+        }
+        let origin = record.origin.as_ref();
+        tx.execute(
+            "INSERT INTO events (topic, seq, timestamp, lane, body, origin, origin_seq)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                topic,
+                record.seq,
+                record.timestamp,
+                record.lane,
+                record.body,
+                origin.map(|o| o.burrow.as_str()),
+                origin.map(|o| o.seq),
+            ],
+        )
+        .map_err(failed)?;
+        tx.execute(
+            "INSERT INTO topics (topic, last_seq, last_write) VALUES (?1, ?2, ?3)
+             ON CONFLICT (topic) DO UPDATE SET last_seq = ?2, last_write = ?3",
+            params![topic, record.seq, record.timestamp],
+        )
+        .map_err(failed)?;
+        tx.commit().map_err(failed)
+    }
+
+    /// Delete the topic's oldest `count` events.
+    fn drop_oldest(
+        &self,
+        topic: &str,
+        records: &[LogRecord],
+        count: usize,
+    ) -> Result<Compaction, ProtocolError> {
+        let removed = count.min(records.len());
+        if removed == 0 {
+            return Ok(Compaction::default());
+        }
+        let last = records[removed - 1].seq;
+        self.conn()
+            .execute(
+                "DELETE FROM events WHERE topic = ?1 AND seq <= ?2",
+                params![topic, last],
+            )
+            .map_err(failed)?;
+        Ok(Compaction {
+            removed,
+            last_removed: Some(last),
+            removed_bytes: records[..removed]
+                .iter()
+                .map(|r| r.body.len() as u64)
+                .sum(),
+        })
+    }
+}
+
+impl EventStore for SqliteStore {
+    fn append_on_lane(&self, topic: &str, lane: u16, event: &Event) -> Result<(), ProtocolError> {
+        self.insert(topic, &LogRecord::new(event, lane, now_unix()))
+    }
+
+    fn append_replicated(
+        &self,
+        topic: &str,
+        lane: u16,
+        event: &Event,
+        origin: Origin,
+    ) -> Result<(), ProtocolError> {
+        let mut record = LogRecord::new(event, lane, now_unix());
+        record.origin = Some(origin);
+        self.insert(topic, &record)
+    }
+
+    fn records(&self, topic: &str) -> Result<Vec<LogRecord>, ProtocolError> {
+        let conn = self.conn();
+        let mut query = conn
+            .prepare_cached(
+                "SELECT seq, timestamp, lane, body, origin, origin_seq FROM events
+                 WHERE topic = ?1 ORDER BY seq",
+            )
+            .map_err(failed)?;
+        let rows = query
+            .query_map([topic], |row| {
+                let origin: Option<String> = row.get(4)?;
+                let origin_seq: Option<u64> = row.get(5)?;
+                Ok(LogRecord {
+                    seq: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    lane: row.get(2)?,
+                    body: row.get(3)?,
+                    prev_hash: None,
+                    signature: None,
+                    origin: origin.map(|burrow| Origin {
+                        burrow,
+                        seq: origin_seq.unwrap_or(0),
+                    }),
+                })
+            })
+            .map_err(failed)?;
+        rows.collect::<Result<_, _>>().map_err(failed)
+    }
+
+    fn last_seq(&self, topic: &str) -> Result<u64, ProtocolError> {
+        self.conn()
+            .query_row(
+                "SELECT last_seq FROM topics WHERE topic = ?1",
+                [topic],
+                |row| row.get(0),
+            )
+            .optional()
+            .map(Option::unwrap_or_default)
+            .map_err(failed)
+    }
+
+    fn prune(&self, topic: &str, keep: usize) -> Result<Compaction, ProtocolError> {
+        let records = self.records(topic)?;
+        let excess = records.len().saturating_sub(keep);
+        self.drop_oldest(topic, &records, excess)
+    }
+
+    fn compact(&self, topic: &str) -> Result<Compaction, ProtocolError> {
+        self.compact_with(topic, self.retention)
+    }
+
+    fn compact_with(&self, topic: &str, retention: Retention) -> Result<Compaction, ProtocolError> {
+        if retention == Retention::default() {
+            return Ok(Compaction::default());
+        }
+        let records = self.records(topic)?;
+        let excess = retention.excess(&records, now_unix());
+        self.drop_oldest(topic, &records, excess)
+    }
+
+    fn list_topics(&self) -> Result<Vec<TopicInfo>, ProtocolError> {
+        let conn = self.conn();
+        let mut query = conn
+            .prepare_cached(
+                "SELECT t.topic, COUNT(e.seq), COALESCE(MIN(e.seq), 0), t.last_seq,
+                        COALESCE(SUM(LENGTH(CAST(e.body AS BLOB))), 0), t.last_write
+                 FROM topics t LEFT JOIN events e ON e.topic = t.topic
+                 GROUP BY t.topic ORDER BY t.topic",
+            )
+            .map_err(failed)?;
+        let rows = query
+            .query_map([], |row| {
+                Ok(TopicInfo {
+                    topic: row.get(0)?,
+                    events: row.get(1)?,
+                    first_seq: row.get(2)?,
+                    last_seq: row.get(3)?,
+                    bytes: row.get(4)?,
+                    last_write: row.get(5)?,
+                })
+            })
+            .map_err(failed)?;
+        rows.collect::<Result<_, _>>().map_err(failed)
+    }
+
+    fn flush(&self) -> Result<(), ProtocolError> {
+        self.conn()
+            .execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")
+            .map_err(failed)
+    }
+
+    fn replay(&self, topic: &str, since_seq: u64) -> Result<Vec<Event>, ProtocolError> {
+        let conn = self.conn();
+        let mut query = conn
+            .prepare_cached(
+                "SELECT seq, body FROM events WHERE topic = ?1 AND seq > ?2 ORDER BY seq",
+            )
+            .map_err(failed)?;
+        let rows = query
+            .query_map(params![topic, since_seq], |row| {
+                Ok(Event {
+                    seq: row.get(0)?,
+                    body: row.get(1)?,
+                })
+            })
+            .map_err(failed)?;
+        rows.collect::<Result<_, _>>().map_err(failed)
+    }
+
+    fn has_topic(&self, topic: &str) -> bool {
+        self.last_seq(topic).is_ok_and(|seq| seq > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(seq: u64, body: &str) -> Event {
+        Event {
+            seq,
+            body: body.into(),
+        }
+    }
+
+    #[test]
+    fn events_append_replay_and_keep_numbering_across_pruning() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.db");
+        let store = SqliteStore::open(&path).unwrap();
+        for seq in 1..=4 {
+            store
+                .append_on_lane("/q/chat", 2, &event(seq, "hi\tthere\n"))
+                .unwrap();
+        }
+        assert!(matches!(
+            store.append("/q/chat", &event(4, "again")),
+            Err(ProtocolError::OutOfOrder { expected: 5 })
+        ));
+        let origin = Origin {
+            burrow: "ed25519:OAK".into(),
+            seq: 70,
+        };
+        store
+            .append_replicated("/q/news", 1, &event(1, "copied"), origin.clone())
+            .unwrap();
+
+        let replayed = store.replay("/q/chat", 2).unwrap();
+        let seqs: Vec<u64> = replayed.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, [3, 4]);
+        assert_eq!(replayed[0].body, "hi\tthere\n");
+        assert_eq!(store.records("/q/chat").unwrap()[0].lane, 2);
+        assert_eq!(store.last_replicated("/q/news", "ed25519:OAK").unwrap(), 70);
+        assert_eq!(store.topics(), ["/q/chat", "/q/news"]);
+
+        let pruned = store.prune("/q/chat", 1).unwrap();
+        assert_eq!((pruned.removed, pruned.last_removed), (3, Some(3)));
+        assert_eq!(pruned.removed_bytes, 3 * 9);
+        drop(store);
+
+        // The high-water mark outlives the pruned events and a reopen.
+        let store = SqliteStore::open(&path).unwrap();
+        let info = &store.list_topics().unwrap()[0];
+        assert_eq!((info.events, info.first_seq, info.last_seq), (1, 4, 4));
+        assert_eq!(info.bytes, 9);
+        assert!(store.append("/q/chat", &event(3, "late")).is_err());
+        store.append("/q/chat", &event(5, "next")).unwrap();
+        assert!(store.verify_topic("/q/chat").is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! Storage backends for persisted events.
+//!
+//! The [`EventStore`] trait is what the rest of the burrow persists
+//! and replays events through.  The [`ContinuityStore`]'s segmented
+//! logs are the default backend; with the `sqlite` feature,
+//! [`SqliteStore`](crate::events::sqlite::SqliteStore) keeps every
+//! topic in one SQLite database instead, for transactional writes and
+//! SQL over the event history.  `identity.event_store` picks one.
+//!
+//! Only [`append_on_lane`](EventStore::append_on_lane),
+//! [`append_replicated`](EventStore::append_replicated),
+//! [`records`](EventStore::records) and the topic bookkeeping must be
+//! written for a backend; replay and lookups by time or origin are
+//! derived from its records unless it can do better.
+
+use std::pin::Pin;
+
+use futures_util::stream::{self, Stream};
+
+use crate::events::continuity::{Compaction, ContinuityStore, IntegrityReport, TopicInfo};
+use crate::events::engine::{event_frame, Event};
+use crate::events::record::{LogRecord, Origin};
+use crate::events::segment::Retention;
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+
+/// Where a burrow's events are persisted.
+///
+/// Every backend refuses, with `409 OUT-OF-ORDER`, an event whose
+/// sequence number is not above every one its topic has stored, even
+/// if those have since been pruned.
+pub trait EventStore: Send + Sync {
+    /// Append an event published on `lane`.
+    fn append_on_lane(&self, topic: &str, lane: u16, event: &Event) -> Result<(), ProtocolError>;
+
+    /// Append an event replicated from another burrow, recording its
+    /// `origin`.
+    fn append_replicated(
+        &self,
+        topic: &str,
+        lane: u16,
+        event: &Event,
+        origin: Origin,
+    ) -> Result<(), ProtocolError>;
+
+    /// Every event stored for a topic, oldest first.
+    fn records(&self, topic: &str) -> Result<Vec<LogRecord>, ProtocolError>;
+
+    /// The highest sequence number the topic has stored, or 0.
+    fn last_seq(&self, topic: &str) -> Result<u64, ProtocolError>;
+
+    /// Drop all but the last `keep` of a topic's events.
+    fn prune(&self, topic: &str, keep: usize) -> Result<Compaction, ProtocolError>;
+
+    /// Drop a topic's oldest events beyond the store's retention
+    /// limits.
+    fn compact(&self, topic: &str) -> Result<Compaction, ProtocolError>;
+
+    /// Drop a topic's oldest events beyond `retention`.
+    fn compact_with(&self, topic: &str, retention: Retention) -> Result<Compaction, ProtocolError>;
+
+    /// Every topic stored, sorted, with what is stored of it.
+    fn list_topics(&self) -> Result<Vec<TopicInfo>, ProtocolError>;
+
+    /// Make everything appended so far durable.
+    fn flush(&self) -> Result<(), ProtocolError>;
+
+    /// Append an event published on lane 0.
+    fn append(&self, topic: &str, event: &Event) -> Result<(), ProtocolError> {
+        self.append_on_lane(topic, 0, event)
+    }
+
+    /// Every topic stored, sorted.
+    fn topics(&self) -> Vec<String> {
+        self.list_topics()
+            .map(|topics| topics.into_iter().map(|t| t.topic).collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether anything has been stored for `topic`.
+    fn has_topic(&self, topic: &str) -> bool {
+        self.topics().iter().any(|t| t == topic)
+    }
+
+    /// Every event stored for a topic, oldest first.
+    fn load(&self, topic: &str) -> Result<Vec<Event>, ProtocolError> {
+        self.replay(topic, 0)
+    }
+
+    /// The events after `since_seq`.
+    fn replay(&self, topic: &str, since_seq: u64) -> Result<Vec<Event>, ProtocolError> {
+        Ok(self
+            .records(topic)?
+            .iter()
+            .filter(|r| r.seq > since_seq)
+            .map(LogRecord::to_event)
+            .collect())
+    }
+
+    /// The events after `since_seq` as EVENT frames on `lane`, ending
+    /// after `limit` events or the last at or before `until_seq`,
+    /// where given.  A failed read ends the stream early, with a
+    /// warning.
+    fn replay_stream(
+        &self,
+        topic: &str,
+        since_seq: u64,
+        lane: &str,
+        limit: Option<usize>,
+        until_seq: Option<u64>,
+    ) -> Pin<Box<dyn Stream<Item = Frame> + Send>> {
+        let events = self.replay(topic, since_seq).unwrap_or_else(|e| {
+            tracing::warn!(topic, error = %e, "event replay stopped");
+            Vec::new()
+        });
+        let frames: Vec<Frame> = events
+            .iter()
+            .take_while(|e| until_seq.is_none_or(|until| e.seq <= until))
+            .take(limit.unwrap_or(usize::MAX))
+            .filter_map(|e| event_frame(topic, e, lane))
+            .collect();
+        Box::pin(stream::iter(frames))
+    }
+
+    /// The sequence number of the last event stored before `secs`
+    /// (Unix time), or 0 if there is none.
+    fn last_seq_before(&self, topic: &str, secs: u64) -> Result<u64, ProtocolError> {
+        Ok(self
+            .records(topic)?
+            .iter()
+            .take_while(|r| r.timestamp < secs)
+            .last()
+            .map_or(0, |r| r.seq))
+    }
+
+    /// The highest sequence number, in `burrow`'s own log, of the
+    /// events of a topic replicated from it, or 0 if none are stored.
+    fn last_replicated(&self, topic: &str, burrow: &str) -> Result<u64, ProtocolError> {
+        Ok(self
+            .records(topic)?
+            .iter()
+            .rev()
+            .filter_map(|r| r.origin.as_ref())
+            .find(|origin| origin.burrow == burrow)
+            .map_or(0, |origin| origin.seq))
+    }
+
+    /// Check a topic's events for signs of tampering or truncation.
+    /// Fails with `400 BAD-REQUEST` if the store keeps no hash chain.
+    fn verify_topic(&self, topic: &str) -> Result<IntegrityReport, ProtocolError> {
+        Err(ProtocolError::BadRequest(format!(
+            "the event store cannot verify {topic}"
+        )))
+    }
+}
+
+/// Run `append`, which waits if the store is behind, so that the wait
+/// holds up only the task calling it: on a multi-threaded Tokio
+/// runtime the worker hands its other tasks off first.  Anywhere else
+/// it is simply run.
+pub(crate) fn append_in_place<R>(append: impl FnOnce() -> R) -> R {
+    use tokio::runtime::{Handle, RuntimeFlavor};
+    match Handle::try_current().map(|runtime| runtime.runtime_flavor()) {
+        Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(append),
+        _ => append(),
+    }
+}
+
+impl EventStore for ContinuityStore {
+    fn append_on_lane(&self, topic: &str, lane: u16, event: &Event) -> Result<(), ProtocolError> {
+        ContinuityStore::append_on_lane(self, topic, lane, event)
+    }
+
+    fn append_replicated(
+        &self,
+        topic: &str,
+        lane: u16,
+        event: &Event,
+        origin: Origin,
+    ) -> Result<(), ProtocolError> {
+        ContinuityStore::append_replicated(self, topic, lane, event, origin)
+    }
+
+    fn records(&self, topic: &str) -> Result<Vec<LogRecord>, ProtocolError> {
+        ContinuityStore::records(self, topic)
+    }
+
+    fn last_seq(&self, topic: &str) -> Result<u64, ProtocolError> {
+        ContinuityStore::last_seq(self, topic)
+    }
+
+    fn prune(&self, topic: &str, keep: usize) -> Result<Compaction, ProtocolError> {
+        ContinuityStore::prune(self, topic, keep)
+    }
+
+    fn compact(&self, topic: &str) -> Result<Compaction, ProtocolError> {
+        ContinuityStore::compact(self, topic)
+    }
+
+    fn compact_with(&self, topic: &str, retention: Retention) -> Result<Compaction, ProtocolError> {
+        ContinuityStore::compact_with(self, topic, retention)
+    }
+
+    fn list_topics(&self) -> Result<Vec<TopicInfo>, ProtocolError> {
+        ContinuityStore::list_topics(self)
+    }
+
+    fn flush(&self) -> Result<(), ProtocolError> {
+        ContinuityStore::flush(self)
+    }
+
+    fn append(&self, topic: &str, event: &Event) -> Result<(), ProtocolError> {
+        ContinuityStore::append(self, topic, event)
+    }
+
+    fn topics(&self) -> Vec<String> {
+        ContinuityStore::topics(self)
+    }
+
+    fn has_topic(&self, topic: &str) -> bool {
+        self.has_log(topic)
+    }
+
+    fn replay(&self, topic: &str, since_seq: u64) -> Result<Vec<Event>, ProtocolError> {
+        ContinuityStore::replay(self, topic, since_seq)
+    }
+
+    fn replay_stream(
+        &self,
+        topic: &str,
+        since_seq: u64,
+        lane: &str,
+        limit: Option<usize>,
+        until_seq: Option<u64>,
+    ) -> Pin<Box<dyn Stream<Item = Frame> + Send>> {
+        Box::pin(ContinuityStore::replay_stream(
+            self, topic, since_seq, lane, limit, until_seq,
+        ))
+    }
+
+    fn last_seq_before(&self, topic: &str, secs: u64) -> Result<u64, ProtocolError> {
+        ContinuityStore::last_seq_before(self, topic, secs)
+    }
+
+    fn verify_topic(&self, topic: &str) -> Result<IntegrityReport, ProtocolError> {
+        ContinuityStore::verify_topic(self, topic)
+    }
+}
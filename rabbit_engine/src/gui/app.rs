@@ -8,6 +8,7 @@ use std::sync::OnceLock;
 
 use dioxus::prelude::*;
 use futures_util::StreamExt;
+use tracing::{debug, info, warn};
 
 use crate::config::GuiConfig;
 use crate::gui::bridge::{
@@ -90,7 +91,7 @@ pub fn launch_gui(
     let css = theme::generate_css(wc.theme, wc.font_size);
 
     let renderer = Renderer::parse(&config.renderer).resolve();
-    eprintln!("rabbit-gui: using renderer {}", renderer);
+    info!("using renderer {}", renderer);
 
     let _ = LAUNCH_DATA.set(LaunchData {
         host,
@@ -142,7 +143,7 @@ fn App() -> Element {
         let ai_config = data.gui_config.ai_renderer.clone();
 
         async move {
-            eprintln!("rabbit-gui: connecting to {}\u{2026}", host);
+            info!("connecting to {}\u{2026}", host);
             let mut conn = match bridge::open_connection(&host).await {
                 Ok(c) => c,
                 Err(e) => {
@@ -160,20 +161,20 @@ fn App() -> Element {
 
             let id_short = short_id(&conn.server_id);
             status_text.set(format!("Connected to {}", id_short));
-            eprintln!("rabbit-gui: connected to {}", conn.server_id);
+            info!("connected to {}", conn.server_id);
 
             // Create AI view generator if enabled and API key is available.
             let mut view_gen: Option<ViewGenerator> = if ai_config.enabled {
                 if std::env::var("OPENAI_API_KEY").is_ok() {
                     let tls = crate::ai::http::tls_config();
-                    eprintln!("rabbit-gui: AI view rendering enabled (model={})", ai_config.model);
+                    info!("AI view rendering enabled (model={})", ai_config.model);
                     Some(ViewGenerator::new(tls, ai_config.clone()))
                 } else {
-                    eprintln!("rabbit-gui: AI rendering configured but OPENAI_API_KEY not set, using fallback");
+                    warn!("AI rendering configured but OPENAI_API_KEY not set, using fallback");
                     None
                 }
             } else {
-                eprintln!("rabbit-gui: AI rendering disabled, using fallback views");
+                info!("AI rendering disabled, using fallback views");
                 None
             };
 
@@ -202,7 +203,7 @@ fn App() -> Element {
                                 conn.tunnel.send_frame(&pong).await.ok();
                             }
                             Ok(Some(f)) => {
-                                eprintln!("rabbit-gui: unexpected frame in idle loop: {}", f.verb);
+                                debug!("unexpected frame in idle loop: {}", f.verb);
                             }
                             Ok(None) => {
                                 status_text.set("Connection closed".into());
@@ -271,7 +272,7 @@ fn App() -> Element {
                                             conn.tunnel.send_frame(&pong).await.ok();
                                         }
                                         Ok(Some(frame)) if frame.verb == "EVENT" || frame.verb == "210" => {
-                                            let seq = frame.header("Seq").unwrap_or("?").to_string();
+                                            let seq = frame.header("Event-Seq").unwrap_or("?").to_string();
                                             let body = frame.body.as_deref().unwrap_or("").trim().to_string();
                                             messages.push(format!("[{}] {}", seq, body));
                                             let content = ViewContent::Events { topic: topic.clone(), messages: messages.clone() };
@@ -378,7 +379,7 @@ fn App() -> Element {
             loop {
                 match eval.recv::<String>().await {
                     Ok(element_id) => {
-                        eprintln!("rabbit-gui: click on '{}'", element_id);
+                        debug!("click on '{}'", element_id);
                         let action = { actions.read().resolve(&element_id).cloned() };
                         if let Some(action) = action {
                             match action {
@@ -389,11 +390,11 @@ fn App() -> Element {
                                 Action::Forward => bridge.send(BridgeCommand::Forward),
                                 Action::Refresh => bridge.send(BridgeCommand::Refresh),
                                 Action::Fetch(sel) => bridge.send(BridgeCommand::Fetch(sel)),
-                                _ => eprintln!("rabbit-gui: unhandled action for '{}'", element_id),
+                                _ => debug!("unhandled action for '{}'", element_id),
                             }
                         }
                     }
-                    Err(e) => { eprintln!("rabbit-gui: eval recv error: {:?}", e); break; }
+                    Err(e) => { warn!("eval recv error: {:?}", e); break; }
                 }
             }
         }
@@ -600,13 +601,13 @@ async fn render_content(
                             conn.tunnel.send_frame(&pong).await.ok();
                         }
                         Ok(Some(f)) => {
-                            eprintln!("rabbit-gui: unexpected frame during render: {}", f.verb);
+                            debug!("unexpected frame during render: {}", f.verb);
                         }
                         Ok(None) => {
-                            eprintln!("rabbit-gui: tunnel closed during AI render");
+                            warn!("tunnel closed during AI render");
                         }
                         Err(e) => {
-                            eprintln!("rabbit-gui: tunnel error during AI render: {}", e);
+                            warn!("tunnel error during AI render: {}", e);
                         }
                     }
                 }
@@ -634,7 +635,7 @@ async fn render_content(
                 return html;
             }
             Err(e) => {
-                eprintln!("rabbit-gui: AI render failed ({}), using fallback", e);
+                warn!("AI render failed ({}), using fallback", e);
                 status_signal.set(format!("AI failed: {}", e));
                 let mut dbg = DebugLog::default();
                 dbg.mode = "error".into();
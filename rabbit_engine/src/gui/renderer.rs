@@ -17,12 +17,15 @@
 
 use std::fmt;
 
+use tracing::warn;
+
 // ── Renderer enum ───────────────────────────────────────────────
 
 /// Backend renderer for the GUI.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Renderer {
     /// WRY/Tauri WebView (stable, full HTML/CSS/JS support).
+    #[default]
     WebView,
     /// Dioxus Blitz native GPU renderer (experimental).
     Blitz,
@@ -33,16 +36,13 @@ impl Renderer {
     ///
     /// Recognised values (case-insensitive): `"webview"`, `"blitz"`.
     /// Unknown strings fall back to [`Renderer::WebView`] with a
-    /// warning logged via `eprintln!`.
+    /// warning logged via `tracing`.
     pub fn parse(s: &str) -> Self {
         match s.trim().to_ascii_lowercase().as_str() {
             "blitz" => Self::Blitz,
             "webview" | "wry" | "tauri" => Self::WebView,
             other => {
-                eprintln!(
-                    "rabbit-gui: unknown renderer {:?}, falling back to webview",
-                    other
-                );
+                warn!("unknown renderer {:?}, falling back to webview", other);
                 Self::WebView
             }
         }
@@ -77,9 +77,9 @@ impl Renderer {
                 // we do not currently compile.  Fall back gracefully.
                 #[cfg(not(feature = "gui-native"))]
                 {
-                    eprintln!(
-                        "rabbit-gui: Blitz renderer requested but gui-native \
-                         feature not enabled; falling back to WebView"
+                    warn!(
+                        "Blitz renderer requested but gui-native feature not \
+                         enabled; falling back to WebView"
                     );
                     Self::WebView
                 }
@@ -99,12 +99,6 @@ impl fmt::Display for Renderer {
     }
 }
 
-impl Default for Renderer {
-    fn default() -> Self {
-        Self::WebView
-    }
-}
-
 // ── Tests ───────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -11,6 +11,7 @@ use std::sync::Arc;
 
 use rustls::ClientConfig;
 use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
 
 use crate::ai::http::{self, AiHttpError, CompletionRequest};
 use crate::ai::types::AiMessage;
@@ -213,7 +214,7 @@ impl ViewGenerator {
         let val: serde_json::Value = match serde_json::from_str(&data) {
             Ok(v) => v,
             Err(e) => {
-                eprintln!("rabbit-gui: cache file corrupt, ignoring: {e}");
+                warn!("cache file corrupt, ignoring: {e}");
                 return;
             }
         };
@@ -231,8 +232,8 @@ impl ViewGenerator {
                 }
             }
         }
-        eprintln!(
-            "rabbit-gui: loaded {} cached views from disk ({})",
+        info!(
+            "loaded {} cached views from disk ({})",
             self.cache.len(),
             path.display()
         );
@@ -254,12 +255,12 @@ impl ViewGenerator {
         match serde_json::to_string(&payload) {
             Ok(json) => {
                 if let Err(e) = std::fs::write(&path, &json) {
-                    eprintln!("rabbit-gui: failed to write cache: {e}");
+                    warn!("failed to write cache: {e}");
                 } else {
-                    eprintln!("rabbit-gui: saved {} cached views to disk", self.cache.len());
+                    info!("saved {} cached views to disk", self.cache.len());
                 }
             }
-            Err(e) => eprintln!("rabbit-gui: failed to serialise cache: {e}"),
+            Err(e) => warn!("failed to serialise cache: {e}"),
         }
     }
 
@@ -287,13 +288,13 @@ impl ViewGenerator {
         // ── Cache HIT → instant return ──────────────────────────
         if self.config.cache_views {
             if let Some(cached) = self.cache.get(&content_key) {
-                eprintln!("rabbit-gui: cache HIT for {} ({})",
+                debug!("cache HIT for {} ({})",
                     content_label(content), &content_key[..12]);
                 dbg.cache_hit = true;
                 dbg.mode = "cached".into();
                 return Ok((cached.clone(), dbg));
             }
-            eprintln!("rabbit-gui: cache MISS for {} ({}) — calling {}",
+            debug!("cache MISS for {} ({}) — calling {}",
                 content_label(content), &content_key[..12], self.config.model);
         }
 
@@ -414,7 +415,7 @@ impl ViewGenerator {
         let total = patches.len();
 
         if patches.is_empty() {
-            eprintln!("rabbit-gui: diff returned 0 patches, using base HTML");
+            debug!("diff returned 0 patches, using base HTML");
             return Ok((base_html.to_string(), raw, 0, 0));
         }
 
@@ -433,16 +434,16 @@ impl ViewGenerator {
                 // Report patch progress.
                 let _ = progress.try_send(format!("\x00PATCH {}/{}", i + 1, total));
             } else {
-                eprintln!("rabbit-gui: patch find not matched: {:?}",
+                debug!("patch find not matched: {:?}",
                     &patch.find[..patch.find.len().min(60)]);
             }
         }
 
-        eprintln!("rabbit-gui: applied {}/{} patches", applied, total);
+        debug!("applied {}/{} patches", applied, total);
 
         // If zero patches applied, fall back to full generation.
         if applied == 0 {
-            eprintln!("rabbit-gui: diff failed, falling back to full generation");
+            warn!("diff failed, falling back to full generation");
             let (fallback_html, fallback_raw) = self.generate_full(prompt, api_key, progress).await
                 .unwrap_or_else(|_| (base_html.to_string(), String::new()));
             return Ok((fallback_html, fallback_raw, 0, 0));
@@ -653,7 +654,7 @@ fn parse_patches(json_str: &str) -> Vec<Patch> {
     let arr: Vec<serde_json::Value> = match serde_json::from_str(trimmed) {
         Ok(a) => a,
         Err(e) => {
-            eprintln!("rabbit-gui: failed to parse patch JSON: {}", e);
+            warn!("failed to parse patch JSON: {}", e);
             return Vec::new();
         }
     };
@@ -0,0 +1,265 @@
+//! Lifecycle states and callbacks for applications embedding a burrow.
+//!
+//! A [`Burrow`](crate::burrow::Burrow) moves through the
+//! [`BurrowState`]s in order — `Initialized` when assembled,
+//! `Starting` and then `Running` as it is started, `Draining` once
+//! [`shutdown`](crate::burrow::Burrow::shutdown) begins and `Stopped`
+//! when its state is saved — and never back.
+//!
+//! Its [`Hooks`] hold async callbacks run when that state changes, when
+//! a peer completes a handshake and when one is trusted, when an event
+//! is published and when a tunnel fails:
+//!
+//! ```no_run
+//! # use rabbit_engine::burrow::Burrow;
+//! # fn wire(burrow: &Burrow) {
+//! burrow.hooks.on_peer_connected(|peer_id| async move {
+//!     println!("{peer_id} connected");
+//! });
+//! # }
+//! ```
+//!
+//! Each callback is run on a task of its own on the current Tokio
+//! runtime, so a slow one holds up neither the burrow nor the other
+//! callbacks; outside a runtime, callbacks are not run.
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::BoxFuture;
+
+use crate::protocol::error::ProtocolError;
+
+/// Where a burrow is in its life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BurrowState {
+    /// Assembled, not yet serving.
+    Initialized,
+    /// Binding listeners and starting background tasks.
+    Starting,
+    /// Serving peers.
+    Running,
+    /// Shutting down: tunnels are told to go away and settle.
+    Draining,
+    /// Shut down, with its state saved.
+    Stopped,
+}
+
+impl BurrowState {
+    /// The state's name, e.g. `"running"`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Initialized => "initialized",
+            Self::Starting => "starting",
+            Self::Running => "running",
+            Self::Draining => "draining",
+            Self::Stopped => "stopped",
+        }
+    }
+}
+
+impl std::fmt::Display for BurrowState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// An event published to one of the burrow's topics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedEvent {
+    /// The topic, e.g. `/q/chat`.
+    pub topic: String,
+    /// The event's number in the topic.
+    pub seq: u64,
+    /// The burrow ID of the peer that published it.
+    pub publisher: String,
+}
+
+/// A tunnel that ended in an error.
+#[derive(Debug, Clone)]
+pub struct TunnelError {
+    /// The peer at the other end, as far as the tunnel got to know it.
+    pub peer_id: String,
+    /// What went wrong.
+    pub error: ProtocolError,
+}
+
+type Callback<T> = Arc<dyn Fn(T) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// The callbacks registered with a burrow.
+#[derive(Default)]
+pub struct Hooks {
+    state_changed: Mutex<Vec<Callback<BurrowState>>>,
+    peer_connected: Mutex<Vec<Callback<String>>>,
+    peer_trusted: Mutex<Vec<Callback<String>>>,
+    event_published: Mutex<Vec<Callback<PublishedEvent>>>,
+    error: Mutex<Vec<Callback<TunnelError>>>,
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn count<T>(callbacks: &Mutex<Vec<Callback<T>>>) -> usize {
+            callbacks.lock().unwrap_or_else(|e| e.into_inner()).len()
+        }
+        f.debug_struct("Hooks")
+            .field("state_changed", &count(&self.state_changed))
+            .field("peer_connected", &count(&self.peer_connected))
+            .field("peer_trusted", &count(&self.peer_trusted))
+            .field("event_published", &count(&self.event_published))
+            .field("error", &count(&self.error))
+            .finish()
+    }
+}
+
+impl Hooks {
+    /// Create a registry with no callbacks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `f` with each state the burrow moves to.
+    pub fn on_state_changed<F, Fut>(&self, f: F)
+    where
+        F: Fn(BurrowState) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        add(&self.state_changed, f);
+    }
+
+    /// Call `f` with the burrow ID of each peer that completes a
+    /// handshake, on tunnels accepted and dialed alike.
+    pub fn on_peer_connected<F, Fut>(&self, f: F)
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        add(&self.peer_connected, f);
+    }
+
+    /// Call `f` with the burrow ID of each authenticated peer whose key
+    /// the trust cache and policy admit, quarantined peers aside.
+    pub fn on_peer_trusted<F, Fut>(&self, f: F)
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        add(&self.peer_trusted, f);
+    }
+
+    /// Call `f` with each event a peer publishes.
+    pub fn on_event_published<F, Fut>(&self, f: F)
+    where
+        F: Fn(PublishedEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        add(&self.event_published, f);
+    }
+
+    /// Call `f` with each accepted tunnel that ends in an error,
+    /// refused handshakes included.
+    pub fn on_error<F, Fut>(&self, f: F)
+    where
+        F: Fn(TunnelError) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        add(&self.error, f);
+    }
+
+    pub(crate) fn state_changed(&self, state: BurrowState) {
+        run(&self.state_changed, state);
+    }
+
+    pub(crate) fn peer_connected(&self, peer_id: &str) {
+        run(&self.peer_connected, peer_id.to_string());
+    }
+
+    pub(crate) fn peer_trusted(&self, peer_id: &str) {
+        run(&self.peer_trusted, peer_id.to_string());
+    }
+
+    pub(crate) fn event_published(&self, topic: &str, seq: u64, publisher: &str) {
+        let event = PublishedEvent {
+            topic: topic.to_string(),
+            seq,
+            publisher: publisher.to_string(),
+        };
+        run(&self.event_published, event);
+    }
+
+    pub(crate) fn error(&self, peer_id: &str, error: &ProtocolError) {
+        let error = TunnelError {
+            peer_id: peer_id.to_string(),
+            error: error.clone(),
+        };
+        run(&self.error, error);
+    }
+}
+
+fn add<T, F, Fut>(callbacks: &Mutex<Vec<Callback<T>>>, f: F)
+where
+    F: Fn(T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let callback: Callback<T> = Arc::new(move |arg| Box::pin(f(arg)));
+    callbacks
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(callback);
+}
+
+/// Start each of `callbacks` with `arg` on a task of its own.
+fn run<T: Clone>(callbacks: &Mutex<Vec<Callback<T>>>, arg: T) {
+    let callbacks = callbacks.lock().unwrap_or_else(|e| e.into_inner());
+    if callbacks.is_empty() {
+        return;
+    }
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    for callback in callbacks.iter() {
+        runtime.spawn(callback(arg.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn callbacks_run_with_what_happened() {
+        let hooks = Hooks::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sent = tx.clone();
+        hooks.on_peer_connected(move |peer_id| {
+            let sent = sent.clone();
+            async move {
+                sent.send(format!("connected {peer_id}")).unwrap();
+            }
+        });
+        hooks.on_event_published(move |event| {
+            let sent = tx.clone();
+            async move {
+                let line = format!("{} {} by {}", event.topic, event.seq, event.publisher);
+                sent.send(line).unwrap();
+            }
+        });
+
+        hooks.peer_connected("ed25519:ELM");
+        assert_eq!(rx.recv().await.unwrap(), "connected ed25519:ELM");
+        hooks.event_published("/q/chat", 7, "ed25519:ELM");
+        assert_eq!(rx.recv().await.unwrap(), "/q/chat 7 by ed25519:ELM");
+        // Nothing is registered for these.
+        hooks.peer_trusted("ed25519:ELM");
+        hooks.state_changed(BurrowState::Running);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn states_are_ordered_as_a_burrow_moves_through_them() {
+        assert!(BurrowState::Initialized < BurrowState::Starting);
+        assert!(BurrowState::Running < BurrowState::Draining);
+        assert!(BurrowState::Draining < BurrowState::Stopped);
+        assert_eq!(BurrowState::Draining.to_string(), "draining");
+    }
+}
@@ -3,15 +3,24 @@
 //! A text-based, peer-to-peer, asynchronous protocol engine for
 //! building federated networks of burrows and warrens.
 
+pub mod admin;
 pub mod ai;
+pub mod bridge;
 pub mod burrow;
 pub mod gui;
 pub mod config;
 pub mod content;
+pub mod daemon;
 pub mod dispatch;
+pub mod error;
 pub mod events;
+pub mod hooks;
+pub mod logging;
+pub mod network;
 pub mod protocol;
 pub mod security;
 pub mod session;
+pub mod snapshot;
 pub mod transport;
+mod util;
 pub mod warren;
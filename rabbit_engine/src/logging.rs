@@ -0,0 +1,160 @@
+//! Tracing subscriber setup shared by the binaries.
+//!
+//! [`init`] installs a global subscriber built from a
+//! [`LoggingConfig`]: an [`EnvFilter`] from the configured level and
+//! per-module directives (or `RABBIT_LOG`), text/pretty/JSON output on
+//! stderr, and an optional rolling log file.
+//!
+//! The library itself only emits `tracing` events and spans.  Tunnel
+//! handling runs inside a span carrying the burrow name and peer ID,
+//! and each dispatched frame inside a child span with its lane and
+//! verb, so every line can be traced back to a conversation.
+
+use std::io::IsTerminal;
+use std::path::Path;
+
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer, Registry};
+
+use crate::config::LoggingConfig;
+use crate::protocol::error::ProtocolError;
+
+/// Environment variable that replaces the configured filter.
+pub const ENV_FILTER_VAR: &str = "RABBIT_LOG";
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Keeps the background log-file writer alive.  Hold it until exit;
+/// dropping it flushes buffered lines.
+#[must_use = "dropping the guard stops file logging"]
+#[derive(Debug, Default)]
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+}
+
+/// Build the filter directives from the config: the level followed by
+/// each per-module filter.
+pub fn config_directives(config: &LoggingConfig) -> String {
+    std::iter::once(config.level.as_str())
+        .chain(config.filters.iter().map(String::as_str))
+        .filter(|d| !d.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parse filter directives into an [`EnvFilter`].
+pub fn build_filter(directives: &str) -> Result<EnvFilter, ProtocolError> {
+    EnvFilter::try_new(directives).map_err(|e| {
+        ProtocolError::InternalError(format!("invalid log filter {:?}: {}", directives, e))
+    })
+}
+
+fn parse_rotation(rotation: &str) -> Rotation {
+    match rotation {
+        "never" => Rotation::NEVER,
+        "minutely" => Rotation::MINUTELY,
+        "hourly" => Rotation::HOURLY,
+        _ => Rotation::DAILY,
+    }
+}
+
+fn fmt_layer<W>(format: &str, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        "json" => layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+        "pretty" => layer.pretty().boxed(),
+        _ => layer.with_target(false).boxed(),
+    }
+}
+
+/// Install the global subscriber.
+///
+/// `base_dir` resolves a relative `file`.  `RABBIT_LOG`, when set,
+/// overrides `level` and `filters`.  Fails if the filter does not
+/// parse or a subscriber is already installed.
+pub fn init(config: &LoggingConfig, base_dir: &Path) -> Result<LogGuard, ProtocolError> {
+    let directives = std::env::var(ENV_FILTER_VAR)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| config_directives(config));
+    let filter = build_filter(&directives)?;
+
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    if config.stderr {
+        let ansi = std::io::stderr().is_terminal();
+        layers.push(fmt_layer(&config.format, std::io::stderr, ansi));
+    }
+
+    let mut guard = LogGuard::default();
+    if let Some(file) = &config.file {
+        let path = base_dir.join(file);
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let name = path.file_name().ok_or_else(|| {
+            ProtocolError::InternalError(format!("log file {} has no name", path.display()))
+        })?;
+        let appender = RollingFileAppender::builder()
+            .rotation(parse_rotation(&config.rotation))
+            .filename_prefix(name.to_string_lossy())
+            .build(dir)
+            .map_err(|e| {
+                ProtocolError::InternalError(format!(
+                    "failed to open log file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        let (writer, file_guard) = tracing_appender::non_blocking(appender);
+        layers.push(fmt_layer(&config.format, writer, false));
+        guard._file = Some(file_guard);
+    }
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(filter)
+        .try_init()
+        .map_err(|e| {
+            ProtocolError::InternalError(format!("failed to install log subscriber: {}", e))
+        })?;
+    Ok(guard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directives_from_config() {
+        let mut config = LoggingConfig::default();
+        assert_eq!(config_directives(&config), "info");
+        config.level = "warn".into();
+        config.filters = vec!["rabbit_engine::transport=debug".into()];
+        assert_eq!(
+            config_directives(&config),
+            "warn,rabbit_engine::transport=debug"
+        );
+        build_filter(&config_directives(&config)).unwrap();
+    }
+
+    #[test]
+    fn bad_filter_rejected() {
+        assert!(build_filter("rabbit_engine=loud").is_err());
+    }
+
+    #[test]
+    fn rotation_names() {
+        assert_eq!(parse_rotation("hourly"), Rotation::HOURLY);
+        assert_eq!(parse_rotation("never"), Rotation::NEVER);
+        assert_eq!(parse_rotation("daily"), Rotation::DAILY);
+    }
+}
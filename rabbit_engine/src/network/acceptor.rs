@@ -0,0 +1,198 @@
+//! Inbound connections, within limits.
+//!
+//! [`run_listener`] accepts tunnels from any [`Acceptor`] and hands
+//! each to the current burrow, enforcing the listener's
+//! [`ListenerLimits`] first so that a flood of connections cannot
+//! swamp a small burrow:
+//!
+//! - once `max_tunnels` tunnels are open through the listener, new
+//!   connections are answered `503 BUSY` and closed;
+//! - an IP address that starts more than `handshakes_per_sec`
+//!   handshakes in a second is answered `429 SLOW-DOWN` and closed;
+//! - a tunnel that has not completed the Rabbit handshake within
+//!   `handshake_timeout_secs` is closed.
+//!
+//! These apply per listener, on top of the burrow-wide
+//! `network.max_connections` and `network.handshake_rate_limit_per_min`.
+//! What each listener accepted and refused is counted in
+//! [`ListenerCounters`], which the admin `listeners` command reports.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::burrow::Burrow;
+use crate::config::ListenerLimits;
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+use crate::transport::stats::{ListenerCounters, ListenerEvent};
+use crate::transport::tunnel::{Acceptor, Tunnel};
+
+/// How long a refused connection gets to read why before it is closed.
+const REFUSAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Handshakes started this second, per source address.
+#[derive(Debug)]
+struct HandshakeRate {
+    per_sec: u32,
+    windows: HashMap<IpAddr, (Instant, u32)>,
+}
+
+impl HandshakeRate {
+    fn new(per_sec: u32) -> Self {
+        Self {
+            per_sec,
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Whether `ip` may start another handshake at `now`.
+    fn admit(&mut self, ip: IpAddr, now: Instant) -> bool {
+        if self.per_sec == 0 {
+            return true;
+        }
+        let second = Duration::from_secs(1);
+        if self.windows.len() > 1024 {
+            self.windows
+                .retain(|_, (start, _)| now.saturating_duration_since(*start) < second);
+        }
+        let (start, count) = self.windows.entry(ip).or_insert((now, 0));
+        if now.saturating_duration_since(*start) >= second {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= self.per_sec {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+/// A tunnel open through a listener, counted until dropped.
+struct Slot(Arc<ListenerCounters>);
+
+impl Slot {
+    fn open(counters: &Arc<ListenerCounters>) -> Self {
+        counters.tunnel_opened();
+        Self(Arc::clone(counters))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.tunnel_closed();
+    }
+}
+
+/// Accept tunnels from `acceptor` within `limits` until aborted, each
+/// served by the burrow current when it arrives, and count what
+/// happens in `counters`.
+pub fn run_listener<A: Acceptor + 'static>(
+    acceptor: A,
+    limits: ListenerLimits,
+    counters: Arc<ListenerCounters>,
+    burrows: watch::Receiver<Arc<Burrow>>,
+) -> JoinHandle<()> {
+    match acceptor.local_addr() {
+        Ok(local_addr) => info!(%local_addr, "listening for connections"),
+        Err(e) => warn!(err = %e, "listening on an unknown address"),
+    }
+    tokio::spawn(async move {
+        let mut rate = HandshakeRate::new(limits.handshakes_per_sec);
+        loop {
+            let mut tunnel = match acceptor.accept().await {
+                Ok(tunnel) => tunnel,
+                Err(e) => {
+                    counters.record(ListenerEvent::AcceptFailed);
+                    warn!(err = %e, "accept failed");
+                    continue;
+                }
+            };
+            let ip = tunnel.peer_addr().map(|addr| addr.ip());
+            if let Some(ip) = ip.filter(|&ip| !rate.admit(ip, Instant::now())) {
+                counters.record(ListenerEvent::RefusedRate);
+                warn!(%ip, "too many handshakes, refusing connection");
+                refuse(
+                    tunnel,
+                    ProtocolError::SlowDown {
+                        reason: "too many handshakes from this address".into(),
+                        retry_after: 1,
+                    },
+                );
+                continue;
+            }
+            if limits.max_tunnels > 0 && counters.active() >= limits.max_tunnels as u64 {
+                counters.record(ListenerEvent::RefusedFull);
+                warn!(
+                    max = limits.max_tunnels,
+                    "listener full, refusing connection"
+                );
+                refuse(
+                    tunnel,
+                    ProtocolError::Busy("listener connection limit reached".into()),
+                );
+                continue;
+            }
+
+            counters.record(ListenerEvent::Accepted);
+            let slot = Slot::open(&counters);
+            let burrow = Arc::clone(&burrows.borrow());
+            let handshake_timeout = match limits.handshake_timeout_secs {
+                0 => burrow.handshake_timeout_secs,
+                secs => secs,
+            };
+            tokio::spawn(async move {
+                info!(peer = ?ip, "accepted connection");
+                let timeout = Duration::from_secs(handshake_timeout);
+                match burrow.handle_tunnel_within(&mut tunnel, timeout).await {
+                    Ok(id) => info!(peer_id = %id, "tunnel closed cleanly"),
+                    Err(ProtocolError::Timeout(e)) => {
+                        slot.0.record(ListenerEvent::HandshakeTimeout);
+                        warn!(err = %e, "tunnel error");
+                    }
+                    Err(e) => warn!(err = %e, "tunnel error"),
+                }
+                drop(slot);
+            });
+        }
+    })
+}
+
+/// Tell `tunnel` why it is refused and close it, without holding up
+/// the accept loop.
+fn refuse<T: Tunnel + 'static>(mut tunnel: T, reason: ProtocolError) {
+    tokio::spawn(async move {
+        let _ = tokio::time::timeout(REFUSAL_TIMEOUT, async {
+            let _ = tunnel.send_frame(&Frame::from(reason)).await;
+            let _ = tunnel.close().await;
+        })
+        .await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshakes_are_limited_per_address_per_second() {
+        let mut rate = HandshakeRate::new(2);
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+        assert!(rate.admit(a, start));
+        assert!(rate.admit(a, start));
+        assert!(!rate.admit(a, start + Duration::from_millis(500)));
+        assert!(rate.admit(b, start));
+        assert!(rate.admit(a, start + Duration::from_secs(1)));
+
+        let mut unlimited = HandshakeRate::new(0);
+        assert!((0..100).all(|_| unlimited.admit(a, start)));
+    }
+}
@@ -0,0 +1,538 @@
+//! Local discovery over multicast DNS (DNS-SD).
+//!
+//! With `discovery.mdns` on, a burrow advertises itself on the local
+//! network as an instance of the `_rabbit._tcp.local` service and
+//! browses for other instances.  Its answer to a query for the service
+//! carries three records:
+//!
+//! ```text
+//! _rabbit._tcp.local        PTR  <name>._rabbit._tcp.local
+//! <name>._rabbit._tcp.local SRV  0 0 <port> <host>.local
+//! <name>._rabbit._tcp.local TXT  "id=ed25519:…" "name=<name>" "caps=Fetch,List,…"
+//!                                "ts=<unix secs>" ["addr=<ip:port>"] "sig=<hex>"
+//! ```
+//!
+//! `caps` lists the capabilities granted to a peer that authenticates.
+//! `addr`, if present, is where the burrow can be dialed from outside
+//! the local network (see [`super::nat`]).  `sig` is the burrow's
+//! Ed25519 signature over
+//! `RABBIT-MDNS\n<id>\n<name>\n<port>\n<caps>\n<ts>`, followed by
+//! `\n<addr>` if there is one, so no one else can advertise its ID
+//! with another port, address or capabilities; answers
+//! unsigned, wrongly signed or more than [`MAX_AGE_SECS`] off the clock
+//! are ignored.  No address records are sent: a burrow found this way
+//! is reached at the address its answer came from and the SRV port.
+//!
+//! Every `discovery.mdns_interval_secs` the [`MdnsService`] queries for
+//! the service and announces itself unasked; it answers the queries of
+//! others as they arrive.  Each burrow found is registered in the
+//! burrow's peer table, again only when its address changes.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::burrow::Burrow;
+use crate::protocol::error::ProtocolError;
+use crate::security::auth::{hex_decode, hex_encode};
+use crate::security::identity::{parse_burrow_id, Identity};
+use crate::util::now_unix;
+use crate::warren::peers::PeerInfo;
+
+use super::dns::{
+    header, labels_eq, put_name, put_question, put_record, txt_entries, txt_rdata, Message,
+    CACHE_FLUSH, CLASS_IN, FLAG_AUTHORITATIVE, FLAG_RESPONSE, TYPE_ANY, TYPE_PTR, TYPE_SRV,
+    TYPE_TXT,
+};
+
+/// The DNS-SD service type, as labels.
+pub const SERVICE_TYPE: &[&str] = &["_rabbit", "_tcp", "local"];
+
+/// The mDNS multicast group.
+pub const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+/// The mDNS port.
+pub const MDNS_PORT: u16 = 5353;
+
+/// How far an announcement's `ts` may be from the clock, in seconds.
+pub const MAX_AGE_SECS: u64 = 300;
+
+/// TTL of the records a burrow announces, in seconds.
+const RECORD_TTL: u32 = 120;
+
+/// Largest packet read.
+const MAX_PACKET: usize = 9000;
+
+/// Longest entry a TXT record can hold.
+const MAX_TXT_ENTRY: usize = 255;
+
+/// A burrow as advertised over DNS-SD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    /// The service instance name, the first label of its records.
+    pub instance: String,
+    /// The burrow's ID.
+    pub burrow_id: String,
+    /// The burrow's name.
+    pub name: String,
+    /// The port it listens on.
+    pub port: u16,
+    /// The capabilities it grants authenticated peers.
+    pub caps: Vec<String>,
+    /// When it was signed (Unix seconds).
+    pub timestamp: u64,
+    /// Where it can be dialed from outside the local network, if it
+    /// knows.
+    pub external: Option<String>,
+    /// The burrow's signature (empty if unsigned).
+    pub signature: Vec<u8>,
+}
+
+impl Announcement {
+    /// Describe `burrow`, listening on `port`, signed now.
+    pub fn for_burrow(burrow: &Burrow, port: u16) -> Self {
+        let burrow_id = burrow.burrow_id();
+        let instance = if burrow.name.is_empty() {
+            burrow_id.clone()
+        } else {
+            burrow.name.clone()
+        };
+        let mut announcement = Self {
+            instance,
+            burrow_id,
+            name: burrow.name.clone(),
+            port,
+            caps: burrow
+                .authenticated_caps()
+                .iter()
+                .map(|c| c.label().to_string())
+                .collect(),
+            timestamp: now_unix(),
+            external: burrow.external_addr().map(|addr| addr.to_string()),
+            signature: Vec::new(),
+        };
+        announcement.sign(&burrow.identity);
+        announcement
+    }
+
+    /// The bytes `sig` covers.
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut signed = format!(
+            "RABBIT-MDNS\n{}\n{}\n{}\n{}\n{}",
+            self.burrow_id,
+            self.name,
+            self.port,
+            self.caps.join(","),
+            self.timestamp
+        );
+        if let Some(external) = &self.external {
+            signed.push('\n');
+            signed.push_str(external);
+        }
+        signed.into_bytes()
+    }
+
+    /// Sign the announcement as `identity`, which must be the burrow
+    /// it describes.
+    pub fn sign(&mut self, identity: &Identity) {
+        self.signature = identity.sign(&self.signed_bytes());
+    }
+
+    /// Check the announcement was signed by the burrow it names, no
+    /// more than [`MAX_AGE_SECS`] from `now`.  Fails with `Forbidden`
+    /// otherwise.
+    pub fn verify(&self, now: u64) -> Result<(), ProtocolError> {
+        if self.timestamp.abs_diff(now) > MAX_AGE_SECS {
+            return Err(ProtocolError::Forbidden(format!(
+                "announcement of {} is stale",
+                self.burrow_id
+            )));
+        }
+        if self.signature.is_empty() {
+            return Err(ProtocolError::Forbidden(format!(
+                "announcement of {} is unsigned",
+                self.burrow_id
+            )));
+        }
+        let key = parse_burrow_id(&self.burrow_id)?;
+        Identity::verify(&key, &self.signed_bytes(), &self.signature)
+    }
+
+    /// The DNS response advertising the burrow.  Fails if a TXT entry
+    /// would not fit the 255 bytes DNS allows it — a long `name`, say —
+    /// rather than send less than `sig` covers.
+    pub fn to_response(&self) -> Result<Vec<u8>, ProtocolError> {
+        let mut packet = header(0, FLAG_RESPONSE | FLAG_AUTHORITATIVE, 0, 3);
+        let service: Vec<&str> = SERVICE_TYPE.to_vec();
+        let mut instance = vec![self.instance.as_str()];
+        instance.extend_from_slice(SERVICE_TYPE);
+        let host = host_label(&self.burrow_id);
+
+        let mut rdata = Vec::new();
+        put_name(&mut rdata, &instance);
+        put_record(
+            &mut packet,
+            &service,
+            TYPE_PTR,
+            CLASS_IN,
+            RECORD_TTL,
+            &rdata,
+        );
+
+        let mut rdata = Vec::new();
+        rdata.extend_from_slice(&0u16.to_be_bytes()); // priority
+        rdata.extend_from_slice(&0u16.to_be_bytes()); // weight
+        rdata.extend_from_slice(&self.port.to_be_bytes());
+        put_name(&mut rdata, &[host.as_str(), "local"]);
+        put_record(
+            &mut packet,
+            &instance,
+            TYPE_SRV,
+            CLASS_IN | CACHE_FLUSH,
+            RECORD_TTL,
+            &rdata,
+        );
+
+        let mut entries = vec![
+            format!("id={}", self.burrow_id),
+            format!("name={}", self.name),
+            format!("caps={}", self.caps.join(",")),
+            format!("ts={}", self.timestamp),
+        ];
+        if let Some(external) = &self.external {
+            entries.push(format!("addr={}", external));
+        }
+        entries.push(format!("sig={}", hex_encode(&self.signature)));
+        if let Some(entry) = entries.iter().find(|entry| entry.len() > MAX_TXT_ENTRY) {
+            let key = entry.split('=').next().unwrap_or_default();
+            return Err(ProtocolError::TooLarge(format!(
+                "mDNS TXT entry {key} is {} bytes, more than {MAX_TXT_ENTRY}",
+                entry.len()
+            )));
+        }
+        let rdata = txt_rdata(&entries);
+        put_record(
+            &mut packet,
+            &instance,
+            TYPE_TXT,
+            CLASS_IN | CACHE_FLUSH,
+            RECORD_TTL,
+            &rdata,
+        );
+        Ok(packet)
+    }
+
+    /// The burrows advertised in a DNS response.  Instances without
+    /// both an SRV record and a TXT record naming an `id` are skipped.
+    pub fn from_response(packet: &[u8]) -> Result<Vec<Self>, ProtocolError> {
+        let message = Message::parse(packet)?;
+        if !message.is_response() {
+            return Ok(Vec::new());
+        }
+        let mut ports: HashMap<String, u16> = HashMap::new();
+        let mut texts: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for record in &message.records {
+            let Some(instance) = service_instance(&record.name) else {
+                continue;
+            };
+            match record.rtype {
+                TYPE_SRV if record.rdata.len() >= 6 => {
+                    let port = u16::from_be_bytes([record.rdata[4], record.rdata[5]]);
+                    ports.insert(instance, port);
+                }
+                TYPE_TXT => {
+                    texts.insert(instance, txt_entries(&record.rdata));
+                }
+                _ => {}
+            }
+        }
+        let mut found: Vec<Self> = ports
+            .into_iter()
+            .filter_map(|(instance, port)| {
+                let txt = texts.get(&instance)?;
+                Some(Self {
+                    burrow_id: txt.get("id")?.clone(),
+                    name: txt.get("name").cloned().unwrap_or_default(),
+                    caps: txt
+                        .get("caps")
+                        .map(|caps| {
+                            caps.split(',')
+                                .filter(|c| !c.is_empty())
+                                .map(String::from)
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    timestamp: txt.get("ts").and_then(|ts| ts.parse().ok()).unwrap_or(0),
+                    external: txt.get("addr").cloned(),
+                    signature: txt
+                        .get("sig")
+                        .and_then(|sig| hex_decode(sig).ok())
+                        .unwrap_or_default(),
+                    instance,
+                    port,
+                })
+            })
+            .collect();
+        found.sort_by(|a, b| a.instance.cmp(&b.instance));
+        Ok(found)
+    }
+}
+
+/// A DNS query for the service's instances.
+pub fn query() -> Vec<u8> {
+    let mut packet = header(0, 0, 1, 0);
+    put_question(&mut packet, SERVICE_TYPE, TYPE_PTR);
+    packet
+}
+
+/// Whether `packet` is a query asking for the service's instances.
+pub fn is_service_query(packet: &[u8]) -> bool {
+    match Message::parse(packet) {
+        Ok(message) => {
+            !message.is_response()
+                && message.questions.iter().any(|(name, qtype)| {
+                    (*qtype == TYPE_PTR || *qtype == TYPE_ANY) && labels_eq(name, SERVICE_TYPE)
+                })
+        }
+        Err(_) => false,
+    }
+}
+
+/// Advertises a burrow over mDNS and registers the burrows it finds.
+#[derive(Debug)]
+pub struct MdnsService {
+    socket: UdpSocket,
+    /// Where queries and announcements are sent: the mDNS group, or
+    /// one peer in tests.
+    target: SocketAddr,
+}
+
+impl MdnsService {
+    /// Join the mDNS group on port 5353, sharing the port with any
+    /// other responder on the host.  Must be called within a Tokio
+    /// runtime.
+    pub fn bind() -> io::Result<Self> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, MDNS_PORT)).into())?;
+        socket.join_multicast_v4(&MDNS_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+        socket.set_multicast_loop_v4(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket: UdpSocket::from_std(socket.into())?,
+            target: SocketAddr::from((MDNS_GROUP, MDNS_PORT)),
+        })
+    }
+
+    /// Use `socket`, sending queries and announcements to `target`
+    /// rather than the mDNS group.
+    pub fn with_socket(socket: UdpSocket, target: SocketAddr) -> Self {
+        Self { socket, target }
+    }
+
+    /// The address the service receives on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Advertise `burrow`, listening on `port`, and browse for others
+    /// every `interval` until the task is aborted.
+    pub fn spawn(self, burrow: Arc<Burrow>, port: u16, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(self.run(burrow, port, interval))
+    }
+
+    async fn run(self, burrow: Arc<Burrow>, port: u16, interval: Duration) {
+        let me = burrow.burrow_id();
+        // Re-signed each time it is sent, so it never goes stale.
+        let advertise = || Announcement::for_burrow(&burrow, port).to_response();
+        let mut response = match advertise() {
+            Ok(response) => Some(response),
+            Err(e) => {
+                warn!(err = %e, "not advertising over mDNS; browsing only");
+                None
+            }
+        };
+        let mut ticker = tokio::time::interval(interval);
+        let mut found: HashMap<String, SocketAddr> = HashMap::new();
+        let mut buf = vec![0u8; MAX_PACKET];
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if response.is_some() {
+                        response = advertise().ok();
+                    }
+                    for packet in std::iter::once(query()).chain(response.clone()) {
+                        if let Err(e) = self.socket.send_to(&packet, self.target).await {
+                            warn!(err = %e, "mDNS send failed");
+                        }
+                    }
+                }
+                received = self.socket.recv_from(&mut buf) => {
+                    let (len, from) = match received {
+                        Ok(received) => received,
+                        Err(e) => {
+                            warn!(err = %e, "mDNS receive failed");
+                            continue;
+                        }
+                    };
+                    let packet = &buf[..len];
+                    if is_service_query(packet) {
+                        if let Some(response) = &response {
+                            if let Err(e) = self.socket.send_to(response, self.target).await {
+                                warn!(err = %e, "mDNS send failed");
+                            }
+                        }
+                        continue;
+                    }
+                    let announcements = match Announcement::from_response(packet) {
+                        Ok(announcements) => announcements,
+                        Err(e) => {
+                            debug!(%from, err = %e, "ignoring malformed mDNS packet");
+                            continue;
+                        }
+                    };
+                    for found_burrow in announcements {
+                        if found_burrow.burrow_id == me {
+                            continue;
+                        }
+                        if let Err(e) = found_burrow.verify(now_unix()) {
+                            debug!(%from, err = %e, "ignoring mDNS announcement");
+                            continue;
+                        }
+                        let addr = SocketAddr::new(from.ip(), found_burrow.port);
+                        if found.get(&found_burrow.burrow_id) == Some(&addr) {
+                            continue;
+                        }
+                        register(&burrow, &found_burrow, addr).await;
+                        found.insert(found_burrow.burrow_id.clone(), addr);
+                        info!(
+                            peer_id = %found_burrow.burrow_id,
+                            name = %found_burrow.name,
+                            %addr,
+                            "discovered burrow on the local network"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Register a burrow found at `addr` in the peer table.
+async fn register(burrow: &Burrow, found: &Announcement, addr: SocketAddr) {
+    let address = match addr.ip() {
+        IpAddr::V4(_) => addr.to_string(),
+        IpAddr::V6(ip) => format!("[{}]:{}", ip, addr.port()),
+    };
+    burrow
+        .peers
+        .register(PeerInfo::new(&found.burrow_id, address, &found.name))
+        .await;
+}
+
+/// The instance label of a name under the service type.
+fn service_instance(name: &[String]) -> Option<String> {
+    match name.split_first() {
+        Some((instance, rest)) if labels_eq(rest, SERVICE_TYPE) => Some(instance.clone()),
+        _ => None,
+    }
+}
+
+/// A host label from a burrow ID, e.g. `rabbit-abcdefghijkl`.
+fn host_label(burrow_id: &str) -> String {
+    let key = burrow_id.strip_prefix("ed25519:").unwrap_or(burrow_id);
+    let short: String = key
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(12)
+        .collect();
+    format!("rabbit-{}", short.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn announcements_round_trip_through_dns() {
+        let identity = Identity::generate();
+        let mut announcement = Announcement {
+            instance: "my burrow".into(),
+            burrow_id: identity.burrow_id(),
+            name: "my burrow".into(),
+            port: 7443,
+            caps: vec!["Fetch".into(), "List".into()],
+            timestamp: 1000,
+            external: None,
+            signature: Vec::new(),
+        };
+        assert!(announcement.verify(1000).is_err());
+        announcement.sign(&identity);
+        let packet = announcement.to_response().unwrap();
+        let parsed = Announcement::from_response(&packet).unwrap();
+        assert_eq!(parsed, [announcement.clone()]);
+        parsed[0].verify(1000 + MAX_AGE_SECS).unwrap();
+        assert!(parsed[0].verify(1001 + MAX_AGE_SECS).is_err());
+
+        // Another port, or another burrow's key, breaks the signature.
+        let mut moved = announcement.clone();
+        moved.port = 7444;
+        assert!(matches!(
+            moved.verify(1000),
+            Err(ProtocolError::Forbidden(_))
+        ));
+        let mut forged = announcement.clone();
+        forged.sign(&Identity::generate());
+        assert!(forged.verify(1000).is_err());
+
+        // An external address travels signed.
+        let mut mapped = announcement.clone();
+        mapped.external = Some("203.0.113.7:7443".into());
+        assert!(mapped.verify(1000).is_err());
+        mapped.sign(&identity);
+        let parsed = Announcement::from_response(&mapped.to_response().unwrap()).unwrap();
+        assert_eq!(parsed, [mapped]);
+        parsed[0].verify(1000).unwrap();
+
+        assert!(!is_service_query(&packet));
+        assert!(is_service_query(&query()));
+        assert!(Announcement::from_response(&query()).unwrap().is_empty());
+        assert!(Announcement::from_response(&packet[..packet.len() - 3]).is_err());
+    }
+
+    #[test]
+    fn announcements_too_long_for_txt_are_refused() {
+        let identity = Identity::generate();
+        let mut announcement = Announcement {
+            instance: "burrow".into(),
+            burrow_id: identity.burrow_id(),
+            name: "b".repeat(251),
+            port: 7443,
+            caps: vec!["Fetch".into()],
+            timestamp: 1000,
+            external: None,
+            signature: Vec::new(),
+        };
+        announcement.sign(&identity);
+        // "name=" and 250 bytes fill an entry exactly; one more would
+        // have been cut off, leaving the signature over a name never sent.
+        assert!(matches!(
+            announcement.to_response(),
+            Err(ProtocolError::TooLarge(_))
+        ));
+        announcement.name.pop();
+        announcement.sign(&identity);
+        let packet = announcement.to_response().unwrap();
+        let parsed = Announcement::from_response(&packet).unwrap();
+        parsed[0].verify(1000).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+//! The DNS wire format, as far as discovery and bootstrap need it.
+//!
+//! Messages are built with [`header`], [`put_question`] and
+//! [`put_record`] and read with [`Message::parse`], which follows name
+//! compression.  Only the record types Rabbit uses are decoded: SRV
+//! rdata has its target name expanded, everything else is kept raw.
+
+use std::collections::HashMap;
+
+use crate::protocol::error::ProtocolError;
+
+pub(crate) const TYPE_PTR: u16 = 12;
+pub(crate) const TYPE_TXT: u16 = 16;
+pub(crate) const TYPE_SRV: u16 = 33;
+pub(crate) const TYPE_ANY: u16 = 255;
+pub(crate) const CLASS_IN: u16 = 1;
+/// Marks an mDNS record as the only one of its name and type.
+pub(crate) const CACHE_FLUSH: u16 = 0x8000;
+pub(crate) const FLAG_RESPONSE: u16 = 0x8000;
+pub(crate) const FLAG_AUTHORITATIVE: u16 = 0x0400;
+pub(crate) const FLAG_TRUNCATED: u16 = 0x0200;
+pub(crate) const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+
+/// The parts of a DNS message Rabbit reads.
+pub(crate) struct Message {
+    pub(crate) id: u16,
+    pub(crate) flags: u16,
+    /// Each question's name and type.
+    pub(crate) questions: Vec<(Vec<String>, u16)>,
+    /// Answer, authority and additional records alike.
+    pub(crate) records: Vec<Record>,
+}
+
+pub(crate) struct Record {
+    pub(crate) name: Vec<String>,
+    pub(crate) rtype: u16,
+    pub(crate) rdata: Vec<u8>,
+}
+
+impl Message {
+    pub(crate) fn parse(packet: &[u8]) -> Result<Self, ProtocolError> {
+        let mut reader = Reader { packet, pos: 0 };
+        let id = reader.u16()?;
+        let flags = reader.u16()?;
+        let questions = reader.u16()?;
+        let records = reader.u16()? as usize + reader.u16()? as usize + reader.u16()? as usize;
+        let mut message = Self {
+            id,
+            flags,
+            questions: Vec::new(),
+            records: Vec::new(),
+        };
+        for _ in 0..questions {
+            let name = reader.name()?;
+            let qtype = reader.u16()?;
+            let _class = reader.u16()?;
+            message.questions.push((name, qtype));
+        }
+        for _ in 0..records {
+            let name = reader.name()?;
+            let rtype = reader.u16()?;
+            let _class = reader.u16()?;
+            let _ttl = reader.u32()?;
+            let len = reader.u16()? as usize;
+            let start = reader.pos;
+            reader.take(len)?;
+            // An SRV target may be compressed against the message.
+            let rdata = if rtype == TYPE_SRV && len >= 6 {
+                let mut target = Reader {
+                    packet,
+                    pos: start + 6,
+                };
+                let mut rdata = packet[start..start + 6].to_vec();
+                let labels = target.name()?;
+                let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+                put_name(&mut rdata, &labels);
+                rdata
+            } else {
+                packet[start..start + len].to_vec()
+            };
+            message.records.push(Record { name, rtype, rdata });
+        }
+        Ok(message)
+    }
+
+    /// Whether the message is a response rather than a query.
+    pub(crate) fn is_response(&self) -> bool {
+        self.flags & FLAG_RESPONSE != 0
+    }
+
+    /// The response code (0 = no error, 3 = no such name).
+    pub(crate) fn rcode(&self) -> u16 {
+        self.flags & 0x000F
+    }
+}
+
+struct Reader<'a> {
+    packet: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], ProtocolError> {
+        let bytes = self
+            .packet
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| ProtocolError::BadRequest("truncated DNS message".into()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, ProtocolError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ProtocolError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read a name, following compression pointers.
+    fn name(&mut self) -> Result<Vec<String>, ProtocolError> {
+        let mut labels = Vec::new();
+        let mut pos = self.pos;
+        let mut jumped = false;
+        for _ in 0..128 {
+            let len = *self
+                .packet
+                .get(pos)
+                .ok_or_else(|| ProtocolError::BadRequest("truncated DNS name".into()))?
+                as usize;
+            if len & 0xC0 == 0xC0 {
+                let low = *self
+                    .packet
+                    .get(pos + 1)
+                    .ok_or_else(|| ProtocolError::BadRequest("truncated DNS name".into()))?;
+                if !jumped {
+                    self.pos = pos + 2;
+                    jumped = true;
+                }
+                pos = ((len & 0x3F) << 8) | low as usize;
+                continue;
+            }
+            if len == 0 {
+                if !jumped {
+                    self.pos = pos + 1;
+                }
+                return Ok(labels);
+            }
+            let label = self
+                .packet
+                .get(pos + 1..pos + 1 + len)
+                .ok_or_else(|| ProtocolError::BadRequest("truncated DNS name".into()))?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            pos += 1 + len;
+        }
+        Err(ProtocolError::BadRequest("DNS name loops".into()))
+    }
+}
+
+/// Start a message: the header, with no authority or additional
+/// records.
+pub(crate) fn header(id: u16, flags: u16, questions: u16, answers: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(512);
+    for field in [id, flags, questions, answers, 0, 0] {
+        packet.extend_from_slice(&field.to_be_bytes());
+    }
+    packet
+}
+
+pub(crate) fn put_name(out: &mut Vec<u8>, labels: &[&str]) {
+    for label in labels {
+        let bytes = &label.as_bytes()[..label.len().min(63)];
+        out.push(bytes.len() as u8);
+        out.extend_from_slice(bytes);
+    }
+    out.push(0);
+}
+
+pub(crate) fn put_question(out: &mut Vec<u8>, name: &[&str], qtype: u16) {
+    put_name(out, name);
+    out.extend_from_slice(&qtype.to_be_bytes());
+    out.extend_from_slice(&CLASS_IN.to_be_bytes());
+}
+
+pub(crate) fn put_record(
+    out: &mut Vec<u8>,
+    name: &[&str],
+    rtype: u16,
+    class: u16,
+    ttl: u32,
+    rdata: &[u8],
+) {
+    put_name(out, name);
+    out.extend_from_slice(&rtype.to_be_bytes());
+    out.extend_from_slice(&class.to_be_bytes());
+    out.extend_from_slice(&ttl.to_be_bytes());
+    out.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+    out.extend_from_slice(rdata);
+}
+
+/// TXT rdata holding `entries`, each cut to 255 bytes.
+pub(crate) fn txt_rdata(entries: &[String]) -> Vec<u8> {
+    let mut rdata = Vec::new();
+    for entry in entries {
+        let bytes = &entry.as_bytes()[..entry.len().min(255)];
+        rdata.push(bytes.len() as u8);
+        rdata.extend_from_slice(bytes);
+    }
+    rdata
+}
+
+pub(crate) fn labels_eq(name: &[String], expected: &[&str]) -> bool {
+    name.len() == expected.len()
+        && name
+            .iter()
+            .zip(expected)
+            .all(|(a, b)| a.eq_ignore_ascii_case(b))
+}
+
+/// The `key=value` entries of TXT rdata, keys lower-cased.
+pub(crate) fn txt_entries(rdata: &[u8]) -> HashMap<String, String> {
+    let mut entries = HashMap::new();
+    let mut pos = 0;
+    while let Some(&len) = rdata.get(pos) {
+        let Some(entry) = rdata.get(pos + 1..pos + 1 + len as usize) else {
+            break;
+        };
+        let entry = String::from_utf8_lossy(entry);
+        if let Some((key, value)) = entry.split_once('=') {
+            entries.insert(key.to_ascii_lowercase(), value.to_string());
+        }
+        pos += 1 + len as usize;
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressed_names_are_followed() {
+        // A PTR answer whose name points back at the question.
+        let service = ["_rabbit", "_tcp", "local"];
+        let mut packet = header(0, FLAG_RESPONSE, 1, 1);
+        put_question(&mut packet, &service, TYPE_PTR);
+        packet.extend_from_slice(&[0xC0, 12]);
+        packet.extend_from_slice(&TYPE_PTR.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet.extend_from_slice(&120u32.to_be_bytes());
+        packet.extend_from_slice(&8u16.to_be_bytes());
+        packet.extend_from_slice(&[5, b'b', b'u', b'n', b'n', b'y', 0xC0, 12]);
+        let message = Message::parse(&packet).unwrap();
+        assert!(message.is_response());
+        assert!(labels_eq(&message.records[0].name, &service));
+
+        // A pointer to itself loops.
+        let mut looped = header(0, 0, 1, 0);
+        looped.extend_from_slice(&[0xC0, 12]);
+        assert!(Message::parse(&looped).is_err());
+    }
+}
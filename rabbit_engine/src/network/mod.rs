@@ -0,0 +1,452 @@
+//! Outbound peer connections that come back after failures.
+//!
+//! A [`ConnectionManager`] dials each peer address it is given (usually
+//! `network.peers`, and the addresses of federation anchors and links),
+//! runs the client handshake — and the federation link's
+//! authentication if the peer is linked, or a fetch of its latest
+//! manifest if it is an anchor — and serves the tunnel until it
+//! closes.  Whenever a dial fails or a session ends it waits
+//! and dials again, backing off exponentially from
+//! `network.reconnect_min_secs` to `network.reconnect_max_secs`.  Up to
+//! half the delay again is added at random, so burrows restarted
+//! together do not all redial at once.  A session that got through the
+//! handshake starts the backoff over.  With `network.proxy` set, every
+//! dial goes through that SOCKS5 or HTTP proxy.  A peer named in
+//! `relay.register_with` is asked to relay for the burrow (see
+//! [`crate::warren::relay`]) once the handshake is done, and one
+//! whose topics the burrow follows is sent a `REPLICATE` for each (see
+//! [`crate::events::replication`]); the events it streams back are
+//! taken into the burrow's own log.
+//!
+//! Every change is sent as a [`ConnectionEvent`] to each subscriber
+//! (see [`ConnectionManager::subscribe`]) and is reflected in the
+//! burrow's peer table.  While a session is up, frames queued for the
+//! peer with [`Burrow::send_to`] or [`Burrow::broadcast`] are written
+//! to its tunnel, and frames from it addressed to other burrows are
+//! relayed (see [`Burrow::forward`]).
+//!
+//! Inbound connections are accepted by [`acceptor::run_listener`].
+
+pub mod acceptor;
+pub mod discovery;
+mod dns;
+pub mod nat;
+#[cfg(feature = "quic")]
+pub mod quic;
+pub mod resolver;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use rand::Rng;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::burrow::Burrow;
+use crate::config::NetworkConfig;
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::{Frame, Verb, VerbKind};
+use crate::transport::connector::connect_via;
+use crate::transport::keepalive;
+use crate::transport::proxy::Proxy;
+use crate::transport::tunnel::Tunnel;
+use crate::util::now_unix;
+use crate::warren::peers::PeerInfo;
+use crate::warren::relay;
+use crate::warren::routing::{address_reply, Forward};
+
+/// Events buffered for a subscriber that falls behind.
+const EVENT_CAPACITY: usize = 64;
+
+/// A change in an outbound connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// Dialing `addr`; `attempt` counts from 1 since the last session.
+    Connecting {
+        /// The peer address.
+        addr: String,
+        /// Dials since the last handshake completed.
+        attempt: u32,
+    },
+    /// The handshake with `addr` completed.
+    Connected {
+        /// The peer address.
+        addr: String,
+        /// The peer's burrow ID.
+        peer_id: String,
+    },
+    /// A dial failed or a session ended.
+    Disconnected {
+        /// The peer address.
+        addr: String,
+        /// The peer's burrow ID, if the handshake completed.
+        peer_id: Option<String>,
+        /// Why, e.g. the error that ended the session.
+        reason: String,
+    },
+    /// Waiting `delay` before dialing `addr` again.
+    Retrying {
+        /// The peer address.
+        addr: String,
+        /// How long until the next dial.
+        delay: Duration,
+    },
+}
+
+/// Exponential backoff between reconnection attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// Delay after the first failure.
+    pub min: Duration,
+    /// Largest delay, however many failures.
+    pub max: Duration,
+}
+
+impl Backoff {
+    /// Back off from `min` to at most `max`.
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self { min, max }
+    }
+
+    /// The delay after `failures` consecutive failures (from 1),
+    /// without jitter: `min` doubled for each failure after the first,
+    /// capped at `max`.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let doublings = failures.saturating_sub(1).min(31);
+        self.min.saturating_mul(1 << doublings).min(self.max)
+    }
+
+    /// [`Backoff::delay`] plus up to half again, at random.
+    pub fn jittered(&self, failures: u32) -> Duration {
+        let delay = self.delay(failures);
+        delay + delay.mul_f64(rand::thread_rng().gen_range(0.0..=0.5))
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), Duration::from_secs(60))
+    }
+}
+
+/// Keeps a burrow connected to its outbound peers.
+///
+/// Each peer is maintained by its own task, which runs until the peer
+/// is removed or the manager is stopped or dropped.
+pub struct ConnectionManager {
+    burrow: Arc<Burrow>,
+    backoff: Backoff,
+    /// Dial through this proxy, if set.
+    proxy: Option<Proxy>,
+    events: broadcast::Sender<ConnectionEvent>,
+    /// The task maintaining each peer address.
+    tasks: Mutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl std::fmt::Debug for ConnectionManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectionManager")
+            .field("burrow", &self.burrow.name)
+            .field("backoff", &self.backoff)
+            .field("proxy", &self.proxy)
+            .field("peers", &self.peers())
+            .finish()
+    }
+}
+
+impl ConnectionManager {
+    /// Create a manager with no peers yet.
+    pub fn new(burrow: Arc<Burrow>, backoff: Backoff) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            burrow,
+            backoff,
+            proxy: None,
+            events,
+            tasks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Dial peers added from now on through `proxy`.
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Create a manager and start dialing `network.peers`, the
+    /// burrow's federation anchors and links and the burrows it follows
+    /// topics on, backing off and proxying as `network` says.  Must be called within a Tokio runtime.
+    pub fn from_config(burrow: Arc<Burrow>, network: &NetworkConfig) -> Self {
+        let backoff = Backoff::new(
+            Duration::from_secs(network.reconnect_min_secs),
+            Duration::from_secs(network.reconnect_max_secs),
+        );
+        let mut manager = Self::new(burrow, backoff);
+        match network.proxy.as_deref().map(Proxy::parse) {
+            Some(Ok(proxy)) => manager = manager.with_proxy(proxy),
+            Some(Err(e)) => warn!(err = %e, "ignoring network.proxy"),
+            None => {}
+        }
+        let federated = manager.burrow.federation.dial_addresses();
+        let followed = manager.burrow.replicator.dial_addresses();
+        for addr in network.peers.iter().chain(&federated).chain(&followed) {
+            manager.add_peer(addr);
+        }
+        manager
+    }
+
+    /// Receive every event from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
+    }
+
+    /// Start maintaining a connection to `addr`.  Returns false if it
+    /// is already maintained.
+    pub fn add_peer(&self, addr: &str) -> bool {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        if tasks.contains_key(addr) {
+            return false;
+        }
+        let task = tokio::spawn(maintain(
+            Arc::clone(&self.burrow),
+            addr.to_string(),
+            self.backoff,
+            self.proxy.clone(),
+            self.events.clone(),
+        ));
+        tasks.insert(addr.to_string(), task);
+        true
+    }
+
+    /// Stop maintaining the connection to `addr`, closing it if it is
+    /// open.  Returns false if it was not maintained.
+    pub fn remove_peer(&self, addr: &str) -> bool {
+        let task = self
+            .tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(addr);
+        match task {
+            Some(task) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The peer addresses maintained, sorted.
+    pub fn peers(&self) -> Vec<String> {
+        let mut peers: Vec<String> = self
+            .tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect();
+        peers.sort();
+        peers
+    }
+
+    /// Stop maintaining every connection.
+    pub fn stop(&self) {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        for (_, task) in tasks.drain() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for ConnectionManager {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Dial `addr` and serve it, over and over, until aborted.
+async fn maintain(
+    burrow: Arc<Burrow>,
+    addr: String,
+    backoff: Backoff,
+    proxy: Option<Proxy>,
+    events: broadcast::Sender<ConnectionEvent>,
+) {
+    let mut failures = 0;
+    loop {
+        let _ = events.send(ConnectionEvent::Connecting {
+            addr: addr.clone(),
+            attempt: failures + 1,
+        });
+        let mut peer_id = None;
+        let reason = match session(&burrow, &addr, proxy.as_ref(), &events, &mut peer_id).await {
+            Ok(()) => "session closed".to_string(),
+            Err(e) => e.to_string(),
+        };
+        if let Some(id) = &peer_id {
+            burrow.peers.mark_disconnected(id).await;
+            info!(peer = %addr, remote_id = %id, %reason, "peer session ended");
+            failures = 0;
+        } else {
+            warn!(peer = %addr, err = %reason, "peer connection failed");
+        }
+        let _ = events.send(ConnectionEvent::Disconnected {
+            addr: addr.clone(),
+            peer_id,
+            reason,
+        });
+
+        failures += 1;
+        let delay = backoff.jittered(failures);
+        let _ = events.send(ConnectionEvent::Retrying {
+            addr: addr.clone(),
+            delay,
+        });
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Dial `addr`, run the handshake and serve the tunnel until it
+/// closes.  `peer_id` is set once the handshake completes.
+async fn session(
+    burrow: &Burrow,
+    addr: &str,
+    proxy: Option<&Proxy>,
+    events: &broadcast::Sender<ConnectionEvent>,
+    peer_id: &mut Option<String>,
+) -> Result<(), ProtocolError> {
+    info!(peer = %addr, "connecting to peer");
+    let mut tunnel = connect_via(addr, proxy, burrow.client_tls_config(None), "localhost").await?;
+    let server_id = burrow.client_handshake(&mut tunnel).await?;
+    if burrow.federation.link(&server_id).is_some() {
+        burrow.authenticate_link(&mut tunnel, &server_id).await?;
+        burrow.federation.open_link(&mut tunnel, &server_id).await?;
+    }
+    if burrow.federation.is_anchor(&server_id) {
+        // A stale manifest is better than no session.
+        let fetched = burrow
+            .federation
+            .fetch_manifest(&mut tunnel, &server_id)
+            .await;
+        match fetched.and_then(|manifest| burrow.accept_manifest(manifest)) {
+            Ok(kept) => info!(anchor = %server_id, kept, "fetched anchor manifest"),
+            Err(e) => warn!(anchor = %server_id, err = %e, "could not fetch anchor manifest"),
+        }
+    }
+    if burrow.register_relays.contains(&server_id) {
+        // The session is still worth having if the relay refuses.
+        match relay::register(&mut tunnel).await {
+            Ok(bandwidth) => info!(peer = %addr, bandwidth, "registered with relay"),
+            Err(e) => warn!(peer = %addr, err = %e, "relay registration refused"),
+        }
+    }
+    info!(peer = %addr, remote_id = %server_id, "handshake complete with peer");
+    burrow.coalesce_writes(&mut tunnel);
+
+    burrow
+        .peers
+        .register(PeerInfo::new(server_id.clone(), addr, ""))
+        .await;
+    burrow.peers.mark_connected(&server_id, now_unix()).await;
+    burrow.hooks.peer_connected(&server_id);
+    *peer_id = Some(server_id.clone());
+    let _ = events.send(ConnectionEvent::Connected {
+        addr: addr.to_string(),
+        peer_id: server_id.clone(),
+    });
+
+    // Frames for the peer from elsewhere in the burrow arrive through
+    // the session manager, as they do for accepted tunnels.
+    let (_registration, mut outbound) = burrow.sessions.attach(&server_id, 256);
+    for request in burrow.replication_requests(&server_id) {
+        tunnel.send_frame(&request).await?;
+    }
+    let dispatcher = burrow.dispatcher();
+    loop {
+        tokio::select! {
+            inbound = tunnel.recv_frame() => {
+                let Some(frame) = inbound? else {
+                    return Ok(());
+                };
+                match burrow.forward(&frame, &server_id).await {
+                    Forward::Local => {}
+                    Forward::Reply(err) => {
+                        tunnel.send_frame(&err).await?;
+                        continue;
+                    }
+                    Forward::Sent(_) | Forward::Dropped => continue,
+                }
+                // Responses answer frames queued for the peer, such as
+                // route adverts; nothing waits on them here.  A pong
+                // answers a health check.
+                if keepalive::is_pong(&frame) {
+                    burrow.peers.touch(&server_id, now_unix()).await;
+                    continue;
+                }
+                if matches!(frame.verb_kind(), VerbKind::Status(_)) {
+                    continue;
+                }
+                if let VerbKind::Verb(
+                    Verb::FedLinkOpen | Verb::FedLinkAccept | Verb::FedLinkClose,
+                ) = frame.verb_kind()
+                {
+                    if let Some(reply) = burrow.answer_link(&frame, &server_id) {
+                        tunnel.send_frame(&reply).await?;
+                    }
+                    continue;
+                }
+                if burrow.accept_replicated(&frame, &server_id).await {
+                    // A live event is numbered on its lane, and sent
+                    // again until acknowledged.
+                    if let (Some(_), Some(seq)) = (frame.header("Event-Seq"), frame.header("Seq")) {
+                        let mut ack = Frame::new("ACK");
+                        ack.set_header("Lane", frame.header("Lane").unwrap_or("0"));
+                        ack.set_header("ACK", seq);
+                        tunnel.send_frame(&ack).await?;
+                    }
+                    continue;
+                }
+                let mut result = match frame.verb_kind() {
+                    VerbKind::Verb(Verb::Manifest) => burrow.answer_manifest(&frame, &server_id).await,
+                    _ => dispatcher.dispatch(&frame, &server_id).await,
+                };
+                address_reply(&frame, &mut result.response);
+                tunnel.send_frame(&result.response).await?;
+                for extra in &mut result.extras {
+                    address_reply(&frame, extra);
+                    tunnel.send_frame(extra).await?;
+                }
+                if let Some(mut replay) = result.replay {
+                    while let Some(mut event) = replay.frames.next().await {
+                        address_reply(&frame, &mut event);
+                        tunnel.send_frame(&event).await?;
+                    }
+                }
+            }
+            queued = outbound.recv() => match queued {
+                Some(frame) => tunnel.send_frame(&frame).await?,
+                // Another tunnel to the same peer replaced this one.
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(10));
+        let delays: Vec<u64> = (1..=6).map(|n| backoff.delay(n).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 10, 10]);
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(10));
+        for _ in 0..100 {
+            let jittered = backoff.jittered(2);
+            assert!(jittered >= Duration::from_secs(2) && jittered <= Duration::from_secs(3));
+        }
+    }
+}
@@ -0,0 +1,647 @@
+//! Asking the local gateway to forward a port to the burrow.
+//!
+//! A burrow behind a home router can only be dialed if the router
+//! forwards its port.  With `network.nat_mapping` on, the burrow asks
+//! the gateway for a mapping when it starts listening, first with
+//! NAT-PMP (RFC 6886) and then, if the gateway does not answer, with
+//! UPnP IGD:
+//!
+//! - NAT-PMP sends the gateway, on UDP port 5351, a request for its
+//!   external address and one mapping the TCP port.
+//! - UPnP finds the gateway with an SSDP `M-SEARCH`, reads its device
+//!   description for the `WANIPConnection` (or `WANPPPConnection`)
+//!   control URL, and calls `GetExternalIPAddress` and
+//!   `AddPortMapping` there.
+//!
+//! The external address of the [`PortMapping`] is kept by the burrow
+//! (see [`crate::burrow::Burrow::external_addr`]) and carried in its
+//! mDNS announcements and `OFFER`s, so peers elsewhere can dial it
+//! directly.  Mappings are leased: the holder renews them at half
+//! their lifetime with [`PortMapping::renew`].
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::burrow::Burrow;
+use crate::protocol::error::ProtocolError;
+
+/// The port NAT-PMP gateways listen on.
+pub const NAT_PMP_PORT: u16 = 5351;
+
+/// The SSDP multicast address UPnP devices answer searches on.
+pub const SSDP_ADDR: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+
+/// How long each attempt waits for the gateway by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Lifetime asked for a mapping by default, in seconds.
+pub const DEFAULT_LIFETIME_SECS: u32 = 3600;
+
+/// How long to wait before trying again after no gateway mapped the
+/// port.
+const RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// The UPnP services that can map ports, newest first.
+const UPNP_SERVICES: &[&str] = &[
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// How a mapping was made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MappingMethod {
+    /// By NAT-PMP, from `gateway`.
+    NatPmp {
+        /// The gateway's NAT-PMP address.
+        gateway: SocketAddr,
+    },
+    /// By UPnP IGD, through `control_url` of `service`.
+    Upnp {
+        /// The control URL of the port-mapping service.
+        control_url: String,
+        /// The service type.
+        service: String,
+        /// This host's address, as the gateway sees it.
+        internal_ip: IpAddr,
+    },
+}
+
+/// A port the gateway forwards to the burrow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortMapping {
+    /// Where peers outside can dial.
+    pub external: SocketAddr,
+    /// The local port forwarded to.
+    pub internal_port: u16,
+    /// How long the gateway keeps the mapping, in seconds (0 for as
+    /// long as it runs).
+    pub lifetime_secs: u32,
+    /// How the mapping was made.
+    pub method: MappingMethod,
+}
+
+impl PortMapping {
+    /// Ask for the same mapping again, before it lapses.
+    pub async fn renew(&self) -> Result<PortMapping, ProtocolError> {
+        match &self.method {
+            MappingMethod::NatPmp { gateway } => {
+                map_nat_pmp(*gateway, self.internal_port, self.lifetime_secs).await
+            }
+            MappingMethod::Upnp {
+                control_url,
+                service,
+                internal_ip,
+            } => {
+                let upnp = Upnp {
+                    control_url: control_url.clone(),
+                    service: service.clone(),
+                    internal_ip: *internal_ip,
+                };
+                upnp.map(self.internal_port, self.lifetime_secs).await
+            }
+        }
+    }
+
+    /// Ask the gateway to drop the mapping.
+    pub async fn release(&self) -> Result<(), ProtocolError> {
+        match &self.method {
+            MappingMethod::NatPmp { gateway } => {
+                nat_pmp_exchange(*gateway, &map_request(self.internal_port, 0), 130).await?;
+                Ok(())
+            }
+            MappingMethod::Upnp {
+                control_url,
+                service,
+                ..
+            } => {
+                let args = format!(
+                    "<NewRemoteHost></NewRemoteHost>\
+                     <NewExternalPort>{}</NewExternalPort>\
+                     <NewProtocol>TCP</NewProtocol>",
+                    self.external.port()
+                );
+                soap_call(control_url, service, "DeletePortMapping", &args).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Map `port` with NAT-PMP from `gateway`, or else with UPnP.  Without
+/// a `gateway`, the default route's is used.
+pub async fn map_port(
+    gateway: Option<IpAddr>,
+    port: u16,
+    lifetime_secs: u32,
+) -> Result<PortMapping, ProtocolError> {
+    let gateway = gateway.or_else(|| default_gateway().map(IpAddr::V4));
+    let nat_pmp = match gateway {
+        Some(ip) => map_nat_pmp(SocketAddr::new(ip, NAT_PMP_PORT), port, lifetime_secs).await,
+        None => Err(ProtocolError::Missing("no default gateway".into())),
+    };
+    match nat_pmp {
+        Ok(mapping) => Ok(mapping),
+        Err(nat_pmp) => map_upnp(port, lifetime_secs).await.map_err(|upnp| {
+            ProtocolError::Missing(format!(
+                "no gateway mapped the port (NAT-PMP: {}; UPnP: {})",
+                nat_pmp.detail(),
+                upnp.detail()
+            ))
+        }),
+    }
+}
+
+/// Map `port` and keep it mapped, renewing at half the lease, while
+/// the task runs.  The burrow's external address follows the mapping.
+pub fn spawn_port_mapping(
+    burrow: Arc<Burrow>,
+    gateway: Option<IpAddr>,
+    port: u16,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut mapping: Option<PortMapping> = None;
+        loop {
+            let result = match &mapping {
+                Some(held) => held.renew().await,
+                None => map_port(gateway, port, DEFAULT_LIFETIME_SECS).await,
+            };
+            let delay = match result {
+                Ok(mapped) => {
+                    if burrow.external_addr() != Some(mapped.external) {
+                        info!(external = %mapped.external, port, "gateway forwards the port");
+                        burrow.set_external_addr(Some(mapped.external));
+                    }
+                    let lease = match mapped.lifetime_secs {
+                        0 => DEFAULT_LIFETIME_SECS,
+                        secs => secs,
+                    };
+                    mapping = Some(mapped);
+                    Duration::from_secs(u64::from(lease / 2).max(1))
+                }
+                Err(e) => {
+                    warn!(err = %e, port, "port mapping failed");
+                    burrow.set_external_addr(None);
+                    mapping = None;
+                    RETRY_DELAY
+                }
+            };
+            tokio::time::sleep(delay).await;
+        }
+    })
+}
+
+// ── NAT-PMP ────────────────────────────────────────────────────
+
+/// Map TCP `port` to the same external port, if free, with NAT-PMP.
+pub async fn map_nat_pmp(
+    gateway: SocketAddr,
+    port: u16,
+    lifetime_secs: u32,
+) -> Result<PortMapping, ProtocolError> {
+    let answer = nat_pmp_exchange(gateway, &[0, 0], 128).await?;
+    let external_ip = Ipv4Addr::new(answer[8], answer[9], answer[10], answer[11]);
+    let answer = nat_pmp_exchange(gateway, &map_request(port, lifetime_secs), 130).await?;
+    let external_port = u16::from_be_bytes([answer[10], answer[11]]);
+    let lifetime_secs = u32::from_be_bytes([answer[12], answer[13], answer[14], answer[15]]);
+    Ok(PortMapping {
+        external: SocketAddr::new(IpAddr::V4(external_ip), external_port),
+        internal_port: port,
+        lifetime_secs,
+        method: MappingMethod::NatPmp { gateway },
+    })
+}
+
+/// A request to map TCP `port` for `lifetime_secs` (0 to unmap).
+fn map_request(port: u16, lifetime_secs: u32) -> Vec<u8> {
+    let mut request = vec![0, 2, 0, 0];
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&lifetime_secs.to_be_bytes());
+    request
+}
+
+/// Send `request` to `gateway` until it answers with `opcode`, waiting
+/// twice as long each time.  Fails with `Timeout` if it never does and
+/// `Busy` if it refuses.
+async fn nat_pmp_exchange(
+    gateway: SocketAddr,
+    request: &[u8],
+    opcode: u8,
+) -> Result<Vec<u8>, ProtocolError> {
+    let local: SocketAddr = if gateway.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(local).await.map_err(io_error)?;
+    socket.connect(gateway).await.map_err(io_error)?;
+    let mut wait = Duration::from_millis(250);
+    let mut buf = [0u8; 16];
+    for _ in 0..4 {
+        socket.send(request).await.map_err(io_error)?;
+        let deadline = tokio::time::Instant::now() + wait;
+        while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+            let len = received.map_err(io_error)?;
+            let expected = if opcode == 128 { 12 } else { 16 };
+            if len < expected || buf[0] != 0 || buf[1] != opcode {
+                continue;
+            }
+            let result = u16::from_be_bytes([buf[2], buf[3]]);
+            if result != 0 {
+                return Err(ProtocolError::Busy(format!(
+                    "NAT-PMP gateway {} refused with result {}",
+                    gateway, result
+                )));
+            }
+            return Ok(buf[..len].to_vec());
+        }
+        wait *= 2;
+    }
+    Err(ProtocolError::Timeout(format!(
+        "NAT-PMP gateway {} did not answer",
+        gateway
+    )))
+}
+
+/// The gateway of the default IPv4 route, from `/proc/net/route`.
+pub fn default_gateway() -> Option<Ipv4Addr> {
+    let table = std::fs::read_to_string("/proc/net/route").ok()?;
+    parse_route_table(&table)
+}
+
+fn parse_route_table(table: &str) -> Option<Ipv4Addr> {
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 || fields[1] != "00000000" {
+            return None;
+        }
+        // The kernel writes the address as a host-order hex word.
+        let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+        (gateway != 0).then(|| Ipv4Addr::from(gateway.to_le_bytes()))
+    })
+}
+
+// ── UPnP IGD ───────────────────────────────────────────────────
+
+/// Map TCP `port` with the first UPnP gateway to answer a search.
+pub async fn map_upnp(port: u16, lifetime_secs: u32) -> Result<PortMapping, ProtocolError> {
+    let location = ssdp_search(SSDP_ADDR).await?;
+    map_upnp_at(&location, port, lifetime_secs).await
+}
+
+/// Map TCP `port` with the UPnP gateway described at `location`.
+pub async fn map_upnp_at(
+    location: &str,
+    port: u16,
+    lifetime_secs: u32,
+) -> Result<PortMapping, ProtocolError> {
+    let upnp = Upnp::describe(location).await?;
+    upnp.map(port, lifetime_secs).await
+}
+
+/// A gateway's port-mapping service.
+struct Upnp {
+    control_url: String,
+    service: String,
+    internal_ip: IpAddr,
+}
+
+impl Upnp {
+    async fn describe(location: &str) -> Result<Self, ProtocolError> {
+        let (description, internal_ip) = http_request(location, "GET", &[], "").await?;
+        let (control_url, service) = control_url(&description, location)?;
+        Ok(Self {
+            control_url,
+            service,
+            internal_ip,
+        })
+    }
+
+    async fn map(&self, port: u16, lifetime_secs: u32) -> Result<PortMapping, ProtocolError> {
+        let answer =
+            soap_call(&self.control_url, &self.service, "GetExternalIPAddress", "").await?;
+        let external_ip: IpAddr = xml_text(&answer, "NewExternalIPAddress")
+            .and_then(|ip| ip.trim().parse().ok())
+            .ok_or_else(|| {
+                ProtocolError::BadRequest("UPnP gateway gave no external address".into())
+            })?;
+        let args = format!(
+            "<NewRemoteHost></NewRemoteHost>\
+             <NewExternalPort>{port}</NewExternalPort>\
+             <NewProtocol>TCP</NewProtocol>\
+             <NewInternalPort>{port}</NewInternalPort>\
+             <NewInternalClient>{}</NewInternalClient>\
+             <NewEnabled>1</NewEnabled>\
+             <NewPortMappingDescription>rabbit</NewPortMappingDescription>\
+             <NewLeaseDuration>{lifetime_secs}</NewLeaseDuration>",
+            self.internal_ip
+        );
+        soap_call(&self.control_url, &self.service, "AddPortMapping", &args).await?;
+        Ok(PortMapping {
+            external: SocketAddr::new(external_ip, port),
+            internal_port: port,
+            lifetime_secs,
+            method: MappingMethod::Upnp {
+                control_url: self.control_url.clone(),
+                service: self.service.clone(),
+                internal_ip: self.internal_ip,
+            },
+        })
+    }
+}
+
+/// Search for an Internet gateway device at `target`, returning the
+/// `LOCATION` of its description.
+async fn ssdp_search(target: SocketAddr) -> Result<String, ProtocolError> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(io_error)?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\n\
+         ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n",
+        target
+    );
+    socket
+        .send_to(search.as_bytes(), target)
+        .await
+        .map_err(io_error)?;
+    let mut buf = vec![0u8; 2048];
+    let deadline = tokio::time::Instant::now() + DEFAULT_TIMEOUT;
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+        let len = received.map_err(io_error)?;
+        let answer = String::from_utf8_lossy(&buf[..len]);
+        let location = answer.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("location")
+                .then(|| value.trim().to_string())
+        });
+        if let Some(location) = location {
+            return Ok(location);
+        }
+    }
+    Err(ProtocolError::Timeout("no UPnP gateway answered".into()))
+}
+
+/// The control URL and type of the first port-mapping service in a
+/// device `description` fetched from `location`.
+fn control_url(description: &str, location: &str) -> Result<(String, String), ProtocolError> {
+    for service in description.split("<service>").skip(1) {
+        let Some(service_type) = xml_text(service, "serviceType") else {
+            continue;
+        };
+        let service_type = service_type.trim();
+        if !UPNP_SERVICES.contains(&service_type) {
+            continue;
+        }
+        let Some(url) = xml_text(service, "controlURL") else {
+            continue;
+        };
+        let url = url.trim();
+        let url = if url.starts_with("http://") {
+            url.to_string()
+        } else {
+            let (host, _) = split_url(location)?;
+            format!("http://{}/{}", host, url.trim_start_matches('/'))
+        };
+        return Ok((url, service_type.to_string()));
+    }
+    Err(ProtocolError::Missing(
+        "UPnP gateway offers no port mapping".into(),
+    ))
+}
+
+/// Call `action` of `service` at `control_url` with `args`, returning
+/// the response body.
+async fn soap_call(
+    control_url: &str,
+    service: &str,
+    action: &str,
+    args: &str,
+) -> Result<String, ProtocolError> {
+    let body = format!(
+        "<?xml version=\"1.0\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:{action} xmlns:u=\"{service}\">{args}</u:{action}></s:Body>\
+         </s:Envelope>"
+    );
+    let soap_action = format!("\"{}#{}\"", service, action);
+    let headers = [
+        ("Content-Type", "text/xml; charset=\"utf-8\""),
+        ("SOAPAction", soap_action.as_str()),
+    ];
+    let (answer, _) = http_request(control_url, "POST", &headers, &body).await?;
+    Ok(answer)
+}
+
+/// Make a plain HTTP/1.1 request, returning the body of a 2xx answer
+/// and this end's address on the connection.
+async fn http_request(
+    url: &str,
+    method: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> Result<(String, IpAddr), ProtocolError> {
+    let (host, path) = split_url(url)?;
+    let exchange = async {
+        let mut stream = TcpStream::connect(host.as_str()).await?;
+        let local_ip = stream.local_addr()?.ip();
+        let mut request = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, path, host);
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        ));
+        stream.write_all(request.as_bytes()).await?;
+        let mut answer = Vec::new();
+        stream.read_to_end(&mut answer).await?;
+        Ok::<_, std::io::Error>((answer, local_ip))
+    };
+    let (answer, local_ip) = tokio::time::timeout(DEFAULT_TIMEOUT, exchange)
+        .await
+        .map_err(|_| ProtocolError::Timeout(format!("{} did not answer", url)))?
+        .map_err(io_error)?;
+    let answer = String::from_utf8_lossy(&answer);
+    let (head, body) = answer.split_once("\r\n\r\n").unwrap_or((&answer, ""));
+    let status = head.split_whitespace().nth(1).unwrap_or("");
+    if !status.starts_with('2') {
+        return Err(ProtocolError::BadRequest(format!(
+            "{} answered {} {}",
+            url,
+            status,
+            xml_text(body, "errorDescription").unwrap_or("")
+        )));
+    }
+    let chunked = head
+        .to_ascii_lowercase()
+        .contains("transfer-encoding: chunked");
+    let body = if chunked {
+        dechunk(body)
+    } else {
+        body.to_string()
+    };
+    Ok((body, local_ip))
+}
+
+/// Split `http://host:port/path` into `host:port` and `/path`.
+fn split_url(url: &str) -> Result<(String, String), ProtocolError> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| ProtocolError::BadRequest(format!("not an http URL: {}", url)))?;
+    let (host, path) = match rest.find('/') {
+        Some(at) => (&rest[..at], &rest[at..]),
+        None => (rest, "/"),
+    };
+    let host = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    Ok((host, path.to_string()))
+}
+
+fn dechunk(body: &str) -> String {
+    let mut out = String::new();
+    let mut rest = body;
+    while let Some((size, after)) = rest.split_once("\r\n") {
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or("").trim(), 16);
+        let Ok(size) = size else { break };
+        if size == 0 || after.len() < size {
+            break;
+        }
+        out.push_str(&after[..size]);
+        rest = after[size..].trim_start_matches("\r\n");
+    }
+    out
+}
+
+/// The text of the first `<tag>` element in `xml`, with or without a
+/// namespace prefix.
+fn xml_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("{}>", tag);
+    let mut search = 0;
+    while let Some(found) = xml[search..].find(&open) {
+        let end = search + found;
+        search = end + open.len();
+        let start = xml[..end].rfind('<')?;
+        let name = &xml[start + 1..end];
+        if name.starts_with('/') || (!name.is_empty() && !name.ends_with(':')) {
+            continue;
+        }
+        let close = xml[search..].find("</")?;
+        return Some(&xml[search..search + close]);
+    }
+    None
+}
+
+fn io_error(e: std::io::Error) -> ProtocolError {
+    ProtocolError::InternalError(format!("port mapping: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn nat_pmp_gateways_map_and_unmap() {
+        let gateway = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = gateway.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 16];
+            loop {
+                let (len, from) = gateway.recv_from(&mut buf).await.unwrap();
+                let mut answer = vec![0, buf[1] + 128, 0, 0, 0, 0, 0, 42];
+                if len == 2 {
+                    answer.extend_from_slice(&[203, 0, 113, 7]);
+                } else {
+                    // Map to the next port up, as if ours were taken.
+                    let port = u16::from_be_bytes([buf[4], buf[5]]);
+                    answer.extend_from_slice(&port.to_be_bytes());
+                    answer.extend_from_slice(&(port + 1).to_be_bytes());
+                    answer.extend_from_slice(&buf[8..12]);
+                }
+                gateway.send_to(&answer, from).await.unwrap();
+            }
+        });
+
+        let mapping = map_nat_pmp(addr, 7443, 600).await.unwrap();
+        assert_eq!(mapping.external, "203.0.113.7:7444".parse().unwrap());
+        assert_eq!(mapping.lifetime_secs, 600);
+        assert_eq!(mapping.renew().await.unwrap(), mapping);
+        mapping.release().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn upnp_gateways_map_through_their_control_url() {
+        let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let location = format!("http://{}/rootDesc.xml", http.local_addr().unwrap());
+        let requests = tokio::spawn(async move {
+            let mut seen = Vec::new();
+            for _ in 0..3 {
+                let (mut stream, _) = http.accept().await.unwrap();
+                let mut buf = vec![0u8; 4096];
+                let len = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..len]).into_owned();
+                let body = if request.starts_with("GET") {
+                    "<root><device><serviceList>\
+                     <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1\
+                     </serviceType><controlURL>/l3f</controlURL></service>\
+                     <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1\
+                     </serviceType><controlURL>/ctl/IPConn</controlURL></service>\
+                     </serviceList></device></root>"
+                } else if request.contains("GetExternalIPAddress") {
+                    "<s:Envelope><s:Body><u:GetExternalIPAddressResponse>\
+                     <NewExternalIPAddress>198.51.100.9</NewExternalIPAddress>\
+                     </u:GetExternalIPAddressResponse></s:Body></s:Envelope>"
+                } else {
+                    "<s:Envelope><s:Body><u:AddPortMappingResponse/></s:Body></s:Envelope>"
+                };
+                let answer = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(answer.as_bytes()).await.unwrap();
+                seen.push(request);
+            }
+            seen
+        });
+
+        let mapping = map_upnp_at(&location, 7443, 0).await.unwrap();
+        assert_eq!(mapping.external, "198.51.100.9:7443".parse().unwrap());
+        let seen = requests.await.unwrap();
+        assert!(seen[2].starts_with("POST /ctl/IPConn "));
+        assert!(seen[2].contains("WANIPConnection:1#AddPortMapping"));
+        assert!(seen[2].contains("<NewInternalClient>127.0.0.1</NewInternalClient>"));
+    }
+
+    #[test]
+    fn the_default_route_names_the_gateway() {
+        let table = "Iface\tDestination\tGateway \tFlags\n\
+                     eth0\t0000A8C0\t00000000\t0001\n\
+                     eth0\t00000000\t0101A8C0\t0003\n";
+        assert_eq!(
+            parse_route_table(table),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(parse_route_table("Iface\tDestination\n"), None);
+    }
+
+    #[test]
+    fn chunked_bodies_are_joined() {
+        assert_eq!(dechunk("4\r\n<a>x\r\n4\r\n</a>\r\n0\r\n\r\n"), "<a>x</a>");
+        assert_eq!(xml_text("<u:Ip>1.2.3.4</u:Ip>", "Ip"), Some("1.2.3.4"));
+        assert_eq!(xml_text("<NewIp>1</NewIp>", "Ip"), None);
+    }
+}
@@ -0,0 +1,408 @@
+//! Experimental QUIC transport (the `quic` feature).
+//!
+//! A [`QuicTunnel`] carries frames over one QUIC connection with each
+//! lane on a stream of its own: the first frame a side sends on lane N
+//! opens a unidirectional stream, and every later frame of lane N
+//! follows it in order.  Frames without a `Lane` header go on lane 0.
+//! A lost packet then stalls only its own lane rather than every lane
+//! queued behind it as on TCP, and a client whose address changes keeps
+//! its connection, and so its tunnel.
+//!
+//! QUIC brings its own TLS 1.3, so [`QuicListener::bind`] and
+//! [`connect`] take the same rustls configurations as the TLS
+//! transport, and the Rabbit handshake runs over a [`QuicTunnel`] as
+//! over any other [`Tunnel`]; `Burrow::serve` takes a [`QuicListener`]
+//! like any other [`Acceptor`].
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn::{Connection, ConnectionError, Endpoint, ReadError, RecvStream, SendStream, VarInt};
+use rustls::pki_types::CertificateDer;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::{Frame, FrameDecoder, FrameLimits};
+use crate::transport::tunnel::{Acceptor, Tunnel};
+
+/// Frames buffered from all lanes before readers wait.
+const INBOUND_CAPACITY: usize = 256;
+
+/// How long [`Tunnel::close`] waits for the peer to read what was sent.
+const CLOSE_GRACE: Duration = Duration::from_secs(5);
+
+/// Accepts QUIC connections as tunnels.
+#[derive(Debug)]
+pub struct QuicListener {
+    endpoint: Endpoint,
+}
+
+impl QuicListener {
+    /// Listen on UDP `addr`, presenting `server_config`'s certificate.
+    /// Must be called within a Tokio runtime.
+    pub fn bind(
+        addr: SocketAddr,
+        server_config: Arc<rustls::ServerConfig>,
+    ) -> Result<Self, ProtocolError> {
+        let crypto = QuicServerConfig::try_from(server_config)
+            .map_err(|e| ProtocolError::InternalError(format!("QUIC server config: {}", e)))?;
+        let config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+        let endpoint = Endpoint::server(config, addr).map_err(|e| {
+            ProtocolError::InternalError(format!("QUIC bind failed on {}: {}", addr, e))
+        })?;
+        Ok(Self { endpoint })
+    }
+
+    /// Accept the next connection.  The tunnel's `peer_id` is
+    /// `"unknown"` until the Rabbit handshake.
+    pub async fn accept(&self) -> Result<QuicTunnel, ProtocolError> {
+        let incoming = self
+            .endpoint
+            .accept()
+            .await
+            .ok_or_else(|| ProtocolError::InternalError("QUIC endpoint closed".into()))?;
+        let connection = incoming
+            .await
+            .map_err(|e| ProtocolError::InternalError(format!("QUIC accept failed: {}", e)))?;
+        Ok(QuicTunnel::new(connection, None))
+    }
+
+    /// The UDP address the listener is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, ProtocolError> {
+        self.endpoint
+            .local_addr()
+            .map_err(|e| ProtocolError::InternalError(format!("local_addr: {}", e)))
+    }
+
+    /// Refuse new connections and close the open ones.
+    pub fn close(&self) {
+        self.endpoint.close(VarInt::from_u32(0), b"closing");
+    }
+}
+
+impl Acceptor for QuicListener {
+    type Tunnel = QuicTunnel;
+
+    async fn accept(&self) -> Result<QuicTunnel, ProtocolError> {
+        QuicListener::accept(self).await
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, ProtocolError> {
+        QuicListener::local_addr(self)
+    }
+}
+
+/// Connect to a burrow listening for QUIC at `addr`.
+///
+/// `server_name` is the TLS SNI value, as for
+/// [`crate::transport::connector::connect`].
+pub async fn connect(
+    addr: SocketAddr,
+    client_config: Arc<rustls::ClientConfig>,
+    server_name: &str,
+) -> Result<QuicTunnel, ProtocolError> {
+    let crypto = QuicClientConfig::try_from(client_config)
+        .map_err(|e| ProtocolError::InternalError(format!("QUIC client config: {}", e)))?;
+    let local: SocketAddr = if addr.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let mut endpoint = Endpoint::client(local)
+        .map_err(|e| ProtocolError::InternalError(format!("QUIC bind failed: {}", e)))?;
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+    let connection = endpoint
+        .connect(addr, server_name)
+        .map_err(|e| ProtocolError::InternalError(format!("QUIC connect to {}: {}", addr, e)))?
+        .await
+        .map_err(|e| {
+            ProtocolError::InternalError(format!("QUIC connect to {} failed: {}", addr, e))
+        })?;
+    Ok(QuicTunnel::new(connection, Some(endpoint)))
+}
+
+/// A tunnel over a QUIC connection, one stream per lane.
+pub struct QuicTunnel {
+    connection: Connection,
+    /// The client's endpoint, kept for as long as the tunnel.
+    _endpoint: Option<Endpoint>,
+    peer_id: String,
+    peer_cert: Option<Vec<u8>>,
+    /// The stream each lane is sent on, opened by its first frame.
+    lanes: HashMap<u16, SendStream>,
+    /// Frames from every lane, as their streams deliver them.
+    inbound: mpsc::Receiver<Result<Frame, ProtocolError>>,
+    limits: watch::Sender<FrameLimits>,
+    acceptor: JoinHandle<()>,
+}
+
+impl std::fmt::Debug for QuicTunnel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut lanes: Vec<&u16> = self.lanes.keys().collect();
+        lanes.sort();
+        f.debug_struct("QuicTunnel")
+            .field("peer_id", &self.peer_id)
+            .field("remote", &self.connection.remote_address())
+            .field("lanes", &lanes)
+            .finish()
+    }
+}
+
+impl QuicTunnel {
+    fn new(connection: Connection, endpoint: Option<Endpoint>) -> Self {
+        let peer_cert = connection
+            .peer_identity()
+            .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+            .and_then(|certs| certs.first().map(|cert| cert.to_vec()));
+        let (tx, inbound) = mpsc::channel(INBOUND_CAPACITY);
+        let (limits, limits_rx) = watch::channel(FrameLimits::default());
+        let acceptor = tokio::spawn(accept_lanes(connection.clone(), tx, limits_rx));
+        Self {
+            connection,
+            _endpoint: endpoint,
+            peer_id: "unknown".to_string(),
+            peer_cert,
+            lanes: HashMap::new(),
+            inbound,
+            limits,
+            acceptor,
+        }
+    }
+
+    /// Update the peer ID (e.g., after the Rabbit handshake completes).
+    pub fn set_peer_id(&mut self, id: String) {
+        self.peer_id = id;
+    }
+
+    /// The lanes this side has sent on, sorted.
+    pub fn lanes(&self) -> Vec<u16> {
+        let mut lanes: Vec<u16> = self.lanes.keys().copied().collect();
+        lanes.sort();
+        lanes
+    }
+}
+
+impl Tunnel for QuicTunnel {
+    async fn send_frame(&mut self, frame: &Frame) -> Result<(), ProtocolError> {
+        let lane = frame
+            .header("Lane")
+            .and_then(|lane| lane.parse::<u16>().ok())
+            .unwrap_or(0);
+        let stream = match self.lanes.entry(lane) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let stream = self.connection.open_uni().await.map_err(|e| {
+                    ProtocolError::InternalError(format!("QUIC stream open failed: {}", e))
+                })?;
+                entry.insert(stream)
+            }
+        };
+        stream
+            .write_all(frame.serialize().as_bytes())
+            .await
+            .map_err(|e| ProtocolError::InternalError(format!("tunnel write failed: {}", e)))
+    }
+
+    async fn recv_frame(&mut self) -> Result<Option<Frame>, ProtocolError> {
+        match self.inbound.recv().await {
+            Some(Ok(frame)) => Ok(Some(frame)),
+            Some(Err(e)) => Err(e),
+            None => Ok(None),
+        }
+    }
+
+    fn peer_id(&self) -> &str {
+        &self.peer_id
+    }
+
+    fn peer_certificate(&self) -> Option<&[u8]> {
+        self.peer_cert.as_deref()
+    }
+
+    /// Where the peer is now, which may change as it moves.
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        Some(self.connection.remote_address())
+    }
+
+    fn set_limits(&mut self, limits: FrameLimits) {
+        self.limits.send_replace(limits);
+    }
+
+    async fn close(&mut self) -> Result<(), ProtocolError> {
+        // Closing the connection discards whatever the peer has not
+        // acknowledged, so finish each lane and give it time to drain.
+        let mut draining = Vec::new();
+        for (_, mut stream) in self.lanes.drain() {
+            if stream.finish().is_ok() {
+                draining.push(async move {
+                    let _ = stream.stopped().await;
+                });
+            }
+        }
+        let _ = tokio::time::timeout(CLOSE_GRACE, futures_util::future::join_all(draining)).await;
+        self.connection.close(VarInt::from_u32(0), b"closing");
+        Ok(())
+    }
+}
+
+impl Drop for QuicTunnel {
+    fn drop(&mut self) {
+        self.acceptor.abort();
+        self.connection.close(VarInt::from_u32(0), b"dropped");
+    }
+}
+
+/// Read each stream the peer opens until the connection closes.
+async fn accept_lanes(
+    connection: Connection,
+    tx: mpsc::Sender<Result<Frame, ProtocolError>>,
+    limits: watch::Receiver<FrameLimits>,
+) {
+    loop {
+        match connection.accept_uni().await {
+            Ok(stream) => {
+                tokio::spawn(read_lane(stream, tx.clone(), limits.clone()));
+            }
+            Err(e) => {
+                if !closed_cleanly(&e) {
+                    let _ = tx
+                        .send(Err(ProtocolError::InternalError(format!(
+                            "QUIC connection lost: {}",
+                            e
+                        ))))
+                        .await;
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Decode the frames of one lane's stream.
+async fn read_lane(
+    mut stream: RecvStream,
+    tx: mpsc::Sender<Result<Frame, ProtocolError>>,
+    limits: watch::Receiver<FrameLimits>,
+) {
+    let mut decoder = FrameDecoder::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        decoder.set_limits(*limits.borrow());
+        match decoder.next_frame() {
+            Ok(Some(frame)) => {
+                if tx.send(Ok(frame)).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            Ok(None) => {}
+            // The decoder skips an oversized frame and carries on.
+            Err(e @ ProtocolError::TooLarge(_)) => {
+                if tx.send(Err(e)).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            Err(e) => {
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+        }
+        match stream.read(&mut chunk).await {
+            Ok(Some(n)) => decoder.push(&chunk[..n]),
+            Ok(None) => {
+                if decoder.buffered() > 0 {
+                    let _ = tx
+                        .send(Err(ProtocolError::BadRequest(
+                            "unexpected EOF in frame".into(),
+                        )))
+                        .await;
+                }
+                return;
+            }
+            Err(ReadError::ConnectionLost(e)) if closed_cleanly(&e) => return,
+            Err(e) => {
+                debug!(err = %e, "QUIC lane read failed");
+                let _ = tx
+                    .send(Err(ProtocolError::InternalError(format!(
+                        "tunnel read failed: {}",
+                        e
+                    ))))
+                    .await;
+                return;
+            }
+        }
+    }
+}
+
+fn closed_cleanly(e: &ConnectionError) -> bool {
+    matches!(
+        e,
+        ConnectionError::ApplicationClosed(_) | ConnectionError::LocallyClosed
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::cert::{generate_self_signed, make_server_config};
+    use crate::transport::connector::make_client_config_insecure;
+
+    #[tokio::test]
+    async fn lanes_travel_on_their_own_streams() {
+        let cert = generate_self_signed().unwrap();
+        let listener = QuicListener::bind(
+            "127.0.0.1:0".parse().unwrap(),
+            make_server_config(&cert).unwrap(),
+        )
+        .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut tunnel = listener.accept().await.unwrap();
+            let mut seen = Vec::new();
+            while let Some(frame) = tunnel.recv_frame().await.unwrap() {
+                let lane = frame.header("Lane").unwrap_or("0").to_string();
+                let mut ok = Frame::new("200 OK");
+                ok.set_header("Lane", lane.as_str());
+                tunnel.send_frame(&ok).await.unwrap();
+                seen.push((lane, frame.args.join(" ")));
+                if seen.len() == 4 {
+                    break;
+                }
+            }
+            tunnel.close().await.unwrap();
+            seen
+        });
+
+        let mut client = connect(addr, make_client_config_insecure(), "localhost")
+            .await
+            .unwrap();
+        assert_eq!(client.peer_addr(), Some(addr));
+        for (lane, selector) in [(1, "/a"), (2, "/b"), (1, "/c"), (0, "/d")] {
+            let mut frame = Frame::new(format!("FETCH {}", selector));
+            frame.set_header("Lane", lane.to_string());
+            client.send_frame(&frame).await.unwrap();
+        }
+        assert_eq!(client.lanes(), [0, 1, 2]);
+        for _ in 0..4 {
+            assert!(client.recv_frame().await.unwrap().is_some());
+        }
+
+        // Each lane keeps its order, whatever the interleaving.
+        let seen = server.await.unwrap();
+        let lane_1: Vec<&str> = seen
+            .iter()
+            .filter(|(lane, _)| lane == "1")
+            .map(|(_, selector)| selector.as_str())
+            .collect();
+        assert_eq!(lane_1, ["/a", "/c"]);
+        assert_eq!(seen.len(), 4);
+        assert_eq!(client.recv_frame().await.unwrap(), None);
+    }
+}
@@ -0,0 +1,395 @@
+//! Finding a warren's burrows from its domain name.
+//!
+//! A domain that hosts a warren publishes where its burrows listen as
+//! SRV records, and the burrow ID to expect there as a TXT record, both
+//! at `_rabbit._tcp.<domain>`:
+//!
+//! ```text
+//! _rabbit._tcp.example.org  SRV  10 5 7443 burrow.example.org.
+//! _rabbit._tcp.example.org  TXT  "id=ed25519:…"
+//! ```
+//!
+//! so `rabbit connect example.org` needs no host or port.  The
+//! [`Resolver`] asks one nameserver directly over UDP, falling back to
+//! TCP when the answer is truncated.  The SRV targets are tried in
+//! order of priority, the heaviest first among equals; DNS is not
+//! trusted to vouch for the burrow, so a caller given an ID checks the
+//! handshake against it.
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::protocol::error::ProtocolError;
+
+use super::dns::{
+    header, labels_eq, put_question, txt_entries, Message, FLAG_RECURSION_DESIRED, FLAG_TRUNCATED,
+    TYPE_SRV, TYPE_TXT,
+};
+
+/// The service label prefixed to a warren's domain.
+pub const SERVICE_PREFIX: &[&str] = &["_rabbit", "_tcp"];
+
+/// The DNS port.
+pub const DNS_PORT: u16 = 53;
+
+/// How long to wait for each answer by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest UDP answer read.
+const MAX_PACKET: usize = 4096;
+
+/// A place a warren's burrows listen, from an SRV record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvTarget {
+    /// Host name, without the trailing dot.
+    pub host: String,
+    /// TCP port.
+    pub port: u16,
+    /// Lower is tried first.
+    pub priority: u16,
+    /// Among equal priorities, heavier is tried first.
+    pub weight: u16,
+}
+
+impl SrvTarget {
+    /// `host:port`, ready to dial.
+    pub fn address(&self) -> String {
+        match self.host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{}]:{}", ip, self.port),
+            _ => format!("{}:{}", self.host, self.port),
+        }
+    }
+}
+
+/// What a domain publishes about its warren.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WarrenRecords {
+    /// The SRV targets, in the order to try them.
+    pub targets: Vec<SrvTarget>,
+    /// The burrow ID from the TXT record's `id`, if there is one.
+    pub burrow_id: Option<String>,
+}
+
+/// Looks up warren records from one nameserver.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    nameserver: SocketAddr,
+    timeout: Duration,
+}
+
+impl Resolver {
+    /// Ask `nameserver`.
+    pub fn new(nameserver: SocketAddr) -> Self {
+        Self {
+            nameserver,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Wait `timeout` for each answer.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Ask the first nameserver in `/etc/resolv.conf`.
+    pub fn system() -> Result<Self, ProtocolError> {
+        Self::from_resolv_conf(Path::new("/etc/resolv.conf"))
+    }
+
+    /// Ask the first nameserver listed in the resolv.conf at `path`.
+    pub fn from_resolv_conf(path: &Path) -> Result<Self, ProtocolError> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            ProtocolError::InternalError(format!("cannot read {}: {}", path.display(), e))
+        })?;
+        text.lines()
+            .filter_map(|line| line.strip_prefix("nameserver"))
+            .filter_map(|rest| rest.trim().parse::<IpAddr>().ok())
+            .map(|ip| Self::new(SocketAddr::new(ip, DNS_PORT)))
+            .next()
+            .ok_or_else(|| {
+                ProtocolError::InternalError(format!("no nameserver in {}", path.display()))
+            })
+    }
+
+    /// The nameserver asked.
+    pub fn nameserver(&self) -> SocketAddr {
+        self.nameserver
+    }
+
+    /// Look up the warren at `domain`.  Fails with `Missing` if the
+    /// domain publishes no SRV records for it.
+    pub async fn lookup_warren(&self, domain: &str) -> Result<WarrenRecords, ProtocolError> {
+        let domain = domain.trim_end_matches('.');
+        let mut name: Vec<&str> = SERVICE_PREFIX.to_vec();
+        name.extend(domain.split('.').filter(|label| !label.is_empty()));
+
+        let mut targets: Vec<SrvTarget> = self
+            .query(&name, TYPE_SRV)
+            .await?
+            .records
+            .iter()
+            .filter(|r| r.rtype == TYPE_SRV && labels_eq(&r.name, &name))
+            .filter_map(|r| parse_srv(&r.rdata))
+            .collect();
+        // A lone target of "." says there is no service.
+        targets.retain(|t| !t.host.is_empty());
+        if targets.is_empty() {
+            return Err(ProtocolError::Missing(format!(
+                "no Rabbit SRV records for {}",
+                domain
+            )));
+        }
+        targets.sort_by(|a, b| {
+            a.priority
+                .cmp(&b.priority)
+                .then(b.weight.cmp(&a.weight))
+                .then(a.host.cmp(&b.host))
+        });
+
+        // The TXT record is optional; without it there is nothing to
+        // check the handshake against.
+        let burrow_id = match self.query(&name, TYPE_TXT).await {
+            Ok(answer) => answer
+                .records
+                .iter()
+                .filter(|r| r.rtype == TYPE_TXT && labels_eq(&r.name, &name))
+                .find_map(|r| txt_entries(&r.rdata).remove("id")),
+            Err(_) => None,
+        };
+        Ok(WarrenRecords { targets, burrow_id })
+    }
+
+    /// Ask for `name`'s records of `qtype`.
+    async fn query(&self, name: &[&str], qtype: u16) -> Result<Message, ProtocolError> {
+        let id: u16 = rand::random();
+        let mut packet = header(id, FLAG_RECURSION_DESIRED, 1, 0);
+        put_question(&mut packet, name, qtype);
+
+        let mut answer = tokio::time::timeout(self.timeout, self.exchange_udp(&packet, id))
+            .await
+            .map_err(|_| self.timed_out())??;
+        if answer.flags & FLAG_TRUNCATED != 0 {
+            answer = tokio::time::timeout(self.timeout, self.exchange_tcp(&packet, id))
+                .await
+                .map_err(|_| self.timed_out())??;
+        }
+        match answer.rcode() {
+            0 => Ok(answer),
+            3 => Err(ProtocolError::Missing(format!(
+                "no such name {}",
+                name.join(".")
+            ))),
+            rcode => Err(ProtocolError::InternalError(format!(
+                "nameserver {} answered rcode {}",
+                self.nameserver, rcode
+            ))),
+        }
+    }
+
+    async fn exchange_udp(&self, packet: &[u8], id: u16) -> Result<Message, ProtocolError> {
+        let local: SocketAddr = if self.nameserver.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(local).await.map_err(io_error)?;
+        socket.connect(self.nameserver).await.map_err(io_error)?;
+        socket.send(packet).await.map_err(io_error)?;
+        let mut buf = vec![0u8; MAX_PACKET];
+        loop {
+            let len = socket.recv(&mut buf).await.map_err(io_error)?;
+            // Anything but the answer to this query is ignored.
+            match Message::parse(&buf[..len]) {
+                Ok(message) if message.id == id && message.is_response() => return Ok(message),
+                _ => continue,
+            }
+        }
+    }
+
+    async fn exchange_tcp(&self, packet: &[u8], id: u16) -> Result<Message, ProtocolError> {
+        let mut stream = TcpStream::connect(self.nameserver)
+            .await
+            .map_err(io_error)?;
+        stream
+            .write_all(&(packet.len() as u16).to_be_bytes())
+            .await
+            .map_err(io_error)?;
+        stream.write_all(packet).await.map_err(io_error)?;
+        let len = stream.read_u16().await.map_err(io_error)? as usize;
+        let mut buf = vec![0u8; len];
+        stream.read_exact(&mut buf).await.map_err(io_error)?;
+        let message = Message::parse(&buf)?;
+        if message.id != id || !message.is_response() {
+            return Err(ProtocolError::InternalError(format!(
+                "nameserver {} answered another query",
+                self.nameserver
+            )));
+        }
+        Ok(message)
+    }
+
+    fn timed_out(&self) -> ProtocolError {
+        ProtocolError::Timeout(format!("nameserver {} did not answer", self.nameserver))
+    }
+}
+
+/// Whether `addr` is a bare domain to look up, rather than a
+/// `host:port` or an IP address to dial.
+pub fn is_domain(addr: &str) -> bool {
+    !addr.is_empty()
+        && !addr.contains(':')
+        && addr.parse::<IpAddr>().is_err()
+        && addr
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+}
+
+fn parse_srv(rdata: &[u8]) -> Option<SrvTarget> {
+    let field = |at: usize| Some(u16::from_be_bytes([*rdata.get(at)?, *rdata.get(at + 1)?]));
+    let mut labels = Vec::new();
+    let mut pos = 6;
+    loop {
+        let len = *rdata.get(pos)? as usize;
+        if len == 0 {
+            break;
+        }
+        labels.push(String::from_utf8_lossy(rdata.get(pos + 1..pos + 1 + len)?).into_owned());
+        pos += 1 + len;
+    }
+    Some(SrvTarget {
+        host: labels.join("."),
+        port: field(4)?,
+        priority: field(0)?,
+        weight: field(2)?,
+    })
+}
+
+fn io_error(e: std::io::Error) -> ProtocolError {
+    ProtocolError::InternalError(format!("DNS: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::dns::{put_name, put_record, txt_rdata, CLASS_IN, FLAG_RESPONSE};
+
+    /// A nameserver on loopback answering every query for
+    /// `_rabbit._tcp.example.org` from `srv` and `txt`.
+    async fn fake_nameserver(
+        srv: Vec<(u16, u16, u16, &'static str)>,
+        txt: Vec<String>,
+    ) -> Resolver {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let name = ["_rabbit", "_tcp", "example", "org"];
+            let mut buf = vec![0u8; MAX_PACKET];
+            loop {
+                let (len, from) = socket.recv_from(&mut buf).await.unwrap();
+                let query = Message::parse(&buf[..len]).unwrap();
+                let (qname, qtype) = &query.questions[0];
+                let mut records = Vec::new();
+                if labels_eq(qname, &name) && *qtype == TYPE_SRV {
+                    for (priority, weight, port, host) in &srv {
+                        let mut rdata = Vec::new();
+                        for field in [priority, weight, port] {
+                            rdata.extend_from_slice(&field.to_be_bytes());
+                        }
+                        let labels: Vec<&str> = host.split('.').collect();
+                        put_name(&mut rdata, &labels);
+                        records.push(rdata);
+                    }
+                } else if labels_eq(qname, &name) && *qtype == TYPE_TXT && !txt.is_empty() {
+                    records.push(txt_rdata(&txt));
+                }
+                let rcode = if labels_eq(qname, &name) { 0 } else { 3 };
+                let mut answer = header(query.id, FLAG_RESPONSE | rcode, 1, records.len() as u16);
+                put_question(&mut answer, &name, *qtype);
+                for rdata in records {
+                    put_record(&mut answer, &name, *qtype, CLASS_IN, 60, &rdata);
+                }
+                socket.send_to(&answer, from).await.unwrap();
+            }
+        });
+        Resolver::new(addr).with_timeout(Duration::from_secs(2))
+    }
+
+    #[tokio::test]
+    async fn srv_targets_come_back_in_order_with_the_txt_id() {
+        let resolver = fake_nameserver(
+            vec![
+                (20, 0, 7445, "backup.example.org"),
+                (10, 1, 7444, "light.example.org"),
+                (10, 5, 7443, "heavy.example.org"),
+            ],
+            vec!["id=ed25519:ABC".into(), "v=1".into()],
+        )
+        .await;
+        let warren = resolver.lookup_warren("example.org.").await.unwrap();
+        let addresses: Vec<String> = warren.targets.iter().map(SrvTarget::address).collect();
+        assert_eq!(
+            addresses,
+            [
+                "heavy.example.org:7443",
+                "light.example.org:7444",
+                "backup.example.org:7445"
+            ]
+        );
+        assert_eq!(warren.burrow_id.as_deref(), Some("ed25519:ABC"));
+
+        assert!(matches!(
+            resolver.lookup_warren("example.net").await,
+            Err(ProtocolError::Missing(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn the_txt_record_is_optional() {
+        let resolver = fake_nameserver(vec![(0, 0, 7443, "127.0.0.1")], Vec::new()).await;
+        let warren = resolver.lookup_warren("example.org").await.unwrap();
+        assert_eq!(warren.targets[0].address(), "127.0.0.1:7443");
+        assert_eq!(warren.burrow_id, None);
+
+        let resolver = fake_nameserver(Vec::new(), Vec::new()).await;
+        assert!(matches!(
+            resolver.lookup_warren("example.org").await,
+            Err(ProtocolError::Missing(_))
+        ));
+    }
+
+    #[test]
+    fn domains_are_told_from_addresses() {
+        assert!(is_domain("example.org"));
+        assert!(is_domain("localhost"));
+        for addr in [
+            "127.0.0.1:7443",
+            "example.org:7443",
+            "::1",
+            "10.0.0.1",
+            "",
+            "a b",
+        ] {
+            assert!(!is_domain(addr), "{addr:?}");
+        }
+    }
+
+    #[test]
+    fn the_first_nameserver_is_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("resolv.conf");
+        std::fs::write(
+            &path,
+            "# generated\nsearch lan\nnameserver 10.0.0.53\nnameserver 1.1.1.1\n",
+        )
+        .unwrap();
+        let resolver = Resolver::from_resolv_conf(&path).unwrap();
+        assert_eq!(resolver.nameserver(), "10.0.0.53:53".parse().unwrap());
+        std::fs::write(&path, "search lan\n").unwrap();
+        assert!(Resolver::from_resolv_conf(&path).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! Addresses naming a selector in any warren.
+//!
+//! A selector on its own, such as `/0/readme`, names content on the
+//! burrow a request is sent to.  To name content elsewhere, a `FETCH`
+//! or `LIST` gives a `rabbit://` address instead, whose authority is
+//! the burrow ID of the burrow serving it:
+//!
+//! ```text
+//! rabbit://ed25519:AAAA…/0/readme
+//! ```
+//!
+//! The burrow receiving the request resolves the address (see
+//! [`crate::burrow::Burrow::resolve`]) to one of three [`Scope`]s:
+//! [`Scope::Local`] if it names the burrow itself, which then serves
+//! the selector; [`Scope::Warren`] if the named burrow is connected or
+//! routed to within the warren; and [`Scope::Federated`] if it is the
+//! remote end of an open federation link.  Requests for the last two
+//! are relayed to the named burrow with a `Target` header, as for any
+//! routed frame (see [`crate::warren::routing`]), and its answer is
+//! relayed back to the requester the same way.
+
+use std::fmt;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::{Frame, Verb, VerbKind};
+
+/// The scheme every address starts with.
+pub const SCHEME: &str = "rabbit://";
+
+/// A selector on a named burrow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RabbitAddress {
+    /// Burrow ID of the burrow serving the selector.
+    pub warren: String,
+    /// The selector there, starting with `/`.
+    pub selector: String,
+}
+
+impl RabbitAddress {
+    /// The address of `selector` on `warren`.
+    pub fn new(warren: &str, selector: &str) -> Self {
+        Self {
+            warren: warren.to_string(),
+            selector: selector.to_string(),
+        }
+    }
+
+    /// Parse `rabbit://<burrow-id>/<selector>`.  An address without a
+    /// selector names `/`.  Fails with `BadRequest` if the scheme or
+    /// burrow ID is missing.
+    pub fn parse(address: &str) -> Result<Self, ProtocolError> {
+        let rest = address.strip_prefix(SCHEME).ok_or_else(|| {
+            ProtocolError::BadRequest(format!("address {:?} must start with {}", address, SCHEME))
+        })?;
+        let (warren, selector) = match rest.find('/') {
+            Some(at) => rest.split_at(at),
+            None => (rest, "/"),
+        };
+        if !warren.starts_with("ed25519:") || warren.len() == "ed25519:".len() {
+            return Err(ProtocolError::BadRequest(format!(
+                "address {:?} must name a burrow ID",
+                address
+            )));
+        }
+        Ok(Self::new(warren, selector))
+    }
+
+    /// The address a `FETCH` or `LIST` gives in place of a selector,
+    /// if it gives one.
+    pub fn of_request(frame: &Frame) -> Option<Result<Self, ProtocolError>> {
+        if !matches!(frame.verb_kind(), VerbKind::Verb(Verb::Fetch | Verb::List)) {
+            return None;
+        }
+        let arg = frame.args.first().filter(|a| a.starts_with(SCHEME))?;
+        Some(Self::parse(arg))
+    }
+}
+
+impl fmt::Display for RabbitAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", SCHEME, self.warren, self.selector)
+    }
+}
+
+/// Where an address's selector is served, as seen from one burrow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// By this burrow.
+    Local,
+    /// By a burrow in this warren, reached directly or through the
+    /// routing table.
+    Warren,
+    /// By another warren, over an open federation link.
+    Federated,
+}
+
+impl Scope {
+    /// Lower-case name, e.g. `"federated"`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::Warren => "warren",
+            Self::Federated => "federated",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_parse_and_print() {
+        let address = RabbitAddress::parse("rabbit://ed25519:OAK/0/readme").unwrap();
+        assert_eq!(address, RabbitAddress::new("ed25519:OAK", "/0/readme"));
+        assert_eq!(address.to_string(), "rabbit://ed25519:OAK/0/readme");
+        assert_eq!(
+            RabbitAddress::parse("rabbit://ed25519:OAK")
+                .unwrap()
+                .selector,
+            "/"
+        );
+        assert!(RabbitAddress::parse("/0/readme").is_err());
+        assert!(RabbitAddress::parse("rabbit://oak/0/readme").is_err());
+        assert!(RabbitAddress::parse("rabbit://ed25519:/0/readme").is_err());
+
+        let fetch = Frame::with_args("FETCH", vec![address.to_string()]);
+        assert_eq!(RabbitAddress::of_request(&fetch).unwrap().unwrap(), address);
+        let plain = Frame::with_args("FETCH", vec!["/0/readme".into()]);
+        assert!(RabbitAddress::of_request(&plain).is_none());
+        let publish = Frame::with_args("PUBLISH", vec![address.to_string()]);
+        assert!(RabbitAddress::of_request(&publish).is_none());
+    }
+}
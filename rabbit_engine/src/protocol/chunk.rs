@@ -0,0 +1,332 @@
+//! Chunked transfer of large bodies.
+//!
+//! A requester that can reassemble chunks adds `Chunk-Size: <bytes>`
+//! to its `FETCH`.  If the response body is larger than that, the
+//! responder sends the response without its body, announcing the
+//! transfer, followed by `CHUNK` frames on the same lane:
+//!
+//! ```text
+//! 200 CONTENT            CHUNK                 CHUNK
+//! Chunks: 2              Chunk-Index: 0        Chunk-Index: 1
+//! Lane: 3                Lane: 3               Final: true
+//! Total-Length: 98304    Length: 65536         Lane: 3
+//! Txn: F1                Txn: F1               Length: 32768
+//! View: text/plain       End:                  Txn: F1
+//! End:                   <65536 bytes>         End:
+//!                                              <32768 bytes>
+//! ```
+//!
+//! [`ChunkAssembler`] rebuilds the original frame on the receiver.  It
+//! refuses transfers over a size limit and caps how many can be in
+//! progress at once, so a peer cannot make it buffer without bound.
+
+use std::collections::HashMap;
+
+use super::error::ProtocolError;
+use super::frame::Frame;
+
+/// Verb of a continuation frame.
+pub const CHUNK_VERB: &str = "CHUNK";
+
+/// Default cap on a reassembled body (64 MiB).
+pub const DEFAULT_MAX_BODY: usize = 64 * 1024 * 1024;
+
+/// Default cap on transfers in progress at once.
+pub const DEFAULT_MAX_TRANSFERS: usize = 16;
+
+/// Split a response into an announcing frame and `CHUNK` frames of at
+/// most `chunk_size` body bytes.
+///
+/// Returns the response unchanged if its body already fits.  Chunks
+/// end on UTF-8 character boundaries, so a chunk may be a few bytes
+/// short of `chunk_size`.
+pub fn chunk_frames(response: &Frame, chunk_size: usize) -> Vec<Frame> {
+    let body = response.body.as_deref().unwrap_or("");
+    let chunk_size = chunk_size.max(4);
+    if body.len() <= chunk_size {
+        return vec![response.clone()];
+    }
+
+    let mut pieces = Vec::new();
+    let mut rest = body;
+    while !rest.is_empty() {
+        let mut end = chunk_size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+
+    let mut head = Frame {
+        body: None,
+        ..response.clone()
+    };
+    head.headers.remove("Length");
+    head.set_header("Chunks", pieces.len().to_string());
+    head.set_header("Total-Length", body.len().to_string());
+
+    let mut frames = Vec::with_capacity(pieces.len() + 1);
+    frames.push(head);
+    for (i, piece) in pieces.iter().enumerate() {
+        let mut chunk = Frame::new(CHUNK_VERB);
+        for key in ["Lane", "Txn"] {
+            if let Some(value) = response.header(key) {
+                chunk.set_header(key, value);
+            }
+        }
+        chunk.set_header("Chunk-Index", i.to_string());
+        if i + 1 == pieces.len() {
+            chunk.set_header("Final", "true");
+        }
+        chunk.set_body(*piece);
+        frames.push(chunk);
+    }
+    frames
+}
+
+/// The requested chunk size of a request, if it asked for chunking.
+pub fn requested_chunk_size(request: &Frame) -> Option<usize> {
+    request
+        .header("Chunk-Size")
+        .and_then(|s| s.parse().ok())
+        .filter(|&n| n > 0)
+}
+
+/// A transfer being reassembled.
+#[derive(Debug)]
+struct Transfer {
+    head: Frame,
+    body: String,
+    next_index: u64,
+}
+
+/// Reassembles chunked transfers, keyed by `Txn` (or `Lane` when a
+/// transfer has no transaction ID).
+#[derive(Debug)]
+pub struct ChunkAssembler {
+    transfers: HashMap<String, Transfer>,
+    max_body: usize,
+    max_transfers: usize,
+}
+
+impl ChunkAssembler {
+    /// Create an assembler with the default limits.
+    pub fn new() -> Self {
+        Self::with_limits(DEFAULT_MAX_BODY, DEFAULT_MAX_TRANSFERS)
+    }
+
+    /// Create an assembler accepting bodies up to `max_body` bytes and
+    /// at most `max_transfers` concurrent transfers.
+    pub fn with_limits(max_body: usize, max_transfers: usize) -> Self {
+        Self {
+            transfers: HashMap::new(),
+            max_body,
+            max_transfers,
+        }
+    }
+
+    fn key(frame: &Frame) -> String {
+        match (frame.header("Txn"), frame.header("Lane")) {
+            (Some(txn), _) => format!("txn:{}", txn),
+            (None, Some(lane)) => format!("lane:{}", lane),
+            (None, None) => "lane:0".to_string(),
+        }
+    }
+
+    /// Feed a received frame.
+    ///
+    /// Returns `Some(frame)` for frames that are not part of a chunked
+    /// transfer and for the reassembled frame once the final chunk
+    /// arrives, and `None` while a transfer is still in progress.  An
+    /// error abandons the transfer it concerns.
+    pub fn feed(&mut self, frame: Frame) -> Result<Option<Frame>, ProtocolError> {
+        if frame.verb == CHUNK_VERB {
+            return self.feed_chunk(frame);
+        }
+        let Some(total) = frame.header("Total-Length") else {
+            return Ok(Some(frame));
+        };
+        if frame.header("Chunks").is_none() {
+            return Ok(Some(frame));
+        }
+        let total: usize = total.parse().map_err(|_| {
+            ProtocolError::BadRequest(format!("invalid Total-Length header: {}", total))
+        })?;
+        if total > self.max_body {
+            return Err(ProtocolError::TooLarge(format!(
+                "chunked body of {} bytes exceeds the {} byte limit",
+                total, self.max_body
+            )));
+        }
+        let key = Self::key(&frame);
+        if !self.transfers.contains_key(&key) && self.transfers.len() >= self.max_transfers {
+            return Err(ProtocolError::Busy(
+                "too many chunked transfers in progress".into(),
+            ));
+        }
+        let mut head = frame;
+        head.headers.remove("Chunks");
+        head.headers.remove("Total-Length");
+        self.transfers.insert(
+            key,
+            Transfer {
+                head,
+                body: String::with_capacity(total),
+                next_index: 0,
+            },
+        );
+        Ok(None)
+    }
+
+    fn feed_chunk(&mut self, chunk: Frame) -> Result<Option<Frame>, ProtocolError> {
+        let key = Self::key(&chunk);
+        let Some(transfer) = self.transfers.get_mut(&key) else {
+            return Err(ProtocolError::BadRequest(
+                "CHUNK without a transfer in progress".into(),
+            ));
+        };
+        let index: Option<u64> = chunk.header("Chunk-Index").and_then(|i| i.parse().ok());
+        if index != Some(transfer.next_index) {
+            let expected = transfer.next_index;
+            self.transfers.remove(&key);
+            return Err(ProtocolError::OutOfOrder { expected });
+        }
+        let piece = chunk.body.as_deref().unwrap_or("");
+        if transfer.body.len() + piece.len() > self.max_body {
+            self.transfers.remove(&key);
+            return Err(ProtocolError::TooLarge(format!(
+                "chunked body exceeds the {} byte limit",
+                self.max_body
+            )));
+        }
+        transfer.body.push_str(piece);
+        transfer.next_index += 1;
+
+        if chunk.header("Final") != Some("true") {
+            return Ok(None);
+        }
+        let Some(Transfer { mut head, body, .. }) = self.transfers.remove(&key) else {
+            return Ok(None);
+        };
+        head.set_body(body);
+        Ok(Some(head))
+    }
+
+    /// Number of transfers in progress.
+    pub fn in_progress(&self) -> usize {
+        self.transfers.len()
+    }
+
+    /// Abandon the transfer for transaction `txn`, e.g. after
+    /// canceling it.  Returns whether one was in progress.
+    pub fn abandon(&mut self, txn: &str) -> bool {
+        self.transfers.remove(&format!("txn:{}", txn)).is_some()
+    }
+
+    /// Abandon all transfers in progress.
+    pub fn reset(&mut self) {
+        self.transfers.clear();
+    }
+}
+
+impl Default for ChunkAssembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: &str) -> Frame {
+        let mut frame = Frame::new("200 CONTENT");
+        frame.set_header("Lane", "3");
+        frame.set_header("Txn", "F1");
+        frame.set_header("View", "text/plain");
+        frame.set_body(body);
+        frame
+    }
+
+    #[test]
+    fn small_bodies_are_not_chunked() {
+        let frame = response("short");
+        assert_eq!(chunk_frames(&frame, 1024), vec![frame]);
+    }
+
+    #[test]
+    fn chunks_reassemble_to_the_original() {
+        let body = "Rabbit ünïcode runs fast. ".repeat(100);
+        let original = response(&body);
+        let frames = chunk_frames(&original, 64);
+        assert_eq!(
+            frames[0].header("Total-Length"),
+            Some(body.len().to_string().as_str())
+        );
+        assert!(frames[0].body.is_none());
+        assert!(frames[1..].iter().all(|f| f.verb == CHUNK_VERB
+            && f.header("Txn") == Some("F1")
+            && f.body.as_ref().unwrap().len() <= 64));
+        assert_eq!(frames.last().unwrap().header("Final"), Some("true"));
+
+        let mut assembler = ChunkAssembler::new();
+        let mut out = Vec::new();
+        for frame in frames {
+            if let Some(done) = assembler.feed(frame).unwrap() {
+                out.push(done);
+            }
+        }
+        assert_eq!(out, vec![original]);
+        assert_eq!(assembler.in_progress(), 0);
+    }
+
+    #[test]
+    fn interleaved_transfers_are_kept_apart() {
+        let a = chunk_frames(&response(&"a".repeat(30)), 10);
+        let mut other = response(&"b".repeat(30));
+        other.set_header("Txn", "F2");
+        let b = chunk_frames(&other, 10);
+
+        let mut assembler = ChunkAssembler::new();
+        let mut done = Vec::new();
+        for (x, y) in a.into_iter().zip(b) {
+            done.extend(assembler.feed(x).unwrap());
+            done.extend(assembler.feed(y).unwrap());
+        }
+        assert_eq!(done.len(), 2);
+        assert_eq!(done[1].body.as_deref(), Some("b".repeat(30).as_str()));
+    }
+
+    #[test]
+    fn limits_and_ordering_are_enforced() {
+        let frames = chunk_frames(&response(&"x".repeat(100)), 10);
+
+        let mut small = ChunkAssembler::with_limits(50, 4);
+        assert!(matches!(
+            small.feed(frames[0].clone()),
+            Err(ProtocolError::TooLarge(_))
+        ));
+
+        let mut assembler = ChunkAssembler::new();
+        assembler.feed(frames[0].clone()).unwrap();
+        let err = assembler.feed(frames[2].clone()).unwrap_err();
+        assert!(matches!(err, ProtocolError::OutOfOrder { expected: 0 }));
+        assert_eq!(assembler.in_progress(), 0);
+        assert!(assembler.feed(frames[1].clone()).is_err());
+
+        let mut busy = ChunkAssembler::with_limits(1024, 1);
+        busy.feed(frames[0].clone()).unwrap();
+        let mut second = frames[0].clone();
+        second.set_header("Txn", "F9");
+        assert!(matches!(
+            busy.feed(second.clone()),
+            Err(ProtocolError::Busy(_))
+        ));
+        // Abandoning a canceled transfer frees its slot.
+        assert!(busy.abandon("F1"));
+        assert!(!busy.abandon("F1"));
+        assert!(busy.feed(second).unwrap().is_none());
+    }
+}
@@ -0,0 +1,154 @@
+//! Adaptive credit for inbound lanes.
+//!
+//! A sender spends one credit per sequenced frame and stops when it
+//! runs out (see [`super::lane`]).  The receiver's [`CreditController`]
+//! tracks how many credits each lane's sender still holds and tops
+//! them up with a `CREDIT` frame once half the window is spent:
+//!
+//! ```text
+//! CREDIT
+//! Credit: +8
+//! Lane: 3
+//! End:
+//! ```
+//!
+//! The window itself adapts between a configured minimum and maximum.
+//! It doubles when the lane consumes a full window per second while the
+//! receiver keeps up, and halves when the receiver's backlog reaches
+//! half the window.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use super::frame::Frame;
+use super::lane::DEFAULT_CREDIT;
+
+/// Weight of a new sample in the smoothed consumption rate.
+const RATE_GAIN: f64 = 0.125;
+
+/// Credit state for one inbound lane.
+#[derive(Debug)]
+struct LaneCredit {
+    /// Credits the sender should hold after a top-up.
+    window: u32,
+    /// Credits the sender still holds, as far as we know.
+    outstanding: u32,
+    /// Smoothed consumption rate in frames per second.
+    rate: f64,
+    /// When the last frame arrived.
+    last: Option<Instant>,
+}
+
+/// Issues `CREDIT` frames for inbound lanes, sizing each lane's window
+/// to its traffic.
+#[derive(Debug)]
+pub struct CreditController {
+    min_window: u32,
+    max_window: u32,
+    lanes: HashMap<u16, LaneCredit>,
+}
+
+impl CreditController {
+    /// Keep every lane's window between `min_window` and `max_window`
+    /// credits.
+    pub fn new(min_window: u32, max_window: u32) -> Self {
+        let min_window = min_window.max(1);
+        Self {
+            min_window,
+            max_window: max_window.max(min_window),
+            lanes: HashMap::new(),
+        }
+    }
+
+    /// The current window of a lane.
+    pub fn window(&self, lane_id: u16) -> u32 {
+        self.lanes
+            .get(&lane_id)
+            .map_or_else(|| self.initial_window(), |l| l.window)
+    }
+
+    /// Record a sequenced frame arriving on a lane.
+    ///
+    /// `buffered` is how many frames the receiver has yet to work
+    /// through.  Returns the `CREDIT` frame to send, if the sender is
+    /// due more credit.
+    pub fn on_receive(&mut self, lane_id: u16, buffered: usize) -> Option<Frame> {
+        let initial = self.initial_window();
+        let lane = self.lanes.entry(lane_id).or_insert(LaneCredit {
+            window: initial,
+            // Senders start every lane with the default credit.
+            outstanding: DEFAULT_CREDIT,
+            rate: 0.0,
+            last: None,
+        });
+        lane.outstanding = lane.outstanding.saturating_sub(1);
+
+        let now = Instant::now();
+        if let Some(last) = lane.last {
+            let secs = now.duration_since(last).as_secs_f64().max(1e-6);
+            lane.rate = lane.rate * (1.0 - RATE_GAIN) + RATE_GAIN / secs;
+        }
+        lane.last = Some(now);
+
+        if lane.outstanding > lane.window / 2 {
+            return None;
+        }
+        let buffered = u32::try_from(buffered).unwrap_or(u32::MAX);
+        if buffered.saturating_mul(2) >= lane.window {
+            lane.window = (lane.window / 2).max(self.min_window);
+        } else if lane.rate >= f64::from(lane.window) && buffered.saturating_mul(4) < lane.window {
+            lane.window = lane.window.saturating_mul(2).min(self.max_window);
+        }
+
+        let grant = lane.window.saturating_sub(lane.outstanding);
+        if grant == 0 {
+            return None;
+        }
+        lane.outstanding += grant;
+        let mut credit = Frame::new("CREDIT");
+        credit.set_header("Lane", lane_id.to_string());
+        credit.set_header("Credit", format!("+{}", grant));
+        Some(credit)
+    }
+
+    /// Forget a lane, e.g. once it is closed or reset.
+    pub fn remove(&mut self, lane_id: u16) {
+        self.lanes.remove(&lane_id);
+    }
+
+    fn initial_window(&self) -> u32 {
+        DEFAULT_CREDIT.clamp(self.min_window, self.max_window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credit_is_topped_up_at_half_window() {
+        let mut credit = CreditController::new(4, 64);
+        for _ in 0..7 {
+            assert!(credit.on_receive(3, 0).is_none());
+        }
+        let grant = credit.on_receive(3, 0).unwrap();
+        assert_eq!(grant.verb, "CREDIT");
+        assert_eq!(grant.header("Lane"), Some("3"));
+        // A fast lane with an empty backlog gets a bigger window.
+        assert_eq!(credit.window(3), 32);
+        assert_eq!(grant.header("Credit"), Some("+24"));
+    }
+
+    #[test]
+    fn backlog_shrinks_the_window() {
+        let mut credit = CreditController::new(4, 64);
+        let mut grants = Vec::new();
+        for _ in 0..40 {
+            grants.extend(credit.on_receive(1, 100));
+        }
+        assert_eq!(credit.window(1), 4);
+        assert!(!grants.is_empty());
+        // Other lanes are unaffected.
+        assert_eq!(credit.window(2), DEFAULT_CREDIT);
+    }
+}
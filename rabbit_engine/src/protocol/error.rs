@@ -2,7 +2,8 @@
 //!
 //! Each variant corresponds to a status code from the spec.  Every
 //! error can be converted into a [`Frame`] for transmission back to
-//! the peer.
+//! the peer; [`ErrorFrame`] builds such frames with extra headers or
+//! tied to the request they answer.
 
 use super::frame::Frame;
 
@@ -36,10 +37,23 @@ pub enum ProtocolError {
     #[error("412 PRECONDITION FAILED: {0}")]
     PreconditionFailed(String),
 
+    /// 413 — Frame exceeds a size or header-count limit.
+    #[error("413 TOO-LARGE: {0}")]
+    TooLarge(String),
+
     /// 429 — Credit exhausted / flow control limit hit.
     #[error("429 FLOW-LIMIT: {0}")]
     FlowLimit(String),
 
+    /// 429 — A rate limit was hit; retry after some seconds.
+    #[error("429 SLOW-DOWN: {reason}, retry after {retry_after}s")]
+    SlowDown {
+        /// Which limit was hit.
+        reason: String,
+        /// Seconds until the request would be allowed.
+        retry_after: u64,
+    },
+
     /// 431 — Invalid HELLO frame.
     #[error("431 BAD-HELLO: {0}")]
     BadHello(String),
@@ -52,6 +66,24 @@ pub enum ProtocolError {
     #[error("499 CANCELED: {0}")]
     Canceled(String),
 
+    /// 507 — A storage quota would be exceeded by the request.
+    #[error("507 QUOTA-EXCEEDED: {scope} {resource} {used}/{limit}")]
+    QuotaExceeded {
+        /// The quota scope that was hit (e.g. `peer ed25519:…` or
+        /// `topic /q/chat`).
+        scope: String,
+        /// The limited resource (`bytes` or `events`).
+        resource: String,
+        /// Usage that the request would have produced.
+        used: u64,
+        /// The configured hard limit.
+        limit: u64,
+    },
+
+    /// 502 — No route to the burrow a frame is addressed to.
+    #[error("502 NO-ROUTE: {0}")]
+    NoRoute(String),
+
     /// 503 — Burrow is busy / overloaded.
     #[error("503 BUSY: {0}")]
     Busy(String),
@@ -71,11 +103,14 @@ impl ProtocolError {
             Self::Timeout(_) => 408,
             Self::OutOfOrder { .. } => 409,
             Self::PreconditionFailed(_) => 412,
-            Self::FlowLimit(_) => 429,
+            Self::TooLarge(_) => 413,
+            Self::FlowLimit(_) | Self::SlowDown { .. } => 429,
             Self::BadHello(_) => 431,
             Self::AuthRequired(_) => 440,
             Self::Canceled(_) => 499,
+            Self::NoRoute(_) => 502,
             Self::Busy(_) => 503,
+            Self::QuotaExceeded { .. } => 507,
             Self::InternalError(_) => 520,
         }
     }
@@ -89,11 +124,15 @@ impl ProtocolError {
             Self::Timeout(_) => "TIMEOUT",
             Self::OutOfOrder { .. } => "OUT-OF-ORDER",
             Self::PreconditionFailed(_) => "PRECONDITION FAILED",
+            Self::TooLarge(_) => "TOO-LARGE",
             Self::FlowLimit(_) => "FLOW-LIMIT",
+            Self::SlowDown { .. } => "SLOW-DOWN",
             Self::BadHello(_) => "BAD-HELLO",
             Self::AuthRequired(_) => "AUTH-REQUIRED",
             Self::Canceled(_) => "CANCELED",
+            Self::NoRoute(_) => "NO-ROUTE",
             Self::Busy(_) => "BUSY",
+            Self::QuotaExceeded { .. } => "QUOTA-EXCEEDED",
             Self::InternalError(_) => "INTERNAL ERROR",
         }
     }
@@ -106,38 +145,133 @@ impl ProtocolError {
             | Self::Missing(s)
             | Self::Timeout(s)
             | Self::PreconditionFailed(s)
+            | Self::TooLarge(s)
             | Self::FlowLimit(s)
             | Self::BadHello(s)
             | Self::AuthRequired(s)
             | Self::Canceled(s)
+            | Self::NoRoute(s)
             | Self::Busy(s)
             | Self::InternalError(s) => s.clone(),
             Self::OutOfOrder { expected } => format!("expected seq {}", expected),
+            Self::SlowDown { reason, .. } => reason.clone(),
+            Self::QuotaExceeded {
+                scope,
+                resource,
+                used,
+                limit,
+            } => format!("{} {} quota exceeded ({}/{})", scope, resource, used, limit),
         }
     }
 }
 
-impl From<ProtocolError> for Frame {
-    /// Convert a protocol error into a response frame suitable for
-    /// sending back to the peer.
-    fn from(err: ProtocolError) -> Frame {
-        let verb = format!("{} {}", err.status_code(), err.status_label());
-        let mut frame = Frame::new(verb);
+/// Builder for error response frames.
+///
+/// Produces a `<code> <LABEL>` frame with the detail as its body.
+/// [`ErrorFrame::in_reply_to`] copies `Lane` and `Txn` from the
+/// request being answered, so the peer can match the error to it.
+#[derive(Debug, Clone)]
+pub struct ErrorFrame {
+    code: u16,
+    label: String,
+    detail: String,
+    headers: Vec<(String, String)>,
+}
 
-        // For OUT-OF-ORDER, include the Expected header.
-        if let ProtocolError::OutOfOrder { expected } = &err {
-            frame.set_header("Expected", expected.to_string());
+impl ErrorFrame {
+    /// Start an error frame with a status code and label.
+    pub fn new(code: u16, label: impl Into<String>) -> Self {
+        Self {
+            code,
+            label: label.into(),
+            detail: String::new(),
+            headers: Vec::new(),
         }
+    }
+
+    /// Set the human-readable detail carried as the body.
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = detail.into();
+        self
+    }
+
+    /// Add a header.
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
 
-        let detail = err.detail();
-        if !detail.is_empty() {
-            frame.set_body(detail);
+    /// Copy `Lane` and `Txn` from `request`.
+    pub fn in_reply_to(mut self, request: &Frame) -> Self {
+        for key in ["Lane", "Txn"] {
+            if let Some(value) = request.header(key) {
+                self.headers.push((key.to_string(), value.to_string()));
+            }
         }
+        self
+    }
 
+    /// Build the frame.
+    pub fn build(self) -> Frame {
+        let mut frame = Frame::new(format!("{} {}", self.code, self.label));
+        for (key, value) in self.headers {
+            frame.set_header(key, value);
+        }
+        if !self.detail.is_empty() {
+            frame.set_body(self.detail);
+        }
         frame
     }
 }
 
+impl From<&ProtocolError> for ErrorFrame {
+    fn from(err: &ProtocolError) -> Self {
+        let mut builder =
+            ErrorFrame::new(err.status_code(), err.status_label()).detail(err.detail());
+
+        // For OUT-OF-ORDER, include the Expected header.
+        if let ProtocolError::OutOfOrder { expected } = err {
+            builder = builder.header("Expected", expected.to_string());
+        }
+
+        // For SLOW-DOWN, say when to retry.
+        if let ProtocolError::SlowDown { retry_after, .. } = err {
+            builder = builder.header("Retry-After", retry_after.to_string());
+        }
+
+        // For QUOTA-EXCEEDED, describe which quota was hit.
+        if let ProtocolError::QuotaExceeded {
+            scope,
+            resource,
+            used,
+            limit,
+        } = err
+        {
+            builder = builder
+                .header("Quota-Scope", scope)
+                .header("Quota-Resource", resource)
+                .header("Quota-Used", used.to_string())
+                .header("Quota-Limit", limit.to_string());
+        }
+
+        builder
+    }
+}
+
+impl From<ErrorFrame> for Frame {
+    fn from(builder: ErrorFrame) -> Frame {
+        builder.build()
+    }
+}
+
+impl From<ProtocolError> for Frame {
+    /// Convert a protocol error into a response frame suitable for
+    /// sending back to the peer.
+    fn from(err: ProtocolError) -> Frame {
+        ErrorFrame::from(&err).build()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -180,6 +314,36 @@ mod tests {
         assert!(parsed.args.contains(&"FORBIDDEN".to_string()));
     }
 
+    #[test]
+    fn error_frame_replies_on_the_request_lane() {
+        let mut request = Frame::new("FETCH /0/readme");
+        request.set_header("Lane", "5");
+        request.set_header("Txn", "T9");
+        let frame = ErrorFrame::from(&ProtocolError::Missing("/0/readme".into()))
+            .in_reply_to(&request)
+            .header("Retry-After", "10")
+            .build();
+        assert_eq!(frame.verb, "404");
+        assert_eq!(frame.args, vec!["MISSING"]);
+        assert_eq!(frame.header("Lane"), Some("5"));
+        assert_eq!(frame.header("Txn"), Some("T9"));
+        assert_eq!(frame.header("Retry-After"), Some("10"));
+        assert_eq!(frame.body.as_deref(), Some("/0/readme"));
+    }
+
+    #[test]
+    fn slow_down_says_when_to_retry() {
+        let frame: Frame = ProtocolError::SlowDown {
+            reason: "rate limit exceeded".into(),
+            retry_after: 3,
+        }
+        .into();
+        assert_eq!(frame.verb, "429");
+        assert_eq!(frame.args, vec!["SLOW-DOWN"]);
+        assert_eq!(frame.header("Retry-After"), Some("3"));
+        assert_eq!(frame.body.as_deref(), Some("rate limit exceeded"));
+    }
+
     #[test]
     fn all_status_codes() {
         let errors: Vec<ProtocolError> = vec![
@@ -193,10 +357,19 @@ mod tests {
             ProtocolError::BadHello("g".into()),
             ProtocolError::AuthRequired("h".into()),
             ProtocolError::Canceled("i".into()),
+            ProtocolError::NoRoute("l".into()),
             ProtocolError::Busy("j".into()),
+            ProtocolError::QuotaExceeded {
+                scope: "topic /q/x".into(),
+                resource: "bytes".into(),
+                used: 2,
+                limit: 1,
+            },
             ProtocolError::InternalError("k".into()),
         ];
-        let expected_codes = [400, 403, 404, 408, 409, 412, 429, 431, 440, 499, 503, 520];
+        let expected_codes = [
+            400, 403, 404, 408, 409, 412, 429, 431, 440, 499, 502, 503, 507, 520,
+        ];
         for (err, code) in errors.into_iter().zip(expected_codes) {
             assert_eq!(err.status_code(), code);
             // Ensure frame conversion doesn't panic
@@ -204,6 +377,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn error_to_frame_quota_exceeded() {
+        let err = ProtocolError::QuotaExceeded {
+            scope: "topic /q/chat".into(),
+            resource: "events".into(),
+            used: 11,
+            limit: 10,
+        };
+        let frame: Frame = err.into();
+        assert_eq!(frame.verb, "507");
+        assert_eq!(frame.args, vec!["QUOTA-EXCEEDED"]);
+        assert_eq!(frame.header("Quota-Scope"), Some("topic /q/chat"));
+        assert_eq!(frame.header("Quota-Resource"), Some("events"));
+        assert_eq!(frame.header("Quota-Used"), Some("11"));
+        assert_eq!(frame.header("Quota-Limit"), Some("10"));
+    }
+
     #[test]
     fn error_display() {
         let err = ProtocolError::FlowLimit("lane 3 exhausted".into());
@@ -13,14 +13,57 @@
 //! All text is UTF-8 with CRLF line endings.  Headers are stored in a
 //! `BTreeMap` for deterministic serialization order.  The body length
 //! is governed by the `Length` header when present.
+//!
+//! The verb is kept as text; [`Frame::verb_kind`] gives its typed form
+//! ([`Verb`] for requests, [`Status`] for responses) for matching.
+//! [`FrameBuilder`] assembles frames with typed header helpers and
+//! checks them before they reach the wire.
+//!
+//! A sender may add a `Digest` header carrying the SHA-256 of the
+//! body, so corruption by a relay that re-frames the message is caught
+//! on arrival:
+//!
+//! ```text
+//! Digest: sha-256=2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824
+//! ```
+//!
+//! Tunnels can instead use a length-prefixed encoding, where each
+//! serialized frame is preceded by a fixed 8-byte header:
+//!
+//! ```text
+//! +------+------+---------+-------+----------------+
+//! | 'R'  | 'B'  | version | flags | length (u32 BE)|
+//! +------+------+---------+-------+----------------+
+//! ```
+//!
+//! The receiver never has to scan for `End:` or trust a `Length`
+//! header to find the end of a frame, so frames of any size survive
+//! arbitrary TCP segmentation.  [`FrameCodec`] encodes and decodes
+//! this format from a stream of byte chunks.
+//!
+//! A binary body is held in a [`Frame`] base64-encoded under
+//! `Transfer: base64` (see [`Frame::set_binary_body`]), which is how
+//! the text format carries it.  The length-prefixed format carries the
+//! bytes themselves instead, marking the frame with the
+//! [`FLAG_BINARY_BODY`] flag; a body that is not UTF-8 is taken as
+//! binary whether flagged or not.
 
 use std::collections::BTreeMap;
 use std::fmt;
 
+use base64::Engine as _;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
 use super::error::ProtocolError;
 
 /// A parsed Rabbit protocol frame.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Frames also serialize with serde as
+/// `{"verb", "args", "headers", "body"}`, for logs and tools that
+/// store frames outside the wire format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Frame {
     /// The verb (e.g. `HELLO`, `FETCH`, `200 MENU`).
     pub verb: String,
@@ -32,6 +75,31 @@ pub struct Frame {
     pub body: Option<String>,
 }
 
+/// Size limits applied when parsing frames.
+///
+/// A frame over any limit is rejected with
+/// [`ProtocolError::TooLarge`], which peers see as `413 TOO-LARGE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameLimits {
+    /// Maximum number of header lines.
+    pub max_headers: usize,
+    /// Maximum size of the start line and headers, in bytes.
+    pub max_header_bytes: usize,
+    /// Maximum body size in bytes.
+    pub max_body_bytes: usize,
+}
+
+impl Default for FrameLimits {
+    /// 64 headers, 16 KiB of headers and a 16 MiB body.
+    fn default() -> Self {
+        Self {
+            max_headers: 64,
+            max_header_bytes: 16 * 1024,
+            max_body_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
 impl Frame {
     /// Create a new frame with the given start line.
     ///
@@ -52,6 +120,11 @@ impl Frame {
         }
     }
 
+    /// Start building a frame; see [`FrameBuilder`].
+    pub fn builder(start_line: impl Into<String>) -> FrameBuilder {
+        FrameBuilder::new(start_line)
+    }
+
     /// Create a new frame with a verb and positional arguments.
     pub fn with_args(verb: impl Into<String>, args: Vec<String>) -> Self {
         Self {
@@ -72,6 +145,15 @@ impl Frame {
         self.headers.get(key).map(|s| s.as_str())
     }
 
+    /// The typed form of the verb.
+    ///
+    /// A verb that is neither a status nor a valid verb (such as an
+    /// empty one) comes back verbatim as [`Verb::Other`].
+    pub fn verb_kind(&self) -> VerbKind {
+        VerbKind::try_from(self.verb.as_str())
+            .unwrap_or_else(|_| VerbKind::Verb(Verb::Other(self.verb.clone())))
+    }
+
     /// Set the body and automatically update the `Length` header.
     pub fn set_body(&mut self, body: impl Into<String>) {
         let body = body.into();
@@ -80,6 +162,73 @@ impl Frame {
         self.body = Some(body);
     }
 
+    /// Set a binary body, base64-encoded under `Transfer: base64`.
+    pub fn set_binary_body(&mut self, data: &[u8]) {
+        self.set_header("Transfer", "base64");
+        self.set_body(base64::engine::general_purpose::STANDARD.encode(data));
+    }
+
+    /// The body set by [`Frame::set_binary_body`], decoded.  Returns
+    /// `None` if the frame has no body, is not `Transfer: base64`, or
+    /// its body is not canonical base64.
+    pub fn binary_body(&self) -> Option<Vec<u8>> {
+        if self.header("Transfer") != Some("base64") {
+            return None;
+        }
+        base64::engine::general_purpose::STANDARD
+            .decode(self.body.as_deref()?)
+            .ok()
+    }
+
+    /// Decode a JSON body.
+    pub fn json_body<T: DeserializeOwned>(&self) -> Result<T, ProtocolError> {
+        let body = self
+            .body
+            .as_deref()
+            .ok_or_else(|| ProtocolError::BadRequest("expected a JSON body".into()))?;
+        serde_json::from_str(body)
+            .map_err(|e| ProtocolError::BadRequest(format!("invalid JSON body: {}", e)))
+    }
+
+    /// Set the body to `value` as JSON and mark the view
+    /// `application/json`.
+    pub fn set_json_body<T: Serialize>(&mut self, value: &T) -> Result<(), ProtocolError> {
+        let body = serde_json::to_string(value)
+            .map_err(|e| ProtocolError::InternalError(format!("cannot encode JSON body: {}", e)))?;
+        self.set_header("View", "application/json");
+        self.set_body(body);
+        Ok(())
+    }
+
+    /// Set the `Digest` header to the SHA-256 of the current body.
+    ///
+    /// Call it once the body is final; a frame without a body digests
+    /// as empty.
+    pub fn set_digest(&mut self) {
+        let digest = body_digest(self.body.as_deref().unwrap_or(""));
+        self.set_header("Digest", format!("{}{}", DIGEST_PREFIX, digest));
+    }
+
+    /// Check the `Digest` header against the body.
+    ///
+    /// Returns whether a digest was present.  A digest that does not
+    /// match, or uses an algorithm other than SHA-256, fails with
+    /// [`ProtocolError::PreconditionFailed`].
+    pub fn verify_digest(&self) -> Result<bool, ProtocolError> {
+        let Some(value) = self.header("Digest") else {
+            return Ok(false);
+        };
+        let expected = value.strip_prefix(DIGEST_PREFIX).ok_or_else(|| {
+            ProtocolError::PreconditionFailed(format!("unsupported digest: {}", value))
+        })?;
+        if !expected.eq_ignore_ascii_case(&body_digest(self.body.as_deref().unwrap_or(""))) {
+            return Err(ProtocolError::PreconditionFailed(
+                "body does not match its digest".into(),
+            ));
+        }
+        Ok(true)
+    }
+
     /// Serialize the frame to its wire representation.
     pub fn serialize(&self) -> String {
         let mut out = String::with_capacity(256);
@@ -115,14 +264,25 @@ impl Frame {
     ///
     /// The input should contain a complete frame: start line, headers,
     /// `End:` marker, and optional body.  Returns a `ProtocolError` if
-    /// the input is malformed.
+    /// the input is malformed or exceeds the default [`FrameLimits`].
     pub fn parse(raw: &str) -> Result<Self, ProtocolError> {
+        Self::parse_with_limits(raw, &FrameLimits::default())
+    }
+
+    /// Parse a frame, enforcing `limits`.
+    pub fn parse_with_limits(raw: &str, limits: &FrameLimits) -> Result<Self, ProtocolError> {
         // We need to split on \r\n but handle the body specially.
         // Strategy: find "End:\r\n" to split headers from body.
         let end_marker = "End:\r\n";
         let end_pos = raw
             .find(end_marker)
             .ok_or(ProtocolError::BadRequest("missing End: marker".into()))?;
+        if end_pos > limits.max_header_bytes {
+            return Err(ProtocolError::TooLarge(format!(
+                "frame headers exceed {} bytes",
+                limits.max_header_bytes
+            )));
+        }
 
         let header_section = &raw[..end_pos];
         let body_section = &raw[end_pos + end_marker.len()..];
@@ -159,6 +319,12 @@ impl Frame {
                 ProtocolError::BadRequest(format!("malformed header line: {}", line))
             })?;
             headers.insert(key.trim().to_string(), value.trim().to_string());
+            if headers.len() > limits.max_headers {
+                return Err(ProtocolError::TooLarge(format!(
+                    "frame has more than {} headers",
+                    limits.max_headers
+                )));
+            }
         }
 
         // Body: use Length header if present, otherwise take everything
@@ -168,6 +334,9 @@ impl Frame {
             let len: usize = len_str.parse().map_err(|_| {
                 ProtocolError::BadRequest(format!("invalid Length header: {}", len_str))
             })?;
+            if len > limits.max_body_bytes {
+                return Err(body_too_large(len, limits));
+            }
             if body_section.len() < len {
                 return Err(ProtocolError::BadRequest(format!(
                     "body too short: expected {} bytes, got {}",
@@ -176,6 +345,8 @@ impl Frame {
                 )));
             }
             Some(body_section[..len].to_string())
+        } else if body_section.len() > limits.max_body_bytes {
+            return Err(body_too_large(body_section.len(), limits));
         } else {
             Some(body_section.to_string())
         };
@@ -189,12 +360,926 @@ impl Frame {
     }
 }
 
+/// The algorithm tag opening a `Digest` header value.
+const DIGEST_PREFIX: &str = "sha-256=";
+
+/// Lower-case hex SHA-256 of `body`.
+fn body_digest(body: &str) -> String {
+    Sha256::digest(body.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn body_too_large(len: usize, limits: &FrameLimits) -> ProtocolError {
+    ProtocolError::TooLarge(format!(
+        "frame body {} bytes exceeds limit {}",
+        len, limits.max_body_bytes
+    ))
+}
+
 impl fmt::Display for Frame {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(&self.serialize())
     }
 }
 
+// ── Typed verbs ────────────────────────────────────────────────
+
+/// A request or control verb.
+///
+/// Verbs this crate does not handle parse as [`Verb::Other`], which
+/// displays as the original text, so unknown verbs round-trip.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Verb {
+    /// `HELLO` — open a session.
+    Hello,
+    /// `AUTH` — answer a challenge.
+    Auth,
+    /// `LIST` — fetch a menu.
+    List,
+    /// `FETCH` — fetch content.
+    Fetch,
+    /// `SEARCH` — full-text search.
+    Search,
+    /// `DESCRIBE` — fetch metadata.
+    Describe,
+    /// `SUBSCRIBE` — follow a topic.
+    Subscribe,
+    /// `UNSUBSCRIBE` — stop following a topic.
+    Unsubscribe,
+    /// `REPLICATE` — follow a topic's log from a sequence number.
+    Replicate,
+    /// `PUBLISH` — post an event.
+    Publish,
+    /// `TOPIC-CONFIG` — read or set a topic's retention.
+    TopicConfig,
+    /// `EVENT` — a delivered event.
+    Event,
+    /// `ACK` — acknowledge a sequence number.
+    Ack,
+    /// `CREDIT` — grant flow-control credit.
+    Credit,
+    /// `PING` — keepalive probe.
+    Ping,
+    /// `PONG` — keepalive reply.
+    Pong,
+    /// `OFFER` — advertise peers.
+    Offer,
+    /// `ROUTE-ADVERT` — advertise burrows reachable through the sender.
+    RouteAdvert,
+    /// `DELEGATE` — grant a capability.
+    Delegate,
+    /// `DELEGATE-GRANT` — notice of a granted capability.
+    DelegateGrant,
+    /// `CHUNK` — continuation of a chunked transfer.
+    Chunk,
+    /// `CANCEL` — abandon a transaction.
+    Cancel,
+    /// `LANE-OPEN` — open a lane.
+    LaneOpen,
+    /// `LANE-CLOSE` — close one side of a lane.
+    LaneClose,
+    /// `LANE-RESET` — abandon a lane.
+    LaneReset,
+    /// `GOAWAY` — the sender is shutting the tunnel down.
+    GoAway,
+    /// `ROTATE` — the sender's key replaces an older one.
+    Rotate,
+    /// `REVOKE` — end a peer's session.
+    Revoke,
+    /// `REFRESH` — trade a refresh token for a new session token.
+    Refresh,
+    /// `GROUP` — signed change to a group's members.
+    Group,
+    /// `MANIFEST` — an anchor's signed list of members.
+    Manifest,
+    /// `FED-AUTH` — prove a federation link's shared secret.
+    FedAuth,
+    /// `FED-LINK-OPEN` — open an authenticated federation link, or
+    /// keep an open one alive.
+    FedLinkOpen,
+    /// `FED-LINK-ACCEPT` — answer to `FED-LINK-OPEN`.
+    FedLinkAccept,
+    /// `FED-LINK-CLOSE` — tear a federation link down.
+    FedLinkClose,
+    /// `RELAY-REGISTER` — ask a burrow to relay frames for the sender.
+    RelayRegister,
+    /// Any other verb, kept verbatim.
+    Other(String),
+}
+
+impl Verb {
+    /// The verb as it appears on the wire.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Hello => "HELLO",
+            Self::Auth => "AUTH",
+            Self::List => "LIST",
+            Self::Fetch => "FETCH",
+            Self::Search => "SEARCH",
+            Self::Describe => "DESCRIBE",
+            Self::Subscribe => "SUBSCRIBE",
+            Self::Unsubscribe => "UNSUBSCRIBE",
+            Self::Replicate => "REPLICATE",
+            Self::Publish => "PUBLISH",
+            Self::TopicConfig => "TOPIC-CONFIG",
+            Self::Event => "EVENT",
+            Self::Ack => "ACK",
+            Self::Credit => "CREDIT",
+            Self::Ping => "PING",
+            Self::Pong => "PONG",
+            Self::Offer => "OFFER",
+            Self::RouteAdvert => "ROUTE-ADVERT",
+            Self::Delegate => "DELEGATE",
+            Self::DelegateGrant => "DELEGATE-GRANT",
+            Self::Chunk => "CHUNK",
+            Self::Cancel => "CANCEL",
+            Self::LaneOpen => "LANE-OPEN",
+            Self::LaneClose => "LANE-CLOSE",
+            Self::LaneReset => "LANE-RESET",
+            Self::GoAway => "GOAWAY",
+            Self::Rotate => "ROTATE",
+            Self::Revoke => "REVOKE",
+            Self::Refresh => "REFRESH",
+            Self::Group => "GROUP",
+            Self::Manifest => "MANIFEST",
+            Self::FedAuth => "FED-AUTH",
+            Self::FedLinkOpen => "FED-LINK-OPEN",
+            Self::FedLinkAccept => "FED-LINK-ACCEPT",
+            Self::FedLinkClose => "FED-LINK-CLOSE",
+            Self::RelayRegister => "RELAY-REGISTER",
+            Self::Other(s) => s,
+        }
+    }
+}
+
+impl TryFrom<&str> for Verb {
+    type Error = ProtocolError;
+
+    /// Parse a verb.  Fails for empty text, text containing
+    /// whitespace, and status codes.
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        if s.is_empty() || s.contains(char::is_whitespace) {
+            return Err(ProtocolError::BadRequest(format!("invalid verb: {:?}", s)));
+        }
+        if Status::try_from(s).is_ok() {
+            return Err(ProtocolError::BadRequest(format!(
+                "{} is a status, not a verb",
+                s
+            )));
+        }
+        Ok(match s {
+            "HELLO" => Self::Hello,
+            "AUTH" => Self::Auth,
+            "LIST" => Self::List,
+            "FETCH" => Self::Fetch,
+            "SEARCH" => Self::Search,
+            "DESCRIBE" => Self::Describe,
+            "SUBSCRIBE" => Self::Subscribe,
+            "UNSUBSCRIBE" => Self::Unsubscribe,
+            "REPLICATE" => Self::Replicate,
+            "PUBLISH" => Self::Publish,
+            "TOPIC-CONFIG" => Self::TopicConfig,
+            "EVENT" => Self::Event,
+            "ACK" => Self::Ack,
+            "CREDIT" => Self::Credit,
+            "PING" => Self::Ping,
+            "PONG" => Self::Pong,
+            "OFFER" => Self::Offer,
+            "ROUTE-ADVERT" => Self::RouteAdvert,
+            "DELEGATE" => Self::Delegate,
+            "DELEGATE-GRANT" => Self::DelegateGrant,
+            "CHUNK" => Self::Chunk,
+            "CANCEL" => Self::Cancel,
+            "LANE-OPEN" => Self::LaneOpen,
+            "LANE-CLOSE" => Self::LaneClose,
+            "LANE-RESET" => Self::LaneReset,
+            "GOAWAY" => Self::GoAway,
+            "ROTATE" => Self::Rotate,
+            "REVOKE" => Self::Revoke,
+            "REFRESH" => Self::Refresh,
+            "GROUP" => Self::Group,
+            "MANIFEST" => Self::Manifest,
+            "FED-AUTH" => Self::FedAuth,
+            "FED-LINK-OPEN" => Self::FedLinkOpen,
+            "FED-LINK-ACCEPT" => Self::FedLinkAccept,
+            "FED-LINK-CLOSE" => Self::FedLinkClose,
+            "RELAY-REGISTER" => Self::RelayRegister,
+            other => Self::Other(other.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Verb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A response status code.
+///
+/// The label that follows the code on the start line (`MENU` in
+/// `200 MENU`) stays in the frame's args; only the code is typed.
+/// Codes without a variant parse as [`Status::Other`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// 200 — success.
+    Ok,
+    /// 201 — subscription created.
+    Subscribed,
+    /// 204 — success with nothing more to send.
+    Done,
+    /// 300 — authentication challenge.
+    Challenge,
+    /// 400 — malformed frame or invalid request.
+    BadRequest,
+    /// 403 — operation not permitted.
+    Forbidden,
+    /// 404 — selector not found.
+    Missing,
+    /// 406 — requested view not available.
+    NotAcceptable,
+    /// 408 — operation timed out.
+    Timeout,
+    /// 409 — sequence number out of order.
+    OutOfOrder,
+    /// 412 — precondition not met.
+    PreconditionFailed,
+    /// 413 — frame over a size limit.
+    TooLarge,
+    /// 429 — flow-control limit hit.
+    FlowLimit,
+    /// 431 — invalid HELLO.
+    BadHello,
+    /// 440 — authentication required.
+    AuthRequired,
+    /// 499 — canceled by the peer.
+    Canceled,
+    /// 502 — no route to the target burrow.
+    NoRoute,
+    /// 503 — burrow busy.
+    Busy,
+    /// 507 — storage quota exceeded.
+    QuotaExceeded,
+    /// 520 — internal error.
+    InternalError,
+    /// Any other three-digit code.
+    Other(u16),
+}
+
+impl Status {
+    /// The status for a numeric code.
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            200 => Self::Ok,
+            201 => Self::Subscribed,
+            204 => Self::Done,
+            300 => Self::Challenge,
+            400 => Self::BadRequest,
+            403 => Self::Forbidden,
+            404 => Self::Missing,
+            406 => Self::NotAcceptable,
+            408 => Self::Timeout,
+            409 => Self::OutOfOrder,
+            412 => Self::PreconditionFailed,
+            413 => Self::TooLarge,
+            429 => Self::FlowLimit,
+            431 => Self::BadHello,
+            440 => Self::AuthRequired,
+            499 => Self::Canceled,
+            502 => Self::NoRoute,
+            503 => Self::Busy,
+            507 => Self::QuotaExceeded,
+            520 => Self::InternalError,
+            other => Self::Other(other),
+        }
+    }
+
+    /// The numeric code.
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::Ok => 200,
+            Self::Subscribed => 201,
+            Self::Done => 204,
+            Self::Challenge => 300,
+            Self::BadRequest => 400,
+            Self::Forbidden => 403,
+            Self::Missing => 404,
+            Self::NotAcceptable => 406,
+            Self::Timeout => 408,
+            Self::OutOfOrder => 409,
+            Self::PreconditionFailed => 412,
+            Self::TooLarge => 413,
+            Self::FlowLimit => 429,
+            Self::BadHello => 431,
+            Self::AuthRequired => 440,
+            Self::Canceled => 499,
+            Self::NoRoute => 502,
+            Self::Busy => 503,
+            Self::QuotaExceeded => 507,
+            Self::InternalError => 520,
+            Self::Other(code) => *code,
+        }
+    }
+
+    /// Whether this is a 2xx status.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code())
+    }
+
+    /// Whether this is a 4xx or 5xx status.
+    pub fn is_error(&self) -> bool {
+        self.code() >= 400
+    }
+}
+
+impl TryFrom<&str> for Status {
+    type Error = ProtocolError;
+
+    /// Parse a three-digit status code.
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        if s.len() != 3 || !s.bytes().all(|b| b.is_ascii_digit()) || s.starts_with('0') {
+            return Err(ProtocolError::BadRequest(format!(
+                "invalid status: {:?}",
+                s
+            )));
+        }
+        s.parse()
+            .map(Self::from_code)
+            .map_err(|_| ProtocolError::BadRequest(format!("invalid status: {:?}", s)))
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// The typed form of a frame's verb: a request verb or a status.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VerbKind {
+    /// A request or control verb.
+    Verb(Verb),
+    /// A response status.
+    Status(Status),
+}
+
+impl TryFrom<&str> for VerbKind {
+    type Error = ProtocolError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match Status::try_from(s) {
+            Ok(status) => Ok(Self::Status(status)),
+            Err(_) => Verb::try_from(s).map(Self::Verb),
+        }
+    }
+}
+
+impl fmt::Display for VerbKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Verb(verb) => verb.fmt(f),
+            Self::Status(status) => status.fmt(f),
+        }
+    }
+}
+
+// ── Builder ────────────────────────────────────────────────────
+
+/// Fluent construction of frames, checked on [`FrameBuilder::build`].
+///
+/// ```
+/// use rabbit_engine::protocol::frame::Frame;
+///
+/// let frame = Frame::builder("EVENT")
+///     .selector("/q/chat")
+///     .lane(3)
+///     .seq(42)
+///     .body_text("hello")
+///     .build()
+///     .unwrap();
+/// assert_eq!(frame.header("Seq"), Some("42"));
+/// ```
+///
+/// `build` refuses anything that would not survive the wire: a verb
+/// that is not a single token, args containing whitespace, header
+/// names that are empty or contain `:` or whitespace, values that
+/// contain line breaks, and bodies that are not UTF-8.  `Length` is
+/// always derived from the body and cannot be set directly.
+#[derive(Debug, Clone)]
+pub struct FrameBuilder {
+    verb: String,
+    args: Vec<String>,
+    headers: BTreeMap<String, String>,
+    body: Option<Vec<u8>>,
+}
+
+impl FrameBuilder {
+    /// Start a frame from its start line, split as by [`Frame::new`].
+    pub fn new(start_line: impl Into<String>) -> Self {
+        let Frame { verb, args, .. } = Frame::new(start_line);
+        Self {
+            verb,
+            args,
+            headers: BTreeMap::new(),
+            body: None,
+        }
+    }
+
+    /// Append a positional argument.
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Append a selector or topic argument.
+    pub fn selector(self, selector: &str) -> Self {
+        self.arg(selector)
+    }
+
+    /// Set a header.
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the `Lane` header.
+    pub fn lane(self, lane: u16) -> Self {
+        self.header("Lane", lane.to_string())
+    }
+
+    /// Set the `Seq` header.
+    pub fn seq(self, seq: u64) -> Self {
+        self.header("Seq", seq.to_string())
+    }
+
+    /// Set the `Txn` header.
+    pub fn txn(self, txn: &str) -> Self {
+        self.header("Txn", txn)
+    }
+
+    /// Set a text body.
+    pub fn body_text(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into().into_bytes());
+        self
+    }
+
+    /// Set a body from bytes, which must be UTF-8.
+    pub fn body_bytes(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Check the frame and build it.
+    pub fn build(self) -> Result<Frame, ProtocolError> {
+        let bad = |msg: String| Err(ProtocolError::BadRequest(msg));
+        VerbKind::try_from(self.verb.as_str())?;
+        if let Some(arg) = self
+            .args
+            .iter()
+            .find(|a| a.is_empty() || a.contains(char::is_whitespace))
+        {
+            return bad(format!("invalid frame argument: {:?}", arg));
+        }
+        for (key, value) in &self.headers {
+            if key.is_empty() || key.contains(':') || key.contains(char::is_whitespace) {
+                return bad(format!("invalid header name: {:?}", key));
+            }
+            if key == "Length" || key == "End" {
+                return bad(format!("{} header is reserved", key));
+            }
+            if value.contains(['\r', '\n']) {
+                return bad(format!("line break in {} header", key));
+            }
+        }
+        let mut frame = Frame {
+            verb: self.verb,
+            args: self.args,
+            headers: self.headers,
+            body: None,
+        };
+        if let Some(body) = self.body {
+            let body = String::from_utf8(body).map_err(|e| {
+                ProtocolError::BadRequest(format!("frame body is not UTF-8: {}", e))
+            })?;
+            frame.set_body(body);
+        }
+        Ok(frame)
+    }
+}
+
+// ── Incremental text decoding ──────────────────────────────────
+
+/// Where a [`FrameDecoder`] is within the frame at the front of its
+/// buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    /// Looking for the `End:` line; bytes before `scanned` hold none.
+    Head { scanned: usize },
+    /// Header block of `head_len` bytes parsed; waiting for the body.
+    Body { head_len: usize, body_len: usize },
+    /// Discarding the body of a rejected frame.
+    Skip { remaining: usize },
+    /// A header block overran the limit without ending, so frame
+    /// boundaries are lost.
+    Desynced,
+}
+
+/// Incremental parser for text frames arriving in arbitrary chunks.
+///
+/// Bytes are accumulated across calls to [`FrameDecoder::feed`], which
+/// returns every frame completed so far: none when a frame is still
+/// partial, several when frames were pipelined into one read.  A
+/// frame's body is the `Length` header's worth of bytes after `End:`;
+/// without a `Length` header it has no body.
+///
+/// Frames over the decoder's [`FrameLimits`] yield
+/// [`ProtocolError::TooLarge`].  An oversized body is skipped without
+/// being buffered, so decoding carries on with the next frame; a
+/// header block that never ends leaves the stream unusable, and every
+/// later call fails.
+///
+/// A frame whose `Digest` header does not match its body is consumed
+/// and reported as [`ProtocolError::PreconditionFailed`]; decoding
+/// carries on with the next frame.
+#[derive(Debug)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    state: DecodeState,
+    limits: FrameLimits,
+}
+
+impl FrameDecoder {
+    /// Create a decoder with the default limits.
+    pub fn new() -> Self {
+        Self::with_limits(FrameLimits::default())
+    }
+
+    /// Create a decoder enforcing `limits`.
+    pub fn with_limits(limits: FrameLimits) -> Self {
+        Self {
+            buf: Vec::new(),
+            state: DecodeState::Head { scanned: 0 },
+            limits,
+        }
+    }
+
+    /// Change the limits for frames not yet decoded.
+    pub fn set_limits(&mut self, limits: FrameLimits) {
+        self.limits = limits;
+    }
+
+    /// Append received bytes and return all frames now complete.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<Frame>, ProtocolError> {
+        self.push(data);
+        let mut frames = Vec::new();
+        while let Some(frame) = self.next_frame()? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    /// Append received bytes without decoding; frames are taken with
+    /// [`FrameDecoder::next_frame`].
+    pub fn push(&mut self, data: &[u8]) {
+        if self.state != DecodeState::Desynced {
+            self.buf.extend_from_slice(data);
+        }
+    }
+
+    /// Take the next complete frame from the buffer, if there is one.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, ProtocolError> {
+        const END: &[u8] = b"End:\r\n";
+        loop {
+            match self.state {
+                DecodeState::Head { scanned } => {
+                    // The End: line starts the buffer or follows a CRLF.
+                    let found = (scanned..self.buf.len()).find(|&i| {
+                        (i == 0 || self.buf[i - 1] == b'\n') && self.buf[i..].starts_with(END)
+                    });
+                    let Some(pos) = found else {
+                        if self.buf.len() > self.limits.max_header_bytes + END.len() {
+                            self.state = DecodeState::Desynced;
+                            self.buf = Vec::new();
+                            return Err(ProtocolError::TooLarge(format!(
+                                "frame headers exceed {} bytes",
+                                self.limits.max_header_bytes
+                            )));
+                        }
+                        // A partial End: line may still complete here.
+                        self.state = DecodeState::Head {
+                            scanned: self.buf.len().saturating_sub(END.len()),
+                        };
+                        return Ok(None);
+                    };
+                    let head_len = pos + END.len();
+                    let head = std::str::from_utf8(&self.buf[..head_len]).map_err(|e| {
+                        ProtocolError::BadRequest(format!("invalid UTF-8 in frame header: {}", e))
+                    })?;
+                    let body_len = match head
+                        .split("\r\n")
+                        .find_map(|line| line.strip_prefix("Length:"))
+                    {
+                        Some(len) => len.trim().parse().map_err(|_| {
+                            ProtocolError::BadRequest(format!(
+                                "invalid Length header: {}",
+                                len.trim()
+                            ))
+                        })?,
+                        None => 0,
+                    };
+                    if body_len > self.limits.max_body_bytes {
+                        self.buf.drain(..head_len);
+                        self.state = DecodeState::Skip {
+                            remaining: body_len,
+                        };
+                        return Err(body_too_large(body_len, &self.limits));
+                    }
+                    self.state = DecodeState::Body { head_len, body_len };
+                }
+                DecodeState::Body { head_len, body_len } => {
+                    let total = head_len + body_len;
+                    if self.buf.len() < total {
+                        return Ok(None);
+                    }
+                    let raw: Vec<u8> = self.buf.drain(..total).collect();
+                    self.state = DecodeState::Head { scanned: 0 };
+                    let text = String::from_utf8(raw).map_err(|e| {
+                        ProtocolError::BadRequest(format!("invalid UTF-8 in frame: {}", e))
+                    })?;
+                    let frame = Frame::parse_with_limits(&text, &self.limits)?;
+                    frame.verify_digest()?;
+                    return Ok(Some(frame));
+                }
+                DecodeState::Skip { remaining } => {
+                    let n = remaining.min(self.buf.len());
+                    self.buf.drain(..n);
+                    if n < remaining {
+                        self.state = DecodeState::Skip {
+                            remaining: remaining - n,
+                        };
+                        return Ok(None);
+                    }
+                    self.state = DecodeState::Head { scanned: 0 };
+                }
+                DecodeState::Desynced => {
+                    return Err(ProtocolError::BadRequest(
+                        "frame stream lost sync after oversized headers".into(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Bytes received but not yet returned as part of a frame.
+    pub fn remaining(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Number of bytes in [`FrameDecoder::remaining`].
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Take the remaining bytes, resetting the decoder.
+    pub fn take_remaining(&mut self) -> Vec<u8> {
+        self.state = DecodeState::Head { scanned: 0 };
+        std::mem::take(&mut self.buf)
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ── Length-prefixed encoding ───────────────────────────────────
+
+/// Magic bytes opening every length-prefixed frame.
+pub const FRAME_MAGIC: [u8; 2] = *b"RB";
+
+/// Version of the length-prefixed encoding.
+pub const FRAME_VERSION: u8 = 1;
+
+/// Size of the fixed header: magic, version, flags and length.
+pub const FRAME_HEADER_LEN: usize = 8;
+
+/// Flag marking a length-prefixed frame whose body is raw bytes,
+/// held in the decoded [`Frame`] under `Transfer: base64`.
+pub const FLAG_BINARY_BODY: u8 = 0x01;
+
+/// Default upper bound on a frame's payload (16 MiB).
+pub const DEFAULT_MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Streaming encoder/decoder for length-prefixed frames.
+///
+/// Bytes are handed to [`FrameCodec::feed`] as they arrive, in chunks
+/// of any size; [`FrameCodec::decode`] returns each frame once all of
+/// its bytes are buffered.  Version 1 defines only
+/// [`FLAG_BINARY_BODY`]; frames carrying any other flag are rejected so
+/// later versions can assign them.
+///
+/// A frame over the payload limit is skipped as its bytes arrive, so
+/// decoding resumes with the frame after it.  Frames failing their
+/// `Digest` check are dropped the same way, as with [`FrameDecoder`].
+#[derive(Debug)]
+pub struct FrameCodec {
+    /// Received bytes not yet decoded.
+    buf: Vec<u8>,
+    /// Largest payload accepted or produced.
+    max_len: usize,
+    /// Limits applied when parsing a payload.
+    limits: FrameLimits,
+    /// Bytes of a rejected frame still to discard.
+    skip: usize,
+}
+
+impl FrameCodec {
+    /// Create a codec with the default payload limit.
+    pub fn new() -> Self {
+        Self::with_max_len(DEFAULT_MAX_FRAME_LEN)
+    }
+
+    /// Create a codec that refuses payloads over `max_len` bytes.
+    pub fn with_max_len(max_len: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            max_len: max_len.min(u32::MAX as usize),
+            limits: FrameLimits::default(),
+            skip: 0,
+        }
+    }
+
+    /// Apply `limits` to frames not yet decoded.  The payload limit
+    /// becomes the header and body limits combined.
+    pub fn set_limits(&mut self, limits: FrameLimits) {
+        self.max_len = (limits.max_header_bytes + limits.max_body_bytes).min(u32::MAX as usize);
+        self.limits = limits;
+    }
+
+    /// Encode a frame with its length-prefixed header.  A binary body
+    /// is sent as its bytes.
+    pub fn encode(&self, frame: &Frame) -> Result<Vec<u8>, ProtocolError> {
+        let (payload, flags) = match binary_payload(frame) {
+            Some(payload) => (payload, FLAG_BINARY_BODY),
+            None => (frame.serialize().into_bytes(), 0),
+        };
+        if payload.len() > self.max_len {
+            return Err(ProtocolError::TooLarge(format!(
+                "frame of {} bytes exceeds the {} byte limit",
+                payload.len(),
+                self.max_len
+            )));
+        }
+        let mut out = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+        out.extend_from_slice(&FRAME_MAGIC);
+        out.push(FRAME_VERSION);
+        out.push(flags);
+        out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        out.extend_from_slice(&payload);
+        Ok(out)
+    }
+
+    /// Append received bytes to the buffer.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Decode the next complete frame, if one is buffered.
+    ///
+    /// Returns `Ok(None)` when more bytes are needed.  A malformed
+    /// header is an error; the stream cannot be resynchronized after
+    /// one, so the tunnel should be closed.
+    pub fn decode(&mut self) -> Result<Option<Frame>, ProtocolError> {
+        if self.skip > 0 {
+            let n = self.skip.min(self.buf.len());
+            self.buf.drain(..n);
+            self.skip -= n;
+        }
+        if self.skip > 0 || self.buf.len() < FRAME_HEADER_LEN {
+            return Ok(None);
+        }
+        if self.buf[..2] != FRAME_MAGIC {
+            return Err(ProtocolError::BadRequest("bad frame magic".into()));
+        }
+        if self.buf[2] != FRAME_VERSION {
+            return Err(ProtocolError::BadRequest(format!(
+                "unsupported frame version {}",
+                self.buf[2]
+            )));
+        }
+        let flags = self.buf[3];
+        if flags & !FLAG_BINARY_BODY != 0 {
+            return Err(ProtocolError::BadRequest(format!(
+                "unknown frame flags {:#04x}",
+                flags
+            )));
+        }
+        let len = u32::from_be_bytes([self.buf[4], self.buf[5], self.buf[6], self.buf[7]]) as usize;
+        if len > self.max_len {
+            self.buf.drain(..FRAME_HEADER_LEN);
+            self.skip = len;
+            return Err(ProtocolError::TooLarge(format!(
+                "frame of {} bytes exceeds the {} byte limit",
+                len, self.max_len
+            )));
+        }
+        if self.buf.len() < FRAME_HEADER_LEN + len {
+            return Ok(None);
+        }
+        let payload: Vec<u8> = self
+            .buf
+            .drain(..FRAME_HEADER_LEN + len)
+            .skip(FRAME_HEADER_LEN)
+            .collect();
+        let frame = parse_payload(payload, flags & FLAG_BINARY_BODY != 0, &self.limits)?;
+        frame.verify_digest()?;
+        Ok(Some(frame))
+    }
+
+    /// Number of received bytes not yet decoded.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Take the bytes not yet decoded, resetting the codec.
+    pub fn take_remaining(&mut self) -> Vec<u8> {
+        self.skip = 0;
+        std::mem::take(&mut self.buf)
+    }
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serialize a frame with a binary body, putting the body's bytes in
+/// place of their base64.  Returns `None` for any other frame.
+fn binary_payload(frame: &Frame) -> Option<Vec<u8>> {
+    let data = frame.binary_body()?;
+    let mut head = Frame {
+        verb: frame.verb.clone(),
+        args: frame.args.clone(),
+        headers: frame.headers.clone(),
+        body: None,
+    };
+    head.headers.remove("Transfer");
+    head.set_header("Length", data.len().to_string());
+    let mut payload = head.serialize().into_bytes();
+    payload.extend_from_slice(&data);
+    Some(payload)
+}
+
+/// Parse a length-prefixed payload.  A body flagged `binary`, or one
+/// that is not UTF-8, becomes a binary body.
+fn parse_payload(
+    payload: Vec<u8>,
+    binary: bool,
+    limits: &FrameLimits,
+) -> Result<Frame, ProtocolError> {
+    let payload = if binary {
+        payload
+    } else {
+        match String::from_utf8(payload) {
+            Ok(text) => return Frame::parse_with_limits(&text, limits),
+            Err(e) => e.into_bytes(),
+        }
+    };
+    const END: &[u8] = b"End:\r\n";
+    let head_len = payload
+        .windows(END.len())
+        .position(|w| w == END)
+        .ok_or_else(|| ProtocolError::BadRequest("missing End: marker".into()))?
+        + END.len();
+    let head = std::str::from_utf8(&payload[..head_len])
+        .map_err(|e| ProtocolError::BadRequest(format!("invalid UTF-8 in frame header: {}", e)))?;
+    let mut frame = Frame::parse_with_limits(head, limits)?;
+    let mut body = &payload[head_len..];
+    if let Some(len) = frame.header("Length") {
+        let len: usize = len
+            .parse()
+            .map_err(|_| ProtocolError::BadRequest(format!("invalid Length header: {}", len)))?;
+        if body.len() < len {
+            return Err(ProtocolError::BadRequest(format!(
+                "body too short: expected {} bytes, got {}",
+                len,
+                body.len()
+            )));
+        }
+        body = &body[..len];
+    }
+    if body.len() > limits.max_body_bytes {
+        return Err(body_too_large(body.len(), limits));
+    }
+    frame.set_binary_body(body);
+    Ok(frame)
+}
+
 /// Multi-part frame reassembly (H7).
 ///
 /// The `Part` header supports streaming large responses across
@@ -499,4 +1584,347 @@ mod tests {
         let parsed = Frame::parse(raw).unwrap();
         assert_eq!(parsed.body.as_deref(), Some("some body text"));
     }
+
+    #[test]
+    fn codec_round_trip_across_chunk_boundaries() {
+        let mut event = Frame::with_args("EVENT", vec!["/q/chat".into()]);
+        event.set_header("Seq", "7");
+        event.set_body("End:\r\nnot a header\r\n");
+        let ping = Frame::new("PING");
+
+        let mut codec = FrameCodec::new();
+        let mut wire = codec.encode(&event).unwrap();
+        wire.extend(codec.encode(&ping).unwrap());
+        assert_eq!(&wire[..4], b"RB\x01\x00");
+
+        let mut decoded = Vec::new();
+        for byte in &wire {
+            codec.feed(std::slice::from_ref(byte));
+            while let Some(frame) = codec.decode().unwrap() {
+                decoded.push(frame);
+            }
+        }
+        assert_eq!(decoded, vec![event, ping]);
+        assert_eq!(codec.buffered(), 0);
+    }
+
+    #[test]
+    fn codec_rejects_bad_headers() {
+        for header in [
+            b"XX\x01\x00\x00\x00\x00\x05".to_vec(),
+            b"RB\x02\x00\x00\x00\x00\x05".to_vec(),
+            b"RB\x01\x80\x00\x00\x00\x05".to_vec(),
+            b"RB\x01\x00\x00\x01\x00\x00".to_vec(),
+        ] {
+            let mut codec = FrameCodec::with_max_len(1024);
+            codec.feed(&header);
+            assert!(codec.decode().is_err(), "accepted {:?}", header);
+        }
+
+        let mut big = Frame::new("200 CONTENT");
+        big.set_body("x".repeat(2048));
+        assert!(FrameCodec::with_max_len(1024).encode(&big).is_err());
+    }
+
+    #[test]
+    fn codec_carries_binary_bodies_as_bytes() {
+        let data = [0u8, 0xff, 0xfe, b'\r', b'\n', b'E', b'n', b'd', b':', 0x80];
+        let mut blob = Frame::new("200 CONTENT");
+        blob.set_header("View", "application/octet-stream");
+        blob.set_binary_body(&data);
+        blob.set_digest();
+
+        let mut codec = FrameCodec::new();
+        let wire = codec.encode(&blob).unwrap();
+        assert_eq!(wire[3], FLAG_BINARY_BODY);
+        assert!(wire.ends_with(&data));
+        assert!(!wire.windows(9).any(|w| w == b"Transfer:"));
+        codec.feed(&wire);
+        let decoded = codec.decode().unwrap().unwrap();
+        assert_eq!(decoded, blob);
+        assert_eq!(decoded.binary_body().unwrap(), data);
+
+        // An unflagged body that is not UTF-8 is taken as binary too.
+        let mut raw = b"PUBLISH /q/raw\r\nLength: 2\r\nEnd:\r\n".to_vec();
+        raw.extend_from_slice(&[0xc3, 0x28]);
+        let mut wire = b"RB\x01\x00".to_vec();
+        wire.extend_from_slice(&(raw.len() as u32).to_be_bytes());
+        wire.extend_from_slice(&raw);
+        codec.feed(&wire);
+        let decoded = codec.decode().unwrap().unwrap();
+        assert_eq!(decoded.binary_body().unwrap(), [0xc3, 0x28]);
+    }
+
+    #[test]
+    fn codec_skips_oversized_frames() {
+        let mut big = Frame::new("PUBLISH");
+        big.set_body("x".repeat(200));
+        let ping = Frame::new("PING");
+        let sender = FrameCodec::new();
+        let mut wire = sender.encode(&big).unwrap();
+        wire.extend(sender.encode(&ping).unwrap());
+
+        let mut codec = FrameCodec::new();
+        codec.set_limits(small_limits());
+        codec.feed(&wire[..20]);
+        assert!(matches!(codec.decode(), Err(ProtocolError::TooLarge(_))));
+        assert_eq!(codec.decode().unwrap(), None);
+        codec.feed(&wire[20..]);
+        assert_eq!(codec.decode().unwrap(), Some(ping));
+    }
+
+    #[test]
+    fn decoder_handles_partial_and_pipelined_frames() {
+        let mut content = Frame::new("200 CONTENT");
+        content.set_header("Lane", "1");
+        content.set_body("body with End:\r\ninside and ünïcode");
+        let mut ping = Frame::new("PING");
+        ping.set_header("Lane", "0");
+        let wire = format!(
+            "{}{}{}",
+            content.serialize(),
+            ping.serialize(),
+            "EVENT /q/chat\r\nLen"
+        );
+
+        let mut decoder = FrameDecoder::new();
+        let mut frames = Vec::new();
+        for chunk in wire.as_bytes().chunks(5) {
+            frames.extend(decoder.feed(chunk).unwrap());
+        }
+        assert_eq!(frames, vec![content.clone(), ping.clone()]);
+        assert_eq!(decoder.remaining(), b"EVENT /q/chat\r\nLen");
+
+        // Everything at once yields both frames from a single call.
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.feed(wire.as_bytes()).unwrap().len(), 2);
+        assert_eq!(decoder.take_remaining(), b"EVENT /q/chat\r\nLen");
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn verb_kinds_round_trip() {
+        for (text, kind) in [
+            ("FETCH", VerbKind::Verb(Verb::Fetch)),
+            ("UNSUBSCRIBE", VerbKind::Verb(Verb::Unsubscribe)),
+            ("REPLICATE", VerbKind::Verb(Verb::Replicate)),
+            ("TOPIC-CONFIG", VerbKind::Verb(Verb::TopicConfig)),
+            ("ROUTE-ADVERT", VerbKind::Verb(Verb::RouteAdvert)),
+            ("DELEGATE-GRANT", VerbKind::Verb(Verb::DelegateGrant)),
+            ("CANCEL", VerbKind::Verb(Verb::Cancel)),
+            ("LANE-RESET", VerbKind::Verb(Verb::LaneReset)),
+            ("GOAWAY", VerbKind::Verb(Verb::GoAway)),
+            ("ROTATE", VerbKind::Verb(Verb::Rotate)),
+            ("REVOKE", VerbKind::Verb(Verb::Revoke)),
+            ("REFRESH", VerbKind::Verb(Verb::Refresh)),
+            ("GROUP", VerbKind::Verb(Verb::Group)),
+            ("MANIFEST", VerbKind::Verb(Verb::Manifest)),
+            ("FED-AUTH", VerbKind::Verb(Verb::FedAuth)),
+            ("FED-LINK-OPEN", VerbKind::Verb(Verb::FedLinkOpen)),
+            ("FED-LINK-CLOSE", VerbKind::Verb(Verb::FedLinkClose)),
+            ("RELAY-REGISTER", VerbKind::Verb(Verb::RelayRegister)),
+            (
+                "FROBNICATE",
+                VerbKind::Verb(Verb::Other("FROBNICATE".into())),
+            ),
+            ("404", VerbKind::Status(Status::Missing)),
+            ("299", VerbKind::Status(Status::Other(299))),
+        ] {
+            assert_eq!(VerbKind::try_from(text).unwrap(), kind);
+            assert_eq!(kind.to_string(), text);
+        }
+        for bad in ["", "TWO WORDS", "200"] {
+            assert!(Verb::try_from(bad).is_err(), "{:?}", bad);
+        }
+        for bad in ["20", "2000", "0200", "OK"] {
+            assert!(Status::try_from(bad).is_err(), "{:?}", bad);
+        }
+
+        let frame = Frame::parse("200 MENU\r\nEnd:\r\n").unwrap();
+        assert_eq!(frame.verb_kind(), VerbKind::Status(Status::Ok));
+        assert!(Status::Ok.is_success() && Status::Busy.is_error());
+        assert_eq!(
+            Frame::new("").verb_kind(),
+            VerbKind::Verb(Verb::Other(String::new()))
+        );
+    }
+
+    #[test]
+    fn builder_sets_typed_headers() {
+        let frame = Frame::builder("200 CONTENT")
+            .lane(4)
+            .txn("T1")
+            .header("View", "text/plain")
+            .body_bytes(b"hi".to_vec())
+            .build()
+            .unwrap();
+        let mut expected = Frame::new("200 CONTENT");
+        expected.set_header("Lane", "4");
+        expected.set_header("Txn", "T1");
+        expected.set_header("View", "text/plain");
+        expected.set_body("hi");
+        assert_eq!(frame, expected);
+        assert_eq!(Frame::parse(&frame.serialize()).unwrap(), frame);
+    }
+
+    #[test]
+    fn builder_rejects_unsendable_frames() {
+        let cases = [
+            Frame::builder(""),
+            Frame::builder("FETCH").selector("two words"),
+            Frame::builder("PING").header("Bad:Key", "v"),
+            Frame::builder("PING").header("Note", "line\r\nEnd:"),
+            Frame::builder("PING").header("Length", "3"),
+            Frame::builder("PUBLISH").body_bytes(vec![0xff, 0xfe]),
+        ];
+        for builder in cases {
+            let err = builder.clone().build().unwrap_err();
+            assert!(matches!(err, ProtocolError::BadRequest(_)), "{:?}", builder);
+        }
+    }
+
+    #[test]
+    fn frames_serialize_with_serde() {
+        let mut frame = Frame::new("200 CONTENT");
+        frame.set_header("Lane", "1");
+        frame.set_body("text");
+        let json = serde_json::to_string(&frame).unwrap();
+        assert!(json.contains(r#""verb":"200""#));
+        let back: Frame = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, frame);
+    }
+
+    #[test]
+    fn json_bodies_round_trip() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Peer {
+            id: String,
+            address: String,
+        }
+        let peers = vec![Peer {
+            id: "ed25519:ABC".into(),
+            address: "10.0.0.1:7443".into(),
+        }];
+        let mut frame = Frame::new("OFFER");
+        frame.set_json_body(&peers).unwrap();
+        assert_eq!(frame.header("View"), Some("application/json"));
+
+        let parsed = Frame::parse(&frame.serialize()).unwrap();
+        assert_eq!(parsed.json_body::<Vec<Peer>>().unwrap(), peers);
+        assert!(Frame::new("OFFER").json_body::<Vec<Peer>>().is_err());
+        let mut garbled = Frame::new("OFFER");
+        garbled.set_body("{not json");
+        assert!(matches!(
+            garbled.json_body::<Vec<Peer>>(),
+            Err(ProtocolError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn digest_covers_the_body() {
+        let mut frame = Frame::new("EVENT /q/chat");
+        frame.set_body("hello");
+        assert!(!frame.verify_digest().unwrap());
+        frame.set_digest();
+        assert_eq!(
+            frame.header("Digest"),
+            Some("sha-256=2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        assert!(frame.verify_digest().unwrap());
+
+        frame.body = Some("hellp".into());
+        assert!(matches!(
+            frame.verify_digest(),
+            Err(ProtocolError::PreconditionFailed(_))
+        ));
+        frame.set_header("Digest", "md5=abc");
+        assert!(frame.verify_digest().is_err());
+    }
+
+    #[test]
+    fn decoder_drops_frames_failing_their_digest() {
+        let mut good = Frame::new("EVENT /q/chat");
+        good.set_body("hello");
+        good.set_digest();
+        let corrupt = good.serialize().replace("hello", "jello");
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(corrupt.as_bytes());
+        decoder.push(good.serialize().as_bytes());
+        assert!(matches!(
+            decoder.next_frame(),
+            Err(ProtocolError::PreconditionFailed(_))
+        ));
+        assert_eq!(decoder.next_frame().unwrap(), Some(good.clone()));
+
+        let mut codec = FrameCodec::new();
+        let mut bytes = codec.encode(&good).unwrap();
+        let at = bytes.len() - 1;
+        bytes[at] = b'p';
+        codec.feed(&bytes);
+        assert!(codec.decode().is_err());
+        assert_eq!(codec.buffered(), 0);
+    }
+
+    fn small_limits() -> FrameLimits {
+        FrameLimits {
+            max_headers: 3,
+            max_header_bytes: 64,
+            max_body_bytes: 16,
+        }
+    }
+
+    #[test]
+    fn parse_enforces_limits() {
+        let limits = small_limits();
+        let too_long = "200 CONTENT\r\nLength: 20\r\nEnd:\r\nxxxxxxxxxxxxxxxxxxxx";
+        let unframed = "200 CONTENT\r\nEnd:\r\nxxxxxxxxxxxxxxxxxxxx";
+        let many = "PING\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\nEnd:\r\n";
+        for raw in [too_long, unframed, many] {
+            let err = Frame::parse_with_limits(raw, &limits).unwrap_err();
+            assert!(matches!(err, ProtocolError::TooLarge(_)), "{}", raw);
+        }
+        assert!(Frame::parse(too_long).is_ok());
+        let frame: Frame = Frame::parse_with_limits(many, &limits).unwrap_err().into();
+        assert_eq!(frame.verb, "413");
+        assert_eq!(frame.args, vec!["TOO-LARGE"]);
+    }
+
+    #[test]
+    fn decoder_skips_oversized_bodies() {
+        let mut big = Frame::new("PUBLISH");
+        big.set_body("x".repeat(40));
+        let ping = Frame::new("PING");
+        let wire = format!("{}{}", big.serialize(), ping.serialize());
+
+        let mut decoder = FrameDecoder::with_limits(small_limits());
+        let mut results = Vec::new();
+        for chunk in wire.as_bytes().chunks(7) {
+            decoder.push(chunk);
+            loop {
+                match decoder.next_frame() {
+                    Ok(Some(frame)) => results.push(Ok(frame)),
+                    Ok(None) => break,
+                    Err(e) => results.push(Err(e)),
+                }
+            }
+        }
+        assert!(matches!(results[0], Err(ProtocolError::TooLarge(_))));
+        assert_eq!(results[1].as_ref().unwrap(), &ping);
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn decoder_rejects_oversized_and_malformed_headers() {
+        let mut decoder = FrameDecoder::with_limits(small_limits());
+        let err = decoder.feed(&[b'A'; 128]).unwrap_err();
+        assert!(matches!(err, ProtocolError::TooLarge(_)));
+        // Frame boundaries are lost; the stream stays unusable.
+        assert!(decoder.feed(b"PING\r\nEnd:\r\n").is_err());
+
+        let mut decoder = FrameDecoder::new();
+        assert!(decoder
+            .feed(b"200 CONTENT\r\nLength: lots\r\nEnd:\r\n")
+            .is_err());
+    }
 }
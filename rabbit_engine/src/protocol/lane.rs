@@ -8,13 +8,101 @@
 //! credits to the sender.  The sender may only transmit when it
 //! holds credits.  Frames sent without credit are queued and
 //! flushed when new credit arrives.
-
-use std::collections::VecDeque;
+//!
+//! Acknowledgements are cumulative, optionally followed by ranges
+//! received beyond the cumulative point (selective ACK):
+//!
+//! ```text
+//! ACK
+//! ACK: 42
+//! Lane: 3
+//! Ranges: 45-47,50
+//! End:
+//! ```
+//!
+//! Here 1–42, 45–47 and 50 have arrived, so only 43, 44, 48 and 49 are
+//! retransmitted.
+//!
+//! On the receiving side each lane remembers the sequence numbers it
+//! has seen within a sliding window, so a retransmitted frame can be
+//! recognised and acknowledged without being processed twice.
+//!
+//! Lanes open implicitly on first use or explicitly with `LANE-OPEN`.
+//! Either side sends `LANE-CLOSE` when it has nothing more to send;
+//! the lane is half-closed until the other side closes too.
+//! `LANE-RESET` abandons a lane at once, dropping whatever it still
+//! holds.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::ops::RangeInclusive;
 use std::time::{Duration, Instant};
 
+use serde::Serialize;
+
+use super::error::ProtocolError;
+use super::frame::Frame;
+use super::scheduler::{DEFAULT_PRIORITY, MAX_PRIORITY};
+
 /// Default credit window granted to new lanes.
 pub const DEFAULT_CREDIT: u32 = 16;
 
+/// How far behind the highest inbound sequence number a lane still
+/// remembers which sequence numbers it has seen.
+pub const DEDUP_WINDOW: u64 = 1024;
+
+/// Where a lane is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaneState {
+    /// Both sides may send.
+    Open,
+    /// One side has sent `LANE-CLOSE`.
+    HalfClosed,
+    /// Both sides have sent `LANE-CLOSE`.
+    Closed,
+}
+
+impl LaneState {
+    /// The state as reported in a `Lane-State` header.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::HalfClosed => "half-closed",
+            Self::Closed => "closed",
+        }
+    }
+}
+
+/// Snapshot of a lane's state and counters.
+///
+/// The traffic counters are not tracked by the lane itself; they are
+/// zero here and filled in from the tunnel's counters by
+/// `TunnelStats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LaneStats {
+    /// Lane identifier.
+    pub lane: u16,
+    /// Lifecycle state, as in the `Lane-State` header.
+    pub state: &'static str,
+    /// Scheduling priority.
+    pub priority: u8,
+    /// Send credits held.
+    pub credits: u32,
+    /// Frames waiting for credit.
+    pub queued: usize,
+    /// Frames sent but not yet acknowledged.
+    pub in_flight: usize,
+    /// Retransmissions made.
+    pub retransmits: u64,
+    /// Frames sent on the lane.
+    pub frames_sent: u64,
+    /// Frames received on the lane.
+    pub frames_received: u64,
+    /// Bytes sent on the lane.
+    pub bytes_sent: u64,
+    /// Bytes received on the lane.
+    pub bytes_received: u64,
+}
+
 /// A frame that has been sent but not yet acknowledged.
 #[derive(Debug, Clone)]
 pub struct InFlightFrame {
@@ -28,18 +116,115 @@ pub struct InFlightFrame {
     pub retries: u32,
 }
 
+/// A cumulative acknowledgement plus any ranges received beyond it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelectiveAck {
+    /// Every sequence number up to and including this one arrived.
+    pub cumulative: u64,
+    /// Further sequence numbers that arrived, in ascending order.
+    pub ranges: Vec<RangeInclusive<u64>>,
+}
+
+impl SelectiveAck {
+    /// Acknowledge everything up to `seq`, with no ranges.
+    pub fn cumulative(seq: u64) -> Self {
+        Self {
+            cumulative: seq,
+            ranges: Vec::new(),
+        }
+    }
+
+    /// Add a range received beyond the cumulative point.
+    pub fn with_range(mut self, range: RangeInclusive<u64>) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    /// Read the `ACK` and `Ranges` headers of an `ACK` frame.
+    ///
+    /// A frame without an `ACK` header acknowledges nothing.
+    pub fn from_frame(frame: &Frame) -> Result<Self, ProtocolError> {
+        let cumulative = match frame.header("ACK") {
+            Some(seq) => parse_seq(seq)?,
+            None => 0,
+        };
+        let mut ranges = Vec::new();
+        for part in frame.header("Ranges").unwrap_or("").split(',') {
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            let (start, end) = (parse_seq(start)?, parse_seq(end)?);
+            if start > end {
+                return Err(ProtocolError::BadRequest(format!(
+                    "invalid ACK range: {}",
+                    part
+                )));
+            }
+            ranges.push(start..=end);
+        }
+        Ok(Self { cumulative, ranges })
+    }
+
+    /// Set the `ACK` and, if there are any ranges, `Ranges` headers.
+    pub fn write_headers(&self, frame: &mut Frame) {
+        frame.set_header("ACK", self.cumulative.to_string());
+        if self.ranges.is_empty() {
+            return;
+        }
+        let ranges: Vec<String> = self
+            .ranges
+            .iter()
+            .map(|r| {
+                if r.start() == r.end() {
+                    r.start().to_string()
+                } else {
+                    format!("{}-{}", r.start(), r.end())
+                }
+            })
+            .collect();
+        frame.set_header("Ranges", ranges.join(","));
+    }
+
+    /// Whether `seq` is acknowledged.
+    pub fn covers(&self, seq: u64) -> bool {
+        seq <= self.cumulative || self.ranges.iter().any(|r| r.contains(&seq))
+    }
+}
+
+fn parse_seq(s: &str) -> Result<u64, ProtocolError> {
+    s.trim()
+        .parse()
+        .map_err(|_| ProtocolError::BadRequest(format!("invalid sequence number: {}", s.trim())))
+}
+
 /// A single lane within a tunnel.
 #[derive(Debug)]
 pub struct Lane {
     /// Lane identifier (0–65535).
     pub id: u16,
 
+    /// Scheduling priority, 0 to [`MAX_PRIORITY`].
+    priority: u8,
+
     /// Next outbound sequence number to assign.
     next_seq_out: u64,
 
     /// Next inbound sequence number we expect to receive.
     expected_seq_in: u64,
 
+    /// Highest inbound sequence number seen.
+    highest_seen_in: u64,
+
+    /// Inbound sequence numbers seen within [`DEDUP_WINDOW`] of
+    /// `highest_seen_in`.
+    seen_in: BTreeSet<u64>,
+
+    /// Every inbound sequence number up to this one has been seen (or
+    /// fell out of the window).
+    received_through: u64,
+
     /// Highest sequence number acknowledged by the remote peer.
     acked_up_to: u64,
 
@@ -51,6 +236,15 @@ pub struct Lane {
 
     /// Frames sent but not yet acknowledged (for retransmission).
     in_flight: VecDeque<InFlightFrame>,
+
+    /// We have sent `LANE-CLOSE`.
+    closed_local: bool,
+
+    /// The remote peer has sent `LANE-CLOSE`.
+    closed_remote: bool,
+
+    /// Retransmissions made.
+    retransmits: u64,
 }
 
 impl Lane {
@@ -58,12 +252,19 @@ impl Lane {
     pub fn new(id: u16) -> Self {
         Self {
             id,
+            priority: DEFAULT_PRIORITY,
             next_seq_out: 1,
             expected_seq_in: 1,
+            highest_seen_in: 0,
+            seen_in: BTreeSet::new(),
+            received_through: 0,
             acked_up_to: 0,
             credits: DEFAULT_CREDIT,
             pending_out: VecDeque::new(),
             in_flight: VecDeque::new(),
+            closed_local: false,
+            closed_remote: false,
+            retransmits: 0,
         }
     }
 
@@ -75,6 +276,57 @@ impl Lane {
         }
     }
 
+    /// The lane's scheduling priority.
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Set the lane's scheduling priority, capped at [`MAX_PRIORITY`].
+    pub fn set_priority(&mut self, priority: u8) {
+        self.priority = priority.min(MAX_PRIORITY);
+    }
+
+    /// Snapshot the lane's state and counters.
+    pub fn stats(&self) -> LaneStats {
+        LaneStats {
+            lane: self.id,
+            state: self.state().as_str(),
+            priority: self.priority,
+            credits: self.credits,
+            queued: self.pending_out.len(),
+            in_flight: self.in_flight.len(),
+            retransmits: self.retransmits,
+            ..LaneStats::default()
+        }
+    }
+
+    /// Where the lane is in its lifecycle.
+    pub fn state(&self) -> LaneState {
+        match (self.closed_local, self.closed_remote) {
+            (false, false) => LaneState::Open,
+            (true, true) => LaneState::Closed,
+            _ => LaneState::HalfClosed,
+        }
+    }
+
+    /// Whether the remote peer has closed its side of the lane.
+    pub fn is_closed_remote(&self) -> bool {
+        self.closed_remote
+    }
+
+    /// Close our side of the lane, returning the new state.
+    pub fn close_local(&mut self) -> LaneState {
+        self.closed_local = true;
+        self.state()
+    }
+
+    /// Record the remote peer closing its side, returning the new
+    /// state.
+    pub fn close_remote(&mut self) -> LaneState {
+        self.closed_remote = true;
+        self.state()
+    }
+
     /// Reserve and return the next outbound sequence number.
     pub fn next_seq(&mut self) -> u64 {
         let seq = self.next_seq_out;
@@ -103,6 +355,46 @@ impl Lane {
         Ok(())
     }
 
+    /// Note an inbound sequence number for duplicate detection.
+    ///
+    /// Returns `true` the first time `seq` is seen, and `false` for a
+    /// repeat or for a number so far behind the highest seen (more than
+    /// [`DEDUP_WINDOW`]) that it can no longer be told apart from one.
+    pub fn observe_inbound(&mut self, seq: u64) -> bool {
+        if seq + DEDUP_WINDOW <= self.highest_seen_in || !self.seen_in.insert(seq) {
+            return false;
+        }
+        if seq > self.highest_seen_in {
+            self.highest_seen_in = seq;
+            let floor = (seq + 1).saturating_sub(DEDUP_WINDOW);
+            self.seen_in = self.seen_in.split_off(&floor);
+            self.received_through = self.received_through.max(floor.saturating_sub(1));
+        }
+        while self.seen_in.contains(&(self.received_through + 1)) {
+            self.received_through += 1;
+        }
+        true
+    }
+
+    /// The acknowledgement to send for what has arrived so far, with
+    /// ranges for anything received beyond a gap.
+    pub fn inbound_ack(&self) -> SelectiveAck {
+        let mut ack = SelectiveAck::cumulative(self.received_through);
+        let mut run: Option<RangeInclusive<u64>> = None;
+        for &seq in self.seen_in.range(self.received_through + 2..) {
+            run = match run {
+                Some(r) if *r.end() + 1 == seq => Some(*r.start()..=seq),
+                Some(r) => {
+                    ack.ranges.push(r);
+                    Some(seq..=seq)
+                }
+                None => Some(seq..=seq),
+            };
+        }
+        ack.ranges.extend(run);
+        ack
+    }
+
     /// Record an acknowledgement from the remote peer.
     pub fn ack(&mut self, seq: u64) {
         if seq > self.acked_up_to {
@@ -118,6 +410,16 @@ impl Lane {
         }
     }
 
+    /// Record a selective acknowledgement: the cumulative point
+    /// advances as with [`Lane::ack`], and in-flight frames inside the
+    /// ranges are dropped so only the gaps are retransmitted.
+    pub fn sack(&mut self, ack: &SelectiveAck) {
+        self.ack(ack.cumulative);
+        if !ack.ranges.is_empty() {
+            self.in_flight.retain(|entry| !ack.covers(entry.seq));
+        }
+    }
+
     /// Return the highest acknowledged sequence number.
     pub fn acked_up_to(&self) -> u64 {
         self.acked_up_to
@@ -200,9 +502,16 @@ impl Lane {
                 to_resend.push(entry.data.clone());
             }
         }
+        self.retransmits += to_resend.len() as u64;
         Ok(to_resend)
     }
 
+    /// Remove and return every in-flight frame, e.g. when the tunnel
+    /// closes with frames still unacknowledged.
+    pub fn drain_in_flight(&mut self) -> Vec<InFlightFrame> {
+        self.in_flight.drain(..).collect()
+    }
+
     /// Return the number of in-flight (sent but unacked) frames.
     pub fn in_flight_count(&self) -> usize {
         self.in_flight.len()
@@ -318,4 +627,96 @@ mod tests {
         assert_eq!(flushed, vec!["second"]);
         assert_eq!(lane.pending_count(), 1);
     }
+
+    #[test]
+    fn selective_ack_retransmits_only_gaps() {
+        let mut lane = Lane::new(1);
+        for _ in 0..10 {
+            let seq = lane.next_seq();
+            lane.record_sent(seq, format!("frame{}", seq));
+        }
+        lane.sack(
+            &SelectiveAck::cumulative(3)
+                .with_range(5..=7)
+                .with_range(9..=9),
+        );
+        assert_eq!(lane.acked_up_to(), 3);
+        assert_eq!(lane.in_flight_count(), 3);
+
+        let resend = lane.check_retransmissions(Duration::ZERO, 3).unwrap();
+        assert_eq!(resend, vec!["frame4", "frame8", "frame10"]);
+
+        let stats = lane.stats();
+        assert_eq!(stats.retransmits, 3);
+        assert_eq!(stats.in_flight, 3);
+        assert_eq!(stats.state, "open");
+    }
+
+    #[test]
+    fn inbound_ack_reports_gaps() {
+        let mut lane = Lane::new(1);
+        for seq in [1, 2, 5, 6, 9] {
+            lane.observe_inbound(seq);
+        }
+        assert_eq!(
+            lane.inbound_ack(),
+            SelectiveAck::cumulative(2)
+                .with_range(5..=6)
+                .with_range(9..=9)
+        );
+        lane.observe_inbound(3);
+        lane.observe_inbound(4);
+        assert_eq!(
+            lane.inbound_ack(),
+            SelectiveAck::cumulative(6).with_range(9..=9)
+        );
+    }
+
+    #[test]
+    fn lane_closes_once_both_sides_have_closed() {
+        let mut lane = Lane::new(4);
+        assert_eq!(lane.state(), LaneState::Open);
+        assert_eq!(lane.close_remote(), LaneState::HalfClosed);
+        assert!(lane.is_closed_remote());
+        assert_eq!(lane.close_remote(), LaneState::HalfClosed);
+        assert_eq!(lane.close_local(), LaneState::Closed);
+        assert_eq!(LaneState::HalfClosed.as_str(), "half-closed");
+    }
+
+    #[test]
+    fn duplicates_are_detected_within_the_window() {
+        let mut lane = Lane::new(1);
+        assert!(lane.observe_inbound(1));
+        assert!(lane.observe_inbound(3));
+        assert!(!lane.observe_inbound(3));
+        assert!(lane.observe_inbound(2));
+        assert!(!lane.observe_inbound(1));
+
+        assert_eq!(lane.inbound_ack(), SelectiveAck::cumulative(3));
+
+        assert!(lane.observe_inbound(DEDUP_WINDOW + 10));
+        assert!(!lane.observe_inbound(10), "fell out of the window");
+        assert!(lane.observe_inbound(11));
+        assert!(!lane.observe_inbound(11));
+        assert!(lane.seen_in.len() <= DEDUP_WINDOW as usize);
+    }
+
+    #[test]
+    fn selective_ack_headers_round_trip() {
+        let ack = SelectiveAck::cumulative(42)
+            .with_range(45..=47)
+            .with_range(50..=50);
+        let mut frame = Frame::new("ACK");
+        ack.write_headers(&mut frame);
+        assert_eq!(frame.header("Ranges"), Some("45-47,50"));
+        assert_eq!(SelectiveAck::from_frame(&frame).unwrap(), ack);
+        assert!(ack.covers(46) && !ack.covers(48));
+
+        frame.set_header("Ranges", "47-45");
+        assert!(SelectiveAck::from_frame(&frame).is_err());
+        assert_eq!(
+            SelectiveAck::from_frame(&Frame::new("ACK")).unwrap(),
+            SelectiveAck::default()
+        );
+    }
 }
@@ -4,17 +4,26 @@
 //! provides concurrency-safe methods for acking, granting credits,
 //! and sending frames.  It is designed to be shared across tasks
 //! via `Arc<LaneManager>`.
+//!
+//! It also tracks each lane's lifecycle: [`LaneManager::open`] admits
+//! a lane up to the tunnel's lane limit, closing both sides or
+//! resetting a lane frees everything it held.
 
 use std::collections::HashMap;
 use std::time::Duration;
 
 use tokio::sync::Mutex;
 
-use super::lane::Lane;
+use super::error::ProtocolError;
+use super::lane::{InFlightFrame, Lane, LaneState, LaneStats, SelectiveAck};
+use super::scheduler::DEFAULT_PRIORITY;
 
 /// Concurrency-safe registry of lanes keyed by lane ID.
 pub struct LaneManager {
     lanes: Mutex<HashMap<u16, Lane>>,
+    /// Most lanes [`LaneManager::open`] admits besides lane 0
+    /// (0 = unlimited).
+    max_lanes: usize,
 }
 
 impl LaneManager {
@@ -22,9 +31,107 @@ impl LaneManager {
     pub fn new() -> Self {
         Self {
             lanes: Mutex::new(HashMap::new()),
+            max_lanes: 0,
+        }
+    }
+
+    /// Create a lane manager that admits at most `max_lanes` lanes
+    /// besides the control lane (0 = unlimited).
+    pub fn with_max_lanes(max_lanes: usize) -> Self {
+        Self {
+            max_lanes,
+            ..Self::new()
         }
     }
 
+    /// Admit a lane, creating it if it does not exist.
+    ///
+    /// Fails with `FlowLimit` if creating it would exceed the lane
+    /// limit, and with `BadRequest` if the remote peer has already
+    /// closed it.  Lane 0 is always admitted.
+    pub async fn open(&self, lane_id: u16) -> Result<(), ProtocolError> {
+        let mut lanes = self.lanes.lock().await;
+        if let Some(lane) = lanes.get(&lane_id) {
+            if lane.is_closed_remote() {
+                return Err(ProtocolError::BadRequest(format!(
+                    "lane {} is closed",
+                    lane_id
+                )));
+            }
+            return Ok(());
+        }
+        let open = lanes.keys().filter(|&&id| id != 0).count();
+        if lane_id != 0 && self.max_lanes > 0 && open >= self.max_lanes {
+            return Err(ProtocolError::FlowLimit(format!(
+                "lane limit of {} reached",
+                self.max_lanes
+            )));
+        }
+        lanes.insert(lane_id, Lane::new(lane_id));
+        Ok(())
+    }
+
+    /// A lane's scheduling priority ([`DEFAULT_PRIORITY`] if it does
+    /// not exist).
+    pub async fn priority(&self, lane_id: u16) -> u8 {
+        self.lanes
+            .lock()
+            .await
+            .get(&lane_id)
+            .map_or(DEFAULT_PRIORITY, Lane::priority)
+    }
+
+    /// Set a lane's scheduling priority, creating the lane if needed.
+    pub async fn set_priority(&self, lane_id: u16, priority: u8) {
+        self.with_lane(lane_id, |lane| lane.set_priority(priority))
+            .await
+    }
+
+    /// The lifecycle state of a lane, or `None` if it does not exist.
+    pub async fn state(&self, lane_id: u16) -> Option<LaneState> {
+        self.lanes.lock().await.get(&lane_id).map(Lane::state)
+    }
+
+    /// Close our side of a lane.  See [`LaneManager::close_remote`].
+    pub async fn close_local(&self, lane_id: u16) -> Result<LaneState, ProtocolError> {
+        self.close(lane_id, Lane::close_local).await
+    }
+
+    /// Record the remote peer closing its side of a lane.
+    ///
+    /// Returns the lane's new state.  Once both sides have closed the
+    /// lane is removed, freeing its queues.  Lane 0 cannot be closed.
+    pub async fn close_remote(&self, lane_id: u16) -> Result<LaneState, ProtocolError> {
+        self.close(lane_id, Lane::close_remote).await
+    }
+
+    async fn close(
+        &self,
+        lane_id: u16,
+        f: fn(&mut Lane) -> LaneState,
+    ) -> Result<LaneState, ProtocolError> {
+        if lane_id == 0 {
+            return Err(ProtocolError::BadRequest(
+                "the control lane cannot be closed".into(),
+            ));
+        }
+        let mut lanes = self.lanes.lock().await;
+        let Some(lane) = lanes.get_mut(&lane_id) else {
+            return Err(ProtocolError::Missing(format!("no lane {}", lane_id)));
+        };
+        let state = f(lane);
+        if state == LaneState::Closed {
+            lanes.remove(&lane_id);
+        }
+        Ok(state)
+    }
+
+    /// Abandon a lane at once, dropping its queued and in-flight
+    /// frames.  Returns `false` if there was no such lane.
+    pub async fn reset(&self, lane_id: u16) -> bool {
+        self.lanes.lock().await.remove(&lane_id).is_some()
+    }
+
     /// Access a lane by ID, creating it with defaults if it does not
     /// exist.  The closure `f` is called with a mutable reference to
     /// the lane while the lock is held.
@@ -45,6 +152,14 @@ impl LaneManager {
         }
     }
 
+    /// Record a selective acknowledgement for the given lane.
+    pub async fn sack(&self, lane_id: u16, ack: &SelectiveAck) {
+        let mut lanes = self.lanes.lock().await;
+        if let Some(lane) = lanes.get_mut(&lane_id) {
+            lane.sack(ack);
+        }
+    }
+
     /// Grant additional credits to a lane.  Returns any frames that
     /// were flushed from the pending queue.
     pub async fn add_credit(&self, lane_id: u16, n: u32) -> Vec<String> {
@@ -73,11 +188,34 @@ impl LaneManager {
             .await
     }
 
+    /// Note an inbound sequence number on a lane.  Returns `false` if
+    /// it is a duplicate; see [`Lane::observe_inbound`].
+    pub async fn observe_inbound(&self, lane_id: u16, seq: u64) -> bool {
+        self.with_lane(lane_id, |lane| lane.observe_inbound(seq))
+            .await
+    }
+
+    /// The acknowledgement to send for what has arrived on a lane; see
+    /// [`Lane::inbound_ack`].
+    pub async fn inbound_ack(&self, lane_id: u16) -> SelectiveAck {
+        self.with_lane(lane_id, |lane| lane.inbound_ack()).await
+    }
+
     /// Return the number of pending (queued) frames on a lane.
     pub async fn pending_count(&self, lane_id: u16) -> usize {
         self.with_lane(lane_id, |lane| lane.pending_count()).await
     }
 
+    /// Frames sent but not yet acknowledged, plus frames queued for
+    /// want of credit, across all lanes.
+    pub async fn unsettled(&self) -> usize {
+        let lanes = self.lanes.lock().await;
+        lanes
+            .values()
+            .map(|lane| lane.in_flight_count() + lane.pending_count())
+            .sum()
+    }
+
     /// Return a sorted list of all active lane IDs.
     pub async fn active_lane_ids(&self) -> Vec<u16> {
         let lanes = self.lanes.lock().await;
@@ -86,6 +224,14 @@ impl LaneManager {
         ids
     }
 
+    /// Snapshot every lane, sorted by lane ID.
+    pub async fn stats(&self) -> Vec<LaneStats> {
+        let lanes = self.lanes.lock().await;
+        let mut stats: Vec<LaneStats> = lanes.values().map(Lane::stats).collect();
+        stats.sort_by_key(|s| s.lane);
+        stats
+    }
+
     /// Record a sent frame for retransmission tracking.
     pub async fn record_sent(&self, lane_id: u16, seq: u64, data: String) {
         let mut lanes = self.lanes.lock().await;
@@ -93,6 +239,17 @@ impl LaneManager {
         lane.record_sent(seq, data);
     }
 
+    /// Remove every in-flight frame from every lane, paired with its
+    /// lane ID.
+    pub async fn drain_in_flight(&self) -> Vec<(u16, InFlightFrame)> {
+        let mut lanes = self.lanes.lock().await;
+        let mut drained = Vec::new();
+        for (&id, lane) in lanes.iter_mut() {
+            drained.extend(lane.drain_in_flight().into_iter().map(|f| (id, f)));
+        }
+        drained
+    }
+
     /// Check all lanes for frames needing retransmission.
     ///
     /// Returns `Ok(frames_to_resend)` or `Err(seq)` if any frame
@@ -131,6 +288,44 @@ mod tests {
         assert_eq!(mgr.active_lane_ids().await, vec![5]);
     }
 
+    #[tokio::test]
+    async fn lane_limit_and_lifecycle() {
+        let mgr = LaneManager::with_max_lanes(2);
+        mgr.open(0).await.unwrap();
+        mgr.open(1).await.unwrap();
+        mgr.open(2).await.unwrap();
+        mgr.open(2).await.unwrap();
+        assert!(matches!(
+            mgr.open(3).await,
+            Err(ProtocolError::FlowLimit(_))
+        ));
+
+        mgr.send_or_queue(1, "frame".into()).await;
+        mgr.record_sent(1, 1, "frame".into()).await;
+        assert_eq!(mgr.close_remote(1).await.unwrap(), LaneState::HalfClosed);
+        assert!(matches!(
+            mgr.open(1).await,
+            Err(ProtocolError::BadRequest(_))
+        ));
+        assert_eq!(mgr.close_local(1).await.unwrap(), LaneState::Closed);
+        assert_eq!(mgr.state(1).await, None);
+        assert!(mgr.drain_in_flight().await.is_empty());
+
+        // Closing lane 1 freed a slot.
+        mgr.open(3).await.unwrap();
+        assert_eq!(mgr.priority(3).await, DEFAULT_PRIORITY);
+        mgr.set_priority(3, 200).await;
+        assert_eq!(mgr.priority(3).await, 7);
+        assert!(mgr.reset(2).await);
+        assert!(!mgr.reset(2).await);
+        assert_eq!(mgr.active_lane_ids().await, vec![0, 3]);
+        assert!(mgr.close_local(0).await.is_err());
+        assert!(matches!(
+            mgr.close_local(9).await,
+            Err(ProtocolError::Missing(_))
+        ));
+    }
+
     #[tokio::test]
     async fn ack_known_lane() {
         let mgr = LaneManager::new();
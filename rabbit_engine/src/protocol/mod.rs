@@ -1,11 +1,17 @@
 //! Protocol primitives for the Rabbit wire format.
 //!
 //! This module contains the core building blocks: frame parsing and
-//! serialization, lane multiplexing with credit-based flow control,
-//! transaction ID generation, and typed protocol errors.
+//! serialization, `rabbit://` addresses, chunked transfer of large bodies, lane multiplexing
+//! with adaptive credit-based flow control and weighted scheduling,
+//! transaction IDs and response correlation, and typed protocol
+//! errors.
 
+pub mod address;
+pub mod chunk;
+pub mod credit;
 pub mod error;
 pub mod frame;
 pub mod lane;
 pub mod lane_manager;
+pub mod scheduler;
 pub mod txn;
@@ -0,0 +1,214 @@
+//! Weighted scheduling of outbound frames across lanes.
+//!
+//! Frames waiting to go out are queued per lane, and [`LaneScheduler`]
+//! picks the next one so a single busy lane cannot starve the rest.
+//! The control lane 0 always goes first.  The other lanes take turns
+//! in weighted round-robin: a lane of priority `p` sends up to `p + 1`
+//! frames per turn, so a bulk FETCH on a priority-0 lane sends one
+//! chunk for every four EVENTs on a lane of the default priority.
+
+use std::collections::{HashMap, VecDeque};
+
+use super::error::ProtocolError;
+use super::frame::Frame;
+
+/// Priority of lanes that do not ask for one.
+pub const DEFAULT_PRIORITY: u8 = 3;
+
+/// Highest lane priority.
+pub const MAX_PRIORITY: u8 = 7;
+
+/// The priority asked for by a frame's `Priority` header, if any.
+///
+/// Fails with `BadRequest` unless the value is a number from 0 to
+/// [`MAX_PRIORITY`].
+pub fn priority_header(frame: &Frame) -> Result<Option<u8>, ProtocolError> {
+    let Some(value) = frame.header("Priority") else {
+        return Ok(None);
+    };
+    match value.parse::<u8>() {
+        Ok(p) if p <= MAX_PRIORITY => Ok(Some(p)),
+        _ => Err(ProtocolError::BadRequest(format!(
+            "invalid Priority {:?} (expected 0-{})",
+            value, MAX_PRIORITY
+        ))),
+    }
+}
+
+/// Outbound queue of one lane.
+#[derive(Debug)]
+struct LaneQueue<T> {
+    priority: u8,
+    items: VecDeque<T>,
+}
+
+/// Per-lane queues drained in weighted round-robin, control lane first.
+#[derive(Debug)]
+pub struct LaneScheduler<T> {
+    queues: HashMap<u16, LaneQueue<T>>,
+    /// Lanes other than 0 with queued items, in turn order.
+    rotation: VecDeque<u16>,
+    /// Items the lane at the front of `rotation` may still send this
+    /// turn.
+    turn_left: u32,
+}
+
+impl<T> LaneScheduler<T> {
+    /// Create an empty scheduler.
+    pub fn new() -> Self {
+        Self {
+            queues: HashMap::new(),
+            rotation: VecDeque::new(),
+            turn_left: 0,
+        }
+    }
+
+    /// Queue an item on a lane.  `priority` (capped at
+    /// [`MAX_PRIORITY`]) replaces the lane's current priority.
+    pub fn push(&mut self, lane_id: u16, priority: u8, item: T) {
+        let queue = self.queues.entry(lane_id).or_insert_with(|| LaneQueue {
+            priority,
+            items: VecDeque::new(),
+        });
+        queue.priority = priority.min(MAX_PRIORITY);
+        if queue.items.is_empty() && lane_id != 0 {
+            self.rotation.push_back(lane_id);
+        }
+        queue.items.push_back(item);
+    }
+
+    /// Take the next item to send, with its lane.
+    pub fn pop(&mut self) -> Option<(u16, T)> {
+        if let Some(item) = self.pop_lane(0) {
+            return Some((0, item));
+        }
+        let lane_id = *self.rotation.front()?;
+        if self.turn_left == 0 {
+            self.turn_left = self.queues.get(&lane_id).map_or(1, |q| q.priority) as u32 + 1;
+        }
+        let item = self.pop_lane(lane_id)?;
+        self.turn_left -= 1;
+        if !self.queues.contains_key(&lane_id) {
+            self.rotation.pop_front();
+            self.turn_left = 0;
+        } else if self.turn_left == 0 {
+            self.rotation.rotate_left(1);
+        }
+        Some((lane_id, item))
+    }
+
+    /// Keep only the items for which `f` returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        for queue in self.queues.values_mut() {
+            queue.items.retain(&mut f);
+        }
+        self.prune();
+    }
+
+    /// Drop everything queued on a lane, returning how many items
+    /// were dropped.
+    pub fn clear_lane(&mut self, lane_id: u16) -> usize {
+        let dropped = self.queues.remove(&lane_id).map_or(0, |q| q.items.len());
+        self.prune();
+        dropped
+    }
+
+    /// Number of items queued on a lane.
+    pub fn queued(&self, lane_id: u16) -> usize {
+        self.queues.get(&lane_id).map_or(0, |q| q.items.len())
+    }
+
+    /// Total number of queued items.
+    pub fn len(&self) -> usize {
+        self.queues.values().map(|q| q.items.len()).sum()
+    }
+
+    /// Whether nothing is queued.
+    pub fn is_empty(&self) -> bool {
+        self.queues.values().all(|q| q.items.is_empty())
+    }
+
+    fn pop_lane(&mut self, lane_id: u16) -> Option<T> {
+        let queue = self.queues.get_mut(&lane_id)?;
+        let item = queue.items.pop_front();
+        if queue.items.is_empty() {
+            self.queues.remove(&lane_id);
+        }
+        item
+    }
+
+    /// Drop empty queues and take their lanes out of the rotation.
+    fn prune(&mut self) {
+        self.queues.retain(|_, q| !q.items.is_empty());
+        let front = self.rotation.front().copied();
+        let queues = &self.queues;
+        self.rotation.retain(|id| queues.contains_key(id));
+        if self.rotation.front().copied() != front {
+            self.turn_left = 0;
+        }
+    }
+}
+
+impl<T> Default for LaneScheduler<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(scheduler: &mut LaneScheduler<&'static str>) -> Vec<u16> {
+        std::iter::from_fn(|| scheduler.pop().map(|(lane, _)| lane)).collect()
+    }
+
+    #[test]
+    fn bulk_lane_cannot_starve_others() {
+        let mut scheduler = LaneScheduler::new();
+        for _ in 0..6 {
+            scheduler.push(3, 0, "chunk");
+        }
+        for _ in 0..4 {
+            scheduler.push(5, DEFAULT_PRIORITY, "event");
+        }
+        scheduler.push(0, 0, "ping");
+
+        assert_eq!(scheduler.len(), 11);
+        assert_eq!(drain(&mut scheduler), [0, 3, 5, 5, 5, 5, 3, 3, 3, 3, 3]);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn equal_priorities_alternate() {
+        let mut scheduler = LaneScheduler::new();
+        for _ in 0..2 {
+            scheduler.push(1, 0, "a");
+            scheduler.push(2, 0, "b");
+        }
+        assert_eq!(drain(&mut scheduler), [1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn priority_header_is_checked() {
+        let mut frame = Frame::new("LANE-OPEN");
+        assert_eq!(priority_header(&frame).unwrap(), None);
+        frame.set_header("Priority", "7");
+        assert_eq!(priority_header(&frame).unwrap(), Some(7));
+        frame.set_header("Priority", "8");
+        assert!(priority_header(&frame).is_err());
+    }
+
+    #[test]
+    fn retain_and_clear_drop_items() {
+        let mut scheduler = LaneScheduler::new();
+        scheduler.push(1, 0, "keep");
+        scheduler.push(1, 0, "drop");
+        scheduler.push(2, 0, "other");
+        scheduler.retain(|item| *item != "drop");
+        assert_eq!(scheduler.queued(1), 1);
+        assert_eq!(scheduler.clear_lane(2), 1);
+        assert_eq!(scheduler.pop(), Some((1, "keep")));
+        assert_eq!(scheduler.pop(), None);
+    }
+}
@@ -1,11 +1,41 @@
-//! Transaction ID generator.
+//! Transaction IDs and request/response correlation.
 //!
 //! Transactions correlate request/response pairs on a lane.  The
 //! [`TxnCounter`] produces monotonically increasing IDs of the form
 //! `T-<n>`.  It is safe to share across threads via its atomic
 //! implementation.
+//!
+//! [`TxnTracker`] sits on top: it stamps outbound requests with a
+//! fresh `Txn` and hands back a [`PendingTxn`] that resolves when the
+//! frame answering it arrives, times out, or is canceled.
+//!
+//! ```text
+//! FETCH /0/readme        200 CONTENT
+//! Txn: T-7        ──►    Txn: T-7        ──► tracker.resolve(frame)
+//! ```
+//!
+//! A requester that stops waiting — its timeout passed, or the answer
+//! is no longer wanted — calls [`PendingTxn::cancel`].  That returns
+//! the lane credit the request consumed and yields a `CANCEL` frame
+//! telling the peer to stop producing the response, including any
+//! `CHUNK` frames it has yet to send:
+//!
+//! ```text
+//! CANCEL T-7
+//! Lane: 3
+//! End:
+//! ```
 
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use super::error::ProtocolError;
+use super::frame::Frame;
+use super::lane_manager::LaneManager;
 
 /// Atomic transaction ID counter.
 ///
@@ -41,6 +71,200 @@ impl Default for TxnCounter {
     }
 }
 
+/// Outstanding transactions, keyed by ID.
+type PendingMap = Arc<Mutex<HashMap<String, oneshot::Sender<Frame>>>>;
+
+/// Matches inbound responses to outbound requests by their `Txn`
+/// header.
+///
+/// A frame whose `Txn` is not pending (an unsolicited frame, or a
+/// late answer to a transaction that already timed out) is left for
+/// the caller to handle.
+#[derive(Default)]
+pub struct TxnTracker {
+    counter: TxnCounter,
+    pending: PendingMap,
+}
+
+impl TxnTracker {
+    /// Create a tracker with no outstanding transactions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stamp `request` with a fresh `Txn` header and start waiting
+    /// for its response on the request's lane.
+    pub fn register(&self, request: &mut Frame) -> PendingTxn {
+        let id = self.counter.next();
+        request.set_header("Txn", id.as_str());
+        let lane = request
+            .header("Lane")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(0);
+        self.register_id(id).on_lane(lane)
+    }
+
+    /// Start waiting for a response to transaction `id`.
+    ///
+    /// Registering an ID that is already pending cancels the earlier
+    /// wait.
+    pub fn register_id(&self, id: impl Into<String>) -> PendingTxn {
+        let id = id.into();
+        let (tx, rx) = oneshot::channel();
+        lock(&self.pending).insert(id.clone(), tx);
+        PendingTxn {
+            id,
+            lane: 0,
+            rx,
+            done: false,
+            timeout: None,
+            pending: Arc::clone(&self.pending),
+        }
+    }
+
+    /// Deliver `frame` to the transaction named by its `Txn` header.
+    ///
+    /// Returns the frame back if no such transaction is pending.
+    pub fn resolve(&self, frame: Frame) -> Result<(), Frame> {
+        let Some(tx) = frame
+            .header("Txn")
+            .and_then(|id| lock(&self.pending).remove(id))
+        else {
+            return Err(frame);
+        };
+        // The waiter may have been dropped in the meantime; the answer
+        // is simply discarded then.
+        let _ = tx.send(frame);
+        Ok(())
+    }
+
+    /// Abandon transaction `id`; its waiter fails with
+    /// [`ProtocolError::Canceled`].  Returns whether it was pending.
+    pub fn cancel(&self, id: &str) -> bool {
+        lock(&self.pending).remove(id).is_some()
+    }
+
+    /// Abandon every outstanding transaction, e.g. when the tunnel
+    /// closes.
+    pub fn cancel_all(&self) {
+        lock(&self.pending).clear();
+    }
+
+    /// Whether transaction `id` is still waiting for its response.
+    pub fn is_pending(&self, id: &str) -> bool {
+        lock(&self.pending).contains_key(id)
+    }
+
+    /// Number of outstanding transactions.
+    pub fn pending(&self) -> usize {
+        lock(&self.pending).len()
+    }
+}
+
+fn lock(
+    pending: &PendingMap,
+) -> std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<Frame>>> {
+    pending.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A registered transaction awaiting its response.
+///
+/// Dropping it withdraws the transaction from its tracker.
+#[derive(Debug)]
+pub struct PendingTxn {
+    id: String,
+    lane: u16,
+    rx: oneshot::Receiver<Frame>,
+    /// Whether `rx` has yielded its answer (or been canceled).
+    done: bool,
+    timeout: Option<Duration>,
+    pending: PendingMap,
+}
+
+impl PendingTxn {
+    /// The transaction ID.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The lane the request was sent on.
+    pub fn lane(&self) -> u16 {
+        self.lane
+    }
+
+    /// Record the lane the request was sent on (lane 0 by default).
+    pub fn on_lane(mut self, lane: u16) -> Self {
+        self.lane = lane;
+        self
+    }
+
+    /// Give up waiting after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Wait for the response.
+    ///
+    /// Fails with [`ProtocolError::Timeout`] once the timeout passes,
+    /// or [`ProtocolError::Canceled`] if the transaction is canceled.
+    /// A timed-out transaction stays registered, so it can be waited
+    /// on again or abandoned with [`PendingTxn::cancel`].
+    pub async fn wait(&mut self) -> Result<Frame, ProtocolError> {
+        if self.done {
+            return Err(ProtocolError::Canceled(format!(
+                "transaction {} already finished",
+                self.id
+            )));
+        }
+        let received = match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, &mut self.rx).await {
+                Ok(received) => received,
+                Err(_) => {
+                    return Err(ProtocolError::Timeout(format!(
+                        "no response to {} within {:?}",
+                        self.id, timeout
+                    )))
+                }
+            },
+            None => (&mut self.rx).await,
+        };
+        self.done = true;
+        received.map_err(|_| ProtocolError::Canceled(format!("transaction {} canceled", self.id)))
+    }
+
+    /// Abandon the transaction.
+    ///
+    /// Withdraws it from the tracker and gives back the credit its
+    /// request consumed on `lanes`.  Returns the `CANCEL` to send the
+    /// peer, followed by any frames the returned credit released.
+    pub async fn cancel(self, lanes: &LaneManager) -> Vec<Frame> {
+        let mut frames = vec![cancel_frame(&self.id, self.lane)];
+        let released = lanes.add_credit(self.lane, 1).await;
+        frames.extend(released.iter().filter_map(|data| Frame::parse(data).ok()));
+        frames
+    }
+}
+
+/// The `CANCEL` asking the peer to stop work on transaction `txn`.
+pub fn cancel_frame(txn: &str, lane: u16) -> Frame {
+    let mut frame = Frame::with_args("CANCEL", vec![txn.to_string()]);
+    frame.set_header("Lane", lane.to_string());
+    frame
+}
+
+impl Drop for PendingTxn {
+    fn drop(&mut self) {
+        self.rx.close();
+        let mut pending = lock(&self.pending);
+        // Only withdraw our own registration, not a newer one that
+        // reused the ID.
+        if pending.get(&self.id).is_some_and(|tx| tx.is_closed()) {
+            pending.remove(&self.id);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -71,6 +295,77 @@ mod tests {
         assert_eq!(txn.peek(), 2);
     }
 
+    #[tokio::test]
+    async fn responses_resolve_by_txn() {
+        let tracker = TxnTracker::new();
+        let mut request = Frame::with_args("FETCH", vec!["/0/readme".into()]);
+        let mut pending = tracker.register(&mut request);
+        assert_eq!(request.header("Txn"), Some(pending.id()));
+        assert!(tracker.is_pending(pending.id()));
+
+        let mut response = Frame::new("200 CONTENT");
+        response.set_header("Txn", pending.id());
+        assert!(tracker.resolve(response.clone()).is_ok());
+        assert_eq!(pending.wait().await.unwrap(), response);
+        assert_eq!(tracker.pending(), 0);
+
+        // Nothing is waiting for a second copy.
+        assert!(tracker.resolve(response).is_err());
+        assert!(tracker.resolve(Frame::new("EVENT")).is_err());
+    }
+
+    #[tokio::test]
+    async fn timeouts_and_cancellation() {
+        let tracker = TxnTracker::new();
+        let mut slow = tracker
+            .register_id("T-slow")
+            .with_timeout(Duration::from_millis(10));
+        assert!(matches!(slow.wait().await, Err(ProtocolError::Timeout(_))));
+        assert!(tracker.is_pending("T-slow"));
+        drop(slow);
+        assert!(!tracker.is_pending("T-slow"));
+
+        let mut abandoned = tracker.register_id("T-gone");
+        assert!(tracker.cancel("T-gone"));
+        assert!(!tracker.cancel("T-gone"));
+        assert!(matches!(
+            abandoned.wait().await,
+            Err(ProtocolError::Canceled(_))
+        ));
+
+        let first = tracker.register_id("T-1");
+        let second = tracker.register_id("T-1");
+        drop(first);
+        assert!(tracker.is_pending("T-1"));
+        drop(second);
+        assert_eq!(tracker.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancel_returns_credit_and_notifies_peer() {
+        let tracker = TxnTracker::new();
+        let lanes = LaneManager::new();
+        let mut fetch = Frame::with_args("FETCH", vec!["/0/big".into()]);
+        fetch.set_header("Lane", "3");
+        let data = fetch.serialize();
+        assert!(lanes.send_or_queue(3, data).await.is_some());
+        let credits = lanes.with_lane(3, |lane| lane.credits()).await;
+
+        let mut pending = tracker
+            .register(&mut fetch)
+            .with_timeout(Duration::from_millis(10));
+        assert_eq!(pending.lane(), 3);
+        assert!(pending.wait().await.is_err());
+
+        let txn = pending.id().to_string();
+        let frames = pending.cancel(&lanes).await;
+        assert_eq!(frames, vec![cancel_frame(&txn, 3)]);
+        assert_eq!(frames[0].args, vec![txn.clone()]);
+        assert_eq!(frames[0].header("Lane"), Some("3"));
+        assert_eq!(lanes.with_lane(3, |lane| lane.credits()).await, credits + 1);
+        assert!(!tracker.is_pending(&txn));
+    }
+
     #[test]
     fn unique_across_threads() {
         use std::collections::HashSet;
@@ -0,0 +1,377 @@
+//! Security audit log.
+//!
+//! A burrow records the auth and trust decisions it makes — handshakes,
+//! failed authentications, capability grants and revocations, key
+//! fingerprint mismatches and manifest verifications — in an
+//! append-only [`AuditLog`].  The log is kept by the continuity engine
+//! as the topic `/t/audit`, one event per record with the body
+//!
+//! ```text
+//! <hash>\t<timestamp>\t<kind>\t<peer>\t<detail>
+//! ```
+//!
+//! Each record's hash is the SHA-256 hex of the previous record's hash
+//! (64 zeros for the first) followed by
+//! `\n<seq>\t<timestamp>\t<kind>\t<peer>\t<detail>`, so editing or
+//! removing a record breaks the chain after it; see
+//! [`AuditLog::verify`].
+//!
+//! Peers holding `ManageBurrows` can read the log with `LIST /t/audit`,
+//! narrowed by optional `Kind`, `Peer`, `Since` (sequence number) and
+//! `Limit` headers.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::events::continuity::ContinuityStore;
+use crate::events::engine::Event;
+use crate::events::store::append_in_place;
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+use crate::util::now_unix;
+
+use super::auth::hex_encode;
+
+/// The continuity topic, and the selector, of the audit log.
+pub const AUDIT_TOPIC: &str = "/t/audit";
+
+/// The hash the first record chains from.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// What an audit record is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AuditKind {
+    /// A peer completed the handshake.
+    Handshake,
+    /// A handshake was rejected or timed out.
+    AuthFailure,
+    /// A capability was granted.
+    Grant,
+    /// Capabilities or sessions were revoked.
+    Revoke,
+    /// A known peer presented a different key.
+    Mismatch,
+    /// A trust manifest was verified, or failed to.
+    Manifest,
+}
+
+impl AuditKind {
+    /// Every kind.
+    pub const ALL: [AuditKind; 6] = [
+        Self::Handshake,
+        Self::AuthFailure,
+        Self::Grant,
+        Self::Revoke,
+        Self::Mismatch,
+        Self::Manifest,
+    ];
+
+    /// The kind's label in the log.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Handshake => "handshake",
+            Self::AuthFailure => "auth-failure",
+            Self::Grant => "grant",
+            Self::Revoke => "revoke",
+            Self::Mismatch => "mismatch",
+            Self::Manifest => "manifest",
+        }
+    }
+
+    /// Parse a kind from its label.
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.label() == label)
+    }
+}
+
+/// One entry of the audit log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// Position in the log, from 1.
+    pub seq: u64,
+    /// When the decision was made (seconds since the epoch).
+    pub timestamp: u64,
+    /// What the record is about.
+    pub kind: AuditKind,
+    /// The peer concerned, or `-` if it is unknown.
+    pub peer: String,
+    /// What was decided, on one line.
+    pub detail: String,
+    /// SHA-256 hex chaining this record to the previous one.
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// The hash of a record with these fields following `prev_hash`.
+    fn chain_hash(&self, prev_hash: &str) -> String {
+        let line = format!(
+            "{}\n{}\t{}\t{}\t{}\t{}",
+            prev_hash,
+            self.seq,
+            self.timestamp,
+            self.kind.label(),
+            self.peer,
+            self.detail
+        );
+        hex_encode(&Sha256::digest(line.as_bytes()))
+    }
+
+    /// The record as a continuity event.
+    fn to_event(&self) -> Event {
+        Event {
+            seq: self.seq,
+            body: format!(
+                "{}\t{}\t{}\t{}\t{}",
+                self.hash,
+                self.timestamp,
+                self.kind.label(),
+                self.peer,
+                self.detail
+            ),
+        }
+    }
+
+    /// Read a record from a continuity event.
+    fn from_event(event: &Event) -> Result<Self, ProtocolError> {
+        let invalid =
+            || ProtocolError::InternalError(format!("invalid audit record {}", event.seq));
+        let mut fields = event.body.splitn(5, '\t');
+        let mut field = || fields.next().ok_or_else(invalid);
+        let hash = field()?.to_string();
+        let timestamp = field()?.parse().map_err(|_| invalid())?;
+        let kind = AuditKind::from_label(field()?).ok_or_else(invalid)?;
+        let peer = field()?.to_string();
+        let detail = field()?.to_string();
+        Ok(Self {
+            seq: event.seq,
+            timestamp,
+            kind,
+            peer,
+            detail,
+            hash,
+        })
+    }
+
+    /// The record as one line of a `LIST /t/audit` menu.
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {} {}",
+            self.seq,
+            self.timestamp,
+            self.kind.label(),
+            self.peer,
+            self.detail
+        )
+    }
+}
+
+/// Which records [`AuditLog::query`] returns.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditQuery {
+    /// Only records of this kind.
+    pub kind: Option<AuditKind>,
+    /// Only records about this peer.
+    pub peer: Option<String>,
+    /// Only records after this sequence number.
+    pub since: Option<u64>,
+    /// At most this many records, the most recent.
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    /// Read a query from the `Kind`, `Peer`, `Since` and `Limit`
+    /// headers of a `LIST /t/audit` request.
+    pub fn from_frame(frame: &Frame) -> Result<Self, ProtocolError> {
+        let number = |name: &str| {
+            frame
+                .header(name)
+                .map(|v| {
+                    v.parse::<u64>()
+                        .map_err(|_| ProtocolError::BadRequest(format!("invalid {}", name)))
+                })
+                .transpose()
+        };
+        let kind = frame
+            .header("Kind")
+            .map(|k| {
+                AuditKind::from_label(k)
+                    .ok_or_else(|| ProtocolError::BadRequest(format!("unknown audit kind {:?}", k)))
+            })
+            .transpose()?;
+        Ok(Self {
+            kind,
+            peer: frame.header("Peer").map(str::to_string),
+            since: number("Since")?,
+            limit: number("Limit")?.map(|n| n as usize),
+        })
+    }
+
+    /// Whether `record` matches every filter but the limit.
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.kind.is_none_or(|k| k == record.kind)
+            && self.peer.as_ref().is_none_or(|p| *p == record.peer)
+            && self.since.is_none_or(|s| record.seq > s)
+    }
+}
+
+/// The append-only, hash-chained record of a burrow's auth and trust
+/// decisions.
+#[derive(Default)]
+pub struct AuditLog {
+    /// Where records are persisted, if anywhere.
+    store: Option<ContinuityStore>,
+    /// Every record, oldest first.
+    records: Mutex<Vec<AuditRecord>>,
+}
+
+impl AuditLog {
+    /// Create an audit log kept only in memory.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Open the audit log persisted under `dir`, creating it if needed.
+    /// A log whose chain no longer verifies is still opened, with a
+    /// warning.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, ProtocolError> {
+        let store = ContinuityStore::new(dir)?;
+        let records = store
+            .load(AUDIT_TOPIC)?
+            .iter()
+            .map(AuditRecord::from_event)
+            .collect::<Result<Vec<_>, _>>()?;
+        let log = Self {
+            store: Some(store),
+            records: Mutex::new(records),
+        };
+        if let Err(e) = log.verify() {
+            warn!(err = %e, "audit log chain is broken");
+        }
+        Ok(log)
+    }
+
+    /// Write and sync every record queued for the log.
+    pub fn flush(&self) -> Result<(), ProtocolError> {
+        match &self.store {
+            Some(store) => store.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Append a record about `peer`.  Tabs and newlines in `detail` are
+    /// replaced with spaces.  A record that cannot be persisted is kept
+    /// in memory with a warning.
+    pub fn record(&self, kind: AuditKind, peer: &str, detail: &str) -> AuditRecord {
+        let clean = |s: &str| s.replace(['\t', '\n', '\r'], " ");
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        let prev_hash = records.last().map_or(GENESIS_HASH, |r| r.hash.as_str());
+        let mut record = AuditRecord {
+            seq: records.last().map_or(1, |r| r.seq + 1),
+            timestamp: now_unix(),
+            kind,
+            peer: clean(peer),
+            detail: clean(detail),
+            hash: String::new(),
+        };
+        record.hash = record.chain_hash(prev_hash);
+        if let Some(store) = &self.store {
+            let appended = append_in_place(|| store.append(AUDIT_TOPIC, &record.to_event()));
+            if let Err(e) = appended {
+                warn!(err = %e, "failed to persist audit record");
+            }
+        }
+        records.push(record.clone());
+        record
+    }
+
+    /// Every record, oldest first.
+    pub fn records(&self) -> Vec<AuditRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// The records matching `query`, oldest first.
+    pub fn query(&self, query: &AuditQuery) -> Vec<AuditRecord> {
+        let mut matched: Vec<AuditRecord> = self
+            .records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|r| query.matches(r))
+            .cloned()
+            .collect();
+        if let Some(limit) = query.limit {
+            matched.drain(..matched.len().saturating_sub(limit));
+        }
+        matched
+    }
+
+    /// Check that every record's hash chains from the one before.
+    /// Fails with `Forbidden` naming the first record that does not.
+    pub fn verify(&self) -> Result<(), ProtocolError> {
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        let mut prev_hash = GENESIS_HASH;
+        for record in records.iter() {
+            if record.chain_hash(prev_hash) != record.hash {
+                return Err(ProtocolError::Forbidden(format!(
+                    "audit record {} does not match the chain",
+                    record.seq
+                )));
+            }
+            prev_hash = &record.hash;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_chain_and_survive_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::open(dir.path()).unwrap();
+        log.record(AuditKind::Handshake, "ed25519:A", "authenticated");
+        log.record(AuditKind::Grant, "ed25519:A", "Fetch for 3600s");
+        log.record(AuditKind::AuthFailure, "-", "bad\tproof\n");
+        log.verify().unwrap();
+        let written = log.records();
+        drop(log);
+
+        let reopened = AuditLog::open(dir.path()).unwrap();
+        assert_eq!(reopened.records(), written);
+        let next = reopened.record(AuditKind::Revoke, "ed25519:A", "all capabilities");
+        assert_eq!(next.seq, 4);
+        reopened.verify().unwrap();
+        assert_eq!(reopened.records()[2].detail, "bad proof ");
+
+        let query = AuditQuery {
+            peer: Some("ed25519:A".into()),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let seqs: Vec<u64> = reopened.query(&query).iter().map(|r| r.seq).collect();
+        assert_eq!(seqs, [2, 4]);
+        let query = AuditQuery {
+            kind: Some(AuditKind::Handshake),
+            since: Some(1),
+            ..Default::default()
+        };
+        assert!(reopened.query(&query).is_empty());
+    }
+
+    #[test]
+    fn tampering_breaks_the_chain() {
+        let log = AuditLog::in_memory();
+        log.record(AuditKind::Handshake, "ed25519:A", "authenticated");
+        log.record(AuditKind::Mismatch, "ed25519:A", "key changed");
+        log.records.lock().unwrap()[0].detail = "anonymous".into();
+        assert!(matches!(log.verify(), Err(ProtocolError::Forbidden(_))));
+    }
+}
@@ -17,17 +17,48 @@
 //!   End:
 //!                              ←    200 HELLO
 //!                                   Burrow-ID: ed25519:YYYY
-//!                                   Session-Token: <hex>
+//!                                   Session-Token: <token>
+//!                                   Refresh-Token: <token>
 //!                                   Caps: lanes,async
 //!                                   End:
 //! ```
 //!
 //! Anonymous connections skip the CHALLENGE/AUTH exchange: the server
-//! responds with `200 HELLO` and `Burrow-ID: anonymous` directly.
+//! responds with `200 HELLO` and `Burrow-ID: anonymous` directly, with
+//! no refresh token.
+//!
+//! Session and refresh tokens are signed by the server's identity (see
+//! [`crate::security::token`]).
+//!
+//! Verbs with lasting effects, such as `DELEGATE`, carry a `Nonce` and
+//! a `Timestamp` (see [`stamp_frame`]); a [`ReplayCache`] refuses one
+//! whose timestamp is outside the clock-skew window or whose nonce it
+//! has already seen, so a captured frame cannot be played again.
+//!
+//! When the peer presented a TLS certificate carrying a Rabbit ID (see
+//! [`crate::security::identity_cert`]), the HELLO's `Burrow-ID` must be
+//! that ID.
 
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::error::RabbitError;
 use crate::protocol::error::ProtocolError;
 use crate::protocol::frame::Frame;
 use crate::security::identity::{parse_burrow_id, Identity};
+use crate::security::identity_cert::extract_rabbit_id_from_cert;
+use crate::security::permissions::Capability;
+use crate::security::token::TokenKind;
+use crate::util::now_unix;
+
+/// Default lifetime of a session token, in seconds.
+pub const DEFAULT_SESSION_TTL_SECS: u64 = 86_400;
+
+/// Default lifetime of a refresh token, in seconds.
+pub const DEFAULT_REFRESH_TTL_SECS: u64 = 604_800;
+
+/// Default clock-skew window for stamped frames, in seconds.
+pub const DEFAULT_REPLAY_WINDOW_SECS: u64 = 300;
 
 /// The server-side handshake state machine.
 #[derive(Debug)]
@@ -71,6 +102,15 @@ pub struct Authenticator {
     require_auth: bool,
     /// Current handshake state.
     state: HandshakeState,
+    /// The burrow ID bound to the peer's TLS certificate, if any.
+    cert_id: Option<String>,
+    /// Lifetimes of issued session and refresh tokens, in seconds.
+    token_ttls: (u64, u64),
+    /// Capabilities named in tokens for authenticated and anonymous
+    /// peers.
+    capabilities: (Vec<Capability>, Vec<Capability>),
+    /// Refresh token issued at the end of an authenticated handshake.
+    refresh_token: Option<String>,
 }
 
 impl Authenticator {
@@ -80,9 +120,45 @@ impl Authenticator {
             identity,
             require_auth,
             state: HandshakeState::AwaitingHello,
+            cert_id: None,
+            token_ttls: (DEFAULT_SESSION_TTL_SECS, DEFAULT_REFRESH_TTL_SECS),
+            capabilities: (Vec::new(), Vec::new()),
+            refresh_token: None,
         }
     }
 
+    /// Set the lifetimes of the session and refresh tokens issued.
+    pub fn with_token_lifetimes(mut self, session_secs: u64, refresh_secs: u64) -> Self {
+        self.token_ttls = (session_secs, refresh_secs);
+        self
+    }
+
+    /// Set the capabilities that tokens for authenticated and for
+    /// anonymous peers name.
+    pub fn with_capabilities(
+        mut self,
+        authenticated: &[Capability],
+        anonymous: &[Capability],
+    ) -> Self {
+        self.capabilities = (authenticated.to_vec(), anonymous.to_vec());
+        self
+    }
+
+    /// Take note of the DER certificate the peer presented at the TLS
+    /// layer, before its HELLO.
+    ///
+    /// A certificate bound to a burrow ID pins the `Burrow-ID` the HELLO
+    /// may claim.  Fails if the certificate claims an ID it cannot prove.
+    pub fn set_peer_certificate(&mut self, cert_der: &[u8]) -> Result<(), RabbitError> {
+        self.cert_id = extract_rabbit_id_from_cert(cert_der)?;
+        Ok(())
+    }
+
+    /// The burrow ID bound to the peer's TLS certificate, if it had one.
+    pub fn peer_certificate_id(&self) -> Option<&str> {
+        self.cert_id.as_deref()
+    }
+
     /// Return a reference to the current state.
     pub fn state(&self) -> &HandshakeState {
         &self.state
@@ -97,13 +173,12 @@ impl Authenticator {
     ///
     /// If `require_auth` is false, responds with `200 HELLO` immediately
     /// (anonymous path).  Otherwise, responds with `300 CHALLENGE`.
-    pub fn handle_hello(&mut self, hello: &Frame) -> Result<Frame, ProtocolError> {
+    pub fn handle_hello(&mut self, hello: &Frame) -> Result<Frame, RabbitError> {
         // Validate it's a HELLO
         if hello.verb != "HELLO" {
-            return Err(ProtocolError::BadHello(format!(
-                "expected HELLO, got {}",
-                hello.verb
-            )));
+            return Err(
+                ProtocolError::BadHello(format!("expected HELLO, got {}", hello.verb)).into(),
+            );
         }
 
         // Check protocol version
@@ -112,13 +187,19 @@ impl Authenticator {
                 return Err(ProtocolError::BadHello(format!(
                     "unsupported protocol version: {}",
                     version
-                )));
+                ))
+                .into());
             }
         }
 
         if !self.require_auth {
             // Anonymous path: skip challenge
-            let token = generate_session_token();
+            let token = self.identity.issue_token(
+                TokenKind::Access,
+                "anonymous",
+                &self.capabilities.1,
+                self.token_ttls.0,
+            );
             let mut response = Frame::new("200 HELLO");
             response.set_header("Burrow-ID", "anonymous");
             response.set_header("Session-Token", &token);
@@ -138,6 +219,17 @@ impl Authenticator {
         // Parse the public key from burrow ID
         let peer_pubkey = parse_burrow_id(&peer_id)?;
 
+        // The TLS certificate, if bound to an ID, must agree
+        if let Some(cert_id) = &self.cert_id {
+            if *cert_id != peer_id {
+                return Err(ProtocolError::Forbidden(format!(
+                    "Burrow-ID {} does not match TLS certificate ({})",
+                    peer_id, cert_id
+                ))
+                .into());
+            }
+        }
+
         // Generate nonce
         let nonce = generate_nonce();
         let nonce_hex = hex_encode(&nonce);
@@ -158,7 +250,7 @@ impl Authenticator {
     ///
     /// Verifies the peer's signature over the nonce.  On success,
     /// transitions to `Authenticated` and returns `200 HELLO`.
-    pub fn handle_auth(&mut self, auth_frame: &Frame) -> Result<Frame, ProtocolError> {
+    pub fn handle_auth(&mut self, auth_frame: &Frame) -> Result<Frame, RabbitError> {
         // Extract challenge state
         let (nonce, peer_id, peer_pubkey) = match &self.state {
             HandshakeState::ChallengeSent {
@@ -169,16 +261,16 @@ impl Authenticator {
             _ => {
                 return Err(ProtocolError::BadHello(
                     "AUTH received but no challenge was sent".into(),
-                ));
+                )
+                .into());
             }
         };
 
         // Validate it's an AUTH frame
         if auth_frame.verb != "AUTH" {
-            return Err(ProtocolError::BadHello(format!(
-                "expected AUTH, got {}",
-                auth_frame.verb
-            )));
+            return Err(
+                ProtocolError::BadHello(format!("expected AUTH, got {}", auth_frame.verb)).into(),
+            );
         }
 
         // Extract proof
@@ -195,14 +287,24 @@ impl Authenticator {
             .map_err(|e| ProtocolError::BadHello(format!("invalid hex in Proof: {}", e)))?;
 
         // Verify signature over the nonce
-        Identity::verify(&peer_pubkey, &nonce, &sig_bytes)?;
-
-        // Success — issue session token
-        let token = generate_session_token();
+        Identity::verify(&peer_pubkey, &nonce, &sig_bytes)
+            .map_err(|e| RabbitError::Auth(format!("{} failed: {}", peer_id, e.detail())))?;
+
+        // Success — issue session and refresh tokens
+        let caps = &self.capabilities.0;
+        let (session_ttl, refresh_ttl) = self.token_ttls;
+        let token = self
+            .identity
+            .issue_token(TokenKind::Access, &peer_id, caps, session_ttl);
+        let refresh = self
+            .identity
+            .issue_token(TokenKind::Refresh, &peer_id, caps, refresh_ttl);
         let mut response = Frame::new("200 HELLO");
         response.set_header("Burrow-ID", self.identity.burrow_id());
         response.set_header("Session-Token", &token);
+        response.set_header("Refresh-Token", &refresh);
         response.set_header("Caps", "lanes,async");
+        self.refresh_token = Some(refresh);
 
         self.state = HandshakeState::Authenticated {
             session_token: token,
@@ -230,6 +332,12 @@ impl Authenticator {
         }
     }
 
+    /// Return the refresh token, if an authenticated handshake
+    /// completed.
+    pub fn refresh_token(&self) -> Option<&str> {
+        self.refresh_token.as_deref()
+    }
+
     /// Return the authenticated peer ID, if available.
     pub fn peer_id(&self) -> Option<&str> {
         match &self.state {
@@ -249,6 +357,90 @@ impl Authenticator {
     }
 }
 
+// ── Replay protection ──────────────────────────────────────────
+
+/// The nonces of recently accepted stamped frames.
+///
+/// A frame is accepted once, and only while its `Timestamp` is within
+/// `window_secs` of the local clock either way.  Nonces are forgotten
+/// once their timestamp falls out of the window, when a replay would
+/// be refused for its age anyway.
+#[derive(Debug)]
+pub struct ReplayCache {
+    /// Allowed clock skew, in seconds.
+    window_secs: u64,
+    /// Nonce → timestamp of each accepted frame.
+    seen: Mutex<HashMap<String, u64>>,
+}
+
+impl ReplayCache {
+    /// Create a cache accepting timestamps within `window_secs`.
+    pub fn new(window_secs: u64) -> Self {
+        Self {
+            window_secs,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check a stamped frame and remember its nonce.
+    ///
+    /// Fails with `BadRequest` if the `Nonce` or `Timestamp` header is
+    /// missing or malformed, and `Forbidden` if the timestamp is out of
+    /// the window or the nonce was seen before.
+    pub fn check(&self, frame: &Frame) -> Result<(), ProtocolError> {
+        self.check_at(frame, now_unix())
+    }
+
+    fn check_at(&self, frame: &Frame, now: u64) -> Result<(), ProtocolError> {
+        let nonce = frame
+            .header("Nonce")
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| {
+                ProtocolError::BadRequest(format!("{} requires a Nonce header", frame.verb))
+            })?;
+        let timestamp: u64 = frame
+            .header("Timestamp")
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| {
+                ProtocolError::BadRequest(format!("{} requires a Timestamp header", frame.verb))
+            })?;
+        if timestamp.abs_diff(now) > self.window_secs {
+            return Err(ProtocolError::Forbidden(format!(
+                "timestamp {} is outside the {}s window",
+                timestamp, self.window_secs
+            )));
+        }
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let oldest = now.saturating_sub(self.window_secs);
+        seen.retain(|_, t| *t >= oldest);
+        if seen.contains_key(nonce) {
+            return Err(ProtocolError::Forbidden(format!(
+                "replayed {} (nonce {})",
+                frame.verb, nonce
+            )));
+        }
+        seen.insert(nonce.to_string(), timestamp);
+        Ok(())
+    }
+
+    /// Number of nonces remembered.
+    pub fn len(&self) -> usize {
+        self.seen.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Whether no nonces are remembered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for ReplayCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPLAY_WINDOW_SECS)
+    }
+}
+
 // ── Client-side helpers ────────────────────────────────────────
 
 /// Build a client HELLO frame.
@@ -278,31 +470,30 @@ pub fn build_auth_proof(identity: &Identity, challenge: &Frame) -> Result<Frame,
     Ok(frame)
 }
 
+/// Give a frame a fresh `Nonce` and the current `Timestamp`, for a
+/// [`ReplayCache`] at the receiver.
+pub fn stamp_frame(frame: &mut Frame) {
+    frame.set_header("Nonce", hex_encode(&generate_nonce()[..16]));
+    frame.set_header("Timestamp", now_unix().to_string());
+}
+
 // ── Utility functions (no external deps for hex) ───────────────
 
 /// Generate 32 random bytes as a nonce.
-fn generate_nonce() -> Vec<u8> {
+pub(crate) fn generate_nonce() -> Vec<u8> {
     use rand::RngCore;
     let mut buf = [0u8; 32];
     rand::thread_rng().fill_bytes(&mut buf);
     buf.to_vec()
 }
 
-/// Generate a random session token (32 bytes, hex-encoded → 64 chars).
-fn generate_session_token() -> String {
-    use rand::RngCore;
-    let mut buf = [0u8; 32];
-    rand::thread_rng().fill_bytes(&mut buf);
-    hex_encode(&buf)
-}
-
 /// Hex-encode bytes to a lowercase hex string.
-fn hex_encode(bytes: &[u8]) -> String {
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
     bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
 /// Decode a hex string to bytes.
-fn hex_decode(hex: &str) -> Result<Vec<u8>, String> {
+pub(crate) fn hex_decode(hex: &str) -> Result<Vec<u8>, String> {
     if !hex.len().is_multiple_of(2) {
         return Err("hex string has odd length".into());
     }
@@ -360,6 +551,66 @@ mod tests {
         assert_eq!(auth.peer_id(), Some(client_id.burrow_id().as_str()));
     }
 
+    #[test]
+    fn handshake_issues_signed_tokens() {
+        use crate::security::token::{caps_digest, TokenClaims};
+
+        let server_id = Identity::generate();
+        let server = server_id.burrow_id();
+        let client_id = Identity::generate();
+        let caps = [Capability::Fetch, Capability::Publish];
+        let mut auth = Authenticator::new(server_id, true)
+            .with_token_lifetimes(60, 600)
+            .with_capabilities(&caps, &[]);
+
+        let challenge = auth.handle_hello(&build_hello(&client_id)).unwrap();
+        let response = auth
+            .handle_auth(&build_auth_proof(&client_id, &challenge).unwrap())
+            .unwrap();
+
+        let session = response.header("Session-Token").unwrap();
+        let claims = TokenClaims::verify_from(session, &server, TokenKind::Access).unwrap();
+        assert_eq!(claims.subject, client_id.burrow_id());
+        assert_eq!(claims.caps_digest, caps_digest(&caps));
+        assert_eq!(claims.expires_at - claims.issued_at, 60);
+
+        let refresh = response.header("Refresh-Token").unwrap();
+        assert_eq!(auth.refresh_token(), Some(refresh));
+        let claims = TokenClaims::verify_from(refresh, &server, TokenKind::Refresh).unwrap();
+        assert_eq!(claims.expires_at - claims.issued_at, 600);
+    }
+
+    #[test]
+    fn replay_cache_refuses_repeats_and_stale_frames() {
+        let cache = ReplayCache::new(60);
+        let mut frame = Frame::with_args("DELEGATE", vec!["Fetch".into(), "peer".into()]);
+        assert!(matches!(
+            cache.check(&frame),
+            Err(ProtocolError::BadRequest(_))
+        ));
+
+        stamp_frame(&mut frame);
+        cache.check(&frame).unwrap();
+        assert!(matches!(
+            cache.check(&frame),
+            Err(ProtocolError::Forbidden(_))
+        ));
+
+        let cache = ReplayCache::new(60);
+        frame.set_header("Nonce", "a");
+        frame.set_header("Timestamp", "1000");
+        assert!(matches!(
+            cache.check_at(&frame, 1061),
+            Err(ProtocolError::Forbidden(_))
+        ));
+        cache.check_at(&frame, 1000).unwrap();
+        frame.set_header("Nonce", "b");
+        frame.set_header("Timestamp", "1070");
+        cache.check_at(&frame, 1070).unwrap();
+        // Nonce "a" has aged out of the window and is forgotten.
+        assert_eq!(cache.len(), 1);
+    }
+
     #[test]
     fn bad_signature_rejected() {
         let server_id = Identity::generate();
@@ -374,7 +625,7 @@ mod tests {
         // Sign with wrong key
         let bad_proof = build_auth_proof(&wrong_id, &challenge).unwrap();
         let result = auth.handle_auth(&bad_proof);
-        assert!(result.is_err());
+        assert!(matches!(result, Err(RabbitError::Auth(_))));
     }
 
     #[test]
@@ -409,6 +660,34 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn hello_must_match_certificate_id() {
+        use crate::security::identity_cert::generate_identity_cert;
+
+        let client_id = Identity::generate();
+        let pair = generate_identity_cert(&client_id, "client").unwrap();
+        let cert = rustls_pemfile::certs(&mut pair.cert_pem.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let mut auth = Authenticator::new(Identity::generate(), true);
+        auth.set_peer_certificate(&cert).unwrap();
+        assert_eq!(
+            auth.peer_certificate_id(),
+            Some(client_id.burrow_id().as_str())
+        );
+        auth.handle_hello(&build_hello(&client_id)).unwrap();
+
+        let mut auth = Authenticator::new(Identity::generate(), true);
+        auth.set_peer_certificate(&cert).unwrap();
+        let result = auth.handle_hello(&build_hello(&Identity::generate()));
+        assert!(matches!(
+            result,
+            Err(RabbitError::Protocol(ProtocolError::Forbidden(_)))
+        ));
+    }
+
     #[test]
     fn session_token_not_available_before_auth() {
         let server_id = Identity::generate();
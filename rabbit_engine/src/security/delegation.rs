@@ -0,0 +1,427 @@
+//! Signed delegation tokens with attenuation chains.
+//!
+//! A burrow holding `ManageWarren` somewhere can hand a subset of its
+//! capabilities there to another burrow without that burrow's grants
+//! being touched: it signs a [`DelegationToken`] naming the holder, the
+//! capabilities, an expiry and a selector prefix.  The holder may pass
+//! it on, attenuated, by signing a further link; each link can only
+//! narrow what the one before it allowed.  Like a macaroon, every link
+//! signs the previous link's signature, so links cannot be spliced out
+//! or reordered.
+//!
+//! Each link is a line of tab-separated fields:
+//!
+//! ```text
+//! <issuer>  <holder>  Fetch,List  <expires-at>  /docs  <hex(signature)>
+//! ```
+//!
+//! and a token travels in a `Delegation` header as the base64url (no
+//! padding) of its links, one per line.  The burrow the token is used
+//! at checks it with [`DelegationToken::require`].
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+
+use crate::protocol::error::ProtocolError;
+use crate::util::now_unix;
+
+use super::auth::{hex_decode, hex_encode};
+use super::identity::{parse_burrow_id, Identity};
+use super::permissions::{Capability, CapabilityManager};
+
+/// One signed step of a delegation chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelegationLink {
+    /// Burrow ID that signed this link.
+    pub issuer: String,
+    /// Burrow ID the capabilities are delegated to.
+    pub holder: String,
+    /// The capabilities delegated.
+    pub capabilities: Vec<Capability>,
+    /// Unix timestamp after which the link is void.
+    pub expires_at: u64,
+    /// Selectors the link covers; empty for all.
+    pub selector_prefix: String,
+    /// The issuer's signature over the link and the previous link's
+    /// signature.
+    pub signature: Vec<u8>,
+}
+
+impl DelegationLink {
+    fn signed_message(&self, previous: &[u8]) -> Vec<u8> {
+        format!(
+            "RABBIT-DELEGATE\n{}\n{}\n{}\n{}\n{}\n{}",
+            hex_encode(previous),
+            self.issuer,
+            self.holder,
+            labels(&self.capabilities),
+            self.expires_at,
+            self.selector_prefix
+        )
+        .into_bytes()
+    }
+}
+
+/// A chain of delegation links, from the root grant to the current
+/// holder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelegationToken {
+    links: Vec<DelegationLink>,
+}
+
+impl DelegationToken {
+    /// Sign a root delegation of `capabilities` to `holder` for
+    /// `ttl_secs`, limited to selectors under `selector_prefix`.
+    pub fn issue(
+        issuer: &Identity,
+        holder: &str,
+        capabilities: &[Capability],
+        ttl_secs: u64,
+        selector_prefix: &str,
+    ) -> Self {
+        let mut token = Self { links: Vec::new() };
+        token.push(issuer, holder, capabilities, ttl_secs, selector_prefix);
+        token
+    }
+
+    /// Pass the token on to `holder`, signed by the current holder.
+    ///
+    /// The new link may only narrow the token: fewer capabilities, an
+    /// earlier expiry, a longer selector prefix.  Fails with
+    /// `Forbidden` if `current` is not the holder or the link would
+    /// widen the token.
+    pub fn attenuate(
+        &self,
+        current: &Identity,
+        holder: &str,
+        capabilities: &[Capability],
+        ttl_secs: u64,
+        selector_prefix: &str,
+    ) -> Result<Self, ProtocolError> {
+        if current.burrow_id() != self.holder() {
+            return Err(ProtocolError::Forbidden(format!(
+                "delegation is held by {}",
+                self.holder()
+            )));
+        }
+        let mut token = self.clone();
+        token.push(current, holder, capabilities, ttl_secs, selector_prefix);
+        let (previous, link) = (&token.links[token.links.len() - 2], token.last());
+        check_narrower(previous, link)?;
+        Ok(token)
+    }
+
+    /// The links, root first.
+    pub fn links(&self) -> &[DelegationLink] {
+        &self.links
+    }
+
+    /// The burrow the token is currently delegated to.
+    pub fn holder(&self) -> &str {
+        &self.last().holder
+    }
+
+    /// Check every signature, that each link is signed by the holder
+    /// of the one before and only narrows it, and that none has
+    /// expired.
+    ///
+    /// Fails with `Forbidden` for a bad signature or a widening link,
+    /// and `AuthRequired` once any link has expired.
+    pub fn verify(&self) -> Result<(), ProtocolError> {
+        let now = now_unix();
+        let mut previous: Option<&DelegationLink> = None;
+        for link in &self.links {
+            if let Some(previous) = previous {
+                if link.issuer != previous.holder {
+                    return Err(ProtocolError::Forbidden(format!(
+                        "delegation link signed by {}, not the holder {}",
+                        link.issuer, previous.holder
+                    )));
+                }
+                check_narrower(previous, link)?;
+            }
+            let prior = previous.map(|p| p.signature.as_slice()).unwrap_or(&[]);
+            Identity::verify(
+                &parse_burrow_id(&link.issuer)?,
+                &link.signed_message(prior),
+                &link.signature,
+            )
+            .map_err(|_| {
+                ProtocolError::Forbidden(format!(
+                    "delegation link is not signed by {}",
+                    link.issuer
+                ))
+            })?;
+            if link.expires_at <= now {
+                return Err(ProtocolError::AuthRequired("delegation has expired".into()));
+            }
+            previous = Some(link);
+        }
+        Ok(())
+    }
+
+    /// Allow `holder` to use `capability` on `selector` on the strength
+    /// of this token, or say why not.
+    ///
+    /// Besides [`DelegationToken::verify`], the root issuer must hold
+    /// `ManageWarren` and every capability it delegated in `authority`,
+    /// the local burrow's grants; the token must be held by `holder`;
+    /// and its caveats must cover `capability` and `selector`.
+    pub fn require(
+        &self,
+        authority: &CapabilityManager,
+        holder: &str,
+        capability: Capability,
+        selector: &str,
+    ) -> Result<(), ProtocolError> {
+        self.verify()?;
+        let root = &self.links[0];
+        for needed in std::iter::once(&Capability::ManageWarren).chain(&root.capabilities) {
+            if !authority.check(&root.issuer, *needed) {
+                return Err(ProtocolError::Forbidden(format!(
+                    "delegation root {} lacks {}",
+                    root.issuer,
+                    needed.label()
+                )));
+            }
+        }
+        let last = self.last();
+        if last.holder != holder {
+            return Err(ProtocolError::Forbidden(format!(
+                "delegation is held by {}",
+                last.holder
+            )));
+        }
+        if !last.capabilities.contains(&capability) {
+            return Err(ProtocolError::Forbidden(format!(
+                "delegation does not grant {}",
+                capability.label()
+            )));
+        }
+        if !selector.starts_with(&last.selector_prefix) {
+            return Err(ProtocolError::Forbidden(format!(
+                "delegation is limited to {}",
+                last.selector_prefix
+            )));
+        }
+        Ok(())
+    }
+
+    /// The token's `Delegation` header value.
+    pub fn encode(&self) -> String {
+        let lines: Vec<String> = self
+            .links
+            .iter()
+            .map(|l| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    l.issuer,
+                    l.holder,
+                    labels(&l.capabilities),
+                    l.expires_at,
+                    l.selector_prefix,
+                    hex_encode(&l.signature)
+                )
+            })
+            .collect();
+        URL_SAFE_NO_PAD.encode(lines.join("\n"))
+    }
+
+    /// Read a token from its `Delegation` header value.  Nothing is
+    /// checked; see [`DelegationToken::require`].
+    pub fn decode(text: &str) -> Result<Self, ProtocolError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(text.trim())
+            .map_err(|_| malformed("not base64url"))?;
+        let text = String::from_utf8(bytes).map_err(|_| malformed("not UTF-8"))?;
+        let links = text
+            .split('\n')
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                let [issuer, holder, caps, expires_at, selector_prefix, signature] = fields[..]
+                else {
+                    return Err(malformed("wrong number of fields"));
+                };
+                let capabilities = caps
+                    .split(',')
+                    .filter(|c| !c.is_empty())
+                    .map(|c| {
+                        Capability::from_label(c)
+                            .ok_or_else(|| malformed(&format!("unknown capability {}", c)))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(DelegationLink {
+                    issuer: issuer.to_string(),
+                    holder: holder.to_string(),
+                    capabilities,
+                    expires_at: expires_at.parse().map_err(|_| malformed("bad expiry"))?,
+                    selector_prefix: selector_prefix.to_string(),
+                    signature: hex_decode(signature).map_err(|e| malformed(&e))?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { links })
+    }
+
+    fn push(
+        &mut self,
+        issuer: &Identity,
+        holder: &str,
+        capabilities: &[Capability],
+        ttl_secs: u64,
+        selector_prefix: &str,
+    ) {
+        let mut link = DelegationLink {
+            issuer: issuer.burrow_id(),
+            holder: holder.to_string(),
+            capabilities: capabilities.to_vec(),
+            expires_at: now_unix().saturating_add(ttl_secs),
+            selector_prefix: selector_prefix.to_string(),
+            signature: Vec::new(),
+        };
+        let prior = self
+            .links
+            .last()
+            .map(|p| p.signature.as_slice())
+            .unwrap_or(&[]);
+        link.signature = issuer.sign(&link.signed_message(prior));
+        self.links.push(link);
+    }
+
+    fn last(&self) -> &DelegationLink {
+        // Tokens are built by `issue` or checked by `decode` to have
+        // at least one link.
+        self.links.last().expect("delegation token has a root link")
+    }
+}
+
+/// Fail unless `link` allows no more than `previous`.
+fn check_narrower(previous: &DelegationLink, link: &DelegationLink) -> Result<(), ProtocolError> {
+    if let Some(cap) = link
+        .capabilities
+        .iter()
+        .find(|c| !previous.capabilities.contains(c))
+    {
+        return Err(ProtocolError::Forbidden(format!(
+            "delegation link adds {}",
+            cap.label()
+        )));
+    }
+    if link.expires_at > previous.expires_at {
+        return Err(ProtocolError::Forbidden(
+            "delegation link outlives its parent".into(),
+        ));
+    }
+    if !link.selector_prefix.starts_with(&previous.selector_prefix) {
+        return Err(ProtocolError::Forbidden(format!(
+            "delegation link widens {} to {}",
+            previous.selector_prefix, link.selector_prefix
+        )));
+    }
+    Ok(())
+}
+
+fn labels(caps: &[Capability]) -> String {
+    caps.iter().map(|c| c.label()).collect::<Vec<_>>().join(",")
+}
+
+fn malformed(what: &str) -> ProtocolError {
+    ProtocolError::BadRequest(format!("malformed delegation: {}", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authority(root: &Identity) -> CapabilityManager {
+        let mut caps = CapabilityManager::new();
+        caps.grant(&root.burrow_id(), Capability::ManageWarren, 60);
+        caps.grant(&root.burrow_id(), Capability::Fetch, 60);
+        caps.grant(&root.burrow_id(), Capability::List, 60);
+        caps
+    }
+
+    #[test]
+    fn attenuated_chain_is_honoured() {
+        let root = Identity::generate();
+        let alice = Identity::generate();
+        let bob = Identity::generate();
+        let authority = authority(&root);
+
+        let token = DelegationToken::issue(
+            &root,
+            &alice.burrow_id(),
+            &[Capability::Fetch, Capability::List],
+            60,
+            "/docs",
+        );
+        let token = token
+            .attenuate(
+                &alice,
+                &bob.burrow_id(),
+                &[Capability::Fetch],
+                30,
+                "/docs/public",
+            )
+            .unwrap();
+        let token = DelegationToken::decode(&token.encode()).unwrap();
+        assert_eq!(token.links().len(), 2);
+
+        let bob_id = bob.burrow_id();
+        token
+            .require(&authority, &bob_id, Capability::Fetch, "/docs/public/a")
+            .unwrap();
+        for (holder, cap, selector) in [
+            (bob_id.as_str(), Capability::List, "/docs/public/a"),
+            (bob_id.as_str(), Capability::Fetch, "/docs/private"),
+            (
+                alice.burrow_id().as_str(),
+                Capability::Fetch,
+                "/docs/public/a",
+            ),
+        ] {
+            assert!(matches!(
+                token.require(&authority, holder, cap, selector),
+                Err(ProtocolError::Forbidden(_))
+            ));
+        }
+
+        // A root without ManageWarren cannot delegate.
+        let mut weak = CapabilityManager::new();
+        weak.grant(&root.burrow_id(), Capability::Fetch, 60);
+        assert!(token
+            .require(&weak, &bob_id, Capability::Fetch, "/docs/public/a")
+            .is_err());
+    }
+
+    #[test]
+    fn widening_and_tampering_are_refused() {
+        let root = Identity::generate();
+        let alice = Identity::generate();
+        let token =
+            DelegationToken::issue(&root, &alice.burrow_id(), &[Capability::Fetch], 60, "/docs");
+
+        for (caps, ttl, prefix) in [
+            (&[Capability::List][..], 30, "/docs"),
+            (&[Capability::Fetch][..], 120, "/docs"),
+            (&[Capability::Fetch][..], 30, "/"),
+        ] {
+            assert!(token.attenuate(&alice, "x", caps, ttl, prefix).is_err());
+        }
+        // Only the holder can pass it on.
+        assert!(token
+            .attenuate(&root, "x", &[Capability::Fetch], 30, "/docs")
+            .is_err());
+
+        let mut forged = token.clone();
+        forged.links[0].selector_prefix = "/".into();
+        assert!(matches!(forged.verify(), Err(ProtocolError::Forbidden(_))));
+
+        let expired =
+            DelegationToken::issue(&root, &alice.burrow_id(), &[Capability::Fetch], 0, "");
+        assert!(matches!(
+            expired.verify(),
+            Err(ProtocolError::AuthRequired(_))
+        ));
+    }
+}
@@ -0,0 +1,241 @@
+//! End-to-end encrypted frame bodies.
+//!
+//! TLS protects each hop, but a frame relayed through other burrows is
+//! readable by every one of them.  A body can instead be sealed for one
+//! burrow, so only it can read it and it knows who sent it.
+//!
+//! Both ends derive X25519 keys from their Ed25519 identities: the
+//! secret from the signing key's scalar, the public key by mapping the
+//! burrow ID's key to its Montgomery form.  The static-static X25519
+//! secret is expanded with HKDF-SHA256 (info
+//! `RABBIT-E2E\n<from>\n<to>`) into an XChaCha20-Poly1305 key, which
+//! seals the body under a random 24-byte nonce with that same string
+//! as associated data.
+//!
+//! A sealed frame keeps its start line and headers and carries:
+//!
+//! ```text
+//! E2E: x25519-xchacha20poly1305
+//! E2E-From: ed25519:…
+//! E2E-To: ed25519:…
+//! E2E-Nonce: <hex>
+//! Length: 60
+//! End:
+//! <base64url ciphertext>
+//! ```
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+
+use super::auth::{hex_decode, hex_encode};
+use super::identity::{parse_burrow_id, Identity};
+
+/// The `E2E` header value naming the scheme.
+pub const E2E_SCHEME: &str = "x25519-xchacha20poly1305";
+
+const NONCE_LEN: usize = 24;
+
+/// A body sealed by one burrow for another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedBody {
+    /// Burrow ID of the sender.
+    pub from: String,
+    /// Burrow ID of the recipient.
+    pub to: String,
+    /// The random XChaCha20 nonce.
+    pub nonce: [u8; NONCE_LEN],
+    /// The encrypted body followed by its Poly1305 tag.
+    pub ciphertext: Vec<u8>,
+}
+
+impl SealedBody {
+    /// Seal `plaintext` from `sender` for the burrow `to`.
+    pub fn seal(sender: &Identity, to: &str, plaintext: &[u8]) -> Result<Self, ProtocolError> {
+        let from = sender.burrow_id();
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let context = context(&from, to);
+        let ciphertext = cipher(sender, to, &context)?
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: context.as_bytes(),
+                },
+            )
+            .map_err(|_| ProtocolError::InternalError("E2E encryption failed".into()))?;
+        Ok(Self {
+            from,
+            to: to.to_string(),
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Open a body sealed for `recipient`.  Fails with `Forbidden` if
+    /// it is addressed to someone else, or was not sealed by its
+    /// sender or was altered on the way.
+    pub fn open(&self, recipient: &Identity) -> Result<Vec<u8>, ProtocolError> {
+        if self.to != recipient.burrow_id() {
+            return Err(ProtocolError::Forbidden(format!(
+                "body is sealed for {}",
+                self.to
+            )));
+        }
+        let context = context(&self.from, &self.to);
+        cipher(recipient, &self.from, &context)?
+            .decrypt(
+                XNonce::from_slice(&self.nonce),
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: context.as_bytes(),
+                },
+            )
+            .map_err(|_| ProtocolError::Forbidden(format!("body was not sealed by {}", self.from)))
+    }
+}
+
+impl Frame {
+    /// Whether the body is sealed (carries an `E2E` header).
+    pub fn is_sealed(&self) -> bool {
+        self.header("E2E").is_some()
+    }
+
+    /// Replace the body with one only the burrow `to` can open,
+    /// sealed by `sender`.  See [`crate::security::e2e`].
+    pub fn seal_body(&mut self, sender: &Identity, to: &str) -> Result<(), ProtocolError> {
+        let plaintext = self.body.take().unwrap_or_default();
+        let sealed = SealedBody::seal(sender, to, plaintext.as_bytes())?;
+        self.set_header("E2E", E2E_SCHEME);
+        self.set_header("E2E-From", &sealed.from);
+        self.set_header("E2E-To", &sealed.to);
+        self.set_header("E2E-Nonce", hex_encode(&sealed.nonce));
+        self.set_body(URL_SAFE_NO_PAD.encode(&sealed.ciphertext));
+        Ok(())
+    }
+
+    /// The sealed body, if the frame carries one.
+    pub fn sealed_body(&self) -> Result<Option<SealedBody>, ProtocolError> {
+        let Some(scheme) = self.header("E2E") else {
+            return Ok(None);
+        };
+        if scheme != E2E_SCHEME {
+            return Err(ProtocolError::BadRequest(format!(
+                "unsupported E2E scheme {:?}",
+                scheme
+            )));
+        }
+        let header = |name: &str| {
+            self.header(name)
+                .ok_or_else(|| ProtocolError::BadRequest(format!("sealed frame missing {}", name)))
+        };
+        let nonce = hex_decode(header("E2E-Nonce")?)
+            .ok()
+            .and_then(|n| <[u8; NONCE_LEN]>::try_from(n).ok())
+            .ok_or_else(|| ProtocolError::BadRequest("invalid E2E-Nonce".into()))?;
+        let ciphertext = URL_SAFE_NO_PAD
+            .decode(self.body.as_deref().unwrap_or(""))
+            .map_err(|e| ProtocolError::BadRequest(format!("invalid sealed body: {}", e)))?;
+        Ok(Some(SealedBody {
+            from: header("E2E-From")?.to_string(),
+            to: header("E2E-To")?.to_string(),
+            nonce,
+            ciphertext,
+        }))
+    }
+
+    /// Open a body sealed for `recipient`, restoring the plaintext and
+    /// removing the `E2E` headers, and return the sender's burrow ID.
+    /// Fails with `BadRequest` if the body is not sealed.
+    pub fn open_body(&mut self, recipient: &Identity) -> Result<String, ProtocolError> {
+        let sealed = self
+            .sealed_body()?
+            .ok_or_else(|| ProtocolError::BadRequest("body is not sealed".into()))?;
+        let plaintext = String::from_utf8(sealed.open(recipient)?)
+            .map_err(|_| ProtocolError::BadRequest("sealed body is not UTF-8".into()))?;
+        for name in ["E2E", "E2E-From", "E2E-To", "E2E-Nonce"] {
+            self.headers.remove(name);
+        }
+        self.set_body(plaintext);
+        Ok(sealed.from)
+    }
+}
+
+/// The HKDF info and associated data for a body from `from` to `to`.
+fn context(from: &str, to: &str) -> String {
+    format!("RABBIT-E2E\n{}\n{}", from, to)
+}
+
+/// The cipher shared by `identity` and the burrow `peer`.
+fn cipher(
+    identity: &Identity,
+    peer: &str,
+    context: &str,
+) -> Result<XChaCha20Poly1305, ProtocolError> {
+    let secret =
+        StaticSecret::from(SigningKey::from_bytes(&identity.seed_bytes()).to_scalar_bytes());
+    let peer_key = VerifyingKey::from_bytes(&parse_burrow_id(peer)?)
+        .map_err(|_| ProtocolError::BadRequest(format!("invalid key for {}", peer)))?;
+    let shared = secret.diffie_hellman(&PublicKey::from(peer_key.to_montgomery().to_bytes()));
+    if !shared.was_contributory() {
+        return Err(ProtocolError::Forbidden(format!(
+            "{} has a low-order key",
+            peer
+        )));
+    }
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, shared.as_bytes())
+        .expand(context.as_bytes(), &mut key)
+        .map_err(|_| ProtocolError::InternalError("E2E key derivation failed".into()))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_bodies_open_only_for_their_recipient() {
+        let alice = Identity::generate();
+        let bob = Identity::generate();
+        let eve = Identity::generate();
+
+        let mut frame = Frame::with_args("EVENT", vec!["/q/private".into()]);
+        frame.set_header("Lane", "3");
+        frame.set_body("meet at dawn");
+        frame.seal_body(&alice, &bob.burrow_id()).unwrap();
+        assert!(frame.is_sealed());
+        assert!(!frame.body.as_deref().unwrap().contains("dawn"));
+
+        let wire = Frame::parse(&frame.serialize()).unwrap();
+        assert!(matches!(
+            wire.clone().open_body(&eve),
+            Err(ProtocolError::Forbidden(_))
+        ));
+
+        let mut opened = wire.clone();
+        assert_eq!(opened.open_body(&bob).unwrap(), alice.burrow_id());
+        assert_eq!(opened.body.as_deref(), Some("meet at dawn"));
+        assert_eq!(opened.header("Lane"), Some("3"));
+        assert!(!opened.is_sealed());
+
+        // Claiming another sender, or altering the body, fails.
+        let mut forged = wire.clone();
+        forged.set_header("E2E-From", eve.burrow_id());
+        assert!(forged.open_body(&bob).is_err());
+        let mut sealed = wire.sealed_body().unwrap().unwrap();
+        sealed.ciphertext[0] ^= 1;
+        assert!(sealed.open(&bob).is_err());
+    }
+}
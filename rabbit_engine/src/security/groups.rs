@@ -0,0 +1,375 @@
+//! Named groups of peers.
+//!
+//! A capability granted to the subject `group:<name>` is held by every
+//! member of the group.  Members are burrow IDs or other groups
+//! (`group:<name>` again), so membership is resolved transitively:
+//! adding `group:oak-kids` to `oak-family` gives every kid whatever
+//! `group:oak-family` was granted.
+//!
+//! Membership changes travel as `GROUP` frames signed by a burrow
+//! holding `ManageBurrows` or `ManageWarren`, so a change can be
+//! relayed and still be checked against its author:
+//!
+//! ```text
+//! GROUP add oak-family ed25519:…
+//! Signer: ed25519:…
+//! Nonce: <hex>
+//! Timestamp: 1700000000
+//! Signature: ed25519:<hex>
+//! End:
+//! ```
+//!
+//! The signature covers
+//! `RABBIT-GROUP\n<op>\n<group>\n<member>\n<signer>\n<nonce>\n<timestamp>`.
+//! A burrow keeps its groups in `<storage>/groups.json`, rewritten on
+//! every change.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+use crate::util::now_unix;
+
+use super::auth::{hex_decode, hex_encode};
+use super::identity::{parse_burrow_id, Identity};
+
+/// Prefix marking a subject or member as a group.
+pub const GROUP_PREFIX: &str = "group:";
+
+/// The grant subject standing for the members of group `name`.
+pub fn group_subject(name: &str) -> String {
+    format!("{}{}", GROUP_PREFIX, name)
+}
+
+/// Whether `name` can name a group: non-empty, without whitespace or
+/// `:`.
+fn valid_group_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || c == ':')
+}
+
+/// Group memberships.
+#[derive(Debug, Default)]
+pub struct GroupManager {
+    /// Maps group name → its members.
+    members: BTreeMap<String, BTreeSet<String>>,
+    /// Where changes are written, if anywhere.
+    path: Option<PathBuf>,
+}
+
+impl GroupManager {
+    /// Create an empty group manager.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read groups written by [`GroupManager::save`], and keep writing
+    /// every change back to `path`.  A missing file means no groups.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        let path = path.as_ref();
+        let members = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| ProtocolError::InternalError(format!("invalid groups file: {}", e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                return Err(ProtocolError::InternalError(format!(
+                    "failed to read groups: {}",
+                    e
+                )))
+            }
+        };
+        Ok(Self {
+            members,
+            path: Some(path.to_path_buf()),
+        })
+    }
+
+    /// Write the groups to a JSON file, group name → members.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProtocolError> {
+        let json = serde_json::to_string_pretty(&self.members)
+            .map_err(|e| ProtocolError::InternalError(format!("failed to encode groups: {}", e)))?;
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                ProtocolError::InternalError(format!("failed to create directory: {}", e))
+            })?;
+        }
+        std::fs::write(path.as_ref(), json)
+            .map_err(|e| ProtocolError::InternalError(format!("failed to write groups: {}", e)))
+    }
+
+    /// Add `member` to `group`.  Returns whether it was new.
+    pub fn add(&mut self, group: &str, member: &str) -> bool {
+        let added = self
+            .members
+            .entry(group.to_string())
+            .or_default()
+            .insert(member.to_string());
+        if added {
+            self.persist();
+        }
+        added
+    }
+
+    /// Remove `member` from `group`, dropping the group once empty.
+    /// Returns whether it was a member.
+    pub fn remove(&mut self, group: &str, member: &str) -> bool {
+        let Some(members) = self.members.get_mut(group) else {
+            return false;
+        };
+        let removed = members.remove(member);
+        if members.is_empty() {
+            self.members.remove(group);
+        }
+        if removed {
+            self.persist();
+        }
+        removed
+    }
+
+    /// Apply a membership change.  The change is not verified; see
+    /// [`GroupChange::verify`].  Returns whether anything changed.
+    pub fn apply(&mut self, change: &GroupChange) -> bool {
+        match change.op {
+            GroupOp::Add => self.add(&change.group, &change.member),
+            GroupOp::Remove => self.remove(&change.group, &change.member),
+        }
+    }
+
+    /// The direct members of a group, sorted.
+    pub fn members(&self, group: &str) -> Vec<String> {
+        self.members
+            .get(group)
+            .map(|m| m.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The names of all groups, sorted.
+    pub fn groups(&self) -> Vec<String> {
+        self.members.keys().cloned().collect()
+    }
+
+    /// Every group `subject` belongs to, directly or through other
+    /// groups, sorted.
+    pub fn groups_of(&self, subject: &str) -> Vec<String> {
+        let mut found: HashSet<&str> = HashSet::new();
+        let mut frontier = vec![subject.to_string()];
+        while let Some(member) = frontier.pop() {
+            for (group, members) in &self.members {
+                if members.contains(&member) && found.insert(group) {
+                    frontier.push(group_subject(group));
+                }
+            }
+        }
+        let mut groups: Vec<String> = found.into_iter().map(str::to_string).collect();
+        groups.sort_unstable();
+        groups
+    }
+
+    fn persist(&self) {
+        if let Some(path) = &self.path {
+            if let Err(e) = self.save(path) {
+                warn!(err = %e, path = %path.display(), "failed to save groups");
+            }
+        }
+    }
+}
+
+/// Whether a [`GroupChange`] adds or removes a member.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupOp {
+    /// Add the member.
+    Add,
+    /// Remove the member.
+    Remove,
+}
+
+impl GroupOp {
+    /// The operation as it appears in a `GROUP` frame.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Remove => "remove",
+        }
+    }
+
+    /// Parse `add` or `remove`.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "add" => Some(Self::Add),
+            "remove" => Some(Self::Remove),
+            _ => None,
+        }
+    }
+}
+
+/// A signed change to a group's members.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupChange {
+    /// Add or remove.
+    pub op: GroupOp,
+    /// The group changed.
+    pub group: String,
+    /// The burrow ID or `group:<name>` added or removed.
+    pub member: String,
+    /// Burrow ID of the author, whose key signs the change.
+    pub signer: String,
+    /// Random nonce, so no two changes are alike.
+    pub nonce: String,
+    /// When the change was signed (seconds since the epoch).
+    pub timestamp: u64,
+    /// Signature by the signer.
+    pub signature: Vec<u8>,
+}
+
+impl GroupChange {
+    /// Sign a change to `group` as `identity`.
+    pub fn sign(identity: &Identity, op: GroupOp, group: &str, member: &str) -> Self {
+        use rand::RngCore;
+        let mut nonce = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let mut change = Self {
+            op,
+            group: group.to_string(),
+            member: member.to_string(),
+            signer: identity.burrow_id(),
+            nonce: hex_encode(&nonce),
+            timestamp: now_unix(),
+            signature: Vec::new(),
+        };
+        change.signature = identity.sign(&change.signed_message());
+        change
+    }
+
+    /// Check the group name and the signature.  Fails with
+    /// `BadRequest` for a bad group name and `Forbidden` for a bad
+    /// signature.  Whether the signer may change groups is up to the
+    /// caller.
+    pub fn verify(&self) -> Result<(), ProtocolError> {
+        if !valid_group_name(&self.group) {
+            return Err(ProtocolError::BadRequest(format!(
+                "invalid group name {:?}",
+                self.group
+            )));
+        }
+        Identity::verify(
+            &parse_burrow_id(&self.signer)?,
+            &self.signed_message(),
+            &self.signature,
+        )
+        .map_err(|_| ProtocolError::Forbidden(format!("GROUP is not signed by {}", self.signer)))
+    }
+
+    /// The `GROUP` frame carrying this change.
+    pub fn to_frame(&self) -> Frame {
+        let mut frame = Frame::with_args(
+            "GROUP",
+            vec![
+                self.op.as_str().to_string(),
+                self.group.clone(),
+                self.member.clone(),
+            ],
+        );
+        frame.set_header("Signer", &self.signer);
+        frame.set_header("Nonce", &self.nonce);
+        frame.set_header("Timestamp", self.timestamp.to_string());
+        frame.set_header(
+            "Signature",
+            format!("ed25519:{}", hex_encode(&self.signature)),
+        );
+        frame
+    }
+
+    /// Read a change from a `GROUP` frame.  The signature is not
+    /// checked; see [`GroupChange::verify`].
+    pub fn from_frame(frame: &Frame) -> Result<Self, ProtocolError> {
+        let header = |name: &str| {
+            frame
+                .header(name)
+                .ok_or_else(|| ProtocolError::BadRequest(format!("GROUP missing {}", name)))
+        };
+        let [op, group, member] = &frame.args[..] else {
+            return Err(ProtocolError::BadRequest(
+                "GROUP requires <add|remove> <group> <member>".into(),
+            ));
+        };
+        let op = GroupOp::parse(op)
+            .ok_or_else(|| ProtocolError::BadRequest(format!("unknown GROUP operation {}", op)))?;
+        let signature = header("Signature")?;
+        let signature = hex_decode(signature.strip_prefix("ed25519:").unwrap_or(signature))
+            .map_err(|e| ProtocolError::BadRequest(format!("invalid Signature: {}", e)))?;
+        Ok(Self {
+            op,
+            group: group.clone(),
+            member: member.clone(),
+            signer: header("Signer")?.to_string(),
+            nonce: header("Nonce")?.to_string(),
+            timestamp: header("Timestamp")?
+                .parse()
+                .map_err(|_| ProtocolError::BadRequest("invalid Timestamp".into()))?,
+            signature,
+        })
+    }
+
+    fn signed_message(&self) -> Vec<u8> {
+        format!(
+            "RABBIT-GROUP\n{}\n{}\n{}\n{}\n{}\n{}",
+            self.op.as_str(),
+            self.group,
+            self.member,
+            self.signer,
+            self.nonce,
+            self.timestamp
+        )
+        .into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn membership_resolves_through_nested_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("groups.json");
+        let mut groups = GroupManager::load(&path).unwrap();
+        groups.add("oak-family", "ed25519:MOM");
+        groups.add("oak-family", "group:oak-kids");
+        groups.add("oak-kids", "ed25519:KID");
+
+        assert_eq!(groups.groups_of("ed25519:MOM"), ["oak-family"]);
+        assert_eq!(groups.groups_of("ed25519:KID"), ["oak-family", "oak-kids"]);
+        assert!(groups.groups_of("ed25519:STRANGER").is_empty());
+
+        // Cycles do not loop forever.
+        groups.add("oak-kids", "group:oak-family");
+        assert_eq!(groups.groups_of("ed25519:MOM"), ["oak-family", "oak-kids"]);
+
+        assert!(groups.remove("oak-kids", "ed25519:KID"));
+        assert!(!groups.remove("oak-kids", "ed25519:KID"));
+        let reloaded = GroupManager::load(&path).unwrap();
+        assert_eq!(reloaded.groups(), ["oak-family", "oak-kids"]);
+        assert_eq!(reloaded.members("oak-kids"), ["group:oak-family"]);
+    }
+
+    #[test]
+    fn changes_round_trip_and_verify() {
+        let admin = Identity::generate();
+        let change = GroupChange::sign(&admin, GroupOp::Add, "oak-family", "ed25519:KID");
+        let parsed = GroupChange::from_frame(&change.to_frame()).unwrap();
+        assert_eq!(parsed, change);
+        parsed.verify().unwrap();
+
+        let mut forged = parsed.clone();
+        forged.member = "ed25519:MALLORY".into();
+        assert!(matches!(forged.verify(), Err(ProtocolError::Forbidden(_))));
+
+        let bad_name = GroupChange::sign(&admin, GroupOp::Add, "group:x", "ed25519:KID");
+        assert!(matches!(
+            bad_name.verify(),
+            Err(ProtocolError::BadRequest(_))
+        ));
+    }
+}
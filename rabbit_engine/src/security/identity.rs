@@ -2,16 +2,45 @@
 //!
 //! Each burrow is identified by an Ed25519 keypair.  The **Burrow ID**
 //! is the string `ed25519:<base32(public_key_bytes)>`.  Keypairs are
-//! persisted to disk as raw 64-byte secret-key files and reloaded on
+//! persisted to disk as 32-byte secret seed files and reloaded on
 //! restart so the burrow keeps the same identity across sessions.
+//!
+//! When the `RABBIT_KEY_PASSPHRASE` environment variable is set, new
+//! key files are encrypted with ChaCha20-Poly1305 under a key derived
+//! from the passphrase (PBKDF2-HMAC-SHA256), and encrypted files are
+//! decrypted on load:
+//!
+//! ```text
+//! "RBKEY01\n" | salt (16) | nonce (12) | sealed seed (32 + 16 tag)
+//! ```
 
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
+use rand::RngCore;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305};
+use ring::pbkdf2;
 use sha2::{Digest, Sha256};
+use std::num::NonZeroU32;
 use std::path::Path;
 
 use crate::protocol::error::ProtocolError;
 
+use super::permissions::Capability;
+use super::rotation::KeyRotation;
+use super::token::{TokenClaims, TokenKind};
+
+/// Environment variable holding the passphrase for identity key files.
+pub const PASSPHRASE_ENV: &str = "RABBIT_KEY_PASSPHRASE";
+
+/// Prefix of an encrypted identity key file.
+const ENCRYPTED_MAGIC: &[u8; 8] = b"RBKEY01\n";
+
+/// PBKDF2 rounds used to derive the file key from a passphrase.
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
 /// An Ed25519 identity for a burrow.
 #[derive(Debug)]
 pub struct Identity {
@@ -26,13 +55,62 @@ impl Identity {
         Self { signing_key }
     }
 
-    /// Load an identity from a file containing the 32-byte secret seed.
+    /// Load the identity at `path`, or generate one and save it there
+    /// if the file does not exist yet.
     ///
-    /// The file must be exactly 32 bytes (the Ed25519 seed).
+    /// The passphrase, if any, comes from [`PASSPHRASE_ENV`].
+    pub fn load_or_create(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        Self::load_or_create_with(path, env_passphrase().as_deref())
+    }
+
+    /// [`Identity::load_or_create`] with an explicit passphrase.  A new
+    /// identity is saved encrypted when `passphrase` is given.
+    pub fn load_or_create_with(
+        path: impl AsRef<Path>,
+        passphrase: Option<&str>,
+    ) -> Result<Self, ProtocolError> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Self::from_file_with(path, passphrase);
+        }
+        let identity = Self::generate();
+        match passphrase {
+            Some(p) => identity.save_encrypted(path, p)?,
+            None => identity.save(path)?,
+        }
+        Ok(identity)
+    }
+
+    /// Load an identity from a file containing the 32-byte secret seed,
+    /// or an encrypted seed when [`PASSPHRASE_ENV`] is set.
     pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        Self::from_file_with(path, env_passphrase().as_deref())
+    }
+
+    /// Load an identity file, decrypting it with `passphrase` if it is
+    /// encrypted.
+    ///
+    /// Plain files must be exactly 32 bytes (the Ed25519 seed).  A
+    /// wrong passphrase fails with `Forbidden`.
+    pub fn from_file_with(
+        path: impl AsRef<Path>,
+        passphrase: Option<&str>,
+    ) -> Result<Self, ProtocolError> {
         let bytes = std::fs::read(path.as_ref()).map_err(|e| {
             ProtocolError::InternalError(format!("failed to read identity file: {}", e))
         })?;
+        if bytes.starts_with(ENCRYPTED_MAGIC) {
+            let passphrase = passphrase.ok_or_else(|| {
+                ProtocolError::Forbidden(format!(
+                    "identity file is encrypted; set {} to its passphrase",
+                    PASSPHRASE_ENV
+                ))
+            })?;
+            let seed = open_seed(&bytes, passphrase)?;
+            return Ok(Self {
+                signing_key: SigningKey::from_bytes(&seed),
+            });
+        }
         if bytes.len() != 32 {
             return Err(ProtocolError::InternalError(format!(
                 "identity file must be 32 bytes, got {}",
@@ -46,17 +124,54 @@ impl Identity {
 
     /// Save the 32-byte seed to a file.
     pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProtocolError> {
-        let dir = path.as_ref().parent();
-        if let Some(d) = dir {
-            if !d.exists() {
-                std::fs::create_dir_all(d).map_err(|e| {
-                    ProtocolError::InternalError(format!("failed to create directory: {}", e))
-                })?;
-            }
-        }
-        std::fs::write(path.as_ref(), self.signing_key.to_bytes()).map_err(|e| {
-            ProtocolError::InternalError(format!("failed to write identity file: {}", e))
-        })
+        write_key_file(path.as_ref(), &self.signing_key.to_bytes())
+    }
+
+    /// Save the seed encrypted under `passphrase`.
+    pub fn save_encrypted(
+        &self,
+        path: impl AsRef<Path>,
+        passphrase: &str,
+    ) -> Result<(), ProtocolError> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        let mut sealed = self.signing_key.to_bytes().to_vec();
+        file_key(passphrase, &salt)
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(ENCRYPTED_MAGIC),
+                &mut sealed,
+            )
+            .map_err(|_| ProtocolError::InternalError("failed to encrypt identity".into()))?;
+
+        let mut bytes = ENCRYPTED_MAGIC.to_vec();
+        bytes.extend_from_slice(&salt);
+        bytes.extend_from_slice(&nonce);
+        bytes.extend_from_slice(&sealed);
+        write_key_file(path.as_ref(), &bytes)
+    }
+
+    /// Generate a successor identity, with the rotation record signed
+    /// by both keys that hands this identity's trust on to it.
+    pub fn rotate(&self) -> (Identity, KeyRotation) {
+        let new = Self::generate();
+        let rotation = KeyRotation::sign(self, &new);
+        (new, rotation)
+    }
+
+    /// Issue a signed session token to `subject`, naming its
+    /// capabilities and valid for `ttl_secs`.  Any burrow can check it
+    /// with [`TokenClaims::verify`].
+    pub fn issue_token(
+        &self,
+        kind: TokenKind,
+        subject: &str,
+        caps: &[Capability],
+        ttl_secs: u64,
+    ) -> String {
+        TokenClaims::new(kind, &self.burrow_id(), subject, caps, ttl_secs).sign(self)
     }
 
     /// Return the public verifying key.
@@ -117,6 +232,65 @@ impl Identity {
     }
 }
 
+/// The passphrase from [`PASSPHRASE_ENV`], if set and not empty.
+fn env_passphrase() -> Option<String> {
+    std::env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty())
+}
+
+/// The ChaCha20-Poly1305 key for an encrypted identity file.
+fn file_key(passphrase: &str, salt: &[u8]) -> LessSafeKey {
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(PBKDF2_ROUNDS).unwrap(),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &key).unwrap())
+}
+
+/// Decrypt the seed of an encrypted identity file.
+fn open_seed(bytes: &[u8], passphrase: &str) -> Result<[u8; 32], ProtocolError> {
+    let rest = &bytes[ENCRYPTED_MAGIC.len()..];
+    if rest.len() != SALT_LEN + NONCE_LEN + 32 + CHACHA20_POLY1305.tag_len() {
+        return Err(ProtocolError::InternalError(
+            "encrypted identity file is truncated".into(),
+        ));
+    }
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).unwrap();
+    let mut sealed = sealed.to_vec();
+    let seed = file_key(passphrase, salt)
+        .open_in_place(nonce, Aad::from(ENCRYPTED_MAGIC), &mut sealed)
+        .map_err(|_| ProtocolError::Forbidden("wrong passphrase for identity file".into()))?;
+    Ok(seed.try_into().unwrap())
+}
+
+/// Write secret key material, creating the directory and keeping the
+/// file private to its owner.
+pub(crate) fn write_key_file(path: &Path, bytes: &[u8]) -> Result<(), ProtocolError> {
+    if let Some(d) = path.parent() {
+        if !d.exists() {
+            std::fs::create_dir_all(d).map_err(|e| {
+                ProtocolError::InternalError(format!("failed to create directory: {}", e))
+            })?;
+        }
+    }
+    std::fs::write(path, bytes).map_err(|e| {
+        ProtocolError::InternalError(format!("failed to write {}: {}", path.display(), e))
+    })?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).map_err(|e| {
+            ProtocolError::InternalError(format!("failed to restrict {}: {}", path.display(), e))
+        })?;
+    }
+    Ok(())
+}
+
 /// Format a Burrow ID from raw public key bytes.
 pub fn format_burrow_id(pubkey_bytes: &[u8; 32]) -> String {
     let encoded = base32::encode(base32::Alphabet::Rfc4648 { padding: false }, pubkey_bytes);
@@ -228,4 +402,35 @@ mod tests {
         let parsed = parse_burrow_id(&formatted).unwrap();
         assert_eq!(parsed, id.public_key_bytes());
     }
+
+    #[test]
+    fn load_or_create_keeps_the_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys").join("identity.key");
+        let created = Identity::load_or_create_with(&path, None).unwrap();
+        let loaded = Identity::load_or_create_with(&path, None).unwrap();
+        assert_eq!(loaded.burrow_id(), created.burrow_id());
+        assert_eq!(std::fs::read(&path).unwrap().len(), 32);
+    }
+
+    #[test]
+    fn encrypted_identity_needs_its_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("identity.key");
+        let created = Identity::load_or_create_with(&path, Some("hunter2")).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.starts_with(ENCRYPTED_MAGIC));
+        assert!(!bytes.windows(32).any(|w| w == created.seed_bytes()));
+
+        let loaded = Identity::from_file_with(&path, Some("hunter2")).unwrap();
+        assert_eq!(loaded.burrow_id(), created.burrow_id());
+        assert!(matches!(
+            Identity::from_file_with(&path, Some("wrong")),
+            Err(ProtocolError::Forbidden(_))
+        ));
+        assert!(matches!(
+            Identity::from_file_with(&path, None),
+            Err(ProtocolError::Forbidden(_))
+        ));
+    }
 }
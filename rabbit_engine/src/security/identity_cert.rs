@@ -0,0 +1,360 @@
+//! TLS certificates bound to a burrow ID.
+//!
+//! [`generate_identity_cert`] makes a self-signed certificate whose
+//! TLS key is vouched for by the burrow's Ed25519 identity.  A custom
+//! extension ([`RABBIT_ID_OID`]) carries
+//!
+//! ```text
+//! RabbitId ::= SEQUENCE {
+//!     burrowId   UTF8String,   -- "ed25519:<base32>"
+//!     signature  OCTET STRING  -- identity signature over the TLS public key
+//! }
+//! ```
+//!
+//! so anyone holding the certificate can check which burrow it belongs
+//! to with [`extract_rabbit_id_from_cert`], without trusting a CA.
+//! Certificates are read with a small DER walker that understands only
+//! as much X.509 as this needs.
+//!
+//! A burrow keeps its certificate as `cert.pem` and `key.pem` in its
+//! certs directory; [`load_or_create_identity_cert`] makes them the
+//! first time it starts.
+
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::protocol::error::ProtocolError;
+use crate::transport::cert::CertPair;
+
+use super::auth::hex_encode;
+use super::identity::{parse_burrow_id, write_key_file, Identity};
+
+/// The certificate's file name in a certs directory.
+pub const CERT_FILE: &str = "cert.pem";
+
+/// The certificate key's file name in a certs directory.
+pub const KEY_FILE: &str = "key.pem";
+
+/// OID of the Rabbit ID extension, under an unregistered private
+/// enterprise arc.
+pub const RABBIT_ID_OID: &[u64] = &[1, 3, 6, 1, 4, 1, 59_754, 1, 1];
+
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_UTF8_STRING: u8 = 0x0c;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_EXTENSIONS: u8 = 0xa3;
+
+/// Generate a self-signed certificate for `localhost`, named `name`,
+/// carrying `identity`'s burrow ID and its signature over the
+/// certificate's (freshly generated) key.
+pub fn generate_identity_cert(identity: &Identity, name: &str) -> Result<CertPair, ProtocolError> {
+    let failed =
+        |e: rcgen::Error| ProtocolError::InternalError(format!("cert generation failed: {}", e));
+    let key_pair = rcgen::KeyPair::generate().map_err(failed)?;
+    let signature = identity.sign(key_pair.public_key_raw());
+    let mut extension = der(TAG_UTF8_STRING, identity.burrow_id().as_bytes());
+    extension.extend(der(TAG_OCTET_STRING, &signature));
+
+    let mut params =
+        rcgen::CertificateParams::new(vec!["localhost".to_string()]).map_err(failed)?;
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, name);
+    params
+        .custom_extensions
+        .push(rcgen::CustomExtension::from_oid_content(
+            RABBIT_ID_OID,
+            der(TAG_SEQUENCE, &extension),
+        ));
+    let cert = params.self_signed(&key_pair).map_err(failed)?;
+    Ok(CertPair {
+        cert_pem: cert.pem(),
+        key_pem: key_pair.serialize_pem(),
+    })
+}
+
+/// Load the certificate kept in `dir`, or, if there is none, generate
+/// one bound to `identity` and save it there, the key readable only by
+/// its owner.  Either way the certificate's fingerprint is logged; a
+/// kept certificate bound to another burrow is used with a warning.
+pub fn load_or_create_identity_cert(
+    dir: &Path,
+    identity: &Identity,
+    name: &str,
+) -> Result<CertPair, ProtocolError> {
+    let cert_path = dir.join(CERT_FILE);
+    let key_path = dir.join(KEY_FILE);
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).map_err(|e| {
+            ProtocolError::InternalError(format!("failed to read {}: {}", path.display(), e))
+        })
+    };
+    if cert_path.exists() && key_path.exists() {
+        let pair = CertPair {
+            cert_pem: read(&cert_path)?,
+            key_pem: read(&key_path)?,
+        };
+        let fingerprint = cert_fingerprint(&pair.cert_pem)?;
+        match extract_rabbit_id_from_pem(&pair.cert_pem)? {
+            Some(id) if id != identity.burrow_id() => {
+                warn!(path = %cert_path.display(), cert_id = %id, %fingerprint, "TLS certificate is bound to another burrow")
+            }
+            _ => info!(path = %cert_path.display(), %fingerprint, "loaded TLS certificate"),
+        }
+        return Ok(pair);
+    }
+
+    let pair = generate_identity_cert(identity, name)?;
+    std::fs::create_dir_all(dir).map_err(|e| {
+        ProtocolError::InternalError(format!("failed to create {}: {}", dir.display(), e))
+    })?;
+    std::fs::write(&cert_path, &pair.cert_pem).map_err(|e| {
+        ProtocolError::InternalError(format!("failed to write {}: {}", cert_path.display(), e))
+    })?;
+    write_key_file(&key_path, pair.key_pem.as_bytes())?;
+    let fingerprint = cert_fingerprint(&pair.cert_pem)?;
+    info!(path = %cert_path.display(), %fingerprint, "generated TLS certificate bound to this burrow");
+    Ok(pair)
+}
+
+/// The SHA-256 hex of the first certificate in a PEM document.
+pub fn cert_fingerprint(cert_pem: &str) -> Result<String, ProtocolError> {
+    let cert = rustls_pemfile::certs(&mut cert_pem.as_bytes())
+        .next()
+        .ok_or_else(|| malformed("no certificate in PEM"))?
+        .map_err(|e| malformed(&format!("parse cert PEM: {}", e)))?;
+    Ok(hex_encode(&Sha256::digest(&cert)))
+}
+
+/// The burrow ID a DER certificate is bound to, if it has the Rabbit ID
+/// extension.
+///
+/// The extension's signature over the certificate's public key is
+/// checked; a certificate claiming an ID it cannot prove fails with
+/// `Forbidden`.  Malformed certificates fail with `BadRequest`.
+pub fn extract_rabbit_id_from_cert(cert_der: &[u8]) -> Result<Option<String>, ProtocolError> {
+    let (certificate, _) = expect(cert_der, TAG_SEQUENCE)?;
+    let (tbs, _) = expect(certificate, TAG_SEQUENCE)?;
+
+    // version?, serial, signature, issuer, validity, subject, spki, …
+    let mut public_key = None;
+    let mut extensions = None;
+    let mut sequences = 0;
+    let mut rest = tbs;
+    while !rest.is_empty() {
+        let (tag, contents, next) = read_tlv(rest)?;
+        rest = next;
+        match tag {
+            TAG_SEQUENCE => {
+                sequences += 1;
+                if sequences == 5 {
+                    let (_algorithm, spki) = expect(contents, TAG_SEQUENCE)?;
+                    let (key, _) = expect(spki, TAG_BIT_STRING)?;
+                    // Skip the unused-bits byte.
+                    public_key = key.get(1..);
+                }
+            }
+            TAG_EXTENSIONS => extensions = Some(expect(contents, TAG_SEQUENCE)?.0),
+            _ => {}
+        }
+    }
+    let public_key = public_key.ok_or_else(|| malformed("no subject public key"))?;
+    let Some(value) = extensions.map(find_rabbit_extension).transpose()?.flatten() else {
+        return Ok(None);
+    };
+
+    let (fields, _) = expect(value, TAG_SEQUENCE)?;
+    let (id, rest) = expect(fields, TAG_UTF8_STRING)?;
+    let (signature, _) = expect(rest, TAG_OCTET_STRING)?;
+    let id = std::str::from_utf8(id)
+        .map_err(|_| malformed("burrow ID is not UTF-8"))?
+        .to_string();
+    Identity::verify(&parse_burrow_id(&id)?, public_key, signature).map_err(|_| {
+        ProtocolError::Forbidden(format!("certificate key is not signed by {}", id))
+    })?;
+    Ok(Some(id))
+}
+
+/// [`extract_rabbit_id_from_cert`] for the first certificate in a PEM
+/// document.
+pub fn extract_rabbit_id_from_pem(cert_pem: &str) -> Result<Option<String>, ProtocolError> {
+    let cert = rustls_pemfile::certs(&mut cert_pem.as_bytes())
+        .next()
+        .ok_or_else(|| malformed("no certificate in PEM"))?
+        .map_err(|e| malformed(&format!("parse cert PEM: {}", e)))?;
+    extract_rabbit_id_from_cert(&cert)
+}
+
+/// The value of the Rabbit ID extension among a certificate's
+/// extensions.
+fn find_rabbit_extension(mut extensions: &[u8]) -> Result<Option<&[u8]>, ProtocolError> {
+    let oid = encode_oid(RABBIT_ID_OID);
+    while !extensions.is_empty() {
+        let (extension, rest) = expect(extensions, TAG_SEQUENCE)?;
+        extensions = rest;
+        let (id, mut fields) = expect(extension, TAG_OID)?;
+        if id != oid.as_slice() {
+            continue;
+        }
+        // Skip the optional `critical` flag.
+        loop {
+            let (tag, contents, next) = read_tlv(fields)?;
+            if tag == TAG_OCTET_STRING {
+                return Ok(Some(contents));
+            }
+            fields = next;
+        }
+    }
+    Ok(None)
+}
+
+/// Split the first DER element off `input`: its tag, its contents, and
+/// what follows it.
+fn read_tlv(input: &[u8]) -> Result<(u8, &[u8], &[u8]), ProtocolError> {
+    let [tag, first, rest @ ..] = input else {
+        return Err(malformed("truncated element"));
+    };
+    let (len, rest) = match *first {
+        n if n < 0x80 => (n as usize, rest),
+        n => {
+            let count = (n & 0x7f) as usize;
+            if count == 0 || count > 4 || rest.len() < count {
+                return Err(malformed("bad length"));
+            }
+            let len = rest[..count]
+                .iter()
+                .fold(0usize, |len, b| (len << 8) | *b as usize);
+            (len, &rest[count..])
+        }
+    };
+    if rest.len() < len {
+        return Err(malformed("truncated element"));
+    }
+    Ok((*tag, &rest[..len], &rest[len..]))
+}
+
+/// [`read_tlv`], requiring the element to have tag `tag`.
+fn expect(input: &[u8], tag: u8) -> Result<(&[u8], &[u8]), ProtocolError> {
+    match read_tlv(input)? {
+        (t, contents, rest) if t == tag => Ok((contents, rest)),
+        (t, _, _) => Err(malformed(&format!(
+            "expected tag {:#04x}, found {:#04x}",
+            tag, t
+        ))),
+    }
+}
+
+/// Encode a DER element.
+fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = contents.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(contents);
+    out
+}
+
+/// The DER contents of an object identifier.
+fn encode_oid(arcs: &[u64]) -> Vec<u8> {
+    let mut out = vec![(arcs[0] * 40 + arcs[1]) as u8];
+    for &arc in &arcs[2..] {
+        let mut groups = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            groups.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        out.extend(groups.into_iter().rev());
+    }
+    out
+}
+
+fn malformed(what: &str) -> ProtocolError {
+    ProtocolError::BadRequest(format!("malformed certificate: {}", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::cert::{generate_self_signed, make_server_config};
+
+    #[test]
+    fn identity_cert_carries_the_burrow_id() {
+        let identity = Identity::generate();
+        let pair = generate_identity_cert(&identity, "my-burrow").unwrap();
+        assert_eq!(
+            extract_rabbit_id_from_pem(&pair.cert_pem).unwrap(),
+            Some(identity.burrow_id())
+        );
+        // The certificate still works for TLS.
+        make_server_config(&pair).unwrap();
+    }
+
+    #[test]
+    fn certs_are_bootstrapped_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let certs = dir.path().join("certs");
+        let identity = Identity::generate();
+        let made = load_or_create_identity_cert(&certs, &identity, "oak").unwrap();
+        assert_eq!(
+            extract_rabbit_id_from_pem(&made.cert_pem).unwrap(),
+            Some(identity.burrow_id())
+        );
+        let loaded = load_or_create_identity_cert(&certs, &identity, "oak").unwrap();
+        assert_eq!(loaded.cert_pem, made.cert_pem);
+        assert_eq!(
+            cert_fingerprint(&loaded.cert_pem).unwrap(),
+            cert_fingerprint(&made.cert_pem).unwrap()
+        );
+        make_server_config(&loaded).unwrap();
+    }
+
+    #[test]
+    fn plain_certs_have_no_id() {
+        let pair = generate_self_signed().unwrap();
+        assert_eq!(extract_rabbit_id_from_pem(&pair.cert_pem).unwrap(), None);
+    }
+
+    #[test]
+    fn borrowed_id_is_refused() {
+        let identity = Identity::generate();
+        let pair = generate_identity_cert(&identity, "my-burrow").unwrap();
+        let mut der = rustls_pemfile::certs(&mut pair.cert_pem.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap()
+            .to_vec();
+        // Corrupt the last byte of the identity signature.
+        let id = identity.burrow_id();
+        let at = der
+            .windows(id.len())
+            .position(|w| w == id.as_bytes())
+            .unwrap();
+        der[at + id.len() + 2 + 63] ^= 1;
+        assert!(matches!(
+            extract_rabbit_id_from_cert(&der),
+            Err(ProtocolError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn oid_encoding() {
+        assert_eq!(
+            encode_oid(&[1, 3, 6, 1, 4, 1, 311]),
+            [0x2b, 6, 1, 4, 1, 0x82, 0x37]
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! Trust manifests.
+//!
+//! An anchor vouches for the burrows of its federation by signing a
+//! [`TrustManifest`] listing their IDs.  A burrow holding a verified
+//! manifest can admit a member it has never seen under a policy that
+//! refuses plain TOFU (see [`super::trust_policy::AnchorRequired`]).
+//!
+//! Each manifest an anchor signs carries a higher `Serial` than the
+//! last, so a newer manifest supersedes an older one, and an `Expires`
+//! time after which it vouches for no one.  Members the anchor no
+//! longer vouches for can be listed as revoked, so a burrow holding an
+//! older manifest learns of the withdrawal.
+//!
+//! A member can itself be made a **sub-anchor**, vouching for further
+//! members with manifests of its own, so a federation can have several
+//! levels.  A [`ManifestChain`] runs from a root anchor's manifest down
+//! through sub-anchors' manifests to the one listing a peer.
+//!
+//! Manifests travel as `MANIFEST` frames, one member per body line,
+//! sub-anchors followed by ` anchor` and each revoked ID prefixed with
+//! `-`:
+//!
+//! ```text
+//! MANIFEST
+//! Anchor: ed25519:…
+//! Serial: 7
+//! Issued: 1700000000
+//! Expires: 1702592000
+//! Signature: ed25519:<hex>
+//! Length: 180
+//! End:
+//! ed25519:…
+//! ed25519:… anchor
+//! -ed25519:…
+//! ```
+//!
+//! The signature covers
+//! `RABBIT-MANIFEST\n<anchor>\n<serial>\n<issued>\n<expires>` followed
+//! by `\n<line>` for each body line, in order.
+
+use std::path::Path;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+use crate::util::now_unix;
+
+use super::auth::{hex_decode, hex_encode};
+use super::identity::{fingerprint, parse_burrow_id, Identity};
+
+/// A list of burrows an anchor vouches for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustManifest {
+    /// Burrow ID of the anchor, whose key signs the manifest.
+    pub anchor: String,
+    /// Version of the anchor's manifest; a higher serial supersedes a
+    /// lower one.
+    pub serial: u64,
+    /// When the manifest was signed (seconds since the epoch).
+    pub issued_at: u64,
+    /// When the manifest stops vouching for its members (seconds since
+    /// the epoch).
+    pub expires_at: u64,
+    /// Burrow IDs of the members.
+    pub members: Vec<String>,
+    /// Burrow IDs of members that may vouch for others in turn.
+    pub sub_anchors: Vec<String>,
+    /// Burrow IDs the anchor no longer vouches for.
+    pub revoked: Vec<String>,
+    /// Signature by the anchor.
+    pub signature: Vec<u8>,
+}
+
+impl TrustManifest {
+    /// Sign version `serial` of `anchor`'s manifest, listing `members`
+    /// and `sub_anchors` and withdrawing `revoked`, valid for
+    /// `ttl_secs`.
+    pub fn sign(
+        anchor: &Identity,
+        serial: u64,
+        members: &[String],
+        sub_anchors: &[String],
+        revoked: &[String],
+        ttl_secs: u64,
+    ) -> Self {
+        let issued_at = now_unix();
+        let mut manifest = Self {
+            anchor: anchor.burrow_id(),
+            serial,
+            issued_at,
+            expires_at: issued_at.saturating_add(ttl_secs),
+            members: members.to_vec(),
+            sub_anchors: sub_anchors.to_vec(),
+            revoked: revoked.to_vec(),
+            signature: Vec::new(),
+        };
+        manifest.signature = anchor.sign(&manifest.signed_message());
+        manifest
+    }
+
+    /// Check the anchor's signature and that the manifest has not
+    /// expired.  Fails with `Forbidden` for either.
+    pub fn verify(&self) -> Result<(), ProtocolError> {
+        Identity::verify(
+            &parse_burrow_id(&self.anchor)?,
+            &self.signed_message(),
+            &self.signature,
+        )
+        .map_err(|_| {
+            ProtocolError::Forbidden(format!("manifest is not signed by {}", self.anchor))
+        })?;
+        if self.is_expired() {
+            return Err(ProtocolError::Forbidden(format!(
+                "manifest {} from {} has expired",
+                self.serial, self.anchor
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the manifest's expiry has passed.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= now_unix()
+    }
+
+    /// Whether this manifest supersedes `other`, from the same anchor.
+    pub fn supersedes(&self, other: &TrustManifest) -> bool {
+        self.anchor == other.anchor && self.serial > other.serial
+    }
+
+    /// Whether the manifest vouches for `burrow_id`: it is listed, as
+    /// a member or sub-anchor, and not revoked.
+    pub fn lists(&self, burrow_id: &str) -> bool {
+        self.members
+            .iter()
+            .chain(&self.sub_anchors)
+            .any(|m| m == burrow_id)
+            && !self.revokes(burrow_id)
+    }
+
+    /// Whether the manifest makes `burrow_id` a sub-anchor, and does
+    /// not revoke it.
+    pub fn delegates_to(&self, burrow_id: &str) -> bool {
+        self.sub_anchors.iter().any(|a| a == burrow_id) && !self.revokes(burrow_id)
+    }
+
+    /// Whether the manifest revokes `burrow_id`.
+    pub fn revokes(&self, burrow_id: &str) -> bool {
+        self.revoked.iter().any(|r| r == burrow_id)
+    }
+
+    /// The file a manifest is kept in, named after the fingerprint of
+    /// its anchor's key: `<fingerprint>.manifest`.
+    pub fn file_name(&self) -> Result<String, ProtocolError> {
+        Ok(format!(
+            "{}.manifest",
+            fingerprint(&parse_burrow_id(&self.anchor)?)
+        ))
+    }
+
+    /// The `MANIFEST` frame carrying this manifest.
+    pub fn to_frame(&self) -> Frame {
+        let mut frame = Frame::new("MANIFEST");
+        frame.set_header("Anchor", &self.anchor);
+        frame.set_header("Serial", self.serial.to_string());
+        frame.set_header("Issued", self.issued_at.to_string());
+        frame.set_header("Expires", self.expires_at.to_string());
+        frame.set_header(
+            "Signature",
+            format!("ed25519:{}", hex_encode(&self.signature)),
+        );
+        frame.set_body(self.body_lines().join("\n"));
+        frame
+    }
+
+    /// Read a manifest from a `MANIFEST` frame.  The signature is not
+    /// checked; see [`TrustManifest::verify`].
+    pub fn from_frame(frame: &Frame) -> Result<Self, ProtocolError> {
+        let header = |name: &str| {
+            frame
+                .header(name)
+                .ok_or_else(|| ProtocolError::BadRequest(format!("MANIFEST missing {}", name)))
+        };
+        let number = |name: &str| {
+            header(name)?
+                .parse::<u64>()
+                .map_err(|_| ProtocolError::BadRequest(format!("invalid {}", name)))
+        };
+        let signature = header("Signature")?;
+        let signature = hex_decode(signature.strip_prefix("ed25519:").unwrap_or(signature))
+            .map_err(|e| ProtocolError::BadRequest(format!("invalid Signature: {}", e)))?;
+        let mut manifest = Self {
+            anchor: header("Anchor")?.to_string(),
+            serial: number("Serial")?,
+            issued_at: number("Issued")?,
+            expires_at: number("Expires")?,
+            members: Vec::new(),
+            sub_anchors: Vec::new(),
+            revoked: Vec::new(),
+            signature,
+        };
+        let lines = frame.body.as_deref().unwrap_or("").lines();
+        for line in lines.filter(|l| !l.is_empty()) {
+            if let Some(revoked) = line.strip_prefix('-') {
+                manifest.revoked.push(revoked.to_string());
+            } else if let Some(sub_anchor) = line.strip_suffix(" anchor") {
+                manifest.sub_anchors.push(sub_anchor.to_string());
+            } else if line.contains(' ') {
+                return Err(ProtocolError::BadRequest(format!(
+                    "invalid MANIFEST line {:?}",
+                    line
+                )));
+            } else {
+                manifest.members.push(line.to_string());
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Write the manifest to a file as its `MANIFEST` frame.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProtocolError> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                ProtocolError::InternalError(format!("failed to create directory: {}", e))
+            })?;
+        }
+        std::fs::write(path.as_ref(), self.to_frame().serialize())
+            .map_err(|e| ProtocolError::InternalError(format!("failed to write manifest: {}", e)))
+    }
+
+    /// Read a manifest written by [`TrustManifest::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        let text = std::fs::read_to_string(path.as_ref())
+            .map_err(|e| ProtocolError::InternalError(format!("failed to read manifest: {}", e)))?;
+        Self::from_frame(&Frame::parse(&text)?)
+    }
+
+    fn signed_message(&self) -> Vec<u8> {
+        let mut message = format!(
+            "RABBIT-MANIFEST\n{}\n{}\n{}\n{}",
+            self.anchor, self.serial, self.issued_at, self.expires_at
+        );
+        for line in self.body_lines() {
+            message.push('\n');
+            message.push_str(&line);
+        }
+        message.into_bytes()
+    }
+
+    /// Members, then sub-anchors, then revoked IDs, as body lines.
+    fn body_lines(&self) -> Vec<String> {
+        self.members
+            .iter()
+            .cloned()
+            .chain(self.sub_anchors.iter().map(|a| format!("{} anchor", a)))
+            .chain(self.revoked.iter().map(|r| format!("-{}", r)))
+            .collect()
+    }
+}
+
+/// Manifests linking a root anchor to a peer: the first is signed by
+/// the root, each makes the signer of the next a sub-anchor, and the
+/// last lists the peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestChain {
+    links: Vec<TrustManifest>,
+}
+
+impl ManifestChain {
+    /// A chain of `links`, root first.
+    pub fn new(links: Vec<TrustManifest>) -> Self {
+        Self { links }
+    }
+
+    /// The manifests, root first.
+    pub fn links(&self) -> &[TrustManifest] {
+        &self.links
+    }
+
+    /// The root anchor's burrow ID.
+    pub fn root(&self) -> Option<&str> {
+        self.links.first().map(|m| m.anchor.as_str())
+    }
+
+    /// Whether the last manifest vouches for `burrow_id`.
+    pub fn vouches_for(&self, burrow_id: &str) -> bool {
+        self.links.last().is_some_and(|m| m.lists(burrow_id))
+    }
+
+    /// Check that the chain starts at the anchor whose public key is
+    /// `root_pk`, that every manifest is signed and unexpired, and that
+    /// each makes the signer of the next a sub-anchor.  Fails with
+    /// `BadRequest` for an empty chain and `Forbidden` otherwise.
+    pub fn verify(&self, root_pk: &[u8; 32]) -> Result<(), ProtocolError> {
+        let first = self
+            .links
+            .first()
+            .ok_or_else(|| ProtocolError::BadRequest("empty manifest chain".into()))?;
+        if parse_burrow_id(&first.anchor)? != *root_pk {
+            return Err(ProtocolError::Forbidden(format!(
+                "manifest chain starts at {}, not the root anchor",
+                first.anchor
+            )));
+        }
+        for manifest in &self.links {
+            manifest.verify()?;
+        }
+        for pair in self.links.windows(2) {
+            if !pair[0].delegates_to(&pair[1].anchor) {
+                return Err(ProtocolError::Forbidden(format!(
+                    "{} is not a sub-anchor of {}",
+                    pair[1].anchor, pair[0].anchor
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_round_trips_and_verifies() {
+        let anchor = Identity::generate();
+        let members = vec!["ed25519:AAA".to_string(), "ed25519:BBB".to_string()];
+        let manifest = TrustManifest::sign(&anchor, 1, &members, &[], &[], 3600);
+        assert!(manifest.lists("ed25519:BBB"));
+        assert!(!manifest.lists("ed25519:CCC"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("anchor.manifest");
+        manifest.save(&path).unwrap();
+        let loaded = TrustManifest::load(&path).unwrap();
+        assert_eq!(loaded, manifest);
+        loaded.verify().unwrap();
+
+        let mut padded = loaded;
+        padded.members.push("ed25519:MALLORY".into());
+        assert!(matches!(padded.verify(), Err(ProtocolError::Forbidden(_))));
+    }
+
+    #[test]
+    fn revocations_and_expiry() {
+        let anchor = Identity::generate();
+        let members = vec!["ed25519:AAA".to_string(), "ed25519:BBB".to_string()];
+        let first = TrustManifest::sign(&anchor, 1, &members, &[], &[], 3600);
+        let second = TrustManifest::sign(&anchor, 2, &members[..1], &[], &members[1..], 3600);
+        let parsed = TrustManifest::from_frame(&second.to_frame()).unwrap();
+        assert_eq!(parsed, second);
+        parsed.verify().unwrap();
+        assert!(parsed.revokes("ed25519:BBB"));
+        assert!(!parsed.lists("ed25519:BBB"));
+        assert!(parsed.supersedes(&first));
+        assert!(!first.supersedes(&parsed));
+
+        let expired = TrustManifest::sign(&anchor, 3, &members, &[], &[], 0);
+        assert!(expired.is_expired());
+        assert!(matches!(expired.verify(), Err(ProtocolError::Forbidden(_))));
+    }
+
+    #[test]
+    fn chains_run_from_the_root_through_sub_anchors() {
+        let root = Identity::generate();
+        let sub = Identity::generate();
+        let other = Identity::generate();
+        let top = TrustManifest::sign(&root, 1, &[], &[sub.burrow_id()], &[], 3600);
+        let leaf = TrustManifest::sign(&sub, 1, &["ed25519:KID".into()], &[], &[], 3600);
+        let parsed = TrustManifest::from_frame(&top.to_frame()).unwrap();
+        assert_eq!(parsed, top);
+        assert!(top.lists(&sub.burrow_id()));
+
+        let chain = ManifestChain::new(vec![top.clone(), leaf.clone()]);
+        chain.verify(&root.public_key_bytes()).unwrap();
+        assert!(chain.vouches_for("ed25519:KID"));
+        assert_eq!(chain.root(), Some(root.burrow_id().as_str()));
+        assert!(matches!(
+            chain.verify(&other.public_key_bytes()),
+            Err(ProtocolError::Forbidden(_))
+        ));
+
+        // A plain member cannot vouch for others.
+        let flat = TrustManifest::sign(&root, 2, &[sub.burrow_id()], &[], &[], 3600);
+        assert!(ManifestChain::new(vec![flat, leaf.clone()])
+            .verify(&root.public_key_bytes())
+            .is_err());
+        let lapsed = TrustManifest::sign(&root, 3, &[], &[sub.burrow_id()], &[], 0);
+        assert!(ManifestChain::new(vec![lapsed, leaf])
+            .verify(&root.public_key_bytes())
+            .is_err());
+    }
+}
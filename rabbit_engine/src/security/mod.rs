@@ -1,10 +1,24 @@
 //! Security primitives for the Rabbit protocol.
 //!
-//! This module covers Ed25519 identity management, TOFU trust
-//! verification, the authentication handshake state machine, and
-//! time-limited capability grants.
+//! This module covers Ed25519 identity management and key rotation,
+//! TLS certificates bound to a burrow ID, TOFU trust verification under
+//! pluggable policies, anchor-signed trust manifests, the
+//! authentication handshake state machine, signed session tokens,
+//! time-limited capability grants, peer groups, signed delegation
+//! chains, per-peer rate limits, a hash-chained audit log of auth and
+//! trust decisions, and end-to-end encrypted frame bodies.
 
+pub mod audit;
 pub mod auth;
+pub mod delegation;
+pub mod e2e;
+pub mod groups;
 pub mod identity;
+pub mod identity_cert;
+pub mod manifest;
 pub mod permissions;
+pub mod rate_limiter;
+pub mod rotation;
+pub mod token;
 pub mod trust;
+pub mod trust_policy;
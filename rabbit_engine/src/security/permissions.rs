@@ -4,10 +4,42 @@
 //! what a peer is allowed to do.  Each grant specifies a subject
 //! (burrow ID), a capability, and a TTL (time-to-live) in seconds.
 //! Expired grants are automatically pruned on access.
+//!
+//! A grant may be scoped to the selectors or topics matching a
+//! pattern: `*` matches one path segment and `**` any number, so
+//! `Publish` on `/q/dialogue/*` covers `/q/dialogue/42` but not
+//! `/q/dialogue/42/replies`, and `Fetch` on `/files/**` covers
+//! everything under `/files`.
+//!
+//! Grants are saved as JSON with their wall-clock expiry
+//! ([`CapabilityManager::save`]), and revocations are appended to a
+//! log as they happen, one tab-separated line each:
+//!
+//! ```text
+//! 1700000000  ed25519:…  Publish
+//! 1700000042  ed25519:…  *            -- every capability
+//! ```
+//!
+//! On load, expired grants are dropped and the log is replayed over
+//! what remains, so a revocation made after the last save still holds
+//! after a crash.
+//!
+//! A grant to `group:<name>` is held by every member of the group, as
+//! resolved by the manager's [`GroupManager`].
 
 use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::protocol::error::ProtocolError;
+use crate::util::now_unix;
+
+use super::groups::{group_subject, GroupManager};
+
 /// The set of capabilities that can be granted to a peer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Capability {
@@ -27,6 +59,8 @@ pub enum Capability {
     Federation,
     /// Access UI control endpoints.
     UIControl,
+    /// Have frames relayed through this burrow (RELAY-REGISTER).
+    Relay,
 }
 
 impl Capability {
@@ -41,6 +75,7 @@ impl Capability {
             Self::ManageBurrows => "ManageBurrows",
             Self::Federation => "Federation",
             Self::UIControl => "UIControl",
+            Self::Relay => "Relay",
         }
     }
 
@@ -55,6 +90,7 @@ impl Capability {
             "ManageBurrows" => Some(Self::ManageBurrows),
             "Federation" => Some(Self::Federation),
             "UIControl" => Some(Self::UIControl),
+            "Relay" => Some(Self::Relay),
             _ => None,
         }
     }
@@ -69,6 +105,9 @@ pub struct Grant {
     pub created: Instant,
     /// How long this grant is valid.
     pub ttl: Duration,
+    /// The selector pattern the grant is limited to; `None` for
+    /// everywhere.
+    pub scope: Option<String>,
 }
 
 impl Grant {
@@ -78,6 +117,15 @@ impl Grant {
             capability,
             created: Instant::now(),
             ttl: Duration::from_secs(ttl_secs),
+            scope: None,
+        }
+    }
+
+    /// Create a grant limited to the selectors matching `scope`.
+    pub fn scoped(capability: Capability, scope: &str, ttl_secs: u64) -> Self {
+        Self {
+            scope: Some(scope.to_string()),
+            ..Self::new(capability, ttl_secs)
         }
     }
 
@@ -87,9 +135,18 @@ impl Grant {
             capability,
             created,
             ttl,
+            scope: None,
         }
     }
 
+    /// Whether the grant's scope covers `selector`, which may itself be
+    /// a pattern.
+    pub fn covers(&self, selector: &str) -> bool {
+        self.scope
+            .as_deref()
+            .is_none_or(|scope| pattern_covers(scope, selector))
+    }
+
     /// Check whether this grant has expired.
     pub fn is_expired(&self) -> bool {
         self.created.elapsed() >= self.ttl
@@ -106,6 +163,21 @@ impl Grant {
 pub struct CapabilityManager {
     /// Maps subject (burrow ID) → list of active grants.
     grants: HashMap<String, Vec<Grant>>,
+    /// Where revocations are appended, if anywhere.
+    revocation_log: Option<PathBuf>,
+    /// Groups whose grants their members hold.
+    groups: GroupManager,
+}
+
+/// A grant as saved to disk.
+#[derive(Debug, Serialize, Deserialize)]
+struct GrantRecord {
+    subject: String,
+    capability: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    granted_at: u64,
+    expires_at: u64,
 }
 
 impl CapabilityManager {
@@ -113,37 +185,219 @@ impl CapabilityManager {
     pub fn new() -> Self {
         Self {
             grants: HashMap::new(),
+            revocation_log: None,
+            groups: GroupManager::new(),
+        }
+    }
+
+    /// The groups grants are resolved through.
+    pub fn groups(&self) -> &GroupManager {
+        &self.groups
+    }
+
+    /// Mutable access to the groups.
+    pub fn groups_mut(&mut self) -> &mut GroupManager {
+        &mut self.groups
+    }
+
+    /// Replace the groups, e.g. with ones loaded from disk.
+    pub fn set_groups(&mut self, groups: GroupManager) {
+        self.groups = groups;
+    }
+
+    /// The subject's own grants followed by those of every group it
+    /// belongs to.
+    fn grants_of(&self, subject: &str) -> Vec<&Grant> {
+        std::iter::once(subject.to_string())
+            .chain(
+                self.groups
+                    .groups_of(subject)
+                    .iter()
+                    .map(|g| group_subject(g)),
+            )
+            .filter_map(|s| self.grants.get(&s))
+            .flatten()
+            .collect()
+    }
+
+    /// Write the unexpired grants to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProtocolError> {
+        let now = now_unix();
+        let mut records: Vec<GrantRecord> = self
+            .grants
+            .iter()
+            .flat_map(|(subject, grants)| grants.iter().map(move |g| (subject, g)))
+            .filter(|(_, g)| !g.is_expired())
+            .map(|(subject, g)| {
+                let granted_at = now.saturating_sub(g.created.elapsed().as_secs());
+                GrantRecord {
+                    subject: subject.clone(),
+                    capability: g.capability.label().to_string(),
+                    scope: g.scope.clone(),
+                    granted_at,
+                    expires_at: granted_at + g.ttl.as_secs(),
+                }
+            })
+            .collect();
+        records.sort_by(|a, b| (&a.subject, &a.capability).cmp(&(&b.subject, &b.capability)));
+        let json = serde_json::to_string_pretty(&records)
+            .map_err(|e| ProtocolError::InternalError(format!("failed to encode grants: {}", e)))?;
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                ProtocolError::InternalError(format!("failed to create directory: {}", e))
+            })?;
+        }
+        std::fs::write(path.as_ref(), json)
+            .map_err(|e| ProtocolError::InternalError(format!("failed to write grants: {}", e)))
+    }
+
+    /// Read grants written by [`CapabilityManager::save`], dropping
+    /// those that have expired.  A missing file means no grants.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        let mut mgr = Self::new();
+        let json = match std::fs::read_to_string(path.as_ref()) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(mgr),
+            Err(e) => {
+                return Err(ProtocolError::InternalError(format!(
+                    "failed to read grants: {}",
+                    e
+                )))
+            }
+        };
+        let records: Vec<GrantRecord> = serde_json::from_str(&json)
+            .map_err(|e| ProtocolError::InternalError(format!("invalid grants file: {}", e)))?;
+        let now = now_unix();
+        for r in records.into_iter().filter(|r| r.expires_at > now) {
+            let Some(capability) = Capability::from_label(&r.capability) else {
+                warn!(capability = %r.capability, "unknown capability in grants file");
+                continue;
+            };
+            let age = Duration::from_secs(now.saturating_sub(r.granted_at));
+            let grant = Grant {
+                capability,
+                created: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+                ttl: Duration::from_secs(r.expires_at.saturating_sub(r.granted_at)),
+                scope: r.scope,
+            };
+            mgr.grants.entry(r.subject).or_default().push(grant);
+        }
+        Ok(mgr)
+    }
+
+    /// Append future revocations to `path`, after replaying the
+    /// revocations already there over the current grants.
+    ///
+    /// A logged revocation removes the matching grants made no later
+    /// than it.
+    pub fn set_revocation_log(&mut self, path: impl AsRef<Path>) -> Result<(), ProtocolError> {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(log) => {
+                let now = now_unix();
+                for line in log.lines().filter(|l| !l.is_empty()) {
+                    let mut fields = line.split('\t');
+                    let (Some(at), Some(subject), Some(what)) =
+                        (fields.next(), fields.next(), fields.next())
+                    else {
+                        continue;
+                    };
+                    let Ok(at) = at.parse::<u64>() else {
+                        continue;
+                    };
+                    let capability = Capability::from_label(what);
+                    if let Some(grants) = self.grants.get_mut(subject) {
+                        grants.retain(|g| {
+                            let granted_at = now.saturating_sub(g.created.elapsed().as_secs());
+                            let matches = what == "*" || capability == Some(g.capability);
+                            !(matches && granted_at <= at)
+                        });
+                        if grants.is_empty() {
+                            self.grants.remove(subject);
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(ProtocolError::InternalError(format!(
+                    "failed to read revocation log: {}",
+                    e
+                )))
+            }
+        }
+        self.revocation_log = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Append a revocation of `what` (a capability label, or `*`) to
+    /// the log, if there is one.
+    fn log_revocation(&self, subject: &str, what: &str) {
+        let Some(path) = &self.revocation_log else {
+            return;
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| {
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+            })
+            .and_then(|mut f| writeln!(f, "{}\t{}\t{}", now_unix(), subject, what));
+        if let Err(e) = result {
+            warn!(err = %e, path = %path.display(), "failed to log revocation");
         }
     }
 
     /// Grant a capability to a subject with a TTL in seconds.
     pub fn grant(&mut self, subject: &str, capability: Capability, ttl_secs: u64) {
-        let entry = self.grants.entry(subject.to_string()).or_default();
-        // Remove any existing grant for the same capability (replace).
-        entry.retain(|g| g.capability != capability);
-        entry.push(Grant::new(capability, ttl_secs));
+        self.grant_with(subject, Grant::new(capability, ttl_secs));
+    }
+
+    /// Grant a capability on the selectors matching `scope`.
+    pub fn grant_scoped(
+        &mut self,
+        subject: &str,
+        capability: Capability,
+        scope: &str,
+        ttl_secs: u64,
+    ) {
+        self.grant_with(subject, Grant::scoped(capability, scope, ttl_secs));
     }
 
     /// Grant with a pre-built Grant object (useful for testing).
+    ///
+    /// Replaces any grant of the same capability with the same scope.
     pub fn grant_with(&mut self, subject: &str, grant: Grant) {
         let entry = self.grants.entry(subject.to_string()).or_default();
-        entry.retain(|g| g.capability != grant.capability);
+        entry.retain(|g| g.capability != grant.capability || g.scope != grant.scope);
         entry.push(grant);
     }
 
-    /// Check whether a subject has a given capability (non-expired).
+    /// Check whether a subject has a given capability (non-expired)
+    /// everywhere, itself or through a group.  Scoped grants do not
+    /// count; see [`CapabilityManager::allowed`].
     pub fn check(&self, subject: &str, capability: Capability) -> bool {
-        if let Some(grants) = self.grants.get(subject) {
-            grants
-                .iter()
-                .any(|g| g.capability == capability && !g.is_expired())
-        } else {
-            false
-        }
+        self.grants_of(subject)
+            .into_iter()
+            .any(|g| g.capability == capability && g.scope.is_none() && !g.is_expired())
+    }
+
+    /// Check whether a subject may use a capability on `selector`.
+    ///
+    /// `selector` may itself be a pattern, in which case every
+    /// selector it matches must be covered by one grant.
+    pub fn allowed(&self, subject: &str, capability: Capability, selector: &str) -> bool {
+        self.grants_of(subject)
+            .into_iter()
+            .any(|g| g.capability == capability && !g.is_expired() && g.covers(selector))
     }
 
     /// Revoke a specific capability from a subject.
     pub fn revoke(&mut self, subject: &str, capability: Capability) {
+        self.log_revocation(subject, capability.label());
         if let Some(grants) = self.grants.get_mut(subject) {
             grants.retain(|g| g.capability != capability);
             if grants.is_empty() {
@@ -154,6 +408,7 @@ impl CapabilityManager {
 
     /// Revoke all capabilities from a subject.
     pub fn revoke_all(&mut self, subject: &str) {
+        self.log_revocation(subject, "*");
         self.grants.remove(subject);
     }
 
@@ -165,17 +420,16 @@ impl CapabilityManager {
         });
     }
 
-    /// List all active (non-expired) capabilities for a subject.
+    /// List all active (non-expired) capabilities for a subject,
+    /// scoped or not, including those of its groups.
     pub fn active_capabilities(&self, subject: &str) -> Vec<Capability> {
-        if let Some(grants) = self.grants.get(subject) {
-            grants
-                .iter()
-                .filter(|g| !g.is_expired())
-                .map(|g| g.capability)
-                .collect()
-        } else {
-            Vec::new()
+        let mut caps: Vec<Capability> = Vec::new();
+        for g in self.grants_of(subject) {
+            if !g.is_expired() && !caps.contains(&g.capability) {
+                caps.push(g.capability);
+            }
         }
+        caps
     }
 
     /// Return the number of subjects with any active grants.
@@ -193,6 +447,27 @@ impl Default for CapabilityManager {
     }
 }
 
+/// Whether every selector matching `inner` also matches `outer`.
+///
+/// Patterns are compared a `/`-separated segment at a time: `**` in
+/// `outer` covers the rest, `*` covers any one segment, and anything
+/// else only itself.
+pub fn pattern_covers(outer: &str, inner: &str) -> bool {
+    fn covers(outer: &[&str], inner: &[&str]) -> bool {
+        match (outer.split_first(), inner.split_first()) {
+            (Some((&"**", _)), _) => true,
+            (None, None) => true,
+            (Some((o, outer)), Some((i, inner))) => {
+                *i != "**" && (*o == "*" || o == i) && covers(outer, inner)
+            }
+            _ => false,
+        }
+    }
+    let outer: Vec<&str> = outer.split('/').filter(|s| !s.is_empty()).collect();
+    let inner: Vec<&str> = inner.split('/').filter(|s| !s.is_empty()).collect();
+    covers(&outer, &inner)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -290,6 +565,7 @@ mod tests {
             Capability::ManageBurrows,
             Capability::Federation,
             Capability::UIControl,
+            Capability::Relay,
         ];
         for cap in &caps {
             let label = cap.label();
@@ -321,6 +597,93 @@ mod tests {
         assert!(mgr.active_capabilities("anyone").is_empty());
     }
 
+    #[test]
+    fn scoped_grants_match_patterns() {
+        let mut mgr = CapabilityManager::new();
+        mgr.grant_scoped("peer-a", Capability::Publish, "/q/dialogue/*", 3600);
+        mgr.grant_scoped("peer-a", Capability::Fetch, "/files/**", 3600);
+
+        assert!(mgr.allowed("peer-a", Capability::Publish, "/q/dialogue/42"));
+        assert!(!mgr.allowed("peer-a", Capability::Publish, "/q/dialogue/42/x"));
+        assert!(!mgr.allowed("peer-a", Capability::Publish, "/q/other"));
+        assert!(mgr.allowed("peer-a", Capability::Fetch, "/files/a/b/c"));
+        assert!(!mgr.allowed("peer-a", Capability::Fetch, "/docs"));
+        // Scoped grants are not global.
+        assert!(!mgr.check("peer-a", Capability::Fetch));
+
+        // Patterns are allowed only if everything they match is.
+        assert!(mgr.allowed("peer-a", Capability::Fetch, "/files/a/*"));
+        assert!(mgr.allowed("peer-a", Capability::Publish, "/q/dialogue/*"));
+        assert!(!mgr.allowed("peer-a", Capability::Publish, "/q/dialogue/**"));
+
+        // A global grant sits alongside the scoped one.
+        mgr.grant("peer-a", Capability::Fetch, 3600);
+        assert!(mgr.allowed("peer-a", Capability::Fetch, "/docs"));
+        assert_eq!(mgr.active_capabilities("peer-a").len(), 2);
+    }
+
+    #[test]
+    fn grants_persist_and_revocations_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let grants = dir.path().join("capabilities.json");
+        let log = dir.path().join("revocations.log");
+
+        let mut mgr = CapabilityManager::new();
+        mgr.set_revocation_log(&log).unwrap();
+        mgr.grant("peer-a", Capability::Fetch, 3600);
+        mgr.grant_scoped("peer-a", Capability::Publish, "/q/*", 3600);
+        mgr.grant("peer-b", Capability::List, 3600);
+        mgr.grant_with(
+            "peer-c",
+            Grant::with_created(
+                Capability::Fetch,
+                Duration::from_secs(1),
+                Instant::now() - Duration::from_secs(10),
+            ),
+        );
+        mgr.save(&grants).unwrap();
+
+        // Revoked after the save, as if the burrow then crashed.
+        mgr.revoke_all("peer-b");
+
+        let mut loaded = CapabilityManager::load(&grants).unwrap();
+        assert!(loaded.allowed("peer-a", Capability::Publish, "/q/x"));
+        assert!(!loaded.check("peer-a", Capability::Publish));
+        assert!(loaded.check("peer-b", Capability::List));
+        assert_eq!(loaded.subject_count(), 2);
+
+        loaded.set_revocation_log(&log).unwrap();
+        assert!(!loaded.check("peer-b", Capability::List));
+        assert!(loaded.check("peer-a", Capability::Fetch));
+
+        let entries = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(entries.lines().count(), 1);
+        assert!(entries.ends_with("\tpeer-b\t*\n"));
+    }
+
+    #[test]
+    fn group_grants_reach_members() {
+        let mut mgr = CapabilityManager::new();
+        mgr.grant(&group_subject("oak-family"), Capability::Fetch, 3600);
+        mgr.grant_scoped(
+            &group_subject("oak-kids"),
+            Capability::Publish,
+            "/q/kids/*",
+            3600,
+        );
+        mgr.groups_mut().add("oak-family", "group:oak-kids");
+        mgr.groups_mut().add("oak-kids", "peer-a");
+
+        assert!(mgr.check("peer-a", Capability::Fetch));
+        assert!(mgr.allowed("peer-a", Capability::Publish, "/q/kids/chat"));
+        assert!(!mgr.allowed("peer-a", Capability::Publish, "/q/adults/chat"));
+        assert!(!mgr.check("peer-b", Capability::Fetch));
+        assert_eq!(mgr.active_capabilities("peer-a").len(), 2);
+
+        mgr.groups_mut().remove("oak-kids", "peer-a");
+        assert!(!mgr.check("peer-a", Capability::Fetch));
+    }
+
     #[test]
     fn grant_remaining_time() {
         let grant = Grant::new(Capability::Fetch, 3600);
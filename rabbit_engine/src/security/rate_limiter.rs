@@ -0,0 +1,318 @@
+//! Per-peer rate limiting and abuse throttling.
+//!
+//! Each limit is a token bucket per key, refilled continuously and
+//! holding at most one period's worth of tokens, so a peer may burst
+//! up to the limit and is then held to the average rate:
+//!
+//! - **frames** — every frame a peer sends, per second;
+//! - **publish** — PUBLISH frames, per second, on top of the frame
+//!   limit;
+//! - **handshake** — handshake attempts from one network address, per
+//!   minute, checked before the handshake starts;
+//! - **fetch bytes** — bytes of FETCH responses sent to a peer, per
+//!   second.  A response is charged after it is built, so one larger
+//!   than the allowance is still sent, but the peer then waits until
+//!   the debt is repaid.
+//!
+//! A request over a limit is answered with `429 SLOW-DOWN` and a
+//! `Retry-After` header giving the seconds until it would succeed (see
+//! [`ProtocolError::SlowDown`]).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::protocol::error::ProtocolError;
+
+/// The kinds of bucket kept per key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Throttle {
+    Frames,
+    Publish,
+    Handshake,
+    FetchBytes,
+}
+
+/// A token bucket: `capacity` tokens, refilled at `rate` per second.
+#[derive(Debug)]
+struct Bucket {
+    /// Tokens available; negative while repaying a debt.
+    tokens: f64,
+    /// When `tokens` was last brought up to date.
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(capacity: f64) -> Self {
+        Self {
+            tokens: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Add the tokens earned since the last update.
+    fn refill(&mut self, capacity: f64, rate: f64) {
+        let now = Instant::now();
+        let earned = now.duration_since(self.updated).as_secs_f64() * rate;
+        self.tokens = (self.tokens + earned).min(capacity);
+        self.updated = now;
+    }
+
+    /// Whole seconds until `cost` tokens are available, at least 1.
+    fn retry_after(&self, cost: f64, rate: f64) -> u64 {
+        ((cost - self.tokens) / rate).ceil().max(1.0) as u64
+    }
+}
+
+/// Per-peer token-bucket rate limiter.
+///
+/// Every limit is off (unlimited) when set to 0.
+pub struct RateLimiter {
+    /// Maximum frames per second per peer.
+    max_fps: u32,
+    /// Maximum PUBLISH frames per second per peer.
+    max_publish_fps: u32,
+    /// Maximum handshake attempts per minute per address.
+    max_handshakes_per_min: u32,
+    /// Maximum FETCH response bytes per second per peer.
+    max_fetch_bps: u64,
+    /// Buckets by key and kind.
+    buckets: Mutex<HashMap<(String, Throttle), Bucket>>,
+}
+
+impl std::fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("max_fps", &self.max_fps)
+            .field("max_publish_fps", &self.max_publish_fps)
+            .field("max_handshakes_per_min", &self.max_handshakes_per_min)
+            .field("max_fetch_bps", &self.max_fetch_bps)
+            .finish()
+    }
+}
+
+impl RateLimiter {
+    /// Create a rate limiter for frames and PUBLISH frames per second.
+    ///
+    /// Pass 0 to disable a limit.
+    pub fn new(max_fps: u32, max_publish_fps: u32) -> Self {
+        Self {
+            max_fps,
+            max_publish_fps,
+            max_handshakes_per_min: 0,
+            max_fetch_bps: 0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Also limit handshake attempts per minute from one address.
+    pub fn with_handshake_limit(mut self, per_min: u32) -> Self {
+        self.max_handshakes_per_min = per_min;
+        self
+    }
+
+    /// Also limit the bytes of FETCH responses per second to a peer.
+    pub fn with_fetch_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        self.max_fetch_bps = bytes_per_sec;
+        self
+    }
+
+    /// Capacity and refill rate per second of a kind of bucket, or
+    /// `None` if its limit is off.
+    fn limit(&self, throttle: Throttle) -> Option<(f64, f64)> {
+        let (capacity, period) = match throttle {
+            Throttle::Frames => (self.max_fps as f64, 1.0),
+            Throttle::Publish => (self.max_publish_fps as f64, 1.0),
+            Throttle::Handshake => (self.max_handshakes_per_min as f64, 60.0),
+            Throttle::FetchBytes => (self.max_fetch_bps as f64, 1.0),
+        };
+        (capacity > 0.0).then_some((capacity, capacity / period))
+    }
+
+    /// Take `cost` tokens from each of `key`'s buckets of the given
+    /// kinds if they all hold enough, or take none and return the
+    /// seconds until they would.
+    fn acquire(&self, key: &str, throttles: &[Throttle], cost: f64) -> Result<(), u64> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let mut retry_after = 0;
+        for &throttle in throttles {
+            let Some((capacity, rate)) = self.limit(throttle) else {
+                continue;
+            };
+            let bucket = buckets
+                .entry((key.to_string(), throttle))
+                .or_insert_with(|| Bucket::full(capacity));
+            bucket.refill(capacity, rate);
+            if bucket.tokens < cost {
+                retry_after = retry_after.max(bucket.retry_after(cost, rate));
+            }
+        }
+        if retry_after > 0 {
+            return Err(retry_after);
+        }
+        for &throttle in throttles {
+            if let Some(bucket) = buckets.get_mut(&(key.to_string(), throttle)) {
+                bucket.tokens -= cost;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether a frame from `peer_id` should be allowed, taking
+    /// a token if so.
+    ///
+    /// `is_publish` should be true for PUBLISH frames (which have a
+    /// separate, stricter limit).
+    ///
+    /// Returns `true` if allowed, `false` if rate-limited.
+    pub fn check(&self, peer_id: &str, is_publish: bool) -> bool {
+        self.check_frame(peer_id, is_publish).is_ok()
+    }
+
+    /// Like [`RateLimiter::check`], but fails with `SlowDown` saying
+    /// when to retry.
+    pub fn check_frame(&self, peer_id: &str, is_publish: bool) -> Result<(), ProtocolError> {
+        let throttles: &[Throttle] = if is_publish {
+            &[Throttle::Frames, Throttle::Publish]
+        } else {
+            &[Throttle::Frames]
+        };
+        self.acquire(peer_id, throttles, 1.0)
+            .map_err(|retry_after| ProtocolError::SlowDown {
+                reason: "rate limit exceeded".into(),
+                retry_after,
+            })
+    }
+
+    /// Count a handshake attempt from `addr`, failing with `SlowDown`
+    /// if it has made too many.
+    pub fn check_handshake(&self, addr: &str) -> Result<(), ProtocolError> {
+        self.acquire(addr, &[Throttle::Handshake], 1.0)
+            .map_err(|retry_after| ProtocolError::SlowDown {
+                reason: "too many handshake attempts".into(),
+                retry_after,
+            })
+    }
+
+    /// Check that `peer_id` is not still repaying FETCH bandwidth,
+    /// failing with `SlowDown` if it is.
+    pub fn check_fetch(&self, peer_id: &str) -> Result<(), ProtocolError> {
+        let Some((capacity, rate)) = self.limit(Throttle::FetchBytes) else {
+            return Ok(());
+        };
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets
+            .entry((peer_id.to_string(), Throttle::FetchBytes))
+            .or_insert_with(|| Bucket::full(capacity));
+        bucket.refill(capacity, rate);
+        if bucket.tokens > 0.0 {
+            return Ok(());
+        }
+        Err(ProtocolError::SlowDown {
+            reason: "fetch bandwidth exceeded".into(),
+            retry_after: bucket.retry_after(1.0, rate),
+        })
+    }
+
+    /// Charge `bytes` of FETCH responses sent to `peer_id`, which may
+    /// leave it in debt.
+    pub fn charge_fetch(&self, peer_id: &str, bytes: u64) {
+        let Some((capacity, rate)) = self.limit(Throttle::FetchBytes) else {
+            return;
+        };
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets
+            .entry((peer_id.to_string(), Throttle::FetchBytes))
+            .or_insert_with(|| Bucket::full(capacity));
+        bucket.refill(capacity, rate);
+        bucket.tokens -= bytes as f64;
+    }
+
+    /// Remove tracking state for a disconnected peer.
+    pub fn remove_peer(&self, peer_id: &str) {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        buckets.retain(|(key, throttle), _| key != peer_id || *throttle == Throttle::Handshake);
+    }
+
+    /// Returns true if frame rate limiting is enabled (at least one of
+    /// the frame and PUBLISH limits > 0).
+    pub fn is_enabled(&self) -> bool {
+        self.max_fps > 0 || self.max_publish_fps > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlimited_always_allows() {
+        let rl = RateLimiter::new(0, 0);
+        for _ in 0..1000 {
+            assert!(rl.check("peer-a", false));
+            assert!(rl.check("peer-a", true));
+        }
+        assert!(rl.check_handshake("10.0.0.1").is_ok());
+        rl.charge_fetch("peer-a", u64::MAX);
+        assert!(rl.check_fetch("peer-a").is_ok());
+    }
+
+    #[test]
+    fn general_limit_enforced() {
+        let rl = RateLimiter::new(5, 0);
+        for _ in 0..5 {
+            assert!(rl.check("peer-a", false));
+        }
+        // 6th frame should be rejected.
+        assert!(!rl.check("peer-a", false));
+        // Different peer is independent.
+        assert!(rl.check("peer-b", false));
+    }
+
+    #[test]
+    fn publish_limit_enforced() {
+        let rl = RateLimiter::new(100, 2);
+        assert!(rl.check("peer-a", true));
+        assert!(rl.check("peer-a", true));
+        // 3rd PUBLISH rejected.
+        assert!(!rl.check("peer-a", true));
+        // Non-publish still allowed.
+        assert!(rl.check("peer-a", false));
+    }
+
+    #[test]
+    fn remove_peer_clears_state() {
+        let rl = RateLimiter::new(2, 0);
+        assert!(rl.check("peer-a", false));
+        assert!(rl.check("peer-a", false));
+        assert!(!rl.check("peer-a", false));
+        rl.remove_peer("peer-a");
+        // After removal, counter resets.
+        assert!(rl.check("peer-a", false));
+    }
+
+    #[test]
+    fn handshakes_and_fetches_say_when_to_retry() {
+        let rl = RateLimiter::new(0, 0)
+            .with_handshake_limit(2)
+            .with_fetch_bandwidth(1000);
+        rl.check_handshake("10.0.0.1").unwrap();
+        rl.check_handshake("10.0.0.1").unwrap();
+        match rl.check_handshake("10.0.0.1") {
+            Err(ProtocolError::SlowDown { retry_after, .. }) => {
+                assert!((1..=30).contains(&retry_after))
+            }
+            other => panic!("expected SlowDown, got {:?}", other),
+        }
+        rl.check_handshake("10.0.0.2").unwrap();
+
+        rl.check_fetch("peer-a").unwrap();
+        rl.charge_fetch("peer-a", 3500);
+        match rl.check_fetch("peer-a") {
+            Err(ProtocolError::SlowDown { retry_after, .. }) => {
+                assert!((2..=3).contains(&retry_after))
+            }
+            other => panic!("expected SlowDown, got {:?}", other),
+        }
+    }
+}
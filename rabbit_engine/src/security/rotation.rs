@@ -0,0 +1,182 @@
+//! Key rotation records.
+//!
+//! A burrow ID is its public key, so a new key means a new ID.  When a
+//! burrow rotates its key it signs a [`KeyRotation`] with both the old
+//! and the new key and announces it as a `ROTATE` frame after each
+//! handshake:
+//!
+//! ```text
+//! ROTATE
+//! Old-ID: ed25519:…
+//! New-ID: ed25519:…
+//! Timestamp: 1700000000
+//! Old-Signature: ed25519:<hex>
+//! New-Signature: ed25519:<hex>
+//! End:
+//! ```
+//!
+//! Both signatures cover `RABBIT-ROTATE\n<old>\n<new>\n<timestamp>`.
+//! A peer that trusted the old ID carries that trust over to the new
+//! one (see [`super::trust::TrustCache::apply_rotation`]).  The record
+//! is kept in `<storage>/rotation.frame` in the same text form.
+
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+
+use super::auth::{hex_decode, hex_encode};
+use super::identity::{parse_burrow_id, Identity};
+
+/// Proof that the holder of one key handed its identity on to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRotation {
+    /// The retired burrow ID.
+    pub old_id: String,
+    /// The burrow ID that replaces it.
+    pub new_id: String,
+    /// When the rotation was signed (seconds since the epoch).
+    pub timestamp: u64,
+    /// Signature by the old key.
+    pub old_signature: Vec<u8>,
+    /// Signature by the new key.
+    pub new_signature: Vec<u8>,
+}
+
+impl KeyRotation {
+    /// Sign a rotation from `old` to `new`.
+    pub fn sign(old: &Identity, new: &Identity) -> Self {
+        let old_id = old.burrow_id();
+        let new_id = new.burrow_id();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let message = signed_message(&old_id, &new_id, timestamp);
+        Self {
+            old_signature: old.sign(&message),
+            new_signature: new.sign(&message),
+            old_id,
+            new_id,
+            timestamp,
+        }
+    }
+
+    /// Check both signatures.  Fails with `Forbidden` if either is
+    /// invalid.
+    pub fn verify(&self) -> Result<(), ProtocolError> {
+        if self.old_id == self.new_id {
+            return Err(ProtocolError::BadRequest(
+                "rotation must change the key".into(),
+            ));
+        }
+        let message = signed_message(&self.old_id, &self.new_id, self.timestamp);
+        Identity::verify(
+            &parse_burrow_id(&self.old_id)?,
+            &message,
+            &self.old_signature,
+        )?;
+        Identity::verify(
+            &parse_burrow_id(&self.new_id)?,
+            &message,
+            &self.new_signature,
+        )
+    }
+
+    /// The `ROTATE` frame announcing this rotation.
+    pub fn to_frame(&self) -> Frame {
+        let mut frame = Frame::new("ROTATE");
+        frame.set_header("Old-ID", &self.old_id);
+        frame.set_header("New-ID", &self.new_id);
+        frame.set_header("Timestamp", self.timestamp.to_string());
+        frame.set_header(
+            "Old-Signature",
+            format!("ed25519:{}", hex_encode(&self.old_signature)),
+        );
+        frame.set_header(
+            "New-Signature",
+            format!("ed25519:{}", hex_encode(&self.new_signature)),
+        );
+        frame
+    }
+
+    /// Read a rotation from a `ROTATE` frame.  The signatures are not
+    /// checked; see [`KeyRotation::verify`].
+    pub fn from_frame(frame: &Frame) -> Result<Self, ProtocolError> {
+        let header = |name: &str| {
+            frame
+                .header(name)
+                .ok_or_else(|| ProtocolError::BadRequest(format!("ROTATE missing {}", name)))
+        };
+        let signature = |name: &str| {
+            let value = header(name)?;
+            let hex = value.strip_prefix("ed25519:").unwrap_or(value);
+            hex_decode(hex)
+                .map_err(|e| ProtocolError::BadRequest(format!("invalid {}: {}", name, e)))
+        };
+        Ok(Self {
+            old_id: header("Old-ID")?.to_string(),
+            new_id: header("New-ID")?.to_string(),
+            timestamp: header("Timestamp")?
+                .parse()
+                .map_err(|_| ProtocolError::BadRequest("invalid Timestamp".into()))?,
+            old_signature: signature("Old-Signature")?,
+            new_signature: signature("New-Signature")?,
+        })
+    }
+
+    /// Write the record to a file as its `ROTATE` frame.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProtocolError> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                ProtocolError::InternalError(format!("failed to create directory: {}", e))
+            })?;
+        }
+        std::fs::write(path.as_ref(), self.to_frame().serialize()).map_err(|e| {
+            ProtocolError::InternalError(format!("failed to write rotation record: {}", e))
+        })
+    }
+
+    /// Read a record written by [`KeyRotation::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        let text = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            ProtocolError::InternalError(format!("failed to read rotation record: {}", e))
+        })?;
+        Self::from_frame(&Frame::parse(&text)?)
+    }
+}
+
+fn signed_message(old_id: &str, new_id: &str, timestamp: u64) -> Vec<u8> {
+    format!("RABBIT-ROTATE\n{}\n{}\n{}", old_id, new_id, timestamp).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_round_trips_and_verifies() {
+        let old = Identity::generate();
+        let (new, rotation) = old.rotate();
+        assert_eq!(rotation.old_id, old.burrow_id());
+        assert_eq!(rotation.new_id, new.burrow_id());
+        rotation.verify().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rotation.frame");
+        rotation.save(&path).unwrap();
+        assert_eq!(KeyRotation::load(&path).unwrap(), rotation);
+    }
+
+    #[test]
+    fn tampered_rotation_is_rejected() {
+        let old = Identity::generate();
+        let (_, mut rotation) = old.rotate();
+        rotation.new_id = Identity::generate().burrow_id();
+        assert!(matches!(
+            rotation.verify(),
+            Err(ProtocolError::Forbidden(_))
+        ));
+    }
+}
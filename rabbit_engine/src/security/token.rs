@@ -0,0 +1,262 @@
+//! Signed session tokens.
+//!
+//! The session token a burrow issues at the end of a handshake is
+//! signed by its identity, so any burrow can check one offline: the
+//! issuer's public key is its burrow ID.  A token is two base64url
+//! parts (no padding) joined by a dot, the claims and the issuer's
+//! Ed25519 signature over them.  The claims are text, one per line:
+//!
+//! ```text
+//! RBT1
+//! access                      -- or "refresh"
+//! ed25519:…                   -- issuer
+//! ed25519:…                   -- subject (the peer)
+//! <sha-256 hex>               -- digest of the subject's capabilities
+//! 1700000000                  -- issued at
+//! 1700086400                  -- expires at
+//! <hex>                       -- token ID
+//! ```
+//!
+//! An access token names a session; a longer-lived refresh token can
+//! be traded for a fresh pair with `REFRESH` (see
+//! [`crate::burrow::Burrow`]).
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use sha2::{Digest, Sha256};
+
+use crate::protocol::error::ProtocolError;
+use crate::util::now_unix;
+
+use super::auth::hex_encode;
+use super::identity::{parse_burrow_id, Identity};
+use super::permissions::Capability;
+
+/// First line of the claims, naming the format.
+const TOKEN_VERSION: &str = "RBT1";
+
+/// What a token may be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Names a session.
+    Access,
+    /// Can be exchanged for a new access token.
+    Refresh,
+}
+
+impl TokenKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Access => "access",
+            Self::Refresh => "refresh",
+        }
+    }
+}
+
+/// The claims of a session token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenClaims {
+    /// Access or refresh.
+    pub kind: TokenKind,
+    /// Burrow ID of the issuer, whose key signs the token.
+    pub issuer: String,
+    /// The peer the token was issued to.
+    pub subject: String,
+    /// [`caps_digest`] of the subject's capabilities at issue.
+    pub caps_digest: String,
+    /// Unix timestamp of issue.
+    pub issued_at: u64,
+    /// Unix timestamp after which the token is refused.
+    pub expires_at: u64,
+    /// Random token ID, so no two tokens are alike.
+    pub id: String,
+}
+
+impl TokenClaims {
+    /// Claims for a token issued now by `issuer` to `subject`, valid
+    /// for `ttl_secs`.
+    pub fn new(
+        kind: TokenKind,
+        issuer: &str,
+        subject: &str,
+        caps: &[Capability],
+        ttl_secs: u64,
+    ) -> Self {
+        use rand::RngCore;
+        let mut id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id);
+        let now = now_unix();
+        Self {
+            kind,
+            issuer: issuer.to_string(),
+            subject: subject.to_string(),
+            caps_digest: caps_digest(caps),
+            issued_at: now,
+            expires_at: now.saturating_add(ttl_secs),
+            id: hex_encode(&id),
+        }
+    }
+
+    /// Sign the claims with the issuer's identity, producing the
+    /// token.
+    pub fn sign(&self, identity: &Identity) -> String {
+        let payload = self.encode();
+        let signature = identity.sign(payload.as_bytes());
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    /// Check a token's signature against its issuer and its expiry,
+    /// returning its claims.
+    ///
+    /// Fails with `BadRequest` for a malformed token, `Forbidden` for
+    /// a bad signature, and `AuthRequired` once it has expired.  Which
+    /// issuers to accept is up to the caller.
+    pub fn verify(token: &str) -> Result<Self, ProtocolError> {
+        let (payload, signature) = token
+            .split_once('.')
+            .ok_or_else(|| malformed("missing signature"))?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| malformed("claims are not base64url"))?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| malformed("signature is not base64url"))?;
+        let claims = std::str::from_utf8(&payload)
+            .map_err(|_| malformed("claims are not UTF-8"))
+            .and_then(Self::decode)?;
+        Identity::verify(&parse_burrow_id(&claims.issuer)?, &payload, &signature).map_err(
+            |_| ProtocolError::Forbidden(format!("token is not signed by {}", claims.issuer)),
+        )?;
+        if claims.expires_at <= now_unix() {
+            return Err(ProtocolError::AuthRequired("token has expired".into()));
+        }
+        Ok(claims)
+    }
+
+    /// [`TokenClaims::verify`], also requiring the token to be of
+    /// `kind` and issued by `issuer`.
+    pub fn verify_from(token: &str, issuer: &str, kind: TokenKind) -> Result<Self, ProtocolError> {
+        let claims = Self::verify(token)?;
+        if claims.issuer != issuer {
+            return Err(ProtocolError::Forbidden(format!(
+                "token was issued by {}",
+                claims.issuer
+            )));
+        }
+        if claims.kind != kind {
+            return Err(ProtocolError::BadRequest(format!(
+                "expected a {} token",
+                kind.as_str()
+            )));
+        }
+        Ok(claims)
+    }
+
+    fn encode(&self) -> String {
+        [
+            TOKEN_VERSION,
+            self.kind.as_str(),
+            &self.issuer,
+            &self.subject,
+            &self.caps_digest,
+            &self.issued_at.to_string(),
+            &self.expires_at.to_string(),
+            &self.id,
+        ]
+        .join("\n")
+    }
+
+    fn decode(text: &str) -> Result<Self, ProtocolError> {
+        let lines: Vec<&str> = text.split('\n').collect();
+        let [version, kind, issuer, subject, caps_digest, issued_at, expires_at, id] = lines[..]
+        else {
+            return Err(malformed("wrong number of claims"));
+        };
+        if version != TOKEN_VERSION {
+            return Err(malformed(&format!("unknown version {:?}", version)));
+        }
+        let kind = match kind {
+            "access" => TokenKind::Access,
+            "refresh" => TokenKind::Refresh,
+            other => return Err(malformed(&format!("unknown kind {:?}", other))),
+        };
+        let time = |s: &str| s.parse().map_err(|_| malformed("bad timestamp"));
+        Ok(Self {
+            kind,
+            issuer: issuer.to_string(),
+            subject: subject.to_string(),
+            caps_digest: caps_digest.to_string(),
+            issued_at: time(issued_at)?,
+            expires_at: time(expires_at)?,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// SHA-256 hex of the sorted capability labels, comma-separated.
+pub fn caps_digest(caps: &[Capability]) -> String {
+    let mut labels: Vec<&str> = caps.iter().map(|c| c.label()).collect();
+    labels.sort_unstable();
+    labels.dedup();
+    hex_encode(&Sha256::digest(labels.join(",").as_bytes()))
+}
+
+fn malformed(what: &str) -> ProtocolError {
+    ProtocolError::BadRequest(format!("malformed token: {}", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_verifies_offline() {
+        let issuer = Identity::generate();
+        let caps = [Capability::Fetch, Capability::List];
+        let token = issuer.issue_token(TokenKind::Access, "ed25519:PEER", &caps, 60);
+
+        let claims = TokenClaims::verify(&token).unwrap();
+        assert_eq!(claims.issuer, issuer.burrow_id());
+        assert_eq!(claims.subject, "ed25519:PEER");
+        assert_eq!(
+            claims.caps_digest,
+            caps_digest(&[Capability::List, Capability::Fetch])
+        );
+        assert!(TokenClaims::verify_from(&token, &issuer.burrow_id(), TokenKind::Access).is_ok());
+        assert!(TokenClaims::verify_from(&token, &issuer.burrow_id(), TokenKind::Refresh).is_err());
+        assert!(matches!(
+            TokenClaims::verify_from(&token, "ed25519:OTHER", TokenKind::Access),
+            Err(ProtocolError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn forged_and_expired_tokens_are_refused() {
+        let issuer = Identity::generate();
+        let mut claims = TokenClaims::new(TokenKind::Access, &issuer.burrow_id(), "p", &[], 60);
+        let token = claims.sign(&issuer);
+
+        // Claims swapped under the original signature.
+        claims.subject = "someone-else".into();
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = format!("{}.{}", URL_SAFE_NO_PAD.encode(claims.encode()), signature);
+        assert!(matches!(
+            TokenClaims::verify(&forged),
+            Err(ProtocolError::Forbidden(_))
+        ));
+
+        let expired = TokenClaims::new(TokenKind::Access, &issuer.burrow_id(), "p", &[], 0);
+        assert!(matches!(
+            TokenClaims::verify(&expired.sign(&issuer)),
+            Err(ProtocolError::AuthRequired(_))
+        ));
+        assert!(matches!(
+            TokenClaims::verify("not-a-token"),
+            Err(ProtocolError::BadRequest(_))
+        ));
+    }
+}
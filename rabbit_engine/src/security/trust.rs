@@ -3,23 +3,85 @@
 //! When a burrow connects for the first time, its public key fingerprint
 //! is recorded.  Subsequent connections verify that the key matches.
 //! If a different key appears for a known burrow ID, the connection is
-//! rejected.
+//! rejected, or, if the cache quarantines mismatches (see
+//! [`TrustCache::set_quarantine_mismatches`]), the peer is admitted but
+//! quarantined.
+//!
+//! Every entry has a [`PeerStatus`].  An operator can **revoke** a peer,
+//! in which case every connection from that burrow ID is rejected
+//! regardless of key, or **quarantine** it with a reason, in which case
+//! it is still admitted but the burrow limits or refuses its sessions.
+//!
+//! Since a burrow ID is its key, a peer that rotates its key arrives
+//! under a new ID.  A verified [`KeyRotation`] carries the old entry's
+//! history and status over to the new ID, and the old ID is refused
+//! from then on.
+//!
+//! A peer whose TLS certificate was bound to its ID also has the
+//! certificate's SHA-256 fingerprint recorded (see
+//! [`TrustCache::bind_certificate`]).  Those fingerprints let a client
+//! refuse an unexpected server during the TLS handshake itself, before
+//! any Rabbit frame is exchanged: see [`PinnedCerts`] and [`TlsVerify`].
 //!
 //! The cache is persisted as **tab-separated text** (no JSON) with one
 //! peer per line:
 //!
 //! ```text
-//! <burrow_id>\t<fingerprint>\t<first_seen>\t<last_seen>\n
+//! <burrow_id>\t<fingerprint>\t<first_seen>\t<last_seen>[\trevoked|\tquarantined:<reason>][\trotated:<new_id>][\tcert:<sha256>]\n
+//! ```
+//!
+//! Timestamps are Unix epoch seconds.  A revoked or quarantined peer
+//! that was never seen has the fingerprint `-`.  Older caches marked
+//! revoked peers `blocked`, which is still read.
+//!
+//! Before pinning a key, the cache asks its [`TrustPolicy`] whether to
+//! admit the peer at all, telling it which anchors vouch for the peer
+//! in the [`TrustManifest`]s the cache holds.  An anchor vouches for a
+//! peer if its manifest lists it, or if a [`ManifestChain`] of held
+//! manifests leads from it through sub-anchors to one that does.  The
+//! default policy, [`TofuOnly`], admits everyone.
+//!
+//! ## Trust bundles
+//!
+//! A burrow can hand everything it trusts to a new burrow of the same
+//! family as a signed **trust bundle** (see [`TrustCache::export_bundle`]),
+//! so the newcomer need not TOFU every peer again.  A bundle is a
+//! `TRUST-BUNDLE` frame whose body holds one `peer` line per cache entry
+//! (the TSV line above) and one `manifest` line per manifest (its
+//! `MANIFEST` frame, base64url without padding):
+//!
+//! ```text
+//! TRUST-BUNDLE
+//! Signer: ed25519:…
+//! Issued: 1700000000
+//! Signature: ed25519:<hex>
+//! Length: 420
+//! End:
+//! peer\ted25519:…\t<fingerprint>\t<first_seen>\t<last_seen>[\t…]
+//! manifest\t<base64url>
 //! ```
 //!
-//! Timestamps are Unix epoch seconds.
+//! The signature covers `RABBIT-TRUST-BUNDLE\n<signer>\n<issued>\n<body>`.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use sha2::{Digest, Sha256};
+use tracing::warn;
 
 use crate::protocol::error::ProtocolError;
-use crate::security::identity::fingerprint;
+use crate::protocol::frame::Frame;
+use crate::security::auth::{hex_decode, hex_encode};
+use crate::security::identity::{fingerprint, parse_burrow_id, Identity};
+use crate::security::identity_cert::extract_rabbit_id_from_cert;
+use crate::security::manifest::{ManifestChain, TrustManifest};
+use crate::security::rotation::KeyRotation;
+use crate::security::trust_policy::{TofuOnly, TrustPolicy, TrustRequest};
+use crate::transport::connector::ServerCertPolicy;
+use crate::util::now_unix;
 
 /// A trusted peer entry.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -32,12 +94,153 @@ pub struct TrustedPeer {
     pub first_seen: u64,
     /// Unix timestamp when the peer was last seen.
     pub last_seen: u64,
+    /// Whether the peer is trusted, quarantined or revoked.
+    pub status: PeerStatus,
+    /// The ID this peer's key was rotated to, if it has been retired.
+    pub rotated_to: Option<String>,
+    /// SHA-256 hex fingerprint of the last TLS certificate seen bound
+    /// to this ID.
+    pub certificate: Option<String>,
+}
+
+impl TrustedPeer {
+    /// The entry as a line of the TSV format, without the newline.
+    fn to_line(&self) -> String {
+        let mut line = format!(
+            "{}\t{}\t{}\t{}",
+            self.burrow_id, self.fingerprint, self.first_seen, self.last_seen
+        );
+        match &self.status {
+            PeerStatus::Trusted => {}
+            PeerStatus::Quarantined { reason } => {
+                line.push_str("\tquarantined:");
+                line.push_str(reason);
+            }
+            PeerStatus::Revoked => line.push_str("\trevoked"),
+        }
+        if let Some(new_id) = &self.rotated_to {
+            line.push_str("\trotated:");
+            line.push_str(new_id);
+        }
+        if let Some(cert) = &self.certificate {
+            line.push_str("\tcert:");
+            line.push_str(cert);
+        }
+        line
+    }
+
+    /// Parse a line written by [`TrustedPeer::to_line`].  `what` names
+    /// the line in errors, e.g. `trust cache line 3`.
+    fn from_line(line: &str, what: &str) -> Result<Self, ProtocolError> {
+        let parts: Vec<&str> = line.split('\t').collect();
+        if !(4..=7).contains(&parts.len()) {
+            return Err(ProtocolError::InternalError(format!(
+                "{}: expected 4 to 7 tab-separated fields, got {}",
+                what,
+                parts.len()
+            )));
+        }
+        let first_seen: u64 = parts[2].parse().map_err(|_| {
+            ProtocolError::InternalError(format!("{}: invalid first_seen timestamp", what))
+        })?;
+        let last_seen: u64 = parts[3].parse().map_err(|_| {
+            ProtocolError::InternalError(format!("{}: invalid last_seen timestamp", what))
+        })?;
+        Ok(Self {
+            burrow_id: parts[0].to_string(),
+            fingerprint: parts[1].to_string(),
+            first_seen,
+            last_seen,
+            status: if parts[4..]
+                .iter()
+                .any(|f| *f == "revoked" || *f == "blocked")
+            {
+                PeerStatus::Revoked
+            } else if let Some(reason) = parts[4..]
+                .iter()
+                .find_map(|f| f.strip_prefix("quarantined:"))
+            {
+                PeerStatus::Quarantined {
+                    reason: reason.to_string(),
+                }
+            } else {
+                PeerStatus::Trusted
+            },
+            rotated_to: parts[4..]
+                .iter()
+                .find_map(|f| f.strip_prefix("rotated:"))
+                .map(str::to_string),
+            certificate: parts[4..]
+                .iter()
+                .find_map(|f| f.strip_prefix("cert:"))
+                .map(str::to_string),
+        })
+    }
+}
+
+/// How far a peer in the trust cache is trusted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PeerStatus {
+    /// Admitted as usual.
+    #[default]
+    Trusted,
+    /// Admitted, but its sessions are limited or refused; see
+    /// `trust.quarantine` in [`crate::config::TrustConfig`].
+    Quarantined {
+        /// Why the peer was quarantined.
+        reason: String,
+    },
+    /// Refused outright.
+    Revoked,
+}
+
+impl PeerStatus {
+    /// `trusted`, `quarantined` or `revoked`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Trusted => "trusted",
+            Self::Quarantined { .. } => "quarantined",
+            Self::Revoked => "revoked",
+        }
+    }
+
+    /// The stricter of two statuses; revoked beats quarantined beats
+    /// trusted.
+    fn stricter(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Revoked, _) | (_, Self::Revoked) => Self::Revoked,
+            (q @ Self::Quarantined { .. }, _) | (_, q @ Self::Quarantined { .. }) => q,
+            _ => Self::Trusted,
+        }
+    }
+}
+
+/// What [`TrustCache::import_bundle`] changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BundleImport {
+    /// Peers the cache did not know.
+    pub added: usize,
+    /// Known peers that gained a fingerprint, stricter status, rotation
+    /// or certificate from the bundle.
+    pub updated: usize,
+    /// Peers whose fingerprint in the bundle contradicts the one pinned
+    /// here; the local entry is kept.
+    pub conflicts: Vec<String>,
+    /// Manifests kept, being newer than any held from their anchor.
+    pub manifests: usize,
 }
 
 /// In-memory TOFU trust cache.
 #[derive(Debug, Clone)]
 pub struct TrustCache {
     peers: HashMap<String, TrustedPeer>,
+    /// The newest verified manifest from each anchor.
+    manifests: HashMap<String, TrustManifest>,
+    /// Decides which peers may be admitted.
+    policy: Arc<dyn TrustPolicy>,
+    /// Whether a key mismatch quarantines the peer instead of refusing
+    /// it.
+    quarantine_mismatches: bool,
 }
 
 impl TrustCache {
@@ -45,6 +248,115 @@ impl TrustCache {
     pub fn new() -> Self {
         Self {
             peers: HashMap::new(),
+            manifests: HashMap::new(),
+            policy: Arc::new(TofuOnly),
+            quarantine_mismatches: false,
+        }
+    }
+
+    /// Replace the policy consulted before admitting a peer.
+    pub fn set_policy(&mut self, policy: Arc<dyn TrustPolicy>) {
+        self.policy = policy;
+    }
+
+    /// Quarantine a known peer that presents a different key instead of
+    /// refusing it (default: refuse).
+    pub fn set_quarantine_mismatches(&mut self, quarantine: bool) {
+        self.quarantine_mismatches = quarantine;
+    }
+
+    /// Keep a manifest once its signature and expiry check out.  A
+    /// manifest whose serial is no higher than the one already held
+    /// from its anchor is superseded and ignored.  Returns whether it
+    /// was kept.
+    pub fn add_manifest(&mut self, manifest: TrustManifest) -> Result<bool, ProtocolError> {
+        manifest.verify()?;
+        if let Some(held) = self.manifests.get(&manifest.anchor) {
+            if !manifest.supersedes(held) {
+                return Ok(false);
+            }
+        }
+        self.manifests.insert(manifest.anchor.clone(), manifest);
+        Ok(true)
+    }
+
+    /// The manifests held, sorted by anchor.
+    pub fn manifests(&self) -> Vec<&TrustManifest> {
+        let mut manifests: Vec<&TrustManifest> = self.manifests.values().collect();
+        manifests.sort_by(|a, b| a.anchor.cmp(&b.anchor));
+        manifests
+    }
+
+    /// Add every `*.manifest` file in `dir` written by
+    /// [`TrustCache::save_manifests`].  A missing directory holds no
+    /// manifests; unreadable or forged files are skipped with a warning.
+    pub fn load_manifests(&mut self, dir: impl AsRef<Path>) {
+        let files = std::fs::read_dir(dir.as_ref())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "manifest"));
+        for path in files {
+            if let Err(e) = TrustManifest::load(&path).and_then(|m| self.add_manifest(m)) {
+                warn!(path = %path.display(), err = %e, "ignoring trust manifest");
+            }
+        }
+    }
+
+    /// Write each manifest held to `dir`, one
+    /// [`TrustManifest::file_name`] per anchor.
+    pub fn save_manifests(&self, dir: impl AsRef<Path>) -> Result<(), ProtocolError> {
+        for manifest in self.manifests.values() {
+            manifest.save(dir.as_ref().join(manifest.file_name()?))?;
+        }
+        Ok(())
+    }
+
+    /// The anchors vouching for `burrow_id`, sorted: those whose
+    /// unexpired manifests list it without revoking it, and the roots
+    /// of every verified [`ManifestChain`] through sub-anchors to such
+    /// a manifest.
+    pub fn anchors_for(&self, burrow_id: &str) -> Vec<&str> {
+        let mut anchors: Vec<&str> = Vec::new();
+        let mut path = Vec::new();
+        for manifest in self.manifests.values().filter(|m| m.lists(burrow_id)) {
+            path.push(manifest);
+            self.collect_roots(&mut path, &mut anchors);
+            path.pop();
+        }
+        anchors.sort_unstable();
+        anchors.dedup();
+        anchors
+    }
+
+    /// Whether `burrow_id` is an anchor whose unexpired manifest is
+    /// held, or is vouched for by one.
+    pub fn is_vouched_for(&self, burrow_id: &str) -> bool {
+        self.manifests
+            .get(burrow_id)
+            .is_some_and(|m| !m.is_expired())
+            || !self.anchors_for(burrow_id).is_empty()
+    }
+
+    /// Add to `roots` the anchor of every manifest chain ending with
+    /// `path` (held leaf first) that verifies, walking up through
+    /// manifests that make the top of `path` a sub-anchor.
+    fn collect_roots<'a>(&'a self, path: &mut Vec<&'a TrustManifest>, roots: &mut Vec<&'a str>) {
+        let top = path[path.len() - 1];
+        let chain = ManifestChain::new(path.iter().rev().map(|m| (*m).clone()).collect());
+        let verified = parse_burrow_id(&top.anchor)
+            .and_then(|root| chain.verify(&root))
+            .is_ok();
+        if verified {
+            roots.push(&top.anchor);
+        }
+        for parent in self.manifests.values() {
+            if parent.delegates_to(&top.anchor) && !path.iter().any(|m| m.anchor == parent.anchor) {
+                path.push(parent);
+                self.collect_roots(path, roots);
+                path.pop();
+            }
         }
     }
 
@@ -58,29 +370,75 @@ impl TrustCache {
         self.peers.is_empty()
     }
 
-    /// Verify a peer's identity or remember it on first contact.
+    /// Verify a peer's identity or remember it on first contact, and
+    /// return the status it is admitted with.
     ///
     /// - If the burrow ID is unknown: record it (TOFU) and return `Ok`.
+    /// - If revoked: return `Err` without updating the entry.
     /// - If known and the fingerprint matches: update `last_seen`, return `Ok`.
-    /// - If known but the fingerprint differs: return `Err` (key mismatch).
+    /// - If known but the fingerprint differs: return `Err` (key mismatch),
+    ///   or quarantine the peer if mismatches are quarantined.
+    /// - If the key was rotated away: return `Err`.
+    ///
+    /// A quarantined peer is admitted as `Quarantined`; what its session
+    /// may do is up to the caller.  A peer that passes these checks must
+    /// still be admitted by the trust policy; if it is refused, nothing
+    /// is recorded.
     pub fn verify_or_remember(
         &mut self,
         burrow_id: &str,
         pubkey_bytes: &[u8; 32],
-    ) -> Result<(), ProtocolError> {
+    ) -> Result<PeerStatus, ProtocolError> {
         let fp = fingerprint(pubkey_bytes);
         let now = now_unix();
 
+        let mut mismatch = None;
+        if let Some(existing) = self.peers.get(burrow_id) {
+            if existing.status == PeerStatus::Revoked {
+                return Err(ProtocolError::Forbidden(format!(
+                    "{} is revoked",
+                    burrow_id
+                )));
+            } else if let Some(new_id) = &existing.rotated_to {
+                return Err(ProtocolError::Forbidden(format!(
+                    "key for {} was rotated to {}",
+                    burrow_id, new_id
+                )));
+            } else if existing.fingerprint != fp && existing.fingerprint != "-" {
+                let reason = format!(
+                    "key mismatch: expected fingerprint {}, got {}",
+                    existing.fingerprint, fp
+                );
+                if !self.quarantine_mismatches {
+                    return Err(ProtocolError::Forbidden(format!(
+                        "{} for {}",
+                        reason, burrow_id
+                    )));
+                }
+                mismatch = Some(reason);
+            }
+        }
+
+        let anchors = self.anchors_for(burrow_id);
+        self.policy.admit(&TrustRequest {
+            burrow_id,
+            fingerprint: &fp,
+            first_contact: !self.peers.contains_key(burrow_id),
+            anchors: &anchors,
+        })?;
+
         if let Some(existing) = self.peers.get_mut(burrow_id) {
-            if existing.fingerprint == fp {
-                existing.last_seen = now;
-                Ok(())
-            } else {
-                Err(ProtocolError::Forbidden(format!(
-                    "key mismatch for {}: expected fingerprint {}, got {}",
-                    burrow_id, existing.fingerprint, fp
-                )))
+            existing.last_seen = now;
+            if existing.fingerprint == "-" {
+                existing.fingerprint = fp;
             }
+            if let Some(reason) = mismatch {
+                if existing.status == PeerStatus::Trusted {
+                    warn!(peer = %burrow_id, %reason, "quarantining peer");
+                    existing.status = PeerStatus::Quarantined { reason };
+                }
+            }
+            Ok(existing.status.clone())
         } else {
             self.peers.insert(
                 burrow_id.to_string(),
@@ -89,10 +447,165 @@ impl TrustCache {
                     fingerprint: fp,
                     first_seen: now,
                     last_seen: now,
+                    status: PeerStatus::Trusted,
+                    rotated_to: None,
+                    certificate: None,
                 },
             );
-            Ok(())
+            Ok(PeerStatus::Trusted)
+        }
+    }
+
+    /// Hand the trust of a rotation's old ID on to its new ID.
+    ///
+    /// The rotation's signatures are checked first.  The new ID takes
+    /// over the old entry's `first_seen` and status, and the old ID is
+    /// marked as rotated so its key is refused from now on.  Fails with
+    /// `Missing` if the old ID was never trusted, and with `Forbidden`
+    /// if the old ID was already rotated to a different key.
+    pub fn apply_rotation(&mut self, rotation: &KeyRotation) -> Result<(), ProtocolError> {
+        rotation.verify()?;
+        let old = self.peers.get(&rotation.old_id).cloned().ok_or_else(|| {
+            ProtocolError::Missing(format!("{} is not a known peer", rotation.old_id))
+        })?;
+        match &old.rotated_to {
+            Some(to) if *to == rotation.new_id => return Ok(()),
+            Some(to) => {
+                return Err(ProtocolError::Forbidden(format!(
+                    "{} was already rotated to {}",
+                    rotation.old_id, to
+                )))
+            }
+            None => {}
         }
+
+        let now = now_unix();
+        let fp = fingerprint(&parse_burrow_id(&rotation.new_id)?);
+        let new = self
+            .peers
+            .entry(rotation.new_id.clone())
+            .or_insert_with(|| TrustedPeer {
+                burrow_id: rotation.new_id.clone(),
+                fingerprint: fp.clone(),
+                first_seen: now,
+                last_seen: now,
+                status: PeerStatus::Trusted,
+                rotated_to: None,
+                certificate: None,
+            });
+        new.fingerprint = fp;
+        new.first_seen = new.first_seen.min(old.first_seen);
+        new.status = std::mem::take(&mut new.status).stricter(old.status);
+        if let Some(entry) = self.peers.get_mut(&rotation.old_id) {
+            entry.rotated_to = Some(rotation.new_id.clone());
+        }
+        Ok(())
+    }
+
+    /// Record that `cert_der` was presented bound to a known peer's ID.
+    ///
+    /// Call after the peer has proven its ID; fails with `Missing` for
+    /// an unknown peer.
+    pub fn bind_certificate(
+        &mut self,
+        burrow_id: &str,
+        cert_der: &[u8],
+    ) -> Result<(), ProtocolError> {
+        let peer = self
+            .peers
+            .get_mut(burrow_id)
+            .ok_or_else(|| ProtocolError::Missing(format!("{} is not a known peer", burrow_id)))?;
+        peer.certificate = Some(hex_encode(&Sha256::digest(cert_der)));
+        Ok(())
+    }
+
+    /// The certificates a client may accept from the peers in the
+    /// cache, as of now; see [`PinnedCerts`].
+    pub fn pinned_certs(&self) -> PinnedCerts {
+        let mut pinned = PinnedCerts::default();
+        for peer in self.peers.values() {
+            if peer.status == PeerStatus::Revoked || peer.rotated_to.is_some() {
+                pinned.refused.insert(peer.burrow_id.clone());
+                continue;
+            }
+            pinned.known.insert(peer.burrow_id.clone());
+            if let Some(cert) = &peer.certificate {
+                pinned.certs.insert(cert.clone(), peer.burrow_id.clone());
+            }
+        }
+        pinned
+    }
+
+    /// Revoke a peer, so every connection from it is refused.  Known
+    /// peers keep their pinned fingerprint; unknown peers get a
+    /// placeholder entry so the revocation persists.
+    pub fn revoke(&mut self, burrow_id: &str) {
+        self.entry_or_placeholder(burrow_id).status = PeerStatus::Revoked;
+    }
+
+    /// Quarantine a peer: it is still admitted, but with
+    /// [`PeerStatus::Quarantined`], for the burrow to limit.  Like
+    /// [`TrustCache::revoke`], this works on peers not yet seen.  A
+    /// revoked peer stays revoked.
+    pub fn quarantine(&mut self, burrow_id: &str, reason: &str) {
+        let peer = self.entry_or_placeholder(burrow_id);
+        if peer.status != PeerStatus::Revoked {
+            // The reason shares a line of the TSV file.
+            let reason = reason.replace(['\t', '\n', '\r'], " ");
+            peer.status = PeerStatus::Quarantined { reason };
+        }
+    }
+
+    /// Returns true if the peer is revoked.
+    pub fn is_revoked(&self, burrow_id: &str) -> bool {
+        self.status(burrow_id) == Some(&PeerStatus::Revoked)
+    }
+
+    /// A known peer's status.
+    pub fn status(&self, burrow_id: &str) -> Option<&PeerStatus> {
+        self.peers.get(burrow_id).map(|p| &p.status)
+    }
+
+    fn entry_or_placeholder(&mut self, burrow_id: &str) -> &mut TrustedPeer {
+        let now = now_unix();
+        self.peers
+            .entry(burrow_id.to_string())
+            .or_insert_with(|| TrustedPeer {
+                burrow_id: burrow_id.to_string(),
+                fingerprint: "-".into(),
+                first_seen: now,
+                last_seen: now,
+                status: PeerStatus::Trusted,
+                rotated_to: None,
+                certificate: None,
+            })
+    }
+
+    /// Update a known peer's `last_seen` to now.
+    pub fn touch(&mut self, burrow_id: &str) {
+        if let Some(peer) = self.peers.get_mut(burrow_id) {
+            peer.last_seen = now_unix();
+        }
+    }
+
+    /// Unrevoked peers not seen for more than `max_idle_secs`, sorted.
+    pub fn stale_peers(&self, max_idle_secs: u64) -> Vec<String> {
+        let cutoff = now_unix().saturating_sub(max_idle_secs);
+        let mut ids: Vec<String> = self
+            .peers
+            .values()
+            .filter(|p| p.status != PeerStatus::Revoked && p.last_seen < cutoff)
+            .map(|p| p.burrow_id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// List all entries, sorted by burrow ID.
+    pub fn entries(&self) -> Vec<&TrustedPeer> {
+        let mut entries: Vec<&TrustedPeer> = self.peers.values().collect();
+        entries.sort_by(|a, b| a.burrow_id.cmp(&b.burrow_id));
+        entries
     }
 
     /// Look up a trusted peer by burrow ID.
@@ -114,7 +627,7 @@ impl TrustCache {
 
     /// Save the trust cache to a TSV file.
     ///
-    /// Format: `<burrow_id>\t<fingerprint>\t<first_seen>\t<last_seen>\n`
+    /// Format: `<burrow_id>\t<fingerprint>\t<first_seen>\t<last_seen>[\trevoked|\tquarantined:<reason>][\trotated:<new_id>][\tcert:<sha256>]\n`
     pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProtocolError> {
         let dir = path.as_ref().parent();
         if let Some(d) = dir {
@@ -125,17 +638,9 @@ impl TrustCache {
             }
         }
         let mut content = String::new();
-        // Sort by burrow_id for deterministic output.
-        let mut entries: Vec<&TrustedPeer> = self.peers.values().collect();
-        entries.sort_by_key(|p| &p.burrow_id);
-        for peer in entries {
-            content.push_str(&peer.burrow_id);
-            content.push('\t');
-            content.push_str(&peer.fingerprint);
-            content.push('\t');
-            content.push_str(&peer.first_seen.to_string());
-            content.push('\t');
-            content.push_str(&peer.last_seen.to_string());
+        // Sorted by burrow_id for deterministic output.
+        for peer in self.entries() {
+            content.push_str(&peer.to_line());
             content.push('\n');
         }
         std::fs::write(path.as_ref(), content).map_err(|e| {
@@ -159,56 +664,311 @@ impl TrustCache {
             if line.trim().is_empty() {
                 continue;
             }
-            let parts: Vec<&str> = line.split('\t').collect();
-            if parts.len() != 4 {
-                return Err(ProtocolError::InternalError(format!(
-                    "trust cache line {}: expected 4 tab-separated fields, got {}",
-                    line_num + 1,
-                    parts.len()
-                )));
+            let peer = TrustedPeer::from_line(line, &format!("trust cache line {}", line_num + 1))?;
+            peers.insert(peer.burrow_id.clone(), peer);
+        }
+        Ok(Self {
+            peers,
+            ..Self::new()
+        })
+    }
+
+    /// Sign a trust bundle of every peer and manifest held, for
+    /// [`TrustCache::import_bundle`] on another burrow.  The signer is
+    /// listed too, so the importer pins it as well.
+    pub fn export_bundle(&self, identity: &Identity) -> String {
+        let signer = identity.burrow_id();
+        let issued = now_unix();
+        let mut entries: Vec<TrustedPeer> = self.entries().into_iter().cloned().collect();
+        if self.get(&signer).is_none() {
+            entries.push(TrustedPeer {
+                burrow_id: signer.clone(),
+                fingerprint: fingerprint(&identity.public_key_bytes()),
+                first_seen: issued,
+                last_seen: issued,
+                status: PeerStatus::Trusted,
+                rotated_to: None,
+                certificate: None,
+            });
+        }
+        let mut body = String::new();
+        for peer in entries {
+            body.push_str("peer\t");
+            body.push_str(&peer.to_line());
+            body.push('\n');
+        }
+        for manifest in self.manifests().into_iter().filter(|m| !m.is_expired()) {
+            body.push_str("manifest\t");
+            body.push_str(&URL_SAFE_NO_PAD.encode(manifest.to_frame().serialize()));
+            body.push('\n');
+        }
+
+        let signature = identity.sign(&bundle_message(&signer, issued, &body));
+        let mut frame = Frame::new("TRUST-BUNDLE");
+        frame.set_header("Signer", &signer);
+        frame.set_header("Issued", issued.to_string());
+        frame.set_header("Signature", format!("ed25519:{}", hex_encode(&signature)));
+        frame.set_body(body);
+        frame.serialize()
+    }
+
+    /// Merge a bundle written by [`TrustCache::export_bundle`], which
+    /// must be signed by `signer`.
+    ///
+    /// Unknown peers are added as they appear in the bundle.  A known
+    /// peer with the same fingerprint gains any stricter status,
+    /// rotation or certificate the bundle records, and a placeholder
+    /// gains the bundle's fingerprint.  A known peer whose fingerprint differs
+    /// keeps its own and is reported as a conflict.  Manifests are
+    /// added as by [`TrustCache::add_manifest`].
+    ///
+    /// Fails with `BadRequest` for a malformed bundle and `Forbidden`
+    /// if it is not signed by `signer` or carries a forged manifest;
+    /// the cache is unchanged on failure.
+    pub fn import_bundle(
+        &mut self,
+        bundle: &str,
+        signer: &str,
+    ) -> Result<BundleImport, ProtocolError> {
+        let frame = Frame::parse(bundle)?;
+        if frame.verb != "TRUST-BUNDLE" {
+            return Err(ProtocolError::BadRequest(format!(
+                "expected TRUST-BUNDLE, got {}",
+                frame.verb
+            )));
+        }
+        let header = |name: &str| {
+            frame
+                .header(name)
+                .ok_or_else(|| ProtocolError::BadRequest(format!("TRUST-BUNDLE missing {}", name)))
+        };
+        if header("Signer")? != signer {
+            return Err(ProtocolError::Forbidden(format!(
+                "trust bundle is signed by {}, not {}",
+                header("Signer")?,
+                signer
+            )));
+        }
+        let issued: u64 = header("Issued")?
+            .parse()
+            .map_err(|_| ProtocolError::BadRequest("invalid Issued".into()))?;
+        let signature = header("Signature")?;
+        let signature = hex_decode(signature.strip_prefix("ed25519:").unwrap_or(signature))
+            .map_err(|e| ProtocolError::BadRequest(format!("invalid Signature: {}", e)))?;
+        let body = frame.body.as_deref().unwrap_or("");
+        Identity::verify(
+            &parse_burrow_id(signer)?,
+            &bundle_message(signer, issued, body),
+            &signature,
+        )
+        .map_err(|_| {
+            ProtocolError::Forbidden(format!("trust bundle is not signed by {}", signer))
+        })?;
+
+        // Parse and check everything before touching the cache.
+        let mut peers = Vec::new();
+        let mut manifests = Vec::new();
+        for (line_num, line) in body.lines().enumerate() {
+            let what = format!("trust bundle line {}", line_num + 1);
+            match line.split_once('\t') {
+                Some(("peer", entry)) => peers.push(
+                    TrustedPeer::from_line(entry, &what)
+                        .map_err(|e| ProtocolError::BadRequest(e.to_string()))?,
+                ),
+                Some(("manifest", encoded)) => {
+                    let manifest = URL_SAFE_NO_PAD
+                        .decode(encoded)
+                        .ok()
+                        .and_then(|bytes| String::from_utf8(bytes).ok())
+                        .ok_or_else(|| {
+                            ProtocolError::BadRequest(format!(
+                                "{}: manifest is not base64url",
+                                what
+                            ))
+                        })
+                        .and_then(|text| TrustManifest::from_frame(&Frame::parse(&text)?))?;
+                    // One that expired in transit is dropped, not fatal.
+                    if !manifest.is_expired() {
+                        manifest.verify()?;
+                        manifests.push(manifest);
+                    }
+                }
+                _ if line.is_empty() => {}
+                _ => {
+                    return Err(ProtocolError::BadRequest(format!(
+                        "{}: expected a peer or manifest",
+                        what
+                    )))
+                }
             }
-            let first_seen: u64 = parts[2].parse().map_err(|_| {
-                ProtocolError::InternalError(format!(
-                    "trust cache line {}: invalid first_seen timestamp",
-                    line_num + 1
-                ))
-            })?;
-            let last_seen: u64 = parts[3].parse().map_err(|_| {
-                ProtocolError::InternalError(format!(
-                    "trust cache line {}: invalid last_seen timestamp",
-                    line_num + 1
-                ))
-            })?;
-            let peer = TrustedPeer {
-                burrow_id: parts[0].to_string(),
-                fingerprint: parts[1].to_string(),
-                first_seen,
-                last_seen,
+        }
+
+        let mut import = BundleImport::default();
+        for peer in peers {
+            let Some(local) = self.peers.get_mut(&peer.burrow_id) else {
+                self.peers.insert(peer.burrow_id.clone(), peer);
+                import.added += 1;
+                continue;
             };
-            peers.insert(peer.burrow_id.clone(), peer);
+            let mut changed = false;
+            if local.fingerprint == "-" && peer.fingerprint != "-" {
+                local.fingerprint = peer.fingerprint;
+                changed = true;
+            } else if peer.fingerprint != "-" && local.fingerprint != peer.fingerprint {
+                import.conflicts.push(peer.burrow_id);
+                continue;
+            }
+            let status = local.status.clone().stricter(peer.status);
+            if status != local.status {
+                local.status = status;
+                changed = true;
+            }
+            if local.rotated_to.is_none() && peer.rotated_to.is_some() {
+                local.rotated_to = peer.rotated_to;
+                changed = true;
+            }
+            if local.certificate.is_none() && peer.certificate.is_some() {
+                local.certificate = peer.certificate;
+                changed = true;
+            }
+            local.first_seen = local.first_seen.min(peer.first_seen);
+            if changed {
+                import.updated += 1;
+            }
         }
-        Ok(Self { peers })
+        for manifest in manifests {
+            if self.add_manifest(manifest)? {
+                import.manifests += 1;
+            }
+        }
+        Ok(import)
     }
 }
 
+/// The bytes a trust bundle's signature covers.
+fn bundle_message(signer: &str, issued: u64, body: &str) -> Vec<u8> {
+    format!("RABBIT-TRUST-BUNDLE\n{}\n{}\n{}", signer, issued, body).into_bytes()
+}
+
 impl Default for TrustCache {
     fn default() -> Self {
         Self::new()
     }
 }
 
-/// Current time as Unix epoch seconds.
-fn now_unix() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs()
+// ── TLS pinning ────────────────────────────────────────────────
+
+/// How an outgoing connection checks the server's TLS certificate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TlsVerify {
+    /// Accept any certificate; the server is only checked by the Rabbit
+    /// handshake.
+    #[default]
+    Any,
+    /// Accept a certificate pinned to, or bound to the ID of, a peer in
+    /// the trust cache, or one from a server never seen before, which
+    /// is then pinned.
+    Tofu,
+    /// Accept only certificates of peers already in the trust cache.
+    Pinned,
+}
+
+impl TlsVerify {
+    /// Every mode.
+    pub const ALL: [TlsVerify; 3] = [Self::Any, Self::Tofu, Self::Pinned];
+
+    /// The mode's name in `trust.tls_verify`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Any => "any",
+            Self::Tofu => "tofu",
+            Self::Pinned => "pinned",
+        }
+    }
+
+    /// Parse a mode from its label.
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.label() == label)
+    }
+}
+
+/// A snapshot of the TLS certificates a client trusts, taken with
+/// [`TrustCache::pinned_certs`].
+///
+/// A server certificate is accepted if its SHA-256 fingerprint was
+/// bound to a peer, or if it is bound (see
+/// [`crate::security::identity_cert`]) to the ID of a known peer, since
+/// a burrow ID is the key that signed the binding.  Certificates of
+/// revoked or rotated peers are refused.  Any other certificate is
+/// refused unless unknown servers are allowed.
+#[derive(Debug, Clone, Default)]
+pub struct PinnedCerts {
+    /// Peer ID by certificate fingerprint.
+    certs: HashMap<String, String>,
+    /// Peers whose identity-bound certificates are accepted.
+    known: HashSet<String>,
+    /// Revoked and rotated peers.
+    refused: HashSet<String>,
+    /// The peer the server must be, if known in advance.
+    expect: Option<String>,
+    /// Whether to accept certificates of servers not in the cache.
+    allow_unknown: bool,
+}
+
+impl PinnedCerts {
+    /// Only accept a certificate belonging to `peer`.
+    pub fn expecting(mut self, peer: &str) -> Self {
+        self.expect = Some(peer.to_string());
+        self
+    }
+
+    /// Also accept certificates of servers not in the cache (TOFU).
+    /// Even then, a certificate bound to a known peer must check out.
+    pub fn allow_unknown(mut self, allow: bool) -> Self {
+        self.allow_unknown = allow;
+        self
+    }
+}
+
+impl ServerCertPolicy for PinnedCerts {
+    fn check(&self, cert_der: &[u8]) -> Result<(), ProtocolError> {
+        let fp = hex_encode(&Sha256::digest(cert_der));
+        let bound = extract_rabbit_id_from_cert(cert_der)?;
+        let owner = self.certs.get(&fp).or(bound.as_ref());
+        if let Some(id) = owner.filter(|id| self.refused.contains(*id)) {
+            return Err(ProtocolError::Forbidden(format!(
+                "TLS certificate belongs to revoked peer {}",
+                id
+            )));
+        }
+        if let (Some(expect), Some(id)) = (&self.expect, owner) {
+            if id != expect {
+                return Err(ProtocolError::Forbidden(format!(
+                    "TLS certificate belongs to {}, expected {}",
+                    id, expect
+                )));
+            }
+        }
+        if self.certs.contains_key(&fp) || owner.is_some_and(|id| self.known.contains(id)) {
+            return Ok(());
+        }
+        let known_peer = self
+            .expect
+            .as_ref()
+            .is_some_and(|id| self.known.contains(id));
+        if self.allow_unknown && !known_peer {
+            return Ok(());
+        }
+        Err(ProtocolError::Forbidden(format!(
+            "TLS certificate {} is not pinned",
+            fp
+        )))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::security::identity::Identity;
 
     #[test]
     fn first_contact_succeeds() {
@@ -283,4 +1043,310 @@ mod tests {
         assert!(cache.is_empty());
         assert_eq!(cache.len(), 0);
     }
+
+    #[test]
+    fn blocked_peer_rejected() {
+        let mut cache = TrustCache::new();
+        let id = Identity::generate();
+        let bid = id.burrow_id();
+        cache.revoke(&bid);
+        assert!(cache.is_revoked(&bid));
+        assert!(cache
+            .verify_or_remember(&bid, &id.public_key_bytes())
+            .is_err());
+    }
+
+    #[test]
+    fn quarantined_peers_are_admitted_with_their_reason() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trust.tsv");
+        let mut cache = TrustCache::new();
+        let id = Identity::generate();
+        let bid = id.burrow_id();
+        cache.quarantine(&bid, "seen\tscanning");
+        let status = cache
+            .verify_or_remember(&bid, &id.public_key_bytes())
+            .unwrap();
+        assert_eq!(
+            status,
+            PeerStatus::Quarantined {
+                reason: "seen scanning".into()
+            }
+        );
+        assert_eq!(
+            cache.get(&bid).unwrap().fingerprint,
+            fingerprint(&id.public_key_bytes())
+        );
+
+        // A mismatch is refused unless mismatches are quarantined.
+        let other = Identity::generate();
+        let oid = other.burrow_id();
+        cache
+            .verify_or_remember(&oid, &other.public_key_bytes())
+            .unwrap();
+        cache.peers.get_mut(&oid).unwrap().fingerprint = "ff".into();
+        assert!(cache
+            .verify_or_remember(&oid, &other.public_key_bytes())
+            .is_err());
+        cache.set_quarantine_mismatches(true);
+        let status = cache
+            .verify_or_remember(&oid, &other.public_key_bytes())
+            .unwrap();
+        assert_eq!(status.label(), "quarantined");
+        assert_eq!(cache.get(&oid).unwrap().fingerprint, "ff");
+
+        cache.revoke(&bid);
+        cache.quarantine(&bid, "again");
+        assert!(cache.is_revoked(&bid));
+
+        cache.save(&path).unwrap();
+        let loaded = TrustCache::load(&path).unwrap();
+        assert!(loaded.is_revoked(&bid));
+        assert_eq!(loaded.status(&oid), Some(&status));
+
+        // Caches written before statuses existed marked revoked peers
+        // `blocked`.
+        std::fs::write(&path, "ed25519:OLD\t-\t1\t1\tblocked\n").unwrap();
+        assert!(TrustCache::load(&path).unwrap().is_revoked("ed25519:OLD"));
+    }
+
+    #[test]
+    fn idle_peers_go_stale_until_touched() {
+        let mut cache = TrustCache::new();
+        let id = Identity::generate();
+        let bid = id.burrow_id();
+        cache
+            .verify_or_remember(&bid, &id.public_key_bytes())
+            .unwrap();
+        assert!(cache.stale_peers(60).is_empty());
+
+        cache.peers.get_mut(&bid).unwrap().last_seen -= 120;
+        assert_eq!(cache.stale_peers(60), vec![bid.clone()]);
+        cache.touch(&bid);
+        assert!(cache.stale_peers(60).is_empty());
+    }
+
+    #[test]
+    fn block_survives_save_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trust.tsv");
+        let mut cache = TrustCache::new();
+        let id = Identity::generate();
+        cache
+            .verify_or_remember(&id.burrow_id(), &id.public_key_bytes())
+            .unwrap();
+        cache.revoke(&id.burrow_id());
+        cache.revoke("ed25519:NEVERSEEN");
+        cache.save(&path).unwrap();
+
+        let loaded = TrustCache::load(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.is_revoked(&id.burrow_id()));
+        assert!(loaded.is_revoked("ed25519:NEVERSEEN"));
+        assert_eq!(
+            loaded.get(&id.burrow_id()).unwrap().fingerprint,
+            cache.get(&id.burrow_id()).unwrap().fingerprint
+        );
+    }
+
+    #[test]
+    fn rotation_carries_trust_to_the_new_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trust.tsv");
+        let mut cache = TrustCache::new();
+        let old = Identity::generate();
+        cache
+            .verify_or_remember(&old.burrow_id(), &old.public_key_bytes())
+            .unwrap();
+        cache.peers.get_mut(&old.burrow_id()).unwrap().first_seen = 1000;
+
+        let (new, rotation) = old.rotate();
+        cache.apply_rotation(&rotation).unwrap();
+        cache
+            .verify_or_remember(&new.burrow_id(), &new.public_key_bytes())
+            .unwrap();
+        assert_eq!(cache.get(&new.burrow_id()).unwrap().first_seen, 1000);
+        assert!(cache
+            .verify_or_remember(&old.burrow_id(), &old.public_key_bytes())
+            .is_err());
+
+        // The old key cannot be handed on a second time.
+        let (_, again) = old.rotate();
+        assert!(matches!(
+            cache.apply_rotation(&again),
+            Err(ProtocolError::Forbidden(_))
+        ));
+
+        cache.save(&path).unwrap();
+        let loaded = TrustCache::load(&path).unwrap();
+        assert_eq!(
+            loaded.get(&old.burrow_id()).unwrap().rotated_to,
+            Some(new.burrow_id())
+        );
+    }
+
+    #[test]
+    fn rotation_from_unknown_or_blocked_peers() {
+        let mut cache = TrustCache::new();
+        let old = Identity::generate();
+        let (new, rotation) = old.rotate();
+        assert!(matches!(
+            cache.apply_rotation(&rotation),
+            Err(ProtocolError::Missing(_))
+        ));
+
+        // A revocation follows the key to its successor.
+        cache.revoke(&old.burrow_id());
+        cache.apply_rotation(&rotation).unwrap();
+        assert!(cache.is_revoked(&new.burrow_id()));
+    }
+
+    #[test]
+    fn policy_consults_manifests_for_new_peers() {
+        let anchor = Identity::generate();
+        let member = Identity::generate();
+        let stranger = Identity::generate();
+        let known = Identity::generate();
+        let mut cache = TrustCache::new();
+        cache
+            .verify_or_remember(&known.burrow_id(), &known.public_key_bytes())
+            .unwrap();
+        cache.set_policy(Arc::new(
+            crate::security::trust_policy::AnchorRequired::new([anchor.burrow_id()]),
+        ));
+
+        let manifest = TrustManifest::sign(&anchor, 1, &[member.burrow_id()], &[], &[], 3600);
+        assert!(cache.add_manifest(manifest.clone()).unwrap());
+        assert!(!cache.add_manifest(manifest).unwrap());
+        assert_eq!(cache.anchors_for(&member.burrow_id()), [anchor.burrow_id()]);
+
+        cache
+            .verify_or_remember(&member.burrow_id(), &member.public_key_bytes())
+            .unwrap();
+        cache
+            .verify_or_remember(&known.burrow_id(), &known.public_key_bytes())
+            .unwrap();
+        assert!(matches!(
+            cache.verify_or_remember(&stranger.burrow_id(), &stranger.public_key_bytes()),
+            Err(ProtocolError::Forbidden(_))
+        ));
+        assert!(cache.get(&stranger.burrow_id()).is_none());
+    }
+
+    #[test]
+    fn sub_anchors_vouch_on_behalf_of_the_root() {
+        let root = Identity::generate();
+        let sub = Identity::generate();
+        let member = Identity::generate();
+        let mut cache = TrustCache::new();
+        cache.set_policy(Arc::new(
+            crate::security::trust_policy::AnchorRequired::new([root.burrow_id()]),
+        ));
+
+        let leaf = TrustManifest::sign(&sub, 1, &[member.burrow_id()], &[], &[], 3600);
+        cache.add_manifest(leaf).unwrap();
+        assert_eq!(cache.anchors_for(&member.burrow_id()), [sub.burrow_id()]);
+        assert!(cache
+            .verify_or_remember(&member.burrow_id(), &member.public_key_bytes())
+            .is_err());
+
+        let top = TrustManifest::sign(&root, 1, &[], &[sub.burrow_id()], &[], 3600);
+        cache.add_manifest(top).unwrap();
+        let mut expected = vec![root.burrow_id(), sub.burrow_id()];
+        expected.sort();
+        assert_eq!(cache.anchors_for(&member.burrow_id()), expected);
+        cache
+            .verify_or_remember(&member.burrow_id(), &member.public_key_bytes())
+            .unwrap();
+
+        // Demoting the sub-anchor to a plain member cuts the chain.
+        let demoted = TrustManifest::sign(&root, 2, &[sub.burrow_id()], &[], &[], 3600);
+        cache.add_manifest(demoted).unwrap();
+        assert_eq!(cache.anchors_for(&member.burrow_id()), [sub.burrow_id()]);
+    }
+
+    #[test]
+    fn bundles_seed_a_new_cache() {
+        let exporter = Identity::generate();
+        let alice = Identity::generate();
+        let bob = Identity::generate();
+        let anchor = Identity::generate();
+        let mut source = TrustCache::new();
+        for peer in [&alice, &bob] {
+            source
+                .verify_or_remember(&peer.burrow_id(), &peer.public_key_bytes())
+                .unwrap();
+        }
+        source.revoke("ed25519:CAROL");
+        source
+            .add_manifest(TrustManifest::sign(
+                &anchor,
+                1,
+                &[bob.burrow_id()],
+                &[],
+                &[],
+                3600,
+            ))
+            .unwrap();
+        let bundle = source.export_bundle(&exporter);
+
+        let mut dest = TrustCache::new();
+        assert!(matches!(
+            dest.import_bundle(&bundle, &alice.burrow_id()),
+            Err(ProtocolError::Forbidden(_))
+        ));
+        let tampered = bundle.replace("\trevoked", "\tREVOKED");
+        assert!(matches!(
+            dest.import_bundle(&tampered, &exporter.burrow_id()),
+            Err(ProtocolError::Forbidden(_))
+        ));
+        assert!(dest.is_empty());
+
+        // A conflicting pin is kept; a placeholder gains the key.
+        dest.revoke(&bob.burrow_id());
+        dest.revoke(&alice.burrow_id());
+        dest.peers.get_mut(&alice.burrow_id()).unwrap().fingerprint = "ff".into();
+        let import = dest.import_bundle(&bundle, &exporter.burrow_id()).unwrap();
+        assert_eq!(
+            import,
+            BundleImport {
+                added: 2,
+                updated: 1,
+                conflicts: vec![alice.burrow_id()],
+                manifests: 1,
+            }
+        );
+        assert_eq!(dest.get(&alice.burrow_id()).unwrap().fingerprint, "ff");
+        assert_eq!(
+            dest.get(&bob.burrow_id()).unwrap().fingerprint,
+            fingerprint(&bob.public_key_bytes())
+        );
+        assert!(dest.is_revoked(&bob.burrow_id()));
+        assert!(dest.is_revoked("ed25519:CAROL"));
+        assert!(dest.get(&exporter.burrow_id()).is_some());
+        assert_eq!(dest.anchors_for(&bob.burrow_id()), [anchor.burrow_id()]);
+    }
+
+    #[test]
+    fn certificate_binding_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trust.tsv");
+        let mut cache = TrustCache::new();
+        let id = Identity::generate();
+        assert!(matches!(
+            cache.bind_certificate(&id.burrow_id(), b"cert"),
+            Err(ProtocolError::Missing(_))
+        ));
+        cache
+            .verify_or_remember(&id.burrow_id(), &id.public_key_bytes())
+            .unwrap();
+        cache.bind_certificate(&id.burrow_id(), b"cert").unwrap();
+
+        cache.save(&path).unwrap();
+        let loaded = TrustCache::load(&path).unwrap();
+        assert_eq!(
+            loaded.get(&id.burrow_id()).unwrap().certificate,
+            Some(hex_encode(&Sha256::digest(b"cert")))
+        );
+    }
 }
@@ -0,0 +1,238 @@
+//! Pluggable policies deciding which peers a burrow admits.
+//!
+//! [`TrustCache::verify_or_remember`](super::trust::TrustCache::verify_or_remember)
+//! still pins every key it admits; a [`TrustPolicy`] decides, before
+//! that, whether a peer may be admitted at all:
+//!
+//! - [`TofuOnly`] admits anyone whose key is not already contradicted
+//!   (the default).
+//! - [`AnchorRequired`] admits a peer on first contact only if a
+//!   verified manifest from one of its anchors lists it.
+//! - [`AllowList`] admits only the listed burrow IDs.
+//! - [`DenyList`] admits everyone but the listed burrow IDs.
+//! - [`Interactive`] asks a callback about each new peer.
+//!
+//! The `[trust]` config section picks one of the first four; an
+//! interactive policy can only be installed from code, with
+//! [`crate::burrow::Burrow::set_trust_policy`].
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::TrustConfig;
+use crate::protocol::error::ProtocolError;
+
+/// Build the policy named by the `[trust]` config section.
+pub fn policy_from_config(config: &TrustConfig) -> Result<Arc<dyn TrustPolicy>, ProtocolError> {
+    Ok(match config.policy.as_str() {
+        "tofu" => Arc::new(TofuOnly),
+        "anchor-required" => Arc::new(AnchorRequired::new(&config.anchors)),
+        "allow-list" => Arc::new(AllowList::new(&config.allow)),
+        "deny-list" => Arc::new(DenyList::new(&config.deny)),
+        other => {
+            return Err(ProtocolError::InternalError(format!(
+                "unknown trust.policy {:?}",
+                other
+            )))
+        }
+    })
+}
+
+/// What a policy knows about a peer asking to be admitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrustRequest<'a> {
+    /// The peer's burrow ID.
+    pub burrow_id: &'a str,
+    /// SHA-256 hex fingerprint of its key.
+    pub fingerprint: &'a str,
+    /// Whether the trust cache has never seen the peer.
+    pub first_contact: bool,
+    /// Anchors whose verified manifests list the peer.
+    pub anchors: &'a [&'a str],
+}
+
+/// Decides whether a peer may be admitted.
+pub trait TrustPolicy: Send + Sync + fmt::Debug {
+    /// Admit the peer, or refuse it with `Forbidden`.
+    fn admit(&self, request: &TrustRequest<'_>) -> Result<(), ProtocolError>;
+}
+
+/// Admit any peer; the trust cache alone pins keys.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TofuOnly;
+
+impl TrustPolicy for TofuOnly {
+    fn admit(&self, _request: &TrustRequest<'_>) -> Result<(), ProtocolError> {
+        Ok(())
+    }
+}
+
+/// Admit a new peer only if it is one of `anchors` or one of them
+/// vouches for it with a manifest.  Peers already in the trust cache
+/// are admitted as before.
+#[derive(Debug, Clone, Default)]
+pub struct AnchorRequired {
+    anchors: HashSet<String>,
+}
+
+impl AnchorRequired {
+    /// Accept manifests from the given anchor burrow IDs.
+    pub fn new(anchors: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            anchors: anchors.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TrustPolicy for AnchorRequired {
+    fn admit(&self, request: &TrustRequest<'_>) -> Result<(), ProtocolError> {
+        if !request.first_contact
+            || self.anchors.contains(request.burrow_id)
+            || request.anchors.iter().any(|a| self.anchors.contains(*a))
+        {
+            return Ok(());
+        }
+        Err(ProtocolError::Forbidden(format!(
+            "{} is not listed in a manifest from a trusted anchor",
+            request.burrow_id
+        )))
+    }
+}
+
+/// Admit only the listed burrow IDs.
+#[derive(Debug, Clone, Default)]
+pub struct AllowList {
+    allowed: HashSet<String>,
+}
+
+impl AllowList {
+    /// Admit exactly `ids`.
+    pub fn new(ids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            allowed: ids.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TrustPolicy for AllowList {
+    fn admit(&self, request: &TrustRequest<'_>) -> Result<(), ProtocolError> {
+        if self.allowed.contains(request.burrow_id) {
+            Ok(())
+        } else {
+            Err(ProtocolError::Forbidden(format!(
+                "{} is not on the allow list",
+                request.burrow_id
+            )))
+        }
+    }
+}
+
+/// Admit everyone but the listed burrow IDs.
+#[derive(Debug, Clone, Default)]
+pub struct DenyList {
+    denied: HashSet<String>,
+}
+
+impl DenyList {
+    /// Refuse `ids`.
+    pub fn new(ids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            denied: ids.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TrustPolicy for DenyList {
+    fn admit(&self, request: &TrustRequest<'_>) -> Result<(), ProtocolError> {
+        if self.denied.contains(request.burrow_id) {
+            Err(ProtocolError::Forbidden(format!(
+                "{} is on the deny list",
+                request.burrow_id
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Ask a callback whether to admit each new peer, e.g. by prompting
+/// the operator.  Peers already in the trust cache are admitted
+/// without asking.
+pub struct Interactive<F> {
+    ask: F,
+}
+
+impl<F> Interactive<F>
+where
+    F: Fn(&TrustRequest<'_>) -> bool + Send + Sync,
+{
+    /// Admit a new peer when `ask` returns true.
+    pub fn new(ask: F) -> Self {
+        Self { ask }
+    }
+}
+
+impl<F> fmt::Debug for Interactive<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interactive").finish_non_exhaustive()
+    }
+}
+
+impl<F> TrustPolicy for Interactive<F>
+where
+    F: Fn(&TrustRequest<'_>) -> bool + Send + Sync,
+{
+    fn admit(&self, request: &TrustRequest<'_>) -> Result<(), ProtocolError> {
+        if !request.first_contact || (self.ask)(request) {
+            Ok(())
+        } else {
+            Err(ProtocolError::Forbidden(format!(
+                "{} was not accepted",
+                request.burrow_id
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request<'a>(id: &'a str, first_contact: bool, anchors: &'a [&'a str]) -> TrustRequest<'a> {
+        TrustRequest {
+            burrow_id: id,
+            fingerprint: "ff",
+            first_contact,
+            anchors,
+        }
+    }
+
+    #[test]
+    fn policies_admit_and_refuse() {
+        assert!(TofuOnly.admit(&request("a", true, &[])).is_ok());
+
+        let anchored = AnchorRequired::new(["hub"]);
+        assert!(anchored.admit(&request("a", true, &["hub"])).is_ok());
+        assert!(anchored.admit(&request("a", true, &["other"])).is_err());
+        assert!(anchored.admit(&request("a", true, &[])).is_err());
+        assert!(anchored.admit(&request("a", false, &[])).is_ok());
+        assert!(anchored.admit(&request("hub", true, &[])).is_ok());
+
+        let allow = AllowList::new(["a"]);
+        assert!(allow.admit(&request("a", false, &[])).is_ok());
+        assert!(allow.admit(&request("b", false, &[])).is_err());
+
+        let deny = DenyList::new(["a"]);
+        assert!(deny.admit(&request("a", false, &[])).is_err());
+        assert!(deny.admit(&request("b", true, &[])).is_ok());
+
+        let ask = Interactive::new(|r: &TrustRequest<'_>| r.burrow_id == "a");
+        assert!(ask.admit(&request("a", true, &[])).is_ok());
+        assert!(matches!(
+            ask.admit(&request("b", true, &[])),
+            Err(ProtocolError::Forbidden(_))
+        ));
+        assert!(ask.admit(&request("b", false, &[])).is_ok());
+    }
+}
@@ -16,19 +16,37 @@
 //!        dispatch    dispatch   dispatch
 //! ```
 //!
-//! Each tunnel loop registers its outbound channel on connect and
-//! unregisters on disconnect.  SUBSCRIBE/PUBLISH go through the
-//! shared [`EventEngine`], which returns `(peer_id, Frame)` pairs.
-//! The session manager routes each frame to the correct tunnel's
-//! sender channel.
+//! Each tunnel loop, accepted or dialed, registers its outbound
+//! channel once the peer's Burrow-ID is authenticated, holding a
+//! [`Registration`] that unregisters it however the loop ends.
+//! SUBSCRIBE/PUBLISH go through the shared [`EventEngine`], which
+//! returns `(peer_id, Frame)` pairs.  The session manager routes each
+//! frame to the correct tunnel's sender channel; any other frame can be
+//! sent to one peer with [`SessionManager::send`] or to every peer with
+//! [`SessionManager::broadcast_all`].
+//!
+//! # Session tokens
+//!
+//! The token issued by each handshake is recorded in a
+//! [`SessionStore`] until it expires or is revoked.  Tunnels watch the
+//! store and close when their session disappears, which lets an
+//! operator kick a peer mid-session.  The store is persisted as
+//! tab-separated text, one session per line:
+//!
+//! ```text
+//! <token>\t<peer_id>\t<issued_at>\t<expires_at>\n
+//! ```
 
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Mutex;
 
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tracing::{debug, warn};
 
+use crate::protocol::error::ProtocolError;
 use crate::protocol::frame::Frame;
+use crate::util::now_unix;
 
 /// A handle to a registered tunnel session.
 ///
@@ -79,6 +97,30 @@ impl SessionManager {
         rx
     }
 
+    /// Register a tunnel session like [`SessionManager::register`],
+    /// returning with the receiver a [`Registration`] that unregisters
+    /// the session when dropped, unless another tunnel has replaced it
+    /// by then.
+    pub fn attach(
+        &self,
+        peer_id: &str,
+        buffer: usize,
+    ) -> (Registration<'_>, mpsc::Receiver<Frame>) {
+        let rx = self.register(peer_id, buffer);
+        let tx = self
+            .sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(peer_id)
+            .map(|s| s.tx.clone());
+        let registration = Registration {
+            manager: self,
+            peer_id: peer_id.to_string(),
+            tx,
+        };
+        (registration, rx)
+    }
+
     /// Unregister a tunnel session.
     ///
     /// Drops the sender channel, which signals the writer task to
@@ -116,6 +158,44 @@ impl SessionManager {
         }
     }
 
+    /// Queue `frame` for the tunnel to `peer_id`.  Fails with
+    /// `Missing` if no tunnel to it is open, and with `Busy` if its
+    /// channel is full.
+    pub fn send(&self, peer_id: &str, frame: Frame) -> Result<(), ProtocolError> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let session = sessions
+            .get(peer_id)
+            .ok_or_else(|| ProtocolError::Missing(format!("no tunnel to {}", peer_id)))?;
+        session.tx.try_send(frame).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => {
+                ProtocolError::Busy(format!("tunnel to {} is backed up", peer_id))
+            }
+            mpsc::error::TrySendError::Closed(_) => {
+                ProtocolError::Missing(format!("tunnel to {} is closing", peer_id))
+            }
+        })
+    }
+
+    /// Queue a copy of `frame` for every open tunnel except the one to
+    /// `except`, if given.  Peers whose channel is full are skipped
+    /// with a warning.  Returns how many tunnels it was queued for.
+    pub fn broadcast_all(&self, frame: &Frame, except: Option<&str>) -> usize {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let mut sent = 0;
+        for (peer_id, session) in sessions.iter() {
+            if Some(peer_id.as_str()) == except {
+                continue;
+            }
+            match session.tx.try_send(frame.clone()) {
+                Ok(()) => sent += 1,
+                Err(_) => {
+                    warn!(peer_id = %peer_id, "broadcast: channel full or closed, dropping frame")
+                }
+            }
+        }
+        sent
+    }
+
     /// Return the number of active sessions.
     pub fn session_count(&self) -> usize {
         self.sessions
@@ -143,6 +223,35 @@ impl SessionManager {
     }
 }
 
+/// A tunnel's place in a [`SessionManager`], held for as long as the
+/// tunnel is open; see [`SessionManager::attach`].
+#[derive(Debug)]
+pub struct Registration<'a> {
+    manager: &'a SessionManager,
+    peer_id: String,
+    tx: Option<mpsc::Sender<Frame>>,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        let mut sessions = self
+            .manager
+            .sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if sessions
+            .get(&self.peer_id)
+            .is_some_and(|s| s.tx.same_channel(tx))
+        {
+            sessions.remove(&self.peer_id);
+            debug!(peer_id = %self.peer_id, count = sessions.len(), "session unregistered");
+        }
+    }
+}
+
 /// Saved lane state for session resumption.
 #[derive(Debug, Clone, PartialEq)]
 pub struct SavedLaneState {
@@ -252,6 +361,209 @@ impl Default for SessionManager {
     }
 }
 
+// ── Session store ──────────────────────────────────────────────
+
+/// A session token issued by a handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionRecord {
+    /// The session token.
+    pub token: String,
+    /// The peer the token was issued to.
+    pub peer_id: String,
+    /// Unix timestamp of the handshake.
+    pub issued_at: u64,
+    /// Unix timestamp after which the token is no longer valid.
+    pub expires_at: u64,
+}
+
+/// Issued session tokens, with expiry and revocation.
+///
+/// Every removal bumps a counter that tunnels watch (see
+/// [`SessionStore::subscribe`]), so a tunnel notices at once when its
+/// session is revoked or swept.
+#[derive(Debug)]
+pub struct SessionStore {
+    sessions: Mutex<HashMap<String, SessionRecord>>,
+    removals: watch::Sender<u64>,
+}
+
+impl SessionStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            removals: watch::Sender::new(0),
+        }
+    }
+
+    /// Record a token issued to `peer_id`, valid for `ttl_secs`.
+    pub fn issue(&self, token: &str, peer_id: &str, ttl_secs: u64) -> SessionRecord {
+        let now = now_unix();
+        let record = SessionRecord {
+            token: token.to_string(),
+            peer_id: peer_id.to_string(),
+            issued_at: now,
+            expires_at: now.saturating_add(ttl_secs),
+        };
+        self.lock().insert(token.to_string(), record.clone());
+        record
+    }
+
+    /// The unexpired session with this token, if any.
+    pub fn get(&self, token: &str) -> Option<SessionRecord> {
+        let now = now_unix();
+        self.lock()
+            .get(token)
+            .filter(|s| s.expires_at > now)
+            .cloned()
+    }
+
+    /// Whether `token` names an unexpired, unrevoked session.
+    pub fn is_active(&self, token: &str) -> bool {
+        self.get(token).is_some()
+    }
+
+    /// All sessions, sorted by peer and then issue time.
+    pub fn list(&self) -> Vec<SessionRecord> {
+        let mut sessions: Vec<SessionRecord> = self.lock().values().cloned().collect();
+        sessions.sort_by(|a, b| {
+            (&a.peer_id, a.issued_at, &a.token).cmp(&(&b.peer_id, b.issued_at, &b.token))
+        });
+        sessions
+    }
+
+    /// Number of sessions held, expired or not.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether the store holds no sessions.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Revoke one session.  Returns it, if it existed.
+    pub fn revoke_session(&self, token: &str) -> Option<SessionRecord> {
+        let removed = self.lock().remove(token);
+        if removed.is_some() {
+            self.removals.send_modify(|n| *n += 1);
+        }
+        removed
+    }
+
+    /// Revoke every session of a peer.  Returns the revoked sessions.
+    pub fn revoke_peer(&self, peer_id: &str) -> Vec<SessionRecord> {
+        self.remove_where(|s| s.peer_id == peer_id)
+    }
+
+    /// Drop expired sessions.  Returns how many were dropped.
+    pub fn sweep_expired(&self) -> usize {
+        let now = now_unix();
+        self.remove_where(|s| s.expires_at <= now).len()
+    }
+
+    /// A receiver that sees a change whenever a session is removed.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.removals.subscribe()
+    }
+
+    /// Save the unexpired sessions to a TSV file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProtocolError> {
+        let now = now_unix();
+        let content: String = self
+            .list()
+            .iter()
+            .filter(|s| s.expires_at > now)
+            .map(|s| {
+                format!(
+                    "{}\t{}\t{}\t{}\n",
+                    s.token, s.peer_id, s.issued_at, s.expires_at
+                )
+            })
+            .collect();
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                ProtocolError::InternalError(format!("failed to create directory: {}", e))
+            })?;
+        }
+        std::fs::write(path.as_ref(), content).map_err(|e| {
+            ProtocolError::InternalError(format!("failed to write session store: {}", e))
+        })
+    }
+
+    /// Load a store written by [`SessionStore::save`], skipping
+    /// sessions that have expired since.
+    ///
+    /// A missing file is treated as an empty store.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        let store = Self::new();
+        let content = match std::fs::read_to_string(path.as_ref()) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(store),
+            Err(e) => {
+                return Err(ProtocolError::InternalError(format!(
+                    "failed to read session store: {}",
+                    e
+                )))
+            }
+        };
+        let now = now_unix();
+        let mut sessions = store.lock();
+        for (line_num, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = || {
+                ProtocolError::InternalError(format!(
+                    "session store line {}: expected <token> <peer> <issued> <expires>",
+                    line_num + 1
+                ))
+            };
+            let parts: Vec<&str> = line.split('\t').collect();
+            let [token, peer_id, issued_at, expires_at] = parts[..] else {
+                return Err(invalid());
+            };
+            let record = SessionRecord {
+                token: token.to_string(),
+                peer_id: peer_id.to_string(),
+                issued_at: issued_at.parse().map_err(|_| invalid())?,
+                expires_at: expires_at.parse().map_err(|_| invalid())?,
+            };
+            if record.expires_at > now {
+                sessions.insert(record.token.clone(), record);
+            }
+        }
+        drop(sessions);
+        Ok(store)
+    }
+
+    fn remove_where(&self, f: impl Fn(&SessionRecord) -> bool) -> Vec<SessionRecord> {
+        let mut sessions = self.lock();
+        let tokens: Vec<String> = sessions
+            .values()
+            .filter(|s| f(s))
+            .map(|s| s.token.clone())
+            .collect();
+        let removed: Vec<SessionRecord> =
+            tokens.iter().filter_map(|t| sessions.remove(t)).collect();
+        drop(sessions);
+        if !removed.is_empty() {
+            self.removals.send_modify(|n| *n += 1);
+        }
+        removed
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, SessionRecord>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -268,6 +580,34 @@ mod tests {
         assert!(!sm.has_session("alice"));
     }
 
+    #[test]
+    fn registrations_leave_replacements_alone() {
+        let sm = SessionManager::new();
+        let (first, _rx1) = sm.attach("alice", 16);
+        let (second, mut rx2) = sm.attach("alice", 16);
+        drop(first);
+        assert!(sm.has_session("alice"));
+
+        sm.send("alice", Frame::new("PING")).unwrap();
+        assert_eq!(rx2.try_recv().unwrap().verb, "PING");
+        let (_bob, mut bob_rx) = sm.attach("bob", 1);
+        assert_eq!(sm.broadcast_all(&Frame::new("OFFER"), Some("alice")), 1);
+        assert_eq!(bob_rx.try_recv().unwrap().verb, "OFFER");
+        assert!(rx2.try_recv().is_err());
+        sm.send("bob", Frame::new("ONE")).unwrap();
+        assert!(matches!(
+            sm.send("bob", Frame::new("TWO")),
+            Err(ProtocolError::Busy(_))
+        ));
+
+        drop(second);
+        assert!(!sm.has_session("alice"));
+        assert!(matches!(
+            sm.send("alice", Frame::new("PING")),
+            Err(ProtocolError::Missing(_))
+        ));
+    }
+
     #[test]
     fn register_replaces_existing() {
         let sm = SessionManager::new();
@@ -312,6 +652,33 @@ mod tests {
         assert!(rx_b.recv().await.is_some());
     }
 
+    #[test]
+    fn session_store_revokes_and_persists() {
+        let store = SessionStore::new();
+        let mut removals = store.subscribe();
+        store.issue("t1", "alice", 3600);
+        store.issue("t2", "alice", 3600);
+        store.issue("t3", "bob", 3600);
+        store.issue("old", "bob", 0);
+        assert!(store.is_active("t1"));
+        assert!(!store.is_active("old"));
+
+        assert_eq!(store.sweep_expired(), 1);
+        assert!(removals.has_changed().unwrap());
+        removals.mark_unchanged();
+        assert_eq!(store.revoke_session("t3").unwrap().peer_id, "bob");
+        assert!(store.revoke_session("t3").is_none());
+        assert!(removals.has_changed().unwrap());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session_tokens.tsv");
+        store.save(&path).unwrap();
+        let loaded = SessionStore::load(&path).unwrap();
+        assert_eq!(loaded.list(), store.list());
+        assert_eq!(loaded.revoke_peer("alice").len(), 2);
+        assert!(loaded.is_empty());
+    }
+
     #[tokio::test]
     async fn broadcast_handles_closed_channel() {
         let sm = SessionManager::new();
@@ -0,0 +1,486 @@
+//! Snapshots of a burrow's persistent state.
+//!
+//! [`snapshot`] packs what a burrow keeps in its storage directory
+//! that is worth moving to another machine — its identity and key
+//! rotation record, continuity and audit logs, trust cache and
+//! manifests, capability grants, revocations and groups, and
+//! federation anchors and links — into one tar archive.  Its last
+//! entry, `MANIFEST.json`, gives the snapshot format version, when it
+//! was taken, and the size and SHA-256 of every other entry:
+//!
+//! ```text
+//! {"version":1,"created":1767225600,"files":[{"path":"trust.tsv","bytes":412,"sha256":"…"}]}
+//! ```
+//!
+//! [`restore`] checks the whole archive against its manifest before
+//! writing anything, and will not overwrite a file already in the
+//! storage directory.  Sessions, routes and dead letters are left
+//! out: they belong to the old machine's connections.
+//!
+//! The archive holds the burrow's secret key, so it is written, and
+//! the files restored from it, readable by their owner only.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::burrow::SNAPSHOT_PATHS;
+use crate::protocol::error::ProtocolError;
+use crate::security::auth::hex_encode;
+use crate::util::now_unix;
+
+/// The snapshot format version written, and the newest read.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Name of the manifest entry.
+const MANIFEST_NAME: &str = "MANIFEST.json";
+
+/// Size of a tar header, and what entries are padded to.
+const BLOCK: usize = 512;
+
+/// Tar entry type of a regular file.
+const FILE_TYPE: u8 = b'0';
+
+/// Tar entry type of a GNU long name, the path of the entry after it.
+const LONG_NAME_TYPE: u8 = b'L';
+
+/// Name of a GNU long-name entry.
+const LONG_NAME: &str = "././@LongLink";
+
+/// What a snapshot holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    /// Snapshot format version.
+    pub version: u32,
+    /// When the snapshot was taken (Unix seconds).
+    pub created: u64,
+    /// Every file in it but the manifest.
+    pub files: Vec<SnapshotFile>,
+}
+
+/// One file of a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    /// Path relative to the storage directory, `/`-separated.
+    pub path: String,
+    /// Size in bytes.
+    pub bytes: u64,
+    /// SHA-256 of the contents, in hex.
+    pub sha256: String,
+}
+
+/// Write a snapshot of the storage directory `storage` to `archive`,
+/// replacing it if it exists.  Files are read one at a time; a burrow
+/// still running should flush what it holds first (see
+/// [`Burrow::snapshot`](crate::burrow::Burrow::snapshot)).
+pub fn snapshot(storage: &Path, archive: &Path) -> Result<SnapshotManifest, ProtocolError> {
+    let mut paths = Vec::new();
+    for name in SNAPSHOT_PATHS {
+        collect(storage, name, &mut paths)?;
+    }
+    let failed = |e: std::io::Error| {
+        ProtocolError::InternalError(format!("failed to write {}: {}", archive.display(), e))
+    };
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = archive.with_file_name(format!("{}.tmp", name));
+    let mut out = BufWriter::new(create_private(&tmp).map_err(failed)?);
+    let created = now_unix();
+    let mut files = Vec::new();
+    for path in paths {
+        let full = storage.join(&path);
+        let data = std::fs::read(&full).map_err(|e| {
+            ProtocolError::InternalError(format!("failed to read {}: {}", full.display(), e))
+        })?;
+        append_entry(&mut out, &path, &data, created).map_err(failed)?;
+        files.push(SnapshotFile {
+            path,
+            bytes: data.len() as u64,
+            sha256: hex_encode(&Sha256::digest(&data)),
+        });
+    }
+    let manifest = SnapshotManifest {
+        version: SNAPSHOT_VERSION,
+        created,
+        files,
+    };
+    let json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| ProtocolError::InternalError(format!("failed to encode manifest: {}", e)))?;
+    append_entry(&mut out, MANIFEST_NAME, &json, created).map_err(failed)?;
+    out.write_all(&[0; 2 * BLOCK]).map_err(failed)?;
+    out.into_inner()
+        .map_err(|e| failed(e.into_error()))?
+        .sync_all()
+        .map_err(failed)?;
+    std::fs::rename(&tmp, archive).map_err(failed)?;
+    Ok(manifest)
+}
+
+/// Unpack the snapshot `archive` into the storage directory `storage`,
+/// creating it if need be.
+///
+/// Fails with `BadRequest`, having written nothing, if the archive is
+/// of a newer version, does not match its manifest, names a path
+/// outside the storage directory, or holds a file already there.
+pub fn restore(archive: &Path, storage: &Path) -> Result<SnapshotManifest, ProtocolError> {
+    let failed = |e: std::io::Error| {
+        ProtocolError::InternalError(format!("failed to read {}: {}", archive.display(), e))
+    };
+    let mut file = File::open(archive).map_err(failed)?;
+    let entries = read_entries(&mut file)?;
+    let manifest_entry = entries
+        .iter()
+        .find(|e| e.path == MANIFEST_NAME)
+        .ok_or_else(|| bad("snapshot has no manifest"))?;
+    let json = read_entry(&mut file, manifest_entry).map_err(failed)?;
+    let manifest: SnapshotManifest = serde_json::from_slice(&json)
+        .map_err(|e| bad(&format!("snapshot manifest is malformed: {}", e)))?;
+    if manifest.version > SNAPSHOT_VERSION {
+        return Err(bad(&format!(
+            "snapshot version {} is newer than {}",
+            manifest.version, SNAPSHOT_VERSION
+        )));
+    }
+    if entries.len() != manifest.files.len() + 1 {
+        return Err(bad("snapshot entries do not match its manifest"));
+    }
+    let mut restored = Vec::new();
+    for listed in &manifest.files {
+        let entry = entries
+            .iter()
+            .find(|e| e.path == listed.path)
+            .ok_or_else(|| bad(&format!("snapshot is missing {}", listed.path)))?;
+        let target = safe_join(storage, &listed.path)?;
+        if target.exists() {
+            return Err(bad(&format!("{} already exists", target.display())));
+        }
+        let data = read_entry(&mut file, entry).map_err(failed)?;
+        if data.len() as u64 != listed.bytes || hex_encode(&Sha256::digest(&data)) != listed.sha256
+        {
+            return Err(bad(&format!("{} does not match the manifest", listed.path)));
+        }
+        restored.push((target, entry));
+    }
+    for (target, entry) in restored {
+        let data = read_entry(&mut file, entry).map_err(failed)?;
+        let written = target
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| create_private(&target))
+            .and_then(|mut out| out.write_all(&data));
+        written.map_err(|e| {
+            ProtocolError::InternalError(format!("failed to write {}: {}", target.display(), e))
+        })?;
+    }
+    Ok(manifest)
+}
+
+/// Add the file, or every file under the directory, at `name` in
+/// `storage` to `paths`, relative to `storage` and `/`-separated.
+/// Nothing is added if it does not exist.
+fn collect(storage: &Path, name: &str, paths: &mut Vec<String>) -> Result<(), ProtocolError> {
+    let full = storage.join(name);
+    if full.is_file() {
+        paths.push(name.to_string());
+        return Ok(());
+    }
+    let entries = match std::fs::read_dir(&full) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(ProtocolError::InternalError(format!(
+                "failed to list {}: {}",
+                full.display(),
+                e
+            )))
+        }
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|child| !child.ends_with(".tmp"))
+        .collect();
+    names.sort();
+    for child in names {
+        collect(storage, &format!("{}/{}", name, child), paths)?;
+    }
+    Ok(())
+}
+
+/// `storage` joined with the `/`-separated relative `path`, which may
+/// not leave it.
+fn safe_join(storage: &Path, path: &str) -> Result<PathBuf, ProtocolError> {
+    let relative = Path::new(path);
+    let plain = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    if !plain || path.is_empty() {
+        return Err(bad(&format!("snapshot path {:?} is not allowed", path)));
+    }
+    Ok(storage.join(relative))
+}
+
+/// A `BadRequest` about a snapshot.
+fn bad(detail: &str) -> ProtocolError {
+    ProtocolError::BadRequest(detail.to_string())
+}
+
+/// Create (or truncate) the file at `path`, readable by its owner
+/// only.
+fn create_private(path: &Path) -> std::io::Result<File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+// ── Tar ────────────────────────────────────────────────────────
+
+/// Where one entry of an archive is.
+#[derive(Debug)]
+struct Entry {
+    /// Its path.
+    path: String,
+    /// Offset of its contents.
+    offset: u64,
+    /// Its size.
+    size: u64,
+}
+
+/// Write a ustar entry for a file at `path` holding `data`.
+/// A path too long for a ustar header is carried by a GNU long-name
+/// entry before it.
+fn append_entry(out: &mut impl Write, path: &str, data: &[u8], mtime: u64) -> std::io::Result<()> {
+    let (prefix, name) = match split_path(path) {
+        Some(split) => split,
+        None => {
+            let mut long = path.as_bytes().to_vec();
+            long.push(0);
+            append_raw(out, ("", LONG_NAME), &long, mtime, LONG_NAME_TYPE)?;
+            let mut end = 100;
+            while !path.is_char_boundary(end) {
+                end -= 1;
+            }
+            ("", &path[..end])
+        }
+    };
+    append_raw(out, (prefix, name), data, mtime, FILE_TYPE)
+}
+
+/// Write a ustar header of type `kind` for `(prefix, name)`, then
+/// `data`, padded.
+fn append_raw(
+    out: &mut impl Write,
+    (prefix, name): (&str, &str),
+    data: &[u8],
+    mtime: u64,
+    kind: u8,
+) -> std::io::Result<()> {
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut header[100..108], 0o600);
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    if !octal(&mut header[124..136], data.len() as u64) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "file too large for tar",
+        ));
+    }
+    octal(&mut header[136..148], mtime);
+    header[156] = kind;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+    let sum = checksum(&header);
+    octal(&mut header[148..155], sum);
+    header[155] = b' ';
+    out.write_all(&header)?;
+    out.write_all(data)?;
+    out.write_all(&[0; BLOCK][..padding(data.len() as u64)])
+}
+
+/// Every entry of the archive `file`, checking each header.
+fn read_entries(file: &mut File) -> Result<Vec<Entry>, ProtocolError> {
+    let failed =
+        |e: std::io::Error| ProtocolError::InternalError(format!("failed to read snapshot: {}", e));
+    let mut entries = Vec::new();
+    let mut long_name = None;
+    let mut offset = 0u64;
+    loop {
+        let mut header = [0u8; BLOCK];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut header))
+            .map_err(|_| bad("snapshot is truncated"))?;
+        if header.iter().all(|&b| b == 0) {
+            return Ok(entries);
+        }
+        let stored = parse_octal(&header[148..156]);
+        if stored != Some(checksum(&header)) {
+            return Err(bad("snapshot has a corrupt header"));
+        }
+        let kind = header[156];
+        if !matches!(kind, FILE_TYPE | 0 | LONG_NAME_TYPE) {
+            return Err(bad("snapshot holds something other than files"));
+        }
+        let size = parse_octal(&header[124..136]).ok_or_else(|| bad("snapshot has a bad size"))?;
+        offset += BLOCK as u64;
+        let len = file.metadata().map_err(failed)?.len();
+        if offset + size > len {
+            return Err(bad("snapshot is truncated"));
+        }
+        if kind == LONG_NAME_TYPE {
+            let entry = Entry {
+                path: String::new(),
+                offset,
+                size,
+            };
+            long_name = Some(field(&read_entry(file, &entry).map_err(failed)?));
+        } else {
+            let name = field(&header[..100]);
+            let prefix = field(&header[345..500]);
+            let path = match (long_name.take(), prefix.is_empty()) {
+                (Some(long), _) => long,
+                (None, true) => name,
+                (None, false) => format!("{}/{}", prefix, name),
+            };
+            entries.push(Entry { path, offset, size });
+        }
+        offset += size + padding(size) as u64;
+    }
+}
+
+/// The contents of `entry` in the archive `file`.
+fn read_entry(file: &mut File, entry: &Entry) -> std::io::Result<Vec<u8>> {
+    let mut data = vec![0; entry.size as usize];
+    file.seek(SeekFrom::Start(entry.offset))?;
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Split `path` into a ustar prefix of at most 155 bytes and a name of
+/// at most 100, at a `/`.
+fn split_path(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some(("", path));
+    }
+    path.match_indices('/')
+        .map(|(at, _)| (&path[..at], &path[at + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && !name.is_empty() && name.len() <= 100)
+}
+
+/// The header's checksum: the sum of its bytes, the checksum field
+/// taken as spaces.
+fn checksum(header: &[u8; BLOCK]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+        .sum()
+}
+
+/// Write `value` into `field` as zero-padded octal ending in a NUL.
+/// Returns false, leaving it alone, if it does not fit.
+fn octal(field: &mut [u8], value: u64) -> bool {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    if digits.len() >= field.len() {
+        return false;
+    }
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+    true
+}
+
+/// The octal number in a header field.
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let text = std::str::from_utf8(field).ok()?;
+    let digits = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// The NUL-terminated text in a header field.
+fn field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Bytes of padding after `size` bytes of contents.
+fn padding(size: u64) -> usize {
+    (BLOCK - (size % BLOCK as u64) as usize) % BLOCK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, text: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn snapshots_round_trip_and_refuse_to_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old");
+        write(&old.join("trust.tsv"), "ed25519:A\tkey\n");
+        write(&old.join("capabilities.json"), "[]");
+        write(&old.join("events/q_chat.log"), "RABBITL2");
+        let long = format!("events/{}.log", "q_".repeat(80));
+        write(&old.join(&long), "long");
+        write(&old.join("sessions.tsv"), "not carried");
+        write(&old.join("events/q_chat.head.tmp"), "half written");
+
+        let archive = dir.path().join("burrow.tar");
+        let taken = snapshot(&old, &archive).unwrap();
+        let paths: Vec<&str> = taken.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "trust.tsv",
+                "capabilities.json",
+                "events/q_chat.log",
+                long.as_str()
+            ]
+        );
+
+        let new = dir.path().join("new");
+        let restored = restore(&archive, &new).unwrap();
+        assert_eq!(restored, taken);
+        let read = |path: &str| std::fs::read_to_string(new.join(path)).unwrap();
+        assert_eq!(read("trust.tsv"), "ed25519:A\tkey\n");
+        assert_eq!(read(&long), "long");
+        assert!(!new.join("sessions.tsv").exists());
+
+        let again = restore(&archive, &new).unwrap_err();
+        assert!(matches!(again, ProtocolError::BadRequest(_)), "{again:?}");
+    }
+
+    #[test]
+    fn damaged_snapshots_are_refused_before_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old");
+        write(&old.join("trust.tsv"), "ed25519:A\tkey\n");
+        write(&old.join("groups.json"), "{}");
+        let archive = dir.path().join("burrow.tar");
+        snapshot(&old, &archive).unwrap();
+
+        // Flip a byte of the second file's contents.
+        let mut bytes = std::fs::read(&archive).unwrap();
+        let at = 3 * BLOCK;
+        bytes[at] ^= 1;
+        std::fs::write(&archive, &bytes).unwrap();
+        let new = dir.path().join("new");
+        assert!(restore(&archive, &new).is_err());
+        assert!(!new.join("trust.tsv").exists());
+
+        bytes.truncate(2 * BLOCK + 10);
+        std::fs::write(&archive, &bytes).unwrap();
+        assert!(restore(&archive, &new).is_err());
+    }
+}
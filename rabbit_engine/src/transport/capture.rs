@@ -0,0 +1,215 @@
+//! Frame capture files.
+//!
+//! A capture is a timestamped record of the frames crossing one or
+//! more tunnels, written by [`CaptureWriter`] (a [`FrameTap`]) and
+//! read back with [`read_capture`].  `rabbit-dump` uses captures to
+//! show and replay traffic.
+//!
+//! The file is text, one record per frame:
+//!
+//! ```text
+//! # rabbit-capture 1
+//! <unix-micros>\t<conn>\t<in|out>\t<peer>\t<length>
+//! <length bytes of the serialized frame>
+//! ```
+//!
+//! Each frame is followed by a newline.  Its length is given in bytes
+//! because frame bodies may contain any text, including blank lines.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::warn;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+
+use super::tap::{FrameTap, TapDirection};
+
+/// First line of every capture file.
+pub const CAPTURE_HEADER: &str = "# rabbit-capture 1";
+
+/// One captured frame.
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    /// When the frame was seen, in microseconds since the Unix epoch.
+    pub micros: u64,
+    /// Connection number from the tap.
+    pub conn: u64,
+    /// Direction relative to the capturing side.
+    pub direction: TapDirection,
+    /// Peer the tunnel reported (may be `unknown` before the
+    /// handshake on TLS tunnels).
+    pub peer: String,
+    /// The frame itself.
+    pub frame: Frame,
+}
+
+/// A [`FrameTap`] that appends every frame to a capture file.
+///
+/// Each record is flushed as it is written so a capture survives the
+/// process being killed.
+pub struct CaptureWriter {
+    out: Mutex<BufWriter<File>>,
+}
+
+impl CaptureWriter {
+    /// Create (or truncate) a capture file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        let path = path.as_ref();
+        let capture_error = |e: std::io::Error| {
+            ProtocolError::InternalError(format!(
+                "failed to create capture {}: {}",
+                path.display(),
+                e
+            ))
+        };
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(capture_error)?;
+        }
+        let mut out = BufWriter::new(File::create(path).map_err(capture_error)?);
+        writeln!(out, "{}", CAPTURE_HEADER).map_err(capture_error)?;
+        out.flush().map_err(capture_error)?;
+        Ok(Self {
+            out: Mutex::new(out),
+        })
+    }
+
+    /// Append one record.
+    pub fn write(&self, record: &CapturedFrame) -> std::io::Result<()> {
+        let data = record.frame.serialize();
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}",
+            record.micros,
+            record.conn,
+            record.direction.label(),
+            record.peer.replace(['\t', '\n'], " "),
+            data.len()
+        )?;
+        out.write_all(data.as_bytes())?;
+        out.write_all(b"\n")?;
+        out.flush()
+    }
+}
+
+impl FrameTap for CaptureWriter {
+    fn on_frame(&self, conn: u64, peer: &str, direction: TapDirection, frame: &Frame) {
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let record = CapturedFrame {
+            micros,
+            conn,
+            direction,
+            peer: peer.to_string(),
+            frame: frame.clone(),
+        };
+        if let Err(e) = self.write(&record) {
+            warn!(err = %e, "failed to write capture record");
+        }
+    }
+}
+
+/// Parse the contents of a capture file.
+pub fn parse_capture(data: &str) -> Result<Vec<CapturedFrame>, ProtocolError> {
+    let bad = |msg: String| ProtocolError::BadRequest(format!("bad capture: {}", msg));
+    let mut rest = data
+        .strip_prefix(CAPTURE_HEADER)
+        .and_then(|r| r.strip_prefix('\n'))
+        .ok_or_else(|| bad("missing header".into()))?;
+
+    let mut records = Vec::new();
+    while !rest.is_empty() {
+        let (line, after) = rest
+            .split_once('\n')
+            .ok_or_else(|| bad("truncated record header".into()))?;
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 5 {
+            return Err(bad(format!("record header {:?}", line)));
+        }
+        let number = |s: &str| {
+            s.parse::<u64>()
+                .map_err(|_| bad(format!("record header {:?}", line)))
+        };
+        let micros = number(fields[0])?;
+        let conn = number(fields[1])?;
+        let direction = TapDirection::from_label(fields[2])
+            .ok_or_else(|| bad(format!("direction {:?}", fields[2])))?;
+        let len = number(fields[4])? as usize;
+
+        let raw = after
+            .get(..len)
+            .ok_or_else(|| bad("truncated frame".into()))?;
+        let frame = Frame::parse(raw)?;
+        rest = after[len..]
+            .strip_prefix('\n')
+            .ok_or_else(|| bad("missing record terminator".into()))?;
+
+        records.push(CapturedFrame {
+            micros,
+            conn,
+            direction,
+            peer: fields[3].to_string(),
+            frame,
+        });
+    }
+    Ok(records)
+}
+
+/// Read a capture file written by [`CaptureWriter`].
+pub fn read_capture(path: impl AsRef<Path>) -> Result<Vec<CapturedFrame>, ProtocolError> {
+    let path = path.as_ref();
+    let data = std::fs::read_to_string(path).map_err(|e| {
+        ProtocolError::InternalError(format!("failed to read capture {}: {}", path.display(), e))
+    })?;
+    parse_capture(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("caps").join("run.rcap");
+        let writer = CaptureWriter::create(&path).unwrap();
+
+        let mut fetch = Frame::with_args("FETCH", vec!["/0/readme".into()]);
+        fetch.set_header("Lane", "1");
+        let mut content = Frame::new("200 CONTENT");
+        content.set_header("Lane", "1");
+        content.set_body("line one\n\nline three\n");
+
+        writer.on_frame(7, "ed25519:PEER", TapDirection::Inbound, &fetch);
+        writer.on_frame(7, "ed25519:PEER", TapDirection::Outbound, &content);
+
+        let records = read_capture(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].conn, 7);
+        assert_eq!(records[0].direction, TapDirection::Inbound);
+        assert_eq!(records[0].frame.args, vec!["/0/readme"]);
+        assert_eq!(records[1].peer, "ed25519:PEER");
+        assert_eq!(
+            records[1].frame.body.as_deref(),
+            Some("line one\n\nline three\n")
+        );
+        assert!(records[0].micros <= records[1].micros);
+    }
+
+    #[test]
+    fn truncated_capture_rejected() {
+        assert!(parse_capture("not a capture").is_err());
+        let data = format!("{}\n1\t1\tin\tp\t500\nPING\r\nEnd:\r\n", CAPTURE_HEADER);
+        assert!(parse_capture(&data).is_err());
+        assert!(parse_capture(&format!("{}\n", CAPTURE_HEADER))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,202 @@
+//! Write coalescing for stream tunnels.
+//!
+//! Writing and flushing each frame on its own costs a TLS record and
+//! a system call per frame, which dominates when a burrow fans small
+//! events out to many subscribers.  A [`CoalescingWriter`] hands each
+//! frame's bytes to a writer task of the tunnel's own.  The task
+//! gathers whatever else arrives within the flush deadline, up to
+//! [`MAX_BATCH`] bytes, and writes the lot with one flush: a burst of
+//! small frames becomes one TLS record.
+//!
+//! A lone frame waits out the deadline before it is written, so the
+//! deadline is kept to a few milliseconds (`network.write_coalesce_ms`).
+//! Sends only fail once the task has: a write error is reported by the
+//! next send, or by [`CoalescingWriter::shutdown`].
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+use crate::protocol::error::ProtocolError;
+
+/// Bytes gathered before a batch is written without waiting out the
+/// deadline: the most one TLS record carries.
+pub const MAX_BATCH: usize = 16 * 1024;
+
+/// Frames queued for the writer task before senders wait.
+const QUEUE_DEPTH: usize = 64;
+
+enum Command {
+    Write(Vec<u8>),
+    Shutdown(oneshot::Sender<Result<(), ProtocolError>>),
+}
+
+/// The sending side of a tunnel's writer task.
+pub struct CoalescingWriter {
+    commands: mpsc::Sender<Command>,
+    failure: Arc<Mutex<Option<ProtocolError>>>,
+}
+
+impl CoalescingWriter {
+    /// Spawn a task writing to `writer`, flushing `flush_after` after
+    /// the first frame of each batch.
+    pub fn spawn<W>(writer: W, flush_after: Duration) -> Self
+    where
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let (commands, queue) = mpsc::channel(QUEUE_DEPTH);
+        let failure = Arc::new(Mutex::new(None));
+        tokio::spawn(run(writer, queue, flush_after, Arc::clone(&failure)));
+        Self { commands, failure }
+    }
+
+    /// Queue one frame's bytes.
+    pub async fn write(&self, data: Vec<u8>) -> Result<(), ProtocolError> {
+        self.commands
+            .send(Command::Write(data))
+            .await
+            .map_err(|_| self.failure())
+    }
+
+    /// Write everything queued, then shut the stream down.
+    pub async fn shutdown(&self) -> Result<(), ProtocolError> {
+        let (reply, done) = oneshot::channel();
+        self.commands
+            .send(Command::Shutdown(reply))
+            .await
+            .map_err(|_| self.failure())?;
+        done.await.unwrap_or_else(|_| Err(self.failure()))
+    }
+
+    fn failure(&self) -> ProtocolError {
+        self.failure
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or_else(|| ProtocolError::InternalError("tunnel writer stopped".into()))
+    }
+}
+
+async fn run<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    mut queue: mpsc::Receiver<Command>,
+    flush_after: Duration,
+    failure: Arc<Mutex<Option<ProtocolError>>>,
+) {
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    while let Some(first) = queue.recv().await {
+        let mut shutdown = None;
+        let mut next = Some(first);
+        let deadline = Instant::now() + flush_after;
+        while let Some(command) = next.take() {
+            match command {
+                Command::Write(data) => batch.extend_from_slice(&data),
+                Command::Shutdown(reply) => {
+                    shutdown = Some(reply);
+                    break;
+                }
+            }
+            if batch.len() >= MAX_BATCH {
+                break;
+            }
+            next = match queue.try_recv() {
+                Ok(command) => Some(command),
+                Err(mpsc::error::TryRecvError::Empty) => {
+                    tokio::time::timeout_at(deadline, queue.recv())
+                        .await
+                        .ok()
+                        .flatten()
+                }
+                Err(mpsc::error::TryRecvError::Disconnected) => None,
+            };
+        }
+
+        let mut result = write_batch(&mut writer, &batch).await;
+        batch.clear();
+        if let Some(reply) = shutdown {
+            if result.is_ok() {
+                result = writer.shutdown().await.map_err(|e| {
+                    ProtocolError::InternalError(format!("tunnel shutdown failed: {}", e))
+                });
+            }
+            let _ = reply.send(result);
+            return;
+        }
+        if let Err(e) = result {
+            *failure.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+            return;
+        }
+    }
+}
+
+async fn write_batch<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    batch: &[u8],
+) -> Result<(), ProtocolError> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    writer
+        .write_all(batch)
+        .await
+        .map_err(|e| ProtocolError::InternalError(format!("tunnel write failed: {}", e)))?;
+    writer
+        .flush()
+        .await
+        .map_err(|e| ProtocolError::InternalError(format!("tunnel flush failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// A sink that counts its flushes.
+    struct Sink {
+        data: Arc<Mutex<Vec<u8>>>,
+        flushes: Arc<Mutex<usize>>,
+    }
+
+    impl AsyncWrite for Sink {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.data.lock().unwrap().extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            *self.flushes.lock().unwrap() += 1;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn bursts_are_written_with_one_flush() {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        let flushes = Arc::new(Mutex::new(0));
+        let sink = Sink {
+            data: Arc::clone(&data),
+            flushes: Arc::clone(&flushes),
+        };
+        let writer = CoalescingWriter::spawn(sink, Duration::from_millis(50));
+        for i in 0..100u8 {
+            writer.write(vec![i]).await.unwrap();
+        }
+        writer.shutdown().await.unwrap();
+
+        assert_eq!(*data.lock().unwrap(), (0..100u8).collect::<Vec<_>>());
+        assert!(*flushes.lock().unwrap() < 5);
+        assert!(writer.write(vec![0]).await.is_err());
+    }
+}
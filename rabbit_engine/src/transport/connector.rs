@@ -3,12 +3,23 @@
 //! Provides a client TLS configuration that accepts any server
 //! certificate (suitable for TOFU where identity is verified at the
 //! Rabbit protocol layer, not the TLS layer) and a `connect` function
-//! that returns a [`TlsTunnel`](super::tls::TlsTunnel).
+//! that returns a [`TlsTunnel`](super::tls::TlsTunnel).  A client can
+//! also present its own certificate with
+//! [`make_client_config_with_cert`].
+//!
+//! A client that wants the TLS layer itself to refuse unexpected
+//! servers uses [`make_client_config_pinned`], which still accepts
+//! self-signed certificates but asks a [`ServerCertPolicy`] — such as
+//! the trust cache's pinned certificates — about each one.
+//!
+//! [`connect_via`] dials through a SOCKS5 or HTTP [`Proxy`].
 
+use std::fmt::Debug;
 use std::sync::Arc;
 use std::time::Duration;
 
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
 use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
 use rustls::{ClientConfig, DigitallySignedStruct, Error, SignatureScheme};
 use tokio::net::TcpStream;
@@ -17,7 +28,16 @@ use tracing::{info, warn};
 
 use crate::protocol::error::ProtocolError;
 
-use super::tls::TlsTunnel;
+use super::cert::{load_certs, load_private_key, CertPair};
+use super::proxy::Proxy;
+use super::tls::{ClientTunnel, TlsTunnel};
+
+/// Decides whether a server's (usually self-signed) certificate is
+/// acceptable, in place of X.509 chain validation.
+pub trait ServerCertPolicy: Send + Sync + Debug {
+    /// Accept the DER certificate `cert_der`, or say why not.
+    fn check(&self, cert_der: &[u8]) -> Result<(), ProtocolError>;
+}
 
 /// Build a `ClientConfig` that accepts **any** server certificate.
 ///
@@ -25,9 +45,24 @@ use super::tls::TlsTunnel;
 /// via the protocol-level Ed25519 handshake and TOFU cache, not via
 /// certificate chain validation.
 pub fn make_client_config_insecure() -> Arc<ClientConfig> {
-    let mut config = ClientConfig::builder()
+    make_client_config_verified(None)
+}
+
+/// Build a `ClientConfig` that accepts a server certificate, self-signed
+/// or not, only if `policy` does.  The handshake fails otherwise.
+pub fn make_client_config_pinned(policy: Arc<dyn ServerCertPolicy>) -> Arc<ClientConfig> {
+    make_client_config_verified(Some(policy))
+}
+
+fn make_client_config_verified(policy: Option<Arc<dyn ServerCertPolicy>>) -> Arc<ClientConfig> {
+    let builder = ClientConfig::builder();
+    let verifier = Arc::new(RabbitServerCertVerifier {
+        provider: builder.crypto_provider().clone(),
+        policy,
+    });
+    let mut config = builder
         .dangerous()
-        .with_custom_certificate_verifier(Arc::new(InsecureServerCertVerifier))
+        .with_custom_certificate_verifier(verifier)
         .with_no_client_auth();
 
     // H1: Set ALPN protocol to "rabbit/1" for protocol identification.
@@ -36,6 +71,29 @@ pub fn make_client_config_insecure() -> Arc<ClientConfig> {
     Arc::new(config)
 }
 
+/// Like [`make_client_config_insecure`], but presenting `cert_pair` to
+/// servers that ask for a client certificate.
+pub fn make_client_config_with_cert(
+    cert_pair: &CertPair,
+) -> Result<Arc<ClientConfig>, ProtocolError> {
+    let certs = load_certs(&cert_pair.cert_pem)?;
+    let key = load_private_key(&cert_pair.key_pem)?;
+
+    let builder = ClientConfig::builder();
+    let verifier = Arc::new(RabbitServerCertVerifier {
+        provider: builder.crypto_provider().clone(),
+        policy: None,
+    });
+    let mut config = builder
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+        .with_client_auth_cert(certs, key)
+        .map_err(|e| ProtocolError::InternalError(format!("client config: {}", e)))?;
+    config.alpn_protocols = vec![b"rabbit/1".to_vec()];
+
+    Ok(Arc::new(config))
+}
+
 /// Connect to a Rabbit burrow at `addr` (e.g., `"127.0.0.1:7443"`).
 ///
 /// `server_name` is the TLS SNI value — typically `"localhost"` for
@@ -44,10 +102,23 @@ pub async fn connect(
     addr: &str,
     client_config: Arc<ClientConfig>,
     server_name: &str,
-) -> Result<TlsTunnel<tokio_rustls::client::TlsStream<TcpStream>>, ProtocolError> {
-    let tcp_stream = TcpStream::connect(addr).await.map_err(|e| {
-        ProtocolError::InternalError(format!("TCP connect to {} failed: {}", addr, e))
-    })?;
+) -> Result<ClientTunnel, ProtocolError> {
+    connect_via(addr, None, client_config, server_name).await
+}
+
+/// Like [`connect`], but through `proxy` if there is one.
+pub async fn connect_via(
+    addr: &str,
+    proxy: Option<&Proxy>,
+    client_config: Arc<ClientConfig>,
+    server_name: &str,
+) -> Result<ClientTunnel, ProtocolError> {
+    let tcp_stream = match proxy {
+        Some(proxy) => proxy.connect(addr).await?,
+        None => TcpStream::connect(addr).await.map_err(|e| {
+            ProtocolError::InternalError(format!("TCP connect to {} failed: {}", addr, e))
+        })?,
+    };
 
     let domain = ServerName::try_from(server_name.to_string()).map_err(|e| {
         ProtocolError::InternalError(format!("invalid server name '{}': {}", server_name, e))
@@ -58,7 +129,15 @@ pub async fn connect(
         ProtocolError::InternalError(format!("TLS handshake with {} failed: {}", addr, e))
     })?;
 
-    Ok(TlsTunnel::new(tls_stream, "unknown".to_string()))
+    let peer_cert = tls_stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .map(|cert| cert.to_vec());
+    let mut tunnel = TlsTunnel::new(tls_stream, "unknown".to_string());
+    tunnel.set_peer_certificate(peer_cert);
+    Ok(tunnel)
 }
 
 /// Connect to a Rabbit burrow with exponential backoff.
@@ -72,7 +151,7 @@ pub async fn connect_with_backoff(
     server_name: &str,
     max_retries: u32,
     max_backoff: Duration,
-) -> Result<TlsTunnel<tokio_rustls::client::TlsStream<TcpStream>>, ProtocolError> {
+) -> Result<ClientTunnel, ProtocolError> {
     let mut delay = Duration::from_secs(1);
     let mut last_err = None;
 
@@ -101,54 +180,69 @@ pub async fn connect_with_backoff(
     }))
 }
 
-// ── Insecure certificate verifier (TOFU model) ────────────────
+// ── Certificate verifier (TOFU model) ──────────────────────────
 
-/// A `ServerCertVerifier` that accepts any certificate.
+/// A `ServerCertVerifier` that accepts any certificate its policy
+/// accepts, or any certificate at all without one.
 ///
 /// Do NOT use this for general-purpose TLS.  It is safe here because
 /// Rabbit verifies peer identity via Ed25519 challenge/response, not
-/// via X.509 certificate chains.
+/// via X.509 certificate chains.  Handshake signatures are still
+/// checked, so the server holds the key of the certificate it shows.
 #[derive(Debug)]
-struct InsecureServerCertVerifier;
+struct RabbitServerCertVerifier {
+    provider: Arc<CryptoProvider>,
+    policy: Option<Arc<dyn ServerCertPolicy>>,
+}
 
-impl ServerCertVerifier for InsecureServerCertVerifier {
+impl ServerCertVerifier for RabbitServerCertVerifier {
     fn verify_server_cert(
         &self,
-        _end_entity: &CertificateDer<'_>,
+        end_entity: &CertificateDer<'_>,
         _intermediates: &[CertificateDer<'_>],
         _server_name: &ServerName<'_>,
         _ocsp: &[u8],
         _now: UnixTime,
     ) -> Result<ServerCertVerified, Error> {
+        if let Some(policy) = &self.policy {
+            policy
+                .check(end_entity)
+                .map_err(|e| Error::General(e.detail()))?;
+        }
         Ok(ServerCertVerified::assertion())
     }
 
     fn verify_tls12_signature(
         &self,
-        _message: &[u8],
-        _cert: &CertificateDer<'_>,
-        _dss: &DigitallySignedStruct,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
     ) -> Result<HandshakeSignatureValid, Error> {
-        Ok(HandshakeSignatureValid::assertion())
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
     }
 
     fn verify_tls13_signature(
         &self,
-        _message: &[u8],
-        _cert: &CertificateDer<'_>,
-        _dss: &DigitallySignedStruct,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
     ) -> Result<HandshakeSignatureValid, Error> {
-        Ok(HandshakeSignatureValid::assertion())
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
     }
 
     fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
-        vec![
-            SignatureScheme::ECDSA_NISTP256_SHA256,
-            SignatureScheme::ECDSA_NISTP384_SHA384,
-            SignatureScheme::ED25519,
-            SignatureScheme::RSA_PSS_SHA256,
-            SignatureScheme::RSA_PSS_SHA384,
-            SignatureScheme::RSA_PSS_SHA512,
-        ]
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
     }
 }
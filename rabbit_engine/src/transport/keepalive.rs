@@ -0,0 +1,188 @@
+//! Keepalive probes and dead-peer detection.
+//!
+//! Each tunnel sends a `PING` on lane 0 every keepalive interval.  The
+//! peer answers with a `200 PONG` echoing the probe's `Ping-Id`:
+//!
+//! ```text
+//! PING                 200 PONG
+//! Lane: 0              Lane: 0
+//! Ping-Id: 17          Ping-Id: 17
+//! End:                 End:
+//! ```
+//!
+//! A bare `PONG`, as sent by clients, is accepted as well.
+//!
+//! [`Keepalive`] tracks the probe in flight, measures the round trip
+//! when its `PONG` arrives and declares the peer dead once
+//! `max_missed` probes in a row go unanswered.  `PING` and `PONG` are
+//! handled at the tunnel level and never reach the dispatcher.
+
+use std::time::{Duration, Instant};
+
+use crate::protocol::frame::{Frame, Status, Verb, VerbKind};
+
+/// Weight of a new sample in the smoothed RTT (1/8, as in TCP).
+const RTT_GAIN: f64 = 0.125;
+
+/// Keepalive state for one tunnel.
+#[derive(Debug)]
+pub struct Keepalive {
+    max_missed: u32,
+    next_id: u64,
+    /// The unanswered probe, if any: its ID and when it was sent.
+    in_flight: Option<(u64, Instant)>,
+    missed: u32,
+    last_rtt: Option<Duration>,
+    smoothed_rtt: Option<Duration>,
+}
+
+impl Keepalive {
+    /// Track probes, giving up after `max_missed` unanswered in a row.
+    pub fn new(max_missed: u32) -> Self {
+        Self {
+            max_missed: max_missed.max(1),
+            next_id: 1,
+            in_flight: None,
+            missed: 0,
+            last_rtt: None,
+            smoothed_rtt: None,
+        }
+    }
+
+    /// Called on each keepalive tick.
+    ///
+    /// Returns the `PING` to send, or `None` if the peer has now missed
+    /// `max_missed` probes and the tunnel should be closed.
+    pub fn on_tick(&mut self) -> Option<Frame> {
+        if self.in_flight.is_some() {
+            self.missed += 1;
+            if self.missed >= self.max_missed {
+                return None;
+            }
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.in_flight = Some((id, Instant::now()));
+
+        let mut ping = Frame::new("PING");
+        ping.set_header("Lane", "0");
+        ping.set_header("Ping-Id", id.to_string());
+        Some(ping)
+    }
+
+    /// Record a `PONG`, returning the round-trip time if it answers
+    /// the probe in flight.
+    ///
+    /// Any pong shows the peer is alive and resets the missed count.
+    /// One without a `Ping-Id` (older peers) is matched to the probe in
+    /// flight.
+    pub fn on_pong(&mut self, pong: &Frame) -> Option<Duration> {
+        self.missed = 0;
+        let (id, sent) = self.in_flight?;
+        if let Some(echoed) = pong.header("Ping-Id") {
+            if echoed.parse::<u64>().ok() != Some(id) {
+                return None;
+            }
+        }
+        self.in_flight = None;
+        let rtt = sent.elapsed();
+        self.last_rtt = Some(rtt);
+        self.smoothed_rtt = Some(match self.smoothed_rtt {
+            Some(srtt) => srtt.mul_f64(1.0 - RTT_GAIN) + rtt.mul_f64(RTT_GAIN),
+            None => rtt,
+        });
+        Some(rtt)
+    }
+
+    /// The most recent round-trip time.
+    pub fn last_rtt(&self) -> Option<Duration> {
+        self.last_rtt
+    }
+
+    /// Smoothed round-trip time across all answered probes.
+    pub fn smoothed_rtt(&self) -> Option<Duration> {
+        self.smoothed_rtt
+    }
+
+    /// Consecutive probes that went unanswered.
+    pub fn missed(&self) -> u32 {
+        self.missed
+    }
+}
+
+/// Whether `frame` answers a keepalive probe: `200 PONG` or `PONG`.
+pub fn is_pong(frame: &Frame) -> bool {
+    match frame.verb_kind() {
+        VerbKind::Verb(Verb::Pong) => true,
+        VerbKind::Status(Status::Ok) => frame.args.first().map(String::as_str) == Some("PONG"),
+        _ => false,
+    }
+}
+
+/// A `PING` for the burrow's peer health checks (see
+/// [`crate::burrow::Burrow::check_peers`]).  Its `Ping-Id` is 0, which
+/// no keepalive uses, so its `PONG` is never taken for the answer to a
+/// keepalive probe.
+pub fn health_probe() -> Frame {
+    let mut ping = Frame::new("PING");
+    ping.set_header("Lane", "0");
+    ping.set_header("Ping-Id", "0");
+    ping
+}
+
+/// The `200 PONG` answering `ping`, on lane 0 with its `Ping-Id`
+/// echoed.
+pub fn pong_for(ping: &Frame) -> Frame {
+    let mut pong = Frame::new("200 PONG");
+    pong.set_header("Lane", "0");
+    if let Some(id) = ping.header("Ping-Id") {
+        pong.set_header("Ping-Id", id);
+    }
+    pong
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pong_measures_rtt() {
+        let mut keepalive = Keepalive::new(3);
+        let ping = keepalive.on_tick().unwrap();
+        assert_eq!(ping.header("Lane"), Some("0"));
+
+        let pong = pong_for(&ping);
+        assert!(is_pong(&pong));
+        assert_eq!(pong.header("Ping-Id"), ping.header("Ping-Id"));
+        assert!(keepalive.on_pong(&pong).is_some());
+        assert!(keepalive.smoothed_rtt().is_some());
+        // A duplicate answers nothing.
+        assert!(keepalive.on_pong(&pong).is_none());
+    }
+
+    #[test]
+    fn stale_pongs_do_not_count_as_answers() {
+        let mut keepalive = Keepalive::new(3);
+        let first = keepalive.on_tick().unwrap();
+        let _second = keepalive.on_tick().unwrap();
+        assert!(keepalive.on_pong(&pong_for(&first)).is_none());
+        assert_eq!(keepalive.missed(), 0);
+        assert!(keepalive.on_pong(&Frame::new("PONG")).is_some());
+    }
+
+    #[test]
+    fn gives_up_after_max_missed() {
+        let mut keepalive = Keepalive::new(2);
+        assert!(keepalive.on_tick().is_some());
+        assert!(keepalive.on_tick().is_some());
+        assert_eq!(keepalive.missed(), 1);
+        assert!(keepalive.on_tick().is_none());
+    }
+
+    #[test]
+    fn bare_pongs_are_recognised() {
+        assert!(is_pong(&Frame::new("PONG")));
+        assert!(!is_pong(&Frame::new("200 OK")));
+        assert!(!is_pong(&Frame::new("PING")));
+    }
+}
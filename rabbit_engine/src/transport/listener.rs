@@ -1,19 +1,56 @@
 //! TLS connection listener for Rabbit burrows.
 //!
 //! Binds a TCP port, wraps incoming connections in TLS, and yields
-//! [`TlsTunnel`](super::tls::TlsTunnel) instances ready for frame I/O.
+//! [`ServerTunnel`](super::tls::ServerTunnel) instances ready for frame
+//! I/O.  [`RabbitListener`] is an [`Acceptor`], so code serving tunnels
+//! need not know they are TLS.
+//!
+//! Listeners bound to `[::]` take IPv4 connections too, as
+//! IPv4-mapped addresses, wherever the system allows dual-stack
+//! sockets; see [`bind_tcp`].
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
 use rustls::ServerConfig;
-use tokio::net::{TcpListener, TcpStream};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::TcpListener;
 use tokio_rustls::TlsAcceptor;
 use tracing::warn;
 
 use crate::protocol::error::ProtocolError;
 
-use super::tls::TlsTunnel;
+use super::tls::{ServerTunnel, TlsTunnel};
+use super::tunnel::Acceptor;
+
+/// Connections waiting to be accepted before the system turns more
+/// away, unless a listener is given its own.
+pub const DEFAULT_BACKLOG: u32 = 128;
+
+/// Bind a TCP listener to `addr`, an address and port or a host name
+/// and port, queueing up to `backlog` connections.  An IPv6
+/// unspecified address listens dual-stack where it can.
+pub async fn bind_tcp(addr: &str, backlog: u32) -> Result<TcpListener, ProtocolError> {
+    let bind_error = |e: std::io::Error| {
+        ProtocolError::InternalError(format!("TCP bind failed on {}: {}", addr, e))
+    };
+    let Ok(socket_addr) = addr.parse::<SocketAddr>() else {
+        return TcpListener::bind(addr).await.map_err(bind_error);
+    };
+    let domain = Domain::for_address(socket_addr);
+    let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP)).map_err(bind_error)?;
+    if socket_addr.is_ipv6() && socket_addr.ip().is_unspecified() {
+        // Not every system allows it; such a listener stays IPv6-only.
+        let _ = socket.set_only_v6(false);
+    }
+    socket.set_reuse_address(true).map_err(bind_error)?;
+    socket.set_nonblocking(true).map_err(bind_error)?;
+    socket.bind(&socket_addr.into()).map_err(bind_error)?;
+    let backlog = i32::try_from(backlog).unwrap_or(i32::MAX);
+    socket.listen(backlog).map_err(bind_error)?;
+    TcpListener::from_std(socket.into()).map_err(bind_error)
+}
 
 /// A TLS listener that accepts incoming Rabbit connections.
 pub struct RabbitListener {
@@ -24,21 +61,23 @@ pub struct RabbitListener {
 impl RabbitListener {
     /// Bind to `addr` (e.g., `"127.0.0.1:7443"`) and prepare to accept TLS connections.
     pub async fn bind(addr: &str, server_config: Arc<ServerConfig>) -> Result<Self, ProtocolError> {
-        let tcp = TcpListener::bind(addr).await.map_err(|e| {
-            ProtocolError::InternalError(format!("TCP bind failed on {}: {}", addr, e))
-        })?;
+        let tcp = bind_tcp(addr, DEFAULT_BACKLOG).await?;
+        Ok(Self::from_tcp(tcp, server_config))
+    }
+
+    /// Accept TLS connections on an already bound listener, e.g. one
+    /// from [`bind_tcp`] with a backlog of its own.
+    pub fn from_tcp(tcp: TcpListener, server_config: Arc<ServerConfig>) -> Self {
         let acceptor = TlsAcceptor::from(server_config);
-        Ok(Self { tcp, acceptor })
+        Self { tcp, acceptor }
     }
 
     /// Accept the next incoming TLS connection.
     ///
     /// Returns a `TlsTunnel` with `peer_id` set to `"unknown"` — the
     /// Rabbit handshake layer will update it after authentication.
-    pub async fn accept(
-        &self,
-    ) -> Result<TlsTunnel<tokio_rustls::server::TlsStream<TcpStream>>, ProtocolError> {
-        let (tcp_stream, _addr) = self
+    pub async fn accept(&self) -> Result<ServerTunnel, ProtocolError> {
+        let (tcp_stream, addr) = self
             .tcp
             .accept()
             .await
@@ -48,7 +87,16 @@ impl RabbitListener {
             .accept(tcp_stream)
             .await
             .map_err(|e| ProtocolError::InternalError(format!("TLS accept failed: {}", e)))?;
-        Ok(TlsTunnel::new(tls_stream, "unknown".to_string()))
+        let peer_cert = tls_stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .map(|cert| cert.to_vec());
+        let mut tunnel = TlsTunnel::new(tls_stream, "unknown".to_string());
+        tunnel.set_peer_certificate(peer_cert);
+        tunnel.set_peer_addr(addr);
+        Ok(tunnel)
     }
 
     /// Return the local address the listener is bound to.
@@ -65,7 +113,7 @@ impl RabbitListener {
     pub async fn accept_with_timeout(
         &self,
         timeout: Duration,
-    ) -> Result<TlsTunnel<tokio_rustls::server::TlsStream<TcpStream>>, ProtocolError> {
+    ) -> Result<ServerTunnel, ProtocolError> {
         match tokio::time::timeout(timeout, self.accept()).await {
             Ok(result) => result,
             Err(_) => {
@@ -78,3 +126,15 @@ impl RabbitListener {
         }
     }
 }
+
+impl Acceptor for RabbitListener {
+    type Tunnel = ServerTunnel;
+
+    async fn accept(&self) -> Result<ServerTunnel, ProtocolError> {
+        RabbitListener::accept(self).await
+    }
+
+    fn local_addr(&self) -> Result<std::net::SocketAddr, ProtocolError> {
+        RabbitListener::local_addr(self)
+    }
+}
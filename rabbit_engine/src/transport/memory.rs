@@ -5,14 +5,24 @@
 //! and parsed on receive, exercising the full wire format just like
 //! a real TLS tunnel would.
 //!
-//! Create a linked pair with [`memory_tunnel_pair`].
+//! Create a linked pair with [`memory_tunnel_pair`], or with [`pair`]
+//! for two ends that, like a fresh TCP connection, know nothing of each
+//! other until the handshake.  A [`MemoryListener`] hands out pairs as
+//! an [`Acceptor`], so whole warrens can be served with
+//! `Burrow::serve` and dialed without certificates or sockets.
 
-use tokio::sync::mpsc;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, Mutex};
 
 use crate::protocol::error::ProtocolError;
-use crate::protocol::frame::Frame;
+use crate::protocol::frame::{Frame, FrameLimits};
+
+use super::tunnel::{Acceptor, Tunnel};
 
-use super::tunnel::Tunnel;
+/// Connections waiting to be accepted by a [`MemoryListener`].
+const BACKLOG: usize = 64;
 
 /// An in-memory tunnel backed by mpsc channels.
 #[derive(Debug)]
@@ -20,11 +30,17 @@ pub struct MemoryTunnel {
     tx: mpsc::Sender<String>,
     rx: mpsc::Receiver<String>,
     peer_id: String,
+    limits: FrameLimits,
 }
 
 impl MemoryTunnel {
     fn new(tx: mpsc::Sender<String>, rx: mpsc::Receiver<String>, peer_id: String) -> Self {
-        Self { tx, rx, peer_id }
+        Self {
+            tx,
+            rx,
+            peer_id,
+            limits: FrameLimits::default(),
+        }
     }
 }
 
@@ -39,7 +55,7 @@ impl Tunnel for MemoryTunnel {
 
     async fn recv_frame(&mut self) -> Result<Option<Frame>, ProtocolError> {
         match self.rx.recv().await {
-            Some(data) => Frame::parse(&data).map(Some),
+            Some(data) => Frame::parse_with_limits(&data, &self.limits).map(Some),
             None => Ok(None),
         }
     }
@@ -48,6 +64,10 @@ impl Tunnel for MemoryTunnel {
         &self.peer_id
     }
 
+    fn set_limits(&mut self, limits: FrameLimits) {
+        self.limits = limits;
+    }
+
     async fn close(&mut self) -> Result<(), ProtocolError> {
         // Dropping the sender side closes the channel.
         // We can't drop self.tx without consuming self, so we
@@ -72,6 +92,70 @@ pub fn memory_tunnel_pair(id_a: &str, id_b: &str) -> (MemoryTunnel, MemoryTunnel
     )
 }
 
+/// Create a linked pair of memory tunnels whose `peer_id` is
+/// `"unknown"`, as for a connection just dialed or accepted.
+pub fn pair() -> (MemoryTunnel, MemoryTunnel) {
+    memory_tunnel_pair("unknown", "unknown")
+}
+
+/// Accepts memory tunnels dialed with [`MemoryListener::connect`].
+///
+/// Clones share the listener, so one can be moved into
+/// `Burrow::serve` while another dials it.
+#[derive(Debug, Clone)]
+pub struct MemoryListener {
+    tx: mpsc::Sender<MemoryTunnel>,
+    rx: Arc<Mutex<mpsc::Receiver<MemoryTunnel>>>,
+}
+
+impl Default for MemoryListener {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryListener {
+    /// Create a listener nobody has dialed yet.
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel(BACKLOG);
+        Self {
+            tx,
+            rx: Arc::new(Mutex::new(rx)),
+        }
+    }
+
+    /// Dial the listener, returning this end of a new [`pair`] and
+    /// queueing the other for [`Acceptor::accept`].
+    pub async fn connect(&self) -> Result<MemoryTunnel, ProtocolError> {
+        let (local, remote) = pair();
+        self.tx
+            .send(remote)
+            .await
+            .map_err(|_| ProtocolError::InternalError("memory listener closed".into()))?;
+        Ok(local)
+    }
+}
+
+impl Acceptor for MemoryListener {
+    type Tunnel = MemoryTunnel;
+
+    async fn accept(&self) -> Result<MemoryTunnel, ProtocolError> {
+        self.rx
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| ProtocolError::InternalError("memory listener closed".into()))
+    }
+
+    /// Memory listeners have no address; this always fails.
+    fn local_addr(&self) -> Result<SocketAddr, ProtocolError> {
+        Err(ProtocolError::InternalError(
+            "memory listener has no address".into(),
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -129,6 +213,28 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn listeners_accept_what_is_dialed() {
+        let listener = MemoryListener::new();
+        let mut dialed = listener.connect().await.unwrap();
+        let mut accepted = listener.accept().await.unwrap();
+        assert_eq!(accepted.peer_id(), "unknown");
+
+        let mut big = Frame::new("PUBLISH /q/chat");
+        big.set_body("x".repeat(100));
+        dialed.send_frame(&big).await.unwrap();
+        accepted.set_limits(FrameLimits {
+            max_body_bytes: 10,
+            ..FrameLimits::default()
+        });
+        assert!(matches!(
+            accepted.recv_frame().await,
+            Err(ProtocolError::TooLarge(_))
+        ));
+        drop(dialed);
+        assert!(accepted.recv_frame().await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn bidirectional() {
         let (mut a, mut b) = memory_tunnel_pair("alice", "bob");
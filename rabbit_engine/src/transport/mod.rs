@@ -1,13 +1,27 @@
 //! Transport layer for the Rabbit protocol.
 //!
 //! Provides the `Tunnel` trait for bidirectional frame exchange, an
-//! in-memory implementation for testing, and a TLS implementation
-//! for production use.  Frame I/O is handled at this layer — higher
-//! layers send and receive `Frame` values, not raw bytes.
+//! in-memory implementation for testing, a TLS implementation for
+//! production use and a plain TCP one for behind a TLS proxy.  The
+//! `Acceptor` trait covers the listeners that yield tunnels.  Frame I/O is handled at this layer — higher
+//! layers send and receive `Frame` values, not raw bytes.  Frame taps
+//! and capture files let that traffic be recorded for debugging, and
+//! per-tunnel counters feed the admin `stats` command.
+//! [`reliable::ReliableTunnel`] layers lanes, acknowledgements,
+//! retransmission and request/response matching over any tunnel.
 
+pub mod capture;
 pub mod cert;
+pub mod coalesce;
 pub mod connector;
+pub mod keepalive;
 pub mod listener;
 pub mod memory;
+pub mod proxy;
+pub mod reliable;
+pub mod sim;
+pub mod stats;
+pub mod tap;
+pub mod tcp;
 pub mod tls;
 pub mod tunnel;
@@ -0,0 +1,588 @@
+//! A tunnel that owns its lanes, acknowledgements, retransmission and
+//! transactions.
+//!
+//! [`ReliableTunnel::new`] takes over a [`Tunnel`] and returns a
+//! handle plus a driver future that does all the tunnel's I/O; run the
+//! driver on its own task.  The [`Tunnel`] trait reads and writes
+//! through one `&mut` borrow, so a single driver serves as both reader
+//! and writer: it sends whatever the handle queues, and answers
+//! inbound traffic itself:
+//!
+//! - `ACK` frames retire in-flight frames ([`SelectiveAck`]).
+//! - `CREDIT` frames release frames queued for want of credit.
+//! - `PING` is answered with `200 PONG`.
+//! - Sequenced frames are acknowledged, and repeats are dropped.
+//! - Responses carrying the `Txn` of a [`ReliableTunnel::request`]
+//!   complete it.
+//! - `GOAWAY` means the peer is shutting down: it is passed on, and
+//!   new requests and reliable frames fail with `503 BUSY`.
+//!
+//! Everything else arrives on [`ReliableTunnel::next_event`].  Frames
+//! sent with [`ReliableTunnel::send_reliable`] are retransmitted until
+//! acknowledged; one that runs out of retries stops the driver with
+//! `408 TIMEOUT`.
+//!
+//! [`ReliableTunnel::close`] is graceful: it sends `GOAWAY` and waits,
+//! up to [`ReliableConfig::drain_timeout`], for the peer to acknowledge
+//! everything in flight before closing the underlying tunnel.
+//!
+//! ```no_run
+//! # async fn demo(tunnel: rabbit_engine::transport::memory::MemoryTunnel)
+//! #     -> Result<(), rabbit_engine::protocol::error::ProtocolError> {
+//! use rabbit_engine::protocol::frame::Frame;
+//! use rabbit_engine::transport::reliable::{ReliableConfig, ReliableTunnel};
+//!
+//! let (mut tunnel, driver) = ReliableTunnel::new(tunnel, ReliableConfig::default());
+//! tokio::spawn(driver);
+//! let menu = tunnel.request(Frame::with_args("LIST", vec!["/".into()])).await?;
+//! tunnel.send_reliable(3, Frame::with_args("PUBLISH", vec!["/q/chat".into()])).await?;
+//! while let Some(event) = tunnel.next_event().await {
+//!     println!("{}", event.verb);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+use crate::protocol::error::{ErrorFrame, ProtocolError};
+use crate::protocol::frame::{Frame, Verb, VerbKind};
+use crate::protocol::lane::SelectiveAck;
+use crate::protocol::lane_manager::LaneManager;
+use crate::protocol::txn::TxnTracker;
+
+use super::keepalive;
+use super::tunnel::Tunnel;
+
+/// Settings for a [`ReliableTunnel`].
+#[derive(Debug, Clone)]
+pub struct ReliableConfig {
+    /// How long a frame waits for its ACK before it is resent.
+    pub retransmit_timeout: Duration,
+    /// Resends before the tunnel gives up.
+    pub max_retries: u32,
+    /// How long [`ReliableTunnel::request`] waits for a response.
+    pub request_timeout: Duration,
+    /// Frames the handle may queue ahead of the driver.
+    pub queue_depth: usize,
+    /// How long [`ReliableTunnel::close`] waits for in-flight frames
+    /// to be acknowledged.
+    pub drain_timeout: Duration,
+}
+
+impl Default for ReliableConfig {
+    fn default() -> Self {
+        Self {
+            retransmit_timeout: Duration::from_secs(5),
+            max_retries: 3,
+            request_timeout: Duration::from_secs(30),
+            queue_depth: 256,
+            drain_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Handle to a tunnel driven by a background task.
+pub struct ReliableTunnel {
+    lanes: Arc<LaneManager>,
+    txns: Arc<TxnTracker>,
+    outbound: mpsc::Sender<Frame>,
+    events: mpsc::Receiver<Frame>,
+    request_timeout: Duration,
+    drain_timeout: Duration,
+    peer_going_away: Arc<AtomicBool>,
+    done: oneshot::Receiver<Result<(), ProtocolError>>,
+}
+
+impl ReliableTunnel {
+    /// Take over `tunnel`.  Returns the handle and the driver, which
+    /// must be run (e.g. with `tokio::spawn`) for anything to move.
+    pub fn new<T: Tunnel>(
+        tunnel: T,
+        config: ReliableConfig,
+    ) -> (Self, impl Future<Output = Result<(), ProtocolError>>) {
+        let lanes = Arc::new(LaneManager::new());
+        let txns = Arc::new(TxnTracker::new());
+        let (outbound, outbound_rx) = mpsc::channel(config.queue_depth.max(1));
+        let (events_tx, events) = mpsc::channel(config.queue_depth.max(1));
+        let (done_tx, done) = oneshot::channel();
+        let peer_going_away = Arc::new(AtomicBool::new(false));
+
+        let driver = Driver {
+            lanes: lanes.clone(),
+            txns: txns.clone(),
+            events: events_tx,
+            peer_going_away: peer_going_away.clone(),
+            config: config.clone(),
+        };
+        let run = async move {
+            let result = driver.run(tunnel, outbound_rx).await;
+            let _ = done_tx.send(result.clone());
+            result
+        };
+        let handle = Self {
+            lanes,
+            txns,
+            outbound,
+            events,
+            request_timeout: config.request_timeout,
+            drain_timeout: config.drain_timeout,
+            peer_going_away,
+            done,
+        };
+        (handle, run)
+    }
+
+    /// The tunnel's lanes.
+    pub fn lanes(&self) -> &LaneManager {
+        &self.lanes
+    }
+
+    /// The tunnel's outstanding requests.
+    pub fn txns(&self) -> &TxnTracker {
+        &self.txns
+    }
+
+    /// Whether the peer has sent `GOAWAY`.
+    pub fn peer_going_away(&self) -> bool {
+        self.peer_going_away.load(Ordering::Relaxed)
+    }
+
+    /// Send a frame once, without sequencing or retransmission.
+    pub async fn send(&self, frame: Frame) -> Result<(), ProtocolError> {
+        self.outbound.send(frame).await.map_err(|_| closed())
+    }
+
+    /// Send a frame on `lane` with a sequence number, resending it
+    /// until the peer acknowledges it.  Returns the sequence number.
+    ///
+    /// Without credit on the lane the frame waits for the peer's next
+    /// `CREDIT`.  Fails with `503 BUSY` once the peer is going away.
+    pub async fn send_reliable(&self, lane: u16, mut frame: Frame) -> Result<u64, ProtocolError> {
+        self.check_peer()?;
+        frame.set_header("Lane", lane.to_string());
+        let seq = self.lanes.next_seq(lane).await;
+        frame.set_header("Seq", seq.to_string());
+        let data = frame.serialize();
+        if self.lanes.send_or_queue(lane, data.clone()).await.is_some() {
+            self.lanes.record_sent(lane, seq, data).await;
+            self.send(frame).await?;
+        }
+        Ok(seq)
+    }
+
+    /// Send a request and wait for its response, matched by `Txn`.
+    ///
+    /// A `Txn` is assigned if the frame has none.  Fails with
+    /// `408 TIMEOUT` after the configured request timeout, and with
+    /// `499 CANCELED` if the tunnel closes first, and with `503 BUSY`
+    /// once the peer is going away.
+    pub async fn request(&self, mut frame: Frame) -> Result<Frame, ProtocolError> {
+        self.check_peer()?;
+        let mut pending = self
+            .txns
+            .register(&mut frame)
+            .with_timeout(self.request_timeout);
+        self.send(frame).await?;
+        pending.wait().await
+    }
+
+    /// The next inbound frame the tunnel did not handle itself, or
+    /// `None` once the tunnel has closed.
+    pub async fn next_event(&mut self) -> Option<Frame> {
+        self.events.recv().await
+    }
+
+    /// Send `GOAWAY`, wait up to the drain timeout until the peer has
+    /// acknowledged every frame in flight and taken every frame queued
+    /// for credit, then close the underlying tunnel.
+    ///
+    /// Returns how the driver finished, or `408 TIMEOUT` if frames
+    /// were still unacknowledged when the drain timeout ran out.
+    pub async fn close(self) -> Result<(), ProtocolError> {
+        let Self {
+            lanes,
+            outbound,
+            done,
+            drain_timeout,
+            ..
+        } = self;
+        let mut goaway = Frame::new("GOAWAY");
+        goaway.set_header("Lane", "0");
+        // A driver that has stopped has nothing left to drain.
+        let drained = outbound.send(goaway).await.is_err()
+            || tokio::time::timeout(drain_timeout, async {
+                while !outbound.is_closed() && lanes.unsettled().await > 0 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .is_ok();
+        drop(outbound);
+        let result = done.await.unwrap_or(Ok(()));
+        if result.is_ok() && !drained {
+            return Err(ProtocolError::Timeout(format!(
+                "{} frames unacknowledged at close",
+                lanes.unsettled().await
+            )));
+        }
+        result
+    }
+
+    fn check_peer(&self) -> Result<(), ProtocolError> {
+        if self.peer_going_away() {
+            return Err(ProtocolError::Busy("peer is going away".into()));
+        }
+        Ok(())
+    }
+}
+
+/// The state the driver shares with the handle.
+struct Driver {
+    lanes: Arc<LaneManager>,
+    txns: Arc<TxnTracker>,
+    events: mpsc::Sender<Frame>,
+    peer_going_away: Arc<AtomicBool>,
+    config: ReliableConfig,
+}
+
+impl Driver {
+    async fn run<T: Tunnel>(
+        &self,
+        mut tunnel: T,
+        mut outbound: mpsc::Receiver<Frame>,
+    ) -> Result<(), ProtocolError> {
+        let tick = (self.config.retransmit_timeout / 2).max(Duration::from_millis(10));
+        let mut retransmit_ticker = tokio::time::interval(tick);
+        retransmit_ticker.tick().await; // consume initial instant tick
+
+        let result = loop {
+            tokio::select! {
+                inbound = tunnel.recv_frame() => match inbound {
+                    Ok(Some(frame)) => {
+                        if let Err(e) = self.on_inbound(&mut tunnel, frame).await {
+                            break Err(e);
+                        }
+                    }
+                    Ok(None) => break Ok(()),
+                    Err(e) => break Err(e),
+                },
+                queued = outbound.recv() => match queued {
+                    Some(frame) => {
+                        if let Err(e) = tunnel.send_frame(&frame).await {
+                            break Err(e);
+                        }
+                    }
+                    // The handle was closed or dropped.
+                    None => break tunnel.close().await,
+                },
+                _ = retransmit_ticker.tick() => {
+                    if let Err(e) = self.retransmit(&mut tunnel).await {
+                        break Err(e);
+                    }
+                }
+            }
+        };
+        self.txns.cancel_all();
+        result
+    }
+
+    async fn on_inbound<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+        frame: Frame,
+    ) -> Result<(), ProtocolError> {
+        let lane: u16 = frame
+            .header("Lane")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        match frame.verb_kind() {
+            VerbKind::Verb(Verb::Ack) => {
+                match SelectiveAck::from_frame(&frame) {
+                    Ok(ack) => self.lanes.sack(lane, &ack).await,
+                    Err(e) => {
+                        let err = ErrorFrame::from(&e).in_reply_to(&frame).build();
+                        tunnel.send_frame(&err).await?;
+                    }
+                }
+                return Ok(());
+            }
+            VerbKind::Verb(Verb::Credit) => {
+                let n: u32 = frame
+                    .header("Credit")
+                    .and_then(|s| s.trim_start_matches('+').parse().ok())
+                    .unwrap_or(0);
+                for data in self.lanes.add_credit(lane, n).await {
+                    self.send_released(tunnel, lane, data).await?;
+                }
+                return Ok(());
+            }
+            VerbKind::Verb(Verb::Ping) => {
+                return tunnel.send_frame(&keepalive::pong_for(&frame)).await;
+            }
+            VerbKind::Verb(Verb::GoAway) => {
+                debug!("peer is going away");
+                self.peer_going_away.store(true, Ordering::Relaxed);
+            }
+            VerbKind::Status(_) if frame.header("Txn").is_some() => {
+                let Err(frame) = self.txns.resolve(frame) else {
+                    return Ok(());
+                };
+                return self.deliver(frame).await;
+            }
+            _ => {}
+        }
+
+        if let Some(seq) = frame.header("Seq").and_then(|s| s.parse::<u64>().ok()) {
+            let new = self.lanes.observe_inbound(lane, seq).await;
+            let mut ack = Frame::new("ACK");
+            ack.set_header("Lane", lane.to_string());
+            self.lanes.inbound_ack(lane).await.write_headers(&mut ack);
+            tunnel.send_frame(&ack).await?;
+            if !new {
+                debug!(lane, seq, "duplicate frame dropped");
+                return Ok(());
+            }
+        }
+        self.deliver(frame).await
+    }
+
+    /// Send a frame that was waiting for credit, tracking it for
+    /// retransmission from now on.
+    async fn send_released<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+        lane: u16,
+        data: String,
+    ) -> Result<(), ProtocolError> {
+        let frame = Frame::parse(&data)?;
+        if let Some(seq) = frame.header("Seq").and_then(|s| s.parse().ok()) {
+            self.lanes.record_sent(lane, seq, data).await;
+        }
+        tunnel.send_frame(&frame).await
+    }
+
+    async fn retransmit<T: Tunnel>(&self, tunnel: &mut T) -> Result<(), ProtocolError> {
+        let resends = self
+            .lanes
+            .check_retransmissions(self.config.retransmit_timeout, self.config.max_retries)
+            .await
+            .map_err(|seq| {
+                ProtocolError::Timeout(format!(
+                    "frame {} unacknowledged after {} retries",
+                    seq, self.config.max_retries
+                ))
+            })?;
+        for data in resends {
+            match Frame::parse(&data) {
+                Ok(frame) => tunnel.send_frame(&frame).await?,
+                Err(e) => warn!(err = %e, "dropping unparseable in-flight frame"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand a frame to the handle.  Frames are dropped once the handle
+    /// stops listening.
+    async fn deliver(&self, frame: Frame) -> Result<(), ProtocolError> {
+        let _ = self.events.send(frame).await;
+        Ok(())
+    }
+}
+
+fn closed() -> ProtocolError {
+    ProtocolError::InternalError("tunnel closed".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::memory::memory_tunnel_pair;
+
+    fn fast() -> ReliableConfig {
+        ReliableConfig {
+            retransmit_timeout: Duration::from_millis(20),
+            max_retries: 2,
+            request_timeout: Duration::from_secs(5),
+            ..ReliableConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_and_reliable_frames_between_two_tunnels() {
+        let (a, b) = memory_tunnel_pair("a", "b");
+        let (client, client_driver) = ReliableTunnel::new(a, fast());
+        let (mut server, server_driver) = ReliableTunnel::new(b, fast());
+        tokio::spawn(client_driver);
+        tokio::spawn(server_driver);
+
+        let responder = tokio::spawn(async move {
+            let request = server.next_event().await.unwrap();
+            assert_eq!(request.verb, "LIST");
+            let mut menu = Frame::new("200 MENU");
+            menu.set_header("Txn", request.header("Txn").unwrap());
+            server.send(menu).await.unwrap();
+
+            let event = server.next_event().await.unwrap();
+            assert_eq!(event.header("Seq"), Some("1"));
+            server
+        });
+
+        let menu = client
+            .request(Frame::with_args("LIST", vec!["/".into()]))
+            .await
+            .unwrap();
+        assert_eq!(menu.verb, "200");
+        assert_eq!(client.txns().pending(), 0);
+
+        let seq = client
+            .send_reliable(3, Frame::with_args("EVENT", vec!["/q/chat".into()]))
+            .await
+            .unwrap();
+        assert_eq!(seq, 1);
+        let server = responder.await.unwrap();
+
+        // The server's ACK retires the frame.
+        for _ in 0..50 {
+            if client.lanes().stats().await[0].in_flight == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(client.lanes().stats().await[0].in_flight, 0);
+
+        client.close().await.unwrap();
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn unacknowledged_frames_are_resent_then_given_up() {
+        let (a, mut peer) = memory_tunnel_pair("a", "peer");
+        let (tunnel, driver) = ReliableTunnel::new(a, fast());
+        let driver = tokio::spawn(driver);
+
+        tunnel
+            .send_reliable(2, Frame::with_args("EVENT", vec!["/q/chat".into()]))
+            .await
+            .unwrap();
+        let first = peer.recv_frame().await.unwrap().unwrap();
+        let again = peer.recv_frame().await.unwrap().unwrap();
+        assert_eq!(first, again);
+
+        let result = driver.await.unwrap();
+        assert!(matches!(result, Err(ProtocolError::Timeout(_))));
+        assert!(matches!(
+            tunnel.close().await,
+            Err(ProtocolError::Timeout(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn repeated_frames_are_acked_but_delivered_once() {
+        let (mut peer, b) = memory_tunnel_pair("peer", "b");
+        let (mut tunnel, driver) = ReliableTunnel::new(b, fast());
+        tokio::spawn(driver);
+
+        let mut event = Frame::with_args("EVENT", vec!["/q/chat".into()]);
+        event.set_header("Lane", "4");
+        event.set_header("Seq", "1");
+        peer.send_frame(&event).await.unwrap();
+        peer.send_frame(&event).await.unwrap();
+        event.set_header("Seq", "3");
+        peer.send_frame(&event).await.unwrap();
+
+        let acks: Vec<Frame> = [
+            peer.recv_frame().await,
+            peer.recv_frame().await,
+            peer.recv_frame().await,
+        ]
+        .into_iter()
+        .map(|f| f.unwrap().unwrap())
+        .collect();
+        assert!(acks.iter().all(|f| f.verb == "ACK"));
+        assert_eq!(acks[1].header("ACK"), Some("1"));
+        assert_eq!(acks[2].header("Ranges"), Some("3"));
+
+        assert_eq!(tunnel.next_event().await.unwrap().header("Seq"), Some("1"));
+        assert_eq!(tunnel.next_event().await.unwrap().header("Seq"), Some("3"));
+        peer.close().await.unwrap();
+        assert!(tunnel.next_event().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn close_waits_for_acks_after_goaway() {
+        let (a, mut peer) = memory_tunnel_pair("a", "peer");
+        let (tunnel, driver) = ReliableTunnel::new(
+            a,
+            ReliableConfig {
+                retransmit_timeout: Duration::from_secs(5),
+                ..ReliableConfig::default()
+            },
+        );
+        tokio::spawn(driver);
+
+        tunnel
+            .send_reliable(2, Frame::with_args("EVENT", vec!["/q/chat".into()]))
+            .await
+            .unwrap();
+        assert_eq!(peer.recv_frame().await.unwrap().unwrap().verb, "EVENT");
+        let closing = tokio::spawn(tunnel.close());
+        assert_eq!(peer.recv_frame().await.unwrap().unwrap().verb, "GOAWAY");
+        assert!(!closing.is_finished());
+
+        let mut ack = Frame::new("ACK");
+        ack.set_header("Lane", "2");
+        ack.set_header("ACK", "1");
+        peer.send_frame(&ack).await.unwrap();
+        assert!(peer.recv_frame().await.unwrap().is_none());
+        closing.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn close_gives_up_on_unacknowledged_frames() {
+        let (a, mut peer) = memory_tunnel_pair("a", "peer");
+        let (tunnel, driver) = ReliableTunnel::new(
+            a,
+            ReliableConfig {
+                drain_timeout: Duration::from_millis(50),
+                ..ReliableConfig::default()
+            },
+        );
+        tokio::spawn(driver);
+
+        tunnel
+            .send_reliable(2, Frame::with_args("EVENT", vec!["/q/chat".into()]))
+            .await
+            .unwrap();
+        assert!(matches!(
+            tunnel.close().await,
+            Err(ProtocolError::Timeout(_))
+        ));
+        assert_eq!(peer.recv_frame().await.unwrap().unwrap().verb, "EVENT");
+        assert_eq!(peer.recv_frame().await.unwrap().unwrap().verb, "GOAWAY");
+    }
+
+    #[tokio::test]
+    async fn peer_goaway_refuses_new_requests() {
+        let (mut peer, b) = memory_tunnel_pair("peer", "b");
+        let (mut tunnel, driver) = ReliableTunnel::new(b, fast());
+        tokio::spawn(driver);
+
+        peer.send_frame(&Frame::new("GOAWAY")).await.unwrap();
+        assert_eq!(tunnel.next_event().await.unwrap().verb, "GOAWAY");
+        assert!(tunnel.peer_going_away());
+        assert!(matches!(
+            tunnel
+                .request(Frame::with_args("LIST", vec!["/".into()]))
+                .await,
+            Err(ProtocolError::Busy(_))
+        ));
+        tunnel.close().await.unwrap();
+    }
+}
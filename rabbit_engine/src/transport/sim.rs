@@ -0,0 +1,340 @@
+//! Simulated network links for testing under bad conditions.
+//!
+//! A [`SimLink`] is a point-to-point link between two burrows with
+//! configurable [`LinkConditions`]: fixed latency, random jitter and
+//! random frame loss.  It can also be partitioned (every frame is
+//! dropped until it heals) or severed (open tunnels close, as when a
+//! peer drops off the network).
+//!
+//! [`sim_tunnel_pair`] opens a connection over a link and returns two
+//! [`SimTunnel`]s, one per end.  Like [`super::memory::MemoryTunnel`],
+//! frames are serialized on send and parsed on receive.  Each
+//! direction has a delivery task that holds a frame until its
+//! delivery time, so frames keep their order and `recv_frame` stays
+//! safe to use in `select!`.
+//!
+//! `rabbit-sim` builds whole warrens out of these links.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+
+use super::tunnel::Tunnel;
+
+/// Delay and loss applied to every frame on a link.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LinkConditions {
+    /// Fixed one-way delay.
+    pub latency: Duration,
+    /// Extra delay, uniformly distributed in `0..=jitter`.
+    pub jitter: Duration,
+    /// Probability in `0.0..=1.0` that a frame is lost.
+    pub loss: f64,
+}
+
+/// Frame counters for a link, summed over both directions and all
+/// connections.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    /// Frames handed to the link.
+    pub sent: u64,
+    /// Frames lost to random loss or a partition.
+    pub dropped: u64,
+    /// Frames delivered to the far end.
+    pub delivered: u64,
+}
+
+/// A simulated link between two endpoints.
+#[derive(Debug)]
+pub struct SimLink {
+    conditions: Mutex<LinkConditions>,
+    rng: Mutex<StdRng>,
+    partitioned: AtomicBool,
+    generation: watch::Sender<u64>,
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    delivered: AtomicU64,
+}
+
+impl SimLink {
+    /// Create a link.  `seed` makes its random loss and jitter
+    /// repeatable.
+    pub fn new(conditions: LinkConditions, seed: u64) -> Arc<Self> {
+        Arc::new(Self {
+            conditions: Mutex::new(conditions),
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            partitioned: AtomicBool::new(false),
+            generation: watch::channel(0).0,
+            sent: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            delivered: AtomicU64::new(0),
+        })
+    }
+
+    /// Current conditions.
+    pub fn conditions(&self) -> LinkConditions {
+        *self.conditions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Change the conditions for frames sent from now on.
+    pub fn set_conditions(&self, conditions: LinkConditions) {
+        *self.conditions.lock().unwrap_or_else(|e| e.into_inner()) = conditions;
+    }
+
+    /// Start or heal a partition.  While partitioned every frame,
+    /// including those already in flight, is dropped.
+    pub fn set_partitioned(&self, partitioned: bool) {
+        self.partitioned.store(partitioned, Ordering::Relaxed);
+    }
+
+    /// Whether the link is partitioned.
+    pub fn is_partitioned(&self) -> bool {
+        self.partitioned.load(Ordering::Relaxed)
+    }
+
+    /// Close every tunnel currently open over this link.  New
+    /// connections can be opened afterwards.
+    pub fn sever(&self) {
+        self.generation.send_modify(|g| *g += 1);
+    }
+
+    /// Frame counters so far.
+    pub fn stats(&self) -> LinkStats {
+        LinkStats {
+            sent: self.sent.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            delivered: self.delivered.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Decide the fate of a frame being sent: `None` if it is lost,
+    /// otherwise the delay before delivery.
+    fn roll(&self) -> Option<Duration> {
+        let conditions = self.conditions();
+        let mut rng = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+        if conditions.loss > 0.0 && rng.gen_bool(conditions.loss.min(1.0)) {
+            return None;
+        }
+        let jitter = if conditions.jitter.is_zero() {
+            Duration::ZERO
+        } else {
+            conditions.jitter.mul_f64(rng.gen::<f64>())
+        };
+        Some(conditions.latency + jitter)
+    }
+}
+
+/// One end of a connection over a [`SimLink`].
+#[derive(Debug)]
+pub struct SimTunnel {
+    link: Arc<SimLink>,
+    wire: mpsc::UnboundedSender<(Instant, String)>,
+    rx: mpsc::Receiver<String>,
+    severed: watch::Receiver<u64>,
+    generation: u64,
+    last_delivery: Instant,
+    peer_id: String,
+}
+
+impl SimTunnel {
+    fn is_severed(&self) -> bool {
+        *self.severed.borrow() != self.generation
+    }
+}
+
+/// Move frames from the wire to the receiver once they are due,
+/// dropping any that arrive while the link is partitioned.
+async fn deliver(
+    link: Arc<SimLink>,
+    mut wire: mpsc::UnboundedReceiver<(Instant, String)>,
+    tx: mpsc::Sender<String>,
+) {
+    while let Some((due, data)) = wire.recv().await {
+        tokio::time::sleep_until(due).await;
+        if link.is_partitioned() {
+            link.dropped.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        if tx.send(data).await.is_err() {
+            break;
+        }
+        link.delivered.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Open a connection over `link`.
+///
+/// Tunnel A's `send_frame` delivers to tunnel B's `recv_frame` and
+/// vice versa.  Each tunnel reports the *other* side's ID from
+/// `peer_id()`.  Must be called inside a Tokio runtime.
+pub fn sim_tunnel_pair(link: &Arc<SimLink>, id_a: &str, id_b: &str) -> (SimTunnel, SimTunnel) {
+    let end = |wire: mpsc::UnboundedSender<(Instant, String)>,
+               rx: mpsc::Receiver<String>,
+               peer_id: &str| {
+        let severed = link.generation.subscribe();
+        let generation = *severed.borrow();
+        SimTunnel {
+            link: Arc::clone(link),
+            wire,
+            rx,
+            severed,
+            generation,
+            last_delivery: Instant::now(),
+            peer_id: peer_id.to_string(),
+        }
+    };
+    let (wire_ab, wire_ab_rx) = mpsc::unbounded_channel();
+    let (wire_ba, wire_ba_rx) = mpsc::unbounded_channel();
+    let (tx_ab, rx_ab) = mpsc::channel(256);
+    let (tx_ba, rx_ba) = mpsc::channel(256);
+    tokio::spawn(deliver(Arc::clone(link), wire_ab_rx, tx_ab));
+    tokio::spawn(deliver(Arc::clone(link), wire_ba_rx, tx_ba));
+    (end(wire_ab, rx_ba, id_b), end(wire_ba, rx_ab, id_a))
+}
+
+impl Tunnel for SimTunnel {
+    async fn send_frame(&mut self, frame: &Frame) -> Result<(), ProtocolError> {
+        if self.is_severed() {
+            return Err(ProtocolError::InternalError(
+                "sim tunnel: link severed".into(),
+            ));
+        }
+        self.link.sent.fetch_add(1, Ordering::Relaxed);
+        let Some(delay) = self.link.roll() else {
+            self.link.dropped.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        };
+        // Never deliver before an earlier frame: links do not reorder.
+        let due = (Instant::now() + delay).max(self.last_delivery);
+        self.last_delivery = due;
+        self.wire
+            .send((due, frame.serialize()))
+            .map_err(|_| ProtocolError::InternalError("sim tunnel: peer dropped".into()))
+    }
+
+    async fn recv_frame(&mut self) -> Result<Option<Frame>, ProtocolError> {
+        loop {
+            if self.is_severed() {
+                return Ok(None);
+            }
+            tokio::select! {
+                data = self.rx.recv() => {
+                    return match data {
+                        Some(data) => Frame::parse(&data).map(Some),
+                        None => Ok(None),
+                    };
+                }
+                changed = self.severed.changed() => {
+                    if changed.is_err() {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+
+    fn peer_id(&self) -> &str {
+        &self.peer_id
+    }
+
+    async fn close(&mut self) -> Result<(), ProtocolError> {
+        // Replace the wire so the delivery task ends and the far side
+        // sees the tunnel close once in-flight frames are delivered.
+        let (dead, _) = mpsc::unbounded_channel();
+        self.wire = dead;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ping(seq: u64) -> Frame {
+        let mut frame = Frame::new("PING");
+        frame.set_header("Seq", seq.to_string());
+        frame
+    }
+
+    #[tokio::test]
+    async fn latency_and_order() {
+        let link = SimLink::new(
+            LinkConditions {
+                latency: Duration::from_millis(50),
+                jitter: Duration::from_millis(30),
+                loss: 0.0,
+            },
+            7,
+        );
+        let (mut a, mut b) = sim_tunnel_pair(&link, "a", "b");
+        let start = Instant::now();
+        for i in 0..20 {
+            a.send_frame(&ping(i)).await.unwrap();
+        }
+        for i in 0..20 {
+            let frame = b.recv_frame().await.unwrap().unwrap();
+            assert_eq!(frame.header("Seq"), Some(i.to_string().as_str()));
+        }
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(link.stats().delivered, 20);
+        assert_eq!(b.peer_id(), "a");
+    }
+
+    #[tokio::test]
+    async fn loss_and_partition_drop_frames() {
+        let link = SimLink::new(
+            LinkConditions {
+                loss: 0.5,
+                ..Default::default()
+            },
+            42,
+        );
+        let (mut a, mut b) = sim_tunnel_pair(&link, "a", "b");
+        for i in 0..200 {
+            a.send_frame(&ping(i)).await.unwrap();
+        }
+        a.close().await.unwrap();
+        let mut received = 0;
+        while b.recv_frame().await.unwrap().is_some() {
+            received += 1;
+        }
+        let stats = link.stats();
+        assert_eq!(stats.sent, 200);
+        assert_eq!(stats.delivered, received);
+        assert_eq!(stats.dropped + stats.delivered, 200);
+        assert!((50..150).contains(&received), "received {}", received);
+
+        link.set_conditions(LinkConditions::default());
+        link.set_partitioned(true);
+        let (mut a, mut b) = sim_tunnel_pair(&link, "a", "b");
+        a.send_frame(&ping(0)).await.unwrap();
+        let waited = tokio::time::timeout(Duration::from_millis(100), b.recv_frame()).await;
+        assert!(waited.is_err());
+        link.set_partitioned(false);
+        a.send_frame(&ping(1)).await.unwrap();
+        let frame = b.recv_frame().await.unwrap().unwrap();
+        assert_eq!(frame.header("Seq"), Some("1"));
+    }
+
+    #[tokio::test]
+    async fn sever_closes_open_tunnels() {
+        let link = SimLink::new(LinkConditions::default(), 1);
+        let (mut a, mut b) = sim_tunnel_pair(&link, "a", "b");
+        link.sever();
+        assert!(b.recv_frame().await.unwrap().is_none());
+        assert!(a.send_frame(&ping(0)).await.is_err());
+
+        // A new connection over the same link works.
+        let (mut a, mut b) = sim_tunnel_pair(&link, "a", "b");
+        a.send_frame(&ping(3)).await.unwrap();
+        assert!(b.recv_frame().await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,423 @@
+//! Per-tunnel and per-lane statistics.
+//!
+//! [`StatsTunnel`] wraps a [`Tunnel`] and counts the frames and bytes
+//! crossing it, per lane, into a shared [`TunnelCounters`].  A burrow
+//! registers each served tunnel's counters and [`LaneManager`] in its
+//! [`TunnelStatsRegistry`], which produces [`TunnelStats`] snapshots
+//! for the admin socket's `stats` command:
+//!
+//! ```text
+//! {"peer_id":"ed25519:…","frames_sent":120,"bytes_received":5120,
+//!  "retransmits":2,"smoothed_rtt_ms":14,"lanes":[{"lane":3,…}]}
+//! ```
+//!
+//! Bytes are counted as the serialized frame, without any transport
+//! framing.
+//!
+//! Listeners keep [`ListenerCounters`] of the connections they accept
+//! and refuse, which a burrow's [`ListenerStatsRegistry`] reports to
+//! the admin socket's `listeners` command as [`ListenerStats`].
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::{Frame, FrameLimits};
+use crate::protocol::lane::LaneStats;
+use crate::protocol::lane_manager::LaneManager;
+
+use super::tls::WireFormat;
+use super::tunnel::Tunnel;
+
+/// Frames and bytes moved in each direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Traffic {
+    frames_sent: u64,
+    frames_received: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+/// Live counters for one tunnel, shared with the registry.
+#[derive(Debug, Default)]
+pub struct TunnelCounters {
+    /// Traffic per lane.
+    lanes: Mutex<HashMap<u16, Traffic>>,
+    /// Last and smoothed keepalive round trips in milliseconds.
+    rtt_ms: Mutex<Option<(u64, u64)>>,
+}
+
+impl TunnelCounters {
+    fn record(&self, frame: &Frame, outbound: bool) {
+        let lane = frame
+            .header("Lane")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        let bytes = frame.serialize().len() as u64;
+        let mut lanes = self.lanes.lock().unwrap_or_else(|e| e.into_inner());
+        let traffic = lanes.entry(lane).or_default();
+        if outbound {
+            traffic.frames_sent += 1;
+            traffic.bytes_sent += bytes;
+        } else {
+            traffic.frames_received += 1;
+            traffic.bytes_received += bytes;
+        }
+    }
+
+    /// Record a keepalive round trip and the smoothed RTT.
+    pub fn record_rtt(&self, rtt: Duration, smoothed: Duration) {
+        *self.rtt_ms.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some((rtt.as_millis() as u64, smoothed.as_millis() as u64));
+    }
+
+    fn traffic(&self) -> HashMap<u16, Traffic> {
+        self.lanes.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// A tunnel that counts its traffic into [`TunnelCounters`].
+pub struct StatsTunnel<'a, T: Tunnel> {
+    inner: &'a mut T,
+    counters: Arc<TunnelCounters>,
+}
+
+impl<'a, T: Tunnel> StatsTunnel<'a, T> {
+    /// Wrap `inner`, counting into `counters`.
+    pub fn new(inner: &'a mut T, counters: Arc<TunnelCounters>) -> Self {
+        Self { inner, counters }
+    }
+}
+
+impl<T: Tunnel> Tunnel for StatsTunnel<'_, T> {
+    async fn send_frame(&mut self, frame: &Frame) -> Result<(), ProtocolError> {
+        self.inner.send_frame(frame).await?;
+        self.counters.record(frame, true);
+        Ok(())
+    }
+
+    async fn recv_frame(&mut self) -> Result<Option<Frame>, ProtocolError> {
+        let frame = self.inner.recv_frame().await?;
+        if let Some(f) = &frame {
+            self.counters.record(f, false);
+        }
+        Ok(frame)
+    }
+
+    fn peer_id(&self) -> &str {
+        self.inner.peer_id()
+    }
+
+    fn peer_certificate(&self) -> Option<&[u8]> {
+        self.inner.peer_certificate()
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    fn set_limits(&mut self, limits: FrameLimits) {
+        self.inner.set_limits(limits);
+    }
+
+    fn coalesce_writes(&mut self, flush_after: Duration) {
+        self.inner.coalesce_writes(flush_after);
+    }
+
+    fn supports_length_prefixed(&self) -> bool {
+        self.inner.supports_length_prefixed()
+    }
+
+    fn set_wire_format(&mut self, format: WireFormat) {
+        self.inner.set_wire_format(format);
+    }
+
+    async fn close(&mut self) -> Result<(), ProtocolError> {
+        self.inner.close().await
+    }
+}
+
+/// Snapshot of one tunnel's statistics.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TunnelStats {
+    /// The peer at the other end.
+    pub peer_id: String,
+    /// Frames sent, all lanes.
+    pub frames_sent: u64,
+    /// Frames received, all lanes.
+    pub frames_received: u64,
+    /// Bytes sent, all lanes.
+    pub bytes_sent: u64,
+    /// Bytes received, all lanes.
+    pub bytes_received: u64,
+    /// Retransmissions, all lanes.
+    pub retransmits: u64,
+    /// Most recent keepalive round trip.
+    pub rtt_ms: Option<u64>,
+    /// Smoothed keepalive round trip.
+    pub smoothed_rtt_ms: Option<u64>,
+    /// Each open lane.
+    pub lanes: Vec<LaneStats>,
+}
+
+impl TunnelStats {
+    /// Combine a tunnel's counters with a snapshot of its lanes.
+    pub async fn collect(peer_id: &str, counters: &TunnelCounters, lanes: &LaneManager) -> Self {
+        let traffic = counters.traffic();
+        let mut lanes = lanes.stats().await;
+        for lane in &mut lanes {
+            let t = traffic.get(&lane.lane).copied().unwrap_or_default();
+            lane.frames_sent = t.frames_sent;
+            lane.frames_received = t.frames_received;
+            lane.bytes_sent = t.bytes_sent;
+            lane.bytes_received = t.bytes_received;
+        }
+        let rtt = *counters.rtt_ms.lock().unwrap_or_else(|e| e.into_inner());
+        Self {
+            peer_id: peer_id.to_string(),
+            frames_sent: traffic.values().map(|t| t.frames_sent).sum(),
+            frames_received: traffic.values().map(|t| t.frames_received).sum(),
+            bytes_sent: traffic.values().map(|t| t.bytes_sent).sum(),
+            bytes_received: traffic.values().map(|t| t.bytes_received).sum(),
+            retransmits: lanes.iter().map(|l| l.retransmits).sum(),
+            rtt_ms: rtt.map(|(last, _)| last),
+            smoothed_rtt_ms: rtt.map(|(_, smoothed)| smoothed),
+            lanes,
+        }
+    }
+}
+
+/// A registered tunnel: its peer, counters and lanes.
+type Registered = (String, Arc<TunnelCounters>, Arc<LaneManager>);
+
+/// The tunnels a burrow is serving, for statistics.
+#[derive(Default)]
+pub struct TunnelStatsRegistry {
+    next_id: AtomicU64,
+    tunnels: Mutex<HashMap<u64, Registered>>,
+}
+
+impl TunnelStatsRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a tunnel.  Returns a handle for
+    /// [`TunnelStatsRegistry::unregister`].
+    pub fn register(
+        &self,
+        peer_id: &str,
+        counters: Arc<TunnelCounters>,
+        lanes: Arc<LaneManager>,
+    ) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock()
+            .insert(id, (peer_id.to_string(), counters, lanes));
+        id
+    }
+
+    /// Forget a tunnel once it closes.
+    pub fn unregister(&self, id: u64) {
+        self.lock().remove(&id);
+    }
+
+    /// Snapshot every registered tunnel, sorted by peer.
+    pub async fn snapshot(&self) -> Vec<TunnelStats> {
+        let mut tunnels: Vec<(u64, Registered)> =
+            self.lock().iter().map(|(&id, r)| (id, r.clone())).collect();
+        tunnels.sort_by(|(a_id, a), (b_id, b)| a.0.cmp(&b.0).then(a_id.cmp(b_id)));
+        let mut stats = Vec::with_capacity(tunnels.len());
+        for (_, (peer_id, counters, lanes)) in tunnels {
+            stats.push(TunnelStats::collect(&peer_id, &counters, &lanes).await);
+        }
+        stats
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Registered>> {
+        self.tunnels.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// ── Listeners ──────────────────────────────────────────────────
+
+/// Something that happened to a connection on a listener.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListenerEvent {
+    /// A connection was accepted and handed to the burrow.
+    Accepted,
+    /// A connection was refused: the listener had as many tunnels open
+    /// as it allows.
+    RefusedFull,
+    /// A connection was refused: its address started too many
+    /// handshakes this second.
+    RefusedRate,
+    /// A tunnel did not complete the handshake in time.
+    HandshakeTimeout,
+    /// Accepting failed, e.g. on a bad TLS handshake.
+    AcceptFailed,
+}
+
+/// Live counters for one listener.
+#[derive(Debug, Default)]
+pub struct ListenerCounters {
+    address: String,
+    active: AtomicU64,
+    accepted: AtomicU64,
+    refused_full: AtomicU64,
+    refused_rate: AtomicU64,
+    handshake_timeouts: AtomicU64,
+    accept_failures: AtomicU64,
+}
+
+impl ListenerCounters {
+    /// Counters for the listener on `address`.
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Count `event`.
+    pub fn record(&self, event: ListenerEvent) {
+        let counter = match event {
+            ListenerEvent::Accepted => &self.accepted,
+            ListenerEvent::RefusedFull => &self.refused_full,
+            ListenerEvent::RefusedRate => &self.refused_rate,
+            ListenerEvent::HandshakeTimeout => &self.handshake_timeouts,
+            ListenerEvent::AcceptFailed => &self.accept_failures,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Note a tunnel opening through the listener.
+    pub fn tunnel_opened(&self) {
+        self.active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Note a tunnel through the listener closing.
+    pub fn tunnel_closed(&self) {
+        self.active.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Tunnels open through the listener.
+    pub fn active(&self) -> u64 {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Snapshot the counters.
+    pub fn snapshot(&self) -> ListenerStats {
+        ListenerStats {
+            address: self.address.clone(),
+            active: self.active(),
+            accepted: self.accepted.load(Ordering::Relaxed),
+            refused_full: self.refused_full.load(Ordering::Relaxed),
+            refused_rate: self.refused_rate.load(Ordering::Relaxed),
+            handshake_timeouts: self.handshake_timeouts.load(Ordering::Relaxed),
+            accept_failures: self.accept_failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Snapshot of one listener's statistics.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListenerStats {
+    /// The address listened on.
+    pub address: String,
+    /// Tunnels open through the listener.
+    pub active: u64,
+    /// Connections handed to the burrow.
+    pub accepted: u64,
+    /// Connections refused because the listener was full.
+    pub refused_full: u64,
+    /// Connections refused for starting too many handshakes.
+    pub refused_rate: u64,
+    /// Tunnels closed for not completing the handshake in time.
+    pub handshake_timeouts: u64,
+    /// Connections that failed before they became tunnels.
+    pub accept_failures: u64,
+}
+
+/// The listeners serving a burrow, for statistics.
+#[derive(Debug, Default)]
+pub struct ListenerStatsRegistry {
+    listeners: Mutex<Vec<Arc<ListenerCounters>>>,
+}
+
+impl ListenerStatsRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a listener's counters.
+    pub fn register(&self, counters: Arc<ListenerCounters>) {
+        self.listeners
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(counters);
+    }
+
+    /// Snapshot every registered listener, sorted by address.
+    pub fn snapshot(&self) -> Vec<ListenerStats> {
+        let mut stats: Vec<ListenerStats> = self
+            .listeners
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|counters| counters.snapshot())
+            .collect();
+        stats.sort_by(|a, b| a.address.cmp(&b.address));
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::memory::memory_tunnel_pair;
+
+    #[tokio::test]
+    async fn counts_traffic_per_lane() {
+        let (mut a, mut b) = memory_tunnel_pair("a", "b");
+        let counters = Arc::new(TunnelCounters::default());
+        let lanes = Arc::new(LaneManager::new());
+        lanes.open(3).await.unwrap();
+
+        let mut event = Frame::with_args("EVENT", vec!["/q/chat".into()]);
+        event.set_header("Lane", "3");
+        event.set_body("hello");
+        let wire = event.serialize().len() as u64;
+        {
+            let mut tunnel = StatsTunnel::new(&mut a, counters.clone());
+            tunnel.send_frame(&event).await.unwrap();
+            tunnel.send_frame(&Frame::new("PING")).await.unwrap();
+            b.send_frame(&event).await.unwrap();
+            tunnel.recv_frame().await.unwrap().unwrap();
+        }
+        counters.record_rtt(Duration::from_millis(12), Duration::from_millis(10));
+
+        let registry = TunnelStatsRegistry::new();
+        let id = registry.register("b", counters, lanes);
+        let stats = registry.snapshot().await;
+        assert_eq!(stats.len(), 1);
+        let stats = &stats[0];
+        assert_eq!(stats.frames_sent, 2);
+        assert_eq!(stats.frames_received, 1);
+        assert_eq!(stats.bytes_received, wire);
+        assert_eq!(stats.smoothed_rtt_ms, Some(10));
+        assert_eq!(stats.lanes.len(), 1);
+        assert_eq!(stats.lanes[0].frames_sent, 1);
+        assert_eq!(stats.lanes[0].bytes_sent, wire);
+        assert_eq!(stats.lanes[0].credits, 16);
+
+        registry.unregister(id);
+        assert!(registry.snapshot().await.is_empty());
+    }
+}
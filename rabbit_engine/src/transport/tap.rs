@@ -0,0 +1,184 @@
+//! Frame taps — observe every frame crossing a tunnel.
+//!
+//! A [`FrameTap`] is told about each frame a tunnel sends or receives,
+//! after TLS decryption and parsing.  [`TapTunnel`] wraps any
+//! [`Tunnel`] and reports its traffic to a tap; a burrow with a tap
+//! installed (see `Burrow::set_frame_tap`) wraps every incoming
+//! tunnel this way.
+//!
+//! Taps are called inline on the tunnel's task, so they should be
+//! quick.  [`super::capture::CaptureWriter`] is the tap behind
+//! `rabbit-dump` and `burrow serve --capture`.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::{Frame, FrameLimits};
+
+use super::tls::WireFormat;
+use super::tunnel::Tunnel;
+
+/// Connection numbers handed out to tapped tunnels.
+static CONNECTION_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+/// Which way a tapped frame travelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapDirection {
+    /// Received from the remote side.
+    Inbound,
+    /// Sent to the remote side.
+    Outbound,
+}
+
+impl TapDirection {
+    /// Short label used in capture files: `in` or `out`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Inbound => "in",
+            Self::Outbound => "out",
+        }
+    }
+
+    /// Parse a label produced by [`TapDirection::label`].
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "in" => Some(Self::Inbound),
+            "out" => Some(Self::Outbound),
+            _ => None,
+        }
+    }
+}
+
+/// Observer of tunnel traffic.
+pub trait FrameTap: Send + Sync {
+    /// Called once per frame.  `conn` identifies the tunnel; `peer` is
+    /// whatever the tunnel reports as its peer at that moment.
+    fn on_frame(&self, conn: u64, peer: &str, direction: TapDirection, frame: &Frame);
+}
+
+/// A tunnel that reports its traffic to a [`FrameTap`].
+///
+/// TLS tunnels report their peer as `unknown`, so once an inbound
+/// `HELLO` names a `Burrow-ID` that ID is reported instead.
+pub struct TapTunnel<'a, T: Tunnel> {
+    inner: &'a mut T,
+    tap: Arc<dyn FrameTap>,
+    conn: u64,
+    hello_id: Option<String>,
+}
+
+impl<'a, T: Tunnel> TapTunnel<'a, T> {
+    /// Wrap `inner`, assigning it a fresh connection number.
+    pub fn new(inner: &'a mut T, tap: Arc<dyn FrameTap>) -> Self {
+        Self {
+            inner,
+            tap,
+            conn: CONNECTION_COUNTER.fetch_add(1, Ordering::Relaxed),
+            hello_id: None,
+        }
+    }
+
+    fn peer(&self) -> &str {
+        self.hello_id
+            .as_deref()
+            .unwrap_or_else(|| self.inner.peer_id())
+    }
+
+    /// The connection number reported to the tap.
+    pub fn conn(&self) -> u64 {
+        self.conn
+    }
+}
+
+impl<T: Tunnel> Tunnel for TapTunnel<'_, T> {
+    async fn send_frame(&mut self, frame: &Frame) -> Result<(), ProtocolError> {
+        self.tap
+            .on_frame(self.conn, self.peer(), TapDirection::Outbound, frame);
+        self.inner.send_frame(frame).await
+    }
+
+    async fn recv_frame(&mut self) -> Result<Option<Frame>, ProtocolError> {
+        let frame = self.inner.recv_frame().await?;
+        if let Some(f) = &frame {
+            if f.verb == "HELLO" && self.inner.peer_id() == "unknown" {
+                self.hello_id = f.header("Burrow-ID").map(str::to_string);
+            }
+            self.tap
+                .on_frame(self.conn, self.peer(), TapDirection::Inbound, f);
+        }
+        Ok(frame)
+    }
+
+    fn peer_id(&self) -> &str {
+        self.inner.peer_id()
+    }
+
+    fn peer_certificate(&self) -> Option<&[u8]> {
+        self.inner.peer_certificate()
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    fn set_limits(&mut self, limits: FrameLimits) {
+        self.inner.set_limits(limits);
+    }
+
+    fn coalesce_writes(&mut self, flush_after: Duration) {
+        self.inner.coalesce_writes(flush_after);
+    }
+
+    fn supports_length_prefixed(&self) -> bool {
+        self.inner.supports_length_prefixed()
+    }
+
+    fn set_wire_format(&mut self, format: WireFormat) {
+        self.inner.set_wire_format(format);
+    }
+
+    async fn close(&mut self) -> Result<(), ProtocolError> {
+        self.inner.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::transport::memory::memory_tunnel_pair;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(TapDirection, String)>>);
+
+    impl FrameTap for Recorder {
+        fn on_frame(&self, _conn: u64, _peer: &str, direction: TapDirection, frame: &Frame) {
+            self.0.lock().unwrap().push((direction, frame.verb.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn records_both_directions() {
+        let recorder = Arc::new(Recorder::default());
+        let (mut a, mut b) = memory_tunnel_pair("alice", "bob");
+        let mut tapped = TapTunnel::new(&mut a, recorder.clone());
+
+        tapped.send_frame(&Frame::new("PING")).await.unwrap();
+        b.recv_frame().await.unwrap().unwrap();
+        b.send_frame(&Frame::new("PONG")).await.unwrap();
+        tapped.recv_frame().await.unwrap().unwrap();
+
+        let seen = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            seen,
+            vec![
+                (TapDirection::Outbound, "PING".to_string()),
+                (TapDirection::Inbound, "PONG".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! Plain TCP tunnels, without TLS.
+//!
+//! The Rabbit handshake still proves each side's identity, but nothing
+//! hides or protects the frames on the wire, so plain tunnels are for
+//! loopback, tests, and burrows behind a proxy that terminates TLS for
+//! them.
+
+use std::net::SocketAddr;
+
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::protocol::error::ProtocolError;
+
+use super::listener::{bind_tcp, DEFAULT_BACKLOG};
+use super::tls::TlsTunnel;
+use super::tunnel::Acceptor;
+
+/// A tunnel over a bare TCP stream.
+pub type TcpTunnel = TlsTunnel<TcpStream>;
+
+/// Connect to a burrow accepting plain TCP at `addr`.
+pub async fn connect_plain(addr: &str) -> Result<TcpTunnel, ProtocolError> {
+    let stream = TcpStream::connect(addr).await.map_err(|e| {
+        ProtocolError::InternalError(format!("TCP connect to {} failed: {}", addr, e))
+    })?;
+    let peer_addr = stream.peer_addr().ok();
+    let mut tunnel = TlsTunnel::new(stream, "unknown".to_string());
+    if let Some(peer_addr) = peer_addr {
+        tunnel.set_peer_addr(peer_addr);
+    }
+    Ok(tunnel)
+}
+
+/// Accepts plain TCP connections as tunnels.
+#[derive(Debug)]
+pub struct PlainListener {
+    tcp: TcpListener,
+}
+
+impl PlainListener {
+    /// Bind to `addr` (e.g., `"127.0.0.1:7080"`).
+    pub async fn bind(addr: &str) -> Result<Self, ProtocolError> {
+        let tcp = bind_tcp(addr, DEFAULT_BACKLOG).await?;
+        Ok(Self::from_tcp(tcp))
+    }
+
+    /// Accept connections on an already bound listener.
+    pub fn from_tcp(tcp: TcpListener) -> Self {
+        Self { tcp }
+    }
+}
+
+impl Acceptor for PlainListener {
+    type Tunnel = TcpTunnel;
+
+    async fn accept(&self) -> Result<TcpTunnel, ProtocolError> {
+        let (stream, addr) = self
+            .tcp
+            .accept()
+            .await
+            .map_err(|e| ProtocolError::InternalError(format!("TCP accept failed: {}", e)))?;
+        let mut tunnel = TlsTunnel::new(stream, "unknown".to_string());
+        tunnel.set_peer_addr(addr);
+        Ok(tunnel)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, ProtocolError> {
+        self.tcp
+            .local_addr()
+            .map_err(|e| ProtocolError::InternalError(format!("local_addr: {}", e)))
+    }
+}
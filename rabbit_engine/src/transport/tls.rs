@@ -2,28 +2,86 @@
 //!
 //! `TlsTunnel<S>` wraps any `AsyncRead + AsyncWrite` stream (typically
 //! a `tokio_rustls` client or server TLS stream) and implements the
-//! [`Tunnel`](super::tunnel::Tunnel) trait for frame-level I/O.
+//! [`Tunnel`](super::tunnel::Tunnel) trait for frame-level I/O.  The
+//! tunnels the connector and listener hand out are named
+//! [`ClientTunnel`] and [`ServerTunnel`]; [`super::tcp::TcpTunnel`] is
+//! the same over a bare TCP stream.
 //!
-//! Frame reading is buffered: we read lines until `End:\r\n`, extract
-//! the `Length` header, then read exactly that many body bytes.  This
+//! Frame reading is incremental: received bytes are fed to a
+//! [`FrameDecoder`], which finds the `End:\r\n` line, reads the
+//! `Length` header, and waits for exactly that many body bytes.  This
 //! correctly handles bodies that contain `End:` or other header-like
-//! content.
+//! content, frames split across reads, and several frames arriving in
+//! one read.
+//!
+//! A tunnel can be switched to [`WireFormat::LengthPrefixed`], which
+//! wraps each frame in the fixed header described in
+//! [`crate::protocol::frame`] and carries binary bodies as raw bytes.
+//! Both ends must use the same format; a burrow with
+//! `network.length_prefixed` offers it in its `HELLO` and both ends
+//! switch once the handshake is done (see
+//! [`crate::burrow::Burrow::client_handshake`]).
+//!
+//! [`Tunnel::set_limits`] bounds the frames either format accepts; a
+//! frame over the limits fails with [`ProtocolError::TooLarge`] and
+//! the tunnel moves on to the next one where the stream allows.
+//!
+//! Each frame is written and flushed as it is sent until
+//! [`Tunnel::coalesce_writes`] hands the write half to a
+//! [`CoalescingWriter`] task.
+
+use std::net::SocketAddr;
+use std::time::Duration;
 
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
 
 use crate::protocol::error::ProtocolError;
-use crate::protocol::frame::Frame;
+use crate::protocol::frame::{Frame, FrameCodec, FrameDecoder, FrameLimits};
 
+use super::coalesce::CoalescingWriter;
 use super::tunnel::Tunnel;
 
+/// Where a tunnel's frames are written.
+enum Writer<S> {
+    /// Straight to the stream, flushing after each frame.
+    Direct(WriteHalf<S>),
+    /// Through a writer task that coalesces frames.
+    Coalescing(CoalescingWriter),
+    /// Only while the write half moves to a writer task; no send
+    /// ever sees it.
+    Moving,
+}
+
+/// How frames are delimited on the stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireFormat {
+    /// CRLF text frames, bodies sized by the `Length` header.
+    #[default]
+    Text,
+    /// Text frames behind a magic/version/flags/length header.
+    LengthPrefixed,
+}
+
+/// A tunnel dialed with [`super::connector::connect`].
+pub type ClientTunnel = TlsTunnel<tokio_rustls::client::TlsStream<TcpStream>>;
+
+/// A tunnel accepted by [`super::listener::RabbitListener`].
+pub type ServerTunnel = TlsTunnel<tokio_rustls::server::TlsStream<TcpStream>>;
+
 /// A TLS tunnel that exchanges frames over an async byte stream.
 ///
 /// Generic over the underlying stream type so it works with both
 /// `tokio_rustls::client::TlsStream` and `server::TlsStream`.
 pub struct TlsTunnel<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin> {
     reader: BufReader<ReadHalf<S>>,
-    writer: WriteHalf<S>,
+    writer: Writer<S>,
     peer_id: String,
+    peer_cert: Option<Vec<u8>>,
+    peer_addr: Option<SocketAddr>,
+    format: WireFormat,
+    codec: FrameCodec,
+    decoder: FrameDecoder,
 }
 
 impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin> TlsTunnel<S> {
@@ -35,25 +93,77 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin> TlsTunnel<S
         let (read_half, write_half) = tokio::io::split(stream);
         Self {
             reader: BufReader::new(read_half),
-            writer: write_half,
+            writer: Writer::Direct(write_half),
             peer_id,
+            peer_cert: None,
+            peer_addr: None,
+            format: WireFormat::Text,
+            codec: FrameCodec::new(),
+            decoder: FrameDecoder::new(),
+        }
+    }
+
+    /// Wrap a stream using the given wire format.
+    pub fn with_format(stream: S, peer_id: String, format: WireFormat) -> Self {
+        let mut tunnel = Self::new(stream, peer_id);
+        tunnel.format = format;
+        tunnel
+    }
+
+    /// Switch wire format for subsequent frames.  Bytes received but
+    /// not yet decoded are decoded in the new format.
+    pub fn set_format(&mut self, format: WireFormat) {
+        if format == self.format {
+            return;
         }
+        match format {
+            WireFormat::Text => self.decoder.push(&self.codec.take_remaining()),
+            WireFormat::LengthPrefixed => self.codec.feed(&self.decoder.take_remaining()),
+        }
+        self.format = format;
+    }
+
+    /// The wire format in use.
+    pub fn format(&self) -> WireFormat {
+        self.format
     }
 
     /// Update the peer ID (e.g., after the Rabbit handshake completes).
     pub fn set_peer_id(&mut self, id: String) {
         self.peer_id = id;
     }
+
+    /// Record the DER certificate the peer presented during the TLS
+    /// handshake.
+    pub fn set_peer_certificate(&mut self, cert: Option<Vec<u8>>) {
+        self.peer_cert = cert;
+    }
+
+    /// Record the peer's network address.
+    pub fn set_peer_addr(&mut self, addr: SocketAddr) {
+        self.peer_addr = Some(addr);
+    }
 }
 
-impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin> Tunnel for TlsTunnel<S> {
+impl<S> Tunnel for TlsTunnel<S>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin + 'static,
+{
     async fn send_frame(&mut self, frame: &Frame) -> Result<(), ProtocolError> {
-        let data = frame.serialize();
-        self.writer
-            .write_all(data.as_bytes())
+        let data = match self.format {
+            WireFormat::Text => frame.serialize().into_bytes(),
+            WireFormat::LengthPrefixed => self.codec.encode(frame)?,
+        };
+        let writer = match &mut self.writer {
+            Writer::Direct(writer) => writer,
+            Writer::Coalescing(coalescer) => return coalescer.write(data).await,
+            Writer::Moving => return Err(writer_moving()),
+        };
+        writer
+            .write_all(&data)
             .await
             .map_err(|e| ProtocolError::InternalError(format!("tunnel write failed: {}", e)))?;
-        self.writer
+        writer
             .flush()
             .await
             .map_err(|e| ProtocolError::InternalError(format!("tunnel flush failed: {}", e)))?;
@@ -61,21 +171,81 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin> Tunnel for
     }
 
     async fn recv_frame(&mut self) -> Result<Option<Frame>, ProtocolError> {
-        read_frame_from_stream(&mut self.reader).await
+        loop {
+            let (frame, buffered) = match self.format {
+                WireFormat::Text => (self.decoder.next_frame()?, self.decoder.buffered()),
+                WireFormat::LengthPrefixed => (self.codec.decode()?, self.codec.buffered()),
+            };
+            if frame.is_some() {
+                return Ok(frame);
+            }
+            let mut chunk = [0u8; 8192];
+            let n =
+                self.reader.read(&mut chunk).await.map_err(|e| {
+                    ProtocolError::InternalError(format!("tunnel read failed: {}", e))
+                })?;
+            if n == 0 {
+                if buffered == 0 {
+                    return Ok(None); // Clean close
+                }
+                return Err(ProtocolError::BadRequest("unexpected EOF in frame".into()));
+            }
+            match self.format {
+                WireFormat::Text => self.decoder.push(&chunk[..n]),
+                WireFormat::LengthPrefixed => self.codec.feed(&chunk[..n]),
+            }
+        }
     }
 
     fn peer_id(&self) -> &str {
         &self.peer_id
     }
 
+    fn peer_certificate(&self) -> Option<&[u8]> {
+        self.peer_cert.as_deref()
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    fn set_limits(&mut self, limits: FrameLimits) {
+        self.decoder.set_limits(limits);
+        self.codec.set_limits(limits);
+    }
+
+    fn supports_length_prefixed(&self) -> bool {
+        true
+    }
+
+    fn set_wire_format(&mut self, format: WireFormat) {
+        self.set_format(format);
+    }
+
+    fn coalesce_writes(&mut self, flush_after: Duration) {
+        self.writer = match std::mem::replace(&mut self.writer, Writer::Moving) {
+            Writer::Direct(writer) => {
+                Writer::Coalescing(CoalescingWriter::spawn(writer, flush_after))
+            }
+            writer => writer,
+        };
+    }
+
     async fn close(&mut self) -> Result<(), ProtocolError> {
-        self.writer
-            .shutdown()
-            .await
-            .map_err(|e| ProtocolError::InternalError(format!("tunnel shutdown failed: {}", e)))
+        match &mut self.writer {
+            Writer::Direct(writer) => writer.shutdown().await.map_err(|e| {
+                ProtocolError::InternalError(format!("tunnel shutdown failed: {}", e))
+            }),
+            Writer::Coalescing(coalescer) => coalescer.shutdown().await,
+            Writer::Moving => Err(writer_moving()),
+        }
     }
 }
 
+fn writer_moving() -> ProtocolError {
+    ProtocolError::InternalError("tunnel writer is moving to its task".into())
+}
+
 /// Read a single complete frame from a buffered async reader.
 ///
 /// Algorithm:
@@ -237,4 +407,33 @@ mod tests {
         tunnel.set_peer_id("ed25519:ABCDEF".to_string());
         assert_eq!(tunnel.peer_id(), "ed25519:ABCDEF");
     }
+
+    #[tokio::test]
+    async fn length_prefixed_survives_small_segments() {
+        // A 64-byte pipe forces every frame to arrive in pieces.
+        let (client_stream, server_stream) = duplex(64);
+        let mut client =
+            TlsTunnel::with_format(client_stream, "server".into(), WireFormat::LengthPrefixed);
+        let mut server =
+            TlsTunnel::with_format(server_stream, "client".into(), WireFormat::LengthPrefixed);
+
+        let body = "line one\r\nEnd:\r\n".repeat(500);
+        let sender = tokio::spawn(async move {
+            for i in 0..3 {
+                let mut frame = Frame::new("200 CONTENT");
+                frame.set_header("Seq", i.to_string());
+                frame.set_body(format!("{}{}", body, i));
+                client.send_frame(&frame).await.unwrap();
+            }
+            client.close().await.unwrap();
+        });
+
+        for i in 0..3 {
+            let received = server.recv_frame().await.unwrap().unwrap();
+            assert_eq!(received.header("Seq"), Some(i.to_string().as_str()));
+            assert!(received.body.unwrap().ends_with(&format!("End:\r\n{}", i)));
+        }
+        assert!(server.recv_frame().await.unwrap().is_none());
+        sender.await.unwrap();
+    }
 }
@@ -4,26 +4,37 @@
 //! Implementations include [`super::memory::MemoryTunnel`] (for tests)
 //! and [`super::tls::TlsTunnel`] (for production TLS connections).
 //!
-//! The trait uses `async fn` (stable since Rust 1.75).  All
-//! implementations must be `Send` so tunnels can be moved across
-//! tokio tasks.
+//! Implementations write the methods as `async fn`; the trait requires
+//! the futures to be `Send`, so code generic over a tunnel can still
+//! serve it on a spawned task.  [`Acceptor`] does the same for the
+//! listeners that yield tunnels, so a burrow can be served over any
+//! transport: TLS ([`super::listener::RabbitListener`]), plain TCP
+//! ([`super::tcp::PlainListener`]) or, with the `quic` feature, QUIC.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
 
 use crate::protocol::error::ProtocolError;
-use crate::protocol::frame::Frame;
+use crate::protocol::frame::{Frame, FrameLimits};
+
+use super::tls::WireFormat;
 
 /// A bidirectional tunnel for exchanging Rabbit protocol frames.
 ///
 /// Implementations handle serialization, framing, and transport
 /// details internally.  Callers work only with [`Frame`] values.
-#[allow(async_fn_in_trait)]
 pub trait Tunnel: Send {
     /// Send a frame to the peer.
-    async fn send_frame(&mut self, frame: &Frame) -> Result<(), ProtocolError>;
+    fn send_frame(
+        &mut self,
+        frame: &Frame,
+    ) -> impl Future<Output = Result<(), ProtocolError>> + Send;
 
     /// Receive the next frame from the peer.
     ///
     /// Returns `Ok(None)` when the tunnel is cleanly closed.
-    async fn recv_frame(&mut self) -> Result<Option<Frame>, ProtocolError>;
+    fn recv_frame(&mut self) -> impl Future<Output = Result<Option<Frame>, ProtocolError>> + Send;
 
     /// The peer's identity string.
     ///
@@ -32,6 +43,58 @@ pub trait Tunnel: Send {
     /// construction time.
     fn peer_id(&self) -> &str;
 
+    /// The DER certificate the peer presented, if the transport has
+    /// one.
+    fn peer_certificate(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// The peer's network address, if the transport has one.
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Apply size limits to frames received from now on.
+    ///
+    /// Tunnels that do not parse bytes themselves ignore this.
+    fn set_limits(&mut self, _limits: FrameLimits) {}
+
+    /// Gather frames sent within `flush_after` of each other into one
+    /// write instead of writing and flushing each (see
+    /// [`super::coalesce`]).  Sends may then succeed before the frame
+    /// is written.
+    ///
+    /// Tunnels that do not write to a byte stream ignore this.
+    fn coalesce_writes(&mut self, _flush_after: Duration) {}
+
+    /// Whether [`Tunnel::set_wire_format`] can move this tunnel to
+    /// [`WireFormat::LengthPrefixed`], which the handshake offers only
+    /// if so.
+    ///
+    /// Tunnels that do not write to a byte stream cannot.
+    fn supports_length_prefixed(&self) -> bool {
+        false
+    }
+
+    /// Delimit the frames sent and received from now on with
+    /// `format`.
+    ///
+    /// Tunnels that do not write to a byte stream ignore this.
+    fn set_wire_format(&mut self, _format: WireFormat) {}
+
     /// Close the tunnel gracefully.
-    async fn close(&mut self) -> Result<(), ProtocolError>;
+    fn close(&mut self) -> impl Future<Output = Result<(), ProtocolError>> + Send;
+}
+
+/// A source of incoming tunnels, such as a listening socket.
+pub trait Acceptor: Send + Sync {
+    /// The tunnels accepted.
+    type Tunnel: Tunnel + 'static;
+
+    /// Accept the next tunnel.  Its `peer_id` is `"unknown"` until the
+    /// Rabbit handshake.
+    fn accept(&self) -> impl Future<Output = Result<Self::Tunnel, ProtocolError>> + Send;
+
+    /// The local address accepted on.
+    fn local_addr(&self) -> Result<SocketAddr, ProtocolError>;
 }
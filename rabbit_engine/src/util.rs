@@ -0,0 +1,12 @@
+//! Small helpers shared across the engine.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The current time in whole seconds since the Unix epoch, or 0 if
+/// the clock is set before it.
+pub(crate) fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -1,24 +1,43 @@
 //! Warren discovery — generates a directory of peers for LIST /warren.
 //!
 //! The `/warren` selector is a virtual menu built dynamically from
-//! the [`PeerTable`](super::peers::PeerTable).
+//! the [`PeerTable`](super::peers::PeerTable).  Two more come from the
+//! [`TrustCache`]: `/anchors`, the anchors whose manifests the burrow
+//! holds, and `/trusted`, the peers whose keys it has pinned.
+//! `/warren/topology` shows the [`Topology`] the routing table knows,
+//! as a menu for `LIST` and as JSON for `FETCH`.
 
 use crate::content::store::MenuItem;
-use crate::warren::peers::PeerTable;
+use crate::security::trust::TrustCache;
+use crate::warren::peers::{PeerHealth, PeerTable};
+use crate::warren::routing::Topology;
+
+/// Selector of the peer directory.
+pub const WARREN_SELECTOR: &str = "/warren";
+
+/// Selector of the anchor directory.
+pub const ANCHORS_SELECTOR: &str = "/anchors";
+
+/// Selector of the trusted peer directory.
+pub const TRUSTED_SELECTOR: &str = "/trusted";
+
+/// Selector of the warren topology.
+pub const TOPOLOGY_SELECTOR: &str = "/warren/topology";
 
 /// Build a list of [`MenuItem`]s representing the current warren.
 ///
 /// Connected peers are shown with their name and address so the user
 /// can connect directly via `rabbit browse <address>`.  Disconnected
-/// peers appear as greyed-out info lines.
+/// peers appear as greyed-out info lines.  Peers that are down are left
+/// out, and degraded ones are marked and listed last.
 ///
 /// Cross-burrow navigation through a single tunnel is not yet
 /// implemented — when it is, connected peers will become navigable
 /// type-`1` items.
 pub async fn warren_menu(table: &PeerTable) -> Vec<MenuItem> {
-    let mut peers = table.list().await;
+    let mut peers = table.list_reachable().await;
     // Sort by name for stable, predictable ordering.
-    peers.sort_by(|a, b| a.name.cmp(&b.name));
+    peers.sort_by(|a, b| (a.health, &a.name).cmp(&(b.health, &b.name)));
     let mut items = Vec::new();
 
     if peers.is_empty() {
@@ -36,7 +55,12 @@ pub async fn warren_menu(table: &PeerTable) -> Vec<MenuItem> {
             peer.name.clone()
         };
 
-        if peer.connected {
+        if peer.health == PeerHealth::Degraded {
+            items.push(MenuItem::info(format!(
+                "  \u{25D0} {} \u{2014} {} (degraded)",
+                display_name, peer.address
+            )));
+        } else if peer.connected {
             items.push(MenuItem::info(format!(
                 "  \u{25CF} {} \u{2014} {}",
                 display_name, peer.address
@@ -55,6 +79,81 @@ pub async fn warren_menu(table: &PeerTable) -> Vec<MenuItem> {
     items
 }
 
+/// Build the `/anchors` menu: each anchor whose manifest the burrow
+/// holds, with the manifest's serial, size and expiry.
+pub fn anchors_menu(trust: &TrustCache) -> Vec<MenuItem> {
+    let manifests = trust.manifests();
+    if manifests.is_empty() {
+        return vec![MenuItem::info("No anchor manifests held")];
+    }
+    let mut items = vec![MenuItem::info("Anchors:"), MenuItem::info("")];
+    for manifest in manifests {
+        items.push(MenuItem::info(format!(
+            "  {} \u{2014} serial {}, {} members, expires {}",
+            manifest.anchor,
+            manifest.serial,
+            manifest.members.len(),
+            manifest.expires_at
+        )));
+    }
+    items
+}
+
+/// Build the `/trusted` menu: each peer in the trust cache with its
+/// status and when it was last seen.
+pub fn trusted_menu(trust: &TrustCache) -> Vec<MenuItem> {
+    let entries = trust.entries();
+    if entries.is_empty() {
+        return vec![MenuItem::info("No trusted peers")];
+    }
+    let mut items = vec![MenuItem::info("Trusted peers:"), MenuItem::info("")];
+    for peer in entries {
+        items.push(MenuItem::info(format!(
+            "  {} \u{2014} {}, last seen {}",
+            peer.burrow_id,
+            peer.status.label(),
+            peer.last_seen
+        )));
+    }
+    items
+}
+
+/// Build the `/warren/topology` menu: each burrow in `topology` with
+/// the shortest path to it, or marked unreachable.
+pub fn topology_menu(topology: &Topology) -> Vec<MenuItem> {
+    if topology.nodes.is_empty() {
+        return vec![MenuItem::info("No burrows known")];
+    }
+    let mut items = vec![
+        MenuItem::info(format!(
+            "Warren topology from {}:",
+            short_id(&topology.local)
+        )),
+        MenuItem::info(""),
+    ];
+    let display = |id: &str| match topology.node(id) {
+        Some(node) if !node.name.is_empty() => node.name.clone(),
+        _ => short_id(id),
+    };
+    for node in &topology.nodes {
+        let line = match topology.shortest_path(&node.id) {
+            Some(path) => {
+                let via: Vec<String> = path.burrows[1..].iter().map(|id| display(id)).collect();
+                format!(
+                    "  \u{25CF} {} \u{2014} {} hop{}: {}",
+                    display(&node.id),
+                    path.hops,
+                    if path.hops == 1 { "" } else { "s" },
+                    via.join(" \u{2192} ")
+                )
+            }
+            None => format!("  \u{25CB} {} (unreachable)", display(&node.id)),
+        };
+        items.push(MenuItem::info(line));
+    }
+    items
+}
+
 /// Shorten a burrow ID for display.
 fn short_id(id: &str) -> String {
     if let Some(rest) = id.strip_prefix("ed25519:") {
@@ -129,6 +228,29 @@ mod tests {
         assert!(items.iter().any(|i| i.label.contains("beta")));
     }
 
+    #[tokio::test]
+    async fn down_peers_are_left_out() {
+        let table = PeerTable::new();
+        let mut p1 = PeerInfo::new("ed25519:AAAA", "10.0.0.1:7443", "alpha");
+        p1.health = PeerHealth::Degraded;
+        table.register(p1).await;
+        let mut p2 = PeerInfo::new("ed25519:BBBB", "10.0.0.2:7443", "beta");
+        p2.health = PeerHealth::Down;
+        table.register(p2).await;
+        table
+            .register(PeerInfo::new("ed25519:CCCC", "10.0.0.3:7443", "gamma"))
+            .await;
+
+        let labels: Vec<String> = warren_menu(&table)
+            .await
+            .into_iter()
+            .map(|i| i.label)
+            .collect();
+        assert!(labels[2].contains("gamma"));
+        assert!(labels[3].contains("alpha") && labels[3].contains("degraded"));
+        assert!(!labels.iter().any(|l| l.contains("beta")));
+    }
+
     #[tokio::test]
     async fn short_id_truncates_long_ids() {
         assert_eq!(
@@ -139,6 +261,37 @@ mod tests {
         assert_eq!(short_id("anonymous"), "anonymous");
     }
 
+    #[test]
+    fn trust_menus_list_anchors_and_peers() {
+        use crate::security::identity::Identity;
+        use crate::security::manifest::TrustManifest;
+
+        let mut trust = TrustCache::new();
+        assert!(anchors_menu(&trust)[0].label.contains("No anchor"));
+        assert!(trusted_menu(&trust)[0].label.contains("No trusted"));
+
+        let anchor = Identity::generate();
+        let member = Identity::generate();
+        let manifest = TrustManifest::sign(&anchor, 7, &[member.burrow_id()], &[], &[], 3600);
+        trust.add_manifest(manifest).unwrap();
+        trust
+            .verify_or_remember(&member.burrow_id(), &member.public_key_bytes())
+            .unwrap();
+        trust.revoke("ed25519:GONE");
+
+        let anchors = anchors_menu(&trust);
+        assert!(anchors
+            .iter()
+            .any(|i| i.label.contains(&anchor.burrow_id()) && i.label.contains("serial 7")));
+        let trusted = trusted_menu(&trust);
+        assert!(trusted
+            .iter()
+            .any(|i| i.label.contains(&member.burrow_id()) && i.label.contains("trusted")));
+        assert!(trusted
+            .iter()
+            .any(|i| i.label.contains("GONE") && i.label.contains("revoked")));
+    }
+
     #[tokio::test]
     async fn unnamed_peer_uses_short_id() {
         let table = PeerTable::new();
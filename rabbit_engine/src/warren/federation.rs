@@ -0,0 +1,1114 @@
+//! Federation between burrows under a common anchor.
+//!
+//! An anchor publishes the [`TrustManifest`] vouching for its members,
+//! bumping the serial each time, and serves the latest one to any peer
+//! that asks with `MANIFEST latest`:
+//!
+//! ```text
+//! MANIFEST latest
+//! Lane: 0
+//! End:
+//! ```
+//!
+//! The answer is `200 MANIFEST` with the manifest's headers and body,
+//! or `404 MISSING` if the burrow publishes none.  A burrow that holds
+//! an anchor's manifest can so replace it before it expires, or learn
+//! of members the anchor has since revoked.
+//!
+//! A [`FederationLink`] to another warren carries a secret both ends
+//! were given out of band.  Once the handshake has named each side,
+//! the dialing end proves the link with a challenge each way, every
+//! proof an HMAC-SHA256 under the secret:
+//!
+//! ```text
+//! FED-AUTH challenge             200 FED-AUTH
+//! Nonce: <a>               →     Nonce: <b>
+//!                          ←     Proof: HMAC(<responder>, <dialer>, a, b)
+//! FED-AUTH proof
+//! Proof: HMAC(<dialer>, <responder>, b, a)   →   200 OK
+//! ```
+//!
+//! where `HMAC(p, v, x, y)` is taken over
+//! `RABBIT-FED-AUTH\n<p>\n<v>\n<x>\n<y>`, `p` proving to `v`.  A
+//! wrong proof fails with `403 FORBIDDEN` and marks the link failed.
+//!
+//! Once both ends are proven, the dialer opens the link:
+//!
+//! ```text
+//! FED-LINK-OPEN                  FED-LINK-ACCEPT
+//! Txn: fed-link-…          →     Txn: fed-link-…
+//! Heartbeat: 30            ←     Heartbeat: 30
+//! ```
+//!
+//! `FED-LINK-OPEN` on a link that is not authenticated fails with
+//! `403 FORBIDDEN`.  While the link is open, each end repeats
+//! `FED-LINK-OPEN` every `federation.heartbeat_secs` as a heartbeat,
+//! and both note the link seen on every open and accept.  A link not
+//! seen for two heartbeats is degraded, and for four down (see
+//! [`FederationManager::link_status`]).  Either end tears the link
+//! down with `FED-LINK-CLOSE`, carrying an optional `Reason`; the link
+//! must then be authenticated again before it reopens.
+//!
+//! Every open and accept also carries the services its sender
+//! declares (see [`crate::warren::services`]), which
+//! [`FederationManager::find_service`] looks up.
+//!
+//! Each link's state and when it was last seen are kept in
+//! `<storage>/federation_links.tsv`, so they outlive a restart.
+//!
+//! The anchors a burrow trusts are registered with it as [`Anchor`]s,
+//! from `[[federation.anchors]]` or at runtime, and kept in
+//! `<storage>/federation_anchors.tsv`.  On startup the burrow dials
+//! every anchor and link with an address (see
+//! [`FederationManager::dial_addresses`]), fetching each anchor's
+//! latest manifest and opening each link.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use ring::hmac;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::{Frame, FrameBuilder};
+use crate::security::auth::{generate_nonce, hex_decode, hex_encode};
+use crate::security::identity::{parse_burrow_id, Identity};
+use crate::security::manifest::TrustManifest;
+use crate::transport::tunnel::Tunnel;
+use crate::util::now_unix;
+use crate::warren::peers::PeerHealth;
+use crate::warren::services::{parse_services, service_lines, Service, ServiceRegistry};
+
+/// Where a federation link's authentication stands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkStatus {
+    /// Not yet authenticated.
+    Unverified,
+    /// Both ends proved the secret at `at` (seconds since the epoch).
+    Authenticated { at: u64 },
+    /// The last `failures` attempts failed, the latest with `reason`.
+    Failed { reason: String, failures: u32 },
+}
+
+impl LinkStatus {
+    /// Lower-case name, e.g. `"authenticated"`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Unverified => "unverified",
+            Self::Authenticated { .. } => "authenticated",
+            Self::Failed { .. } => "failed",
+        }
+    }
+}
+
+/// Whether a federation link is in use.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LinkState {
+    /// Never opened.
+    #[default]
+    Idle,
+    /// Opened at `since` (seconds since the epoch).
+    Open { since: u64 },
+    /// Torn down at `at`, for `reason`.
+    Closed { at: u64, reason: String },
+}
+
+impl LinkState {
+    /// Lower-case name, e.g. `"open"`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Open { .. } => "open",
+            Self::Closed { .. } => "closed",
+        }
+    }
+
+    /// One `federation_links.tsv` line for `peer`.
+    fn to_tsv(&self, peer: &str, last_seen: u64) -> String {
+        let (at, reason) = match self {
+            Self::Idle => (0, ""),
+            Self::Open { since } => (*since, ""),
+            Self::Closed { at, reason } => (*at, reason.as_str()),
+        };
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            peer,
+            self.label(),
+            at,
+            last_seen,
+            reason
+        )
+    }
+
+    /// Parse a line written by [`LinkState::to_tsv`]: the peer, its
+    /// link's state and when it was last seen.
+    fn from_tsv(line: &str) -> Option<(String, Self, u64)> {
+        let mut fields = line.splitn(5, '\t');
+        let peer = fields.next()?.to_string();
+        let label = fields.next()?;
+        let at = fields.next()?.parse().ok()?;
+        let last_seen = fields.next()?.parse().ok()?;
+        let state = match label {
+            "idle" => Self::Idle,
+            "open" => Self::Open { since: at },
+            "closed" => Self::Closed {
+                at,
+                reason: fields.next().unwrap_or("").to_string(),
+            },
+            _ => return None,
+        };
+        Some((peer, state, last_seen))
+    }
+}
+
+/// How one federation link stands, for operators.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LinkReport {
+    /// Burrow ID of the remote end.
+    pub peer: String,
+    /// `unverified`, `authenticated` or `failed`.
+    pub auth: &'static str,
+    /// `idle`, `open` or `closed`.
+    pub state: &'static str,
+    /// When the link was opened or closed (0 if idle).
+    pub since: u64,
+    /// When the remote end was last heard on the link (0 if never).
+    pub last_seen: u64,
+    /// `up`, `degraded` or `down`, judged by heartbeats; only an open
+    /// link is ever up.
+    pub health: &'static str,
+    /// Why the link last failed to authenticate or was closed.
+    pub detail: String,
+    /// Names of the services the remote end declares, sorted.
+    pub services: Vec<String>,
+}
+
+/// An anchor this burrow trusts, and where to reach it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Anchor {
+    /// The anchor's burrow ID, which names its key.
+    pub id: String,
+    /// `host:port` to dial it at, if it is dialed at all.
+    pub address: Option<String>,
+}
+
+impl Anchor {
+    /// The anchor `id`, dialed at `address` if one is given.
+    pub fn new(id: &str, address: Option<&str>) -> Self {
+        Self {
+            id: id.to_string(),
+            address: address.map(str::to_string),
+        }
+    }
+
+    /// One `federation_anchors.tsv` line.
+    fn to_tsv(&self) -> String {
+        format!("{}\t{}", self.id, self.address.as_deref().unwrap_or(""))
+    }
+
+    /// Parse a line written by [`Anchor::to_tsv`].
+    fn from_tsv(line: &str) -> Option<Self> {
+        let (id, address) = line.split_once('\t').unwrap_or((line, ""));
+        let id = id.trim();
+        if id.is_empty() {
+            return None;
+        }
+        Some(Self::new(
+            id,
+            Some(address.trim()).filter(|a| !a.is_empty()),
+        ))
+    }
+}
+
+/// A link to another warren, authenticated by a pre-shared secret.
+#[derive(Clone)]
+pub struct FederationLink {
+    /// Burrow ID of the remote end.
+    pub peer: String,
+    /// The secret both ends hold.
+    pub shared_secret: Vec<u8>,
+    /// Outcome of the last authentication.
+    pub status: LinkStatus,
+    /// Whether the link is open.
+    pub state: LinkState,
+    /// When the remote end was last heard on the link (0 if never).
+    pub last_seen: u64,
+    /// The services the remote end declared when last heard.
+    pub services: Vec<Service>,
+    /// `host:port` to dial the remote end at on startup, if any.
+    pub address: Option<String>,
+}
+
+impl std::fmt::Debug for FederationLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FederationLink")
+            .field("peer", &self.peer)
+            .field("status", &self.status)
+            .field("state", &self.state)
+            .field("last_seen", &self.last_seen)
+            .field("services", &self.services)
+            .field("address", &self.address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The manifest a burrow publishes as an anchor, fetching of other
+/// anchors' manifests, and its federation links.
+#[derive(Debug, Default)]
+pub struct FederationManager {
+    /// The latest manifest this burrow signed, if it is an anchor.
+    published: Mutex<Option<TrustManifest>>,
+    /// Where the published manifest is written, if anywhere.
+    path: Option<PathBuf>,
+    /// Federation links by the remote end's burrow ID.
+    links: Mutex<HashMap<String, FederationLink>>,
+    /// Nonces of link authentications we are answering, by peer:
+    /// theirs and ours.
+    challenges: Mutex<HashMap<String, (String, String)>>,
+    /// Manifests published or accepted so far, for gossip backoff.
+    changes: AtomicU64,
+    /// Seconds between link heartbeats (0 = no heartbeats).
+    heartbeat_secs: u64,
+    /// Where link state is written, if anywhere.
+    links_path: Option<PathBuf>,
+    /// Link state read from `links_path` for links not yet added.
+    saved_links: Mutex<HashMap<String, (LinkState, u64)>>,
+    /// The services this burrow declares on its links.
+    services: ServiceRegistry,
+    /// Anchors trusted here, by burrow ID.
+    anchors: Mutex<BTreeMap<String, Anchor>>,
+    /// Where the anchors are written, if anywhere.
+    anchors_path: Option<PathBuf>,
+}
+
+impl FederationManager {
+    /// Create a federation manager that publishes nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the manifest published before, and keep writing each newly
+    /// published one to `path`.  A missing file means none.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        let path = path.as_ref();
+        let published = if path.exists() {
+            Some(TrustManifest::load(path)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            published: Mutex::new(published),
+            path: Some(path.to_path_buf()),
+            ..Self::default()
+        })
+    }
+
+    /// Send link heartbeats every `secs` seconds (0 = none), judging
+    /// the links' health by them.
+    pub fn with_heartbeat(mut self, secs: u64) -> Self {
+        self.heartbeat_secs = secs;
+        self
+    }
+
+    /// Read link state written before from `path`, for links as they
+    /// are added, and keep writing it there.  A missing file means
+    /// none.
+    pub fn with_link_state(mut self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let saved = std::fs::read_to_string(path).unwrap_or_default();
+        self.saved_links = Mutex::new(
+            saved
+                .lines()
+                .filter_map(LinkState::from_tsv)
+                .map(|(peer, state, last_seen)| (peer, (state, last_seen)))
+                .collect(),
+        );
+        self.links_path = Some(path.to_path_buf());
+        self
+    }
+
+    /// Read the anchors registered before from `path`, and keep
+    /// writing them there.  A missing file means none.
+    pub fn with_anchors(mut self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let saved = std::fs::read_to_string(path).unwrap_or_default();
+        self.anchors = Mutex::new(
+            saved
+                .lines()
+                .filter_map(Anchor::from_tsv)
+                .map(|anchor| (anchor.id.clone(), anchor))
+                .collect(),
+        );
+        self.anchors_path = Some(path.to_path_buf());
+        self
+    }
+
+    /// Seconds between link heartbeats (0 = none).
+    pub fn heartbeat_secs(&self) -> u64 {
+        self.heartbeat_secs
+    }
+
+    /// The services this burrow declares on its links.  Changes reach
+    /// each link with its next heartbeat.
+    pub fn services(&self) -> &ServiceRegistry {
+        &self.services
+    }
+
+    /// The latest manifest this burrow published.
+    pub fn published(&self) -> Option<TrustManifest> {
+        self.published
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Sign and publish a manifest listing `members` and `sub_anchors`
+    /// and revoking `revoked`, valid for `ttl_secs`.  Its serial is one higher than
+    /// the last published, so it supersedes it wherever it is held.
+    pub fn publish(
+        &self,
+        identity: &Identity,
+        members: &[String],
+        sub_anchors: &[String],
+        revoked: &[String],
+        ttl_secs: u64,
+    ) -> Result<TrustManifest, ProtocolError> {
+        let mut published = self.published.lock().unwrap_or_else(|e| e.into_inner());
+        let serial = published.as_ref().map_or(1, |m| m.serial + 1);
+        let manifest =
+            TrustManifest::sign(identity, serial, members, sub_anchors, revoked, ttl_secs);
+        if let Some(path) = &self.path {
+            manifest.save(path)?;
+        }
+        *published = Some(manifest.clone());
+        self.note_change();
+        Ok(manifest)
+    }
+
+    /// Count a manifest published or accepted, so gossip picks up pace
+    /// (see [`crate::warren::gossip`]).
+    pub fn note_change(&self) {
+        self.changes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// How many manifests have been published or accepted.
+    pub fn changes(&self) -> u64 {
+        self.changes.load(Ordering::Relaxed)
+    }
+
+    /// Ask the anchor at the other end of `tunnel`, an authenticated
+    /// tunnel to `anchor`, for its latest manifest.
+    ///
+    /// The manifest is verified, and must be signed by `anchor`; keeping
+    /// it is up to the caller, e.g. with
+    /// [`crate::burrow::Burrow::accept_manifest`].  Fails with
+    /// `Missing` if the anchor publishes none and `Forbidden` for a
+    /// manifest that is forged, expired or from someone else.
+    pub async fn fetch_manifest<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+        anchor: &str,
+    ) -> Result<TrustManifest, ProtocolError> {
+        let request = Frame::builder("MANIFEST").arg("latest").lane(0).build()?;
+        tunnel.send_frame(&request).await?;
+        let response = tunnel
+            .recv_frame()
+            .await?
+            .ok_or_else(|| ProtocolError::InternalError("tunnel closed during MANIFEST".into()))?;
+        let detail = || response.body.clone().unwrap_or_default();
+        match response.verb.as_str() {
+            "200" => {}
+            "404" => return Err(ProtocolError::Missing(detail())),
+            "403" => return Err(ProtocolError::Forbidden(detail())),
+            other => {
+                return Err(ProtocolError::BadRequest(format!(
+                    "unexpected reply to MANIFEST: {} {}",
+                    other,
+                    response.args.join(" ")
+                )))
+            }
+        }
+        let manifest = TrustManifest::from_frame(&response)?;
+        if manifest.anchor != anchor {
+            return Err(ProtocolError::Forbidden(format!(
+                "asked {} for its manifest, got one from {}",
+                anchor, manifest.anchor
+            )));
+        }
+        manifest.verify()?;
+        Ok(manifest)
+    }
+
+    /// Add, or replace, the link to `peer` with `shared_secret`.  The
+    /// link starts unverified, in the state saved for it if any.
+    pub fn add_link(&self, peer: &str, shared_secret: impl Into<Vec<u8>>) {
+        let (state, last_seen) = self
+            .saved_links
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(peer)
+            .unwrap_or_default();
+        let link = FederationLink {
+            peer: peer.to_string(),
+            shared_secret: shared_secret.into(),
+            status: LinkStatus::Unverified,
+            state,
+            last_seen,
+            services: Vec::new(),
+            address: None,
+        };
+        self.links
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(peer.to_string(), link);
+    }
+
+    /// Dial the link to `peer` at `address` on startup.  Does nothing
+    /// if there is no link to `peer`.
+    pub fn set_link_address(&self, peer: &str, address: &str) {
+        if let Some(link) = self
+            .links
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(peer)
+        {
+            link.address = Some(address.to_string());
+        }
+    }
+
+    /// Trust `anchor`, in place of any registered under its ID, and
+    /// write the anchors out.  Returns whether it was new or changed.
+    /// Fails with `BadRequest` if its ID is not a burrow ID.
+    pub fn register_anchor(&self, anchor: Anchor) -> Result<bool, ProtocolError> {
+        parse_burrow_id(&anchor.id)?;
+        let mut anchors = self.anchors.lock().unwrap_or_else(|e| e.into_inner());
+        if anchors.get(&anchor.id) == Some(&anchor) {
+            return Ok(false);
+        }
+        anchors.insert(anchor.id.clone(), anchor);
+        self.save_anchors(&anchors)?;
+        Ok(true)
+    }
+
+    /// Stop trusting the anchor `id`.  Returns whether it was
+    /// registered.
+    pub fn forget_anchor(&self, id: &str) -> Result<bool, ProtocolError> {
+        let mut anchors = self.anchors.lock().unwrap_or_else(|e| e.into_inner());
+        if anchors.remove(id).is_none() {
+            return Ok(false);
+        }
+        self.save_anchors(&anchors)?;
+        Ok(true)
+    }
+
+    /// The anchors trusted here, sorted by ID.
+    pub fn anchors(&self) -> Vec<Anchor> {
+        self.anchors
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Whether `id` is a registered anchor.
+    pub fn is_anchor(&self, id: &str) -> bool {
+        self.anchors
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(id)
+    }
+
+    /// The addresses of anchors and links to dial on startup, sorted
+    /// and without repeats.
+    pub fn dial_addresses(&self) -> Vec<String> {
+        let mut addresses: Vec<String> = self
+            .anchors()
+            .into_iter()
+            .filter_map(|a| a.address)
+            .chain(self.links().into_iter().filter_map(|l| l.address))
+            .collect();
+        addresses.sort();
+        addresses.dedup();
+        addresses
+    }
+
+    fn save_anchors(&self, anchors: &BTreeMap<String, Anchor>) -> Result<(), ProtocolError> {
+        let Some(path) = &self.anchors_path else {
+            return Ok(());
+        };
+        let lines: Vec<String> = anchors.values().map(Anchor::to_tsv).collect();
+        write_lines(path, &lines).map_err(|e| {
+            ProtocolError::InternalError(format!("could not save federation anchors: {}", e))
+        })
+    }
+
+    /// The link to `peer`, if there is one.
+    pub fn link(&self, peer: &str) -> Option<FederationLink> {
+        self.links
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(peer)
+            .cloned()
+    }
+
+    /// Every link, sorted by peer.
+    pub fn links(&self) -> Vec<FederationLink> {
+        let mut links: Vec<FederationLink> = self
+            .links
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        links.sort_by(|a, b| a.peer.cmp(&b.peer));
+        links
+    }
+
+    /// Whether the link to `peer` has been authenticated.
+    pub fn is_link_authenticated(&self, peer: &str) -> bool {
+        self.link(peer)
+            .is_some_and(|l| matches!(l.status, LinkStatus::Authenticated { .. }))
+    }
+
+    /// Peers whose links are open, sorted.
+    pub fn open_links(&self) -> Vec<String> {
+        self.links()
+            .into_iter()
+            .filter(|l| matches!(l.state, LinkState::Open { .. }))
+            .map(|l| l.peer)
+            .collect()
+    }
+
+    /// How every link stands, sorted by peer.
+    pub fn link_status(&self) -> Vec<LinkReport> {
+        let now = now_unix();
+        self.links()
+            .into_iter()
+            .map(|link| {
+                let since = match link.state {
+                    LinkState::Idle => 0,
+                    LinkState::Open { since } => since,
+                    LinkState::Closed { at, .. } => at,
+                };
+                let detail = match (&link.state, &link.status) {
+                    (LinkState::Closed { reason, .. }, _)
+                    | (_, LinkStatus::Failed { reason, .. }) => reason.clone(),
+                    _ => String::new(),
+                };
+                LinkReport {
+                    auth: link.status.label(),
+                    state: link.state.label(),
+                    since,
+                    last_seen: link.last_seen,
+                    health: self.link_health(&link, now).label(),
+                    detail,
+                    services: link.services.into_iter().map(|s| s.name).collect(),
+                    peer: link.peer,
+                }
+            })
+            .collect()
+    }
+
+    /// Where the open links offer the service `name`: the remote
+    /// warren's burrow ID and the selector serving it, sorted by
+    /// warren.
+    pub fn find_service(&self, name: &str) -> Vec<(String, String)> {
+        self.links()
+            .into_iter()
+            .filter(|l| matches!(l.state, LinkState::Open { .. }))
+            .flat_map(|l| {
+                let peer = l.peer;
+                l.services
+                    .into_iter()
+                    .filter(|s| s.name == name)
+                    .map(move |s| (peer.clone(), s.selector))
+            })
+            .collect()
+    }
+
+    /// An open link is up while heard within two heartbeats and
+    /// degraded within four; any other link is down.
+    fn link_health(&self, link: &FederationLink, now: u64) -> PeerHealth {
+        if !matches!(link.state, LinkState::Open { .. }) {
+            return PeerHealth::Down;
+        }
+        if self.heartbeat_secs == 0 {
+            return PeerHealth::Up;
+        }
+        match now.saturating_sub(link.last_seen) {
+            idle if idle <= 2 * self.heartbeat_secs => PeerHealth::Up,
+            idle if idle <= 4 * self.heartbeat_secs => PeerHealth::Degraded,
+            _ => PeerHealth::Down,
+        }
+    }
+
+    /// A `FED-LINK-OPEN`, opening a link or, on an open one, serving
+    /// as its heartbeat.
+    pub fn open_frame(&self) -> Frame {
+        let nonce = hex_encode(&generate_nonce());
+        let builder = Frame::builder("FED-LINK-OPEN")
+            .lane(0)
+            .txn(&format!("fed-link-{}", &nonce[..16]));
+        self.declare_services(builder)
+            .build()
+            .unwrap_or_else(Frame::from)
+    }
+
+    /// Add our heartbeat interval and the services we declare.
+    fn declare_services(&self, builder: FrameBuilder) -> FrameBuilder {
+        let builder = builder.header("Heartbeat", self.heartbeat_secs.to_string());
+        let services = self.services.list();
+        if services.is_empty() {
+            return builder;
+        }
+        builder.body_text(service_lines(&services))
+    }
+
+    /// Open the authenticated link to `peer` over `tunnel`, a tunnel
+    /// we dialed to it.
+    ///
+    /// Fails with `Forbidden` if the link is not authenticated, at
+    /// either end.
+    pub async fn open_link<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+        peer: &str,
+    ) -> Result<(), ProtocolError> {
+        if !self.is_link_authenticated(peer) {
+            return Err(ProtocolError::Forbidden(format!(
+                "federation link to {} is not authenticated",
+                peer
+            )));
+        }
+        let request = self.open_frame();
+        tunnel.send_frame(&request).await?;
+        loop {
+            let reply = tunnel.recv_frame().await?.ok_or_else(|| {
+                ProtocolError::InternalError("tunnel closed during FED-LINK-OPEN".into())
+            })?;
+            if reply.header("Txn") != request.header("Txn") {
+                continue;
+            }
+            let detail = || reply.body.clone().unwrap_or_default();
+            return match reply.verb.as_str() {
+                "FED-LINK-ACCEPT" => {
+                    self.link_opened(peer, &reply);
+                    Ok(())
+                }
+                "403" => Err(ProtocolError::Forbidden(detail())),
+                other => Err(ProtocolError::BadRequest(format!(
+                    "unexpected reply to FED-LINK-OPEN: {} {}",
+                    other,
+                    reply.args.join(" ")
+                ))),
+            };
+        }
+    }
+
+    /// Answer a `FED-LINK-OPEN` from `peer` with `FED-LINK-ACCEPT`,
+    /// opening the link if it is not open already.
+    ///
+    /// Fails with `Forbidden` unless the link to `peer` is
+    /// authenticated.
+    pub fn answer_link_open(&self, frame: &Frame, peer: &str) -> Result<Frame, ProtocolError> {
+        if !self.is_link_authenticated(peer) {
+            return Err(ProtocolError::Forbidden(format!(
+                "federation link with {} is not authenticated",
+                peer
+            )));
+        }
+        self.link_opened(peer, frame);
+        self.declare_services(reply_to(frame, "FED-LINK-ACCEPT"))
+            .build()
+    }
+
+    /// Note that `peer` answered on its link with `accept`, a
+    /// `FED-LINK-ACCEPT` to a heartbeat, taking the services it
+    /// declares.
+    pub fn note_link_accept(&self, accept: &Frame, peer: &str) {
+        let services = parse_services(accept.body.as_deref().unwrap_or(""));
+        self.update_link(peer, |link, now| {
+            link.last_seen = now;
+            link.services = services;
+        });
+    }
+
+    /// Close the link to `peer` for `reason`.  Returns the
+    /// `FED-LINK-CLOSE` to send it, or `None` if there is no link.
+    pub fn close_link(&self, peer: &str, reason: &str) -> Option<Frame> {
+        self.link(peer)?;
+        self.link_closed(peer, reason);
+        let builder = Frame::builder("FED-LINK-CLOSE").lane(0);
+        let builder = match reason {
+            "" => builder,
+            reason => builder.header("Reason", reason),
+        };
+        Some(builder.build().unwrap_or_else(Frame::from))
+    }
+
+    /// Answer a `FED-LINK-CLOSE` from `peer` with `200 OK`, closing
+    /// the link.  Fails with `Forbidden` if there is no link to `peer`.
+    pub fn answer_link_close(&self, frame: &Frame, peer: &str) -> Result<Frame, ProtocolError> {
+        if self.link(peer).is_none() {
+            return Err(ProtocolError::Forbidden(format!(
+                "no federation link with {}",
+                peer
+            )));
+        }
+        self.link_closed(peer, frame.header("Reason").unwrap_or("closed by peer"));
+        reply_to(frame, "200 OK").build()
+    }
+
+    /// Open the link to `peer`, if it is not open, on `frame`, taking
+    /// the services it declares.
+    fn link_opened(&self, peer: &str, frame: &Frame) {
+        let services = parse_services(frame.body.as_deref().unwrap_or(""));
+        self.update_link(peer, |link, now| {
+            link.services = services;
+            if !matches!(link.state, LinkState::Open { .. }) {
+                link.state = LinkState::Open { since: now };
+            }
+            link.last_seen = now;
+        });
+    }
+
+    /// Close the link to `peer`; it must authenticate again to reopen.
+    fn link_closed(&self, peer: &str, reason: &str) {
+        self.update_link(peer, |link, now| {
+            link.state = LinkState::Closed {
+                at: now,
+                reason: reason.to_string(),
+            };
+            link.status = LinkStatus::Unverified;
+            link.services.clear();
+        });
+    }
+
+    /// Change the link to `peer`, if there is one, and write every
+    /// link's state out.
+    fn update_link(&self, peer: &str, change: impl FnOnce(&mut FederationLink, u64)) {
+        let mut links = self.links.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(link) = links.get_mut(peer) else {
+            return;
+        };
+        change(link, now_unix());
+        let Some(path) = &self.links_path else {
+            return;
+        };
+        let mut lines: Vec<String> = links
+            .values()
+            .map(|l| l.state.to_tsv(&l.peer, l.last_seen))
+            .collect();
+        lines.sort();
+        if let Err(e) = write_lines(path, &lines) {
+            warn!(path = %path.display(), err = %e, "could not save federation link state");
+        }
+    }
+
+    /// Authenticate the link to `peer` over `tunnel`, a tunnel we
+    /// dialed to it that has completed the handshake as `local`.
+    ///
+    /// Fails with `Missing` if there is no link to `peer` and
+    /// `Forbidden` if either end's proof is wrong; the link is then
+    /// marked failed.
+    pub async fn authenticate_link<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+        local: &str,
+        peer: &str,
+    ) -> Result<(), ProtocolError> {
+        let link = self
+            .link(peer)
+            .ok_or_else(|| ProtocolError::Missing(format!("no federation link to {}", peer)))?;
+        let result = self.run_link_auth(tunnel, &link, local).await;
+        self.set_link_result(peer, &result);
+        result
+    }
+
+    async fn run_link_auth<T: Tunnel>(
+        &self,
+        tunnel: &mut T,
+        link: &FederationLink,
+        local: &str,
+    ) -> Result<(), ProtocolError> {
+        let ours = hex_encode(&generate_nonce());
+        let txn = format!("fed-auth-{}", &ours[..16]);
+
+        let challenge = Frame::builder("FED-AUTH challenge")
+            .lane(0)
+            .txn(&txn)
+            .header("Nonce", ours.as_str())
+            .build()?;
+        let reply = link_exchange(tunnel, &challenge).await?;
+        let theirs = required(&reply, "Nonce")?;
+        let expected = link_message(&link.peer, local, &ours, theirs);
+        let their_proof = required(&reply, "Proof")?;
+        check_proof(&link.shared_secret, &expected, their_proof, &link.peer)?;
+
+        let message = link_message(local, &link.peer, theirs, &ours);
+        let proof = Frame::builder("FED-AUTH proof")
+            .lane(0)
+            .txn(&txn)
+            .header("Proof", link_proof(&link.shared_secret, &message))
+            .build()?;
+        link_exchange(tunnel, &proof).await?;
+        Ok(())
+    }
+
+    /// Answer a `FED-AUTH` frame from `peer` to us, `local`: a
+    /// challenge with our proof and nonce, a proof with `200 OK`.
+    ///
+    /// Fails with `Forbidden` if there is no link to `peer` or its
+    /// proof is wrong, marking the link failed in the latter case.
+    pub fn answer_link_auth(
+        &self,
+        frame: &Frame,
+        local: &str,
+        peer: &str,
+    ) -> Result<Frame, ProtocolError> {
+        let link = self
+            .link(peer)
+            .ok_or_else(|| ProtocolError::Forbidden(format!("no federation link with {}", peer)))?;
+        let mut challenges = self.challenges.lock().unwrap_or_else(|e| e.into_inner());
+        let reply = match frame.args.first().map(String::as_str) {
+            Some("challenge") => {
+                let theirs = required(frame, "Nonce")?.to_string();
+                let ours = hex_encode(&generate_nonce());
+                let message = link_message(local, peer, &theirs, &ours);
+                let reply = reply_to(frame, "200 FED-AUTH")
+                    .header("Nonce", ours.as_str())
+                    .header("Proof", link_proof(&link.shared_secret, &message));
+                challenges.insert(peer.to_string(), (theirs, ours));
+                reply
+            }
+            Some("proof") => {
+                let (theirs, ours) = challenges.remove(peer).ok_or_else(|| {
+                    ProtocolError::BadRequest("FED-AUTH proof without a challenge".into())
+                })?;
+                let expected = link_message(peer, local, &ours, &theirs);
+                let result = required(frame, "Proof")
+                    .and_then(|p| check_proof(&link.shared_secret, &expected, p, peer));
+                self.set_link_result(peer, &result);
+                result?;
+                reply_to(frame, "200 OK")
+            }
+            _ => {
+                return Err(ProtocolError::BadRequest(
+                    "FED-AUTH needs challenge or proof".into(),
+                ))
+            }
+        };
+        reply.build()
+    }
+
+    /// Record how authenticating the link to `peer` went.
+    fn set_link_result(&self, peer: &str, result: &Result<(), ProtocolError>) {
+        let mut links = self.links.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(link) = links.get_mut(peer) else {
+            return;
+        };
+        link.status = match (result, &link.status) {
+            (Ok(()), _) => LinkStatus::Authenticated { at: now_unix() },
+            (Err(e), LinkStatus::Failed { failures, .. }) => LinkStatus::Failed {
+                reason: e.to_string(),
+                failures: failures + 1,
+            },
+            (Err(e), _) => LinkStatus::Failed {
+                reason: e.to_string(),
+                failures: 1,
+            },
+        };
+    }
+}
+
+/// Send `request` and wait for the reply carrying its `Txn`, skipping
+/// anything else the peer sends meanwhile.
+async fn link_exchange<T: Tunnel>(tunnel: &mut T, request: &Frame) -> Result<Frame, ProtocolError> {
+    tunnel.send_frame(request).await?;
+    loop {
+        let reply = tunnel
+            .recv_frame()
+            .await?
+            .ok_or_else(|| ProtocolError::InternalError("tunnel closed during FED-AUTH".into()))?;
+        if reply.header("Txn") != request.header("Txn") {
+            continue;
+        }
+        let detail = || reply.body.clone().unwrap_or_default();
+        return match reply.verb.as_str() {
+            "200" => Ok(reply),
+            "403" => Err(ProtocolError::Forbidden(detail())),
+            other => Err(ProtocolError::BadRequest(format!(
+                "unexpected reply to FED-AUTH: {} {}",
+                other,
+                reply.args.join(" ")
+            ))),
+        };
+    }
+}
+
+/// What `prover` signs to show `verifier` it holds the link secret,
+/// answering the verifier's nonce with its own.
+fn link_message(prover: &str, verifier: &str, verifier_nonce: &str, prover_nonce: &str) -> String {
+    format!(
+        "RABBIT-FED-AUTH\n{}\n{}\n{}\n{}",
+        prover, verifier, verifier_nonce, prover_nonce
+    )
+}
+
+/// The proof, hex, of `message` under `secret`.
+fn link_proof(secret: &[u8], message: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    hex_encode(hmac::sign(&key, message.as_bytes()).as_ref())
+}
+
+/// Check, in constant time, that `proof` from `peer` is `message`
+/// under `secret`.
+fn check_proof(secret: &[u8], message: &str, proof: &str, peer: &str) -> Result<(), ProtocolError> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    hex_decode(proof)
+        .ok()
+        .and_then(|tag| hmac::verify(&key, message.as_bytes(), &tag).ok())
+        .ok_or_else(|| {
+            ProtocolError::Forbidden(format!("wrong federation link proof from {}", peer))
+        })
+}
+
+fn write_lines(path: &Path, lines: &[String]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, lines.join("\n"))
+}
+
+/// Start a reply to `frame`, echoing its `Lane` and `Txn`.
+fn reply_to(frame: &Frame, start_line: &str) -> FrameBuilder {
+    ["Lane", "Txn"]
+        .into_iter()
+        .fold(Frame::builder(start_line), |builder, key| {
+            match frame.header(key) {
+                Some(value) => builder.header(key, value),
+                None => builder,
+            }
+        })
+}
+
+fn required<'f>(frame: &'f Frame, name: &str) -> Result<&'f str, ProtocolError> {
+    frame
+        .header(name)
+        .ok_or_else(|| ProtocolError::BadRequest(format!("FED-AUTH missing {}", name)))
+}
+
+/// The `200 MANIFEST` reply to `MANIFEST latest` carrying `manifest`.
+pub fn manifest_reply(manifest: &TrustManifest) -> Frame {
+    let mut reply = manifest.to_frame();
+    reply.verb = "200".into();
+    reply.args = vec!["MANIFEST".into()];
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publishing_bumps_the_serial_and_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("published.manifest");
+        let anchor = Identity::generate();
+        let federation = FederationManager::load(&path).unwrap();
+        assert!(federation.published().is_none());
+
+        let members = vec!["ed25519:KID".to_string()];
+        let first = federation
+            .publish(&anchor, &members, &[], &[], 3600)
+            .unwrap();
+        let second = federation
+            .publish(&anchor, &[], &[], &members, 3600)
+            .unwrap();
+        assert_eq!((first.serial, second.serial), (1, 2));
+        assert!(second.supersedes(&first));
+
+        let reloaded = FederationManager::load(&path).unwrap();
+        assert_eq!(reloaded.published(), Some(second.clone()));
+        let reply = TrustManifest::from_frame(&manifest_reply(&second)).unwrap();
+        assert_eq!(reply, second);
+    }
+
+    #[test]
+    fn link_state_persists_and_health_follows_heartbeats() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("federation_links.tsv");
+        let federation = FederationManager::new()
+            .with_heartbeat(30)
+            .with_link_state(&path);
+        federation.add_link("ed25519:OAK", "secret");
+        federation.add_link("ed25519:ELM", "secret");
+        federation.set_link_result("ed25519:OAK", &Ok(()));
+        let open = federation.open_frame();
+        let accept = federation.answer_link_open(&open, "ed25519:OAK").unwrap();
+        assert_eq!(accept.verb, "FED-LINK-ACCEPT");
+        assert_eq!(accept.header("Txn"), open.header("Txn"));
+        assert!(federation.answer_link_open(&open, "ed25519:ELM").is_err());
+        federation.close_link("ed25519:ELM", "retired").unwrap();
+
+        let health = |f: &FederationManager| -> Vec<&str> {
+            f.link_status().iter().map(|l| l.health).collect()
+        };
+        assert_eq!(health(&federation), ["down", "up"]);
+        federation.update_link("ed25519:OAK", |link, now| link.last_seen = now - 90);
+        assert_eq!(health(&federation), ["down", "degraded"]);
+        federation.update_link("ed25519:OAK", |link, now| link.last_seen = now - 150);
+        assert_eq!(health(&federation), ["down", "down"]);
+
+        // After a restart the links come back as they were left.
+        let reloaded = FederationManager::new().with_link_state(&path);
+        reloaded.add_link("ed25519:OAK", "secret");
+        reloaded.add_link("ed25519:ELM", "secret");
+        let status = reloaded.link_status();
+        assert_eq!(
+            (status[0].state, status[0].detail.as_str()),
+            ("closed", "retired")
+        );
+        assert_eq!(status[1].state, "open");
+        assert_eq!(status[1].auth, "unverified");
+        assert_eq!(reloaded.open_links(), ["ed25519:OAK"]);
+    }
+
+    #[test]
+    fn anchors_persist_and_are_dialed_with_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("federation_anchors.tsv");
+        let (hub, sub) = (
+            Identity::generate().burrow_id(),
+            Identity::generate().burrow_id(),
+        );
+        let federation = FederationManager::new().with_anchors(&path);
+        let anchor = Anchor::new(&hub, Some("hub.example.org:7443"));
+        assert!(federation.register_anchor(anchor.clone()).unwrap());
+        assert!(!federation.register_anchor(anchor).unwrap());
+        assert!(federation.register_anchor(Anchor::new(&sub, None)).unwrap());
+        assert!(federation
+            .register_anchor(Anchor::new("oak", None))
+            .is_err());
+        federation.add_link("ed25519:OAK", "secret");
+        federation.set_link_address("ed25519:OAK", "oak.example.org:7443");
+        federation.add_link("ed25519:ELM", "secret");
+        assert_eq!(
+            federation.dial_addresses(),
+            ["hub.example.org:7443", "oak.example.org:7443"]
+        );
+
+        // After a restart the anchors are still trusted.
+        let reloaded = FederationManager::new().with_anchors(&path);
+        assert_eq!(reloaded.anchors(), federation.anchors());
+        assert!(reloaded.is_anchor(&hub));
+        assert!(reloaded.forget_anchor(&sub).unwrap());
+        assert!(!reloaded.forget_anchor(&sub).unwrap());
+        let reloaded = FederationManager::new().with_anchors(&path);
+        assert_eq!(reloaded.dial_addresses(), ["hub.example.org:7443"]);
+    }
+}
@@ -0,0 +1,220 @@
+//! Anti-entropy gossip of anchors' manifests between federation links.
+//!
+//! Every so often a burrow sends each authenticated federation link it
+//! has a tunnel to a digest of the manifests it holds, one
+//! `<anchor>\t<serial>` line per anchor (its own published manifest
+//! included):
+//!
+//! ```text
+//! MANIFEST digest
+//! Lane: 0
+//! End:
+//! ed25519:AAAA… 7
+//! ed25519:BBBB… 2
+//! ```
+//!
+//! The link compares the digest with what it holds.  Manifests the
+//! sender lacks, or holds an older serial of, it pushes back as plain
+//! `MANIFEST` frames; anchors the sender holds newer serials of it asks
+//! for with `MANIFEST want`, one anchor per line, which the sender
+//! answers by pushing those manifests.  Only missing or updated
+//! manifests cross the link, and each is verified against its anchor's
+//! key before it is kept (see [`crate::burrow::Burrow::accept_manifest`]).
+//! All three need the sender to hold `Federation`.
+//!
+//! A round that changes nothing doubles the interval, up to
+//! `federation.gossip_max_secs`; one in which a manifest is accepted
+//! or published starts it over at `federation.gossip_secs` (see
+//! [`GossipBackoff`]).  Digests and wants carry at most
+//! [`MAX_ENTRIES`] anchors, so a round's traffic stays bounded.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::burrow::Burrow;
+use crate::protocol::frame::Frame;
+use crate::security::manifest::TrustManifest;
+
+/// Anchors listed in one digest or want, and manifests pushed in
+/// answer to one.
+pub const MAX_ENTRIES: usize = 256;
+
+/// The `MANIFEST digest` frame listing `manifests`.
+pub fn digest_frame(manifests: &[TrustManifest]) -> Frame {
+    let body: String = manifests
+        .iter()
+        .take(MAX_ENTRIES)
+        .map(|m| format!("{}\t{}\n", m.anchor, m.serial))
+        .collect();
+    Frame::builder("MANIFEST digest")
+        .lane(0)
+        .body_text(body)
+        .build()
+        .unwrap_or_else(Frame::from)
+}
+
+/// The `MANIFEST want` frame asking for `anchors`' manifests.
+pub fn want_frame(anchors: &[String]) -> Frame {
+    let body: String = anchors
+        .iter()
+        .take(MAX_ENTRIES)
+        .map(|a| format!("{}\n", a))
+        .collect();
+    Frame::builder("MANIFEST want")
+        .lane(0)
+        .body_text(body)
+        .build()
+        .unwrap_or_else(Frame::from)
+}
+
+/// The serial of each anchor a digest lists.  Malformed lines are
+/// skipped.
+pub fn parse_digest(frame: &Frame) -> HashMap<String, u64> {
+    frame
+        .body
+        .as_deref()
+        .unwrap_or("")
+        .lines()
+        .filter_map(|line| {
+            let (anchor, serial) = line.split_once('\t')?;
+            Some((anchor.trim().to_string(), serial.trim().parse().ok()?))
+        })
+        .filter(|(anchor, _)| !anchor.is_empty())
+        .take(MAX_ENTRIES)
+        .collect()
+}
+
+/// The anchors a want asks for.
+pub fn parse_want(frame: &Frame) -> Vec<String> {
+    frame
+        .body
+        .as_deref()
+        .unwrap_or("")
+        .lines()
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .take(MAX_ENTRIES)
+        .map(str::to_string)
+        .collect()
+}
+
+/// What a digest calls for, given the manifests `held` here.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    /// Manifests the digest's sender lacks or holds older serials of.
+    pub push: Vec<TrustManifest>,
+    /// Anchors whose manifests the sender holds newer serials of.
+    pub want: Vec<String>,
+}
+
+/// Compare `held` with a digest, `theirs`.
+pub fn reconcile(held: &[TrustManifest], theirs: &HashMap<String, u64>) -> Reconciliation {
+    let ours: HashMap<&str, u64> = held.iter().map(|m| (m.anchor.as_str(), m.serial)).collect();
+    let push = held
+        .iter()
+        .filter(|m| theirs.get(&m.anchor).is_none_or(|&serial| serial < m.serial))
+        .take(MAX_ENTRIES)
+        .cloned()
+        .collect();
+    let mut want: Vec<String> = theirs
+        .iter()
+        .filter(|(anchor, &serial)| ours.get(anchor.as_str()).is_none_or(|&s| s < serial))
+        .map(|(anchor, _)| anchor.clone())
+        .collect();
+    want.sort();
+    want.truncate(MAX_ENTRIES);
+    Reconciliation { push, want }
+}
+
+/// The interval between gossip rounds: `base` after a round in which
+/// something changed, doubling up to `max` while nothing does.
+#[derive(Debug)]
+pub struct GossipBackoff {
+    base: Duration,
+    max: Duration,
+    current: Duration,
+    seen: Option<u64>,
+}
+
+impl GossipBackoff {
+    /// Start at `base`, backing off to at most `max`.
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max: max.max(base),
+            current: base,
+            seen: None,
+        }
+    }
+
+    /// The wait before the next round, given the federation's change
+    /// count now (see [`crate::warren::federation::FederationManager::changes`]).
+    pub fn next(&mut self, changes: u64) -> Duration {
+        if self.seen.is_some_and(|seen| seen == changes) {
+            self.current = (self.current * 2).min(self.max);
+        } else {
+            self.current = self.base;
+        }
+        self.seen = Some(changes);
+        self.current
+    }
+}
+
+/// Gossip manifests with `burrow`'s links every `base` seconds, backing
+/// off to `max` while nothing changes.  Runs until aborted.
+pub fn spawn_gossip(burrow: Arc<Burrow>, base: Duration, max: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut backoff = GossipBackoff::new(base, max);
+        loop {
+            let wait = backoff.next(burrow.federation.changes());
+            tokio::time::sleep(wait).await;
+            let links = burrow.gossip_manifests();
+            debug!(links, next_secs = wait.as_secs(), "sent manifest digests");
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::identity::Identity;
+
+    fn manifest(anchor: &Identity, serial: u64) -> TrustManifest {
+        TrustManifest::sign(anchor, serial, &[], &[], &[], 3600)
+    }
+
+    #[test]
+    fn digests_reconcile_to_pushes_and_wants() {
+        let (a, b, c) = (Identity::generate(), Identity::generate(), Identity::generate());
+        let held = vec![manifest(&a, 3), manifest(&b, 1)];
+        let digest = digest_frame(&[manifest(&b, 2), manifest(&c, 1), manifest(&a, 3)]);
+        let theirs = parse_digest(&digest);
+        assert_eq!(theirs.len(), 3);
+
+        let plan = reconcile(&held, &theirs);
+        assert!(plan.push.is_empty());
+        let mut expected = vec![b.burrow_id(), c.burrow_id()];
+        expected.sort();
+        assert_eq!(plan.want, expected);
+        assert_eq!(parse_want(&want_frame(&plan.want)), expected);
+
+        // The other way round, the older and missing ones are pushed.
+        let plan = reconcile(&[manifest(&a, 4)], &theirs);
+        assert_eq!(plan.push.len(), 1);
+        assert!(plan.want.contains(&b.burrow_id()));
+    }
+
+    #[test]
+    fn quiet_rounds_back_off() {
+        let mut backoff = GossipBackoff::new(Duration::from_secs(10), Duration::from_secs(35));
+        assert_eq!(backoff.next(0), Duration::from_secs(10));
+        assert_eq!(backoff.next(0), Duration::from_secs(20));
+        assert_eq!(backoff.next(0), Duration::from_secs(35));
+        assert_eq!(backoff.next(0), Duration::from_secs(35));
+        assert_eq!(backoff.next(1), Duration::from_secs(10));
+    }
+}
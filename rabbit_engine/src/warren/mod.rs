@@ -1,8 +1,18 @@
 //! Warren — a cluster of cooperating burrows.
 //!
 //! This module provides the peer table and discovery mechanisms
-//! that let burrows know about each other.
+//! that let burrows know about each other, federation under common
+//! anchors and gossip of their manifests, signed advertisements of
+//! reachable peers, discovery of the services other warrens
+//! offer, relaying for burrows behind NAT, plus declarative
+//! topologies for launching whole warrens.
 
 pub mod discovery;
+pub mod federation;
+pub mod gossip;
+pub mod offer;
 pub mod peers;
+pub mod relay;
 pub mod routing;
+pub mod services;
+pub mod topology;
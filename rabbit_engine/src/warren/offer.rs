@@ -0,0 +1,228 @@
+//! Signed peer advertisements.
+//!
+//! A burrow advertises the peers it can reach in an `OFFER` frame,
+//! one `<id>\t<address>\t<name>` line per peer, signed by the burrow
+//! making the advertisement:
+//!
+//! ```text
+//! OFFER /warren
+//! Warren-ID: ed25519:…
+//! Signature: ed25519:<hex>
+//! Length: 42
+//! End:
+//! ed25519:… 203.0.113.7:7443 oak
+//! ```
+//!
+//! The signature covers `RABBIT-OFFER\n<warren-id>` followed by
+//! `\n<line>` for each body line, in order, so an advertisement whose
+//! `Warren-ID` names any key but the one that signed it fails to
+//! verify.
+//!
+//! A burrow configured with `trust.anchors` takes only signed
+//! advertisements, and only registers the peers in one once the
+//! burrow that signed it is an anchor whose manifest it holds, or is
+//! vouched for by one (see
+//! [`crate::security::trust::TrustCache::is_vouched_for`]).  Until
+//! then the advertisement waits in [`PendingOffers`], and is taken
+//! when a manifest proving its signer arrives.  Without anchors,
+//! signatures are checked when present and unsigned advertisements
+//! are still taken.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+use crate::security::auth::{hex_decode, hex_encode};
+use crate::security::identity::{parse_burrow_id, Identity};
+use crate::warren::peers::PeerInfo;
+
+/// Advertisements held at once, one per signer.
+pub const MAX_PENDING: usize = 64;
+
+/// The peers one burrow advertises, and its signature over them.
+#[derive(Debug, Clone)]
+pub struct PeerOffer {
+    /// Burrow ID of the advertiser, whose key signs the offer.
+    pub warren_id: String,
+    /// The peers advertised.
+    pub peers: Vec<PeerInfo>,
+    /// Signature by the advertiser.
+    pub signature: Vec<u8>,
+}
+
+impl PeerOffer {
+    /// Sign an advertisement of `peers` as `identity`.
+    pub fn sign(identity: &Identity, peers: Vec<PeerInfo>) -> Self {
+        let mut offer = Self {
+            warren_id: identity.burrow_id(),
+            peers,
+            signature: Vec::new(),
+        };
+        offer.signature = identity.sign(&offer.signed_message());
+        offer
+    }
+
+    /// Check the signature against the key `Warren-ID` names.  Fails
+    /// with `Forbidden` if another key signed it.
+    pub fn verify(&self) -> Result<(), ProtocolError> {
+        Identity::verify(
+            &parse_burrow_id(&self.warren_id)?,
+            &self.signed_message(),
+            &self.signature,
+        )
+        .map_err(|_| {
+            ProtocolError::Forbidden(format!("advertisement is not signed by {}", self.warren_id))
+        })
+    }
+
+    /// The `OFFER /warren` frame carrying this advertisement.
+    pub fn to_frame(&self) -> Frame {
+        Frame::builder("OFFER")
+            .selector("/warren")
+            .header("Warren-ID", &self.warren_id)
+            .header(
+                "Signature",
+                format!("ed25519:{}", hex_encode(&self.signature)),
+            )
+            .body_text(peer_lines(&self.peers))
+            .build()
+            .unwrap_or_else(Frame::from)
+    }
+
+    /// Read a signed advertisement from an `OFFER` frame.  The
+    /// signature is not checked; see [`PeerOffer::verify`].
+    pub fn from_frame(frame: &Frame) -> Result<Self, ProtocolError> {
+        let header = |name: &str| {
+            frame
+                .header(name)
+                .ok_or_else(|| ProtocolError::BadRequest(format!("OFFER missing {}", name)))
+        };
+        let signature = header("Signature")?;
+        let signature = hex_decode(signature.strip_prefix("ed25519:").unwrap_or(signature))
+            .map_err(|e| ProtocolError::BadRequest(format!("invalid Signature: {}", e)))?;
+        Ok(Self {
+            warren_id: header("Warren-ID")?.to_string(),
+            peers: parse_peers(frame.body.as_deref().unwrap_or("")),
+            signature,
+        })
+    }
+
+    fn signed_message(&self) -> Vec<u8> {
+        let mut message = format!("RABBIT-OFFER\n{}", self.warren_id);
+        for line in peer_lines(&self.peers).lines() {
+            message.push('\n');
+            message.push_str(line);
+        }
+        message.into_bytes()
+    }
+}
+
+/// The body of an `OFFER`: `<id>\t<address>\t<name>` per peer.
+pub fn peer_lines(peers: &[PeerInfo]) -> String {
+    peers
+        .iter()
+        .map(|p| format!("{}\t{}\t{}\n", p.id, p.address, p.name))
+        .collect()
+}
+
+/// The peers listed in an `OFFER` body.  Lines without an address are
+/// skipped.
+pub fn parse_peers(body: &str) -> Vec<PeerInfo> {
+    body.lines()
+        .filter_map(|line| {
+            let mut parts = line.split('\t');
+            let id = parts.next()?;
+            let address = parts.next()?;
+            Some(PeerInfo::new(id, address, parts.next().unwrap_or("")))
+        })
+        .collect()
+}
+
+/// Verified advertisements from burrows not yet vouched for.
+#[derive(Debug, Default)]
+pub struct PendingOffers {
+    offers: Mutex<HashMap<String, PeerOffer>>,
+}
+
+impl PendingOffers {
+    /// An empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold `offer` until its signer is vouched for, in place of any
+    /// held from the same signer.  Fails with `Busy` if
+    /// [`MAX_PENDING`] other signers' offers are held.
+    pub fn hold(&self, offer: PeerOffer) -> Result<(), ProtocolError> {
+        let mut offers = self.offers.lock().unwrap_or_else(|e| e.into_inner());
+        if offers.len() >= MAX_PENDING && !offers.contains_key(&offer.warren_id) {
+            return Err(ProtocolError::Busy(format!(
+                "{} advertisements already await a manifest",
+                offers.len()
+            )));
+        }
+        offers.insert(offer.warren_id.clone(), offer);
+        Ok(())
+    }
+
+    /// Take the offers whose signers `vouched` now accepts.
+    pub fn release(&self, vouched: impl Fn(&str) -> bool) -> Vec<PeerOffer> {
+        let mut offers = self.offers.lock().unwrap_or_else(|e| e.into_inner());
+        let ready: Vec<String> = offers.keys().filter(|id| vouched(id)).cloned().collect();
+        ready.iter().filter_map(|id| offers.remove(id)).collect()
+    }
+
+    /// How many offers are held.
+    pub fn len(&self) -> usize {
+        self.offers.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Whether no offers are held.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offers_verify_only_under_their_warren_id() {
+        let oak = Identity::generate();
+        let peers = vec![PeerInfo::new("ed25519:PEER", "10.0.0.1:7443", "alpha")];
+        let frame = PeerOffer::sign(&oak, peers).to_frame();
+        let offer = PeerOffer::from_frame(&frame).unwrap();
+        offer.verify().unwrap();
+        assert_eq!(offer.peers[0].address, "10.0.0.1:7443");
+
+        let mut claimed = frame.clone();
+        claimed.set_header("Warren-ID", Identity::generate().burrow_id());
+        assert!(PeerOffer::from_frame(&claimed).unwrap().verify().is_err());
+        let mut altered = frame;
+        altered.set_body("ed25519:PEER\t10.6.6.6:7443\talpha\n");
+        assert!(PeerOffer::from_frame(&altered).unwrap().verify().is_err());
+        assert!(PeerOffer::from_frame(&Frame::new("OFFER")).is_err());
+    }
+
+    #[test]
+    fn pending_offers_are_bounded_and_released() {
+        let pending = PendingOffers::new();
+        let signers: Vec<Identity> = (0..MAX_PENDING).map(|_| Identity::generate()).collect();
+        for signer in &signers {
+            pending.hold(PeerOffer::sign(signer, Vec::new())).unwrap();
+        }
+        let late = PeerOffer::sign(&Identity::generate(), Vec::new());
+        assert!(matches!(pending.hold(late), Err(ProtocolError::Busy(_))));
+        // A newer offer from a signer already held replaces its last.
+        pending
+            .hold(PeerOffer::sign(&signers[0], Vec::new()))
+            .unwrap();
+
+        let first = signers[0].burrow_id();
+        let released = pending.release(|id| id == first);
+        assert_eq!(released.len(), 1);
+        assert_eq!(pending.len(), MAX_PENDING - 1);
+    }
+}
@@ -2,11 +2,74 @@
 //!
 //! The [`PeerTable`] keeps track of peers in a warren.  It is
 //! designed for concurrent access via `tokio::sync::Mutex`.
+//!
+//! Each peer the burrow has seen — over a tunnel, or answering a
+//! health-check `PING` — has a [`PeerHealth`] judged from how long ago
+//! that was (see [`PeerTable::check_health`]): `Degraded` after
+//! `network.peer_degraded_secs`, `Down` after `network.peer_down_secs`,
+//! and pruned from the table after `network.peer_prune_secs`.  Down
+//! peers are left out of [`PeerTable::list_reachable`], which `/warren`
+//! and OFFER use, and degraded ones come last.  Peers only heard of
+//! through OFFER are not judged.
 
 use std::collections::HashMap;
 
 use tokio::sync::Mutex;
 
+/// How recently a peer has been seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum PeerHealth {
+    /// Seen within `peer_degraded_secs`, or never judged.
+    #[default]
+    Up,
+    /// Not seen for `peer_degraded_secs`.
+    Degraded,
+    /// Not seen for `peer_down_secs`.
+    Down,
+}
+
+impl PeerHealth {
+    /// Lower-case name, e.g. `"degraded"`.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Degraded => "degraded",
+            Self::Down => "down",
+        }
+    }
+}
+
+/// When unseen peers are judged degraded, down and gone, in seconds
+/// since they were last seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Liveness {
+    /// Unseen this long, a peer is [`PeerHealth::Degraded`].
+    pub degraded_secs: u64,
+    /// Unseen this long, a peer is [`PeerHealth::Down`].
+    pub down_secs: u64,
+    /// Unseen this long, a peer is removed (0 = never).
+    pub prune_secs: u64,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self {
+            degraded_secs: 45,
+            down_secs: 120,
+            prune_secs: 3600,
+        }
+    }
+}
+
+/// What a [`PeerTable::check_health`] pass changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HealthReport {
+    /// Peers whose health changed, with their new health.
+    pub changed: Vec<(String, PeerHealth)>,
+    /// Peers removed from the table.
+    pub pruned: Vec<String>,
+}
+
 /// Information about a peer burrow.
 #[derive(Debug, Clone)]
 pub struct PeerInfo {
@@ -20,6 +83,10 @@ pub struct PeerInfo {
     pub last_seen: u64,
     /// Whether the peer is currently connected.
     pub connected: bool,
+    /// Smoothed keepalive round-trip time in milliseconds, if measured.
+    pub rtt_ms: Option<u64>,
+    /// How recently the peer has been seen.
+    pub health: PeerHealth,
 }
 
 impl PeerInfo {
@@ -31,6 +98,8 @@ impl PeerInfo {
             name: name.into(),
             last_seen: 0,
             connected: false,
+            rtt_ms: None,
+            health: PeerHealth::Up,
         }
     }
 }
@@ -49,9 +118,17 @@ impl PeerTable {
         }
     }
 
-    /// Register or update a peer.
-    pub async fn register(&self, peer: PeerInfo) {
+    /// Register or update a peer.  What the burrow has observed of a
+    /// peer it already knows — whether it is connected, when it was
+    /// last seen, its round-trip time and health — is kept.
+    pub async fn register(&self, mut peer: PeerInfo) {
         let mut map = self.peers.lock().await;
+        if let Some(known) = map.get(&peer.id) {
+            peer.connected |= known.connected;
+            peer.last_seen = peer.last_seen.max(known.last_seen);
+            peer.rtt_ms = peer.rtt_ms.or(known.rtt_ms);
+            peer.health = known.health;
+        }
         map.insert(peer.id.clone(), peer);
     }
 
@@ -73,6 +150,19 @@ impl PeerTable {
         map.values().cloned().collect()
     }
 
+    /// The peers not [`PeerHealth::Down`], healthy ones first, each
+    /// group sorted by ID.
+    pub async fn list_reachable(&self) -> Vec<PeerInfo> {
+        let map = self.peers.lock().await;
+        let mut peers: Vec<PeerInfo> = map
+            .values()
+            .filter(|p| p.health != PeerHealth::Down)
+            .cloned()
+            .collect();
+        peers.sort_by(|a, b| (a.health, &a.id).cmp(&(b.health, &b.id)));
+        peers
+    }
+
     /// Number of known peers.
     pub async fn count(&self) -> usize {
         let map = self.peers.lock().await;
@@ -85,6 +175,27 @@ impl PeerTable {
         if let Some(peer) = map.get_mut(id) {
             peer.connected = true;
             peer.last_seen = timestamp;
+            peer.health = PeerHealth::Up;
+        }
+    }
+
+    /// Record that a peer was seen, e.g. answering a `PING`.
+    pub async fn touch(&self, id: &str, timestamp: u64) {
+        let mut map = self.peers.lock().await;
+        if let Some(peer) = map.get_mut(id) {
+            peer.last_seen = timestamp;
+            peer.health = PeerHealth::Up;
+        }
+    }
+
+    /// Record a keepalive round trip, which also counts as seeing the
+    /// peer.
+    pub async fn record_rtt(&self, id: &str, rtt_ms: u64, timestamp: u64) {
+        let mut map = self.peers.lock().await;
+        if let Some(peer) = map.get_mut(id) {
+            peer.rtt_ms = Some(rtt_ms);
+            peer.last_seen = timestamp;
+            peer.health = PeerHealth::Up;
         }
     }
 
@@ -95,6 +206,39 @@ impl PeerTable {
             peer.connected = false;
         }
     }
+
+    /// Judge each seen peer's health at `now` (seconds since epoch) by
+    /// how long ago it was last seen, pruning those unseen for
+    /// `liveness.prune_secs`.
+    pub async fn check_health(&self, now: u64, liveness: &Liveness) -> HealthReport {
+        let mut map = self.peers.lock().await;
+        let mut report = HealthReport::default();
+        map.retain(|id, peer| {
+            if peer.last_seen == 0 {
+                return true;
+            }
+            let unseen = now.saturating_sub(peer.last_seen);
+            if liveness.prune_secs > 0 && unseen >= liveness.prune_secs {
+                report.pruned.push(id.clone());
+                return false;
+            }
+            let health = if unseen >= liveness.down_secs {
+                PeerHealth::Down
+            } else if unseen >= liveness.degraded_secs {
+                PeerHealth::Degraded
+            } else {
+                PeerHealth::Up
+            };
+            if health != peer.health {
+                peer.health = health;
+                report.changed.push((id.clone(), health));
+            }
+            true
+        });
+        report.changed.sort();
+        report.pruned.sort();
+        report
+    }
 }
 
 impl Default for PeerTable {
@@ -180,4 +324,58 @@ mod tests {
         assert_eq!(p.address, "10.0.0.2:7443");
         assert_eq!(p.name, "new");
     }
+
+    #[tokio::test]
+    async fn unseen_peers_degrade_go_down_and_are_pruned() {
+        let table = PeerTable::new();
+        for (id, seen) in [("ed25519:AAAA", 1000), ("ed25519:BBBB", 900)] {
+            table.register(PeerInfo::new(id, "", "")).await;
+            table.mark_connected(id, seen).await;
+        }
+        // Only heard of, never seen: not judged.
+        table
+            .register(PeerInfo::new("ed25519:CCCC", "10.0.0.3:7443", "c"))
+            .await;
+        let liveness = Liveness {
+            degraded_secs: 50,
+            down_secs: 100,
+            prune_secs: 200,
+        };
+
+        let report = table.check_health(1010, &liveness).await;
+        assert_eq!(
+            report.changed,
+            [("ed25519:BBBB".to_string(), PeerHealth::Down)]
+        );
+        let report = table.check_health(1060, &liveness).await;
+        assert_eq!(
+            report.changed,
+            [("ed25519:AAAA".to_string(), PeerHealth::Degraded)]
+        );
+        let reachable: Vec<String> = table
+            .list_reachable()
+            .await
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(reachable, ["ed25519:CCCC", "ed25519:AAAA"]);
+
+        // A re-advertised peer keeps its health; a pong restores it.
+        table
+            .register(PeerInfo::new("ed25519:BBBB", "10.0.0.2:7443", "b"))
+            .await;
+        assert_eq!(
+            table.get("ed25519:BBBB").await.unwrap().health,
+            PeerHealth::Down
+        );
+        table.touch("ed25519:AAAA", 1070).await;
+        assert_eq!(
+            table.get("ed25519:AAAA").await.unwrap().health,
+            PeerHealth::Up
+        );
+
+        let report = table.check_health(1100, &liveness).await;
+        assert_eq!(report.pruned, ["ed25519:BBBB"]);
+        assert_eq!(table.count().await, 2);
+    }
 }
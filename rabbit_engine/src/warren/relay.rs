@@ -0,0 +1,283 @@
+//! Relaying for burrows that cannot be dialed.
+//!
+//! A burrow behind NAT dials a publicly reachable one that offers to
+//! relay and registers with it:
+//!
+//! ```text
+//! RELAY-REGISTER
+//! Lane: 0
+//! Txn: relay-register
+//! End:
+//! ```
+//!
+//! The relay needs to have granted the caller `Relay`; it answers
+//! `200 OK` with `Relay-Bandwidth`, the bytes per second it will carry
+//! for the caller (0 for no cap), `403 FORBIDDEN` if it does not relay
+//! for the caller, or `503 BUSY` if it already relays for
+//! `relay.max_clients` burrows.  Registration lasts as long as the
+//! tunnel.  From then on, frames anyone addresses to the registered
+//! burrow with a `Target` header reach it over that tunnel (see
+//! [`crate::burrow::Burrow::forward`]), and since the relay advertises
+//! its direct peers in `ROUTE-ADVERT`, the rest of the warren learns to
+//! route through it.
+//!
+//! Frames relayed to or from a registered burrow are charged to it,
+//! up to `relay.bytes_per_sec` with a second's worth of burst; frames
+//! over the cap are refused with `429 FLOW-LIMIT`.
+//!
+//! A relay with room left says so on its route adverts with a
+//! `Relay: <free slots>` header, so burrows that hear its gossip know
+//! where to register (see [`RelayTable::relays`]).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+use crate::transport::tunnel::Tunnel;
+
+/// A burrow relayed for, and what it may still send this second.
+#[derive(Debug, Clone)]
+struct Allowance {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// The burrows this one relays for and the relays it has heard of.
+#[derive(Debug)]
+pub struct RelayTable {
+    max_clients: usize,
+    bytes_per_sec: u64,
+    clients: Mutex<HashMap<String, Allowance>>,
+    /// Advertised relays, with when each advert goes stale (Unix
+    /// seconds).
+    relays: Mutex<HashMap<String, u64>>,
+}
+
+impl Default for RelayTable {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl RelayTable {
+    /// A table relaying for up to `max_clients` burrows (0 = not a
+    /// relay), each capped at `bytes_per_sec` (0 = uncapped).
+    pub fn new(max_clients: usize, bytes_per_sec: u64) -> Self {
+        Self {
+            max_clients,
+            bytes_per_sec,
+            clients: Mutex::new(HashMap::new()),
+            relays: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether this burrow relays at all.
+    pub fn is_relay(&self) -> bool {
+        self.max_clients > 0
+    }
+
+    /// The per-client cap in bytes per second (0 = uncapped).
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// Relay for `peer`.  Registering again keeps its allowance.
+    /// Fails with `Forbidden` if this burrow does not relay and `Busy`
+    /// if it relays for as many burrows as it will.
+    pub fn register(&self, peer: &str) -> Result<(), ProtocolError> {
+        if !self.is_relay() {
+            return Err(ProtocolError::Forbidden(
+                "this burrow does not relay".into(),
+            ));
+        }
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if clients.contains_key(peer) {
+            return Ok(());
+        }
+        if clients.len() >= self.max_clients {
+            return Err(ProtocolError::Busy(format!(
+                "already relaying for {} burrows",
+                clients.len()
+            )));
+        }
+        clients.insert(
+            peer.to_string(),
+            Allowance {
+                tokens: self.bytes_per_sec as f64,
+                refilled: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Stop relaying for `peer`.  Returns whether it was registered.
+    pub fn unregister(&self, peer: &str) -> bool {
+        self.clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(peer)
+            .is_some()
+    }
+
+    /// Whether `peer` is registered.
+    pub fn is_client(&self, peer: &str) -> bool {
+        self.clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(peer)
+    }
+
+    /// The registered burrows, sorted.
+    pub fn clients(&self) -> Vec<String> {
+        let mut clients: Vec<String> = self
+            .clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect();
+        clients.sort();
+        clients
+    }
+
+    /// How many more burrows this one would relay for.
+    pub fn free_slots(&self) -> usize {
+        let clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        self.max_clients.saturating_sub(clients.len())
+    }
+
+    /// Charge `bytes` relayed at `now` to `peer`.  Fails with
+    /// `FlowLimit` if that would take it over its cap; peers that are
+    /// not registered are never charged.
+    pub fn charge(&self, peer: &str, bytes: usize, now: Instant) -> Result<(), ProtocolError> {
+        if self.bytes_per_sec == 0 {
+            return Ok(());
+        }
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(allowance) = clients.get_mut(peer) else {
+            return Ok(());
+        };
+        let rate = self.bytes_per_sec as f64;
+        let elapsed = now.saturating_duration_since(allowance.refilled);
+        allowance.tokens = (allowance.tokens + elapsed.as_secs_f64() * rate).min(rate);
+        allowance.refilled = now;
+        if bytes as f64 > allowance.tokens {
+            return Err(ProtocolError::FlowLimit(format!(
+                "relay bandwidth for {} exceeded ({} bytes/s)",
+                peer, self.bytes_per_sec
+            )));
+        }
+        allowance.tokens -= bytes as f64;
+        Ok(())
+    }
+
+    /// Note that `relay` advertised room at `now`, for `ttl_secs`.
+    pub fn heard(&self, relay: &str, now: u64, ttl_secs: u64) {
+        self.relays
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(relay.to_string(), now.saturating_add(ttl_secs));
+    }
+
+    /// Forget `relay`, e.g. once it advertises no room.
+    pub fn forget(&self, relay: &str) {
+        self.relays
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(relay);
+    }
+
+    /// Relays whose last advert has not gone stale by `now`, sorted.
+    pub fn relays(&self, now: u64) -> Vec<String> {
+        let mut relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        relays.retain(|_, expires_at| *expires_at > now);
+        let mut ids: Vec<String> = relays.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+}
+
+/// Register with the relay at the other end of `tunnel`, a tunnel
+/// that has completed the handshake.  Returns the bandwidth the relay
+/// allows, in bytes per second (0 for no cap).
+///
+/// Fails with `Forbidden` or `Busy` as the relay answers.
+pub async fn register<T: Tunnel>(tunnel: &mut T) -> Result<u64, ProtocolError> {
+    let request = Frame::builder("RELAY-REGISTER")
+        .lane(0)
+        .txn("relay-register")
+        .build()?;
+    tunnel.send_frame(&request).await?;
+    loop {
+        let reply = tunnel.recv_frame().await?.ok_or_else(|| {
+            ProtocolError::InternalError("tunnel closed during RELAY-REGISTER".into())
+        })?;
+        if reply.header("Txn") != request.header("Txn") {
+            continue;
+        }
+        let detail = || reply.body.clone().unwrap_or_default();
+        return match reply.verb.as_str() {
+            "200" => Ok(reply
+                .header("Relay-Bandwidth")
+                .and_then(|b| b.parse().ok())
+                .unwrap_or(0)),
+            "403" => Err(ProtocolError::Forbidden(detail())),
+            "503" => Err(ProtocolError::Busy(detail())),
+            other => Err(ProtocolError::BadRequest(format!(
+                "unexpected reply to RELAY-REGISTER: {} {}",
+                other,
+                reply.args.join(" ")
+            ))),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn registration_is_bounded() {
+        assert!(RelayTable::default().register("a").is_err());
+
+        let table = RelayTable::new(1, 0);
+        table.register("a").unwrap();
+        table.register("a").unwrap();
+        assert!(matches!(table.register("b"), Err(ProtocolError::Busy(_))));
+        assert_eq!(table.free_slots(), 0);
+        assert!(table.unregister("a"));
+        table.register("b").unwrap();
+        assert_eq!(table.clients(), vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn bandwidth_refills_over_time() {
+        let table = RelayTable::new(4, 1000);
+        table.register("a").unwrap();
+        let start = Instant::now();
+        table.charge("a", 800, start).unwrap();
+        assert!(matches!(
+            table.charge("a", 400, start),
+            Err(ProtocolError::FlowLimit(_))
+        ));
+        table
+            .charge("a", 400, start + Duration::from_millis(300))
+            .unwrap();
+        // Unregistered peers are not charged.
+        table.charge("b", 1_000_000, start).unwrap();
+    }
+
+    #[test]
+    fn advertised_relays_go_stale() {
+        let table = RelayTable::default();
+        table.heard("r1", 100, 30);
+        table.heard("r2", 120, 30);
+        assert_eq!(table.relays(125), vec!["r1".to_string(), "r2".to_string()]);
+        assert_eq!(table.relays(140), vec!["r2".to_string()]);
+        table.forget("r2");
+        assert!(table.relays(140).is_empty());
+    }
+}
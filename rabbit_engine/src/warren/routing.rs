@@ -1,17 +1,194 @@
 //! Routing table for multi-hop frame forwarding.
 //!
 //! The [`RoutingTable`] maps target burrow IDs to next-hop burrow
-//! IDs.  It is populated from OFFER advertisements and direct peer
-//! connections.  Frame forwarding uses this table to determine where
-//! to send a frame when the target is not the local burrow.
+//! IDs.  Frame forwarding uses this table to determine where to send a
+//! frame when the target is not the local burrow.
+//!
+//! Routes are learned from `ROUTE-ADVERT` frames.  Every
+//! `network.route_advert_secs` a burrow tells each connected peer
+//! which burrows it can reach, one `<burrow-id>\t<hops>` line each:
+//! its other direct peers at 1 hop and the targets of its table, less
+//! those it reaches through that peer (split horizon).  The peer
+//! installs each as a route through the sender one hop longer (see
+//! [`RoutingTable::learn`]), which lives for `network.route_ttl_secs`
+//! unless another advert refreshes it; stale routes are dropped by
+//! [`RoutingTable::expire`].  Routes longer than
+//! [`DEFAULT_HOP_COUNT`] are ignored.
+//!
+//! A frame is addressed to another burrow with a `Target` header.  A
+//! burrow that receives one relays it (see
+//! [`crate::burrow::Burrow::forward`]) to the target itself if it has
+//! a tunnel to it, or else to the next hop in its table, taking one
+//! off `Hop-Count` (8 if absent) and adding itself to `Via`, the list
+//! of burrows the frame has passed, originator first.  A frame that
+//! cannot go on is answered with `502 NO-ROUTE`, addressed back to the
+//! originator, and a frame whose `Via` already names the burrow has
+//! looped.  The target addresses its answer back to the originator
+//! (see [`address_reply`]); responses are relayed the same way but
+//! never answered.
+//!
+//! [`RoutingTable::topology`] takes a [`Topology`] snapshot of what the
+//! burrow knows of its warren: the peers in its peer table, the burrows
+//! it has tunnels to, and the routes in its table, each route a link
+//! from its next hop to its target.  [`Topology::shortest_path`] finds
+//! the path to a burrow with the fewest hops, preferring, between
+//! paths as short, the one whose first hop was seen most recently.
 //!
 //! Thread-safe via `tokio::sync::Mutex` for async contexts.
 
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
 
+use serde::Serialize;
 use tokio::sync::Mutex;
 use tracing::debug;
 
+use crate::protocol::error::ProtocolError;
+use crate::protocol::frame::Frame;
+use crate::warren::peers::PeerInfo;
+
+/// Hops a frame without a `Hop-Count` header may take.
+pub const DEFAULT_HOP_COUNT: u32 = 8;
+
+/// What became of a frame offered for forwarding.
+#[derive(Debug)]
+pub enum Forward {
+    /// The frame is for this burrow: dispatch it.
+    Local,
+    /// The frame was queued for the next hop, a burrow ID.
+    Sent(String),
+    /// The frame cannot go on; send this error back where it came from.
+    Reply(Frame),
+    /// A response that cannot go on was dropped.
+    Dropped,
+}
+
+/// The burrows named in a `Via` header, in order.
+pub fn via_hops(frame: &Frame) -> Vec<&str> {
+    frame
+        .header("Via")
+        .map(|via| {
+            via.split(',')
+                .map(str::trim)
+                .filter(|hop| !hop.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Address `reply` to the originator of `request`, the first burrow
+/// its `Via` names, if `request` was relayed here.
+pub fn address_reply(request: &Frame, reply: &mut Frame) {
+    if request.header("Target").is_none() {
+        return;
+    }
+    if let Some(origin) = via_hops(request).first() {
+        reply.set_header("Target", *origin);
+    }
+}
+
+/// A burrow in a [`Topology`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TopologyNode {
+    /// The burrow's ID.
+    pub id: String,
+    /// Human-readable name, empty if unknown.
+    pub name: String,
+    /// Whether this burrow has a tunnel to it.
+    pub connected: bool,
+    /// Last time it was seen (seconds since epoch), 0 if never.
+    pub last_seen: u64,
+}
+
+/// A link in a [`Topology`]: `to` is reached from `from` in `hops`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct TopologyLink {
+    /// Burrow ID at the near end.
+    pub from: String,
+    /// Burrow ID at the far end.
+    pub to: String,
+    /// Hops between them; more than 1 where the burrows in between are
+    /// unknown.
+    pub hops: u32,
+}
+
+/// A path through a [`Topology`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TopologyPath {
+    /// Hops along the path.
+    pub hops: u32,
+    /// Burrow IDs along the path, from the local burrow to the target.
+    pub burrows: Vec<String>,
+}
+
+/// A snapshot of the warren as one burrow knows it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Topology {
+    /// The burrow that took the snapshot.
+    pub local: String,
+    /// Every other burrow it knows of, sorted by ID.
+    pub nodes: Vec<TopologyNode>,
+    /// Every link it knows of, sorted.
+    pub links: Vec<TopologyLink>,
+}
+
+/// How a path ranks, least first: by hops, then by how recently its
+/// first hop was seen.
+type PathRank = (u32, Reverse<u64>);
+
+impl Topology {
+    /// The node for `id`, if known.
+    pub fn node(&self, id: &str) -> Option<&TopologyNode> {
+        self.nodes.iter().find(|n| n.id == id)
+    }
+
+    /// The path to `target` with the fewest hops, or `None` if no
+    /// known link leads there.  Between paths as short, the one whose
+    /// first hop was seen most recently wins.
+    pub fn shortest_path(&self, target: &str) -> Option<TopologyPath> {
+        let seen: HashMap<&str, u64> = self
+            .nodes
+            .iter()
+            .map(|n| (n.id.as_str(), n.last_seen))
+            .collect();
+        let mut best: HashMap<&str, (PathRank, Vec<&str>)> = HashMap::new();
+        let mut done: HashSet<&str> = HashSet::new();
+        best.insert(&self.local, ((0, Reverse(u64::MAX)), vec![&self.local]));
+        loop {
+            let (node, (key, path)) = best
+                .iter()
+                .filter(|(id, _)| !done.contains(*id))
+                .min_by_key(|(id, (key, _))| (*key, **id))
+                .map(|(id, found)| (*id, found.clone()))?;
+            if node == target {
+                return Some(TopologyPath {
+                    hops: key.0,
+                    burrows: path.into_iter().map(String::from).collect(),
+                });
+            }
+            done.insert(node);
+            for link in self.links.iter().filter(|l| l.from == node) {
+                let to = link.to.as_str();
+                if done.contains(to) {
+                    continue;
+                }
+                let fresh = if path.len() == 1 {
+                    seen.get(to).copied().unwrap_or(0)
+                } else {
+                    key.1 .0
+                };
+                let candidate = (key.0 + link.hops, Reverse(fresh));
+                if best.get(to).is_none_or(|(known, _)| candidate < *known) {
+                    let mut longer = path.clone();
+                    longer.push(to);
+                    best.insert(to, (candidate, longer));
+                }
+            }
+        }
+    }
+}
+
 /// An entry in the routing table.
 #[derive(Debug, Clone)]
 pub struct RouteEntry {
@@ -19,6 +196,9 @@ pub struct RouteEntry {
     pub next_hop: String,
     /// Number of hops to reach the target (1 = direct peer).
     pub distance: u32,
+    /// When a learned route goes stale (Unix seconds); `None` for a
+    /// route set with [`RoutingTable::update`], which never does.
+    pub expires_at: Option<u64>,
 }
 
 /// Maps target burrow IDs to next-hop routing entries.
@@ -49,12 +229,52 @@ impl RoutingTable {
                 RouteEntry {
                     next_hop: next_hop.to_string(),
                     distance,
+                    expires_at: None,
                 },
             );
             debug!(target = %target, next_hop = %next_hop, distance = distance, "route updated");
         }
     }
 
+    /// Install a route learned at `now` (Unix seconds) from
+    /// `next_hop`'s advertisement, to go stale `ttl_secs` later.
+    ///
+    /// The route replaces one that is longer or already stale.  A
+    /// learned route through the same hop is always replaced, so its
+    /// metric follows the hop's latest advertisement.  Returns whether
+    /// the table changed.
+    pub async fn learn(
+        &self,
+        target: &str,
+        next_hop: &str,
+        distance: u32,
+        now: u64,
+        ttl_secs: u64,
+    ) -> bool {
+        let mut routes = self.routes.lock().await;
+        let replace = match routes.get(target) {
+            None => true,
+            Some(entry) => match entry.expires_at {
+                Some(stale) => {
+                    entry.next_hop == next_hop || distance < entry.distance || stale <= now
+                }
+                None => distance < entry.distance,
+            },
+        };
+        if replace {
+            routes.insert(
+                target.to_string(),
+                RouteEntry {
+                    next_hop: next_hop.to_string(),
+                    distance,
+                    expires_at: Some(now.saturating_add(ttl_secs)),
+                },
+            );
+            debug!(target = %target, next_hop = %next_hop, distance = distance, "route learned");
+        }
+        replace
+    }
+
     /// Look up the next hop for a target burrow ID.
     pub async fn next_hop(&self, target: &str) -> Option<String> {
         let routes = self.routes.lock().await;
@@ -78,6 +298,34 @@ impl RoutingTable {
         routes.retain(|_, v| v.next_hop != next_hop);
     }
 
+    /// Remove the learned routes that are stale at `now` (Unix
+    /// seconds).  Returns how many were removed.
+    pub async fn expire(&self, now: u64) -> usize {
+        let mut routes = self.routes.lock().await;
+        let before = routes.len();
+        routes.retain(|target, v| {
+            let live = v.expires_at.is_none_or(|stale| stale > now);
+            if !live {
+                debug!(target = %target, next_hop = %v.next_hop, "route expired");
+            }
+            live
+        });
+        before - routes.len()
+    }
+
+    /// The `(target, distance)` pairs to advertise to `peer`: every
+    /// route except those through `peer` or to it, sorted by target.
+    pub async fn advert_for(&self, peer: &str) -> Vec<(String, u32)> {
+        let routes = self.routes.lock().await;
+        let mut advert: Vec<(String, u32)> = routes
+            .iter()
+            .filter(|(t, e)| e.next_hop != peer && t.as_str() != peer)
+            .map(|(t, e)| (t.clone(), e.distance))
+            .collect();
+        advert.sort();
+        advert
+    }
+
     /// Return all known routes as `(target, next_hop, distance)` triples.
     pub async fn all_routes(&self) -> Vec<(String, String, u32)> {
         let routes = self.routes.lock().await;
@@ -87,6 +335,71 @@ impl RoutingTable {
             .collect()
     }
 
+    /// A snapshot of the warren as seen from `local`, from its known
+    /// `peers`, the burrows it has tunnels to (`direct`), and this
+    /// table.  A route is a link from its next hop to its target, and
+    /// counts only if the next hop is connected.
+    pub async fn topology(&self, local: &str, peers: &[PeerInfo], direct: &[String]) -> Topology {
+        let mut nodes: BTreeMap<String, TopologyNode> = BTreeMap::new();
+        let mut node = |id: &str| {
+            nodes.entry(id.to_string()).or_insert_with(|| TopologyNode {
+                id: id.to_string(),
+                name: String::new(),
+                connected: false,
+                last_seen: 0,
+            });
+        };
+        for id in direct {
+            node(id);
+        }
+        let routes = self.all_routes().await;
+        for (target, next_hop, _) in &routes {
+            node(target);
+            node(next_hop);
+        }
+        for peer in peers {
+            node(&peer.id);
+        }
+        for peer in peers {
+            if let Some(n) = nodes.get_mut(&peer.id) {
+                n.name = peer.name.clone();
+                n.connected = peer.connected;
+                n.last_seen = peer.last_seen;
+            }
+        }
+        for id in direct {
+            if let Some(n) = nodes.get_mut(id) {
+                n.connected = true;
+            }
+        }
+        nodes.remove(local);
+
+        let mut links = BTreeSet::new();
+        let connected = |id: &str| nodes.get(id).is_some_and(|n| n.connected);
+        for n in nodes.values().filter(|n| n.connected) {
+            links.insert(TopologyLink {
+                from: local.to_string(),
+                to: n.id.clone(),
+                hops: 1,
+            });
+        }
+        for (target, next_hop, distance) in routes {
+            if target == next_hop || target == local || !connected(&next_hop) {
+                continue;
+            }
+            links.insert(TopologyLink {
+                from: next_hop,
+                to: target,
+                hops: distance.saturating_sub(1).max(1),
+            });
+        }
+        Topology {
+            local: local.to_string(),
+            nodes: nodes.into_values().collect(),
+            links: links.into_iter().collect(),
+        }
+    }
+
     /// Return the number of known routes.
     pub async fn len(&self) -> usize {
         self.routes.lock().await.len()
@@ -96,6 +409,70 @@ impl RoutingTable {
     pub async fn is_empty(&self) -> bool {
         self.routes.lock().await.is_empty()
     }
+
+    /// Save the table to a TSV file.
+    ///
+    /// Format: `<target>\t<next_hop>\t<distance>\n`, sorted by target,
+    /// with a fourth `<expires_at>` column for learned routes.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), ProtocolError> {
+        let mut routes: Vec<(String, RouteEntry)> = self
+            .routes
+            .lock()
+            .await
+            .iter()
+            .map(|(t, e)| (t.clone(), e.clone()))
+            .collect();
+        routes.sort_by(|a, b| a.0.cmp(&b.0));
+        let content: String = routes
+            .iter()
+            .map(|(t, e)| match e.expires_at {
+                Some(stale) => format!("{}\t{}\t{}\t{}\n", t, e.next_hop, e.distance, stale),
+                None => format!("{}\t{}\t{}\n", t, e.next_hop, e.distance),
+            })
+            .collect();
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                ProtocolError::InternalError(format!("failed to create directory: {}", e))
+            })?;
+        }
+        std::fs::write(path.as_ref(), content).map_err(|e| {
+            ProtocolError::InternalError(format!("failed to write routing table: {}", e))
+        })
+    }
+
+    /// Load a table saved by [`save`](Self::save).
+    ///
+    /// A missing file yields an empty table; malformed lines are
+    /// skipped.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let mut routes = HashMap::new();
+        if let Ok(content) = std::fs::read_to_string(path) {
+            for line in content.lines() {
+                let parts: Vec<&str> = line.split('\t').collect();
+                let expires_at = match parts.len() {
+                    3 => None,
+                    4 => match parts[3].parse() {
+                        Ok(stale) => Some(stale),
+                        Err(_) => continue,
+                    },
+                    _ => continue,
+                };
+                if let Ok(distance) = parts[2].parse() {
+                    routes.insert(
+                        parts[0].to_string(),
+                        RouteEntry {
+                            next_hop: parts[1].to_string(),
+                            distance,
+                            expires_at,
+                        },
+                    );
+                }
+            }
+        }
+        Self {
+            routes: Mutex::new(routes),
+        }
+    }
 }
 
 impl Default for RoutingTable {
@@ -169,4 +546,98 @@ mod tests {
         assert_eq!(routes[0], ("t1".into(), "h1".into(), 1));
         assert_eq!(routes[1], ("t2".into(), "h2".into(), 2));
     }
+
+    #[tokio::test]
+    async fn save_and_load_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("routes.tsv");
+        let rt = RoutingTable::new();
+        rt.update("t1", "h1", 1).await;
+        rt.update("t2", "h1", 3).await;
+        rt.save(&path).await.unwrap();
+
+        let loaded = RoutingTable::load(&path);
+        assert_eq!(loaded.len().await, 2);
+        assert_eq!(loaded.get("t2").await.unwrap().distance, 3);
+        assert!(
+            RoutingTable::load(dir.path().join("missing.tsv"))
+                .is_empty()
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn learned_routes_follow_adverts_and_expire() {
+        let rt = RoutingTable::new();
+        assert!(rt.learn("t1", "hop-B", 3, 1000, 90).await);
+        // A longer route through another hop is ignored, a shorter one
+        // wins, and the current hop's metric is taken even if worse.
+        assert!(!rt.learn("t1", "hop-C", 4, 1010, 90).await);
+        assert!(rt.learn("t1", "hop-C", 2, 1010, 90).await);
+        assert!(rt.learn("t1", "hop-C", 5, 1020, 90).await);
+        assert_eq!(rt.get("t1").await.unwrap().distance, 5);
+        // Once stale, any route replaces it.
+        assert!(rt.learn("t1", "hop-B", 6, 1110, 90).await);
+        assert_eq!(rt.next_hop("t1").await, Some("hop-B".into()));
+
+        // Routes set with update never go stale or yield to longer ones.
+        rt.update("t2", "hop-C", 2).await;
+        assert!(!rt.learn("t2", "hop-C", 3, 1110, 90).await);
+        rt.learn("t3", "hop-C", 2, 1110, 90).await;
+        assert_eq!(
+            rt.advert_for("hop-B").await,
+            [("t2".to_string(), 2), ("t3".to_string(), 2)]
+        );
+        assert_eq!(rt.advert_for("hop-C").await, [("t1".to_string(), 6)]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("routes.tsv");
+        rt.save(&path).await.unwrap();
+        let loaded = RoutingTable::load(&path);
+        assert_eq!(loaded.get("t3").await.unwrap().expires_at, Some(1200));
+        assert_eq!(loaded.get("t2").await.unwrap().expires_at, None);
+
+        assert_eq!(rt.expire(1199).await, 0);
+        assert_eq!(rt.expire(1200).await, 2);
+        assert_eq!(rt.all_routes().await, [("t2".into(), "hop-C".into(), 2)]);
+    }
+
+    #[tokio::test]
+    async fn topology_finds_the_shortest_freshest_path() {
+        let rt = RoutingTable::new();
+        rt.update("far", "hop-B", 3).await;
+        rt.update("near", "hop-C", 2).await;
+        rt.update("lost", "hop-D", 2).await;
+        let mut b = PeerInfo::new("hop-B", "b:7443", "bee");
+        b.last_seen = 100;
+        let mut c = PeerInfo::new("hop-C", "c:7443", "sea");
+        c.connected = true;
+        c.last_seen = 200;
+        let d = PeerInfo::new("hop-D", "d:7443", "dee");
+        let topology = rt.topology("me", &[b, c, d], &["hop-B".to_string()]).await;
+
+        assert_eq!(topology.nodes.len(), 6);
+        assert_eq!(topology.node("hop-C").unwrap().name, "sea");
+        assert!(topology.node("hop-B").unwrap().connected);
+        assert!(!topology.node("hop-D").unwrap().connected);
+        assert_eq!(topology.links.len(), 4);
+
+        let far = topology.shortest_path("far").unwrap();
+        assert_eq!(far.hops, 3);
+        assert_eq!(far.burrows, ["me", "hop-B", "far"]);
+        assert_eq!(topology.shortest_path("hop-C").unwrap().hops, 1);
+        assert_eq!(topology.shortest_path("me").unwrap().hops, 0);
+        // hop-D is not connected, so neither it nor its route counts.
+        assert!(topology.shortest_path("hop-D").is_none());
+        assert!(topology.shortest_path("lost").is_none());
+
+        // Two paths as short: the one through the fresher hop wins.
+        let mut tied = topology.clone();
+        tied.links.push(TopologyLink {
+            from: "hop-C".into(),
+            to: "far".into(),
+            hops: 2,
+        });
+        assert_eq!(tied.shortest_path("far").unwrap().burrows[1], "hop-C");
+    }
 }
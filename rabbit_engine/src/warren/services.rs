@@ -0,0 +1,196 @@
+//! Services a burrow offers other warrens, and discovery of theirs.
+//!
+//! A burrow declares each service it offers under a name, with the
+//! selector that serves it and the capabilities a caller needs there.
+//! The declarations travel in the body of every `FED-LINK-OPEN` and
+//! `FED-LINK-ACCEPT` on its federation links, one
+//! `<name>\t<selector>\t<capability>,…` line per service:
+//!
+//! ```text
+//! FED-LINK-OPEN
+//! Txn: fed-link-…
+//! Heartbeat: 30
+//! Length: 42
+//! End:
+//! dialogue /q/dialogue Subscribe,Publish
+//! ```
+//!
+//! Each end replaces what it holds for the link with the list in every
+//! open and accept it receives, so a service declared or withdrawn
+//! reaches the other warren with the next heartbeat, and a closed link
+//! takes its services with it.  See
+//! [`crate::warren::federation::FederationManager::find_service`].
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::protocol::error::ProtocolError;
+use crate::security::permissions::Capability;
+
+/// Services taken from one link's declarations.
+pub const MAX_SERVICES: usize = 64;
+
+/// A named service and where it is served.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Service {
+    /// The name other warrens look it up by, e.g. `dialogue`.
+    pub name: String,
+    /// The selector that serves it, e.g. `/q/dialogue`.
+    pub selector: String,
+    /// Capabilities a caller needs at the selector.
+    pub requires: Vec<Capability>,
+}
+
+impl Service {
+    /// A service named `name` at `selector`, requiring `requires`.
+    pub fn new(name: &str, selector: &str, requires: Vec<Capability>) -> Self {
+        Self {
+            name: name.to_string(),
+            selector: selector.to_string(),
+            requires,
+        }
+    }
+
+    /// Fails with `BadRequest` unless the name is a single word and the
+    /// selector absolute.
+    fn check(&self) -> Result<(), ProtocolError> {
+        if self.name.is_empty() || self.name.contains(char::is_whitespace) {
+            return Err(ProtocolError::BadRequest(format!(
+                "service name {:?} must be one word",
+                self.name
+            )));
+        }
+        if !self.selector.starts_with('/') || self.selector.contains(char::is_whitespace) {
+            return Err(ProtocolError::BadRequest(format!(
+                "service {} selector {:?} must be an absolute path",
+                self.name, self.selector
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The services this burrow declares, by name.
+#[derive(Debug, Default)]
+pub struct ServiceRegistry {
+    services: Mutex<BTreeMap<String, Service>>,
+}
+
+impl ServiceRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare `service`, in place of any declared under its name.
+    /// Fails with `BadRequest` if its name or selector would not
+    /// survive the trip over a link.
+    pub fn declare(&self, service: Service) -> Result<(), ProtocolError> {
+        service.check()?;
+        self.services
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(service.name.clone(), service);
+        Ok(())
+    }
+
+    /// Withdraw the service named `name`.  Returns whether it was
+    /// declared.
+    pub fn withdraw(&self, name: &str) -> bool {
+        self.services
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(name)
+            .is_some()
+    }
+
+    /// Every declared service, sorted by name.
+    pub fn list(&self) -> Vec<Service> {
+        self.services
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// How many services are declared.
+    pub fn len(&self) -> usize {
+        self.services
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Whether no services are declared.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The declaration lines for `services`.
+pub fn service_lines(services: &[Service]) -> String {
+    services
+        .iter()
+        .map(|s| {
+            let requires: Vec<&str> = s.requires.iter().map(Capability::label).collect();
+            format!("{}\t{}\t{}\n", s.name, s.selector, requires.join(","))
+        })
+        .collect()
+}
+
+/// The services declared in a link frame's body, at most
+/// [`MAX_SERVICES`].  Lines that are malformed or require a capability
+/// this burrow does not know are skipped.
+pub fn parse_services(body: &str) -> Vec<Service> {
+    body.lines()
+        .filter_map(|line| {
+            let mut parts = line.split('\t');
+            let name = parts.next()?;
+            let selector = parts.next()?;
+            let requires = parts
+                .next()
+                .unwrap_or("")
+                .split(',')
+                .filter(|label| !label.is_empty())
+                .map(Capability::from_label)
+                .collect::<Option<Vec<_>>>()?;
+            let service = Service::new(name, selector, requires);
+            service.check().ok()?;
+            Some(service)
+        })
+        .take(MAX_SERVICES)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declarations_round_trip_and_bad_lines_are_skipped() {
+        let registry = ServiceRegistry::new();
+        let requires = vec![Capability::Subscribe, Capability::Publish];
+        registry
+            .declare(Service::new("dialogue", "/q/dialogue", requires))
+            .unwrap();
+        registry
+            .declare(Service::new("archive", "/0/archive", Vec::new()))
+            .unwrap();
+        assert!(registry
+            .declare(Service::new("two words", "/q/x", Vec::new()))
+            .is_err());
+        assert!(registry
+            .declare(Service::new("relative", "q/x", Vec::new()))
+            .is_err());
+
+        let body = service_lines(&registry.list());
+        assert_eq!(parse_services(&body), registry.list());
+        let body = format!("{}oddity\t/q/odd\tTeleport\nnoselector\n", body);
+        assert_eq!(parse_services(&body).len(), 2);
+
+        assert!(registry.withdraw("archive"));
+        assert!(!registry.withdraw("archive"));
+        assert_eq!(registry.len(), 1);
+    }
+}
@@ -0,0 +1,369 @@
+//! Declarative warren topologies.
+//!
+//! A topology file describes a whole warren: which burrows exist,
+//! their role and port, which burrows dial which, the capability
+//! grants they hand each other, and which peers each one treats as a
+//! federation anchor.  The `rabbit-warren` launcher reads it, starts
+//! every burrow, and checks that each declared link comes up.
+//!
+//! ```toml
+//! [[burrow]]
+//! name = "hub"
+//! port = 7443
+//! config = "burrow-0/config.toml"   # optional, relative to this file
+//!
+//! [[burrow]]
+//! name = "leaf"
+//! role = "headed"
+//! port = 7444
+//! connect = ["hub"]
+//! anchors = ["hub"]
+//!
+//! [[burrow.grants]]
+//! to = "hub"
+//! capability = "Publish"
+//! ttl = 3600
+//! ```
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::protocol::error::ProtocolError;
+use crate::security::permissions::Capability;
+
+/// Whether a burrow serves an interactive UI or runs as pure
+/// infrastructure (spec §1.6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BurrowRole {
+    /// Serves UI declarations for interactive use (GUI enabled).
+    Headed,
+    /// Pure infrastructure, no UI.
+    #[default]
+    Headless,
+}
+
+impl BurrowRole {
+    /// Return the label used in topology files.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Headed => "headed",
+            Self::Headless => "headless",
+        }
+    }
+}
+
+/// A capability grant from one burrow to another.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TopologyGrant {
+    /// Name of the burrow receiving the capability.
+    pub to: String,
+    /// Capability label (e.g. `"Publish"`).
+    pub capability: String,
+    /// Grant lifetime in seconds (default 86400).
+    #[serde(default = "default_grant_ttl")]
+    pub ttl: u64,
+}
+
+fn default_grant_ttl() -> u64 {
+    86400
+}
+
+/// One burrow in a topology.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TopologyBurrow {
+    /// Unique name; also the display name unless `config` sets one.
+    pub name: String,
+    /// Headed or headless (default headless).
+    #[serde(default)]
+    pub role: BurrowRole,
+    /// Listening port (0 = pick a free port).
+    #[serde(default)]
+    pub port: u16,
+    /// Optional config file, relative to the topology file.
+    #[serde(default)]
+    pub config: Option<PathBuf>,
+    /// Names of burrows this one dials after startup.
+    #[serde(default)]
+    pub connect: Vec<String>,
+    /// Capabilities this burrow grants to other burrows.
+    #[serde(default)]
+    pub grants: Vec<TopologyGrant>,
+    /// Burrows this one trusts as federation anchors.  Each anchor is
+    /// registered with the burrow's federation and granted the
+    /// `Federation` capability.
+    #[serde(default)]
+    pub anchors: Vec<String>,
+}
+
+/// A complete warren description.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Topology {
+    /// The burrows, in launch order.
+    #[serde(rename = "burrow", default)]
+    pub burrows: Vec<TopologyBurrow>,
+    /// Directory the topology was loaded from (for resolving
+    /// `config` paths).
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+impl Topology {
+    /// Build the classic star warren: a root burrow on `base_port`
+    /// and `count - 1` children on the following ports, each dialing
+    /// the root.
+    pub fn star(count: usize, base_port: u16) -> Self {
+        let burrows = (0..count)
+            .map(|i| TopologyBurrow {
+                name: if i == 0 {
+                    "warren-root".to_string()
+                } else {
+                    format!("burrow-{}", i)
+                },
+                role: BurrowRole::Headless,
+                port: base_port + i as u16,
+                config: None,
+                connect: if i == 0 {
+                    Vec::new()
+                } else {
+                    vec!["warren-root".to_string()]
+                },
+                grants: Vec::new(),
+                anchors: Vec::new(),
+            })
+            .collect();
+        Self {
+            burrows,
+            base_dir: PathBuf::from("."),
+        }
+    }
+
+    /// Load and validate a topology file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProtocolError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ProtocolError::InternalError(format!(
+                "failed to read topology {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let mut topology = Self::parse(&content)?;
+        topology.base_dir = path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("."));
+        Ok(topology)
+    }
+
+    /// Parse and validate a topology from a TOML string.
+    pub fn parse(toml_str: &str) -> Result<Self, ProtocolError> {
+        let topology: Self = toml::from_str(toml_str)
+            .map_err(|e| ProtocolError::InternalError(format!("invalid topology TOML: {}", e)))?;
+        topology.validate()?;
+        Ok(topology)
+    }
+
+    /// Check names, references, ports and capability labels.
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        if self.burrows.is_empty() {
+            return Err(ProtocolError::InternalError(
+                "topology declares no burrows".into(),
+            ));
+        }
+
+        let mut names = HashSet::new();
+        let mut ports = HashSet::new();
+        for b in &self.burrows {
+            if b.name.trim().is_empty() {
+                return Err(ProtocolError::InternalError(
+                    "topology burrow with empty name".into(),
+                ));
+            }
+            if !names.insert(b.name.as_str()) {
+                return Err(ProtocolError::InternalError(format!(
+                    "duplicate burrow name {:?}",
+                    b.name
+                )));
+            }
+            if b.port != 0 && !ports.insert(b.port) {
+                return Err(ProtocolError::InternalError(format!(
+                    "port {} is used by more than one burrow",
+                    b.port
+                )));
+            }
+        }
+
+        for b in &self.burrows {
+            let targets = b
+                .connect
+                .iter()
+                .map(|t| ("connect", t))
+                .chain(b.anchors.iter().map(|t| ("anchors", t)))
+                .chain(b.grants.iter().map(|g| ("grants", &g.to)));
+            for (field, target) in targets {
+                if !names.contains(target.as_str()) {
+                    return Err(ProtocolError::InternalError(format!(
+                        "{}.{} refers to unknown burrow {:?}",
+                        b.name, field, target
+                    )));
+                }
+                if target == &b.name {
+                    return Err(ProtocolError::InternalError(format!(
+                        "{}.{} refers to itself",
+                        b.name, field
+                    )));
+                }
+            }
+            for grant in &b.grants {
+                if Capability::from_label(&grant.capability).is_none() {
+                    return Err(ProtocolError::InternalError(format!(
+                        "{}.grants: unknown capability {:?}",
+                        b.name, grant.capability
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Look up a burrow's index by name.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.burrows.iter().position(|b| b.name == name)
+    }
+
+    /// All declared links as `(dialer, listener)` index pairs.
+    pub fn links(&self) -> Vec<(usize, usize)> {
+        let mut links = Vec::new();
+        for (i, b) in self.burrows.iter().enumerate() {
+            for target in &b.connect {
+                if let Some(j) = self.index_of(target) {
+                    links.push((i, j));
+                }
+            }
+        }
+        links
+    }
+
+    /// All declared anchors as `(truster, anchor)` index pairs.
+    pub fn anchors(&self) -> Vec<(usize, usize)> {
+        let mut anchors = Vec::new();
+        for (i, b) in self.burrows.iter().enumerate() {
+            for anchor in &b.anchors {
+                if let Some(j) = self.index_of(anchor) {
+                    anchors.push((i, j));
+                }
+            }
+        }
+        anchors
+    }
+
+    /// Resolve a burrow's config path against the topology directory.
+    pub fn config_path(&self, burrow: &TopologyBurrow) -> Option<PathBuf> {
+        burrow.config.as_ref().map(|p| self.base_dir.join(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+[[burrow]]
+name = "hub"
+port = 7443
+
+[[burrow]]
+name = "leaf"
+role = "headed"
+port = 7444
+config = "leaf/config.toml"
+connect = ["hub"]
+anchors = ["hub"]
+
+[[burrow.grants]]
+to = "hub"
+capability = "Publish"
+"#;
+
+    #[test]
+    fn parse_sample() {
+        let t = Topology::parse(SAMPLE).unwrap();
+        assert_eq!(t.burrows.len(), 2);
+        assert_eq!(t.burrows[0].role, BurrowRole::Headless);
+        assert_eq!(t.burrows[1].role, BurrowRole::Headed);
+        assert_eq!(t.burrows[1].grants[0].ttl, 86400);
+        assert_eq!(t.links(), vec![(1, 0)]);
+        assert_eq!(t.anchors(), vec![(1, 0)]);
+        assert_eq!(t.index_of("leaf"), Some(1));
+    }
+
+    #[test]
+    fn config_path_relative_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("topology.toml");
+        std::fs::write(&path, SAMPLE).unwrap();
+        let t = Topology::load(&path).unwrap();
+        assert_eq!(
+            t.config_path(&t.burrows[1]),
+            Some(dir.path().join("leaf/config.toml"))
+        );
+        assert_eq!(t.config_path(&t.burrows[0]), None);
+    }
+
+    #[test]
+    fn star_matches_classic_layout() {
+        let t = Topology::star(3, 9000);
+        t.validate().unwrap();
+        assert_eq!(t.burrows[0].name, "warren-root");
+        assert_eq!(t.burrows[2].port, 9002);
+        assert_eq!(t.links(), vec![(1, 0), (2, 0)]);
+    }
+
+    #[test]
+    fn unknown_reference_rejected() {
+        let toml = r#"
+[[burrow]]
+name = "a"
+connect = ["ghost"]
+"#;
+        assert!(Topology::parse(toml).is_err());
+    }
+
+    #[test]
+    fn duplicate_port_rejected() {
+        let toml = r#"
+[[burrow]]
+name = "a"
+port = 7000
+
+[[burrow]]
+name = "b"
+port = 7000
+"#;
+        assert!(Topology::parse(toml).is_err());
+    }
+
+    #[test]
+    fn bad_capability_rejected() {
+        let toml = r#"
+[[burrow]]
+name = "a"
+
+[[burrow]]
+name = "b"
+
+[[burrow.grants]]
+to = "a"
+capability = "Root"
+"#;
+        assert!(Topology::parse(toml).is_err());
+    }
+
+    #[test]
+    fn empty_topology_rejected() {
+        assert!(Topology::parse("").is_err());
+    }
+}
@@ -219,3 +219,301 @@ async fn dispatch_content_round_trip_over_memory_tunnel() {
     assert!(body.contains("1Docs"));
     assert!(body.ends_with(".\r\n"));
 }
+
+#[tokio::test]
+async fn registered_handlers_answer_new_verbs() {
+    use std::sync::Arc;
+
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::dispatch::handlers::FrameHandler;
+    use rabbit_engine::dispatch::router::DispatchResult;
+    use rabbit_engine::security::permissions::Capability;
+    use rabbit_engine::transport::memory::memory_tunnel_pair;
+    use rabbit_engine::transport::tunnel::Tunnel;
+
+    struct Shutdown;
+    impl FrameHandler for Shutdown {
+        fn handle(&self, _: &Frame, _: &str) -> DispatchResult {
+            DispatchResult::single(Frame::new("200 OK"))
+        }
+        fn capability(&self) -> Option<Capability> {
+            Some(Capability::ManageBurrows)
+        }
+    }
+
+    let server = Arc::new(Burrow::in_memory("server"));
+    let whoami = |_: &Frame, peer_id: &str| {
+        let mut reply = Frame::new("200 OK");
+        reply.set_body(peer_id);
+        DispatchResult::single(reply)
+    };
+    server.register_handler("WHOAMI", Arc::new(whoami)).unwrap();
+    server
+        .register_handler("SHUTDOWN", Arc::new(Shutdown))
+        .unwrap();
+    assert!(server.register_handler("LIST", Arc::new(whoami)).is_err());
+
+    let client = Burrow::in_memory("client");
+    let (mut near, mut far) = memory_tunnel_pair("client", "server");
+    let srv = Arc::clone(&server);
+    let serving = tokio::spawn(async move { srv.handle_tunnel(&mut far).await });
+    client.client_handshake(&mut near).await.unwrap();
+
+    let mut req = Frame::new("WHOAMI");
+    req.set_header("Lane", "1");
+    req.set_header("Txn", "T-9");
+    near.send_frame(&req).await.unwrap();
+    let resp = near.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.verb, "200");
+    assert_eq!(resp.body.as_deref(), Some(client.burrow_id().as_str()));
+    assert_eq!(resp.header("Lane"), Some("1"));
+    assert_eq!(resp.header("Txn"), Some("T-9"));
+
+    near.send_frame(&Frame::new("SHUTDOWN")).await.unwrap();
+    assert_eq!(near.recv_frame().await.unwrap().unwrap().verb, "403");
+
+    near.close().await.unwrap();
+    drop(near);
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn list_serves_anchors_and_trusted_peers() {
+    use std::sync::Arc;
+
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::security::identity::Identity;
+    use rabbit_engine::security::manifest::TrustManifest;
+    use rabbit_engine::transport::memory::memory_tunnel_pair;
+    use rabbit_engine::transport::tunnel::Tunnel;
+
+    let server = Arc::new(Burrow::in_memory("server"));
+    let anchor = Identity::generate();
+    let manifest = TrustManifest::sign(&anchor, 3, &[], &[], &[], 3600);
+    server.trust.lock().unwrap().add_manifest(manifest).unwrap();
+
+    let client = Burrow::in_memory("client");
+    let (mut near, mut far) = memory_tunnel_pair("client", "server");
+    let srv = Arc::clone(&server);
+    let serving = tokio::spawn(async move { srv.handle_tunnel(&mut far).await });
+    client.client_handshake(&mut near).await.unwrap();
+
+    let mut req = Frame::with_args("LIST", vec!["/anchors".into()]);
+    req.set_header("Lane", "2");
+    near.send_frame(&req).await.unwrap();
+    let resp = near.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.args, vec!["MENU"]);
+    assert_eq!(resp.header("Lane"), Some("2"));
+    let body = resp.body.unwrap();
+    assert!(body.contains(&anchor.burrow_id()) && body.contains("serial 3"));
+
+    let req = Frame::with_args("LIST", vec!["/trusted".into()]);
+    near.send_frame(&req).await.unwrap();
+    let resp = near.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.args, vec!["MENU"]);
+    assert!(resp.body.unwrap().contains(&client.burrow_id()));
+
+    near.close().await.unwrap();
+    drop(near);
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn providers_serve_files_and_generated_content() {
+    use std::sync::Arc;
+
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::config::Config;
+    use rabbit_engine::content::store::ContentEntry;
+    use rabbit_engine::protocol::chunk::ChunkAssembler;
+    use rabbit_engine::transport::memory::memory_tunnel_pair;
+    use rabbit_engine::transport::tunnel::Tunnel;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("public/notes")).unwrap();
+    let long = "rabbit ".repeat(100);
+    std::fs::write(dir.path().join("public/notes/long.txt"), &long).unwrap();
+    let config = Config::parse(
+        r#"
+[[content.files]]
+selector = "/files/"
+root = "public"
+"#,
+    )
+    .unwrap();
+    let server = Arc::new(Burrow::from_config(&config, dir.path()).unwrap());
+    server
+        .register_provider(
+            "/about",
+            Arc::new(|selector: &str| {
+                Ok(ContentEntry::Text(format!("you asked for {}", selector)))
+            }),
+        )
+        .unwrap();
+
+    let client = Burrow::in_memory("client");
+    let (mut near, mut far) = memory_tunnel_pair("client", "server");
+    let srv = Arc::clone(&server);
+    let serving = tokio::spawn(async move { srv.handle_tunnel(&mut far).await });
+    client.client_handshake(&mut near).await.unwrap();
+
+    near.send_frame(&Frame::with_args("LIST", vec!["/files/notes".into()]))
+        .await
+        .unwrap();
+    let menu = near.recv_frame().await.unwrap().unwrap();
+    assert_eq!(menu.args, vec!["MENU"]);
+    assert!(menu
+        .body
+        .unwrap()
+        .contains("0long.txt\t/files/notes/long.txt"));
+
+    let mut fetch = Frame::with_args("FETCH", vec!["/files/notes/long.txt".into()]);
+    fetch.set_header("Txn", "F-1");
+    fetch.set_header("Chunk-Size", "256");
+    near.send_frame(&fetch).await.unwrap();
+    let mut assembler = ChunkAssembler::new();
+    let content = loop {
+        let frame = near.recv_frame().await.unwrap().unwrap();
+        if let Some(whole) = assembler.feed(frame).unwrap() {
+            break whole;
+        }
+    };
+    assert_eq!(content.header("View"), Some("text/plain"));
+    assert_eq!(content.body.as_deref(), Some(long.as_str()));
+
+    near.send_frame(&Frame::with_args("FETCH", vec!["/about/team".into()]))
+        .await
+        .unwrap();
+    let about = near.recv_frame().await.unwrap().unwrap();
+    assert_eq!(about.body.as_deref(), Some("you asked for /about/team"));
+
+    near.send_frame(&Frame::with_args("FETCH", vec!["/files/../secret".into()]))
+        .await
+        .unwrap();
+    assert_eq!(near.recv_frame().await.unwrap().unwrap().verb, "403");
+
+    near.close().await.unwrap();
+    drop(near);
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn unsubscribe_and_replay_since_a_time() {
+    use rabbit_engine::events::continuity::ContinuityStore;
+
+    let dir = tempfile::tempdir().unwrap();
+    let continuity = ContinuityStore::new(dir.path()).unwrap();
+    let (cs, ee) = make_subsystems();
+    let d = Dispatcher::new(&cs, &ee).with_continuity(&continuity);
+
+    let subscribe = |since: Option<&str>| {
+        let mut sub = Frame::with_args("SUBSCRIBE", vec!["/q/news".into()]);
+        sub.set_header("Lane", "2");
+        if let Some(since) = since {
+            sub.set_header("Since", since);
+        }
+        sub
+    };
+    let mut publish = Frame::with_args("PUBLISH", vec!["/q/news".into()]);
+    publish.set_body("extra!");
+
+    d.dispatch(&subscribe(None), "alice").await;
+    assert_eq!(d.dispatch(&publish, "bob").await.broadcast.len(), 1);
+
+    let mut unsub = Frame::with_args("UNSUBSCRIBE", vec!["/q/news".into()]);
+    unsub.set_header("Txn", "U-1");
+    let result = d.dispatch(&unsub, "alice").await;
+    assert_eq!(result.response.verb, "204");
+    assert_eq!(result.response.header("Txn"), Some("U-1"));
+    assert!(d.dispatch(&publish, "bob").await.broadcast.is_empty());
+
+    // Both events were logged just now: a time in the past replays
+    // them, one in the future none.
+    let past = d
+        .dispatch(&subscribe(Some("2020-01-01T00:00:00Z")), "carol")
+        .await;
+    assert_eq!(past.extras.len(), 2);
+    assert_eq!(past.extras[0].header("Lane"), Some("2"));
+    let future = d
+        .dispatch(&subscribe(Some("2999-01-01T00:00:00Z")), "dave")
+        .await;
+    assert!(future.extras.is_empty());
+
+    let bad = d.dispatch(&subscribe(Some("last tuesday")), "erin").await;
+    assert_eq!(bad.response.verb, "400");
+}
+
+#[tokio::test]
+async fn events_beyond_the_memory_limit_are_replayed_from_the_log() {
+    use futures_util::StreamExt;
+    use rabbit_engine::events::continuity::ContinuityStore;
+
+    let dir = tempfile::tempdir().unwrap();
+    let continuity = ContinuityStore::new(dir.path()).unwrap();
+    let (cs, _) = make_subsystems();
+    let ee = EventEngine::new().with_memory_limit(3);
+    let d = Dispatcher::new(&cs, &ee).with_continuity(&continuity);
+
+    for n in 1..=10 {
+        let mut publish = Frame::with_args("PUBLISH", vec!["/q/news".into()]);
+        publish.set_body(format!("story {}", n));
+        d.dispatch(&publish, "bob").await;
+    }
+    assert_eq!(ee.event_count("/q/news"), 3);
+
+    let mut sub = Frame::with_args("SUBSCRIBE", vec!["/q/news".into()]);
+    sub.set_header("Since", "4");
+    let result = d.dispatch(&sub, "alice").await;
+    assert!(result.extras.is_empty());
+    let replay = result.replay.expect("replayed from the log");
+    let replayed: Vec<Frame> = replay.frames.collect().await;
+    let seqs: Vec<&str> = replayed.iter().filter_map(|f| f.header("Seq")).collect();
+    assert_eq!(seqs, ["5", "6", "7", "8", "9", "10"]);
+    assert_eq!(replayed[0].body.as_deref(), Some("story 5"));
+}
+
+#[tokio::test]
+async fn topics_are_listed_and_matched_by_pattern() {
+    use rabbit_engine::events::continuity::ContinuityStore;
+
+    let dir = tempfile::tempdir().unwrap();
+    let continuity = ContinuityStore::new(dir.path()).unwrap();
+    let (cs, ee) = make_subsystems();
+    let d = Dispatcher::new(&cs, &ee).with_continuity(&continuity);
+
+    let mut sub = Frame::with_args("SUBSCRIBE", vec!["/q/dialogue/*".into()]);
+    sub.set_header("Lane", "7");
+    assert_eq!(d.dispatch(&sub, "alice").await.response.verb, "201");
+    for topic in [
+        "/q/dialogue/bob",
+        "/q/dialogue/carol",
+        "/q/news",
+        "/q/dialogue/bob",
+    ] {
+        let mut publish = Frame::with_args("PUBLISH", vec![topic.into()]);
+        publish.set_body("hello");
+        let result = d.dispatch(&publish, "bob").await;
+        let to_alice = result.broadcast.iter().any(|(peer, _)| peer == "alice");
+        assert_eq!(to_alice, topic.starts_with("/q/dialogue/"), "{topic}");
+    }
+    let mut publish = Frame::with_args("PUBLISH", vec!["/q/dialogue/*".into()]);
+    publish.set_body("everyone");
+    assert_eq!(d.dispatch(&publish, "bob").await.response.verb, "400");
+
+    let list = Frame::with_args("LIST", vec!["/q".into()]);
+    let body = d.dispatch(&list, "alice").await.response.body.unwrap();
+    assert_eq!(body.matches("\tq").count(), 0);
+    assert_eq!(body.lines().filter(|l| l.starts_with('q')).count(), 3);
+    assert!(body.contains("/q/dialogue/bob \u{2014} 2 events, seq 1\u{2013}2"));
+
+    let list = Frame::with_args("LIST", vec!["/q/dialogue/*".into()]);
+    let body = d.dispatch(&list, "alice").await.response.body.unwrap();
+    assert!(body.contains("\t/q/dialogue/carol\t"));
+    assert!(!body.contains("/q/news"));
+
+    let topics = continuity.list_topics().unwrap();
+    let names: Vec<&str> = topics.iter().map(|t| t.topic.as_str()).collect();
+    assert_eq!(names, ["/q/dialogue/bob", "/q/dialogue/carol", "/q/news"]);
+    assert_eq!((topics[0].events, topics[0].last_seq), (2, 2));
+    assert!(topics[0].bytes > 0 && topics[0].last_write > 0);
+}
@@ -83,6 +83,7 @@ fn continuity_persist_and_reload() {
     }
 
     // Simulate restart — create a new store over the same directory
+    drop(store);
     let store2 = ContinuityStore::new(dir.path().join("events")).unwrap();
     let events = store2.load("/q/chat").unwrap();
     assert_eq!(events.len(), 5);
@@ -291,7 +291,7 @@ async fn cross_tunnel_replay_for_late_subscriber() {
         .unwrap()
         .unwrap();
     assert_eq!(r1.verb, "EVENT");
-    assert_eq!(r1.header("Seq"), Some("2"));
+    assert_eq!(r1.header("Event-Seq"), Some("2"));
 
     let r2 = tokio::time::timeout(Duration::from_secs(2), carol.recv_frame())
         .await
@@ -299,7 +299,7 @@ async fn cross_tunnel_replay_for_late_subscriber() {
         .unwrap()
         .unwrap();
     assert_eq!(r2.verb, "EVENT");
-    assert_eq!(r2.header("Seq"), Some("3"));
+    assert_eq!(r2.header("Event-Seq"), Some("3"));
 
     // Clean up.
     alice.close().await.unwrap();
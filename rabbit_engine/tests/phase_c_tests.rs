@@ -138,7 +138,7 @@ async fn handshake_timeout() {
     );
 }
 
-// ── Max frame size: oversized body gets 400 ────────────────────
+// ── Max frame size: oversized body gets 413 ────────────────────
 
 #[tokio::test]
 async fn max_frame_size_rejected() {
@@ -155,15 +155,15 @@ async fn max_frame_size_rejected() {
     big.set_body(&big_body);
     client.send_frame(&big).await.unwrap();
 
-    // Server should respond with a 400 error.
+    // Server should respond with a 413 error.
     let resp = tokio::time::timeout(Duration::from_secs(2), client.recv_frame())
         .await
         .expect("timed out waiting for error response")
         .unwrap()
         .unwrap();
     assert!(
-        resp.verb.starts_with("400"),
-        "expected 400 response, got: {}",
+        resp.verb.starts_with("413"),
+        "expected 413 response, got: {}",
         resp.verb
     );
 
@@ -2,6 +2,7 @@
 //!
 //! Tests cover:
 //! - DELEGATE: admin grants, non-admin rejection, unknown cap, missing args
+//! - GROUP: signed membership changes and group-addressed grants
 //! - OFFER: peer table merge, bidirectional exchange, partial lines
 //! - Dispatcher-level tests avoid Tunnel dyn-compat issues.
 
@@ -11,6 +12,9 @@ use rabbit_engine::content::store::ContentStore;
 use rabbit_engine::dispatch::router::Dispatcher;
 use rabbit_engine::events::engine::EventEngine;
 use rabbit_engine::protocol::frame::Frame;
+use rabbit_engine::security::auth::{stamp_frame, ReplayCache};
+use rabbit_engine::security::groups::{group_subject, GroupChange, GroupOp};
+use rabbit_engine::security::identity::Identity;
 use rabbit_engine::security::permissions::{Capability, CapabilityManager};
 use rabbit_engine::warren::peers::{PeerInfo, PeerTable};
 
@@ -37,6 +41,7 @@ async fn delegate_admin_grants_capability() {
     let peers = PeerTable::new();
     let mut caps = CapabilityManager::new();
     caps.grant("admin", Capability::ManageBurrows, 3600);
+    caps.grant("admin", Capability::Subscribe, 3600);
     let caps = Mutex::new(caps);
 
     let d = make_delegate_dispatcher(&cs, &ee, &caps, &peers);
@@ -64,6 +69,7 @@ async fn delegate_produces_broadcast_grant_frame() {
     let peers = PeerTable::new();
     let mut caps = CapabilityManager::new();
     caps.grant("admin", Capability::ManageBurrows, 3600);
+    caps.grant("admin", Capability::Publish, 3600);
     let caps = Mutex::new(caps);
 
     let d = make_delegate_dispatcher(&cs, &ee, &caps, &peers);
@@ -88,6 +94,7 @@ async fn delegate_default_ttl_is_3600() {
     let peers = PeerTable::new();
     let mut caps = CapabilityManager::new();
     caps.grant("admin", Capability::ManageBurrows, 3600);
+    caps.grant("admin", Capability::Fetch, 3600);
     let caps = Mutex::new(caps);
 
     let d = make_delegate_dispatcher(&cs, &ee, &caps, &peers);
@@ -114,6 +121,68 @@ async fn delegate_non_admin_rejected() {
     assert_eq!(result.response.verb, "403");
 }
 
+#[tokio::test]
+async fn delegate_only_capabilities_the_delegator_holds() {
+    let cs = ContentStore::new();
+    let ee = EventEngine::new();
+    let peers = PeerTable::new();
+    let mut caps = CapabilityManager::new();
+    caps.grant("admin", Capability::ManageBurrows, 3600);
+    caps.grant("admin", Capability::Fetch, 3600);
+    caps.grant("warden", Capability::ManageWarren, 3600);
+    caps.grant("warden", Capability::List, 3600);
+    let caps = Mutex::new(caps);
+
+    let d = make_delegate_dispatcher(&cs, &ee, &caps, &peers);
+
+    let frame = Frame::with_args("DELEGATE", vec!["Publish".into(), "target".into()]);
+    let result = d.dispatch(&frame, "admin").await;
+    assert_eq!(result.response.verb, "403");
+    assert!(result.broadcast.is_empty());
+    assert!(!caps.lock().unwrap().check("target", Capability::Publish));
+
+    // ManageWarren also allows delegating.
+    let frame = Frame::with_args("DELEGATE", vec!["List".into(), "target".into()]);
+    let result = d.dispatch(&frame, "warden").await;
+    assert_eq!(result.response.verb, "200");
+    assert!(caps.lock().unwrap().check("target", Capability::List));
+}
+
+#[tokio::test]
+async fn delegate_scoped_grant() {
+    let cs = ContentStore::new();
+    let ee = EventEngine::new();
+    let peers = PeerTable::new();
+    let mut caps = CapabilityManager::new();
+    caps.grant("admin", Capability::ManageBurrows, 3600);
+    caps.grant_scoped("admin", Capability::Publish, "/q/**", 3600);
+    let caps = Mutex::new(caps);
+
+    let d = make_delegate_dispatcher(&cs, &ee, &caps, &peers);
+
+    // The admin cannot delegate Publish everywhere, or outside /q.
+    let mut frame = Frame::with_args("DELEGATE", vec!["Publish".into(), "target".into()]);
+    assert_eq!(d.dispatch(&frame, "admin").await.response.verb, "403");
+    frame.set_header("Scope", "/files/**");
+    assert_eq!(d.dispatch(&frame, "admin").await.response.verb, "403");
+
+    frame.set_header("Scope", "/q/dialogue/*");
+    let result = d.dispatch(&frame, "admin").await;
+    assert_eq!(result.response.verb, "200");
+    assert_eq!(result.response.header("Scope"), Some("/q/dialogue/*"));
+    assert_eq!(result.broadcast[0].1.header("Scope"), Some("/q/dialogue/*"));
+
+    let publish = |topic: &str| {
+        let mut frame = Frame::with_args("PUBLISH", vec![topic.into()]);
+        frame.set_body("hi");
+        frame
+    };
+    let result = d.dispatch(&publish("/q/dialogue/7"), "target").await;
+    assert_eq!(result.response.verb, "204");
+    let result = d.dispatch(&publish("/q/news"), "target").await;
+    assert_eq!(result.response.verb, "403");
+}
+
 #[tokio::test]
 async fn delegate_unknown_capability_returns_400() {
     let cs = ContentStore::new();
@@ -169,6 +238,7 @@ async fn delegate_echoes_lane_and_txn() {
     let peers = PeerTable::new();
     let mut caps = CapabilityManager::new();
     caps.grant("admin", Capability::ManageBurrows, 3600);
+    caps.grant("admin", Capability::List, 3600);
     let caps = Mutex::new(caps);
 
     let d = make_delegate_dispatcher(&cs, &ee, &caps, &peers);
@@ -181,6 +251,72 @@ async fn delegate_echoes_lane_and_txn() {
     assert_eq!(result.response.header("Txn"), Some("t42"));
 }
 
+#[tokio::test]
+async fn delegate_with_replay_cache_needs_a_fresh_stamp() {
+    let cs = ContentStore::new();
+    let ee = EventEngine::new();
+    let peers = PeerTable::new();
+    let mut caps = CapabilityManager::new();
+    caps.grant("admin", Capability::ManageBurrows, 3600);
+    caps.grant("admin", Capability::Fetch, 3600);
+    let caps = Mutex::new(caps);
+    let replay = ReplayCache::new(60);
+
+    let d = make_delegate_dispatcher(&cs, &ee, &caps, &peers).with_replay_cache(&replay);
+
+    let mut frame = Frame::with_args("DELEGATE", vec!["Fetch".into(), "peer-z".into()]);
+    let result = d.dispatch(&frame, "admin").await;
+    assert_eq!(result.response.verb, "400");
+
+    stamp_frame(&mut frame);
+    let result = d.dispatch(&frame, "admin").await;
+    assert_eq!(result.response.verb, "200");
+
+    // The same frame, captured and sent again.
+    let result = d.dispatch(&frame, "admin").await;
+    assert_eq!(result.response.verb, "403");
+}
+
+// ── GROUP tests ────────────────────────────────────────────────
+
+#[tokio::test]
+async fn group_change_extends_group_grants() {
+    let cs = ContentStore::new();
+    let ee = EventEngine::new();
+    let peers = PeerTable::new();
+    let admin = Identity::generate();
+    let mut caps = CapabilityManager::new();
+    caps.grant(&admin.burrow_id(), Capability::ManageBurrows, 3600);
+    caps.grant(&group_subject("oak-family"), Capability::Fetch, 3600);
+    let caps = Mutex::new(caps);
+    let replay = ReplayCache::new(60);
+
+    let d = make_delegate_dispatcher(&cs, &ee, &caps, &peers).with_replay_cache(&replay);
+    let fetch = Frame::with_args("FETCH", vec!["/".into()]);
+    assert_eq!(d.dispatch(&fetch, "kid").await.response.verb, "403");
+
+    // Relayed by some other peer; the signature is what counts.
+    let add = GroupChange::sign(&admin, GroupOp::Add, "oak-family", "kid").to_frame();
+    let result = d.dispatch(&add, "relay").await;
+    assert_eq!(result.response.verb, "200");
+    assert_eq!(result.response.header("Changed"), Some("true"));
+    assert_ne!(d.dispatch(&fetch, "kid").await.response.verb, "403");
+    assert_eq!(d.dispatch(&add, "relay").await.response.verb, "403");
+
+    // Tampered, or signed by someone without ManageBurrows.
+    let mut forged = add.clone();
+    forged.args[2] = "mallory".into();
+    assert_eq!(d.dispatch(&forged, "relay").await.response.verb, "403");
+    let outsider = Identity::generate();
+    let frame = GroupChange::sign(&outsider, GroupOp::Add, "oak-family", "mallory").to_frame();
+    assert_eq!(d.dispatch(&frame, "relay").await.response.verb, "403");
+    assert_eq!(d.dispatch(&fetch, "mallory").await.response.verb, "403");
+
+    let remove = GroupChange::sign(&admin, GroupOp::Remove, "oak-family", "kid").to_frame();
+    assert_eq!(d.dispatch(&remove, "relay").await.response.verb, "200");
+    assert_eq!(d.dispatch(&fetch, "kid").await.response.verb, "403");
+}
+
 // ── OFFER tests ────────────────────────────────────────────────
 
 #[tokio::test]
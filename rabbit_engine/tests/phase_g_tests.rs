@@ -9,13 +9,17 @@
 
 use rabbit_engine::burrow::Burrow;
 use rabbit_engine::content::store::MenuItem;
+use rabbit_engine::protocol::address::{RabbitAddress, Scope};
 use rabbit_engine::protocol::error::ProtocolError;
 use rabbit_engine::protocol::frame::Frame;
+use rabbit_engine::security::permissions::Capability;
 use rabbit_engine::session::{
     load_session_states, save_session_states, SavedLaneState, SavedSessionState,
 };
 use rabbit_engine::transport::memory::memory_tunnel_pair;
 use rabbit_engine::transport::tunnel::Tunnel;
+use rabbit_engine::warren::peers::{PeerHealth, PeerInfo};
+use rabbit_engine::warren::relay::{self, RelayTable};
 use rabbit_engine::warren::routing::RoutingTable;
 
 // ───── G1: Session State Persistence ───────────────────────────────
@@ -181,7 +185,7 @@ async fn connected_pair(
 }
 
 #[tokio::test]
-async fn forwarding_no_route_returns_404() {
+async fn forwarding_no_route_returns_502() {
     let (mut c, sh) = connected_pair("server").await;
 
     // Send a frame targeted at an unknown burrow.
@@ -191,7 +195,8 @@ async fn forwarding_no_route_returns_404() {
     c.send_frame(&f).await.unwrap();
 
     let resp = c.recv_frame().await.unwrap().unwrap();
-    assert_eq!(resp.verb, "404");
+    assert_eq!(resp.verb, "502");
+    assert_eq!(resp.args, vec!["NO-ROUTE"]);
     assert!(resp.body.as_deref().unwrap_or("").contains("no route"));
     // Lane header echoed back.
     assert_eq!(resp.header("Lane"), Some("L1"));
@@ -256,7 +261,7 @@ async fn local_target_dispatched_normally() {
 
 #[tokio::test]
 async fn forwarding_without_hop_count_defaults_to_8() {
-    // No Hop-Count header → defaults to 8; since no route exists → 404.
+    // No Hop-Count header → defaults to 8; since no route exists → 502.
     let (mut c, sh) = connected_pair("server").await;
 
     let mut f = Frame::with_args("FETCH", vec!["/hello".into()]);
@@ -265,8 +270,8 @@ async fn forwarding_without_hop_count_defaults_to_8() {
     c.send_frame(&f).await.unwrap();
 
     let resp = c.recv_frame().await.unwrap().unwrap();
-    // Should be 404, not 400 — hop count defaults to 8 which is > 0.
-    assert_eq!(resp.verb, "404");
+    // Should be 502, not 400 — hop count defaults to 8 which is > 0.
+    assert_eq!(resp.verb, "502");
 
     c.close().await.unwrap();
     sh.await.unwrap().unwrap();
@@ -385,14 +390,11 @@ async fn burrow_routing_table_accessible() {
 }
 
 // ───── G4: Forwarding with a route present ─────────────────────────
-// When a route exists, the frame should be forwarded (broadcast to the
-// next-hop peer) and NOT produce a 404.  We verify this indirectly:
-// because no such peer is actually connected, the broadcast silently
-// drops the frame, and the client gets no error response — a timeout
-// indicates the forward path was taken.
+// A route whose next hop is not connected leads nowhere: the sender
+// learns so with 502 rather than the frame vanishing.
 
 #[tokio::test]
-async fn forwarding_with_route_no_error() {
+async fn forwarding_to_a_disconnected_hop_returns_502() {
     let mut server = Burrow::in_memory("server");
     server.require_auth = false;
     server
@@ -408,30 +410,459 @@ async fn forwarding_with_route_no_error() {
     let sh = tokio::spawn(async move { server.handle_tunnel(&mut s).await });
     client.client_handshake(&mut c).await.unwrap();
 
-    // Frame targeted at the remote burrow — should be forwarded, not 404.
     let mut f = Frame::with_args("FETCH", vec!["/data".into()]);
     f.set_header("Target", "remote-burrow");
     f.set_header("Hop-Count", "5");
+    f.set_header("Txn", "F-1");
     c.send_frame(&f).await.unwrap();
 
-    // The frame was forwarded (broadcast to fake-hop, which is not connected,
-    // so it drops silently). Client should NOT get a 404 error.
-    // Send a local request to verify the connection is still alive.
-    let list = Frame::with_args("LIST", vec!["/".into()]);
-    c.send_frame(&list).await.unwrap();
     let resp = c.recv_frame().await.unwrap().unwrap();
-    // This should be the LIST response, not a 404 from the forwarded frame.
-    assert!(
-        resp.verb.starts_with("200"),
-        "expected 200 LIST response, got: {} {}",
-        resp.verb,
-        resp.args.join(" ")
+    assert_eq!(resp.verb, "502");
+    assert_eq!(resp.header("Txn"), Some("F-1"));
+    assert!(resp.body.unwrap().contains("fake-hop is not connected"));
+
+    c.close().await.unwrap();
+    sh.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn frames_are_relayed_between_peers_and_answers_come_back() {
+    use std::sync::Arc;
+
+    // alice and carol both connect to bob, who relays between them.
+    let bob = Arc::new(Burrow::in_memory("bob"));
+    let alice = Burrow::in_memory("alice");
+    let carol = Burrow::in_memory("carol");
+    let (alice_id, bob_id, carol_id) = (alice.burrow_id(), bob.burrow_id(), carol.burrow_id());
+
+    let (mut a, mut ab) = memory_tunnel_pair("alice", "bob");
+    let (mut c, mut cb) = memory_tunnel_pair("carol", "bob");
+    let b1 = Arc::clone(&bob);
+    let serving_alice = tokio::spawn(async move { b1.handle_tunnel(&mut ab).await });
+    let b2 = Arc::clone(&bob);
+    let serving_carol = tokio::spawn(async move { b2.handle_tunnel(&mut cb).await });
+    alice.client_handshake(&mut a).await.unwrap();
+    carol.client_handshake(&mut c).await.unwrap();
+    while !bob.sessions.has_session(&carol_id) || !bob.sessions.has_session(&alice_id) {
+        tokio::task::yield_now().await;
+    }
+
+    let mut fetch = Frame::with_args("FETCH", vec!["/0/notes".into()]);
+    fetch.set_header("Target", &carol_id);
+    fetch.set_header("Lane", "4");
+    fetch.set_header("Txn", "X-1");
+    a.send_frame(&fetch).await.unwrap();
+
+    let relayed = c.recv_frame().await.unwrap().unwrap();
+    assert_eq!(relayed.verb, "FETCH");
+    assert_eq!(relayed.header("Hop-Count"), Some("7"));
+    assert_eq!(
+        relayed.header("Via"),
+        Some(format!("{}, {}", alice_id, bob_id).as_str())
+    );
+
+    let mut answer = Frame::new("200 CONTENT");
+    answer.set_header("Target", &alice_id);
+    answer.set_header("Lane", "4");
+    answer.set_header("Txn", "X-1");
+    answer.set_body("carol's notes");
+    c.send_frame(&answer).await.unwrap();
+    let answer = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(answer.body.as_deref(), Some("carol's notes"));
+    assert_eq!(answer.header("Txn"), Some("X-1"));
+
+    // A frame that has already passed bob has looped.
+    let mut looped = fetch.clone();
+    looped.set_header("Via", format!("{}, {}", alice_id, bob_id));
+    a.send_frame(&looped).await.unwrap();
+    let resp = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.verb, "502");
+    assert!(resp.body.unwrap().contains("loop"));
+
+    // carol asks for a burrow bob cannot reach; the error names her
+    // as its target only when it must travel further than one hop.
+    let mut lost = Frame::with_args("FETCH", vec!["/".into()]);
+    lost.set_header("Target", "ed25519:NOWHERE");
+    c.send_frame(&lost).await.unwrap();
+    let resp = c.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.verb, "502");
+    assert_eq!(resp.header("Target"), None);
+
+    a.close().await.unwrap();
+    c.close().await.unwrap();
+    drop((a, c));
+    serving_alice.await.unwrap().unwrap();
+    serving_carol.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn rabbit_addresses_are_served_here_or_relayed_to_the_burrow_they_name() {
+    use std::sync::Arc;
+
+    let mut bob = Burrow::in_memory("bob");
+    bob.content
+        .register_menu("/", vec![MenuItem::info("bob's burrow")]);
+    let bob = Arc::new(bob);
+    let alice = Burrow::in_memory("alice");
+    let carol = Burrow::in_memory("carol");
+    let (alice_id, bob_id, carol_id) = (alice.burrow_id(), bob.burrow_id(), carol.burrow_id());
+
+    let (mut a, mut ab) = memory_tunnel_pair("alice", "bob");
+    let (mut c, mut cb) = memory_tunnel_pair("carol", "bob");
+    let b1 = Arc::clone(&bob);
+    let serving_alice = tokio::spawn(async move { b1.handle_tunnel(&mut ab).await });
+    let b2 = Arc::clone(&bob);
+    let serving_carol = tokio::spawn(async move { b2.handle_tunnel(&mut cb).await });
+    alice.client_handshake(&mut a).await.unwrap();
+    carol.client_handshake(&mut c).await.unwrap();
+    while !bob.sessions.has_session(&carol_id) || !bob.sessions.has_session(&alice_id) {
+        tokio::task::yield_now().await;
+    }
+
+    // An address naming bob is served by bob.
+    let mut local = Frame::with_args("LIST", vec![format!("rabbit://{}/", bob_id)]);
+    local.set_header("Txn", "A-1");
+    a.send_frame(&local).await.unwrap();
+    let resp = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.verb, "200");
+    assert!(resp.body.unwrap().contains("bob's burrow"));
+
+    // One naming carol goes to carol, and her answer comes back.
+    let mut fetch = Frame::with_args("FETCH", vec![format!("rabbit://{}/0/notes", carol_id)]);
+    fetch.set_header("Txn", "A-2");
+    a.send_frame(&fetch).await.unwrap();
+    let relayed = c.recv_frame().await.unwrap().unwrap();
+    assert_eq!(relayed.header("Target"), Some(carol_id.as_str()));
+    assert_eq!(
+        relayed.header("Via"),
+        Some(format!("{}, {}", alice_id, bob_id).as_str())
     );
+    let scope = bob.resolve(&RabbitAddress::new(&carol_id, "/")).await;
+    assert_eq!(scope.unwrap(), Scope::Warren);
+
+    let mut answer = Frame::new("200 CONTENT");
+    answer.set_header("Target", &alice_id);
+    answer.set_header("Txn", "A-2");
+    answer.set_body("carol's notes");
+    c.send_frame(&answer).await.unwrap();
+    let answer = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(answer.body.as_deref(), Some("carol's notes"));
+
+    // One naming a burrow bob cannot reach is refused.
+    let lost = Frame::with_args("FETCH", vec!["rabbit://ed25519:NOWHERE/".into()]);
+    a.send_frame(&lost).await.unwrap();
+    let resp = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.verb, "502");
+    let bad = Frame::with_args("FETCH", vec!["rabbit://nowhere/".into()]);
+    a.send_frame(&bad).await.unwrap();
+    let resp = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.verb, "400");
+
+    a.close().await.unwrap();
+    c.close().await.unwrap();
+    drop((a, c));
+    serving_alice.await.unwrap().unwrap();
+    serving_carol.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn answers_to_relayed_requests_are_addressed_to_the_originator() {
+    let mut server = Burrow::in_memory("carol");
+    server.require_auth = false;
+    server
+        .content
+        .register_menu("/", vec![MenuItem::info("welcome")]);
+    let carol_id = server.burrow_id();
+    let client = Burrow::in_memory("bob");
+    let (mut c, mut s) = memory_tunnel_pair("c", "s");
+    let sh = tokio::spawn(async move { server.handle_tunnel(&mut s).await });
+    client.client_handshake(&mut c).await.unwrap();
+
+    let mut relayed = Frame::with_args("LIST", vec![format!("rabbit://{}/", carol_id)]);
+    relayed.set_header("Target", &carol_id);
+    relayed.set_header("Via", format!("ed25519:ALICE, {}", client.burrow_id()));
+    c.send_frame(&relayed).await.unwrap();
+    let resp = c.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.verb, "200");
+    assert_eq!(resp.header("Target"), Some("ed25519:ALICE"));
 
     c.close().await.unwrap();
     sh.await.unwrap().unwrap();
 }
 
+#[tokio::test]
+async fn the_topology_shows_paths_to_every_burrow_known() {
+    use std::sync::Arc;
+
+    let bob = Arc::new(Burrow::in_memory("bob"));
+    let alice = Burrow::in_memory("alice");
+    let alice_id = alice.burrow_id();
+    bob.routing.update("ed25519:DAVE", &alice_id, 2).await;
+    bob.routing.update("ed25519:ERIN", "ed25519:GONE", 2).await;
+
+    let (mut a, mut ab) = memory_tunnel_pair("alice", "bob");
+    let b = Arc::clone(&bob);
+    let serving = tokio::spawn(async move { b.handle_tunnel(&mut ab).await });
+    alice.client_handshake(&mut a).await.unwrap();
+    while !bob.sessions.has_session(&alice_id) {
+        tokio::task::yield_now().await;
+    }
+
+    let topology = bob.topology().await;
+    let dave = topology.shortest_path("ed25519:DAVE").unwrap();
+    assert_eq!(dave.hops, 2);
+    assert_eq!(dave.burrows[1], alice_id);
+    assert!(topology.shortest_path("ed25519:ERIN").is_none());
+    let dave = RabbitAddress::new("ed25519:DAVE", "/");
+    assert_eq!(bob.resolve(&dave).await.unwrap(), Scope::Warren);
+    assert!(bob
+        .resolve(&RabbitAddress::new("ed25519:ERIN", "/"))
+        .await
+        .is_err());
+
+    a.send_frame(&Frame::with_args("LIST", vec!["/warren/topology".into()]))
+        .await
+        .unwrap();
+    let menu = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(menu.verb, "200");
+    let body = menu.body.unwrap();
+    assert!(body.contains("2 hops"));
+    assert!(body.contains("ed25519:ERIN (unreachable)"));
+
+    a.send_frame(&Frame::with_args("FETCH", vec!["/warren/topology".into()]))
+        .await
+        .unwrap();
+    let json = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(json.header("View"), Some("application/json"));
+    let value: serde_json::Value = json.json_body().unwrap();
+    assert_eq!(value["local"], bob.burrow_id());
+    assert_eq!(value["links"].as_array().unwrap().len(), 2);
+
+    a.close().await.unwrap();
+    drop(a);
+    serving.await.unwrap().unwrap();
+}
+
+// ───── G3: Route advertisement ─────────────────────────────────────
+// bob tells each peer what it can reach through him, and learns routes
+// from what his peers tell him.
+
+#[tokio::test]
+async fn routes_are_advertised_and_learned() {
+    use std::sync::Arc;
+
+    // bob's learned routes go stale at once, unless refreshed.
+    let mut bob = Burrow::in_memory("bob");
+    bob.route_ttl_secs = 0;
+    let bob = Arc::new(bob);
+    let alice = Burrow::in_memory("alice");
+    let carol = Burrow::in_memory("carol");
+    let (alice_id, carol_id) = (alice.burrow_id(), carol.burrow_id());
+    bob.capabilities
+        .lock()
+        .unwrap()
+        .grant(&alice_id, Capability::Federation, 3600);
+
+    let (mut a, mut ab) = memory_tunnel_pair("alice", "bob");
+    let (mut c, mut cb) = memory_tunnel_pair("carol", "bob");
+    let b1 = Arc::clone(&bob);
+    let serving_alice = tokio::spawn(async move { b1.handle_tunnel(&mut ab).await });
+    let b2 = Arc::clone(&bob);
+    let serving_carol = tokio::spawn(async move { b2.handle_tunnel(&mut cb).await });
+    alice.client_handshake(&mut a).await.unwrap();
+    carol.client_handshake(&mut c).await.unwrap();
+    while !bob.sessions.has_session(&carol_id) || !bob.sessions.has_session(&alice_id) {
+        tokio::task::yield_now().await;
+    }
+
+    // Routes through a peer are not advertised back to it.
+    bob.routing
+        .learn("ed25519:DAVE", &carol_id, 2, 0, u64::MAX)
+        .await;
+    assert_eq!(bob.advertise_routes().await, 2);
+    let advert = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(advert.verb, "ROUTE-ADVERT");
+    assert_eq!(
+        advert.body.as_deref(),
+        Some(format!("{}\t1\ned25519:DAVE\t2\n", carol_id).as_str())
+    );
+    let advert = c.recv_frame().await.unwrap().unwrap();
+    assert_eq!(advert.body, Some(format!("{}\t1\n", alice_id)));
+
+    // Of alice's lines, only eve is a usable route: alice herself,
+    // a malformed line and a route past the hop limit are skipped.
+    let mut advert = Frame::new("ROUTE-ADVERT");
+    advert.set_body(format!(
+        "ed25519:EVE\t2\n{}\t0\nnonsense\ned25519:FAR\t8\n",
+        alice_id
+    ));
+    a.send_frame(&advert).await.unwrap();
+    let resp = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.verb, "200");
+    assert_eq!(resp.header("Accepted"), Some("1"));
+    let route = bob.routing.get("ed25519:EVE").await.unwrap();
+    assert_eq!(
+        (route.next_hop.as_str(), route.distance),
+        (alice_id.as_str(), 3)
+    );
+
+    // carol's frames for eve now go through alice.
+    let mut fetch = Frame::with_args("FETCH", vec!["/".into()]);
+    fetch.set_header("Target", "ed25519:EVE");
+    c.send_frame(&fetch).await.unwrap();
+    let relayed = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(relayed.header("Target"), Some("ed25519:EVE"));
+
+    // Not advertised again, the route goes stale.
+    assert_eq!(bob.expire_routes().await, 1);
+    assert!(bob.routing.get("ed25519:EVE").await.is_none());
+
+    // carol lacks the Federation capability.
+    c.send_frame(&advert).await.unwrap();
+    assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "403");
+
+    a.close().await.unwrap();
+    c.close().await.unwrap();
+    drop((a, c));
+    serving_alice.await.unwrap().unwrap();
+    serving_carol.await.unwrap().unwrap();
+}
+
+// ───── G4: Relaying ────────────────────────────────────────────────
+// bob relays for alice, who cannot be dialed, within a bandwidth cap.
+
+#[tokio::test]
+async fn relays_advertise_and_carry_frames_for_registered_burrows() {
+    use std::sync::Arc;
+
+    let mut bob = Burrow::in_memory("bob");
+    bob.relay = RelayTable::new(1, 600);
+    let bob = Arc::new(bob);
+    let alice = Burrow::in_memory("alice");
+    let carol = Burrow::in_memory("carol");
+    let (alice_id, bob_id, carol_id) = (alice.burrow_id(), bob.burrow_id(), carol.burrow_id());
+    for peer in [&alice_id, &carol_id] {
+        bob.capabilities
+            .lock()
+            .unwrap()
+            .grant(peer, Capability::Relay, 3600);
+    }
+
+    let (mut a, mut ab) = memory_tunnel_pair("alice", "bob");
+    let (mut c, mut cb) = memory_tunnel_pair("carol", "bob");
+    let b1 = Arc::clone(&bob);
+    let serving_alice = tokio::spawn(async move { b1.handle_tunnel(&mut ab).await });
+    let b2 = Arc::clone(&bob);
+    let serving_carol = tokio::spawn(async move { b2.handle_tunnel(&mut cb).await });
+    alice.client_handshake(&mut a).await.unwrap();
+    carol.client_handshake(&mut c).await.unwrap();
+    while !bob.sessions.has_session(&carol_id) || !bob.sessions.has_session(&alice_id) {
+        tokio::task::yield_now().await;
+    }
+
+    // bob's gossip says he has room, and alice takes note.
+    assert_eq!(bob.advertise_routes().await, 2);
+    let advert = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(advert.header("Relay"), Some("1"));
+    c.recv_frame().await.unwrap().unwrap();
+    alice
+        .capabilities
+        .lock()
+        .unwrap()
+        .grant(&bob_id, Capability::Federation, 3600);
+    alice.dispatcher().dispatch(&advert, &bob_id).await;
+    assert_eq!(alice.relay.relays(0), vec![bob_id.clone()]);
+
+    // bob relays for one burrow only.
+    assert_eq!(relay::register(&mut a).await.unwrap(), 600);
+    assert!(bob.relay.is_client(&alice_id));
+    assert!(matches!(
+        relay::register(&mut c).await,
+        Err(ProtocolError::Busy(_))
+    ));
+
+    // carol's frames reach alice until alice's allowance runs out.
+    let mut fetch = Frame::with_args("FETCH", vec!["/0/notes".into()]);
+    fetch.set_header("Target", &alice_id);
+    c.send_frame(&fetch).await.unwrap();
+    let relayed = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(relayed.verb, "FETCH");
+    let mut bulky = Frame::with_args("PUBLISH", vec!["/q/chat".into()]);
+    bulky.set_header("Target", &alice_id);
+    bulky.set_body("x".repeat(600));
+    c.send_frame(&bulky).await.unwrap();
+    let resp = c.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.verb, "429");
+
+    // Registration ends with alice's tunnel.
+    a.close().await.unwrap();
+    drop(a);
+    serving_alice.await.unwrap().unwrap();
+    assert!(!bob.relay.is_client(&alice_id));
+
+    // Without Relay, bob refuses to carry frames for carol.
+    bob.capabilities
+        .lock()
+        .unwrap()
+        .revoke(&carol_id, Capability::Relay);
+    assert!(matches!(
+        relay::register(&mut c).await,
+        Err(ProtocolError::Forbidden(_))
+    ));
+
+    c.close().await.unwrap();
+    drop(c);
+    serving_carol.await.unwrap().unwrap();
+}
+
+// ───── G3: Peer liveness ───────────────────────────────────────────
+// A peer unseen for long is down until it answers a health check.
+
+#[tokio::test]
+async fn unseen_peers_go_down_until_they_answer_a_ping() {
+    use std::sync::Arc;
+
+    let mut bob = Burrow::in_memory("bob");
+    bob.liveness.prune_secs = 0;
+    let bob = Arc::new(bob);
+    let alice = Burrow::in_memory("alice");
+    let alice_id = alice.burrow_id();
+
+    let (mut a, mut ab) = memory_tunnel_pair("alice", "bob");
+    let b = Arc::clone(&bob);
+    let serving = tokio::spawn(async move { b.handle_tunnel(&mut ab).await });
+    alice.client_handshake(&mut a).await.unwrap();
+    while !bob.sessions.has_session(&alice_id) {
+        tokio::task::yield_now().await;
+    }
+
+    // bob last saw alice long ago.
+    bob.peers
+        .register(PeerInfo::new(&alice_id, "10.0.0.1:7443", "alice"))
+        .await;
+    bob.peers.mark_connected(&alice_id, 1).await;
+    bob.routing.update("ed25519:EVE", &alice_id, 2).await;
+
+    let report = bob.check_peers().await;
+    assert_eq!(report.changed, [(alice_id.clone(), PeerHealth::Down)]);
+    assert!(bob.peers.list_reachable().await.is_empty());
+    assert!(bob.routing.get("ed25519:EVE").await.is_none());
+
+    let ping = a.recv_frame().await.unwrap().unwrap();
+    assert_eq!(ping.verb, "PING");
+    let mut pong = Frame::new("200 PONG");
+    pong.set_header("Ping-Id", ping.header("Ping-Id").unwrap());
+    a.send_frame(&pong).await.unwrap();
+    while bob.peers.get(&alice_id).await.unwrap().health != PeerHealth::Up {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(bob.peers.list_reachable().await.len(), 1);
+
+    a.close().await.unwrap();
+    drop(a);
+    serving.await.unwrap().unwrap();
+}
+
 // ───── G1: Multiple round-trips of save/load ───────────────────────
 
 #[test]
@@ -490,8 +921,9 @@ fn save_load_multiple_rounds() {
 //   - 5 persistence tests (G1)
 //   - 2 routing integration tests (G3)
 //   - 5 forwarding tests (G4)
+//   - 1 relay test (G4)
 //   - 2 resume handshake tests (G2)
 //   - 1 session manager peer_ids test (G1)
 //   - 1 burrow routing field test (G3+G4)
 //   - 1 save/load round-trip test (G1)
-// Total: 17 tests
+// Total: 18 tests
@@ -15,7 +15,7 @@
 use rabbit_engine::burrow::Burrow;
 use rabbit_engine::content::store::MenuItem;
 use rabbit_engine::dispatch::idem_cache::IdemCache;
-use rabbit_engine::dispatch::rate_limiter::RateLimiter;
+use rabbit_engine::security::rate_limiter::RateLimiter;
 use rabbit_engine::events::engine::{EventEngine, QoS};
 use rabbit_engine::protocol::error::ProtocolError;
 use rabbit_engine::protocol::frame::{split_multipart, Frame, PartAssembler};
@@ -161,12 +161,51 @@ async fn rate_limit_returns_429_on_tunnel() {
     c.send_frame(&f).await.unwrap();
     let resp = c.recv_frame().await.unwrap().unwrap();
     assert_eq!(resp.verb, "429");
+    assert_eq!(resp.args, ["SLOW-DOWN"]);
+    assert_eq!(resp.header("Retry-After"), Some("1"));
+    assert_eq!(resp.header("Lane"), Some("L-overflow"));
     assert!(resp.body.as_deref().unwrap_or("").contains("rate limit"));
 
     c.close().await.unwrap();
     sh.await.unwrap().unwrap();
 }
 
+#[tokio::test]
+async fn handshakes_and_fetch_bandwidth_are_throttled() {
+    let mut server = hardened_burrow("throttle");
+    server.rate_limiter = RateLimiter::new(0, 0)
+        .with_handshake_limit(1)
+        .with_fetch_bandwidth(10);
+    let server = std::sync::Arc::new(server);
+    let client = Burrow::in_memory("client");
+
+    let (mut c, mut s) = memory_tunnel_pair("c", "s");
+    let srv = server.clone();
+    let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+    client.client_handshake(&mut c).await.unwrap();
+
+    // "hello world" is 11 bytes, one over the allowance: the first
+    // FETCH is sent and the second must wait.
+    for expected in ["200", "429"] {
+        let f = Frame::with_args("FETCH", vec!["/hello".into()]);
+        c.send_frame(&f).await.unwrap();
+        let resp = c.recv_frame().await.unwrap().unwrap();
+        assert_eq!(resp.verb, expected);
+    }
+    c.close().await.unwrap();
+    sh.await.unwrap().unwrap();
+
+    // A second handshake from the same address within the minute.
+    let (mut c, mut s) = memory_tunnel_pair("c", "s");
+    let srv = server.clone();
+    let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+    assert!(client.client_handshake(&mut c).await.is_err());
+    assert!(matches!(
+        sh.await.unwrap(),
+        Err(ProtocolError::SlowDown { .. })
+    ));
+}
+
 // ═══════════════════════════════════════════════════════════════════
 // H3: Connection Limits
 // ═══════════════════════════════════════════════════════════════════
@@ -292,3 +292,656 @@ fn handshake_replay_protection() {
     let result = auth2.handle_auth(&proof1);
     assert!(result.is_err());
 }
+
+#[tokio::test]
+async fn delegation_token_authorizes_fetch() {
+    use rabbit_engine::content::store::ContentStore;
+    use rabbit_engine::dispatch::router::Dispatcher;
+    use rabbit_engine::events::engine::EventEngine;
+    use rabbit_engine::security::delegation::DelegationToken;
+    use std::sync::Mutex;
+
+    let mut cs = ContentStore::new();
+    cs.register_text("/0/shared/notes", "shared");
+    cs.register_text("/0/private", "private");
+    let ee = EventEngine::new();
+
+    let manager = Identity::generate();
+    let holder = Identity::generate();
+    let mut caps = CapabilityManager::new();
+    caps.grant(&manager.burrow_id(), Capability::ManageWarren, 3600);
+    caps.grant(&manager.burrow_id(), Capability::Fetch, 3600);
+    let caps = Mutex::new(caps);
+    let d = Dispatcher::new(&cs, &ee).with_capabilities(&caps);
+
+    let token = DelegationToken::issue(
+        &manager,
+        &holder.burrow_id(),
+        &[Capability::Fetch],
+        60,
+        "/0/shared",
+    );
+    let fetch = |selector: &str| {
+        let mut frame = Frame::with_args("FETCH", vec![selector.into()]);
+        frame.set_header("Delegation", token.encode());
+        frame
+    };
+
+    let result = d
+        .dispatch(&fetch("/0/shared/notes"), &holder.burrow_id())
+        .await;
+    assert_eq!(result.response.verb, "200");
+    // Outside the delegated prefix, or presented by someone else.
+    let result = d.dispatch(&fetch("/0/private"), &holder.burrow_id()).await;
+    assert_eq!(result.response.verb, "403");
+    let result = d.dispatch(&fetch("/0/shared/notes"), "someone-else").await;
+    assert_eq!(result.response.verb, "403");
+}
+
+#[tokio::test]
+async fn anchor_required_policy_admits_manifest_members() {
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::config::Config;
+    use rabbit_engine::security::manifest::TrustManifest;
+    use rabbit_engine::transport::memory::memory_tunnel_pair;
+    use rabbit_engine::transport::tunnel::Tunnel;
+    use std::sync::Arc;
+
+    let dir = tempfile::tempdir().unwrap();
+    let anchor = Burrow::in_memory("anchor");
+    let member = Burrow::in_memory("member");
+    let stranger = Burrow::in_memory("stranger");
+
+    let mut config = Config::default();
+    config.trust.policy = "anchor-required".into();
+    config.trust.anchors = vec![anchor.burrow_id()];
+    let mut server = Burrow::from_config(&config, dir.path()).unwrap();
+    server.keepalive_secs = 0;
+    server.offer_interval_secs = 0;
+    server
+        .capabilities
+        .lock()
+        .unwrap()
+        .grant(&anchor.burrow_id(), Capability::Federation, 3600);
+    let server = Arc::new(server);
+
+    // Neither is vouched for yet.
+    for client in [&stranger, &member] {
+        let (mut c, mut s) = memory_tunnel_pair("client", "server");
+        let srv = server.clone();
+        let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+        let _ = client.client_handshake(&mut c).await;
+        assert!(sh.await.unwrap().is_err());
+    }
+
+    // The anchor itself is admitted, and vouches for the member.
+    let (mut c, mut s) = memory_tunnel_pair("anchor", "server");
+    let srv = server.clone();
+    let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+    anchor.client_handshake(&mut c).await.unwrap();
+    let manifest = TrustManifest::sign(&anchor.identity, 1, &[member.burrow_id()], &[], &[], 3600);
+    c.send_frame(&manifest.to_frame()).await.unwrap();
+    let resp = c.recv_frame().await.unwrap().unwrap();
+    assert_eq!(resp.verb, "200");
+    assert_eq!(resp.header("Kept"), Some("true"));
+    c.close().await.unwrap();
+    sh.await.unwrap().unwrap();
+
+    let (mut c, mut s) = memory_tunnel_pair("member", "server");
+    let srv = server.clone();
+    let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+    member.client_handshake(&mut c).await.unwrap();
+    c.close().await.unwrap();
+    sh.await.unwrap().unwrap();
+
+    // The manifest outlives a restart.
+    let reloaded = Burrow::from_config(&config, dir.path()).unwrap();
+    let trust = reloaded.trust.lock().unwrap();
+    assert_eq!(trust.anchors_for(&member.burrow_id()), [anchor.burrow_id()]);
+}
+
+#[tokio::test]
+async fn quarantined_peers_are_limited_or_refused() {
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::transport::memory::memory_tunnel_pair;
+    use rabbit_engine::transport::tunnel::Tunnel;
+    use std::sync::Arc;
+
+    let client = Burrow::in_memory("client");
+    let mut server = Burrow::in_memory("server");
+    server.keepalive_secs = 0;
+    server.offer_interval_secs = 0;
+    server
+        .trust
+        .lock()
+        .unwrap()
+        .quarantine(&client.burrow_id(), "suspected compromise");
+    server
+        .capabilities
+        .lock()
+        .unwrap()
+        .grant(&client.burrow_id(), Capability::ManageBurrows, 3600);
+    let server = Arc::new(server);
+
+    let (mut c, mut s) = memory_tunnel_pair("client", "server");
+    let srv = server.clone();
+    let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+    client.client_handshake(&mut c).await.unwrap();
+    c.close().await.unwrap();
+    sh.await.unwrap().unwrap();
+    let mut caps = server
+        .capabilities
+        .lock()
+        .unwrap()
+        .active_capabilities(&client.burrow_id());
+    caps.sort_by_key(|c| c.label());
+    assert_eq!(caps, [Capability::Fetch, Capability::List]);
+
+    let Ok(mut server) = Arc::try_unwrap(server) else {
+        panic!("server still shared");
+    };
+    server.refuse_quarantined = true;
+    let server = Arc::new(server);
+    let (mut c, mut s) = memory_tunnel_pair("client", "server");
+    let srv = server.clone();
+    let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+    let _ = client.client_handshake(&mut c).await;
+    assert!(sh.await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn latest_manifest_is_fetched_from_its_anchor() {
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::protocol::error::ProtocolError;
+    use rabbit_engine::transport::memory::memory_tunnel_pair;
+    use rabbit_engine::transport::tunnel::Tunnel;
+    use std::sync::Arc;
+
+    let mut anchor = Burrow::in_memory("anchor");
+    anchor.keepalive_secs = 0;
+    anchor.offer_interval_secs = 0;
+    let anchor = Arc::new(anchor);
+    let follower = Burrow::in_memory("follower");
+    let members = vec!["ed25519:MEMBER".to_string()];
+
+    let fetch = |anchor: Arc<Burrow>| {
+        let follower = &follower;
+        async move {
+            let (mut c, mut s) = memory_tunnel_pair("follower", "anchor");
+            let srv = anchor.clone();
+            let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+            follower.client_handshake(&mut c).await.unwrap();
+            let fetched = follower
+                .federation
+                .fetch_manifest(&mut c, &anchor.burrow_id())
+                .await;
+            c.close().await.unwrap();
+            sh.await.unwrap().unwrap();
+            fetched
+        }
+    };
+
+    assert!(matches!(
+        fetch(anchor.clone()).await,
+        Err(ProtocolError::Missing(_))
+    ));
+
+    anchor
+        .federation
+        .publish(&anchor.identity, &members, &[], &[], 3600)
+        .unwrap();
+    let first = fetch(anchor.clone()).await.unwrap();
+    assert!(follower.accept_manifest(first).unwrap());
+    assert_eq!(
+        follower.trust.lock().unwrap().anchors_for(&members[0]),
+        [anchor.burrow_id()]
+    );
+
+    // A later manifest revoking the member supersedes the first.
+    anchor
+        .federation
+        .publish(&anchor.identity, &[], &[], &members, 3600)
+        .unwrap();
+    let second = fetch(anchor.clone()).await.unwrap();
+    assert_eq!(second.serial, 2);
+    assert!(follower.accept_manifest(second.clone()).unwrap());
+    assert!(!follower.accept_manifest(second).unwrap());
+    assert!(follower
+        .trust
+        .lock()
+        .unwrap()
+        .anchors_for(&members[0])
+        .is_empty());
+}
+
+#[tokio::test]
+async fn manifest_gossip_fetches_only_missing_or_newer_manifests() {
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::security::manifest::TrustManifest;
+    use rabbit_engine::warren::gossip;
+
+    let hub = Burrow::in_memory("hub");
+    let follower = Burrow::in_memory("follower");
+    let (a, b) = (Identity::generate(), Identity::generate());
+    for (burrow, peer) in [(&hub, &follower), (&follower, &hub)] {
+        burrow
+            .capabilities
+            .lock()
+            .unwrap()
+            .grant(&peer.burrow_id(), Capability::Federation, 3600);
+    }
+    let manifest =
+        |anchor: &Identity, serial| TrustManifest::sign(anchor, serial, &[], &[], &[], 3600);
+    hub.accept_manifest(manifest(&a, 2)).unwrap();
+    follower.accept_manifest(manifest(&a, 1)).unwrap();
+    follower.accept_manifest(manifest(&b, 1)).unwrap();
+
+    // The hub is sent the follower's digest on its next round.
+    let mut queued = hub.sessions.register(&follower.burrow_id(), 8);
+    assert_eq!(hub.gossip_manifests(), 1);
+    assert_eq!(queued.recv().await.unwrap().args, ["digest"]);
+
+    // The follower's digest: the hub pushes its newer manifest of a and
+    // wants b's, which the follower then pushes.
+    let digest = gossip::digest_frame(&follower.held_manifests());
+    let result = hub.answer_manifest(&digest, &follower.burrow_id()).await;
+    assert_eq!(result.response.header("Pushed"), Some("1"));
+    assert_eq!(result.response.header("Wanted"), Some("1"));
+    let mut pushed = Vec::new();
+    for extra in &result.extras {
+        let answer = follower.answer_manifest(extra, &hub.burrow_id()).await;
+        assert_eq!(answer.response.verb, "200");
+        pushed.extend(answer.extras);
+    }
+    assert_eq!(pushed.len(), 1);
+    let answer = hub.answer_manifest(&pushed[0], &follower.burrow_id()).await;
+    assert_eq!(answer.response.header("Kept"), Some("true"));
+
+    // Once both hold the same serials, nothing more crosses.
+    let digest = gossip::digest_frame(&follower.held_manifests());
+    let result = hub.answer_manifest(&digest, &follower.burrow_id()).await;
+    assert_eq!(result.response.header("Pushed"), Some("0"));
+    assert!(result.extras.is_empty());
+
+    // Forged manifests are refused, as is gossip from peers without
+    // Federation.
+    let mut forged = manifest(&a, 3).to_frame();
+    forged.set_header("Serial", "9");
+    let result = hub.answer_manifest(&forged, &follower.burrow_id()).await;
+    assert_ne!(result.response.verb, "200");
+    let result = hub.answer_manifest(&digest, "stranger").await;
+    assert_eq!(result.response.verb, "403");
+    assert_eq!(hub.held_manifests().iter().map(|m| m.serial).max(), Some(2));
+}
+
+#[tokio::test]
+async fn offers_wait_for_a_manifest_vouching_for_their_signer() {
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::config::Config;
+    use rabbit_engine::security::manifest::TrustManifest;
+    use rabbit_engine::warren::offer::PeerOffer;
+    use rabbit_engine::warren::peers::PeerInfo;
+
+    let dir = tempfile::tempdir().unwrap();
+    let anchor = Identity::generate();
+    let oak = Identity::generate();
+    let mut config = Config::default();
+    config.trust.anchors = vec![anchor.burrow_id()];
+    let burrow = Burrow::from_config(&config, dir.path()).unwrap();
+    burrow
+        .capabilities
+        .lock()
+        .unwrap()
+        .grant("relay", Capability::Federation, 3600);
+    let peers = vec![PeerInfo::new("ed25519:PEER", "10.0.0.1:7443", "alpha")];
+
+    // Unsigned and forged offers are refused outright.
+    let mut unsigned = Frame::new("OFFER");
+    unsigned.set_body("ed25519:PEER\t10.0.0.1:7443\talpha\n");
+    let result = burrow.dispatcher().dispatch(&unsigned, "relay").await;
+    assert_eq!(result.response.verb, "403");
+    let mut forged = PeerOffer::sign(&oak, peers.clone()).to_frame();
+    forged.set_header("Warren-ID", anchor.burrow_id());
+    let result = burrow.dispatcher().dispatch(&forged, "relay").await;
+    assert_eq!(result.response.verb, "403");
+
+    // oak's offer waits until the anchor's manifest vouches for oak.
+    let offer = PeerOffer::sign(&oak, peers).to_frame();
+    let result = burrow.dispatcher().dispatch(&offer, "relay").await;
+    assert_eq!(result.response.header("Pending"), Some("true"));
+    assert_eq!(burrow.peers.count().await, 0);
+
+    let manifest = TrustManifest::sign(&anchor, 1, &[oak.burrow_id()], &[], &[], 3600);
+    let result = burrow.answer_manifest(&manifest.to_frame(), "relay").await;
+    assert_eq!(result.response.header("Kept"), Some("true"));
+    assert!(burrow.peers.get("ed25519:PEER").await.is_some());
+
+    // Once vouched for, oak's offers are taken at once.
+    let result = burrow.dispatcher().dispatch(&offer, "relay").await;
+    assert_eq!(result.response.header("Accepted"), Some("1"));
+}
+
+#[test]
+fn anchors_registered_at_runtime_survive_a_restart() {
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::config::{Config, FederationAnchorConfig};
+    use rabbit_engine::security::manifest::TrustManifest;
+    use rabbit_engine::warren::federation::Anchor;
+
+    let dir = tempfile::tempdir().unwrap();
+    let (hub, sub, oak) = (
+        Identity::generate(),
+        Identity::generate(),
+        Identity::generate(),
+    );
+    let mut config = Config::default();
+    config.trust.policy = "anchor-required".into();
+    config.federation.anchors = vec![FederationAnchorConfig {
+        id: hub.burrow_id(),
+        address: Some("hub.example.org:7443".into()),
+    }];
+    let burrow = Burrow::from_config(&config, dir.path()).unwrap();
+    assert!(burrow.pending_offers.is_some());
+    assert_eq!(burrow.federation.dial_addresses(), ["hub.example.org:7443"]);
+
+    // oak is refused until an anchor trusted here vouches for it.
+    let manifest = TrustManifest::sign(&sub, 1, &[oak.burrow_id()], &[], &[], 3600);
+    burrow.accept_manifest(manifest).unwrap();
+    let admit = |b: &Burrow| {
+        b.trust
+            .lock()
+            .unwrap()
+            .verify_or_remember(&oak.burrow_id(), &oak.public_key_bytes())
+    };
+    assert!(admit(&burrow).is_err());
+    let anchor = Anchor::new(&sub.burrow_id(), None);
+    assert!(burrow.register_anchor(anchor).unwrap());
+    drop(burrow);
+
+    // After a restart sub is still trusted, beside the configured hub.
+    let reloaded = Burrow::from_config(&config, dir.path()).unwrap();
+    let ids: Vec<String> = reloaded
+        .federation
+        .anchors()
+        .into_iter()
+        .map(|a| a.id)
+        .collect();
+    let mut expected = vec![hub.burrow_id(), sub.burrow_id()];
+    expected.sort();
+    assert_eq!(ids, expected);
+    assert!(admit(&reloaded).is_ok());
+    assert!(reloaded.forget_anchor(&sub.burrow_id()).unwrap());
+    assert!(!reloaded.federation.is_anchor(&sub.burrow_id()));
+}
+
+#[tokio::test]
+async fn handshakes_are_audited_and_listed_to_operators() {
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::security::audit::{AuditKind, AuditQuery};
+    use rabbit_engine::transport::memory::memory_tunnel_pair;
+    use rabbit_engine::transport::tunnel::Tunnel;
+    use std::sync::Arc;
+
+    let client = Burrow::in_memory("client");
+    let mut server = Burrow::in_memory("server");
+    server.keepalive_secs = 0;
+    server.offer_interval_secs = 0;
+    let server = Arc::new(server);
+
+    let (mut c, mut s) = memory_tunnel_pair("client", "server");
+    let srv = server.clone();
+    let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+    client.client_handshake(&mut c).await.unwrap();
+    c.close().await.unwrap();
+    sh.await.unwrap().unwrap();
+
+    let query = AuditQuery {
+        peer: Some(client.burrow_id()),
+        ..Default::default()
+    };
+    let kinds: Vec<AuditKind> = server.audit.query(&query).iter().map(|r| r.kind).collect();
+    assert_eq!(kinds, [AuditKind::Grant, AuditKind::Handshake]);
+    server.audit.verify().unwrap();
+
+    let mut list = Frame::with_args("LIST", vec!["/t/audit".into()]);
+    list.set_header("Kind", "handshake");
+    let d = server.dispatcher();
+    let result = d.dispatch(&list, &client.burrow_id()).await;
+    assert_eq!(result.response.verb, "403");
+    server
+        .capabilities
+        .lock()
+        .unwrap()
+        .grant(&client.burrow_id(), Capability::ManageBurrows, 3600);
+    let result = d.dispatch(&list, &client.burrow_id()).await;
+    assert_eq!(result.response.verb, "200");
+    let body = result.response.body.unwrap();
+    assert!(body.contains("handshake"));
+    assert!(body.contains(&client.burrow_id()));
+    assert!(!body.contains(" grant "));
+}
+
+#[tokio::test]
+async fn federation_links_prove_their_shared_secret() {
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::protocol::error::ProtocolError;
+    use rabbit_engine::security::audit::{AuditKind, AuditQuery};
+    use rabbit_engine::transport::memory::memory_tunnel_pair;
+    use rabbit_engine::transport::tunnel::Tunnel;
+    use rabbit_engine::warren::federation::LinkStatus;
+    use std::sync::Arc;
+
+    let mut remote = Burrow::in_memory("remote");
+    remote.keepalive_secs = 0;
+    remote.offer_interval_secs = 0;
+    let remote = Arc::new(remote);
+    let local = Burrow::in_memory("local");
+    local
+        .federation
+        .add_link(&remote.burrow_id(), "correct horse");
+
+    let dial = |remote: Arc<Burrow>| {
+        let local = &local;
+        async move {
+            let (mut c, mut s) = memory_tunnel_pair("local", "remote");
+            let srv = remote.clone();
+            let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+            local.client_handshake(&mut c).await.unwrap();
+            let linked = local.authenticate_link(&mut c, &remote.burrow_id()).await;
+            c.close().await.unwrap();
+            sh.await.unwrap().unwrap();
+            linked
+        }
+    };
+
+    // The remote end has no link to us yet.
+    assert!(matches!(
+        dial(remote.clone()).await,
+        Err(ProtocolError::Forbidden(_))
+    ));
+
+    // A different secret fails both ways round.
+    remote
+        .federation
+        .add_link(&local.burrow_id(), "battery staple");
+    assert!(matches!(
+        dial(remote.clone()).await,
+        Err(ProtocolError::Forbidden(_))
+    ));
+    assert!(matches!(
+        local.federation.link(&remote.burrow_id()).unwrap().status,
+        LinkStatus::Failed { failures: 2, .. }
+    ));
+
+    remote
+        .federation
+        .add_link(&local.burrow_id(), "correct horse");
+    dial(remote.clone()).await.unwrap();
+    assert!(local.federation.is_link_authenticated(&remote.burrow_id()));
+    assert!(remote.federation.is_link_authenticated(&local.burrow_id()));
+    assert!(remote
+        .capabilities
+        .lock()
+        .unwrap()
+        .check(&local.burrow_id(), Capability::Federation));
+    let query = AuditQuery {
+        kind: Some(AuditKind::AuthFailure),
+        ..Default::default()
+    };
+    assert_eq!(local.audit.query(&query).len(), 2);
+}
+
+#[tokio::test]
+async fn federation_links_open_beat_and_close() {
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::transport::memory::memory_tunnel_pair;
+    use rabbit_engine::transport::tunnel::Tunnel;
+    use rabbit_engine::warren::federation::LinkState;
+    use std::sync::Arc;
+
+    let mut remote = Burrow::in_memory("remote");
+    remote.keepalive_secs = 0;
+    remote.offer_interval_secs = 0;
+    let remote = Arc::new(remote);
+    let local = Burrow::in_memory("local");
+    local
+        .federation
+        .add_link(&remote.burrow_id(), "correct horse");
+    remote
+        .federation
+        .add_link(&local.burrow_id(), "correct horse");
+
+    let (mut c, mut s) = memory_tunnel_pair("local", "remote");
+    let srv = remote.clone();
+    let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+    local.client_handshake(&mut c).await.unwrap();
+
+    // A link opens only once both ends are proven.
+    c.send_frame(&local.federation.open_frame()).await.unwrap();
+    assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "403");
+    local
+        .authenticate_link(&mut c, &remote.burrow_id())
+        .await
+        .unwrap();
+    local
+        .federation
+        .open_link(&mut c, &remote.burrow_id())
+        .await
+        .unwrap();
+    let link = remote.federation.link(&local.burrow_id()).unwrap();
+    assert!(matches!(link.state, LinkState::Open { .. }));
+    assert!(link.last_seen > 0);
+    let status = local.federation.link_status();
+    assert_eq!((status[0].state, status[0].health), ("open", "up"));
+
+    // Opening an open link is a heartbeat.
+    c.send_frame(&local.federation.open_frame()).await.unwrap();
+    assert_eq!(
+        c.recv_frame().await.unwrap().unwrap().verb,
+        "FED-LINK-ACCEPT"
+    );
+
+    // Closing takes the Federation grant with it.
+    let close = local
+        .federation
+        .close_link(&remote.burrow_id(), "retired")
+        .unwrap();
+    c.send_frame(&close).await.unwrap();
+    assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "200");
+    c.close().await.unwrap();
+    sh.await.unwrap().unwrap();
+
+    let status = remote.federation.link_status();
+    assert_eq!(status[0].state, "closed");
+    assert_eq!(status[0].health, "down");
+    assert_eq!(status[0].detail, "retired");
+    assert!(!remote.federation.is_link_authenticated(&local.burrow_id()));
+    assert!(!remote
+        .capabilities
+        .lock()
+        .unwrap()
+        .check(&local.burrow_id(), Capability::Federation));
+}
+
+#[tokio::test]
+async fn services_declared_on_links_are_found_across_warrens() {
+    use rabbit_engine::burrow::Burrow;
+    use rabbit_engine::transport::memory::memory_tunnel_pair;
+    use rabbit_engine::transport::tunnel::Tunnel;
+    use rabbit_engine::warren::services::Service;
+    use std::sync::Arc;
+
+    let mut remote = Burrow::in_memory("remote");
+    remote.keepalive_secs = 0;
+    remote.offer_interval_secs = 0;
+    let remote = Arc::new(remote);
+    let local = Burrow::in_memory("local");
+    local
+        .federation
+        .add_link(&remote.burrow_id(), "correct horse");
+    remote
+        .federation
+        .add_link(&local.burrow_id(), "correct horse");
+    let requires = vec![Capability::Subscribe, Capability::Publish];
+    remote
+        .federation
+        .services()
+        .declare(Service::new("dialogue", "/q/dialogue", requires))
+        .unwrap();
+    local
+        .federation
+        .services()
+        .declare(Service::new("archive", "/0/archive", Vec::new()))
+        .unwrap();
+
+    let (mut c, mut s) = memory_tunnel_pair("local", "remote");
+    let srv = remote.clone();
+    let sh = tokio::spawn(async move { srv.handle_tunnel(&mut s).await });
+    local.client_handshake(&mut c).await.unwrap();
+    local
+        .authenticate_link(&mut c, &remote.burrow_id())
+        .await
+        .unwrap();
+    local
+        .federation
+        .open_link(&mut c, &remote.burrow_id())
+        .await
+        .unwrap();
+
+    // Each end learns the other's services as the link opens.
+    assert_eq!(
+        local.federation.find_service("dialogue"),
+        [(remote.burrow_id(), "/q/dialogue".to_string())]
+    );
+    assert_eq!(
+        remote.federation.find_service("archive"),
+        [(local.burrow_id(), "/0/archive".to_string())]
+    );
+    assert!(local.federation.find_service("archive").is_empty());
+    let link = local.federation.link(&remote.burrow_id()).unwrap();
+    assert_eq!(link.services[0].requires.len(), 2);
+
+    // A withdrawal reaches the other end with the next heartbeat.
+    local.federation.services().withdraw("archive");
+    c.send_frame(&local.federation.open_frame()).await.unwrap();
+    let accept = c.recv_frame().await.unwrap().unwrap();
+    assert_eq!(accept.verb, "FED-LINK-ACCEPT");
+    local
+        .federation
+        .note_link_accept(&accept, &remote.burrow_id());
+    assert!(remote.federation.find_service("archive").is_empty());
+    assert_eq!(local.federation.find_service("dialogue").len(), 1);
+    assert_eq!(local.federation.link_status()[0].services, ["dialogue"]);
+
+    // Closing the link takes its services with it.
+    let close = local
+        .federation
+        .close_link(&remote.burrow_id(), "retired")
+        .unwrap();
+    c.send_frame(&close).await.unwrap();
+    assert_eq!(c.recv_frame().await.unwrap().unwrap().verb, "200");
+    c.close().await.unwrap();
+    sh.await.unwrap().unwrap();
+    assert!(local.federation.find_service("dialogue").is_empty());
+    assert!(remote.federation.link_status()[0].services.is_empty());
+}
@@ -3,12 +3,30 @@
 //! Covers memory tunnels, TLS tunnels over real TCP, and cross-module
 //! interactions between transport and protocol layers.
 
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+use rabbit_engine::burrow::Burrow;
+use rabbit_engine::config::ListenerLimits;
+use rabbit_engine::network::acceptor::run_listener;
+use rabbit_engine::network::{Backoff, ConnectionEvent, ConnectionManager};
+use rabbit_engine::protocol::error::ProtocolError;
 use rabbit_engine::protocol::frame::Frame;
-use rabbit_engine::transport::cert::{generate_self_signed, make_server_config};
-use rabbit_engine::transport::connector::{connect, make_client_config_insecure};
+use rabbit_engine::security::identity::Identity;
+use rabbit_engine::security::identity_cert::{extract_rabbit_id_from_cert, generate_identity_cert};
+use rabbit_engine::security::trust::TlsVerify;
+use rabbit_engine::transport::cert::{generate_self_signed, make_server_config, CertPair};
+use rabbit_engine::transport::connector::{
+    connect, make_client_config_insecure, make_client_config_with_cert,
+};
 use rabbit_engine::transport::listener::RabbitListener;
-use rabbit_engine::transport::memory::memory_tunnel_pair;
-use rabbit_engine::transport::tunnel::Tunnel;
+use rabbit_engine::transport::memory::{memory_tunnel_pair, MemoryListener};
+use rabbit_engine::transport::stats::ListenerCounters;
+use rabbit_engine::transport::tcp::{connect_plain, PlainListener};
+use rabbit_engine::transport::tls::WireFormat;
+use rabbit_engine::transport::tunnel::{Acceptor, Tunnel};
 
 // ── Memory Tunnel Integration ──────────────────────────────────
 
@@ -260,3 +278,566 @@ async fn tls_tunnel_disconnect_detected() {
 
     server_handle.await.unwrap();
 }
+
+// ── Other transports ───────────────────────────────────────────
+
+/// Serve one burrow on `acceptor`, dial it with `dial`, and ping it
+/// over the tunnel, whatever the transport.
+async fn ping_over<A, T, F>(acceptor: A, dial: F) -> Frame
+where
+    A: Acceptor + 'static,
+    T: Tunnel,
+    F: std::future::Future<Output = Result<T, ProtocolError>>,
+{
+    let server = Arc::new(Burrow::in_memory("server"));
+    let serving = Arc::clone(&server);
+    let task = tokio::spawn(async move { serving.serve(&acceptor).await });
+
+    let client = Burrow::in_memory("client");
+    let mut tunnel = dial.await.unwrap();
+    let server_id = client.client_handshake(&mut tunnel).await.unwrap();
+    assert_eq!(server_id, server.burrow_id());
+    let mut ping = Frame::new("PING");
+    ping.set_header("Lane", "0");
+    tunnel.send_frame(&ping).await.unwrap();
+    let reply = tunnel.recv_frame().await.unwrap().unwrap();
+    tunnel.close().await.unwrap();
+    task.abort();
+    reply
+}
+
+#[tokio::test]
+async fn burrows_serve_any_acceptor() {
+    let listener = PlainListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let reply = ping_over(listener, connect_plain(&addr)).await;
+    assert!(reply.verb.starts_with("200"), "{}", reply.verb);
+
+    // No sockets at all.
+    let listener = MemoryListener::new();
+    let dialer = listener.clone();
+    let reply = ping_over(listener, async move { dialer.connect().await }).await;
+    assert!(reply.verb.starts_with("200"), "{}", reply.verb);
+
+    let cert = generate_self_signed().unwrap();
+    let listener = RabbitListener::bind("127.0.0.1:0", make_server_config(&cert).unwrap())
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let reply = ping_over(
+        listener,
+        connect(&addr, make_client_config_insecure(), "localhost"),
+    )
+    .await;
+    assert!(reply.verb.starts_with("200"), "{}", reply.verb);
+}
+
+#[tokio::test]
+async fn dual_stack_listeners_take_ipv4() {
+    // Hosts without IPv6 cannot bind `[::]` at all.
+    let Ok(listener) = PlainListener::bind("[::]:0").await else {
+        return;
+    };
+    let port = listener.local_addr().unwrap().port();
+    let addr = format!("127.0.0.1:{}", port);
+    let reply = ping_over(listener, connect_plain(&addr)).await;
+    assert!(reply.verb.starts_with("200"), "{}", reply.verb);
+}
+
+#[tokio::test]
+async fn listeners_refuse_connections_over_their_limits() {
+    let server = Arc::new(Burrow::in_memory("server"));
+    let (_current, burrows) = watch::channel(Arc::clone(&server));
+    let listener = PlainListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let counters = Arc::new(ListenerCounters::new(addr.clone()));
+    server.listener_stats.register(Arc::clone(&counters));
+    let limits = ListenerLimits {
+        max_tunnels: 1,
+        handshakes_per_sec: 2,
+        handshake_timeout_secs: 1,
+        backlog: 8,
+    };
+    let task = run_listener(listener, limits, Arc::clone(&counters), burrows);
+
+    let client = Burrow::in_memory("client");
+    let mut first = connect_plain(&addr).await.unwrap();
+    client.client_handshake(&mut first).await.unwrap();
+
+    // One tunnel fills the listener...
+    let mut second = connect_plain(&addr).await.unwrap();
+    let reply = second.recv_frame().await.unwrap().unwrap();
+    assert!(reply.verb.starts_with("503"), "{}", reply.verb);
+    // ...and a third handshake this second is one too many.
+    let mut third = connect_plain(&addr).await.unwrap();
+    let reply = third.recv_frame().await.unwrap().unwrap();
+    assert!(reply.verb.starts_with("429"), "{}", reply.verb);
+
+    first.close().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(counters.active(), 0);
+
+    // A connection that never says HELLO is dropped.
+    let mut silent = connect_plain(&addr).await.unwrap();
+    let closed = tokio::time::timeout(Duration::from_secs(5), silent.recv_frame()).await;
+    assert!(!matches!(closed, Ok(Ok(Some(_)))));
+
+    let stats = server.listener_stats.snapshot();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].address, addr);
+    assert_eq!(stats[0].accepted, 2);
+    assert_eq!(stats[0].refused_full, 1);
+    assert_eq!(stats[0].refused_rate, 1);
+    assert_eq!(stats[0].handshake_timeouts, 1);
+    task.abort();
+}
+
+// ── Identity-bound certificates ────────────────────────────────
+
+/// Serve one tunnel from `server` over TLS and connect to it as
+/// `client`, presenting a certificate bound to `cert_owner`.
+async fn tls_handshake(
+    server: &Arc<Burrow>,
+    client: &Burrow,
+    cert_owner: &Identity,
+) -> Result<String, ProtocolError> {
+    let server_config =
+        make_server_config(&generate_identity_cert(&server.identity, "server").unwrap()).unwrap();
+    let client_config =
+        make_client_config_with_cert(&generate_identity_cert(cert_owner, "client").unwrap())
+            .unwrap();
+    let listener = RabbitListener::bind("127.0.0.1:0", server_config)
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let srv = Arc::clone(server);
+    let server_handle = tokio::spawn(async move {
+        let mut tunnel = listener.accept().await.unwrap();
+        srv.handle_tunnel(&mut tunnel).await
+    });
+    let mut tunnel = connect(&addr.to_string(), client_config, "localhost")
+        .await
+        .unwrap();
+    assert_eq!(
+        extract_rabbit_id_from_cert(tunnel.peer_certificate().unwrap()).unwrap(),
+        Some(server.identity.burrow_id())
+    );
+    let result = client.client_handshake(&mut tunnel).await;
+    tunnel.close().await.unwrap();
+    drop(tunnel);
+    server_handle.await.unwrap()?;
+    result
+}
+
+#[tokio::test]
+async fn tls_certificate_binds_burrow_id() {
+    let server = Arc::new(Burrow::in_memory("server"));
+    let client = Burrow::in_memory("client");
+
+    let server_id = tls_handshake(&server, &client, &client.identity)
+        .await
+        .unwrap();
+    assert_eq!(server_id, server.identity.burrow_id());
+    let trust = server.trust.lock().unwrap();
+    let peer = trust.get(&client.identity.burrow_id()).unwrap();
+    assert_eq!(peer.certificate.as_ref().map(String::len), Some(64));
+}
+
+#[tokio::test]
+async fn tls_certificate_for_another_burrow_is_rejected() {
+    let server = Arc::new(Burrow::in_memory("server"));
+    let client = Burrow::in_memory("client");
+
+    let err = tls_handshake(&server, &client, &Identity::generate())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ProtocolError::Forbidden(_)), "{err}");
+    assert!(server
+        .trust
+        .lock()
+        .unwrap()
+        .get(&client.identity.burrow_id())
+        .is_none());
+}
+
+/// Serve tunnels from `server` over TLS with `cert` until the test ends.
+async fn serve_tls(server: &Arc<Burrow>, cert: &CertPair) -> String {
+    let listener = RabbitListener::bind("127.0.0.1:0", make_server_config(cert).unwrap())
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let srv = Arc::clone(server);
+    tokio::spawn(async move {
+        loop {
+            if let Ok(mut tunnel) = listener.accept().await {
+                let _ = srv.handle_tunnel(&mut tunnel).await;
+            }
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn pinned_server_certificates() {
+    let server = Arc::new(Burrow::in_memory("server"));
+    let server_id = server.identity.burrow_id();
+    let addr = serve_tls(
+        &server,
+        &generate_identity_cert(&server.identity, "server").unwrap(),
+    )
+    .await;
+    let mut client = Burrow::in_memory("client");
+
+    // An unknown server is refused during the TLS handshake.
+    client.tls_verify = TlsVerify::Pinned;
+    assert!(connect(&addr, client.client_tls_config(None), "localhost")
+        .await
+        .is_err());
+
+    // Under TOFU it is admitted and its certificate pinned.
+    client.tls_verify = TlsVerify::Tofu;
+    let mut tunnel = connect(&addr, client.client_tls_config(None), "localhost")
+        .await
+        .unwrap();
+    assert_eq!(
+        client.client_handshake(&mut tunnel).await.unwrap(),
+        server_id
+    );
+    tunnel.close().await.unwrap();
+    let pinned = client
+        .trust
+        .lock()
+        .unwrap()
+        .get(&server_id)
+        .unwrap()
+        .certificate
+        .clone();
+    assert_eq!(pinned.map(|c| c.len()), Some(64));
+
+    // From then on it is accepted even when only pinned servers are.
+    client.tls_verify = TlsVerify::Pinned;
+    let config = client.client_tls_config(Some(&server_id));
+    let mut tunnel = connect(&addr, config, "localhost").await.unwrap();
+    client.client_handshake(&mut tunnel).await.unwrap();
+    tunnel.close().await.unwrap();
+
+    // A plain self-signed certificate is not the server's.
+    let imposter = serve_tls(&server, &generate_self_signed().unwrap()).await;
+    let config = client.client_tls_config(Some(&server_id));
+    assert!(connect(&imposter, config, "localhost").await.is_err());
+    client.tls_verify = TlsVerify::Tofu;
+    let config = client.client_tls_config(Some(&server_id));
+    assert!(connect(&imposter, config, "localhost").await.is_err());
+
+    // A revoked server is refused whatever it presents.
+    client.trust.lock().unwrap().revoke(&server_id);
+    assert!(connect(&addr, client.client_tls_config(None), "localhost")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn length_prefixed_tunnels_carry_binary_bodies() {
+    let png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0xFF];
+    let mut server = Burrow::in_memory("server");
+    server.length_prefixed = true;
+    server
+        .content
+        .register_binary("/9/logo", png.clone(), "image/png");
+    let server = Arc::new(server);
+    let addr = serve_tls(&server, &generate_self_signed().unwrap()).await;
+
+    let mut client = Burrow::in_memory("client");
+    for offer in [true, false] {
+        client.length_prefixed = offer;
+        let mut tunnel = connect(&addr, make_client_config_insecure(), "localhost")
+            .await
+            .unwrap();
+        client.client_handshake(&mut tunnel).await.unwrap();
+        let expected = if offer {
+            WireFormat::LengthPrefixed
+        } else {
+            WireFormat::Text
+        };
+        assert_eq!(tunnel.format(), expected);
+
+        let mut fetch = Frame::with_args("FETCH", vec!["/9/logo".into()]);
+        fetch.set_header("Lane", "0");
+        tunnel.send_frame(&fetch).await.unwrap();
+        let reply = tunnel.recv_frame().await.unwrap().unwrap();
+        assert_eq!(reply.verb, "200", "{:?}", reply.args);
+        assert_eq!(reply.header("Transfer"), Some("base64"));
+        assert_eq!(reply.binary_body(), Some(png.clone()));
+        tunnel.close().await.unwrap();
+    }
+}
+
+// ── Reconnection ───────────────────────────────────────────────
+
+#[tokio::test]
+async fn connection_manager_redials_after_a_failure() {
+    let server = Arc::new(Burrow::in_memory("server"));
+    let server_config =
+        make_server_config(&generate_identity_cert(&server.identity, "server").unwrap()).unwrap();
+    let listener = RabbitListener::bind("127.0.0.1:0", server_config)
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let srv = Arc::clone(&server);
+    tokio::spawn(async move {
+        // Hang up on the first dial, serve the second.
+        drop(listener.accept().await.unwrap());
+        let mut tunnel = listener.accept().await.unwrap();
+        let _ = srv.handle_tunnel(&mut tunnel).await;
+    });
+
+    let client = Arc::new(Burrow::in_memory("client"));
+    let backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(50));
+    let manager = ConnectionManager::new(Arc::clone(&client), backoff);
+    let mut events = manager.subscribe();
+    assert!(manager.add_peer(&addr));
+    assert!(!manager.add_peer(&addr));
+
+    let mut seen = Vec::new();
+    let server_id = loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        seen.push(event.clone());
+        if let ConnectionEvent::Connected { peer_id, .. } = event {
+            break peer_id;
+        }
+    };
+    assert_eq!(server_id, server.identity.burrow_id());
+    assert!(matches!(
+        &seen[..],
+        [
+            ConnectionEvent::Connecting { attempt: 1, .. },
+            ConnectionEvent::Disconnected { peer_id: None, .. },
+            ConnectionEvent::Retrying { .. },
+            ConnectionEvent::Connecting { attempt: 2, .. },
+            ConnectionEvent::Connected { .. },
+        ]
+    ));
+    assert!(client.peers.get(&server_id).await.unwrap().connected);
+
+    assert!(manager.remove_peer(&addr));
+    assert!(manager.peers().is_empty());
+}
+
+#[tokio::test]
+async fn connection_manager_dials_through_a_socks5_proxy() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    let server = Arc::new(Burrow::in_memory("server"));
+    let tls = RabbitListener::bind(
+        "127.0.0.1:0",
+        make_server_config(&generate_self_signed().unwrap()).unwrap(),
+    )
+    .await
+    .unwrap();
+    let target = tls.local_addr().unwrap();
+    let srv = Arc::clone(&server);
+    tokio::spawn(async move { srv.serve(&tls).await });
+
+    // A SOCKS5 proxy without auth that only knows "burrow.test".
+    let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy_addr = proxy.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut client, _) = proxy.accept().await.unwrap();
+        let mut greeting = [0u8; 3];
+        client.read_exact(&mut greeting).await.unwrap();
+        client.write_all(&[0x05, 0x00]).await.unwrap();
+        let mut request = [0u8; 5];
+        client.read_exact(&mut request).await.unwrap();
+        let mut name = vec![0u8; request[4] as usize + 2];
+        client.read_exact(&mut name).await.unwrap();
+        assert_eq!(&name[..name.len() - 2], b"burrow.test");
+        let mut upstream = TcpStream::connect(target).await.unwrap();
+        client
+            .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+        let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+    });
+
+    let client = Arc::new(Burrow::in_memory("client"));
+    let network = rabbit_engine::config::NetworkConfig {
+        proxy: Some(format!("socks5://{}", proxy_addr)),
+        ..Default::default()
+    };
+    let manager = ConnectionManager::from_config(Arc::clone(&client), &network);
+    let mut events = manager.subscribe();
+    let addr = format!("burrow.test:{}", target.port());
+    manager.add_peer(&addr);
+    let server_id = loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        if let ConnectionEvent::Connected { peer_id, .. } = event {
+            break peer_id;
+        }
+    };
+    assert_eq!(server_id, server.burrow_id());
+    manager.stop();
+}
+
+// ── Sending to peers ───────────────────────────────────────────
+
+#[tokio::test]
+async fn frames_reach_peers_by_burrow_id_until_they_close() {
+    let server = Arc::new(Burrow::in_memory("server"));
+    let client = Burrow::in_memory("client");
+    let client_id = client.identity.burrow_id();
+    let (mut near, mut far) = memory_tunnel_pair("client", "server");
+    let srv = Arc::clone(&server);
+    let serving = tokio::spawn(async move { srv.handle_tunnel(&mut far).await });
+    client.client_handshake(&mut near).await.unwrap();
+    while !server.sessions.has_session(&client_id) {
+        tokio::task::yield_now().await;
+    }
+
+    let mut frame = Frame::with_args("EVENT", vec!["/q/news".into()]);
+    frame.set_header("Lane", "0");
+    frame.set_body("hello");
+    server.send_to(&client_id, frame.clone()).unwrap();
+    let got = near.recv_frame().await.unwrap().unwrap();
+    assert_eq!(got.body.as_deref(), Some("hello"));
+    assert_eq!(server.broadcast(&frame), 1);
+    assert_eq!(server.broadcast_except(&frame, &client_id), 0);
+    near.recv_frame().await.unwrap().unwrap();
+
+    near.close().await.unwrap();
+    drop(near);
+    let _ = serving.await.unwrap();
+    assert!(!server.sessions.has_session(&client_id));
+    assert!(matches!(
+        server.send_to(&client_id, frame),
+        Err(ProtocolError::Missing(_))
+    ));
+}
+
+// ── Replication ────────────────────────────────────────────────
+
+#[tokio::test]
+async fn followers_replicate_a_topic_and_resume_after_reconnecting() {
+    use rabbit_engine::config::Config;
+
+    let leader_dir = tempfile::tempdir().unwrap();
+    let leader = Arc::new(Burrow::from_config(&Config::default(), leader_dir.path()).unwrap());
+    let leader_id = leader.identity.burrow_id();
+    let publish = |body: &str| {
+        let (broadcast, event) = leader.events.publish("/q/chat", body);
+        let log = leader.continuity.as_ref().unwrap();
+        log.append("/q/chat", &event).unwrap();
+        broadcast
+    };
+    publish("one");
+    publish("two");
+    let addr = serve_tls(&leader, leader.certificate.as_ref().unwrap()).await;
+
+    let follower_dir = tempfile::tempdir().unwrap();
+    let config = Config::parse(&format!(
+        "[network]\nreconnect_min_secs = 1\n\
+         [[replication.follow]]\npeer = \"{leader_id}\"\ntopic = \"/q/chat\"\naddress = \"{addr}\""
+    ))
+    .unwrap();
+    let follower = Arc::new(Burrow::from_config(&config, follower_dir.path()).unwrap());
+    let follower_id = follower.identity.burrow_id();
+    let logged = |count: usize| {
+        let follower = Arc::clone(&follower);
+        async move {
+            let log = follower.continuity.as_ref().unwrap();
+            for _ in 0..500 {
+                if log.records("/q/chat").unwrap().len() >= count {
+                    return log.records("/q/chat").unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!(
+                "only {} events replicated",
+                log.records("/q/chat").unwrap().len()
+            );
+        }
+    };
+
+    let manager = ConnectionManager::from_config(Arc::clone(&follower), &config.network);
+    logged(2).await;
+    // New events follow the replayed ones.
+    while !leader.sessions.has_session(&follower_id) {
+        tokio::task::yield_now().await;
+    }
+    leader.sessions.broadcast(publish("three")).await;
+    logged(3).await;
+    manager.stop();
+    drop(manager);
+
+    // Once back, only what was missed comes across.
+    publish("four");
+    let manager = ConnectionManager::from_config(Arc::clone(&follower), &config.network);
+    let records = logged(4).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        follower
+            .continuity
+            .as_ref()
+            .unwrap()
+            .records("/q/chat")
+            .unwrap(),
+        records
+    );
+    let bodies: Vec<&str> = records.iter().map(|r| r.body.as_str()).collect();
+    assert_eq!(bodies, ["one", "two", "three", "four"]);
+    for (record, seq) in records.iter().zip(1..) {
+        let origin = record.origin.as_ref().unwrap();
+        assert_eq!(
+            (origin.burrow.as_str(), origin.seq),
+            (leader_id.as_str(), seq)
+        );
+    }
+    manager.stop();
+}
+
+#[tokio::test]
+async fn mdns_services_find_each_other() {
+    use rabbit_engine::network::discovery::MdnsService;
+    use tokio::net::UdpSocket;
+
+    // Two services on loopback, each sending to the other in place of
+    // the multicast group.
+    let alice = Arc::new(Burrow::in_memory("alice"));
+    let bob = Arc::new(Burrow::in_memory("bob"));
+    let a_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let b_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let (a_addr, b_addr) = (
+        a_socket.local_addr().unwrap(),
+        b_socket.local_addr().unwrap(),
+    );
+    let interval = Duration::from_millis(50);
+    let a_task =
+        MdnsService::with_socket(a_socket, b_addr).spawn(Arc::clone(&alice), 7001, interval);
+    let b_task = MdnsService::with_socket(b_socket, a_addr).spawn(Arc::clone(&bob), 7002, interval);
+
+    let bob_id = bob.burrow_id();
+    let found = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(peer) = alice.peers.get(&bob_id).await {
+                return peer;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(found.address, "127.0.0.1:7002");
+    assert_eq!(found.name, "bob");
+    // Neither registers itself.
+    assert!(alice.peers.get(&alice.burrow_id()).await.is_none());
+
+    a_task.abort();
+    b_task.abort();
+}